@@ -57,7 +57,7 @@ async fn add_items_and_sync_again(provider: &mut CalDavProvider) {
     let new_name = "This is a new task in a new calendar";
     let new_task = Task::new(String::from(new_name), true, &new_calendar_url);
     provider.local().get_calendar(&new_calendar_url).await.unwrap()
-        .lock().unwrap().add_item(Item::Task(new_task)).await.unwrap();
+        .write().unwrap().add_item(Item::Task(new_task)).await.unwrap();
 
 
     // Also create a task in a previously existing calendar
@@ -66,7 +66,7 @@ async fn add_items_and_sync_again(provider: &mut CalDavProvider) {
     let new_task = Task::new(String::from(new_task_name), false, &changed_calendar_url);
     let new_url = new_task.url().clone();
     provider.local().get_calendar(&changed_calendar_url).await.unwrap()
-        .lock().unwrap().add_item(Item::Task(new_task)).await.unwrap();
+        .write().unwrap().add_item(Item::Task(new_task)).await.unwrap();
 
 
     if provider.sync().await == false {
@@ -89,7 +89,7 @@ async fn complete_item_and_sync_again(
 
     let completion_status = CompletionStatus::Completed(Some(Utc::now()));
     provider.local().get_calendar(changed_calendar_url).await.unwrap()
-        .lock().unwrap().get_item_by_url_mut(url_to_complete).await.unwrap()
+        .write().unwrap().get_item_by_url_mut(url_to_complete).await.unwrap()
         .unwrap_task_mut()
         .set_completion_status(completion_status);
 
@@ -113,7 +113,7 @@ async fn remove_items_and_sync_again(
 
     // Remove the task we had created
     provider.local().get_calendar(changed_calendar_url).await.unwrap()
-        .lock().unwrap()
+        .write().unwrap()
         .mark_for_deletion(id_to_remove).await.unwrap();
 
     if provider.sync().await == false {
@@ -8,6 +8,7 @@ use chrono::Utc;
 use kitchen_fridge::item::Item;
 use kitchen_fridge::task::CompletionStatus;
 use kitchen_fridge::CalDavProvider;
+use kitchen_fridge::traits::CompleteCalendar;
 use kitchen_fridge::utils::pause;
 
 mod shared;
@@ -38,8 +39,8 @@ async fn toggle_all_tasks_and_sync_again(provider: &mut CalDavProvider) -> Resul
     let mut n_toggled = 0;
 
     for (_url, cal) in provider.local().get_calendars_sync()?.iter() {
-        for (_url, item) in cal.lock().unwrap().get_items_mut_sync()?.iter_mut() {
-            match item {
+        for (_url, item) in cal.write().unwrap().iter_items_mut() {
+            match item.into_mut() {
                 Item::Task(task) => {
                     match task.completed() {
                         false => task.set_completion_status(CompletionStatus::Completed(Some(Utc::now()))),
@@ -47,8 +48,8 @@ async fn toggle_all_tasks_and_sync_again(provider: &mut CalDavProvider) -> Resul
                     };
                     n_toggled += 1;
                 }
-                Item::Event(_) => {
-                    // Not doing anything with calendar events
+                Item::Event(_) | Item::Journal(_) | Item::Contact(_) => {
+                    // Not doing anything with calendar events, journals nor contacts
                 },
             }
         }
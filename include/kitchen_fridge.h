@@ -0,0 +1,93 @@
+/*
+ * kitchen-fridge C API (see the `ffi` module of the crate).
+ *
+ * Functions that fail return NULL (or false), and the reason is available with kf_last_error().
+ * Panics inside the library never unwind into the caller: they are caught, and reported as errors as well.
+ * The provider that was being used may then be in an inconsistent state, and should be freed.
+ */
+
+#ifndef KITCHEN_FRIDGE_H
+#define KITCHEN_FRIDGE_H
+
+/* This file is generated by cbindgen from src/ffi.rs. Do not edit it by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// A provider, that syncs a CalDAV server with a local cache (see [`CalDavProvider`])
+typedef struct KfProvider KfProvider;
+
+// A task, as it is listed by [`kf_provider_tasks`]
+typedef struct KfTask {
+  // The URL of the task, that identifies it (e.g. in [`kf_provider_set_task_completion`])
+  char *url;
+  char *calendar_url;
+  char *name;
+  bool completed;
+} KfTask;
+
+// A list of tasks, that must be freed with [`kf_task_list_free`]
+typedef struct KfTaskList {
+  struct KfTask *tasks;
+  size_t len;
+} KfTaskList;
+
+// The message of the last error that happened in the current thread, or `NULL` if nothing failed yet.
+//
+// The returned string is owned by the library, and is valid until the next call to a `kf_*` function in this thread
+const char *kf_last_error(void);
+
+// Create a provider for a CalDAV server, whose local cache is stored in `cache_folder` (which is created if needed). This does not start a connection.
+//
+// Returns `NULL` on error
+//
+// # Safety
+// Every argument must be a valid NUL-terminated string
+struct KfProvider *kf_provider_new(const char *url,
+                                   const char *username,
+                                   const char *password,
+                                   const char *cache_folder);
+
+// Free a provider (this saves its local cache). Freeing `NULL` does nothing
+//
+// # Safety
+// `provider` must have been returned by [`kf_provider_new`], and must not be used afterwards
+void kf_provider_free(struct KfProvider *provider);
+
+// Sync the local cache with the server, then save it. This blocks until the sync is over.
+//
+// Returns whether the sync has completed without errors (a sync that has failed can safely be started again)
+//
+// # Safety
+// `provider` must have been returned by [`kf_provider_new`]
+bool kf_provider_sync(struct KfProvider *provider);
+
+// List the tasks of the local cache (tasks that have been deleted but not synced yet are left out).
+//
+// Returns `NULL` on error
+//
+// # Safety
+// `provider` must have been returned by [`kf_provider_new`]
+struct KfTaskList *kf_provider_tasks(const struct KfProvider *provider);
+
+// Free a list of tasks. Freeing `NULL` does nothing
+//
+// # Safety
+// `list` must have been returned by [`kf_provider_tasks`], and must not be used afterwards
+void kf_task_list_free(struct KfTaskList *list);
+
+// Complete (or un-complete) a task of the local cache, according to the subtask policy of the provider (see [`crate::provider::SubtaskCompletionPolicy`]).
+// The change is pushed to the server during the next sync.
+//
+// Returns `false` on error
+//
+// # Safety
+// `provider` must have been returned by [`kf_provider_new`], and `task_url` must be a valid NUL-terminated string
+bool kf_provider_set_task_completion(struct KfProvider *provider,
+                                     const char *task_url,
+                                     bool completed);
+
+#endif /* KITCHEN_FRIDGE_H */
@@ -0,0 +1,136 @@
+//! Reminders of events and tasks (iCal `VALARM` components)
+
+use chrono::{DateTime, Utc};
+use ical::property::Property;
+use serde::{Deserialize, Serialize};
+
+/// What happens when an alarm is triggered
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AlarmAction {
+    Audio,
+    Display,
+    Email,
+    /// Any other (e.g. `X-` or deprecated `PROCEDURE`) action
+    Other(String),
+}
+
+impl AlarmAction {
+    pub fn as_ical_str(&self) -> &str {
+        match self {
+            AlarmAction::Audio => "AUDIO",
+            AlarmAction::Display => "DISPLAY",
+            AlarmAction::Email => "EMAIL",
+            AlarmAction::Other(s) => s,
+        }
+    }
+}
+
+impl From<&str> for AlarmAction {
+    fn from(s: &str) -> Self {
+        match s {
+            "AUDIO" => AlarmAction::Audio,
+            "DISPLAY" => AlarmAction::Display,
+            "EMAIL" => AlarmAction::Email,
+            other => AlarmAction::Other(other.to_string()),
+        }
+    }
+}
+
+/// When an alarm is triggered
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AlarmTrigger {
+    /// Relative to the start of the item (DTSTART), or to its end (DTEND or DUE) in case `related_to_end` is set. \
+    /// Negative offsets mean "before"
+    Relative { offset_seconds: i64, related_to_end: bool },
+    /// At a given date
+    Absolute(DateTime<Utc>),
+    /// A TRIGGER property that could not be parsed. It is kept as is, so that it is written back unchanged, but such alarms are never triggered
+    Unparsed(Property),
+}
+
+impl AlarmTrigger {
+    /// A trigger that happens some time before the start of the item
+    pub fn before_start(duration: chrono::Duration) -> Self {
+        AlarmTrigger::Relative { offset_seconds: -duration.num_seconds(), related_to_end: false }
+    }
+}
+
+/// How many more times an alarm is repeated after it has first been triggered (iCal `REPEAT` and `DURATION`)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AlarmRepeat {
+    pub count: u32,
+    pub interval_seconds: i64,
+}
+
+impl AlarmRepeat {
+    pub fn interval(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.interval_seconds)
+    }
+}
+
+/// A reminder attached to an [`Event`](crate::Event) or a [`Task`](crate::Task)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Alarm {
+    action: AlarmAction,
+    trigger: AlarmTrigger,
+    /// DESCRIPTION (this is required for `DISPLAY` and `EMAIL` alarms)
+    description: Option<String>,
+    repeat: Option<AlarmRepeat>,
+
+    /// Extra parameters that have not been parsed from the iCal file (e.g. the SUMMARY and ATTENDEEs of email alarms).
+    /// They are needed to serialize this alarm into an equivalent iCal component
+    extra_parameters: Vec<Property>,
+}
+
+impl Alarm {
+    pub fn new(action: AlarmAction, trigger: AlarmTrigger) -> Self {
+        Self::new_with_parameters(action, trigger, None, None, Vec::new())
+    }
+
+    pub fn new_with_parameters(
+        action: AlarmAction,
+        trigger: AlarmTrigger,
+        description: Option<String>,
+        repeat: Option<AlarmRepeat>,
+        extra_parameters: Vec<Property>,
+    ) -> Self {
+        Self { action, trigger, description, repeat, extra_parameters }
+    }
+
+    pub fn action(&self) -> &AlarmAction { &self.action }
+    pub fn trigger(&self) -> &AlarmTrigger { &self.trigger }
+    pub fn description(&self) -> Option<&str> { self.description.as_deref() }
+    pub fn repeat(&self) -> Option<&AlarmRepeat> { self.repeat.as_ref() }
+    pub fn extra_parameters(&self) -> &[Property] { &self.extra_parameters }
+
+    pub fn set_description(&mut self, description: Option<String>) {
+        self.description = description;
+    }
+    pub fn set_repeat(&mut self, repeat: Option<AlarmRepeat>) {
+        self.repeat = repeat;
+    }
+
+    /// When this alarm is first triggered, given the start and end of the item it belongs to. \
+    /// This is `None` for relative triggers when the date they are relative to is unknown, for unparsed triggers, or when the trigger date is out of range
+    pub fn first_trigger_date(&self, item_start: Option<&DateTime<Utc>>, item_end: Option<&DateTime<Utc>>) -> Option<DateTime<Utc>> {
+        match &self.trigger {
+            AlarmTrigger::Absolute(date) => Some(*date),
+            AlarmTrigger::Unparsed(_) => None,
+            AlarmTrigger::Relative { offset_seconds, related_to_end } => {
+                let reference = match related_to_end {
+                    false => item_start?,
+                    true => item_end?,
+                };
+                reference.checked_add_signed(checked_seconds(*offset_seconds)?)
+            },
+        }
+    }
+}
+
+/// A duration of `seconds` seconds, or `None` if it is too long to be represented
+pub(crate) fn checked_seconds(seconds: i64) -> Option<chrono::Duration> {
+    match seconds.checked_abs()? > i64::MAX / 1000 {
+        true => None,
+        false => Some(chrono::Duration::seconds(seconds)),
+    }
+}
@@ -0,0 +1,79 @@
+//! Files attached to items (iCal `ATTACH` property)
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::attendee::PropertyParams;
+
+/// A document attached to an event or a task
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Attachment {
+    /// A document that is stored elsewhere, and referenced by its URI
+    Uri {
+        /// The URI, as it is written in the iCal file (so that it is written back verbatim)
+        #[serde(alias = "url")]
+        uri: String,
+        /// FMTTYPE, i.e. the media type of the document (e.g. `application/pdf`)
+        format_type: Option<String>,
+        /// Parameters that are not parsed by this crate (e.g. `FILENAME`).
+        /// They are needed to serialize this attachment into an equivalent iCal property
+        extra_parameters: PropertyParams,
+    },
+    /// A document that is inlined into the item (BASE64-encoded in the iCal file)
+    Binary {
+        data: Vec<u8>,
+        /// FMTTYPE, i.e. the media type of the document (e.g. `image/png`)
+        format_type: Option<String>,
+        /// Parameters that are not parsed by this crate (e.g. `FILENAME`).
+        /// They are needed to serialize this attachment into an equivalent iCal property
+        extra_parameters: PropertyParams,
+    },
+}
+
+impl Attachment {
+    /// An attachment that references a document by its URI
+    pub fn from_url(url: Url, format_type: Option<String>) -> Self {
+        Attachment::Uri { uri: url.to_string(), format_type, extra_parameters: Vec::new() }
+    }
+
+    /// An attachment whose content is inlined into the item.
+    /// Keep in mind that some servers limit the size of items, linking to documents should usually be preferred
+    pub fn from_bytes(data: Vec<u8>, format_type: Option<String>) -> Self {
+        Attachment::Binary { data, format_type, extra_parameters: Vec::new() }
+    }
+
+    /// The URI of the document, if it is not inlined
+    pub fn uri(&self) -> Option<&str> {
+        match self {
+            Attachment::Uri { uri, .. } => Some(uri),
+            Attachment::Binary { .. } => None,
+        }
+    }
+
+    /// The URI of the document as a URL, if it is not inlined
+    pub fn url(&self) -> Option<Url> {
+        self.uri().and_then(|uri| Url::parse(uri).ok())
+    }
+
+    /// The content of the document, if it is inlined
+    pub fn data(&self) -> Option<&[u8]> {
+        match self {
+            Attachment::Uri { .. } => None,
+            Attachment::Binary { data, .. } => Some(data),
+        }
+    }
+
+    pub fn format_type(&self) -> Option<&str> {
+        match self {
+            Attachment::Uri { format_type, .. } => format_type.as_deref(),
+            Attachment::Binary { format_type, .. } => format_type.as_deref(),
+        }
+    }
+
+    pub fn extra_parameters(&self) -> &[(String, Vec<String>)] {
+        match self {
+            Attachment::Uri { extra_parameters, .. } => extra_parameters,
+            Attachment::Binary { extra_parameters, .. } => extra_parameters,
+        }
+    }
+}
@@ -0,0 +1,168 @@
+//! Participants of events (iCal `ATTENDEE` and `ORGANIZER` properties)
+
+use serde::{Deserialize, Serialize};
+
+/// The parameters of a property, as they are stored by the `ical` crate
+pub type PropertyParams = Vec<(String, Vec<String>)>;
+
+/// Whether an attendee takes part in an event (iCal `PARTSTAT`)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ParticipationStatus {
+    NeedsAction,
+    Accepted,
+    Declined,
+    Tentative,
+    Delegated,
+    /// Any other (e.g. `X-` or to-do-only) status
+    Other(String),
+}
+
+impl ParticipationStatus {
+    pub fn as_ical_str(&self) -> &str {
+        match self {
+            ParticipationStatus::NeedsAction => "NEEDS-ACTION",
+            ParticipationStatus::Accepted => "ACCEPTED",
+            ParticipationStatus::Declined => "DECLINED",
+            ParticipationStatus::Tentative => "TENTATIVE",
+            ParticipationStatus::Delegated => "DELEGATED",
+            ParticipationStatus::Other(s) => s,
+        }
+    }
+}
+
+impl From<&str> for ParticipationStatus {
+    fn from(s: &str) -> Self {
+        match s {
+            "NEEDS-ACTION" => ParticipationStatus::NeedsAction,
+            "ACCEPTED" => ParticipationStatus::Accepted,
+            "DECLINED" => ParticipationStatus::Declined,
+            "TENTATIVE" => ParticipationStatus::Tentative,
+            "DELEGATED" => ParticipationStatus::Delegated,
+            other => ParticipationStatus::Other(other.to_string()),
+        }
+    }
+}
+
+/// The role of an attendee (iCal `ROLE`)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ParticipantRole {
+    Chair,
+    RequiredParticipant,
+    OptionalParticipant,
+    NonParticipant,
+    /// Any other (e.g. `X-`) role
+    Other(String),
+}
+
+impl ParticipantRole {
+    pub fn as_ical_str(&self) -> &str {
+        match self {
+            ParticipantRole::Chair => "CHAIR",
+            ParticipantRole::RequiredParticipant => "REQ-PARTICIPANT",
+            ParticipantRole::OptionalParticipant => "OPT-PARTICIPANT",
+            ParticipantRole::NonParticipant => "NON-PARTICIPANT",
+            ParticipantRole::Other(s) => s,
+        }
+    }
+}
+
+impl From<&str> for ParticipantRole {
+    fn from(s: &str) -> Self {
+        match s {
+            "CHAIR" => ParticipantRole::Chair,
+            "REQ-PARTICIPANT" => ParticipantRole::RequiredParticipant,
+            "OPT-PARTICIPANT" => ParticipantRole::OptionalParticipant,
+            "NON-PARTICIPANT" => ParticipantRole::NonParticipant,
+            other => ParticipantRole::Other(other.to_string()),
+        }
+    }
+}
+
+/// Someone who is invited to an event
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Attendee {
+    /// The cal-address of this attendee, usually a `mailto:` URI
+    address: String,
+    /// CN, the display name of this attendee
+    common_name: Option<String>,
+    /// PARTSTAT. When missing, RFC5545 defaults to `NEEDS-ACTION`
+    status: Option<ParticipationStatus>,
+    /// ROLE. When missing, RFC5545 defaults to `REQ-PARTICIPANT`
+    role: Option<ParticipantRole>,
+    /// RSVP, i.e. whether a reply is expected
+    rsvp: bool,
+
+    /// Parameters that are not parsed by this crate (e.g. `CUTYPE` or `DELEGATED-FROM`).
+    /// They are needed to serialize this attendee into an equivalent iCal property
+    extra_parameters: PropertyParams,
+}
+
+impl Attendee {
+    pub fn new(address: String) -> Self {
+        Self::new_with_parameters(address, None, None, None, false, Vec::new())
+    }
+
+    pub fn new_with_parameters(
+        address: String,
+        common_name: Option<String>,
+        status: Option<ParticipationStatus>,
+        role: Option<ParticipantRole>,
+        rsvp: bool,
+        extra_parameters: PropertyParams,
+    ) -> Self {
+        Self { address, common_name, status, role, rsvp, extra_parameters }
+    }
+
+    pub fn address(&self) -> &str { &self.address }
+    pub fn common_name(&self) -> Option<&str> { self.common_name.as_deref() }
+    pub fn role(&self) -> Option<&ParticipantRole> { self.role.as_ref() }
+    pub fn rsvp(&self) -> bool { self.rsvp }
+    pub fn extra_parameters(&self) -> &[(String, Vec<String>)] { &self.extra_parameters }
+
+    /// The participation status, as it is written in the iCal file (if any)
+    pub fn status(&self) -> Option<&ParticipationStatus> { self.status.as_ref() }
+    /// The participation status, defaulting to `NEEDS-ACTION` when it is missing
+    pub fn effective_status(&self) -> ParticipationStatus {
+        self.status.clone().unwrap_or(ParticipationStatus::NeedsAction)
+    }
+
+    pub fn set_common_name(&mut self, common_name: Option<String>) {
+        self.common_name = common_name;
+    }
+    pub fn set_status(&mut self, status: Option<ParticipationStatus>) {
+        self.status = status;
+    }
+    pub fn set_role(&mut self, role: Option<ParticipantRole>) {
+        self.role = role;
+    }
+    pub fn set_rsvp(&mut self, rsvp: bool) {
+        self.rsvp = rsvp;
+    }
+}
+
+/// The person who organizes an event
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Organizer {
+    /// The cal-address of the organizer, usually a `mailto:` URI
+    address: String,
+    /// CN, the display name of the organizer
+    common_name: Option<String>,
+
+    /// Parameters that are not parsed by this crate (e.g. `SENT-BY`).
+    /// They are needed to serialize the organizer into an equivalent iCal property
+    extra_parameters: PropertyParams,
+}
+
+impl Organizer {
+    pub fn new(address: String, common_name: Option<String>) -> Self {
+        Self::new_with_parameters(address, common_name, Vec::new())
+    }
+
+    pub fn new_with_parameters(address: String, common_name: Option<String>, extra_parameters: PropertyParams) -> Self {
+        Self { address, common_name, extra_parameters }
+    }
+
+    pub fn address(&self) -> &str { &self.address }
+    pub fn common_name(&self) -> Option<&str> { self.common_name.as_deref() }
+    pub fn extra_parameters(&self) -> &[(String, Vec<String>)] { &self.extra_parameters }
+}
@@ -0,0 +1,182 @@
+//! HTTP Digest authentication (see [RFC 7616](https://datatracker.ietf.org/doc/html/rfc7616)), for servers that do not accept Basic authentication.
+//!
+//! Only the `MD5` and `MD5-sess` algorithms are supported, since they are the only ones such servers commonly offer
+
+use std::sync::Mutex;
+
+use reqwest::Response;
+use reqwest::header::WWW_AUTHENTICATE;
+use url::Url;
+
+/// A `Digest` challenge, as sent by a server in a `WWW-Authenticate` header
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Challenge {
+    realm: String,
+    nonce: String,
+    opaque: Option<String>,
+    /// Whether the `MD5-sess` algorithm is used (rather than `MD5`)
+    session_algorithm: bool,
+    /// Whether the `auth` quality of protection is used (otherwise, this is the legacy RFC 2069 scheme)
+    qop_auth: bool,
+}
+
+impl Challenge {
+    /// The Digest challenge of a `401 Unauthorized` response, if it has a supported one
+    pub(crate) fn from_response(response: &Response) -> Option<Self> {
+        response.headers().get_all(WWW_AUTHENTICATE).iter()
+            .filter_map(|value| value.to_str().ok())
+            .find_map(Self::parse)
+    }
+
+    /// Parse the value of a `WWW-Authenticate` header
+    fn parse(header: &str) -> Option<Self> {
+        let header = header.trim();
+        let (scheme, params) = header.split_once(' ')?;
+        if scheme.eq_ignore_ascii_case("Digest") == false {
+            return None;
+        }
+
+        let mut realm = None;
+        let mut nonce = None;
+        let mut opaque = None;
+        let mut session_algorithm = false;
+        let mut qop_auth = false;
+        for (name, value) in parse_params(params) {
+            match name.to_ascii_lowercase().as_str() {
+                "realm" => realm = Some(value),
+                "nonce" => nonce = Some(value),
+                "opaque" => opaque = Some(value),
+                "algorithm" => match value.to_ascii_uppercase().as_str() {
+                    "MD5" => session_algorithm = false,
+                    "MD5-SESS" => session_algorithm = true,
+                    _ => {
+                        log::warn!("Unsupported Digest algorithm {}", value);
+                        return None;
+                    },
+                },
+                "qop" => qop_auth = value.split(',').any(|qop| qop.trim().eq_ignore_ascii_case("auth")),
+                _ => (),
+            }
+        }
+
+        Some(Self { realm: realm?, nonce: nonce?, opaque, session_algorithm, qop_auth })
+    }
+}
+
+/// Split `name=value, name="quoted, value"` parameters
+fn parse_params(params: &str) -> Vec<(String, String)> {
+    let mut parsed = Vec::new();
+    let mut chars = params.chars().peekable();
+    loop {
+        while matches!(chars.peek(), Some(c) if *c == ',' || c.is_whitespace()) {
+            chars.next();
+        }
+        let name: String = std::iter::from_fn(|| chars.next_if(|c| *c != '=')).collect();
+        if chars.next().is_none() {
+            break;
+        }
+
+        let mut value = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => if let Some(escaped) = chars.next() { value.push(escaped) },
+                    '"' => break,
+                    c => value.push(c),
+                }
+            }
+        } else {
+            value = std::iter::from_fn(|| chars.next_if(|c| *c != ',')).collect();
+        }
+        parsed.push((name.trim().to_string(), value.trim().to_string()));
+    }
+    parsed
+}
+
+/// The Digest challenge a server has sent last, shared by every request that is sent with the same credentials.
+///
+/// Once a server has sent one, requests are directly sent with Digest credentials, so that they do not have to be sent twice
+#[derive(Debug, Default)]
+pub(crate) struct DigestSession {
+    /// The current challenge, and how many requests have been sent with its nonce
+    state: Mutex<Option<(Challenge, u32)>>,
+}
+
+impl DigestSession {
+    pub(crate) fn set_challenge(&self, challenge: Challenge) {
+        *self.state.lock().unwrap() = Some((challenge, 0));
+    }
+
+    /// The value of the `Authorization` header of a request, or `None` if the server has not sent any challenge yet
+    pub(crate) fn authorization(&self, username: &str, password: &str, method: &str, url: &Url) -> Option<String> {
+        let mut state = self.state.lock().unwrap();
+        let (challenge, nonce_count) = state.as_mut()?;
+        *nonce_count += 1;
+        let cnonce = uuid::Uuid::new_v4().to_simple().to_string();
+        Some(authorization(challenge, username, password, method, &request_uri(url), *nonce_count, &cnonce))
+    }
+}
+
+/// The path and the query of a URL, as they are used in Digest computations
+fn request_uri(url: &Url) -> String {
+    match url.query() {
+        None => url.path().to_string(),
+        Some(query) => format!("{}?{}", url.path(), query),
+    }
+}
+
+fn md5_hex(data: &str) -> String {
+    format!("{:x}", md5::compute(data))
+}
+
+fn authorization(challenge: &Challenge, username: &str, password: &str, method: &str, uri: &str, nonce_count: u32, cnonce: &str) -> String {
+    let mut ha1 = md5_hex(&format!("{}:{}:{}", username, challenge.realm, password));
+    if challenge.session_algorithm {
+        ha1 = md5_hex(&format!("{}:{}:{}", ha1, challenge.nonce, cnonce));
+    }
+    let ha2 = md5_hex(&format!("{}:{}", method, uri));
+    let nc = format!("{:08x}", nonce_count);
+
+    let response = match challenge.qop_auth {
+        true => md5_hex(&format!("{}:{}:{}:{}:auth:{}", ha1, challenge.nonce, nc, cnonce, ha2)),
+        false => md5_hex(&format!("{}:{}:{}", ha1, challenge.nonce, ha2)),
+    };
+
+    let mut header = format!(r#"Digest username="{}", realm="{}", nonce="{}", uri="{}", algorithm={}, response="{}""#,
+        username, challenge.realm, challenge.nonce, uri,
+        if challenge.session_algorithm { "MD5-sess" } else { "MD5" },
+        response,
+    );
+    if challenge.qop_auth {
+        header.push_str(&format!(r#", qop=auth, nc={}, cnonce="{}""#, nc, cnonce));
+    }
+    if let Some(opaque) = &challenge.opaque {
+        header.push_str(&format!(r#", opaque="{}""#, opaque));
+    }
+    header
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_digest_response() {
+        // The example of RFC 2617, section 3.5
+        let header = r#"Digest realm="testrealm@host.com", qop="auth,auth-int", nonce="dcd98b7102dd2f0e8b11d0f600bfb0c093", opaque="5ccc069c403ebaf9f0171e9517f40e41""#;
+        let challenge = Challenge::parse(header).unwrap();
+        assert_eq!(challenge.realm, "testrealm@host.com");
+        assert_eq!(challenge.opaque.as_deref(), Some("5ccc069c403ebaf9f0171e9517f40e41"));
+        assert!(challenge.qop_auth);
+
+        let header = authorization(&challenge, "Mufasa", "Circle Of Life", "GET", "/dir/index.html", 1, "0a4f113b");
+        assert!(header.contains(r#"response="6629fae49393a05397450978507c4ef1""#));
+        assert!(header.contains("nc=00000001"));
+
+        assert!(Challenge::parse(r#"Basic realm="testrealm@host.com""#).is_none());
+        assert!(Challenge::parse(r#"Digest realm="x", nonce="y", algorithm=SHA-512-256"#).is_none());
+    }
+}
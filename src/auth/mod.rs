@@ -0,0 +1,116 @@
+//! How requests to CalDAV servers are authenticated (see [`Client::with_authentication`](crate::client::Client::with_authentication))
+//!
+//! Usernames and passwords are only sent once the server has asked for them in a `WWW-Authenticate` challenge (so that they are never sent in clear to servers that do not expect them):
+//! with HTTP Digest authentication if the server asks for it, otherwise with HTTP Basic authentication
+
+use std::error::Error;
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use reqwest::{RequestBuilder, Response};
+use reqwest::header::WWW_AUTHENTICATE;
+
+mod digest;
+pub(crate) use digest::{Challenge as DigestChallenge, DigestSession};
+
+/// Provides OAuth2 access tokens, e.g. for Google or Fastmail CalDAV endpoints.
+///
+/// Implementors are responsible for obtaining tokens, caching them, and refreshing them when they expire
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+pub trait TokenProvider: Send + Sync {
+    /// Returns a valid access token
+    async fn token(&self) -> Result<String, Box<dyn Error>>;
+
+    /// Called when the server has refused the last token (e.g. because it has been revoked before it expired), before the request is sent again once.
+    /// The next call to [`Self::token`] should return a new one.
+    ///
+    /// The default implementation does nothing
+    async fn invalidate(&self) {}
+}
+
+/// The credentials that are sent with every request
+#[derive(Clone)]
+pub enum Authentication {
+    /// HTTP Basic authentication (or HTTP Digest authentication, for servers that ask for it). The credentials are only sent once the server has asked for them
+    Basic { username: String, password: String },
+    /// A bearer token (e.g. an OAuth2 access token) that never changes
+    Bearer(String),
+    /// Bearer tokens that are asked to a [`TokenProvider`] before every request, so that they can be refreshed
+    TokenProvider(Arc<dyn TokenProvider>),
+    /// No credentials at all (e.g. for public calendar feeds, see [`crate::webcal`])
+    None,
+}
+
+impl Authentication {
+    pub fn basic<T: ToString, U: ToString>(username: T, password: U) -> Self {
+        Authentication::Basic { username: username.to_string(), password: password.to_string() }
+    }
+
+    pub fn bearer<T: ToString>(token: T) -> Self {
+        Authentication::Bearer(token.to_string())
+    }
+
+    pub fn token_provider<P: TokenProvider + 'static>(provider: P) -> Self {
+        Authentication::TokenProvider(Arc::new(provider))
+    }
+
+    /// Add the credentials to a request
+    pub(crate) async fn apply(&self, request: RequestBuilder) -> Result<RequestBuilder, Box<dyn Error>> {
+        match self {
+            Authentication::Basic { username, password } => Ok(request.basic_auth(username, Some(password))),
+            Authentication::Bearer(token) => Ok(request.bearer_auth(token)),
+            Authentication::TokenProvider(provider) => Ok(request.bearer_auth(provider.token().await.map_err(|err| crate::Error::Auth(crate::error::shareable(err)))?)),
+            Authentication::None => Ok(request),
+        }
+    }
+
+    /// Called when the server has refused the credentials. Returns whether the request is worth sending again, with new credentials
+    pub(crate) async fn refresh(&self) -> bool {
+        match self {
+            Authentication::TokenProvider(provider) => {
+                provider.invalidate().await;
+                true
+            },
+            _ => false,
+        }
+    }
+}
+
+/// Whether a `401 Unauthorized` response asks for Basic authentication
+pub(crate) fn asks_for_basic(response: &Response) -> bool {
+    response.headers().get_all(WWW_AUTHENTICATE).iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(|value| {
+            let scheme = value.trim().split(' ').next().unwrap_or_default();
+            scheme.eq_ignore_ascii_case("Basic")
+        })
+}
+
+impl Debug for Authentication {
+    /// Secrets are never displayed
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Authentication::Basic { username, .. } => write!(f, "Basic {{ username: {:?}, password: <hidden> }}", username),
+            Authentication::Bearer(_) => write!(f, "Bearer(<hidden>)"),
+            Authentication::TokenProvider(_) => write!(f, "TokenProvider(<provider>)"),
+            Authentication::None => write!(f, "None"),
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secrets_are_hidden() {
+        let basic = format!("{:?}", Authentication::basic("john", "s3cr3t"));
+        assert!(basic.contains("john"));
+        assert!(basic.contains("s3cr3t") == false);
+        assert!(format!("{:?}", Authentication::bearer("t0k3n")).contains("t0k3n") == false);
+    }
+}
@@ -1,14 +1,14 @@
 //! This module provides a local cache for CalDAV data
 
-use std::path::PathBuf;
-use std::path::Path;
 use std::error::Error;
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
-use std::ffi::OsStr;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, RwLock};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{ffi::OsStr, fs::File, io::{BufWriter, ErrorKind, Write}, path::{Path, PathBuf}};
 
 use serde::{Deserialize, Serialize};
 use async_trait::async_trait;
+use chrono::Utc;
 use csscolorparser::Color;
 use url::Url;
 
@@ -17,21 +17,52 @@ use crate::traits::BaseCalendar;
 use crate::traits::CompleteCalendar;
 use crate::calendar::cached_calendar::CachedCalendar;
 use crate::calendar::SupportedComponents;
+use crate::item::VersionTag;
+use crate::changelog::{self, ChangeKind, ChangeLog, ChangeRecord, SharedChangeLog};
+use crate::storage::{CacheStorage, CalendarRecord, ItemRecord};
+#[cfg(target_arch = "wasm32")]
+use crate::storage::MemoryStorage;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{calendar::Privileges, error::Rejection, storage::ItemChange};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::migration::{self, CURRENT_FORMAT_VERSION, LEGACY_FORMAT_VERSION};
+use crate::provider::calendar_selection::CalendarSelection;
+use crate::Item;
 
 #[cfg(feature = "local_calendar_mocks_remote_calendars")]
 use crate::mock_behaviour::MockBehaviour;
 
+#[cfg(not(target_arch = "wasm32"))]
 const MAIN_FILE: &str = "data.json";
+/// This file is kept apart from the other ones, and its format is only ever extended, so that it can be read by any version of this crate.
+const SYNC_STATE_FILE: &str = "sync_state.json";
+const CHANGELOG_FILE: &str = "changelog.json";
+const DELETED_CALENDARS_FILE: &str = "deleted_calendars.json";
+const PENDING_MOVES_FILE: &str = "pending_moves.json";
+const CALENDAR_SELECTION_FILE: &str = "calendar_selection.json";
+/// Files are first written with this extension, then renamed (see [`write_atomically`])
+#[cfg(not(target_arch = "wasm32"))]
+const TEMP_EXTENSION: &str = "tmp";
+/// Calendar files that cannot be read are renamed with this extension
+#[cfg(not(target_arch = "wasm32"))]
+const CORRUPT_EXTENSION: &str = "corrupt";
 
-/// A CalDAV source that stores its items in a local folder.
+/// The storage of a [`Cache`], unless another one is given: a [`FolderStorage`], or a [`MemoryStorage`](crate::storage::MemoryStorage) in web browsers (since they have no file system)
+#[cfg(not(target_arch = "wasm32"))]
+pub type DefaultStorage = FolderStorage;
+/// The storage of a [`Cache`], unless another one is given: a [`MemoryStorage`], since web browsers have no file system (apps that want their cache to persist should provide their own [`CacheStorage`], e.g. on top of IndexedDB)
+#[cfg(target_arch = "wasm32")]
+pub type DefaultStorage = MemoryStorage;
+
+/// A CalDAV source that stores its items in a local folder (or in any other [`CacheStorage`]).
 ///
-/// It automatically updates the content of the folder when dropped (see its `Drop` implementation), but you can also manually call [`Cache::save_to_folder`]
+/// It automatically updates the content of its storage when dropped (see its `Drop` implementation), but you can also manually call [`Cache::save_to_folder`] (or [`Cache::save`])
 ///
 /// Most of its functionality is provided by the `CalDavSource` async trait it implements.
 /// However, since these functions do not _need_ to be actually async, non-async versions of them are also provided for better convenience. See [`Cache::get_calendar_sync`] for example
 #[derive(Debug)]
-pub struct Cache {
-    backing_folder: PathBuf,
+pub struct Cache<S: CacheStorage = DefaultStorage> {
+    storage: Mutex<S>,
     data: CachedData,
 
     /// In tests, we may add forced errors to this object
@@ -39,20 +70,35 @@ pub struct Cache {
     mock_behaviour: Option<Arc<Mutex<MockBehaviour>>>,
 }
 
-#[derive(Default, Debug, Serialize, Deserialize)]
+#[derive(Default, Debug)]
 struct CachedData {
-    #[serde(skip)]
-    calendars: HashMap<Url, Arc<Mutex<CachedCalendar>>>,
+    calendars: HashMap<Url, Arc<RwLock<CachedCalendar>>>,
+    sync_states: HashMap<Url, CalendarSyncState>,
+    change_log: SharedChangeLog,
+    /// Calendars that have been deleted, and whose deletion has not been pushed to the server yet
+    deleted_calendars: HashSet<Url>,
+    /// Items that have been moved between calendars, and whose move has not been pushed to the server yet
+    pending_moves: HashMap<Url, Url>,
+    /// The calendars that are synced (see [`Provider::set_calendar_selection`](crate::provider::Provider::set_calendar_selection))
+    calendar_selection: CalendarSelection,
 }
 
-impl Cache {
-    /// Activate the "mocking remote source" features (i.e. tell its children calendars that they are mocked remote calendars)
-    #[cfg(feature = "local_calendar_mocks_remote_calendars")]
-    pub fn set_mock_behaviour(&mut self, mock_behaviour: Option<Arc<Mutex<MockBehaviour>>>) {
-        self.mock_behaviour = mock_behaviour;
-    }
-
+/// What the server told us about the state of a calendar the last time it was synced.
+///
+/// These are stored in their own section of the cache, that is forward-compatible: new fields can only be added to it (with default values), so that they can survive changes of the cache format.
+/// This way, upgrading this crate does not force a full re-sync of every calendar.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CalendarSyncState {
+    /// The WebDAV `sync-token` (see [RFC 6578](https://datatracker.ietf.org/doc/html/rfc6578))
+    #[serde(default)]
+    pub sync_token: Option<String>,
+    /// The `getctag` of the calendar collection
+    #[serde(default)]
+    pub ctag: Option<VersionTag>,
+}
 
+#[cfg(not(target_arch = "wasm32"))]
+impl Cache<FolderStorage> {
     /// Get the path to the cache folder
     pub fn cache_folder() -> PathBuf {
         return PathBuf::from(String::from("~/.config/my-tasks/cache/"))
@@ -61,41 +107,81 @@ impl Cache {
     /// Initialize a cache from the content of a valid backing folder if it exists.
     /// Returns an error otherwise
     pub fn from_folder(folder: &Path) -> Result<Self, Box<dyn Error>> {
-        // Load shared data...
         let main_file = folder.join(MAIN_FILE);
-        let mut data: CachedData = match std::fs::File::open(&main_file) {
-            Err(err) => {
-                return Err(format!("Unable to open file {:?}: {}", main_file, err).into());
-            },
-            Ok(file) => serde_json::from_reader(file)?,
-        };
+        if let Err(err) = std::fs::File::open(&main_file) {
+            return Err(format!("Unable to open file {:?}: {}", main_file, err).into());
+        }
+        Self::from_storage(FolderStorage::new(folder))
+    }
 
-        // ...and every calendar
-        for entry in std::fs::read_dir(folder)? {
-            match entry {
+    /// Initialize a cache with the default contents
+    pub fn new(folder_path: &Path) -> Self {
+        Self::with_storage(FolderStorage::new(folder_path))
+    }
+
+    /// Store the current Cache to its backing folder
+    ///
+    /// Note that this is automatically called when `self` is `drop`ped
+    pub fn save_to_folder(&self) -> Result<(), std::io::Error> {
+        self.save().map_err(|err| std::io::Error::new(ErrorKind::Other, err.to_string()))
+    }
+}
+
+impl<S: CacheStorage> Cache<S> {
+    /// Activate the "mocking remote source" features (i.e. tell its children calendars that they are mocked remote calendars)
+    #[cfg(feature = "local_calendar_mocks_remote_calendars")]
+    pub fn set_mock_behaviour(&mut self, mock_behaviour: Option<Arc<Mutex<MockBehaviour>>>) {
+        self.mock_behaviour = mock_behaviour;
+    }
+
+
+    /// Initialize a cache from the content of a storage
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "cache_load", skip_all, err))]
+    pub fn from_storage(mut storage: S) -> Result<Self, Box<dyn Error>> {
+        let mut data = CachedData::default();
+
+        // Load every calendar...
+        for record in storage.load_calendars()? {
+            match storage.load_items(&record.url) {
                 Err(err) => {
-                    log::error!("Unable to read dir: {:?}", err);
+                    log::error!("Unable to load calendar {} from cache: {:?}", record.url, err);
                     continue;
                 },
-                Ok(entry) => {
-                    let cal_path = entry.path();
-                    log::debug!("Considering {:?}", cal_path);
-                    if cal_path.extension() == Some(OsStr::new("cal")) {
-                        match Self::load_calendar(&cal_path) {
-                            Err(err) => {
-                                log::error!("Unable to load calendar {:?} from cache: {:?}", cal_path, err);
-                                continue;
-                            },
-                            Ok(cal) =>
-                                data.calendars.insert(cal.url().clone(), Arc::new(Mutex::new(cal))),
-                        };
-                    }
+                Ok(items) => {
+                    let cal = CachedCalendar::from_records(record, items);
+                    data.calendars.insert(cal.url().clone(), Arc::new(RwLock::new(cal)));
                 },
             }
         }
 
+        // ...and the changelog
+        data.change_log = Arc::new(Mutex::new(ChangeLog::load(&mut storage, CHANGELOG_FILE)));
+        for cal in data.calendars.values() {
+            cal.write().unwrap().set_change_log(Some(data.change_log.clone()));
+        }
+
+        // ...and the sync states of the calendars that have been successfully loaded
+        data.sync_states = Self::load_sync_states(&mut storage);
+        let loaded_calendars = &data.calendars;
+        data.sync_states.retain(|url, _| {
+            let keep = loaded_calendars.contains_key(url);
+            if keep == false {
+                log::info!("Dropping the sync state of {}, since this calendar could not be loaded from the cache", url);
+            }
+            keep
+        });
+
+        // ...and the calendars that are still to be deleted from the server
+        data.deleted_calendars = Self::load_deleted_calendars(&mut storage);
+
+        // ...and the items that are still to be moved on the server
+        data.pending_moves = Self::load_pending_moves(&mut storage);
+
+        // ...and the calendars that are synced
+        data.calendar_selection = Self::load_calendar_selection(&mut storage);
+
         Ok(Self{
-            backing_folder: PathBuf::from(folder),
+            storage: Mutex::new(storage),
             data,
 
             #[cfg(feature = "local_calendar_mocks_remote_calendars")]
@@ -103,15 +189,10 @@ impl Cache {
         })
     }
 
-    fn load_calendar(path: &Path) -> Result<CachedCalendar, Box<dyn Error>> {
-        let file = std::fs::File::open(&path)?;
-        Ok(serde_json::from_reader(file)?)
-    }
-
-    /// Initialize a cache with the default contents
-    pub fn new(folder_path: &Path) -> Self {
+    /// Initialize an empty cache, that will be saved to a storage (overwriting what it may contain)
+    pub fn with_storage(storage: S) -> Self {
         Self{
-            backing_folder: PathBuf::from(folder_path),
+            storage: Mutex::new(storage),
             data: CachedData::default(),
 
             #[cfg(feature = "local_calendar_mocks_remote_calendars")]
@@ -119,25 +200,104 @@ impl Cache {
         }
     }
 
-    /// Store the current Cache to its backing folder
+    /// The storage of this cache
+    pub fn storage(&self) -> &Mutex<S> {
+        &self.storage
+    }
+
+    /// Load the sync states. Any error here is not fatal, it will only trigger a full sync of the calendars
+    fn load_sync_states(storage: &mut S) -> HashMap<Url, CalendarSyncState> {
+        let data = match storage.load_metadata(SYNC_STATE_FILE) {
+            Err(err) => {
+                log::warn!("Unable to read the sync states from the cache ({}). Every calendar will be fully synced", err);
+                return HashMap::new();
+            },
+            Ok(None) => {
+                log::info!("No sync state available in the cache");
+                return HashMap::new();
+            },
+            Ok(Some(data)) => data,
+        };
+        match serde_json::from_slice(&data) {
+            Err(err) => {
+                log::warn!("Unable to read the sync states from the cache ({}). Every calendar will be fully synced", err);
+                HashMap::new()
+            },
+            Ok(states) => states,
+        }
+    }
+
+    /// Load the calendars that are to be deleted from the server. Any error here is not fatal, it will only make the next sync download these calendars again
+    fn load_deleted_calendars(storage: &mut S) -> HashSet<Url> {
+        match storage.load_metadata(DELETED_CALENDARS_FILE) {
+            Err(err) => {
+                log::warn!("Unable to read the deleted calendars from the cache ({}). They will be downloaded again at the next sync", err);
+                HashSet::new()
+            },
+            Ok(None) => HashSet::new(),
+            Ok(Some(data)) => serde_json::from_slice(&data).unwrap_or_else(|err| {
+                log::warn!("Unable to read the deleted calendars from the cache ({}). They will be downloaded again at the next sync", err);
+                HashSet::new()
+            }),
+        }
+    }
+
+    /// Load the items that are to be moved on the server. Any error here is not fatal, these items will only be deleted from the server then created again
+    fn load_pending_moves(storage: &mut S) -> HashMap<Url, Url> {
+        match storage.load_metadata(PENDING_MOVES_FILE) {
+            Err(err) => {
+                log::warn!("Unable to read the pending moves from the cache ({}). These items will be deleted then created again at the next sync", err);
+                HashMap::new()
+            },
+            Ok(None) => HashMap::new(),
+            Ok(Some(data)) => serde_json::from_slice(&data).unwrap_or_else(|err| {
+                log::warn!("Unable to read the pending moves from the cache ({}). These items will be deleted then created again at the next sync", err);
+                HashMap::new()
+            }),
+        }
+    }
+
+    /// Load the calendars that are synced. Any error here is not fatal, it will only make the next syncs handle every calendar
+    fn load_calendar_selection(storage: &mut S) -> CalendarSelection {
+        match storage.load_metadata(CALENDAR_SELECTION_FILE) {
+            Err(err) => {
+                log::warn!("Unable to read the calendar selection from the cache ({}). Every calendar will be synced", err);
+                CalendarSelection::All
+            },
+            Ok(None) => CalendarSelection::All,
+            Ok(Some(data)) => serde_json::from_slice(&data).unwrap_or_else(|err| {
+                log::warn!("Unable to read the calendar selection from the cache ({}). Every calendar will be synced", err);
+                CalendarSelection::All
+            }),
+        }
+    }
+
+    /// Store the current Cache to its storage
     ///
     /// Note that this is automatically called when `self` is `drop`ped
-    pub fn save_to_folder(&self) -> Result<(), std::io::Error> {
-        let folder = &self.backing_folder;
-        std::fs::create_dir_all(folder)?;
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "cache_save", skip_all, err))]
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let mut storage = self.storage.lock().unwrap();
 
-        // Save the general data
-        let main_file_path = folder.join(MAIN_FILE);
-        let file = std::fs::File::create(&main_file_path)?;
-        serde_json::to_writer(file, &self.data)?;
+        // Save the sync states
+        storage.save_metadata(SYNC_STATE_FILE, &serde_json::to_vec(&self.data.sync_states)?)?;
+
+        // Save the changelog
+        storage.save_metadata(CHANGELOG_FILE, &serde_json::to_vec(&*self.data.change_log.lock().unwrap())?)?;
+
+        // Save the deleted calendars (they have already been removed from the storage)
+        storage.save_metadata(DELETED_CALENDARS_FILE, &serde_json::to_vec(&self.data.deleted_calendars)?)?;
+
+        // Save the pending moves
+        storage.save_metadata(PENDING_MOVES_FILE, &serde_json::to_vec(&self.data.pending_moves)?)?;
+
+        // Save the calendar selection
+        storage.save_metadata(CALENDAR_SELECTION_FILE, &serde_json::to_vec(&self.data.calendar_selection)?)?;
 
         // Save each calendar
-        for (cal_url, cal_mutex) in &self.data.calendars {
-            let file_name = sanitize_filename::sanitize(cal_url.as_str()) + ".cal";
-            let cal_file = folder.join(file_name);
-            let file = std::fs::File::create(&cal_file)?;
-            let cal = cal_mutex.lock().unwrap();
-            serde_json::to_writer(file, &*cal)?;
+        for cal in self.data.calendars.values() {
+            let cal = cal.read().unwrap();
+            storage.replace_calendar(&cal.record(), cal.item_records())?;
         }
 
         Ok(())
@@ -159,9 +319,9 @@ impl Cache {
 
         for (calendar_url, cal_l) in calendars_l {
             log::debug!("Comparing calendars {}", calendar_url);
-            let cal_l = cal_l.lock().unwrap();
+            let cal_l = cal_l.read().unwrap();
             let cal_r = match calendars_r.get(&calendar_url) {
-                Some(c) => c.lock().unwrap(),
+                Some(c) => c.read().unwrap(),
                 None => return Err("should not happen, we've just tested keys are the same".into()),
             };
 
@@ -176,17 +336,17 @@ impl Cache {
     }
 }
 
-impl Drop for Cache {
+impl<S: CacheStorage> Drop for Cache<S> {
     fn drop(&mut self) {
-        if let Err(err) = self.save_to_folder() {
+        if let Err(err) = self.save() {
             log::error!("Unable to automatically save the cache when it's no longer required: {}", err);
         }
     }
 }
 
-impl Cache {
+impl<S: CacheStorage> Cache<S> {
     /// The non-async version of [`crate::traits::CalDavSource::get_calendars`]
-    pub fn get_calendars_sync(&self) -> Result<HashMap<Url, Arc<Mutex<CachedCalendar>>>, Box<dyn Error>> {
+    pub fn get_calendars_sync(&self) -> Result<HashMap<Url, Arc<RwLock<CachedCalendar>>>, Box<dyn Error>> {
         #[cfg(feature = "local_calendar_mocks_remote_calendars")]
         self.mock_behaviour.as_ref().map_or(Ok(()), |b| b.lock().unwrap().can_get_calendars())?;
 
@@ -197,49 +357,515 @@ impl Cache {
     }
 
     /// The non-async version of [`crate::traits::CalDavSource::get_calendar`]
-    pub fn get_calendar_sync(&self, url: &Url) -> Option<Arc<Mutex<CachedCalendar>>> {
+    pub fn get_calendar_sync(&self, url: &Url) -> Option<Arc<RwLock<CachedCalendar>>> {
         self.data.calendars.get(url).map(|arc| arc.clone())
     }
+
+    /// Returns what the server told us about the state of a calendar the last time it was synced
+    pub fn sync_state(&self, calendar_url: &Url) -> Option<&CalendarSyncState> {
+        self.data.sync_states.get(calendar_url)
+    }
+
+    /// The non-async version of [`crate::traits::CalDavSource::delete_calendar`]
+    pub fn delete_calendar_sync(&mut self, url: &Url) -> Result<(), Box<dyn Error>> {
+        let calendar = self.data.calendars.remove(url)
+            .ok_or_else(|| format!("Calendar {} does not exist", url))?;
+        self.data.sync_states.remove(url);
+        self.data.deleted_calendars.insert(url.clone());
+
+        // Apps that read the changelog must forget about its items
+        let item_urls = calendar.read().unwrap().get_item_urls_sync()?;
+        for item_url in &item_urls {
+            changelog::record_change(&self.data.change_log, url, item_url, ChangeKind::Deleted);
+        }
+        changelog::record_calendar_change(&self.data.change_log, url);
+
+        self.storage.lock().unwrap().delete_calendar(url)
+    }
+
+    /// Remember the state of a calendar on the server, as reported at the end of a sync
+    pub fn set_sync_state(&mut self, calendar_url: &Url, state: CalendarSyncState) {
+        self.data.sync_states.insert(calendar_url.clone(), state);
+    }
+
+    /// Forget the sync state of a calendar. This will trigger a full sync of this calendar
+    pub fn reset_sync_state(&mut self, calendar_url: &Url) {
+        self.data.sync_states.remove(calendar_url);
+    }
+
+    /// Record the items that have been modified through mutable references since their calendar was last used (calendars that are currently locked are skipped)
+    fn record_lent_changes(&self) {
+        for cal in self.data.calendars.values() {
+            if let Ok(cal) = cal.try_read() {
+                cal.record_lent_changes();
+            }
+        }
+    }
+
+    /// The sequence number of the latest change made to the items of this cache
+    pub fn current_change_seq(&self) -> u64 {
+        self.record_lent_changes();
+        self.data.change_log.lock().unwrap().current_seq()
+    }
+
+    /// The changes made to the items of this cache after the change numbered `seq`. See [`ChangeLog::changes_since`]
+    pub fn changes_since(&self, seq: u64) -> Option<Vec<ChangeRecord>> {
+        self.record_lent_changes();
+        self.data.change_log.lock().unwrap().changes_since(seq)
+    }
+
+    /// Register a callback that is called whenever an item is added or modified, be it locally or by a sync.
+    ///
+    /// Callbacks are called while the calendar of the item is locked, so they must not lock it themselves (they can e.g. send a message to a GUI thread instead)
+    pub fn on_item_changed<F: Fn(&ChangeRecord) + Send + Sync + 'static>(&self, callback: F) {
+        self.data.change_log.lock().unwrap().add_item_observer(ChangeKind::Updated, Arc::new(callback));
+    }
+
+    /// Register a callback that is called whenever an item is deleted (or marked for deletion), be it locally or by a sync. See [`Self::on_item_changed`]
+    pub fn on_item_deleted<F: Fn(&ChangeRecord) + Send + Sync + 'static>(&self, callback: F) {
+        self.data.change_log.lock().unwrap().add_item_observer(ChangeKind::Deleted, Arc::new(callback));
+    }
+
+    /// Register a callback that is called with the URL of a calendar whenever it is created, deleted, or whenever its properties (e.g. its name or its privileges) change. See [`Self::on_item_changed`]
+    pub fn on_calendar_changed<F: Fn(&Url) + Send + Sync + 'static>(&self, callback: F) {
+        self.data.change_log.lock().unwrap().add_calendar_observer(Arc::new(callback));
+    }
+
+    /// Drop the content of the items that have been marked for deletion more than `retention` ago, and return how many have been compacted.
+    ///
+    /// Deletions that the server has confirmed are applied by the sync itself. Items that are still marked for deletion
+    /// are the ones whose deletion could not be pushed yet (e.g. because the server keeps refusing it, or because the cache has not been synced since then). \
+    /// They are kept as tombstones, so that their deletion is still pushed at the next syncs, but only with what this needs (their URL, UID, version tag, and the attendees of events)
+    pub fn compact(&self, retention: chrono::Duration) -> usize {
+        let deleted_before = Utc::now() - retention;
+        self.data.calendars.values()
+            .map(|cal| cal.write().unwrap().compact_tombstones_sync(&deleted_before))
+            .sum()
+    }
 }
 
-#[async_trait]
-impl CalDavSource<CachedCalendar> for Cache {
-    async fn get_calendars(&self) -> Result<HashMap<Url, Arc<Mutex<CachedCalendar>>>, Box<dyn Error>> {
-        self.get_calendars_sync()
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl<S: CacheStorage> CalDavSource<CachedCalendar> for Cache<S> {
+    async fn get_calendars(&self) -> Result<HashMap<Url, Arc<RwLock<CachedCalendar>>>, crate::Error> {
+        Ok(self.get_calendars_sync()?)
     }
 
-    async fn get_calendar(&self, url: &Url) -> Option<Arc<Mutex<CachedCalendar>>> {
+    async fn get_calendar(&self, url: &Url) -> Option<Arc<RwLock<CachedCalendar>>> {
         self.get_calendar_sync(url)
     }
 
-    async fn create_calendar(&mut self, url: Url, name: String, supported_components: SupportedComponents, color: Option<Color>) -> Result<Arc<Mutex<CachedCalendar>>, Box<dyn Error>> {
+    async fn create_calendar(&mut self, url: Url, name: String, supported_components: SupportedComponents, color: Option<Color>) -> Result<Arc<RwLock<CachedCalendar>>, crate::Error> {
         log::debug!("Inserting local calendar {}", url);
         #[cfg(feature = "local_calendar_mocks_remote_calendars")]
         self.mock_behaviour.as_ref().map_or(Ok(()), |b| b.lock().unwrap().can_create_calendar())?;
 
-        let new_calendar = CachedCalendar::new(name, url.clone(), supported_components, color);
-        let arc = Arc::new(Mutex::new(new_calendar));
+        let mut new_calendar = CachedCalendar::new(name, url.clone(), supported_components, color);
+        new_calendar.set_change_log(Some(self.data.change_log.clone()));
+        let arc = Arc::new(RwLock::new(new_calendar));
 
         #[cfg(feature = "local_calendar_mocks_remote_calendars")]
         if let Some(behaviour) = &self.mock_behaviour {
-            arc.lock().unwrap().set_mock_behaviour(Some(Arc::clone(behaviour)));
+            arc.write().unwrap().set_mock_behaviour(Some(Arc::clone(behaviour)));
         };
 
-        match self.data.calendars.insert(url, arc.clone()) {
+        match self.data.calendars.insert(url.clone(), arc.clone()) {
             Some(_) => Err("Attempt to insert calendar failed: there is alredy such a calendar.".into()),
-            None => Ok(arc),
+            None => {
+                changelog::record_calendar_change(&self.data.change_log, &url);
+                Ok(arc)
+            },
         }
     }
+
+    async fn delete_calendar(&mut self, url: &Url) -> Result<(), crate::Error> {
+        Ok(self.delete_calendar_sync(url)?)
+    }
+
+    fn deleted_calendars(&self) -> HashSet<Url> {
+        self.data.deleted_calendars.clone()
+    }
+
+    fn forget_deleted_calendar(&mut self, url: &Url) {
+        self.data.deleted_calendars.remove(url);
+    }
+
+    fn pending_moves(&self) -> HashMap<Url, Url> {
+        self.data.pending_moves.clone()
+    }
+
+    fn record_move(&mut self, from: Url, to: Url) {
+        self.data.pending_moves.insert(from, to);
+    }
+
+    fn forget_move(&mut self, from: &Url) {
+        self.data.pending_moves.remove(from);
+    }
+
+    fn calendar_selection(&self) -> CalendarSelection {
+        self.data.calendar_selection.clone()
+    }
+
+    fn set_calendar_selection(&mut self, selection: CalendarSelection) -> Result<(), crate::Error> {
+        self.data.calendar_selection = selection;
+        Ok(())
+    }
 }
 
-#[cfg(test)]
+
+
+/// The content of the main file of a [`FolderStorage`]
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Serialize, Deserialize)]
+struct MainFile {
+    /// The format version of the items of the calendar files (see [`crate::migration`])
+    #[serde(default = "legacy_format_version")]
+    format_version: u32,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn legacy_format_version() -> u32 {
+    LEGACY_FORMAT_VERSION
+}
+
+/// The content of a `.cal` file of a [`FolderStorage`] (this is the same format as a serialized [`CachedCalendar`])
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Serialize, Deserialize)]
+struct CalendarFile {
+    name: String,
+    url: Url,
+    supported_components: SupportedComponents,
+    color: Option<Color>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    order: Option<i32>,
+    #[serde(default)]
+    properties_modified: bool,
+    #[serde(default)]
+    privileges: Privileges,
+    items: HashMap<Url, Item>,
+    #[serde(default)]
+    rejected_items: HashMap<Url, Rejection>,
+    #[serde(default)]
+    evicted_items: HashMap<Url, VersionTag>,
+    #[serde(default)]
+    sync_token: Option<String>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl CalendarFile {
+    fn new(record: &CalendarRecord) -> Self {
+        let mut file = Self {
+            name: String::new(),
+            url: record.url.clone(),
+            supported_components: record.supported_components,
+            color: None,
+            description: None,
+            order: None,
+            properties_modified: false,
+            privileges: Privileges::default(),
+            items: HashMap::new(),
+            rejected_items: HashMap::new(),
+            evicted_items: HashMap::new(),
+            sync_token: None,
+        };
+        file.set_record(record);
+        file
+    }
+
+    fn record(&self) -> CalendarRecord {
+        CalendarRecord {
+            url: self.url.clone(),
+            name: self.name.clone(),
+            supported_components: self.supported_components,
+            color: self.color.clone(),
+            description: self.description.clone(),
+            order: self.order,
+            properties_modified: self.properties_modified,
+            privileges: self.privileges,
+            sync_token: self.sync_token.clone(),
+        }
+    }
+
+    fn set_record(&mut self, record: &CalendarRecord) {
+        self.name = record.name.clone();
+        self.supported_components = record.supported_components;
+        self.color = record.color.clone();
+        self.description = record.description.clone();
+        self.order = record.order;
+        self.properties_modified = record.properties_modified;
+        self.privileges = record.privileges;
+        self.sync_token = record.sync_token.clone();
+    }
+
+    fn into_items(self) -> Vec<ItemRecord> {
+        let mut rejected_items = self.rejected_items;
+        let mut items: Vec<ItemRecord> = self.items.into_iter()
+            .map(|(url, item)| ItemRecord::Item { rejection: rejected_items.remove(&url), item })
+            .collect();
+        items.extend(self.evicted_items.into_iter()
+            .map(|(url, version_tag)| ItemRecord::Evicted { url, version_tag }));
+        items
+    }
+
+    fn apply(&mut self, change: ItemChange) {
+        let url = change.url().clone();
+        self.items.remove(&url);
+        self.rejected_items.remove(&url);
+        self.evicted_items.remove(&url);
+        match change {
+            ItemChange::Saved(ItemRecord::Item { item, rejection }) => {
+                if let Some(rejection) = rejection {
+                    self.rejected_items.insert(url.clone(), rejection);
+                }
+                self.items.insert(url, item);
+            },
+            ItemChange::Saved(ItemRecord::Evicted { version_tag, .. }) => {
+                self.evicted_items.insert(url, version_tag);
+            },
+            ItemChange::Removed(_) => (),
+        }
+    }
+}
+
+/// The default storage of a [`Cache`]: a folder, that contains a JSON file for every calendar.
+///
+/// This is not available in web browsers (`wasm32` targets)
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug)]
+pub struct FolderStorage {
+    folder: PathBuf,
+    /// Calendars that have been read by [`CacheStorage::load_calendars`], until their items are loaded
+    loaded: HashMap<Url, CalendarFile>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FolderStorage {
+    pub fn new(folder: &Path) -> Self {
+        Self { folder: PathBuf::from(folder), loaded: HashMap::new() }
+    }
+
+    /// The folder this storage writes to
+    pub fn folder(&self) -> &Path {
+        &self.folder
+    }
+
+    fn calendar_path(&self, calendar_url: &Url) -> PathBuf {
+        let file_name = sanitize_filename::sanitize(calendar_url.as_str()) + ".cal";
+        self.folder.join(file_name)
+    }
+
+    /// Read a calendar file, or return `None` if there is no such file
+    fn read_calendar(&self, calendar_url: &Url) -> Result<Option<CalendarFile>, Box<dyn Error>> {
+        let path = self.calendar_path(calendar_url);
+        if path.exists() == false {
+            return Ok(None);
+        }
+        read_calendar_file(&path, self.format_version()?).map(Some)
+    }
+
+    /// The format version the calendar files have been written with (see [`crate::migration`])
+    fn format_version(&self) -> Result<u32, Box<dyn Error>> {
+        let main_file = match std::fs::read(self.folder.join(MAIN_FILE)) {
+            // Nothing has been written yet
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(CURRENT_FORMAT_VERSION),
+            Err(err) => return Err(err.into()),
+            Ok(content) => content,
+        };
+        let main_file: MainFile = serde_json::from_slice(&main_file)?;
+        migration::check_format_version(main_file.format_version)?;
+        Ok(main_file.format_version)
+    }
+
+    fn write_main_file(&self) -> Result<(), Box<dyn Error>> {
+        let main_file = MainFile { format_version: CURRENT_FORMAT_VERSION };
+        write_atomically(&self.folder.join(MAIN_FILE), |writer| {
+            Ok(serde_json::to_writer(writer, &main_file)?)
+        })
+    }
+
+    fn write_calendar(&self, calendar: &CalendarFile) -> Result<(), Box<dyn Error>> {
+        self.prepare_folder()?;
+        write_atomically(&self.calendar_path(&calendar.url), |writer| {
+            Ok(serde_json::to_writer(writer, calendar)?)
+        })
+    }
+
+    /// Create the folder (and its main file, that tells this is a valid cache folder) if needed
+    fn prepare_folder(&self) -> Result<(), Box<dyn Error>> {
+        std::fs::create_dir_all(&self.folder)?;
+        let main_file_path = self.folder.join(MAIN_FILE);
+        if main_file_path.exists() == false {
+            self.write_main_file()?;
+        }
+        Ok(())
+    }
+}
+
+/// Read a calendar file, whose items have been written with the format `format_version`
+#[cfg(not(target_arch = "wasm32"))]
+fn read_calendar_file(path: &Path, format_version: u32) -> Result<CalendarFile, Box<dyn Error>> {
+    let file = File::open(path)?;
+    if format_version == CURRENT_FORMAT_VERSION {
+        return Ok(serde_json::from_reader(file)?);
+    }
+
+    let mut content: serde_json::Value = serde_json::from_reader(file)?;
+    if let Some(items) = content.get_mut("items").and_then(|items| items.as_object_mut()) {
+        for item in items.values_mut() {
+            migration::migrate_item(item, format_version)?;
+        }
+    }
+    Ok(serde_json::from_value(content)?)
+}
+
+/// Write a file, so that it is never left half-written (e.g. in case the process is killed meanwhile): the content is written to a temporary file, that then replaces the actual file
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn write_atomically<F>(path: &Path, write: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnOnce(&mut BufWriter<File>) -> Result<(), Box<dyn Error>>
+{
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".");
+    temp_path.push(TEMP_EXTENSION);
+    let temp_path = PathBuf::from(temp_path);
+
+    let mut writer = BufWriter::new(File::create(&temp_path)?);
+    write(&mut writer)?;
+    writer.flush()?;
+    writer.get_ref().sync_all()?;
+    drop(writer);
+
+    std::fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl CacheStorage for FolderStorage {
+    fn load_calendars(&mut self) -> Result<Vec<CalendarRecord>, Box<dyn Error>> {
+        self.loaded.clear();
+        let format_version = self.format_version()?;
+        for entry in std::fs::read_dir(&self.folder)? {
+            match entry {
+                Err(err) => {
+                    log::error!("Unable to read dir: {:?}", err);
+                    continue;
+                },
+                Ok(entry) => {
+                    let cal_path = entry.path();
+                    log::debug!("Considering {:?}", cal_path);
+                    if cal_path.extension() == Some(OsStr::new(TEMP_EXTENSION)) {
+                        // A save has been interrupted. The file it was about to replace is still intact
+                        log::warn!("Removing {:?}, that has been left by an interrupted save", cal_path);
+                        if let Err(err) = std::fs::remove_file(&cal_path) {
+                            log::error!("Unable to remove {:?}: {}", cal_path, err);
+                        }
+                    } else if cal_path.extension() == Some(OsStr::new("cal")) {
+                        match read_calendar_file(&cal_path, format_version) {
+                            Err(err) => {
+                                log::error!("Unable to load calendar {:?} from cache: {:?}", cal_path, err);
+                                if err.is::<serde_json::Error>() {
+                                    // This calendar will be fetched again at the next sync. Let's keep its file aside rather than overwriting it, in case its content can be salvaged
+                                    let corrupt_path = cal_path.with_extension(format!("cal.{}", CORRUPT_EXTENSION));
+                                    log::warn!("Moving the unreadable calendar file to {:?}", corrupt_path);
+                                    if let Err(err) = std::fs::rename(&cal_path, &corrupt_path) {
+                                        log::error!("Unable to move {:?}: {}", cal_path, err);
+                                    }
+                                }
+                                continue;
+                            },
+                            Ok(cal) => self.loaded.insert(cal.url.clone(), cal),
+                        };
+                    }
+                },
+            }
+        }
+
+        if format_version != CURRENT_FORMAT_VERSION {
+            // Rewrite the migrated calendars, so that they are not migrated again
+            log::info!("Upgrading the cache in {:?} from format version {} to {}", self.folder, format_version, CURRENT_FORMAT_VERSION);
+            for cal in self.loaded.values() {
+                self.write_calendar(cal)?;
+            }
+            self.write_main_file()?;
+        }
+
+        Ok(self.loaded.values().map(|cal| cal.record()).collect())
+    }
+
+    fn load_items(&mut self, calendar_url: &Url) -> Result<Vec<ItemRecord>, Box<dyn Error>> {
+        let cal = match self.loaded.remove(calendar_url) {
+            Some(cal) => Some(cal),
+            None => self.read_calendar(calendar_url)?,
+        };
+        Ok(cal.map(|cal| cal.into_items()).unwrap_or_default())
+    }
+
+    fn save_calendar(&mut self, calendar: &CalendarRecord) -> Result<(), Box<dyn Error>> {
+        let file = match self.read_calendar(&calendar.url)? {
+            None => CalendarFile::new(calendar),
+            Some(mut file) => {
+                file.set_record(calendar);
+                file
+            },
+        };
+        self.write_calendar(&file)
+    }
+
+    fn delete_calendar(&mut self, calendar_url: &Url) -> Result<(), Box<dyn Error>> {
+        self.loaded.remove(calendar_url);
+        match std::fs::remove_file(self.calendar_path(calendar_url)) {
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err.into()),
+            Ok(()) => Ok(()),
+        }
+    }
+
+    fn save_items(&mut self, calendar_url: &Url, changes: Vec<ItemChange>) -> Result<(), Box<dyn Error>> {
+        let mut file = match self.read_calendar(calendar_url)? {
+            None => return Err(format!("Calendar {} is absent from {:?}", calendar_url, self.folder).into()),
+            Some(file) => file,
+        };
+        for change in changes {
+            file.apply(change);
+        }
+        self.write_calendar(&file)
+    }
+
+    /// Write the whole calendar file at once
+    fn replace_calendar(&mut self, calendar: &CalendarRecord, items: Vec<ItemRecord>) -> Result<(), Box<dyn Error>> {
+        let mut file = CalendarFile::new(calendar);
+        for item in items {
+            file.apply(ItemChange::Saved(item));
+        }
+        self.write_calendar(&file)
+    }
+
+    fn load_metadata(&mut self, name: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        match std::fs::read(self.folder.join(name)) {
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+            Ok(data) => Ok(Some(data)),
+        }
+    }
+
+    fn save_metadata(&mut self, name: &str, data: &[u8]) -> Result<(), Box<dyn Error>> {
+        self.prepare_folder()?;
+        write_atomically(&self.folder.join(name), |writer| Ok(writer.write_all(data)?))
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
 mod tests {
     use super::*;
 
     use url::Url;
-    use crate::calendar::SupportedComponents;
+    use chrono::{TimeZone, Utc};
+    use crate::calendar::{ItemQuery, SearchFilter, SupportedComponents};
     use crate::item::Item;
     use crate::task::Task;
+    use crate::event::Event;
 
     async fn populate_cache(cache_path: &Path) -> Cache {
         let mut cache = Cache::new(&cache_path);
@@ -259,7 +885,7 @@ mod tests {
         ).await.unwrap();
 
         {
-            let mut bucket_list = bucket_list.lock().unwrap();
+            let mut bucket_list = bucket_list.write().unwrap();
             let cal_url = bucket_list.url().clone();
             bucket_list.add_item(Item::Task(Task::new(
                 String::from("Attend a concert of JS Bach"), false, &cal_url
@@ -282,12 +908,287 @@ mod tests {
         cache.save_to_folder().unwrap();
 
         let retrieved_cache = Cache::from_folder(&cache_path).unwrap();
-        assert_eq!(cache.backing_folder, retrieved_cache.backing_folder);
+        assert_eq!(cache.storage().lock().unwrap().folder(), retrieved_cache.storage().lock().unwrap().folder());
         let test = cache.has_same_observable_content_as(&retrieved_cache).await;
         println!("Equal? {:?}", test);
         assert_eq!(test.unwrap(), true);
     }
 
+    #[tokio::test]
+    async fn cache_mixed_content() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cache_path = PathBuf::from(String::from("test_cache/mixed_content"));
+        let cache = populate_cache(&cache_path).await;
+
+        // Some servers host calendars that contain both events and tasks
+        let bucket_list = cache.get_calendar_sync(&Url::parse("https://caldav.com/bucket-list").unwrap()).unwrap();
+        {
+            let mut bucket_list = bucket_list.write().unwrap();
+            let cal_url = bucket_list.url().clone();
+            bucket_list.add_item(Item::Event(Event::new(
+                String::from("Watch the total solar eclipse"),
+                Utc.ymd(2026, 8, 12).and_hms(17, 30, 0),
+                Utc.ymd(2026, 8, 12).and_hms(18, 30, 0),
+                &cal_url,
+            ))).await.unwrap();
+
+            assert_eq!(bucket_list.item_count(), 3);
+            assert_eq!(bucket_list.count_items(SearchFilter::Tasks), 2);
+            assert_eq!(bucket_list.count_items(SearchFilter::Events), 1);
+            assert!(bucket_list.iter_items_filtered(SearchFilter::Events).all(|(_, item)| item.is_event()));
+
+            let undone_tasks = bucket_list.query(&ItemQuery::new().with_kind(SearchFilter::Tasks).with_completed(false));
+            assert_eq!(undone_tasks.len(), 1);
+            assert_eq!(undone_tasks[0].1.name(), "Attend a concert of JS Bach");
+            let in_2026 = ItemQuery::new().with_range(Utc.ymd(2026, 1, 1).and_hms(0, 0, 0), Utc.ymd(2027, 1, 1).and_hms(0, 0, 0));
+            assert_eq!(bucket_list.query(&in_2026).len(), 1);
+            assert_eq!(bucket_list.query(&ItemQuery::new().with_text("LIGHTHOUSE")).len(), 1);
+            assert_eq!(bucket_list.query(&ItemQuery::new().with_category("Travels".to_string())).len(), 0);
+        }
+
+        cache.save_to_folder().unwrap();
+        let retrieved_cache = Cache::from_folder(&cache_path).unwrap();
+        assert!(cache.has_same_observable_content_as(&retrieved_cache).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn cache_sync_states() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cache_path = PathBuf::from(String::from("test_cache/sync_states"));
+        let mut cache = populate_cache(&cache_path).await;
+
+        let shopping_list = Url::parse("https://caldav.com/shopping").unwrap();
+        let unknown_calendar = Url::parse("https://caldav.com/not-in-the-cache").unwrap();
+        let state = CalendarSyncState {
+            sync_token: Some("http://sabre.io/ns/sync/42".to_string()),
+            ctag: Some(VersionTag::from("ctag-42".to_string())),
+        };
+        cache.set_sync_state(&shopping_list, state.clone());
+        cache.set_sync_state(&unknown_calendar, state.clone());
+        cache.save_to_folder().unwrap();
+
+        let retrieved_cache = Cache::from_folder(&cache_path).unwrap();
+        assert_eq!(retrieved_cache.sync_state(&shopping_list), Some(&state));
+        // The sync state of a calendar that could not be loaded must not be trusted
+        assert_eq!(retrieved_cache.sync_state(&unknown_calendar), None);
+
+        // Unknown fields (e.g. written by a newer version of this crate) are ignored
+        let states: HashMap<Url, CalendarSyncState> = serde_json::from_str(
+            r#"{"https://caldav.com/shopping": {"sync_token": "abc", "some_future_field": 3}}"#
+        ).unwrap();
+        assert_eq!(states[&shopping_list].sync_token.as_deref(), Some("abc"));
+        assert_eq!(states[&shopping_list].ctag, None);
+    }
+
+    #[tokio::test]
+    async fn cache_changelog() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cache_path = PathBuf::from(String::from("test_cache/changelog"));
+        let cache = populate_cache(&cache_path).await;
+
+        let initial_changes = cache.changes_since(0).unwrap();
+        assert_eq!(initial_changes.len(), 2);
+        assert!(initial_changes.iter().all(|change| change.kind() == ChangeKind::Added));
+        let seq = cache.current_change_seq();
+        assert_eq!(seq, 2);
+
+        let bucket_list = cache.get_calendar_sync(&Url::parse("https://caldav.com/bucket-list").unwrap()).unwrap();
+        let deleted_url = {
+            let mut bucket_list = bucket_list.write().unwrap();
+            let url = bucket_list.iter_items().next().unwrap().0.clone();
+            bucket_list.mark_for_deletion(&url).await.unwrap();
+            url
+        };
+
+        let changes = cache.changes_since(seq).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].item_url(), &deleted_url);
+        assert_eq!(changes[0].kind(), ChangeKind::Deleted);
+        assert!(cache.changes_since(seq + 1).unwrap().is_empty());
+        // Sequence numbers from another changelog are not trusted
+        assert!(cache.changes_since(1000).is_none());
+
+        // The changelog persists across sessions
+        cache.save_to_folder().unwrap();
+        let retrieved_cache = Cache::from_folder(&cache_path).unwrap();
+        assert_eq!(retrieved_cache.current_change_seq(), 3);
+        assert_eq!(retrieved_cache.changes_since(seq).unwrap(), changes);
+    }
+
+    #[tokio::test]
+    async fn cache_changelog_mutable_references() {
+        let cache_path = PathBuf::from(String::from("test_cache/changelog_mutable_references"));
+        let cache = populate_cache(&cache_path).await;
+        let seq = cache.current_change_seq();
+
+        let bucket_list = cache.get_calendar_sync(&Url::parse("https://caldav.com/bucket-list").unwrap()).unwrap();
+        let item_url = bucket_list.read().unwrap().iter_items().next().unwrap().0.clone();
+
+        // Borrowing items mutably does not record anything by itself...
+        {
+            let mut bucket_list = bucket_list.write().unwrap();
+            bucket_list.get_item_by_url_mut(&item_url).await.unwrap();
+            for (_url, _item) in bucket_list.iter_items_mut() {}
+        }
+        assert!(cache.changes_since(seq).unwrap().is_empty());
+
+        // ...only actual changes are
+        bucket_list.write().unwrap().get_item_by_url_mut(&item_url).await.unwrap()
+            .unwrap_task_mut().set_name(String::from("Climb the Mont Blanc"));
+        let changes = cache.changes_since(seq).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].item_url(), &item_url);
+        assert_eq!(changes[0].kind(), ChangeKind::Updated);
+        assert!(cache.changes_since(seq + 1).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn cache_change_hooks() {
+        let cache_path = PathBuf::from(String::from("test_cache/change_hooks"));
+        let mut cache = populate_cache(&cache_path).await;
+
+        let changed = Arc::new(Mutex::new(Vec::new()));
+        let deleted = Arc::new(Mutex::new(Vec::new()));
+        let calendars = Arc::new(Mutex::new(Vec::new()));
+        let (changed_, deleted_, calendars_) = (changed.clone(), deleted.clone(), calendars.clone());
+        cache.on_item_changed(move |record| changed_.lock().unwrap().push(record.item_url().clone()));
+        cache.on_item_deleted(move |record| deleted_.lock().unwrap().push(record.item_url().clone()));
+        cache.on_calendar_changed(move |url| calendars_.lock().unwrap().push(url.clone()));
+        let seq = cache.current_change_seq();
+
+        let bucket_list_url = Url::parse("https://caldav.com/bucket-list").unwrap();
+        let bucket_list = cache.get_calendar_sync(&bucket_list_url).unwrap();
+        let (added_url, deleted_url) = {
+            let mut bucket_list = bucket_list.write().unwrap();
+            let deleted_url = bucket_list.iter_items().next().unwrap().0.clone();
+            bucket_list.mark_for_deletion(&deleted_url).await.unwrap();
+            let task = Task::new(String::from("See the aurora borealis"), false, &bucket_list_url);
+            let added_url = task.url().clone();
+            bucket_list.add_item(Item::Task(task)).await.unwrap();
+            bucket_list.set_name(String::from("My real bucket list"));
+            (added_url, deleted_url)
+        };
+        assert_eq!(*changed.lock().unwrap(), vec![added_url]);
+        assert_eq!(*deleted.lock().unwrap(), vec![deleted_url]);
+        assert_eq!(cache.changes_since(seq).unwrap().len(), 2);
+
+        let new_calendar_url = Url::parse("https://caldav.com/new").unwrap();
+        cache.create_calendar(new_calendar_url.clone(), "New".to_string(), SupportedComponents::TODO, None).await.unwrap();
+        cache.delete_calendar(&bucket_list_url).await.unwrap();
+        assert_eq!(*calendars.lock().unwrap(), vec![bucket_list_url.clone(), new_calendar_url, bucket_list_url]);
+        // Deleting a calendar deletes its items
+        assert_eq!(deleted.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn cache_delete_calendar() {
+        let cache_path = PathBuf::from(String::from("test_cache/delete_calendar"));
+        let _ = std::fs::remove_dir_all(&cache_path);
+        let mut cache = populate_cache(&cache_path).await;
+        cache.save_to_folder().unwrap();
+        let seq = cache.current_change_seq();
+
+        let bucket_list = Url::parse("https://caldav.com/bucket-list").unwrap();
+        cache.delete_calendar(&bucket_list).await.unwrap();
+        assert!(cache.get_calendar_sync(&bucket_list).is_none());
+        assert!(cache.delete_calendar(&bucket_list).await.is_err());
+        // Its items are reported as deleted
+        assert_eq!(cache.changes_since(seq).unwrap().len(), 2);
+        cache.save_to_folder().unwrap();
+
+        // The deletion is remembered until it has been pushed to the server
+        let mut retrieved_cache = Cache::from_folder(&cache_path).unwrap();
+        assert!(retrieved_cache.get_calendar_sync(&bucket_list).is_none());
+        assert_eq!(retrieved_cache.deleted_calendars(), vec![bucket_list.clone()].into_iter().collect());
+        retrieved_cache.forget_deleted_calendar(&bucket_list);
+        retrieved_cache.save_to_folder().unwrap();
+        assert!(Cache::from_folder(&cache_path).unwrap().deleted_calendars().is_empty());
+    }
+
+    #[tokio::test]
+    async fn cache_calendar_selection() {
+        let cache_path = PathBuf::from(String::from("test_cache/calendar_selection"));
+        let _ = std::fs::remove_dir_all(&cache_path);
+        let mut cache = populate_cache(&cache_path).await;
+        assert_eq!(cache.calendar_selection(), CalendarSelection::All);
+
+        let bucket_list = Url::parse("https://caldav.com/bucket-list").unwrap();
+        let mut selection = CalendarSelection::All;
+        selection.exclude(bucket_list.clone());
+        cache.set_calendar_selection(selection.clone()).unwrap();
+        cache.save_to_folder().unwrap();
+
+        let retrieved_cache = Cache::from_folder(&cache_path).unwrap();
+        assert_eq!(retrieved_cache.calendar_selection(), selection);
+        assert_eq!(retrieved_cache.calendar_selection().includes(&bucket_list), false);
+    }
+
+    #[tokio::test]
+    async fn cache_compaction() {
+        let cache_path = PathBuf::from(String::from("test_cache/compaction"));
+        let cache = populate_cache(&cache_path).await;
+        let bucket_list = cache.get_calendar_sync(&Url::parse("https://caldav.com/bucket-list").unwrap()).unwrap();
+        let (deleted_url, kept_url) = {
+            let mut bucket_list = bucket_list.write().unwrap();
+            let cal_url = bucket_list.url().clone();
+            let mut urls = Vec::new();
+            for name in &["Visit the Hanging Gardens of Babylon", "See the Colossus of Rhodes"] {
+                let mut task = Task::new(name.to_string(), false, &cal_url);
+                task.add_category(String::from("Wonders"));
+                task.set_sync_status(crate::item::SyncStatus::Synced(VersionTag::from(String::from("etag"))));
+                urls.push(task.url().clone());
+                bucket_list.add_item(Item::Task(task)).await.unwrap();
+            }
+            bucket_list.mark_for_deletion(&urls[0]).await.unwrap();
+            (urls[0].clone(), urls[1].clone())
+        };
+
+        // Recent tombstones are kept
+        assert_eq!(cache.compact(chrono::Duration::days(30)), 0);
+        assert!(bucket_list.read().unwrap().get_item_by_url_sync(&deleted_url).is_some());
+
+        // Old tombstones lose their content, but their deletion is still to be pushed
+        let compacted = bucket_list.write().unwrap().compact_tombstones_sync(&(Utc::now() + chrono::Duration::seconds(1)));
+        assert_eq!(compacted, 1);
+        let again = bucket_list.write().unwrap().compact_tombstones_sync(&(Utc::now() + chrono::Duration::seconds(1)));
+        assert_eq!(again, 0);
+        let bucket_list = bucket_list.read().unwrap();
+        let tombstone = bucket_list.get_item_by_url_sync(&deleted_url).unwrap();
+        assert!(matches!(tombstone.sync_status(), crate::item::SyncStatus::LocallyDeleted(_)));
+        assert!(tombstone.unwrap_task().categories().is_empty());
+        assert_eq!(bucket_list.get_item_by_url_sync(&kept_url).unwrap().unwrap_task().categories(), ["Wonders"]);
+        assert_eq!(bucket_list.item_count(), 5);
+    }
+
+    #[tokio::test]
+    async fn cache_interrupted_save() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let cache_path = PathBuf::from(String::from("test_cache/interrupted_save"));
+        let _ = std::fs::remove_dir_all(&cache_path);
+        let cache = populate_cache(&cache_path).await;
+        cache.save_to_folder().unwrap();
+
+        let shopping_list = Url::parse("https://caldav.com/shopping").unwrap();
+        let bucket_list = Url::parse("https://caldav.com/bucket-list").unwrap();
+        let storage = FolderStorage::new(&cache_path);
+
+        // A save has been interrupted while it was writing a temporary file
+        let shopping_path = storage.calendar_path(&shopping_list);
+        let temp_path = shopping_path.with_extension("cal.tmp");
+        std::fs::write(&temp_path, r#"{"name": "My shopp"#).unwrap();
+        // A calendar file has been truncated (e.g. by an older version of this crate)
+        let bucket_path = storage.calendar_path(&bucket_list);
+        let content = std::fs::read(&bucket_path).unwrap();
+        std::fs::write(&bucket_path, &content[..content.len() / 2]).unwrap();
+
+        let retrieved_cache = Cache::from_folder(&cache_path).unwrap();
+        assert!(retrieved_cache.get_calendar_sync(&shopping_list).is_some());
+        assert!(temp_path.exists() == false);
+        // The truncated calendar is not loaded (it will be fetched again at the next sync), but its file is kept aside
+        assert!(retrieved_cache.get_calendar_sync(&bucket_list).is_none());
+        assert!(bucket_path.with_extension("cal.corrupt").exists());
+    }
+
     #[tokio::test]
     async fn cache_sanity_checks() {
         let _ = env_logger::builder().is_test(true).try_init();
@@ -1,15 +1,19 @@
 use std::collections::{HashMap, HashSet};
 use std::error::Error;
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use async_trait::async_trait;
 use csscolorparser::Color;
 use url::Url;
 
-use crate::item::SyncStatus;
+use crate::item::{ItemMut, ModifiedItems, SyncStatus, VersionTag};
 use crate::traits::{BaseCalendar, CompleteCalendar};
-use crate::calendar::SupportedComponents;
+use crate::calendar::{CalendarProperties, ItemQuery, LazyQueryIndex, Privileges, SupportedComponents};
 use crate::Item;
+use crate::error::{Rejection, ServerError};
+use crate::changelog::{self, ChangeKind, SharedChangeLog};
+use crate::storage::{CalendarRecord, ItemRecord};
 
 #[cfg(feature = "local_calendar_mocks_remote_calendars")]
 use std::sync::{Arc, Mutex};
@@ -27,14 +31,144 @@ pub struct CachedCalendar {
     url: Url,
     supported_components: SupportedComponents,
     color: Option<Color>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    order: Option<i32>,
+    /// Whether the name, the description, the color or the order have been changed since the last sync
+    #[serde(default)]
+    properties_modified: bool,
+    /// What the current user is allowed to do in the remote counterpart of this calendar
+    #[serde(default)]
+    privileges: Privileges,
     #[cfg(feature = "local_calendar_mocks_remote_calendars")]
     #[serde(skip)]
     mock_behaviour: Option<Arc<Mutex<MockBehaviour>>>,
 
     items: HashMap<Url, Item>,
+
+    /// Items that the server has refused
+    #[serde(default)]
+    rejected_items: HashMap<Url, Rejection>,
+
+    /// Items whose content has been dropped (see [`CompleteCalendar::evict_item`]), with their version tags
+    #[serde(default)]
+    evicted_items: HashMap<Url, VersionTag>,
+
+    /// The sync token of the remote counterpart of this calendar, as of the last sync
+    #[serde(default)]
+    sync_token: Option<String>,
+
+    /// Where the changes to the items are recorded (this is shared with the other calendars of the same cache)
+    #[serde(skip)]
+    change_log: Option<SharedChangeLog>,
+
+    /// Speeds up [`CompleteCalendar::query`]. This is built again after the items change
+    #[serde(skip)]
+    query_index: LazyQueryIndex,
+
+    /// Items that have been modified through the mutable references that have been handed out, and that have not been recorded yet
+    #[serde(skip)]
+    lent_items: ModifiedItems,
 }
 
 impl CachedCalendar {
+    /// Record changes to the items in a given changelog (see [`crate::cache::Cache::changes_since`])
+    pub(crate) fn set_change_log(&mut self, change_log: Option<SharedChangeLog>) {
+        self.change_log = change_log;
+    }
+
+    fn record_change(&self, item_url: &Url, kind: ChangeKind) {
+        self.record_lent_changes();
+        self.log_change(item_url, kind);
+    }
+
+    fn log_change(&self, item_url: &Url, kind: ChangeKind) {
+        self.query_index.invalidate();
+        if let Some(log) = &self.change_log {
+            changelog::record_change(log, &self.url, item_url, kind);
+        }
+    }
+
+    /// Record the items that have been modified through the mutable references that have been handed out.
+    ///
+    /// This is done whenever this calendar is used again, since there is no telling when these references are dropped
+    pub(crate) fn record_lent_changes(&self) {
+        for item_url in self.lent_items.take() {
+            // Items that have been removed in the meantime have been recorded as such
+            if self.items.contains_key(&item_url) {
+                self.log_change(&item_url, ChangeKind::Updated);
+            }
+        }
+    }
+
+    fn record_properties_change(&self) {
+        if let Some(log) = &self.change_log {
+            changelog::record_calendar_change(log, &self.url);
+        }
+    }
+
+    /// Build a calendar from what a [`CacheStorage`](crate::storage::CacheStorage) has stored, without checking nor recording anything
+    pub(crate) fn from_records(record: CalendarRecord, items: Vec<ItemRecord>) -> Self {
+        let mut calendar: Self = CompleteCalendar::new(record.name, record.url, record.supported_components, record.color);
+        calendar.description = record.description;
+        calendar.order = record.order;
+        calendar.properties_modified = record.properties_modified;
+        calendar.privileges = record.privileges;
+        calendar.sync_token = record.sync_token;
+        for item in items {
+            match item {
+                ItemRecord::Item { item, rejection } => {
+                    let item_url = item.url().clone();
+                    if let Some(rejection) = rejection {
+                        calendar.rejected_items.insert(item_url.clone(), rejection);
+                    }
+                    calendar.items.insert(item_url, item);
+                },
+                ItemRecord::Evicted { url, version_tag } => {
+                    calendar.evicted_items.insert(url, version_tag);
+                },
+            }
+        }
+        calendar
+    }
+
+    /// What a [`CacheStorage`](crate::storage::CacheStorage) stores about this calendar (but not its items)
+    pub(crate) fn record(&self) -> CalendarRecord {
+        CalendarRecord {
+            url: self.url.clone(),
+            name: self.name.clone(),
+            supported_components: self.supported_components,
+            color: self.color.clone(),
+            description: self.description.clone(),
+            order: self.order,
+            properties_modified: self.properties_modified,
+            privileges: self.privileges,
+            sync_token: self.sync_token.clone(),
+        }
+    }
+
+    /// What a [`CacheStorage`](crate::storage::CacheStorage) stores about an item, or `None` if it is not in this calendar (and has not been evicted from it either)
+    pub(crate) fn item_record(&self, item_url: &Url) -> Option<ItemRecord> {
+        self.record_lent_changes();
+        match self.items.get(item_url) {
+            Some(item) => Some(ItemRecord::Item {
+                item: item.clone(),
+                rejection: self.rejection(item_url).cloned(),
+            }),
+            None => self.evicted_items.get(item_url)
+                .map(|version_tag| ItemRecord::Evicted { url: item_url.clone(), version_tag: version_tag.clone() }),
+        }
+    }
+
+    /// What a [`CacheStorage`](crate::storage::CacheStorage) stores about every item of this calendar
+    pub(crate) fn item_records(&self) -> Vec<ItemRecord> {
+        self.items.keys()
+            .chain(self.evicted_items.keys())
+            .filter_map(|item_url| self.item_record(item_url))
+            .collect()
+    }
+
     /// Activate the "mocking remote calendar" feature (i.e. ignore sync statuses, since this is what an actual CalDAV sever would do)
     #[cfg(feature = "local_calendar_mocks_remote_calendars")]
     pub fn set_mock_behaviour(&mut self, mock_behaviour: Option<Arc<Mutex<MockBehaviour>>>) {
@@ -56,6 +190,13 @@ impl CachedCalendar {
     fn update_item_maybe_mocked(&mut self, item: Item) -> Result<SyncStatus, Box<dyn Error>> {
         if self.mock_behaviour.is_some() {
             self.mock_behaviour.as_ref().map_or(Ok(()), |b| b.lock().unwrap().can_update_item())?;
+            // Just like a server that is sent an If-Match header, refuse to overwrite changes that have been made since the version the client knows
+            if let SyncStatus::LocallyModified(known_tag) = item.sync_status() {
+                let current_tag = self.items.get(item.url()).and_then(|current| current.sync_status().version_tag());
+                if current_tag != Some(known_tag) {
+                    return Err(ServerError::new(412, "").into());
+                }
+            }
             self.add_or_update_item_force_synced(item)
         } else {
             self.regular_add_or_update_item(item)
@@ -64,9 +205,20 @@ impl CachedCalendar {
 
     /// Add or update an item
     fn regular_add_or_update_item(&mut self, item: Item) -> Result<SyncStatus, Box<dyn Error>> {
+        if self.supported_components.contains(SupportedComponents::of_item(&item)) == false {
+            // Some servers host mixed collections anyway. Let's keep this item, it will be synced like any other one
+            log::info!("Calendar {} does not advertise it supports items such as {}. Adding it anyway", self.url, item.url());
+        }
         let ss_clone = item.sync_status().clone();
         log::debug!("Adding or updating an item with {:?}", ss_clone);
-        self.items.insert(item.url().clone(), item);
+        self.rejected_items.remove(item.url());
+        self.evicted_items.remove(item.url());
+        let item_url = item.url().clone();
+        let change = match self.items.insert(item_url.clone(), item) {
+            None => ChangeKind::Added,
+            Some(_) => ChangeKind::Updated,
+        };
+        self.record_change(&item_url, change);
         Ok(ss_clone)
     }
 
@@ -79,7 +231,13 @@ impl CachedCalendar {
             _ => item.set_sync_status(SyncStatus::random_synced()),
         };
         let ss_clone = item.sync_status().clone();
-        self.items.insert(item.url().clone(), item);
+        let item_url = item.url().clone();
+        self.evicted_items.remove(&item_url);
+        let change = match self.items.insert(item_url.clone(), item) {
+            None => ChangeKind::Added,
+            Some(_) => ChangeKind::Updated,
+        };
+        self.record_change(&item_url, change);
         Ok(ss_clone)
     }
 
@@ -128,7 +286,10 @@ impl CachedCalendar {
     }
 
     /// The non-async version of [`Self::get_items`]
+    ///
+    /// Note that this allocates a new map. See [`CompleteCalendar::iter_items`] for a cheaper way to go through the items
     pub fn get_items_sync(&self) -> Result<HashMap<Url, &Item>, Box<dyn Error>> {
+        self.record_lent_changes();
         Ok(self.items.iter()
             .map(|(url, item)| (url.clone(), item))
             .collect()
@@ -136,21 +297,27 @@ impl CachedCalendar {
     }
 
     /// The non-async version of [`Self::get_items_mut`]
-    pub fn get_items_mut_sync(&mut self) -> Result<HashMap<Url, &mut Item>, Box<dyn Error>> {
+    pub fn get_items_mut_sync(&mut self) -> Result<HashMap<Url, ItemMut<'_>>, Box<dyn Error>> {
+        self.record_lent_changes();
+        let lent_items = &self.lent_items;
         Ok(self.items.iter_mut()
-            .map(|(url, item)| (url.clone(), item))
+            .map(|(url, item)| (url.clone(), ItemMut::new(item, lent_items)))
             .collect()
         )
     }
 
     /// The non-async version of [`Self::get_item_by_url`]
     pub fn get_item_by_url_sync<'a>(&'a self, url: &Url) -> Option<&'a Item> {
+        self.record_lent_changes();
         self.items.get(url)
     }
 
     /// The non-async version of [`Self::get_item_by_url_mut`]
-    pub fn get_item_by_url_mut_sync<'a>(&'a mut self, url: &Url) -> Option<&'a mut Item> {
+    pub fn get_item_by_url_mut_sync<'a>(&'a mut self, url: &Url) -> Option<ItemMut<'a>> {
+        self.record_lent_changes();
+        let lent_items = &self.lent_items;
         self.items.get_mut(url)
+            .map(|item| ItemMut::new(item, lent_items))
     }
 
     /// The non-async version of [`Self::add_item`]
@@ -182,14 +349,17 @@ impl CachedCalendar {
         match self.items.get_mut(item_url) {
             None => Err("no item for this key".into()),
             Some(item) => {
+                // The "last modified" date of a deleted item tells when it has been deleted (see `Self::tombstones`)
                 match item.sync_status() {
                     SyncStatus::Synced(prev_ss) => {
                         let prev_ss = prev_ss.clone();
                         item.set_sync_status( SyncStatus::LocallyDeleted(prev_ss));
+                        item.update_last_modified();
                     },
                     SyncStatus::LocallyModified(prev_ss) => {
                         let prev_ss = prev_ss.clone();
                         item.set_sync_status( SyncStatus::LocallyDeleted(prev_ss));
+                        item.update_last_modified();
                     },
                     SyncStatus::LocallyDeleted(prev_ss) => {
                         let prev_ss = prev_ss.clone();
@@ -200,23 +370,77 @@ impl CachedCalendar {
                         self.items.remove(item_url);
                     },
                 };
+                self.record_change(item_url, ChangeKind::Deleted);
                 Ok(())
             }
         }
     }
 
+    /// The non-async version of [`Self::evict_item`]
+    pub fn evict_item_sync(&mut self, item_url: &Url) -> Result<(), Box<dyn Error>> {
+        let version_tag = match self.items.get(item_url).map(|item| item.sync_status()) {
+            None => return Err(format!("Item {} is absent from this calendar", item_url).into()),
+            Some(SyncStatus::Synced(version_tag)) => version_tag.clone(),
+            Some(_) => return Err(format!("Item {} has local changes, it cannot be evicted", item_url).into()),
+        };
+        self.record_lent_changes();
+        self.items.remove(item_url);
+        self.query_index.invalidate();
+        self.evicted_items.insert(item_url.clone(), version_tag);
+        Ok(())
+    }
+
+    /// Remove an item from memory, without recording anything. This is used by [`PersistentCalendar`](crate::persistent_cache::PersistentCalendar)s to evict items their storage still has
+    pub(crate) fn unload_item(&mut self, item_url: &Url) -> Option<Item> {
+        self.record_lent_changes();
+        self.query_index.invalidate();
+        self.items.remove(item_url)
+    }
+
+    /// Put back an item that has been removed with [`Self::unload_item`]
+    pub(crate) fn reload_item(&mut self, item: Item) {
+        self.query_index.invalidate();
+        self.items.insert(item.url().clone(), item);
+    }
+
     /// The non-async version of [`Self::immediately_delete_item`]
     pub fn immediately_delete_item_sync(&mut self, item_url: &Url) -> Result<(), Box<dyn Error>> {
+        self.rejected_items.remove(item_url);
+        self.query_index.invalidate();
         match self.items.remove(item_url) {
             None => Err(format!("Item {} is absent from this calendar", item_url).into()),
-            Some(_) => Ok(())
+            Some(item) => {
+                // Items that were marked for deletion have already been reported as deleted
+                if matches!(item.sync_status(), SyncStatus::LocallyDeleted(_)) == false {
+                    self.record_change(item_url, ChangeKind::Deleted);
+                }
+                Ok(())
+            }
         }
     }
 
+    /// Drop the content of the items that have been marked for deletion before `deleted_before`, and that are still waiting for their deletion to be pushed to the server.
+    /// Returns the URLs of the items whose content has been dropped
+    pub(crate) fn compact_tombstones(&mut self, deleted_before: &DateTime<Utc>) -> Vec<Url> {
+        self.record_lent_changes();
+        self.query_index.invalidate();
+        self.items.iter_mut()
+            .filter(|(_url, item)| matches!(item.sync_status(), SyncStatus::LocallyDeleted(_)))
+            .filter(|(_url, item)| item.last_modified() < deleted_before)
+            .filter_map(|(url, item)| item.drop_content().then(|| url.clone()))
+            .collect()
+    }
+
+    /// Drop the content of the items that have been marked for deletion before `deleted_before` (see [`crate::cache::Cache::compact`]).
+    /// This returns the number of compacted items
+    pub fn compact_tombstones_sync(&mut self, deleted_before: &DateTime<Utc>) -> usize {
+        self.compact_tombstones(deleted_before).len()
+    }
 }
 
 
-#[async_trait]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 impl BaseCalendar for CachedCalendar {
     fn name(&self) -> &str {
         &self.name
@@ -234,52 +458,192 @@ impl BaseCalendar for CachedCalendar {
         self.color.as_ref()
     }
 
-    async fn add_item(&mut self, item: Item) -> Result<SyncStatus, Box<dyn Error>> {
-        self.add_item_sync(item)
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    fn order(&self) -> Option<i32> {
+        self.order
+    }
+
+    fn privileges(&self) -> Privileges {
+        self.privileges
+    }
+
+    async fn add_item(&mut self, item: Item) -> Result<SyncStatus, crate::Error> {
+        Ok(self.add_item_sync(item)?)
     }
 
-    async fn update_item(&mut self, item: Item) -> Result<SyncStatus, Box<dyn Error>> {
-        self.update_item_sync(item)
+    async fn update_item(&mut self, item: Item) -> Result<SyncStatus, crate::Error> {
+        Ok(self.update_item_sync(item)?)
     }
 }
 
-#[async_trait]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 impl CompleteCalendar for CachedCalendar {
     fn new(name: String, url: Url, supported_components: SupportedComponents, color: Option<Color>) -> Self {
         Self {
             name, url, supported_components, color,
+            description: None,
+            order: None,
+            properties_modified: false,
+            privileges: Privileges::default(),
             #[cfg(feature = "local_calendar_mocks_remote_calendars")]
             mock_behaviour: None,
             items: HashMap::new(),
+            rejected_items: HashMap::new(),
+            evicted_items: HashMap::new(),
+            sync_token: None,
+            change_log: None,
+            query_index: LazyQueryIndex::default(),
+            lent_items: ModifiedItems::default(),
         }
     }
 
-    async fn get_item_urls(&self) -> Result<HashSet<Url>, Box<dyn Error>> {
-        self.get_item_urls_sync()
+    async fn get_item_urls(&self) -> Result<HashSet<Url>, crate::Error> {
+        Ok(self.get_item_urls_sync()?)
+    }
+
+    async fn get_items(&self) -> Result<HashMap<Url, &Item>, crate::Error> {
+        Ok(self.get_items_sync()?)
+    }
+
+    async fn get_items_mut(&mut self) -> Result<HashMap<Url, ItemMut<'_>>, crate::Error> {
+        Ok(self.get_items_mut_sync()?)
     }
 
-    async fn get_items(&self) -> Result<HashMap<Url, &Item>, Box<dyn Error>> {
-        self.get_items_sync()
+    fn iter_items<'a>(&'a self) -> Box<dyn Iterator<Item = (&'a Url, &'a Item)> + 'a> {
+        self.record_lent_changes();
+        Box::new(self.items.iter())
     }
 
-    async fn get_items_mut(&mut self) -> Result<HashMap<Url, &mut Item>, Box<dyn Error>> {
-        self.get_items_mut_sync()
+    fn iter_items_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = (&'a Url, ItemMut<'a>)> + 'a> {
+        self.record_lent_changes();
+        let lent_items = &self.lent_items;
+        Box::new(self.items.iter_mut()
+            .map(move |(url, item)| (url, ItemMut::new(item, lent_items)))
+        )
+    }
+
+    fn item_count(&self) -> usize {
+        self.items.len()
+    }
+
+    fn query<'a>(&'a self, query: &ItemQuery) -> Vec<(&'a Url, &'a Item)> {
+        self.record_lent_changes();
+        self.query_index.get(self.items.iter()).query(query, &self.items)
     }
 
     async fn get_item_by_url<'a>(&'a self, url: &Url) -> Option<&'a Item> {
         self.get_item_by_url_sync(url)
     }
 
-    async fn get_item_by_url_mut<'a>(&'a mut self, url: &Url) -> Option<&'a mut Item> {
+    async fn get_item_by_url_mut<'a>(&'a mut self, url: &Url) -> Option<ItemMut<'a>> {
         self.get_item_by_url_mut_sync(url)
     }
 
-    async fn mark_for_deletion(&mut self, item_url: &Url) -> Result<(), Box<dyn Error>> {
-        self.mark_for_deletion_sync(item_url)
+    async fn mark_for_deletion(&mut self, item_url: &Url) -> Result<(), crate::Error> {
+        Ok(self.mark_for_deletion_sync(item_url)?)
+    }
+
+    async fn immediately_delete_item(&mut self, item_url: &Url) -> Result<(), crate::Error> {
+        Ok(self.immediately_delete_item_sync(item_url)?)
+    }
+
+    fn mark_as_rejected(&mut self, item_url: &Url, error: ServerError) {
+        match self.items.get(item_url) {
+            None => log::warn!("Unable to mark {} as rejected, it is absent from this calendar", item_url),
+            Some(item) => {
+                let rejection = Rejection::new(error, *item.last_modified());
+                self.rejected_items.insert(item_url.clone(), rejection);
+            },
+        }
+    }
+
+    fn rejection(&self, item_url: &Url) -> Option<&Rejection> {
+        let item = self.items.get(item_url)?;
+        self.rejected_items.get(item_url)
+            // This rejection is outdated in case the item has been modified since then
+            .filter(|rejection| rejection.item_last_modified() == item.last_modified())
+    }
+
+    fn evict_item(&mut self, item_url: &Url) -> Result<(), crate::Error> {
+        Ok(self.evict_item_sync(item_url)?)
+    }
+
+    fn evicted_items(&self) -> &HashMap<Url, VersionTag> {
+        &self.evicted_items
+    }
+
+    fn update_evicted_item(&mut self, item_url: &Url, version_tag: Option<VersionTag>) {
+        match version_tag {
+            Some(tag) => { self.evicted_items.insert(item_url.clone(), tag); },
+            None => { self.evicted_items.remove(item_url); },
+        }
+    }
+
+    fn set_privileges(&mut self, privileges: Privileges) {
+        if self.privileges != privileges {
+            self.privileges = privileges;
+            self.record_properties_change();
+        }
+    }
+
+    fn set_name(&mut self, name: String) {
+        self.name = name;
+        self.properties_modified = true;
+        self.record_properties_change();
     }
 
-    async fn immediately_delete_item(&mut self, item_url: &Url) -> Result<(), Box<dyn Error>> {
-        self.immediately_delete_item_sync(item_url)
+    fn set_description(&mut self, description: Option<String>) {
+        self.description = description;
+        self.properties_modified = true;
+        self.record_properties_change();
+    }
+
+    fn set_color(&mut self, color: Option<Color>) {
+        self.color = color;
+        self.properties_modified = true;
+        self.record_properties_change();
+    }
+
+    fn set_order(&mut self, order: Option<i32>) {
+        self.order = order;
+        self.properties_modified = true;
+        self.record_properties_change();
+    }
+
+    fn has_modified_properties(&self) -> bool {
+        self.properties_modified
+    }
+
+    fn set_synced_properties(&mut self, properties: CalendarProperties) {
+        let changed = self.properties() != properties;
+        self.name = properties.name;
+        self.description = properties.description;
+        self.color = properties.color;
+        self.order = properties.order;
+        self.properties_modified = false;
+        if changed {
+            self.record_properties_change();
+        }
+    }
+
+    fn sync_token(&self) -> Option<&str> {
+        self.sync_token.as_deref()
+    }
+
+    fn set_sync_token(&mut self, sync_token: Option<String>) {
+        self.sync_token = sync_token;
+    }
+
+    fn can_edit(&self, item_url: &Url) -> bool {
+        self.items.contains_key(item_url) && self.privileges.contains(Privileges::WRITE_CONTENT)
+    }
+
+    fn can_delete(&self, item_url: &Url) -> bool {
+        self.items.contains_key(item_url) && self.privileges.contains(Privileges::UNBIND)
     }
 }
 
@@ -288,18 +652,33 @@ impl CompleteCalendar for CachedCalendar {
 // This class can be used to mock a remote calendar for integration tests
 
 #[cfg(feature = "local_calendar_mocks_remote_calendars")]
-use crate::{item::VersionTag,
-            traits::DavCalendar,
-            resource::Resource};
+use crate::{traits::DavCalendar,
+            resource::Resource,
+            calendar::CollectionChanges};
 
+/// Mocked calendars use the sequence numbers of their changelog as sync tokens
 #[cfg(feature = "local_calendar_mocks_remote_calendars")]
-#[async_trait]
+const MOCK_SYNC_TOKEN_PREFIX: &str = "mock-sync-token/";
+
+#[cfg(feature = "local_calendar_mocks_remote_calendars")]
+impl CachedCalendar {
+    /// The changelog of this calendar, in case it mocks a remote calendar that supports sync tokens
+    fn mock_sync_token_log(&self) -> Option<&SharedChangeLog> {
+        self.record_lent_changes();
+        let supported = self.mock_behaviour.as_ref().map(|b| b.lock().unwrap().supports_sync_tokens) == Some(true);
+        self.change_log.as_ref().filter(|_| supported)
+    }
+}
+
+#[cfg(feature = "local_calendar_mocks_remote_calendars")]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 impl DavCalendar for CachedCalendar {
     fn new(name: String, resource: Resource, supported_components: SupportedComponents, color: Option<Color>) -> Self {
         crate::traits::CompleteCalendar::new(name, resource.url().clone(), supported_components, color)
     }
 
-    async fn get_item_version_tags(&self) -> Result<HashMap<Url, VersionTag>, Box<dyn Error>> {
+    async fn get_item_version_tags(&self) -> Result<HashMap<Url, VersionTag>, crate::Error> {
         #[cfg(feature = "local_calendar_mocks_remote_calendars")]
         self.mock_behaviour.as_ref().map_or(Ok(()), |b| b.lock().unwrap().can_get_item_version_tags())?;
 
@@ -320,14 +699,23 @@ impl DavCalendar for CachedCalendar {
         Ok(result)
     }
 
-    async fn get_item_by_url(&self, url: &Url) -> Result<Option<Item>, Box<dyn Error>> {
+    async fn get_item_version_tags_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<HashMap<Url, VersionTag>, crate::Error> {
+        let mut result = DavCalendar::get_item_version_tags(self).await?;
+        result.retain(|url, _vt| match self.items.get(url) {
+            Some(Item::Event(event)) => event.occurs_between(start, end),
+            _ => true,
+        });
+        Ok(result)
+    }
+
+    async fn get_item_by_url(&self, url: &Url) -> Result<Option<Item>, crate::Error> {
         #[cfg(feature = "local_calendar_mocks_remote_calendars")]
         self.mock_behaviour.as_ref().map_or(Ok(()), |b| b.lock().unwrap().can_get_item_by_url())?;
 
         Ok(self.items.get(url).cloned())
     }
 
-    async fn get_items_by_url(&self, urls: &[Url]) -> Result<Vec<Option<Item>>, Box<dyn Error>> {
+    async fn get_items_by_url(&self, urls: &[Url]) -> Result<Vec<Option<Item>>, crate::Error> {
         let mut v = Vec::new();
         for url in urls {
             v.push(DavCalendar::get_item_by_url(self, url).await?);
@@ -335,10 +723,55 @@ impl DavCalendar for CachedCalendar {
         Ok(v)
     }
 
-    async fn delete_item(&mut self, item_url: &Url) -> Result<(), Box<dyn Error>> {
+    async fn delete_item(&mut self, item_url: &Url) -> Result<(), crate::Error> {
         #[cfg(feature = "local_calendar_mocks_remote_calendars")]
         self.mock_behaviour.as_ref().map_or(Ok(()), |b| b.lock().unwrap().can_delete_item())?;
 
         self.immediately_delete_item(item_url).await
     }
+
+    async fn update_properties(&mut self, properties: &CalendarProperties) -> Result<(), crate::Error> {
+        self.set_synced_properties(properties.clone());
+        Ok(())
+    }
+
+    async fn get_sync_token(&self) -> Result<Option<String>, crate::Error> {
+        Ok(self.mock_sync_token_log().map(|log| format!("{}{}", MOCK_SYNC_TOKEN_PREFIX, log.lock().unwrap().current_seq())))
+    }
+
+    async fn get_changes_since(&self, sync_token: &str) -> Result<Option<CollectionChanges>, crate::Error> {
+        let log = match self.mock_sync_token_log() {
+            None => return Ok(None),
+            Some(log) => log,
+        };
+        self.mock_behaviour.as_ref().map_or(Ok(()), |b| b.lock().unwrap().can_get_item_version_tags())?;
+
+        let seq = match sync_token.strip_prefix(MOCK_SYNC_TOKEN_PREFIX).and_then(|seq| seq.parse().ok()) {
+            None => return Ok(None),
+            Some(seq) => seq,
+        };
+        let log = log.lock().unwrap();
+        let records = match log.changes_since(seq) {
+            // Just like a server that has forgotten about this token
+            None => return Ok(None),
+            Some(records) => records,
+        };
+
+        let mut changed = HashMap::new();
+        let mut deleted = HashSet::new();
+        for record in records.iter().filter(|record| record.calendar_url() == &self.url) {
+            let url = record.item_url().clone();
+            match self.items.get(&url).and_then(|item| item.sync_status().version_tag()) {
+                Some(tag) => {
+                    deleted.remove(&url);
+                    changed.insert(url, tag.clone());
+                },
+                None => {
+                    changed.remove(&url);
+                    deleted.insert(url);
+                },
+            }
+        }
+        Ok(Some(CollectionChanges::new(format!("{}{}", MOCK_SYNC_TOKEN_PREFIX, log.current_seq()), changed, deleted)))
+    }
 }
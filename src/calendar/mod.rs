@@ -2,14 +2,23 @@
 
 pub mod cached_calendar;
 pub mod remote_calendar;
+mod query;
+pub use query::{ItemOrder, ItemQuery};
+pub(crate) use query::LazyQueryIndex;
 
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 use std::error::Error;
 
+use csscolorparser::Color;
 use serde::{Deserialize, Serialize};
+use url::Url;
 
 use bitflags::bitflags;
 
+use crate::Item;
+use crate::item::VersionTag;
+
 bitflags! {
     #[derive(Serialize, Deserialize)]
     pub struct SupportedComponents: u8 {
@@ -17,18 +26,57 @@ bitflags! {
         const EVENT = 1;
         /// A to-do item, such as a reminder
         const TODO = 2;
+        /// A journal entry, such as a note
+        const JOURNAL = 4;
+        /// A vCard, such as an entry of an address book. Collections that hold them are CardDAV address books, not calendars
+        const CONTACT = 8;
     }
 }
 
 impl SupportedComponents {
+    /// Returns the kind of components an item belongs to
+    pub fn of_item(item: &Item) -> Self {
+        match item {
+            Item::Event(_) => Self::EVENT,
+            Item::Task(_) => Self::TODO,
+            Item::Journal(_) => Self::JOURNAL,
+            Item::Contact(_) => Self::CONTACT,
+        }
+    }
+
+    /// The body of a `calendar-query` REPORT that lists the items of these kinds.
+    ///
+    /// Some servers host collections that mix several kinds of components. In case several kinds are requested, we do not filter on any particular one.
+    pub fn to_calendar_query_filter(&self) -> String {
+        let comp_filter = if *self == Self::TODO {
+            r#"<c:comp-filter name="VTODO" />"#
+        } else if *self == Self::EVENT {
+            r#"<c:comp-filter name="VEVENT" />"#
+        } else if *self == Self::JOURNAL {
+            r#"<c:comp-filter name="VJOURNAL" />"#
+        } else {
+            ""
+        };
+        format!(r#"
+            <c:filter>
+                <c:comp-filter name="VCALENDAR">
+                    {}
+                </c:comp-filter>
+            </c:filter>
+            "#,
+            comp_filter,
+        )
+    }
+
     pub fn to_xml_string(&self) -> String {
         format!(r#"
             <B:supported-calendar-component-set>
-                {} {}
+                {} {} {}
             </B:supported-calendar-component-set>
             "#,
             if self.contains(Self::EVENT) { "<B:comp name=\"VEVENT\"/>" } else { "" },
             if self.contains(Self::TODO)  { "<B:comp name=\"VTODO\"/>"  } else { "" },
+            if self.contains(Self::JOURNAL) { "<B:comp name=\"VJOURNAL\"/>" } else { "" },
         )
     }
 }
@@ -48,6 +96,7 @@ impl TryFrom<minidom::Element> for SupportedComponents {
                 None => continue,
                 Some("VEVENT") => flags.insert(Self::EVENT),
                 Some("VTODO") => flags.insert(Self::TODO),
+                Some("VJOURNAL") => flags.insert(Self::JOURNAL),
                 Some(other) => {
                     log::warn!("Unimplemented supported component type: {:?}. Ignoring it", other);
                     continue
@@ -59,17 +108,147 @@ impl TryFrom<minidom::Element> for SupportedComponents {
     }
 }
 
+bitflags! {
+    /// What the current user is allowed to do in a calendar (as advertised by the server in its `current-user-privilege-set`, see RFC 3744)
+    #[derive(Serialize, Deserialize)]
+    pub struct Privileges: u8 {
+        /// Read the calendar and its items
+        const READ = 1;
+        /// Modify the content of existing items
+        const WRITE_CONTENT = 2;
+        /// Add new items into the calendar
+        const BIND = 4;
+        /// Remove items from the calendar
+        const UNBIND = 8;
+        /// Modify the properties of the calendar (e.g. its name or its color)
+        const WRITE_PROPERTIES = 16;
+    }
+}
+
+impl Default for Privileges {
+    /// Servers that do not advertise privileges are assumed to allow everything (they will refuse forbidden requests anyway)
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+impl TryFrom<minidom::Element> for Privileges {
+    type Error = Box<dyn Error>;
+
+    /// Create an instance from an XML <current-user-privilege-set> element
+    fn try_from(element: minidom::Element) -> Result<Self, Self::Error> {
+        if element.name() != "current-user-privilege-set" {
+            return Err("Element must be a <current-user-privilege-set>".into());
+        }
+
+        let mut flags = Self::empty();
+        for privilege in element.children().filter(|child| child.name() == "privilege") {
+            for child in privilege.children() {
+                match child.name() {
+                    "all" => flags.insert(Self::all()),
+                    "read" => flags.insert(Self::READ),
+                    "write" => flags.insert(Self::WRITE_CONTENT | Self::BIND | Self::UNBIND | Self::WRITE_PROPERTIES),
+                    "write-content" => flags.insert(Self::WRITE_CONTENT),
+                    "bind" => flags.insert(Self::BIND),
+                    "unbind" => flags.insert(Self::UNBIND),
+                    "write-properties" => flags.insert(Self::WRITE_PROPERTIES),
+                    _ => continue,
+                };
+            }
+        }
+
+        Ok(flags)
+    }
+}
+
+
+/// The user-editable properties of a calendar (see [`crate::traits::BaseCalendar::properties`])
+#[derive(Clone, Debug, PartialEq)]
+pub struct CalendarProperties {
+    pub name: String,
+    pub description: Option<String>,
+    pub color: Option<Color>,
+    /// Where this calendar should be displayed among the other ones (the Apple `calendar-order` property). Lower values come first
+    pub order: Option<i32>,
+}
+
+
+/// The URL of a calendar collection.
+///
+/// Collection URLs always end with a slash, which is required so that their items' URLs can be correctly built (joining `https://server/calendar` and `item.ics` would otherwise give `https://server/item.ics`).
+/// This type ensures this is the case.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CalendarUrl(Url);
+
+impl From<Url> for CalendarUrl {
+    fn from(mut url: Url) -> Self {
+        if url.path().ends_with('/') == false {
+            let new_path = format!("{}/", url.path());
+            url.set_path(&new_path);
+        }
+        Self(url)
+    }
+}
+
+impl CalendarUrl {
+    pub fn as_url(&self) -> &Url {
+        &self.0
+    }
+
+    pub fn into_url(self) -> Url {
+        self.0
+    }
+
+    /// Whether the given URL is the URL of an item that belongs to this calendar
+    pub fn contains(&self, item_url: &Url) -> bool {
+        item_url.as_str().starts_with(self.0.as_str())
+            && item_url.as_str().len() > self.0.as_str().len()
+    }
+
+    /// Generate a new random URL for an item of this calendar
+    pub fn random_item_url(&self) -> Url {
+        let random = uuid::Uuid::new_v4().to_hyphenated().to_string();
+        self.0.join(&random).unwrap(/* this cannot panic since we've just created a string that is a valid URL */)
+    }
+}
+
+impl std::fmt::Display for CalendarUrl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+
 
 /// Flags to tell which events should be retrieved
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum SearchFilter {
     /// Return all items
     All,
     /// Return only tasks
     Tasks,
+    /// Return only calendar events
+    Events,
+    /// Return only journal entries
+    Journals,
+    /// Return only contacts
+    Contacts,
     // /// Return only completed tasks
     // CompletedTasks,
-    // /// Return only calendar events
-    // Events,
+}
+
+impl SearchFilter {
+    /// Returns whether an item should be retrieved according to this filter
+    pub fn matches(&self, item: &Item) -> bool {
+        match self {
+            SearchFilter::All => true,
+            SearchFilter::Tasks => item.is_task(),
+            SearchFilter::Events => item.is_event(),
+            SearchFilter::Journals => item.is_journal(),
+            SearchFilter::Contacts => item.is_contact(),
+        }
+    }
 }
 
 impl Default for SearchFilter {
@@ -77,3 +256,65 @@ impl Default for SearchFilter {
         SearchFilter::All
     }
 }
+
+
+
+/// What has changed in a remote calendar since a given sync token (see [`DavCalendar::get_changes_since`](crate::traits::DavCalendar::get_changes_since))
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CollectionChanges {
+    sync_token: String,
+    changed: HashMap<Url, VersionTag>,
+    deleted: HashSet<Url>,
+}
+
+impl CollectionChanges {
+    pub fn new(sync_token: String, changed: HashMap<Url, VersionTag>, deleted: HashSet<Url>) -> Self {
+        Self { sync_token, changed, deleted }
+    }
+
+    /// The sync token that describes the current state of the calendar, to be used for the next request
+    pub fn sync_token(&self) -> &str { &self.sync_token }
+    /// The items that have been created or modified, with their current version tags
+    pub fn changed(&self) -> &HashMap<Url, VersionTag> { &self.changed }
+    /// The items that have been deleted
+    pub fn deleted(&self) -> &HashSet<Url> { &self.deleted }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calendar_url() {
+        let without_slash = CalendarUrl::from(Url::parse("https://caldav.com/calendars/john/shopping").unwrap());
+        let with_slash = CalendarUrl::from(Url::parse("https://caldav.com/calendars/john/shopping/").unwrap());
+        assert_eq!(without_slash, with_slash);
+
+        let item_url = with_slash.random_item_url();
+        assert!(with_slash.contains(&item_url));
+        assert!(item_url.as_str().starts_with("https://caldav.com/calendars/john/shopping/"));
+        assert!(with_slash.contains(with_slash.as_url()) == false);
+        assert!(with_slash.contains(&Url::parse("https://caldav.com/calendars/john/shopping-2/item").unwrap()) == false);
+    }
+
+    #[test]
+    fn test_privileges_parsing() {
+        let xml = r#"<d:current-user-privilege-set xmlns:d="DAV:">
+                <d:privilege><d:read/></d:privilege>
+                <d:privilege><d:write-content/></d:privilege>
+                <d:privilege><d:read-current-user-privilege-set/></d:privilege>
+            </d:current-user-privilege-set>"#;
+        let element: minidom::Element = xml.parse().unwrap();
+        let privileges = Privileges::try_from(element).unwrap();
+        assert_eq!(privileges, Privileges::READ | Privileges::WRITE_CONTENT);
+
+        let xml = r#"<d:current-user-privilege-set xmlns:d="DAV:"><d:privilege><d:write/></d:privilege><d:privilege><d:read/></d:privilege></d:current-user-privilege-set>"#;
+        let element: minidom::Element = xml.parse().unwrap();
+        assert_eq!(Privileges::try_from(element).unwrap(), Privileges::all());
+
+        let element: minidom::Element = r#"<d:resourcetype xmlns:d="DAV:"/>"#.parse().unwrap();
+        assert!(Privileges::try_from(element).is_err());
+    }
+}
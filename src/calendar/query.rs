@@ -0,0 +1,330 @@
+//! Queries over the items of a [`CompleteCalendar`](crate::traits::CompleteCalendar) (see [`CompleteCalendar::query`](crate::traits::CompleteCalendar::query))
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use url::Url;
+
+use crate::calendar::SearchFilter;
+use crate::item::SyncStatus;
+use crate::Item;
+
+/// Which items a query returns. Every criterion that is set must be met.
+///
+/// ```
+/// # use kitchen_fridge::calendar::{ItemQuery, SearchFilter};
+/// let unfinished_groceries = ItemQuery::new()
+///     .with_kind(SearchFilter::Tasks)
+///     .with_completed(false)
+///     .with_category("Groceries".to_string());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ItemQuery {
+    kind: SearchFilter,
+    /// Lowercase
+    text: Option<String>,
+    range: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    completed: Option<bool>,
+    categories: Vec<String>,
+    include_deleted: bool,
+}
+
+impl ItemQuery {
+    /// A query that returns every item (except the ones that are marked for deletion)
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only return items of a given kind
+    pub fn with_kind(mut self, kind: SearchFilter) -> Self { self.kind = kind; self }
+    /// Only return items whose name, description, location (for events) or email addresses (for contacts) contain `text`, ignoring the case
+    pub fn with_text(mut self, text: &str) -> Self { self.text = Some(text.to_lowercase()); self }
+    /// Only return the events that happen between `start` (included) and `end` (excluded), as well as the tasks that are due and the journal entries that are dated in this range
+    pub fn with_range(mut self, start: DateTime<Utc>, end: DateTime<Utc>) -> Self { self.range = Some((start, end)); self }
+    /// Only return tasks that are completed (or that are not)
+    pub fn with_completed(mut self, completed: bool) -> Self { self.completed = Some(completed); self }
+    /// Only return items that have this category (ignoring the case). This can be called several times, to return items that have all of these categories
+    pub fn with_category(mut self, category: String) -> Self { self.categories.push(category); self }
+    /// Also return the items that are marked for deletion (but not synced yet)
+    pub fn with_deleted_items(mut self) -> Self { self.include_deleted = true; self }
+
+    pub fn kind(&self) -> SearchFilter { self.kind }
+    pub fn text(&self) -> Option<&str> { self.text.as_deref() }
+    pub fn range(&self) -> Option<(DateTime<Utc>, DateTime<Utc>)> { self.range }
+    pub fn completed(&self) -> Option<bool> { self.completed }
+    pub fn categories(&self) -> &[String] { &self.categories }
+
+    /// Returns whether an item is returned by this query
+    pub fn matches(&self, item: &Item) -> bool {
+        match &self.text {
+            Some(text) if searchable_text(item).contains(text.as_str()) == false => false,
+            _ => self.matches_except_text(item),
+        }
+    }
+
+    fn matches_except_text(&self, item: &Item) -> bool {
+        if self.include_deleted == false {
+            if let SyncStatus::LocallyDeleted(_) = item.sync_status() {
+                return false;
+            }
+        }
+        if self.kind.matches(item) == false {
+            return false;
+        }
+        if let Some(completed) = self.completed {
+            match item {
+                Item::Task(task) if task.completed() == completed => (),
+                _ => return false,
+            }
+        }
+        if let Some((start, end)) = self.range {
+            let in_range = |date: Option<&DateTime<Utc>>| date.map(|date| start <= *date && *date < end).unwrap_or(false);
+            let matches_range = match item {
+                Item::Event(event) => event.occurs_between(start, end),
+                Item::Task(task) => in_range(task.due()),
+                Item::Journal(journal) => in_range(journal.start()),
+                Item::Contact(_) => false,
+            };
+            if matches_range == false {
+                return false;
+            }
+        }
+        if self.categories.is_empty() == false {
+            let item_categories = categories(item);
+            let has_all = self.categories.iter()
+                .all(|category| item_categories.iter().any(|c| c.to_lowercase() == category.to_lowercase()));
+            if has_all == false {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// How items are sorted (see [`CompleteCalendar::iter_sorted`](crate::traits::CompleteCalendar::iter_sorted)).
+///
+/// Items that have no date to be sorted by come last. Items that are equal for this order are sorted by URL, so that paging through them is stable
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ItemOrder {
+    /// Sort by URL only
+    Url,
+    /// Sort by the start of events and by the date of journal entries
+    StartDate,
+    /// Sort by the due date of tasks
+    DueDate,
+}
+
+impl Default for ItemOrder {
+    fn default() -> Self {
+        ItemOrder::Url
+    }
+}
+
+impl ItemOrder {
+    fn date<'a>(&self, item: &'a Item) -> Option<&'a DateTime<Utc>> {
+        match (self, item) {
+            (ItemOrder::StartDate, Item::Event(event)) => Some(event.start()),
+            (ItemOrder::StartDate, Item::Journal(journal)) => journal.start(),
+            (ItemOrder::DueDate, Item::Task(task)) => task.due(),
+            _ => None,
+        }
+    }
+
+    /// Compare two items (and their URLs) for this order
+    pub fn compare(&self, a: (&Url, &Item), b: (&Url, &Item)) -> Ordering {
+        let by_date = match (self.date(a.1), self.date(b.1)) {
+            (Some(date_a), Some(date_b)) => date_a.cmp(date_b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        };
+        by_date.then_with(|| a.0.as_str().cmp(b.0.as_str()))
+    }
+
+    /// Sort items, only making sure the first `count` ones are in the right order (which is quicker than sorting them all when `count` is small)
+    pub(crate) fn sort_first<'a>(&self, items: &mut Vec<(&'a Url, &'a Item)>, count: usize) {
+        if count == 0 {
+            items.clear();
+            return;
+        }
+        if count < items.len() {
+            items.select_nth_unstable_by(count - 1, |a, b| self.compare(*a, *b));
+            items.truncate(count);
+        }
+        items.sort_unstable_by(|a, b| self.compare(*a, *b));
+    }
+}
+
+fn categories(item: &Item) -> &[String] {
+    match item {
+        Item::Event(event) => event.categories(),
+        Item::Task(task) => task.categories(),
+        Item::Journal(_) | Item::Contact(_) => &[],
+    }
+}
+
+/// The lowercase text [`ItemQuery::with_text`] searches in
+fn searchable_text(item: &Item) -> String {
+    let mut text = item.name().to_string();
+    match item {
+        Item::Event(event) => {
+            for part in event.description().iter().chain(event.location().iter()) {
+                text.push('\n');
+                text.push_str(part);
+            }
+        },
+        Item::Journal(journal) => {
+            if let Some(description) = journal.description() {
+                text.push('\n');
+                text.push_str(description);
+            }
+        },
+        Item::Contact(contact) => {
+            for part in contact.emails().iter().map(|e| e.as_str()).chain(contact.organization()) {
+                text.push('\n');
+                text.push_str(part);
+            }
+        },
+        Item::Task(_) => (),
+    }
+    text.to_lowercase()
+}
+
+
+
+/// What [`ItemQuery`]s need to quickly go through the items of a large calendar
+#[derive(Debug)]
+pub(crate) struct QueryIndex {
+    /// The items of every kind
+    by_kind: HashMap<SearchFilter, HashSet<Url>>,
+    /// The items of every (lowercase) category
+    by_category: HashMap<String, HashSet<Url>>,
+    /// The lowercase text of every item, that text searches look into
+    texts: HashMap<Url, String>,
+}
+
+impl QueryIndex {
+    fn build<'a, I: Iterator<Item = (&'a Url, &'a Item)>>(items: I) -> Self {
+        let mut index = Self { by_kind: HashMap::new(), by_category: HashMap::new(), texts: HashMap::new() };
+        for (url, item) in items {
+            for kind in &[SearchFilter::Tasks, SearchFilter::Events, SearchFilter::Journals, SearchFilter::Contacts] {
+                if kind.matches(item) {
+                    index.by_kind.entry(*kind).or_default().insert(url.clone());
+                }
+            }
+            for category in categories(item) {
+                index.by_category.entry(category.to_lowercase()).or_default().insert(url.clone());
+            }
+            index.texts.insert(url.clone(), searchable_text(item));
+        }
+        index
+    }
+
+    /// Run a query over `items`, which must be the items this index has been built from
+    pub(crate) fn query<'a>(&self, query: &ItemQuery, items: &'a HashMap<Url, Item>) -> Vec<(&'a Url, &'a Item)> {
+        // Start from the smallest set of items the index can tell
+        let mut candidates: Option<&HashSet<Url>> = match query.kind {
+            SearchFilter::All => None,
+            kind => match self.by_kind.get(&kind) {
+                None => return Vec::new(),
+                Some(urls) => Some(urls),
+            },
+        };
+        for category in &query.categories {
+            let with_category = match self.by_category.get(&category.to_lowercase()) {
+                None => return Vec::new(),
+                Some(urls) => urls,
+            };
+            if candidates.map(|c| with_category.len() < c.len()).unwrap_or(true) {
+                candidates = Some(with_category);
+            }
+        }
+
+        let matches = |url: &Url, item: &Item| {
+            if let Some(text) = &query.text {
+                let item_text = self.texts.get(url).map(|t| t.as_str()).unwrap_or_default();
+                if item_text.contains(text.as_str()) == false {
+                    return false;
+                }
+            }
+            query.matches_except_text(item)
+        };
+        match candidates {
+            None => items.iter().filter(|(url, item)| matches(url, item)).collect(),
+            Some(urls) => urls.iter()
+                .filter_map(|url| items.get_key_value(url))
+                .filter(|(url, item)| matches(url, item))
+                .collect(),
+        }
+    }
+}
+
+/// A [`QueryIndex`] that is built the first time it is needed, and dropped whenever the items change
+#[derive(Debug, Default)]
+pub(crate) struct LazyQueryIndex(Mutex<Option<Arc<QueryIndex>>>);
+
+impl LazyQueryIndex {
+    pub(crate) fn get<'a, I: Iterator<Item = (&'a Url, &'a Item)>>(&self, items: I) -> Arc<QueryIndex> {
+        self.0.lock().unwrap()
+            .get_or_insert_with(|| Arc::new(QueryIndex::build(items)))
+            .clone()
+    }
+
+    pub(crate) fn invalidate(&self) {
+        *self.0.lock().unwrap() = None;
+    }
+}
+
+impl Clone for LazyQueryIndex {
+    /// Indexes are not copied, they are built again when needed
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::TimeZone;
+
+    use crate::calendar::cached_calendar::CachedCalendar;
+    use crate::calendar::SupportedComponents;
+    use crate::traits::CompleteCalendar;
+    use crate::{Event, Task};
+
+    #[test]
+    fn test_sorted_pages() {
+        let url: Url = "https://caldav.com/agenda".parse().unwrap();
+        let mut cal = CachedCalendar::new("Agenda".to_string(), url.clone(), SupportedComponents::EVENT | SupportedComponents::TODO, None);
+        for day in &[12, 3, 25, 7, 19] {
+            cal.add_item_sync(Item::Event(Event::new(
+                format!("Event on day {}", day),
+                Utc.ymd(2022, 5, *day).and_hms(9, 0, 0),
+                Utc.ymd(2022, 5, *day).and_hms(10, 0, 0),
+                &url,
+            ))).unwrap();
+        }
+        let mut task = Task::new("Pay the rent".to_string(), false, &url);
+        task.set_due(Some(Utc.ymd(2022, 5, 1).and_hms(0, 0, 0)), true);
+        cal.add_item_sync(Item::Task(task)).unwrap();
+
+        let names = |page: Vec<(&Url, &Item)>| page.into_iter().map(|(_, item)| item.name().to_string()).collect::<Vec<_>>();
+        let all = ItemQuery::new();
+
+        assert_eq!(names(cal.query_page(&all, ItemOrder::StartDate, 0, 2)), vec!["Event on day 3", "Event on day 7"]);
+        assert_eq!(names(cal.query_page(&all, ItemOrder::StartDate, 2, 2)), vec!["Event on day 12", "Event on day 19"]);
+        // The task has no start date, it comes last
+        assert_eq!(names(cal.query_page(&all, ItemOrder::StartDate, 4, 10)), vec!["Event on day 25", "Pay the rent"]);
+        assert_eq!(cal.query_page(&all, ItemOrder::StartDate, 6, 10).len(), 0);
+        assert_eq!(cal.query_page(&all, ItemOrder::StartDate, 0, 0).len(), 0);
+
+        assert_eq!(names(cal.query_page(&all, ItemOrder::DueDate, 0, 1)), vec!["Pay the rent"]);
+        let events = ItemQuery::new().with_kind(SearchFilter::Events);
+        assert_eq!(cal.iter_sorted(&events, ItemOrder::Url).count(), 5);
+    }
+}
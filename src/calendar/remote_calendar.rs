@@ -1,37 +1,47 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::sync::Mutex;
 
 use async_trait::async_trait;
-use reqwest::{header::CONTENT_TYPE, header::CONTENT_LENGTH};
+use reqwest::{header::CONTENT_TYPE, header::CONTENT_LENGTH, Method};
 use csscolorparser::Color;
+use chrono::{DateTime, Utc};
+use minidom::Element;
 use url::Url;
 
 use crate::traits::BaseCalendar;
 use crate::traits::DavCalendar;
-use crate::calendar::SupportedComponents;
+use crate::calendar::{CalendarProperties, CollectionChanges, Privileges, SupportedComponents};
 use crate::item::Item;
 use crate::item::VersionTag;
 use crate::item::SyncStatus;
 use crate::resource::Resource;
-use crate::utils::find_elem;
+use crate::quota::Quota;
+use crate::error::{ServerError, ServerErrorKind};
+use crate::utils::{escape_xml, find_elem, find_elems};
 
-static TASKS_BODY: &str = r#"
+static ITEMS_BODY_PREFIX: &str = r#"
     <c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
         <d:prop>
             <d:getetag />
         </d:prop>
-        <c:filter>
-            <c:comp-filter name="VCALENDAR">
-                <c:comp-filter name="VTODO" />
-            </c:comp-filter>
-        </c:filter>
+"#;
+static ITEMS_BODY_SUFFIX: &str = r#"
     </c:calendar-query>
 "#;
 
+static SYNC_TOKEN_BODY: &str = r#"
+    <d:propfind xmlns:d="DAV:">
+        <d:prop>
+            <d:sync-token />
+        </d:prop>
+    </d:propfind>
+"#;
+
 static MULTIGET_BODY_PREFIX: &str = r#"
     <c:calendar-multiget xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
         <d:prop>
+            <d:getetag />
             <c:calendar-data />
         </d:prop>
 "#;
@@ -39,20 +49,146 @@ static MULTIGET_BODY_SUFFIX: &str = r#"
     </c:calendar-multiget>
 "#;
 
+static ADDRESSBOOK_ITEMS_BODY: &str = r#"
+    <d:propfind xmlns:d="DAV:">
+        <d:prop>
+            <d:getetag />
+        </d:prop>
+    </d:propfind>
+"#;
+
+static ADDRESSBOOK_MULTIGET_BODY_PREFIX: &str = r#"
+    <card:addressbook-multiget xmlns:d="DAV:" xmlns:card="urn:ietf:params:xml:ns:carddav">
+        <d:prop>
+            <d:getetag />
+            <card:address-data />
+        </d:prop>
+"#;
+static ADDRESSBOOK_MULTIGET_BODY_SUFFIX: &str = r#"
+    </card:addressbook-multiget>
+"#;
+
 
 
 /// A CalDAV calendar created by a [`Client`](crate::client::Client).
+///
+/// This can also be a CardDAV address book (see [`Client::set_address_books_enabled`](crate::client::Client::set_address_books_enabled)), whose [`SupportedComponents`] are [`SupportedComponents::CONTACT`]
 #[derive(Debug)]
 pub struct RemoteCalendar {
     name: String,
     resource: Resource,
     supported_components: SupportedComponents,
     color: Option<Color>,
+    description: Option<String>,
+    order: Option<i32>,
+    privileges: Privileges,
+    /// The storage space the server has reported for this calendar
+    quota: Quota,
+    /// Whether the server has advertised it supports `sync-collection` REPORTs for this calendar
+    supports_sync_collection: bool,
 
     cached_version_tags: Mutex<Option<HashMap<Url, VersionTag>>>,
 }
 
-#[async_trait]
+impl RemoteCalendar {
+    /// Set the privileges the server has advertised for this calendar
+    pub(crate) fn set_privileges(&mut self, privileges: Privileges) {
+        self.privileges = privileges;
+    }
+
+    /// Set the description the server has for this calendar
+    pub(crate) fn set_description(&mut self, description: Option<String>) {
+        self.description = description;
+    }
+
+    /// Set the order the server has for this calendar
+    pub(crate) fn set_order(&mut self, order: Option<i32>) {
+        self.order = order;
+    }
+
+    /// Set the storage space the server has reported for this calendar
+    pub(crate) fn set_quota(&mut self, quota: Quota) {
+        self.quota = quota;
+    }
+
+    /// How much storage space this calendar uses, and how much is still available, as the server reported it when calendars were listed (see also [`crate::client::Client::quota`])
+    pub fn quota(&self) -> Quota {
+        self.quota
+    }
+
+    /// Set whether the server has advertised `sync-collection` in the `supported-report-set` of this calendar
+    pub(crate) fn set_supports_sync_collection(&mut self, supported: bool) {
+        self.supports_sync_collection = supported;
+    }
+
+    /// Whether this is a CardDAV address book rather than a calendar
+    fn is_address_book(&self) -> bool {
+        self.supported_components.contains(SupportedComponents::CONTACT)
+    }
+
+    /// The MIME type of the items of this collection
+    fn content_type(&self) -> &'static str {
+        match self.is_address_book() {
+            true => "text/vcard",
+            false => "text/calendar",
+        }
+    }
+
+    /// Send a `calendar-query` REPORT with a given filter, and return the URLs and version tags of the items that match
+    async fn query_version_tags(&self, filter: String) -> Result<HashMap<Url, VersionTag>, Box<dyn Error>> {
+        let body = format!("{}{}{}", ITEMS_BODY_PREFIX, filter, ITEMS_BODY_SUFFIX);
+        let responses = crate::client::sub_request_and_extract_elems(&self.resource, "REPORT", body, "response").await?;
+        Ok(self.extract_version_tags(responses))
+    }
+
+    /// List the URLs and version tags of the items of an address book.
+    ///
+    /// Not every CardDAV server supports `addressbook-query` REPORTs without filters, but they all support listing the collection with a PROPFIND
+    async fn address_book_version_tags(&self) -> Result<HashMap<Url, VersionTag>, Box<dyn Error>> {
+        let responses = crate::client::sub_request_and_extract_elems(&self.resource, "PROPFIND", ADDRESSBOOK_ITEMS_BODY.to_string(), "response").await?;
+        let responses = responses.into_iter()
+            .filter(|response| {
+                // The collection itself is part of the reply
+                let href = find_elem(response, "href").map(|href| href.text()).unwrap_or_default();
+                href.trim_end_matches('/') != self.resource.url().path().trim_end_matches('/')
+            })
+            .collect();
+        Ok(self.extract_version_tags(responses))
+    }
+
+    fn extract_version_tags(&self, responses: Vec<Element>) -> HashMap<Url, VersionTag> {
+        let mut items = HashMap::new();
+        for response in responses {
+            let item_url = crate::utils::find_elem(&response, "href")
+                .map(|elem| self.resource.combine(&elem.text()));
+            let item_url = match item_url {
+                None => {
+                    log::warn!("Unable to extract HREF");
+                    continue;
+                },
+                Some(resource) => {
+                    resource.url().clone()
+                },
+            };
+
+            let version_tag = match crate::utils::find_elem(&response, "getetag") {
+                None => {
+                    log::warn!("Unable to extract ETAG for item {}, ignoring it", item_url);
+                    continue;
+                },
+                Some(etag) => {
+                    VersionTag::from(etag.text())
+                }
+            };
+
+            items.insert(item_url.clone(), version_tag);
+        }
+        items
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 impl BaseCalendar for RemoteCalendar {
     fn name(&self) -> &str { &self.name }
     fn url(&self) -> &Url { &self.resource.url() }
@@ -62,36 +198,43 @@ impl BaseCalendar for RemoteCalendar {
     fn color(&self) -> Option<&Color> {
         self.color.as_ref()
     }
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+    fn order(&self) -> Option<i32> {
+        self.order
+    }
+    fn privileges(&self) -> Privileges {
+        self.privileges
+    }
 
-    async fn add_item(&mut self, item: Item) -> Result<SyncStatus, Box<dyn Error>> {
+    async fn add_item(&mut self, item: Item) -> Result<SyncStatus, crate::Error> {
         let ical_text = crate::ical::build_from(&item)?;
 
-        let response = reqwest::Client::new()
+        let request = self.resource.http()
             .put(item.url().clone())
             .header("If-None-Match", "*")
-            .header(CONTENT_TYPE, "text/calendar")
+            .header(CONTENT_TYPE, self.content_type())
             .header(CONTENT_LENGTH, ical_text.len())
-            .basic_auth(self.resource.username(), Some(self.resource.password()))
-            .body(ical_text)
-            .send()
-            .await?;
+            .body(ical_text);
+        let response = self.resource.send(request).await?;
 
         if response.status().is_success() == false {
-            return Err(format!("Unexpected HTTP status code {:?}", response.status()).into());
+            return Err(ServerError::from_response(response).await.into());
         }
 
         let reply_hdrs = response.headers();
         match reply_hdrs.get("ETag") {
             None => Err(format!("No ETag in these response headers: {:?} (request was {:?})", reply_hdrs, item.url()).into()),
             Some(etag) => {
-                let vtag_str = etag.to_str()?;
+                let vtag_str = etag.to_str().map_err(|err| crate::Error::Other(Box::new(err)))?;
                 let vtag = VersionTag::from(String::from(vtag_str));
                 Ok(SyncStatus::Synced(vtag))
             }
         }
     }
 
-    async fn update_item(&mut self, item: Item) -> Result<SyncStatus, Box<dyn Error>> {
+    async fn update_item(&mut self, item: Item) -> Result<SyncStatus, crate::Error> {
         let old_etag = match item.sync_status() {
             SyncStatus::NotSynced => return Err("Cannot update an item that has not been synced already".into()),
             SyncStatus::Synced(_) => return Err("Cannot update an item that has not changed".into()),
@@ -100,25 +243,27 @@ impl BaseCalendar for RemoteCalendar {
         };
         let ical_text = crate::ical::build_from(&item)?;
 
-        let request = reqwest::Client::new()
+        let request = self.resource.http()
             .put(item.url().clone())
             .header("If-Match", old_etag.as_str())
-            .header(CONTENT_TYPE, "text/calendar")
+            .header(CONTENT_TYPE, self.content_type())
             .header(CONTENT_LENGTH, ical_text.len())
-            .basic_auth(self.resource.username(), Some(self.resource.password()))
-            .body(ical_text)
-            .send()
-            .await?;
+            .body(ical_text);
+        let request = self.resource.send(request).await?;
 
         if request.status().is_success() == false {
-            return Err(format!("Unexpected HTTP status code {:?}", request.status()).into());
+            let server_error = ServerError::from_response(request).await;
+            if server_error.is_edit_conflict() {
+                return Err(crate::Error::Conflict { url: item.url().clone(), source: server_error });
+            }
+            return Err(server_error.into());
         }
 
         let reply_hdrs = request.headers();
         match reply_hdrs.get("ETag") {
             None => Err(format!("No ETag in these response headers: {:?} (request was {:?})", reply_hdrs, item.url()).into()),
             Some(etag) => {
-                let vtag_str = etag.to_str()?;
+                let vtag_str = etag.to_str().map_err(|err| crate::Error::Other(Box::new(err)))?;
                 let vtag = VersionTag::from(String::from(vtag_str));
                 Ok(SyncStatus::Synced(vtag))
             }
@@ -126,127 +271,391 @@ impl BaseCalendar for RemoteCalendar {
     }
 }
 
-#[async_trait]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 impl DavCalendar for RemoteCalendar {
     fn new(name: String, resource: Resource, supported_components: SupportedComponents, color: Option<Color>) -> Self {
         Self {
             name, resource, supported_components, color,
+            description: None,
+            order: None,
+            privileges: Privileges::default(),
+            quota: Quota::default(),
+            supports_sync_collection: false,
             cached_version_tags: Mutex::new(None),
         }
     }
 
 
-    async fn get_item_version_tags(&self) -> Result<HashMap<Url, VersionTag>, Box<dyn Error>> {
+    async fn get_item_version_tags(&self) -> Result<HashMap<Url, VersionTag>, crate::Error> {
         if let Some(map) = &*self.cached_version_tags.lock().unwrap() {
             log::debug!("Version tags are already cached.");
             return Ok(map.clone());
         };
 
-        let responses = crate::client::sub_request_and_extract_elems(&self.resource, "REPORT", TASKS_BODY.to_string(), "response").await?;
-
-        let mut items = HashMap::new();
-        for response in responses {
-            let item_url = crate::utils::find_elem(&response, "href")
-                .map(|elem| self.resource.combine(&elem.text()));
-            let item_url = match item_url {
-                None => {
-                    log::warn!("Unable to extract HREF");
-                    continue;
-                },
-                Some(resource) => {
-                    resource.url().clone()
-                },
-            };
+        let items = match self.is_address_book() {
+            true => self.address_book_version_tags().await?,
+            false => self.query_version_tags(self.supported_components.to_calendar_query_filter()).await?,
+        };
 
-            let version_tag = match crate::utils::find_elem(&response, "getetag") {
-                None => {
-                    log::warn!("Unable to extract ETAG for item {}, ignoring it", item_url);
-                    continue;
-                },
-                Some(etag) => {
-                    VersionTag::from(etag.text())
-                }
-            };
+        // Note: the mutex cannot be locked during this whole async function, but it can safely be re-entrant (this will just waste an unnecessary request)
+        *self.cached_version_tags.lock().unwrap() = Some(items.clone());
+        Ok(items)
+    }
 
-            items.insert(item_url.clone(), version_tag);
+    async fn get_item_version_tags_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<HashMap<Url, VersionTag>, crate::Error> {
+        // Contacts are not bound to any date
+        if self.is_address_book() {
+            return self.get_item_version_tags().await;
         }
 
-        // Note: the mutex cannot be locked during this whole async function, but it can safely be re-entrant (this will just waste an unnecessary request)
-        *self.cached_version_tags.lock().unwrap() = Some(items.clone());
+        // A calendar-query can only filter on one kind of component, hence one request per kind
+        let mut items = HashMap::new();
+        if self.supported_components.contains(SupportedComponents::EVENT) {
+            items.extend(self.query_version_tags(component_filter("VEVENT", Some((start, end)))).await?);
+        }
+        if self.supported_components.contains(SupportedComponents::TODO) {
+            items.extend(self.query_version_tags(component_filter("VTODO", None)).await?);
+        }
+        if self.supported_components.contains(SupportedComponents::JOURNAL) {
+            items.extend(self.query_version_tags(component_filter("VJOURNAL", None)).await?);
+        }
         Ok(items)
     }
 
-    async fn get_item_by_url(&self, url: &Url) -> Result<Option<Item>, Box<dyn Error>> {
-        let res = reqwest::Client::new()
-            .get(url.clone())
-            .header(CONTENT_TYPE, "text/calendar")
-            .basic_auth(self.resource.username(), Some(self.resource.password()))
-            .send()
-            .await?;
+    async fn get_sync_token(&self) -> Result<Option<String>, crate::Error> {
+        if self.supports_sync_collection == false {
+            return Ok(None);
+        }
+        let token = crate::client::sub_request_and_extract_elem(&self.resource, SYNC_TOKEN_BODY.to_string(), &["sync-token"]).await?;
+        Ok(Some(token).filter(|t| t.is_empty() == false))
+    }
 
-        if res.status().is_success() == false {
-            return Err(format!("Unexpected HTTP status code {:?}", res.status()).into());
+    async fn get_changes_since(&self, sync_token: &str) -> Result<Option<CollectionChanges>, crate::Error> {
+        if self.supports_sync_collection == false {
+            return Ok(None);
         }
 
-        let text = res.text().await?;
+        let mut changed = HashMap::new();
+        let mut deleted = HashSet::new();
+        let mut sync_token = sync_token.to_string();
+        loop {
+            let text = match crate::client::sub_request(&self.resource, "REPORT", sync_collection_body(&sync_token), 0).await {
+                Ok(text) => text,
+                Err(err) => match err.downcast_ref::<ServerError>().map(|e| e.kind()) {
+                    // The server has forgotten about this token
+                    Some(ServerErrorKind::Precondition(precondition)) if precondition == "valid-sync-token" => return Ok(None),
+                    _ => return Err(err.into()),
+                },
+            };
+            let multistatus: Element = text.parse()?;
+            let (new_token, truncated) = parse_sync_collection(&multistatus, &self.resource, &mut changed, &mut deleted)?;
+            sync_token = new_token;
 
-        // This is supposed to be cached
-        let version_tags = self.get_item_version_tags().await?;
-        let vt = match version_tags.get(url) {
-            None => return Err(format!("Inconsistent data: {} has no version tag", url).into()),
-            Some(vt) => vt,
-        };
+            if truncated == false {
+                break;
+            }
+        }
 
-        let item = crate::ical::parse(&text, url.clone(), SyncStatus::Synced(vt.clone()))?;
-        Ok(Some(item))
+        Ok(Some(CollectionChanges::new(sync_token, changed, deleted)))
     }
 
-    async fn get_items_by_url(&self, urls: &[Url]) -> Result<Vec<Option<Item>>, Box<dyn Error>> {
+    async fn get_item_by_url(&self, url: &Url) -> Result<Option<Item>, crate::Error> {
+        // A multiget of a single item also returns its version tag, which saves listing the whole calendar
+        let mut items = self.get_items_by_url(std::slice::from_ref(url)).await?;
+        Ok(items.pop().flatten())
+    }
+
+    async fn get_items_by_url(&self, urls: &[Url]) -> Result<Vec<Option<Item>>, crate::Error> {
+        if urls.is_empty() {
+            return Ok(Vec::new());
+        }
+
         // Build the request body
         let mut hrefs = String::new();
         for url in urls {
             hrefs.push_str(&format!("        <d:href>{}</d:href>\n", url.path()));
         }
-        let body = format!("{}{}{}", MULTIGET_BODY_PREFIX, hrefs, MULTIGET_BODY_SUFFIX);
+        let body = match self.is_address_book() {
+            true => format!("{}{}{}", ADDRESSBOOK_MULTIGET_BODY_PREFIX, hrefs, ADDRESSBOOK_MULTIGET_BODY_SUFFIX),
+            false => format!("{}{}{}", MULTIGET_BODY_PREFIX, hrefs, MULTIGET_BODY_SUFFIX),
+        };
+        let data_elem = match self.is_address_book() {
+            true => "address-data",
+            false => "calendar-data",
+        };
 
         // Send the request
         let xml_replies = crate::client::sub_request_and_extract_elems(&self.resource, "REPORT", body, "response").await?;
 
-        // This is supposed to be cached
-        let version_tags = self.get_item_version_tags().await?;
-
         // Parse the results
-        let mut results = Vec::new();
+        let mut found = HashMap::new();
         for xml_reply in xml_replies {
             let href = find_elem(&xml_reply, "href").ok_or("Missing HREF")?.text();
             let mut url = self.resource.url().clone();
             url.set_path(&href);
-            let ical_data = find_elem(&xml_reply, "calendar-data").ok_or("Missing calendar-data")?.text();
+            let ical_data = match find_elem(&xml_reply, data_elem) {
+                Some(data) => data.text(),
+                None => {
+                    log::debug!("No {} for {}, it may have been deleted", data_elem, url);
+                    continue;
+                },
+            };
 
-            let vt = match version_tags.get(&url) {
-                None => return Err(format!("Inconsistent data: {} has no version tag", url).into()),
-                Some(vt) => vt,
+            let vt = match find_elem(&xml_reply, "getetag").map(|etag| etag.text()).filter(|etag| etag.is_empty() == false) {
+                Some(etag) => VersionTag::from(etag),
+                None => {
+                    // This is supposed to be cached
+                    let version_tags = self.get_item_version_tags().await?;
+                    match version_tags.get(&url) {
+                        None => return Err(format!("Inconsistent data: {} has no version tag", url).into()),
+                        Some(vt) => vt.clone(),
+                    }
+                },
             };
 
-            let item = crate::ical::parse(&ical_data, url.clone(), SyncStatus::Synced(vt.clone()))?;
-            results.push(Some(item));
+            let item = crate::ical::parse(&ical_data, url.clone(), SyncStatus::Synced(vt))?;
+            found.insert(url, item);
         }
 
-        Ok(results)
+        // Servers may reply in any order, and leave out the items they do not have
+        Ok(urls.iter().map(|url| found.remove(url)).collect())
     }
 
-    async fn delete_item(&mut self, item_url: &Url) -> Result<(), Box<dyn Error>> {
-        let del_response = reqwest::Client::new()
-            .delete(item_url.clone())
-            .basic_auth(self.resource.username(), Some(self.resource.password()))
-            .send()
-            .await?;
+    async fn delete_item(&mut self, item_url: &Url) -> Result<(), crate::Error> {
+        let request = self.resource.http()
+            .delete(item_url.clone());
+        let del_response = self.resource.send(request).await?;
 
         if del_response.status().is_success() == false {
-            return Err(format!("Unexpected HTTP status code {:?}", del_response.status()).into());
+            return Err(ServerError::from_response(del_response).await.into());
         }
 
         Ok(())
     }
+
+    async fn move_item(&mut self, item_url: &Url, version_tag: &VersionTag, destination: &Url) -> Result<Option<VersionTag>, crate::Error> {
+        let request = self.resource.http()
+            .request(Method::from_bytes(b"MOVE").unwrap(), item_url.clone())
+            .header("Destination", destination.as_str())
+            .header("Overwrite", "F")
+            .header("If-Match", version_tag.as_str());
+        let response = self.resource.send(request).await?;
+
+        if response.status().is_success() == false {
+            let server_error = ServerError::from_response(response).await;
+            if server_error.is_edit_conflict() {
+                return Err(crate::Error::Conflict { url: item_url.clone(), source: server_error });
+            }
+            return Err(server_error.into());
+        }
+
+        // Servers seldom send the ETag of a moved item
+        Ok(response.headers().get("ETag")
+            .and_then(|etag| etag.to_str().ok())
+            .map(|etag| VersionTag::from(etag.to_string())))
+    }
+
+    async fn update_properties(&mut self, properties: &CalendarProperties) -> Result<(), crate::Error> {
+        let body = proppatch_body(properties, self.is_address_book());
+        let reply = crate::client::sub_request(&self.resource, "PROPPATCH", body, 0).await?;
+
+        // Every property is updated, or none is (see RFC 4918, section 9.2)
+        let root: Element = reply.parse()?;
+        for propstat in find_elems(&root, "propstat") {
+            let status = find_elem(propstat, "status").map(|s| s.text()).unwrap_or_default();
+            if status.contains(" 200 ") == false {
+                return Err(format!("The server refused to update the properties of calendar {}: {}", self.url(), status.trim()).into());
+            }
+        }
+
+        self.name = properties.name.clone();
+        self.description = properties.description.clone();
+        self.color = properties.color.clone();
+        self.order = properties.order;
+        Ok(())
+    }
+}
+
+fn proppatch_body(properties: &CalendarProperties, address_book: bool) -> String {
+    let mut set = format!("<d:displayname>{}</d:displayname>", escape_xml(&properties.name));
+    let mut remove = String::new();
+    let description_elem = match address_book {
+        true => "card:addressbook-description",
+        false => "c:calendar-description",
+    };
+    match &properties.description {
+        Some(description) => set.push_str(&format!("<{0}>{1}</{0}>", description_elem, escape_xml(description))),
+        None => remove.push_str(&format!("<{} />", description_elem)),
+    }
+    match &properties.color {
+        Some(color) => set.push_str(&format!("<a:calendar-color>{}FF</a:calendar-color>", color.to_hex_string().to_ascii_uppercase())),
+        None => remove.push_str("<a:calendar-color />"),
+    }
+    match properties.order {
+        Some(order) => set.push_str(&format!("<a:calendar-order>{}</a:calendar-order>", order)),
+        None => remove.push_str("<a:calendar-order />"),
+    }
+    let remove = match remove.is_empty() {
+        true => String::new(),
+        false => format!("<d:remove><d:prop>{}</d:prop></d:remove>", remove),
+    };
+
+    format!(r#"<?xml version="1.0" encoding="utf-8" ?>
+    <d:propertyupdate xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav" xmlns:card="urn:ietf:params:xml:ns:carddav" xmlns:a="http://apple.com/ns/ical/">
+        <d:set><d:prop>{}</d:prop></d:set>
+        {}
+    </d:propertyupdate>
+    "#,
+        set,
+        remove,
+    )
+}
+
+/// Read the reply to a `sync-collection` REPORT into `changed` and `deleted` (which may already contain the changes of previous pages).
+/// This returns the new sync token, and whether the server has truncated its results
+fn parse_sync_collection(multistatus: &Element, resource: &Resource, changed: &mut HashMap<Url, VersionTag>, deleted: &mut HashSet<Url>) -> Result<(String, bool), Box<dyn Error>> {
+    let sync_token = find_elem(multistatus, "sync-token").map(|elem| elem.text()).ok_or("Missing sync-token")?;
+
+    let mut truncated = false;
+    for response in multistatus.children().filter(|elem| elem.name() == "response") {
+        let url = match find_elem(response, "href") {
+            None => {
+                log::warn!("Unable to extract HREF");
+                continue;
+            },
+            Some(href) => resource.combine(&href.text()).url().clone(),
+        };
+        // A status that is directly in the response (rather than in a propstat) tells the whole resource is gone
+        let status = response.children().find(|elem| elem.name() == "status").map(|elem| elem.text());
+        match status {
+            Some(status) if status.contains(" 404") => {
+                changed.remove(&url);
+                deleted.insert(url);
+            },
+            // The server has truncated the results, the remaining changes must be asked with the new token
+            Some(status) if status.contains(" 507") => truncated = true,
+            _ => {
+                if url.path().trim_end_matches('/') == resource.url().path().trim_end_matches('/') {
+                    continue;
+                }
+                match find_elem(response, "getetag") {
+                    None => log::warn!("Unable to extract ETAG for item {}, ignoring it", url),
+                    Some(etag) => {
+                        deleted.remove(&url);
+                        changed.insert(url, VersionTag::from(etag.text()));
+                    },
+                }
+            },
+        }
+    }
+
+    Ok((sync_token, truncated))
+}
+
+fn sync_collection_body(sync_token: &str) -> String {
+    format!(r#"
+    <d:sync-collection xmlns:d="DAV:">
+        <d:sync-token>{}</d:sync-token>
+        <d:sync-level>1</d:sync-level>
+        <d:prop>
+            <d:getetag />
+        </d:prop>
+    </d:sync-collection>
+    "#,
+        escape_xml(sync_token),
+    )
+}
+
+/// A `calendar-query` filter on a kind of component (e.g. `VEVENT`), optionally restricted to the ones that happen between two dates
+fn component_filter(component: &str, time_range: Option<(DateTime<Utc>, DateTime<Utc>)>) -> String {
+    let time_range = match time_range {
+        None => String::new(),
+        Some((start, end)) => format!(r#"<c:time-range start="{}" end="{}" />"#, start.format("%Y%m%dT%H%M%SZ"), end.format("%Y%m%dT%H%M%SZ")),
+    };
+    format!(r#"
+        <c:filter>
+            <c:comp-filter name="VCALENDAR">
+                <c:comp-filter name="{}">{}</c:comp-filter>
+            </c:comp-filter>
+        </c:filter>
+        "#,
+        component,
+        time_range,
+    )
 }
 
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calendar_resource() -> Resource {
+        Resource::new("https://caldav.com/calendars/john/shopping/".parse().unwrap(), "john".to_string(), "secret".to_string())
+    }
+
+    #[test]
+    fn test_sync_collection_body() {
+        let body = sync_collection_body("http://sabre.io/ns/sync/42?a=1&b=<2>");
+        assert!(body.contains("<d:sync-token>http://sabre.io/ns/sync/42?a=1&amp;b=&lt;2&gt;</d:sync-token>"));
+    }
+
+    #[test]
+    fn test_sync_collection_parsing() {
+        let resource = calendar_resource();
+        let milk: Url = "https://caldav.com/calendars/john/shopping/milk.ics".parse().unwrap();
+        let eggs: Url = "https://caldav.com/calendars/john/shopping/eggs.ics".parse().unwrap();
+        let bread: Url = "https://caldav.com/calendars/john/shopping/bread.ics".parse().unwrap();
+
+        let first_page: Element = r#"<d:multistatus xmlns:d="DAV:">
+            <d:response>
+                <d:href>/calendars/john/shopping/milk.ics</d:href>
+                <d:propstat><d:prop><d:getetag>"1"</d:getetag></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat>
+            </d:response>
+            <d:response>
+                <d:href>/calendars/john/shopping/eggs.ics</d:href>
+                <d:status>HTTP/1.1 404 Not Found</d:status>
+            </d:response>
+            <d:response>
+                <d:href>/calendars/john/shopping/</d:href>
+                <d:status>HTTP/1.1 507 Insufficient Storage</d:status>
+            </d:response>
+            <d:sync-token>http://sabre.io/ns/sync/43</d:sync-token>
+        </d:multistatus>"#.parse().unwrap();
+
+        let mut changed = HashMap::new();
+        let mut deleted = HashSet::new();
+        let (token, truncated) = parse_sync_collection(&first_page, &resource, &mut changed, &mut deleted).unwrap();
+        assert_eq!(token, "http://sabre.io/ns/sync/43");
+        assert!(truncated);
+        assert_eq!(changed.get(&milk), Some(&VersionTag::from(String::from("\"1\""))));
+        assert_eq!(deleted, vec![eggs.clone()].into_iter().collect());
+
+        // Later pages override what the previous ones have told
+        let second_page: Element = r#"<d:multistatus xmlns:d="DAV:">
+            <d:response>
+                <d:href>/calendars/john/shopping/milk.ics</d:href>
+                <d:status>HTTP/1.1 404 Not Found</d:status>
+            </d:response>
+            <d:response>
+                <d:href>/calendars/john/shopping/eggs.ics</d:href>
+                <d:propstat><d:prop><d:getetag>"2"</d:getetag></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat>
+            </d:response>
+            <d:response>
+                <d:href>/calendars/john/shopping/bread.ics</d:href>
+                <d:propstat><d:prop><d:getetag>"3"</d:getetag></d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat>
+            </d:response>
+            <d:sync-token>http://sabre.io/ns/sync/44</d:sync-token>
+        </d:multistatus>"#.parse().unwrap();
+
+        let (token, truncated) = parse_sync_collection(&second_page, &resource, &mut changed, &mut deleted).unwrap();
+        assert_eq!(token, "http://sabre.io/ns/sync/44");
+        assert_eq!(truncated, false);
+        assert_eq!(changed.keys().cloned().collect::<HashSet<_>>(), vec![eggs, bread].into_iter().collect());
+        assert_eq!(deleted, vec![milk].into_iter().collect());
+
+        let missing_token: Element = r#"<d:multistatus xmlns:d="DAV:"></d:multistatus>"#.parse().unwrap();
+        assert!(parse_sync_collection(&missing_token, &resource, &mut changed, &mut deleted).is_err());
+    }
+}
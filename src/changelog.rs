@@ -0,0 +1,189 @@
+//! A feed of the changes made to the items of a [`Cache`](crate::cache::Cache) or of a [`PersistentCache`](crate::persistent_cache::PersistentCache)
+//!
+//! Apps that maintain their own indexes (e.g. a full-text search database) can remember the last sequence number they have processed,
+//! and only ask for the changes that happened since then, instead of re-reading entire calendars.
+
+use std::collections::VecDeque;
+use std::fmt::{Debug, Formatter};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::storage::CacheStorage;
+
+/// Only this many records are kept. Apps that have not fetched the changes for too long will have to re-read everything
+const MAX_RECORDS: usize = 10_000;
+
+/// A changelog that is shared between a cache and its calendars
+pub type SharedChangeLog = Arc<Mutex<ChangeLog>>;
+
+/// A callback that is told about a change made to an item
+pub type ItemCallback = Arc<dyn Fn(&ChangeRecord) + Send + Sync>;
+/// A callback that is told about a calendar that has been created, deleted, or whose properties have changed
+pub type CalendarCallback = Arc<dyn Fn(&Url) + Send + Sync>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChangeKind {
+    Added,
+    /// The item has been modified.
+    /// Modifications made through mutable references (e.g. [`CompleteCalendar::get_item_by_url_mut`](crate::traits::CompleteCalendar::get_item_by_url_mut)) are recorded the next time their calendar is used, and only if the content or the sync status of the item has actually changed
+    Updated,
+    /// The item has been deleted (or marked for deletion)
+    Deleted,
+}
+
+/// A change that happened to an item
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChangeRecord {
+    seq: u64,
+    calendar_url: Url,
+    item_url: Url,
+    kind: ChangeKind,
+}
+
+impl ChangeRecord {
+    pub fn seq(&self) -> u64 { self.seq }
+    pub fn calendar_url(&self) -> &Url { &self.calendar_url }
+    pub fn item_url(&self) -> &Url { &self.item_url }
+    pub fn kind(&self) -> ChangeKind { self.kind }
+}
+
+/// A list of changes, ordered by monotonically increasing sequence numbers
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ChangeLog {
+    /// The sequence number of the latest record (0 if nothing has ever been recorded)
+    last_seq: u64,
+    /// Records up to this sequence number (included) have been dropped
+    #[serde(default)]
+    pruned_up_to: u64,
+    records: VecDeque<ChangeRecord>,
+    #[serde(skip)]
+    observers: ChangeObservers,
+}
+
+/// The callbacks that have been registered on a changelog
+#[derive(Clone, Default)]
+pub(crate) struct ChangeObservers {
+    item_changed: Vec<ItemCallback>,
+    item_deleted: Vec<ItemCallback>,
+    calendar_changed: Vec<CalendarCallback>,
+}
+
+impl Debug for ChangeObservers {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChangeObservers")
+            .field("item_changed", &self.item_changed.len())
+            .field("item_deleted", &self.item_deleted.len())
+            .field("calendar_changed", &self.calendar_changed.len())
+            .finish()
+    }
+}
+
+impl ChangeObservers {
+    fn notify_item(&self, record: &ChangeRecord) {
+        let callbacks = match record.kind {
+            ChangeKind::Added | ChangeKind::Updated => &self.item_changed,
+            ChangeKind::Deleted => &self.item_deleted,
+        };
+        for callback in callbacks {
+            callback(record);
+        }
+    }
+
+    fn notify_calendar(&self, calendar_url: &Url) {
+        for callback in &self.calendar_changed {
+            callback(calendar_url);
+        }
+    }
+}
+
+impl ChangeLog {
+    /// Load a changelog from the metadata of a storage. Any error here is not fatal, it will only make the apps that use it re-read every calendar
+    pub(crate) fn load<S: CacheStorage>(storage: &mut S, metadata_name: &str) -> Self {
+        let data = match storage.load_metadata(metadata_name) {
+            Err(err) => {
+                log::warn!("Unable to read the changelog from the cache ({}). It will be reset", err);
+                return Self::default();
+            },
+            Ok(None) => {
+                log::info!("No changelog available in the cache");
+                return Self::default();
+            },
+            Ok(Some(data)) => data,
+        };
+        match serde_json::from_slice(&data) {
+            Err(err) => {
+                log::warn!("Unable to read the changelog from the cache ({}). It will be reset", err);
+                Self::default()
+            },
+            Ok(log) => log,
+        }
+    }
+
+    fn record(&mut self, calendar_url: &Url, item_url: &Url, kind: ChangeKind) -> ChangeRecord {
+        self.last_seq += 1;
+        let record = ChangeRecord {
+            seq: self.last_seq,
+            calendar_url: calendar_url.clone(),
+            item_url: item_url.clone(),
+            kind,
+        };
+        self.records.push_back(record.clone());
+
+        while self.records.len() > MAX_RECORDS {
+            if let Some(dropped) = self.records.pop_front() {
+                self.pruned_up_to = dropped.seq;
+            }
+        }
+        record
+    }
+
+    pub(crate) fn add_item_observer(&mut self, kind: ChangeKind, callback: ItemCallback) {
+        match kind {
+            ChangeKind::Added | ChangeKind::Updated => self.observers.item_changed.push(callback),
+            ChangeKind::Deleted => self.observers.item_deleted.push(callback),
+        }
+    }
+
+    pub(crate) fn add_calendar_observer(&mut self, callback: CalendarCallback) {
+        self.observers.calendar_changed.push(callback);
+    }
+
+    /// The sequence number of the latest change
+    pub fn current_seq(&self) -> u64 {
+        self.last_seq
+    }
+
+    /// The changes that happened after the change numbered `seq` (use `0` to get every available change).
+    ///
+    /// This returns `None` in case some of these changes are not available any more (or if `seq` does not belong to this changelog).
+    /// In this case, you should re-read every calendar, then use [`Self::current_seq`] as your new starting point
+    pub fn changes_since(&self, seq: u64) -> Option<Vec<ChangeRecord>> {
+        if seq < self.pruned_up_to || seq > self.last_seq {
+            return None;
+        }
+        Some(self.records.iter()
+            .filter(|record| record.seq > seq)
+            .cloned()
+            .collect())
+    }
+}
+
+/// Record a change to an item, then tell the registered callbacks about it.
+///
+/// Callbacks are called once the changelog has been unlocked, so that they can read it
+pub(crate) fn record_change(log: &SharedChangeLog, calendar_url: &Url, item_url: &Url, kind: ChangeKind) {
+    let (record, observers) = {
+        let mut log = log.lock().unwrap();
+        let record = log.record(calendar_url, item_url, kind);
+        (record, log.observers.clone())
+    };
+    observers.notify_item(&record);
+}
+
+/// Tell the registered callbacks that a calendar has been created, deleted, or that its properties have changed
+pub(crate) fn record_calendar_change(log: &SharedChangeLog, calendar_url: &Url) {
+    let observers = log.lock().unwrap().observers.clone();
+    observers.notify_calendar(calendar_url);
+}
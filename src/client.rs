@@ -3,7 +3,7 @@
 use std::error::Error;
 use std::convert::TryFrom;
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, RwLock};
 
 use async_trait::async_trait;
 use reqwest::{Method, StatusCode};
@@ -11,11 +11,24 @@ use reqwest::header::CONTENT_TYPE;
 use minidom::Element;
 use url::Url;
 use csscolorparser::Color;
+use chrono::{DateTime, Utc};
 
 use crate::resource::Resource;
-use crate::utils::{find_elem, find_elems};
+use crate::auth::Authentication;
+use crate::retry::RetryPolicy;
+use crate::rate_limit::RateLimit;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::tls::TlsConfig;
+use crate::proxy::ProxyConfig;
+use crate::transport::HttpTransport;
+use crate::error::ServerError;
+use crate::free_busy::BusyPeriod;
+use crate::quota::Quota;
+use crate::scheduling::{DeliveryStatus, ItipMessage};
+use crate::discovery;
+use crate::utils::{escape_xml, find_elem, find_elems};
 use crate::calendar::remote_calendar::RemoteCalendar;
-use crate::calendar::SupportedComponents;
+use crate::calendar::{Privileges, SupportedComponents};
 use crate::traits::CalDavSource;
 use crate::traits::BaseCalendar;
 use crate::traits::DavCalendar;
@@ -43,8 +56,55 @@ static CAL_BODY: &str = r#"
        <d:prop>
          <d:displayname />
          <E:calendar-color xmlns:E="http://apple.com/ns/ical/"/>
+         <E:calendar-order xmlns:E="http://apple.com/ns/ical/"/>
+         <c:calendar-description />
          <d:resourcetype />
          <c:supported-calendar-component-set />
+         <d:current-user-privilege-set />
+         <d:supported-report-set />
+         <d:quota-available-bytes />
+         <d:quota-used-bytes />
+       </d:prop>
+    </d:propfind>
+"#;
+
+static ADDRESSBOOK_HOMESET_BODY: &str = r#"
+    <d:propfind xmlns:d="DAV:" xmlns:card="urn:ietf:params:xml:ns:carddav" >
+      <d:self/>
+      <d:prop>
+        <card:addressbook-home-set />
+      </d:prop>
+    </d:propfind>
+"#;
+
+static ADDRESSBOOK_BODY: &str = r#"
+    <d:propfind xmlns:d="DAV:" xmlns:card="urn:ietf:params:xml:ns:carddav" >
+       <d:prop>
+         <d:displayname />
+         <card:addressbook-description />
+         <d:resourcetype />
+         <d:current-user-privilege-set />
+         <d:supported-report-set />
+         <d:quota-available-bytes />
+         <d:quota-used-bytes />
+       </d:prop>
+    </d:propfind>
+"#;
+
+static SCHEDULE_BODY: &str = r#"
+    <d:propfind xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav" >
+      <d:prop>
+        <c:schedule-inbox-URL />
+        <c:schedule-outbox-URL />
+      </d:prop>
+    </d:propfind>
+"#;
+
+static QUOTA_BODY: &str = r#"
+    <d:propfind xmlns:d="DAV:">
+       <d:prop>
+         <d:quota-available-bytes />
+         <d:quota-used-bytes />
        </d:prop>
     </d:propfind>
 "#;
@@ -55,17 +115,15 @@ pub(crate) async fn sub_request(resource: &Resource, method: &str, body: String,
     let method = method.parse()
         .expect("invalid method name");
 
-    let res = reqwest::Client::new()
+    let request = resource.http()
         .request(method, resource.url().clone())
         .header("Depth", depth)
         .header(CONTENT_TYPE, "application/xml")
-        .basic_auth(resource.username(), Some(resource.password()))
-        .body(body)
-        .send()
-        .await?;
+        .body(body);
+    let res = resource.send(request).await?;
 
     if res.status().is_success() == false {
-        return Err(format!("Unexpected HTTP status code {:?}", res.status()).into());
+        return Err(ServerError::from_response(res).await.into());
     }
 
     let text = res.text().await?;
@@ -98,9 +156,13 @@ pub(crate) async fn sub_request_and_extract_elems(resource: &Resource, method: &
 
 
 /// A CalDAV data source that fetches its data from a CalDAV server
+///
+/// It can also fetch the CardDAV address books of the same server (see [`Client::set_address_books_enabled`])
 #[derive(Debug)]
 pub struct Client {
     resource: Resource,
+    /// Whether CardDAV address books are listed along with calendars
+    address_books_enabled: bool,
 
     /// The interior mutable part of a Client.
     /// This data may be retrieved once and then cached
@@ -112,30 +174,135 @@ pub struct Client {
 struct CachedReplies {
     principal: Option<Resource>,
     calendar_home_set: Option<Resource>,
-    calendars: Option<HashMap<Url, Arc<Mutex<RemoteCalendar>>>>,
+    address_book_home_set: Option<Resource>,
+    calendars: Option<HashMap<Url, Arc<RwLock<RemoteCalendar>>>>,
 }
 
 impl Client {
-    /// Create a client. This does not start a connection
-    pub fn new<S: AsRef<str>, T: ToString, U: ToString>(url: S, username: T, password: U) -> Result<Self, Box<dyn Error>> {
+    /// Create a client. This does not start a connection.
+    ///
+    /// `url` can be the address of the server only (e.g. `https://example.com`), in which case the CalDAV service is looked for at its well-known URL (see [`crate::discovery`])
+    pub fn new<S: AsRef<str>, T: ToString, U: ToString>(url: S, username: T, password: U) -> Result<Self, crate::Error> {
         let url = Url::parse(url.as_ref())?;
 
         Ok(Self{
             resource: Resource::new(url, username.to_string(), password.to_string()),
+            address_books_enabled: false,
+            cached_replies: Mutex::new(CachedReplies::default()),
+        })
+    }
+
+    /// Create a client that authenticates with something else than a username and a password (e.g. OAuth2 bearer tokens, see [`Authentication`]). This does not start a connection
+    pub fn with_authentication<S: AsRef<str>>(url: S, authentication: Authentication) -> Result<Self, crate::Error> {
+        let url = Url::parse(url.as_ref())?;
+
+        Ok(Self{
+            resource: Resource::with_authentication(url, authentication),
+            address_books_enabled: false,
             cached_replies: Mutex::new(CachedReplies::default()),
         })
     }
 
+    /// How requests that fail because of transient errors (e.g. network blips, or `503 Service Unavailable` replies) are retried. This defaults to [`RetryPolicy::default`]
+    pub fn retry_policy(&self) -> &RetryPolicy { self.resource.retry_policy() }
+    /// Change how requests that fail because of transient errors are retried.
+    ///
+    /// Calendars that have already been fetched are fetched again, so that they use this policy as well
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.resource.set_retry_policy(policy);
+        *self.cached_replies.lock().unwrap() = CachedReplies::default();
+    }
+
+    /// Create a client for the CalDAV server of the domain of an email address, which is located with DNS records (see [`discovery::find_service`]). The email address is used as the username.
+    ///
+    /// This is only available with the `dns_discovery` feature
+    #[cfg(feature = "dns_discovery")]
+    pub async fn from_email<T: ToString>(email: &str, password: T) -> Result<Self, crate::Error> {
+        let url = discovery::find_service(email).await?;
+        Self::new(url, email, password)
+    }
+
+    /// How many requests this client sends to the server at most (no limit by default)
+    pub fn rate_limit(&self) -> Option<RateLimit> { self.resource.rate_limit() }
+    /// Pace the requests to the server (or stop pacing them if `limit` is `None`), for servers that throttle clients that send too many requests (e.g. with `429 Too Many Requests` replies).
+    ///
+    /// The limit is shared by every request of this client. Calendars that have already been fetched are fetched again, so that they share it as well
+    pub fn set_rate_limit(&mut self, limit: Option<RateLimit>) {
+        self.resource.set_rate_limit(limit);
+        *self.cached_replies.lock().unwrap() = CachedReplies::default();
+    }
+
+    /// How the identity of the server is checked, and which certificate is presented to it (see [`TlsConfig`]). This is not available in web browsers, which handle TLS by themselves
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn tls_config(&self) -> &TlsConfig { self.resource.tls_config() }
+    /// Change the TLS settings of this client, e.g. to trust the self-signed certificate of a home server, or to present a client certificate.
+    ///
+    /// This fails if the settings cannot be used by the TLS library. Calendars that have already been fetched are fetched again, so that they use these settings as well
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_tls_config(&mut self, config: TlsConfig) -> Result<(), crate::Error> {
+        self.resource.set_tls_config(config)?;
+        *self.cached_replies.lock().unwrap() = CachedReplies::default();
+        Ok(())
+    }
+
+    /// Which proxy requests go through. By default, this is the one of the `HTTPS_PROXY` environment variable (if any, see [`ProxyConfig::System`])
+    pub fn proxy(&self) -> &ProxyConfig { self.resource.proxy() }
+    /// Send requests through another proxy (e.g. a corporate proxy, or Tor with the `socks` feature), or directly to the server.
+    ///
+    /// Calendars that have already been fetched are fetched again, so that they use this proxy as well
+    pub fn set_proxy(&mut self, proxy: ProxyConfig) -> Result<(), crate::Error> {
+        self.resource.set_proxy(proxy)?;
+        *self.cached_replies.lock().unwrap() = CachedReplies::default();
+        Ok(())
+    }
+
+    /// The transport requests are sent with, if it is not the default one (see [`crate::transport`])
+    pub fn transport(&self) -> Option<&Arc<dyn HttpTransport>> { self.resource.transport() }
+    /// Send requests with another transport, e.g. a `reqwest::Client` that is shared with the rest of an application, or a fake server in tests (or with the default one, if `transport` is `None`).
+    ///
+    /// The TLS and proxy settings of this client do not apply to other transports. Calendars that have already been fetched are fetched again, so that they use this transport as well
+    pub fn set_transport(&mut self, transport: Option<Arc<dyn HttpTransport>>) {
+        self.resource.set_transport(transport);
+        *self.cached_replies.lock().unwrap() = CachedReplies::default();
+    }
+
+    /// Whether the CardDAV address books of the user are listed along with their calendars (this is disabled by default)
+    pub fn address_books_enabled(&self) -> bool { self.address_books_enabled }
+    /// List the CardDAV address books of the user along with their calendars (or stop listing them).
+    ///
+    /// Address books are [`RemoteCalendar`]s that support [`SupportedComponents::CONTACT`], and their items are [`Item::Contact`](crate::Item::Contact)s.
+    /// They are synced just like calendars. Calendars that have already been fetched are fetched again, so that address books are listed as well
+    pub fn set_address_books_enabled(&mut self, enabled: bool) {
+        self.address_books_enabled = enabled;
+        *self.cached_replies.lock().unwrap() = CachedReplies::default();
+    }
+
     /// Return the Principal URL, or fetch it from server if not known yet
     async fn get_principal(&self) -> Result<Resource, Box<dyn Error>> {
         if let Some(p) = &self.cached_replies.lock().unwrap().principal {
             return Ok(p.clone());
         }
 
-        let href = sub_request_and_extract_elem(&self.resource, DAVCLIENT_BODY.into(), &["current-user-principal", "href"]).await?;
-        let principal_url = self.resource.combine(&href);
+        let principal_href = sub_request_and_extract_elem(&self.resource, DAVCLIENT_BODY.into(), &["current-user-principal", "href"]).await
+            .map_err(|err| err.to_string());
+        let principal_url = match principal_href {
+            Ok(href) => self.resource.combine(&href),
+            Err(err) => {
+                // The user may only have given the address of the server
+                log::debug!("No principal found at {} ({}), looking for the CalDAV service at {}", self.resource.url(), err, discovery::WELL_KNOWN_PATH);
+                let context_path = discovery::well_known_context_path(&self.resource, DAVCLIENT_BODY).await?
+                    .ok_or_else(|| format!("Unable to find the CalDAV service at {}: {}", self.resource.url(), err))?;
+                if discovery::is_same_origin(&context_path, self.resource.url()) == false {
+                    // The credentials of the user must not be sent to a server they are not meant for
+                    return Err(format!("The CalDAV service of {} is at {}, which is on another server (or not on HTTPS). Use this URL if it is trusted", self.resource.url(), context_path).into());
+                }
+                let context = self.resource.with_url(context_path);
+                let href = sub_request_and_extract_elem(&context, DAVCLIENT_BODY.into(), &["current-user-principal", "href"]).await?;
+                context.combine(&href)
+            },
+        };
         self.cached_replies.lock().unwrap().principal = Some(principal_url.clone());
-        log::debug!("Principal URL is {}", href);
+        log::debug!("Principal URL is {}", principal_url.url());
 
         return Ok(principal_url);
     }
@@ -148,13 +315,28 @@ impl Client {
         let principal_url = self.get_principal().await?;
 
         let href = sub_request_and_extract_elem(&principal_url, HOMESET_BODY.into(), &["calendar-home-set", "href"]).await?;
-        let chs_url = self.resource.combine(&href);
+        let chs_url = principal_url.combine(&href);
         self.cached_replies.lock().unwrap().calendar_home_set = Some(chs_url.clone());
         log::debug!("Calendar home set URL is {:?}", href);
 
         Ok(chs_url)
     }
 
+    /// Return the address book home set URL, or fetch it from server if not known yet
+    async fn get_address_book_home_set(&self) -> Result<Resource, Box<dyn Error>> {
+        if let Some(h) = &self.cached_replies.lock().unwrap().address_book_home_set {
+            return Ok(h.clone());
+        }
+        let principal_url = self.get_principal().await?;
+
+        let href = sub_request_and_extract_elem(&principal_url, ADDRESSBOOK_HOMESET_BODY.into(), &["addressbook-home-set", "href"]).await?;
+        let abhs_url = principal_url.combine(&href);
+        self.cached_replies.lock().unwrap().address_book_home_set = Some(abhs_url.clone());
+        log::debug!("Address book home set URL is {:?}", href);
+
+        Ok(abhs_url)
+    }
+
     async fn populate_calendars(&self) -> Result<(), Box<dyn Error>> {
         let cal_home_set = self.get_cal_home_set().await?;
 
@@ -197,7 +379,7 @@ impl Client {
                 Some(h) => h.text(),
             };
 
-            let this_calendar_url = self.resource.combine(&calendar_href);
+            let this_calendar_url = cal_home_set.combine(&calendar_href);
 
             let supported_components = match crate::calendar::SupportedComponents::try_from(el_supported_comps.clone()) {
                 Err(err) => {
@@ -213,9 +395,41 @@ impl Client {
                         .and_then(|t| csscolorparser::parse(t).ok())
                 });
 
-            let this_calendar = RemoteCalendar::new(display_name, this_calendar_url, supported_components, this_calendar_color);
+            let description = find_elem(&rep, "calendar-description")
+                .map(|desc| desc.text())
+                .filter(|desc| desc.is_empty() == false);
+
+            let order = find_elem(&rep, "calendar-order")
+                .and_then(|order| order.text().trim().parse::<i32>().ok());
+
+            let privileges = match find_elem(&rep, "current-user-privilege-set") {
+                None => Privileges::default(),
+                Some(el) => Privileges::try_from(el.clone()).unwrap_or_else(|err| {
+                    log::warn!("Calendar {} has invalid privileges ({}). Assuming full access", display_name, err);
+                    Privileges::default()
+                }),
+            };
+
+            let supports_sync_collection = find_elem(&rep, "supported-report-set")
+                .map(|reports| find_elem(reports, "sync-collection").is_some())
+                .unwrap_or(false);
+
+            let mut this_calendar = RemoteCalendar::new(display_name, this_calendar_url, supported_components, this_calendar_color);
+            this_calendar.set_privileges(privileges);
+            this_calendar.set_description(description);
+            this_calendar.set_order(order);
+            this_calendar.set_quota(Quota::from_response(&rep));
+            this_calendar.set_supports_sync_collection(supports_sync_collection);
             log::info!("Found calendar {}", this_calendar.name());
-            calendars.insert(this_calendar.url().clone(), Arc::new(Mutex::new(this_calendar)));
+            calendars.insert(this_calendar.url().clone(), Arc::new(RwLock::new(this_calendar)));
+        }
+
+        if self.address_books_enabled {
+            match self.fetch_address_books().await {
+                Ok(address_books) => calendars.extend(address_books),
+                // Many CalDAV servers are not CardDAV servers
+                Err(err) => log::warn!("Unable to fetch address books: {}", err),
+            }
         }
 
         let mut replies = self.cached_replies.lock().unwrap();
@@ -223,11 +437,146 @@ impl Client {
         Ok(())
     }
 
+    async fn fetch_address_books(&self) -> Result<HashMap<Url, Arc<RwLock<RemoteCalendar>>>, Box<dyn Error>> {
+        let ab_home_set = self.get_address_book_home_set().await?;
+
+        let reps = sub_request_and_extract_elems(&ab_home_set, "PROPFIND", ADDRESSBOOK_BODY.to_string(), "response").await?;
+        let mut address_books = HashMap::new();
+        for rep in reps {
+            let display_name = find_elem(&rep, "displayname").map(|e| e.text()).unwrap_or("<no name>".to_string());
+            log::debug!("Considering address book {}", display_name);
+
+            // We filter out non-addressbook items (including the home set itself)
+            let is_address_book = find_elem(&rep, "resourcetype")
+                .map(|rt| rt.children().any(|resource_type| resource_type.name() == "addressbook"))
+                .unwrap_or(false);
+            if is_address_book == false {
+                continue;
+            }
+
+            let href = match find_elem(&rep, "href") {
+                None => {
+                    log::warn!("Address book {} has no URL! Ignoring it.", display_name);
+                    continue;
+                },
+                Some(h) => h.text(),
+            };
+
+            let description = find_elem(&rep, "addressbook-description")
+                .map(|desc| desc.text())
+                .filter(|desc| desc.is_empty() == false);
+
+            let privileges = match find_elem(&rep, "current-user-privilege-set") {
+                None => Privileges::default(),
+                Some(el) => Privileges::try_from(el.clone()).unwrap_or_else(|err| {
+                    log::warn!("Address book {} has invalid privileges ({}). Assuming full access", display_name, err);
+                    Privileges::default()
+                }),
+            };
+
+            let supports_sync_collection = find_elem(&rep, "supported-report-set")
+                .map(|reports| find_elem(reports, "sync-collection").is_some())
+                .unwrap_or(false);
+
+            let mut address_book = RemoteCalendar::new(display_name, ab_home_set.combine(&href), SupportedComponents::CONTACT, None);
+            address_book.set_privileges(privileges);
+            address_book.set_description(description);
+            address_book.set_quota(Quota::from_response(&rep));
+            address_book.set_supports_sync_collection(supports_sync_collection);
+            log::info!("Found address book {}", address_book.name());
+            address_books.insert(address_book.url().clone(), Arc::new(RwLock::new(address_book)));
+        }
+        Ok(address_books)
+    }
+
+    /// Create a calendar on the server (with a `MKCALENDAR` request), at a new URL in the calendar home set of the current user.
+    ///
+    /// See also [`CalDavSource::create_calendar`] to create a calendar at a given URL,
+    /// and [`Provider::create_calendar`](crate::provider::Provider::create_calendar) to create a calendar in a local cache, that will be created on the server at the next sync
+    pub async fn create_new_calendar(&mut self, name: String, color: Option<Color>, supported_components: SupportedComponents) -> Result<Arc<RwLock<RemoteCalendar>>, crate::Error> {
+        let url = self.new_calendar_url().await?;
+        self.create_calendar(url, name, supported_components, color).await
+    }
+
+    /// Create a CardDAV address book on the server (with an extended `MKCOL` request), at a new URL in the address book home set of the current user.
+    ///
+    /// See also [`CalDavSource::create_calendar`] with [`SupportedComponents::CONTACT`] to create an address book at a given URL
+    pub async fn create_new_address_book(&mut self, name: String) -> Result<Arc<RwLock<RemoteCalendar>>, crate::Error> {
+        let home_set = self.get_address_book_home_set().await?;
+        let url = random_collection_url(home_set.url())?;
+        self.create_calendar(url, name, SupportedComponents::CONTACT, None).await
+    }
+
+    /// The scheduling Inbox or Outbox collection of the current user (see [RFC 6638](https://datatracker.ietf.org/doc/html/rfc6638#section-2.2))
+    async fn get_schedule_collection(&self, name: &str) -> Result<Resource, Box<dyn Error>> {
+        let principal_url = self.get_principal().await?;
+        let href = sub_request_and_extract_elem(&principal_url, SCHEDULE_BODY.into(), &[name, "href"]).await
+            .map_err(|err| format!("The server does not support CalDAV scheduling ({})", err))?;
+        Ok(principal_url.combine(&href))
+    }
+
+    /// Send an iTIP message (e.g. an invitation, see [`crate::scheduling`]) to its recipients, through the scheduling Outbox of the current user.
+    ///
+    /// Returns whether the server could deliver the message, for every recipient
+    pub async fn send_scheduling_message(&self, message: &ItipMessage) -> Result<Vec<DeliveryStatus>, crate::Error> {
+        let outbox = self.get_schedule_collection("schedule-outbox-URL").await?;
+        let request = outbox.http()
+            .post(outbox.url().clone())
+            .header(CONTENT_TYPE, message.content_type())
+            .header("Originator", message.originator())
+            .header("Recipient", message.recipients().join(", "))
+            .body(message.content().to_string());
+        let response = outbox.send(request).await?;
+        if response.status().is_success() == false {
+            return Err(ServerError::from_response(response).await.into());
+        }
+
+        let root: Element = response.text().await?.parse()?;
+        let statuses = find_elems(&root, "response").iter()
+            .filter_map(|response| {
+                let recipient = find_elem(response, "recipient")?.text();
+                let request_status = find_elem(response, "request-status").map(|status| status.text()).unwrap_or_default();
+                Some(DeliveryStatus::new(recipient.trim().to_string(), request_status.trim().to_string()))
+            })
+            .collect();
+        Ok(statuses)
+    }
+
+    /// The scheduling Inbox of the current user, where the server delivers the iTIP messages that are sent to them (e.g. invitations, or replies to the invitations they have sent).
+    ///
+    /// Messages are items of this collection, that should be deleted once they have been processed
+    pub async fn schedule_inbox(&self) -> Result<RemoteCalendar, crate::Error> {
+        let inbox = self.get_schedule_collection("schedule-inbox-URL").await?;
+        Ok(RemoteCalendar::new("Inbox".to_string(), inbox, SupportedComponents::EVENT | SupportedComponents::TODO, None))
+    }
+
+    /// Ask the server how much storage space the user has used, and how much is still available in their calendar home set (see [RFC 4331](https://datatracker.ietf.org/doc/html/rfc4331)).
+    ///
+    /// Apps can use this to warn users before the server refuses uploads because they are over quota (see also [`RemoteCalendar::quota`] for the quota of a single calendar).
+    /// Servers that do not support quotas return a [`Quota`] with unknown values
+    pub async fn quota(&self) -> Result<Quota, crate::Error> {
+        let cal_home_set = self.get_cal_home_set().await?;
+        let reply = sub_request(&cal_home_set, "PROPFIND", QUOTA_BODY.to_string(), 0).await?;
+        let root: Element = reply.parse()?;
+        Ok(Quota::from_response(&root))
+    }
+
+    /// Ask the server when the owner of a calendar is busy, between `start` (included) and `end` (excluded).
+    ///
+    /// This issues a CalDAV `free-busy-query` REPORT (see [RFC 4791](https://datatracker.ietf.org/doc/html/rfc4791#section-7.10)).
+    /// The server only considers the events that make the time busy (e.g. not the transparent nor the cancelled ones)
+    pub async fn free_busy(&self, calendar_url: &Url, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<BusyPeriod>, crate::Error> {
+        let calendar = self.resource.combine(calendar_url.path());
+        let body = free_busy_body(&start, &end);
+        let reply = sub_request(&calendar, "REPORT", body, 1).await?;
+        crate::ical::parse_free_busy(&reply)
+    }
 }
 
-#[async_trait]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 impl CalDavSource<RemoteCalendar> for Client {
-    async fn get_calendars(&self) -> Result<HashMap<Url, Arc<Mutex<RemoteCalendar>>>, Box<dyn Error>> {
+    async fn get_calendars(&self) -> Result<HashMap<Url, Arc<RwLock<RemoteCalendar>>>, crate::Error> {
         self.populate_calendars().await?;
 
         match &self.cached_replies.lock().unwrap().calendars {
@@ -238,7 +587,7 @@ impl CalDavSource<RemoteCalendar> for Client {
         };
     }
 
-    async fn get_calendar(&self, url: &Url) -> Option<Arc<Mutex<RemoteCalendar>>> {
+    async fn get_calendar(&self, url: &Url) -> Option<Arc<RwLock<RemoteCalendar>>> {
         if let Err(err) = self.populate_calendars().await {
             log::warn!("Unable to fetch calendars: {}", err);
             return None;
@@ -251,7 +600,7 @@ impl CalDavSource<RemoteCalendar> for Client {
             .map(|cal| cal.clone())
     }
 
-    async fn create_calendar(&mut self, url: Url, name: String, supported_components: SupportedComponents, color: Option<Color>) -> Result<Arc<Mutex<RemoteCalendar>>, Box<dyn Error>> {
+    async fn create_calendar(&mut self, url: Url, name: String, supported_components: SupportedComponents, color: Option<Color>) -> Result<Arc<RwLock<RemoteCalendar>>, crate::Error> {
         self.populate_calendars().await?;
 
         match self.cached_replies.lock().unwrap().calendars.as_ref() {
@@ -263,15 +612,17 @@ impl CalDavSource<RemoteCalendar> for Client {
             },
         }
 
-        let creation_body = calendar_body(name, supported_components, color);
+        let (method, creation_body) = match supported_components.contains(SupportedComponents::CONTACT) {
+            true if supported_components != SupportedComponents::CONTACT => return Err("Address books cannot hold calendar items".into()),
+            true => ("MKCOL", address_book_body(name)),
+            false => ("MKCALENDAR", calendar_body(name, supported_components, color)),
+        };
 
-        let response = reqwest::Client::new()
-            .request(Method::from_bytes(b"MKCALENDAR").unwrap(), url.clone())
+        let request = self.resource.http()
+            .request(Method::from_bytes(method.as_bytes()).unwrap(), url.clone())
             .header(CONTENT_TYPE, "application/xml")
-            .basic_auth(self.resource.username(), Some(self.resource.password()))
-            .body(creation_body)
-            .send()
-            .await?;
+            .body(creation_body);
+        let response = self.resource.send(request).await?;
 
         let status = response.status();
         if status != StatusCode::CREATED {
@@ -280,6 +631,42 @@ impl CalDavSource<RemoteCalendar> for Client {
 
         self.get_calendar(&url).await.ok_or(format!("Unable to insert calendar {:?}", url).into())
     }
+
+    async fn delete_calendar(&mut self, url: &Url) -> Result<(), crate::Error> {
+        let request = self.resource.http()
+            .delete(url.clone());
+        let response = self.resource.send(request).await?;
+
+        if response.status().is_success() == false {
+            return Err(ServerError::from_response(response).await.into());
+        }
+
+        if let Some(cals) = self.cached_replies.lock().unwrap().calendars.as_mut() {
+            cals.remove(url);
+        }
+        Ok(())
+    }
+
+    /// Returns a random URL in the calendar home set
+    async fn new_calendar_url(&self) -> Result<Url, crate::Error> {
+        let home_set = self.get_cal_home_set().await?;
+        Ok(random_collection_url(home_set.url())?)
+    }
+
+    /// Sends the message through the scheduling Outbox of the current user
+    async fn send_scheduling_message(&self, message: &ItipMessage) -> Result<Vec<DeliveryStatus>, crate::Error> {
+        Client::send_scheduling_message(self, message).await
+    }
+}
+
+/// Returns a random collection URL in a home set
+fn random_collection_url(home_set: &Url) -> Result<Url, Box<dyn Error>> {
+    let mut home_set = home_set.clone();
+    if home_set.path().ends_with('/') == false {
+        home_set.set_path(&format!("{}/", home_set.path()));
+    }
+    let random = uuid::Uuid::new_v4().to_hyphenated().to_string();
+    Ok(home_set.join(&format!("{}/", random))?)
 }
 
 fn calendar_body(name: String, supported_components: SupportedComponents, color: Option<Color>) -> String {
@@ -300,8 +687,38 @@ fn calendar_body(name: String, supported_components: SupportedComponents, color:
             </A:set>
         </B:mkcalendar>
         "#,
-        name,
+        escape_xml(&name),
         color_property,
         supported_components.to_xml_string(),
     )
 }
+
+fn address_book_body(name: String) -> String {
+    // This is taken from https://tools.ietf.org/html/rfc6352#section-6.3.1
+    format!(r#"<?xml version="1.0" encoding="utf-8" ?>
+        <D:mkcol xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:carddav">
+            <D:set>
+                <D:prop>
+                    <D:resourcetype>
+                        <D:collection/>
+                        <C:addressbook/>
+                    </D:resourcetype>
+                    <D:displayname>{}</D:displayname>
+                </D:prop>
+            </D:set>
+        </D:mkcol>
+        "#,
+        escape_xml(&name),
+    )
+}
+
+fn free_busy_body(start: &DateTime<Utc>, end: &DateTime<Utc>) -> String {
+    format!(r#"<?xml version="1.0" encoding="utf-8" ?>
+        <C:free-busy-query xmlns:C="urn:ietf:params:xml:ns:caldav">
+            <C:time-range start="{}" end="{}"/>
+        </C:free-busy-query>
+        "#,
+        start.format("%Y%m%dT%H%M%SZ"),
+        end.format("%Y%m%dT%H%M%SZ"),
+    )
+}
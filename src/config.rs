@@ -10,3 +10,7 @@ pub static ORG_NAME: Lazy<Arc<Mutex<String>>> = Lazy::new(|| Arc::new(Mutex::new
 /// Part of the ProdID string that describes the product name (example of a ProdID string: `-//ABC Corporation//My Product//EN`).
 /// Feel free to override it when initing this library.
 pub static PRODUCT_NAME: Lazy<Arc<Mutex<String>>> = Lazy::new(|| Arc::new(Mutex::new("KitchenFridge".to_string())));
+
+/// Whether the property values that legacy producers encode with `ENCODING=QUOTED-PRINTABLE` (possibly in a non-UTF-8 `CHARSET`, such as latin-1) are decoded when parsing items.
+/// When this is disabled, these values are kept as they are.
+pub static DECODE_LEGACY_ENCODINGS: Lazy<Arc<Mutex<bool>>> = Lazy::new(|| Arc::new(Mutex::new(true)));
@@ -0,0 +1,238 @@
+//! Contacts (vCard items, as they are stored in CardDAV address books)
+
+use chrono::{DateTime, Utc};
+use ical::property::Property;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::item::{SyncStatus, Uid};
+use crate::calendar::CalendarUrl;
+
+/// A contact of an address book
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Contact {
+    /// The contact URL
+    url: Url,
+
+    /// The UID of the vCard
+    uid: Uid,
+
+    /// FN, i.e. the formatted name of the contact
+    full_name: String,
+
+    /// EMAIL addresses, the preferred one first
+    emails: Vec<String>,
+    /// TEL numbers, the preferred one first
+    phones: Vec<String>,
+    /// ORG, i.e. the organization (company, association...) this contact belongs to
+    organization: Option<String>,
+    /// The EMAIL and TEL properties as they have been parsed, so that their parameters (e.g. `TYPE=WORK`) are kept when the vCard is built again
+    #[serde(default)]
+    value_properties: Vec<Property>,
+
+    sync_status: SyncStatus,
+
+    /// The PRODID of the vCard
+    ical_prod_id: String,
+
+    /// vCards have no creation date, this is only known for contacts that have been created locally
+    creation_date: Option<DateTime<Utc>>,
+    /// REV, i.e. the last revision of this vCard
+    last_modified: DateTime<Utc>,
+
+    /// Extra parameters that have not been parsed from the vCard (because they're not supported (yet) by this crate).
+    /// They are needed to serialize this item into an equivalent vCard
+    extra_parameters: Vec<Property>,
+}
+
+impl Contact {
+    /// Create a brand new contact that is not on a server yet.
+    /// This will pick a new (random) ID.
+    pub fn new(full_name: String, parent_address_book_url: &Url) -> Self {
+        let mut new_url = CalendarUrl::from(parent_address_book_url.clone()).random_item_url();
+        new_url.set_path(&format!("{}.vcf", new_url.path()));
+        let new_sync_status = SyncStatus::NotSynced;
+        let new_uid = Uid::random();
+        let new_creation_date = Some(Utc::now());
+        let new_last_modified = Utc::now();
+        let ical_prod_id = crate::ical::default_prod_id();
+        let extra_parameters = Vec::new();
+        Self::new_with_parameters(
+            full_name,
+            new_uid,
+            new_url,
+            Vec::new(),
+            Vec::new(),
+            None,
+            new_sync_status,
+            new_creation_date,
+            new_last_modified,
+            ical_prod_id,
+            extra_parameters,
+        )
+    }
+
+    /// Create a new Contact instance, that may be synced on the server already
+    pub fn new_with_parameters(
+        full_name: String,
+        uid: Uid,
+        url: Url,
+        emails: Vec<String>,
+        phones: Vec<String>,
+        organization: Option<String>,
+        sync_status: SyncStatus,
+        creation_date: Option<DateTime<Utc>>,
+        last_modified: DateTime<Utc>,
+        ical_prod_id: String,
+        extra_parameters: Vec<Property>,
+    ) -> Self {
+        Self {
+            url,
+            uid,
+            full_name,
+            emails,
+            phones,
+            organization,
+            value_properties: Vec::new(),
+            sync_status,
+            ical_prod_id,
+            creation_date,
+            last_modified,
+            extra_parameters,
+        }
+    }
+
+    /// Remember the EMAIL and TEL properties of the vCard this contact has been parsed from
+    pub(crate) fn with_value_properties(mut self, properties: Vec<Property>) -> Self {
+        self.value_properties = properties;
+        self
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+    pub fn uid(&self) -> &Uid {
+        &self.uid
+    }
+    /// The formatted name of this contact
+    pub fn name(&self) -> &str {
+        &self.full_name
+    }
+    pub fn emails(&self) -> &[String] {
+        &self.emails
+    }
+    pub fn phones(&self) -> &[String] {
+        &self.phones
+    }
+    pub fn organization(&self) -> Option<&str> {
+        self.organization.as_deref()
+    }
+    pub fn ical_prod_id(&self) -> &str {
+        &self.ical_prod_id
+    }
+    pub fn sync_status(&self) -> &SyncStatus {
+        &self.sync_status
+    }
+    pub fn last_modified(&self) -> &DateTime<Utc> {
+        &self.last_modified
+    }
+    pub fn creation_date(&self) -> Option<&DateTime<Utc>> {
+        self.creation_date.as_ref()
+    }
+    pub fn extra_parameters(&self) -> &[Property] {
+        &self.extra_parameters
+    }
+    /// The original EMAIL and TEL properties (see [`Self::emails`] and [`Self::phones`] for their current values)
+    pub(crate) fn value_properties(&self) -> &[Property] {
+        &self.value_properties
+    }
+
+    #[cfg(any(test, feature = "integration_tests"))]
+    pub fn has_same_observable_content_as(&self, other: &Contact) -> bool {
+        self.url == other.url
+        && self.uid == other.uid
+        && self.full_name == other.full_name
+        && self.emails == other.emails
+        && self.phones == other.phones
+        && self.organization == other.organization
+        // sync status must be the same variant, but we ignore its embedded version tag
+        && std::mem::discriminant(&self.sync_status) == std::mem::discriminant(&other.sync_status)
+        // last modified dates are ignored (they are not totally mocked in integration tests)
+    }
+
+    pub fn set_sync_status(&mut self, new_status: SyncStatus) {
+        self.sync_status = new_status;
+    }
+
+    /// Change the URL of this item, e.g. because it has been moved on the server
+    pub(crate) fn set_url(&mut self, new_url: Url) {
+        self.url = new_url;
+    }
+
+    /// Change the UID of this item, e.g. to make it a distinct copy
+    pub(crate) fn set_uid(&mut self, new_uid: Uid) {
+        self.uid = new_uid;
+    }
+
+    fn update_sync_status(&mut self) {
+        match &self.sync_status {
+            SyncStatus::NotSynced => return,
+            SyncStatus::LocallyModified(_) => return,
+            SyncStatus::Synced(prev_vt) => {
+                self.sync_status = SyncStatus::LocallyModified(prev_vt.clone());
+            }
+            SyncStatus::LocallyDeleted(_) => {
+                log::warn!("Trying to update an item that has previously been deleted. These changes will probably be ignored at next sync.");
+                return;
+            }
+        }
+    }
+
+    pub(crate) fn update_last_modified(&mut self) {
+        self.last_modified = Utc::now();
+    }
+
+    /// Drop what a deleted contact does not need any more (see [`Item::drop_content`](crate::item::Item::drop_content))
+    pub(crate) fn drop_content(&mut self) -> bool {
+        let had_content = self.emails.is_empty() == false || self.phones.is_empty() == false || self.organization.is_some()
+            || self.value_properties.is_empty() == false || self.extra_parameters.is_empty() == false;
+        self.emails.clear();
+        self.phones.clear();
+        self.organization = None;
+        self.value_properties.clear();
+        self.extra_parameters.clear();
+        had_content
+    }
+
+    /// Rename a contact.
+    /// This updates its "last modified" field
+    pub fn set_name(&mut self, new_name: String) {
+        self.update_sync_status();
+        self.update_last_modified();
+        self.full_name = new_name;
+    }
+
+    /// Change the email addresses of a contact (the preferred one first).
+    /// This updates its "last modified" field
+    pub fn set_emails(&mut self, new_emails: Vec<String>) {
+        self.update_sync_status();
+        self.update_last_modified();
+        self.emails = new_emails;
+    }
+
+    /// Change the phone numbers of a contact (the preferred one first).
+    /// This updates its "last modified" field
+    pub fn set_phones(&mut self, new_phones: Vec<String>) {
+        self.update_sync_status();
+        self.update_last_modified();
+        self.phones = new_phones;
+    }
+
+    /// Change the organization of a contact.
+    /// This updates its "last modified" field
+    pub fn set_organization(&mut self, new_organization: Option<String>) {
+        self.update_sync_status();
+        self.update_last_modified();
+        self.organization = new_organization;
+    }
+}
@@ -0,0 +1,103 @@
+//! DATE-TIME values, as they are written in iCal files
+//!
+//! Most of this crate handles dates as instants in UTC. This module keeps track of how they were written,
+//! which matters for "floating" date-times (e.g. "every day at 9:00, wherever I am"), that do not refer to any timezone.
+
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+/// A DATE-TIME value (RFC 5545 §3.3.5)
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IcalDateTime {
+    /// A date-time written in UTC, e.g. `19980119T070000Z`
+    Utc(DateTime<Utc>),
+    /// A date-time written in the local time of a timezone, e.g. `TZID=America/New_York:19980119T020000`
+    Local { date_time: NaiveDateTime, tzid: String },
+    /// A date-time written without any timezone, e.g. `19980118T230000`. \
+    /// It means the same wall-clock time, whatever the timezone of the user
+    Floating(NaiveDateTime),
+}
+
+impl IcalDateTime {
+    /// Parse a DATE-TIME value, and the TZID parameter of its property (if any)
+    pub fn parse(value: &str, tzid: Option<&str>) -> Option<Self> {
+        if let Some(utc) = value.strip_suffix('Z') {
+            return Utc.datetime_from_str(utc, "%Y%m%dT%H%M%S").ok().map(IcalDateTime::Utc);
+        }
+        let date_time = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+        match tzid {
+            Some(tzid) => Some(IcalDateTime::Local { date_time, tzid: tzid.to_string() }),
+            None => Some(IcalDateTime::Floating(date_time)),
+        }
+    }
+
+    /// The value of this date-time, as written in iCal files (the TZID parameter is given by [`Self::tzid`])
+    pub fn value(&self) -> String {
+        match self {
+            IcalDateTime::Utc(dt) => dt.format("%Y%m%dT%H%M%SZ").to_string(),
+            IcalDateTime::Local { date_time, .. } => date_time.format("%Y%m%dT%H%M%S").to_string(),
+            IcalDateTime::Floating(date_time) => date_time.format("%Y%m%dT%H%M%S").to_string(),
+        }
+    }
+
+    pub fn tzid(&self) -> Option<&str> {
+        match self {
+            IcalDateTime::Local { tzid, .. } => Some(tzid),
+            _ => None,
+        }
+    }
+
+    pub fn is_floating(&self) -> bool {
+        matches!(self, IcalDateTime::Floating(_))
+    }
+
+    /// The instant this refers to, for a user that is in `timezone`. \
+    /// Only floating date-times depend on it (as well as local times whose TZID is not an IANA timezone name)
+    pub fn resolve(&self, timezone: &Tz) -> DateTime<Utc> {
+        match self {
+            IcalDateTime::Utc(dt) => *dt,
+            IcalDateTime::Local { date_time, tzid } => {
+                let tz = crate::ical::lookup_timezone(tzid).unwrap_or(*timezone);
+                local_to_utc(date_time, &tz)
+            },
+            IcalDateTime::Floating(date_time) => local_to_utc(date_time, timezone),
+        }
+    }
+
+    /// The instant this refers to, reading floating date-times (and unknown TZIDs) as UTC, as the rest of this crate does
+    pub fn to_utc(&self) -> DateTime<Utc> {
+        self.resolve(&chrono_tz::UTC)
+    }
+}
+
+fn local_to_utc(local: &NaiveDateTime, tz: &Tz) -> DateTime<Utc> {
+    crate::recurrence::to_utc(local, Some(tz)).unwrap_or_else(|| Utc.from_utc_datetime(local))
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_floating_date_times() {
+        let floating = IcalDateTime::parse("20210322T090000", None).unwrap();
+        assert!(floating.is_floating());
+        assert_eq!(floating.value(), "20210322T090000");
+        assert_eq!(floating.to_utc(), Utc.ymd(2021, 3, 22).and_hms(9, 0, 0));
+        assert_eq!(floating.resolve(&chrono_tz::Europe::Paris), Utc.ymd(2021, 3, 22).and_hms(8, 0, 0));
+        assert_eq!(floating.resolve(&chrono_tz::America::New_York), Utc.ymd(2021, 3, 22).and_hms(13, 0, 0));
+
+        let utc = IcalDateTime::parse("20210322T090000Z", Some("Europe/Paris")).unwrap();
+        assert_eq!(utc, IcalDateTime::Utc(Utc.ymd(2021, 3, 22).and_hms(9, 0, 0)));
+        assert_eq!(utc.resolve(&chrono_tz::America::New_York), Utc.ymd(2021, 3, 22).and_hms(9, 0, 0));
+
+        let local = IcalDateTime::parse("20210322T090000", Some("Europe/Paris")).unwrap();
+        assert_eq!(local.tzid(), Some("Europe/Paris"));
+        assert_eq!(local.resolve(&chrono_tz::America::New_York), Utc.ymd(2021, 3, 22).and_hms(8, 0, 0));
+
+        assert_eq!(IcalDateTime::parse("20210322", None), None);
+    }
+}
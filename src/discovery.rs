@@ -0,0 +1,252 @@
+//! Locating the CalDAV service of a server (see [RFC 6764](https://datatracker.ietf.org/doc/html/rfc6764)).
+//!
+//! Users usually only know the address of their server (e.g. `https://example.com`), not where its CalDAV service is (e.g. `https://example.com/remote.php/dav`).
+//! [`Client`](crate::client::Client)s find it by themselves: when the URL they have been given does not tell the principal of the user, they follow the redirections of `/.well-known/caldav`.
+//!
+//! With the `dns_discovery` feature, the server itself can be found from an email address (see [`find_service`]), using the `_caldavs._tcp` SRV and TXT records of its domain.
+
+use std::error::Error;
+
+use reqwest::header::{CONTENT_TYPE, LOCATION};
+#[cfg(not(target_arch = "wasm32"))]
+use reqwest::redirect::Policy;
+use url::Url;
+
+use crate::resource::Resource;
+
+/// The path servers should redirect to their CalDAV service (see [RFC 6764, section 5](https://datatracker.ietf.org/doc/html/rfc6764#section-5))
+pub const WELL_KNOWN_PATH: &str = "/.well-known/caldav";
+
+/// How many redirections are followed at most
+const MAX_REDIRECTS: usize = 10;
+
+/// Follow the redirections of the well-known CalDAV URL of the server of `resource`, and return where they lead (the "context path" of the CalDAV service).
+///
+/// This returns `None` if the server does not know this well-known URL.
+/// The credentials of `resource` are only sent to its own origin (see [`is_same_origin`]): redirections to other servers are followed anonymously.
+/// Redirections have to be followed by hand, since HTTP clients replace `PROPFIND` requests with `GET` ones when they are redirected
+/// (web browsers follow them by themselves, and keep the method of the request)
+pub(crate) async fn well_known_context_path(resource: &Resource, propfind_body: &str) -> Result<Option<Url>, Box<dyn Error>> {
+    let builder = resource.http_builder()?;
+    #[cfg(not(target_arch = "wasm32"))]
+    let builder = builder.redirect(Policy::none());
+    let client = builder.build()?;
+
+    let mut url = resource.url().join(WELL_KNOWN_PATH)?;
+    for _ in 0..MAX_REDIRECTS {
+        let request = client
+            .request("PROPFIND".parse().expect("invalid method name"), url.clone())
+            .header("Depth", 0)
+            .header(CONTENT_TYPE, "application/xml")
+            .body(propfind_body.to_string());
+        let response = match is_same_origin(&url, resource.url()) {
+            true => resource.send(request).await?,
+            false => resource.send_without_credentials(request).await?,
+        };
+
+        let status = response.status();
+        if status.is_redirection() {
+            let location = response.headers().get(LOCATION)
+                .ok_or_else(|| format!("{} has been redirected without any location", url))?
+                .to_str()?;
+            let target = url.join(location)?;
+            log::debug!("{} redirects to {}", url, target);
+            url = target;
+            continue;
+        }
+        if status.is_success() {
+            return Ok(Some(url));
+        }
+        log::debug!("{} is not available (HTTP {})", url, status);
+        return Ok(None);
+    }
+
+    Err(format!("Too many redirections when looking for the CalDAV service at {}", WELL_KNOWN_PATH).into())
+}
+
+/// Whether two URLs have the same origin (scheme, host and port), i.e. whether credentials that are meant for one can be sent to the other.
+///
+/// Note that a redirection from HTTPS to plain HTTP changes the origin
+pub(crate) fn is_same_origin(url: &Url, other: &Url) -> bool {
+    url.origin() == other.origin()
+}
+
+/// The SRV record of a CalDAV service
+#[cfg(feature = "dns_discovery")]
+#[derive(Clone, Debug, PartialEq)]
+struct SrvRecord {
+    priority: u16,
+    weight: u16,
+    port: u16,
+    target: String,
+}
+
+/// Find the URL of the CalDAV service of the domain of an email address (e.g. `user@example.com`), using DNS SRV and TXT records (see [RFC 6764, section 3](https://datatracker.ietf.org/doc/html/rfc6764#section-3)).
+///
+/// Only secure (`_caldavs._tcp`) services are looked for. When the domain has no such record, this falls back to `https://<domain>/`, whose well-known URL is then used by [`Client`](crate::client::Client)s.
+/// The returned URL can be given to [`Client::new`](crate::client::Client::new), or see [`Client::from_email`](crate::client::Client::from_email)
+#[cfg(feature = "dns_discovery")]
+pub async fn find_service(email: &str) -> Result<Url, Box<dyn Error>> {
+    use trust_dns_resolver::TokioAsyncResolver;
+
+    let domain = domain_of(email).ok_or_else(|| format!("{} is not an email address", email))?;
+    let resolver = TokioAsyncResolver::tokio_from_system_conf()?;
+    let service_name = format!("_caldavs._tcp.{}.", domain);
+
+    let records: Vec<SrvRecord> = match resolver.srv_lookup(service_name.as_str()).await {
+        Err(err) => {
+            log::debug!("No SRV record for {} ({})", service_name, err);
+            Vec::new()
+        },
+        Ok(lookup) => lookup.iter()
+            .map(|srv| SrvRecord {
+                priority: srv.priority(),
+                weight: srv.weight(),
+                port: srv.port(),
+                target: srv.target().to_utf8(),
+            })
+            .collect(),
+    };
+
+    let record = match preferred_record(&records) {
+        None => {
+            log::info!("No CalDAV service is advertised for {}, falling back to https://{}/", domain, domain);
+            return Ok(Url::parse(&format!("https://{}/", domain))?);
+        },
+        Some(record) => record,
+    };
+    // A target of "." means the service is decidedly not available
+    if record.target == "." {
+        return Err(format!("{} does not provide any CalDAV service", domain).into());
+    }
+
+    let texts: Vec<String> = match resolver.txt_lookup(service_name.as_str()).await {
+        Err(err) => {
+            log::debug!("No TXT record for {} ({})", service_name, err);
+            Vec::new()
+        },
+        Ok(lookup) => lookup.iter()
+            .map(|txt| txt.txt_data().iter().map(|chunk| String::from_utf8_lossy(chunk)).collect())
+            .collect(),
+    };
+
+    service_url(&record.target, record.port, context_path(&texts).as_deref())
+}
+
+/// The domain of an email address
+#[cfg(feature = "dns_discovery")]
+fn domain_of(email: &str) -> Option<&str> {
+    match email.rsplit_once('@') {
+        Some((user, domain)) if user.is_empty() == false && domain.is_empty() == false => Some(domain),
+        _ => None,
+    }
+}
+
+/// The record clients should use, among the ones of a service. \
+/// This is the one with the lowest priority and the highest weight (rather than a random one among those that share the lowest priority, since one server is enough for a client)
+#[cfg(feature = "dns_discovery")]
+fn preferred_record(records: &[SrvRecord]) -> Option<&SrvRecord> {
+    records.iter()
+        .min_by(|a, b| a.priority.cmp(&b.priority).then(b.weight.cmp(&a.weight)))
+}
+
+/// The context path that is advertised in the TXT records of a service (e.g. `path=/dav`)
+#[cfg(feature = "dns_discovery")]
+fn context_path(texts: &[String]) -> Option<String> {
+    texts.iter()
+        .find_map(|text| text.strip_prefix("path="))
+        .map(|path| path.to_string())
+}
+
+#[cfg(feature = "dns_discovery")]
+fn service_url(target: &str, port: u16, path: Option<&str>) -> Result<Url, Box<dyn Error>> {
+    let host = target.trim_end_matches('.');
+    let path = path.unwrap_or("/");
+    let url = match port {
+        443 => format!("https://{}{}", host, path),
+        port => format!("https://{}:{}{}", host, port, path),
+    };
+    Ok(Url::parse(&url)?)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::{Arc, Mutex};
+    use async_trait::async_trait;
+    use reqwest::{Request, Response};
+    use reqwest::header::AUTHORIZATION;
+
+    use crate::transport::HttpTransport;
+
+    /// A server that redirects its well-known URL to another server, and records whether requests carry credentials
+    struct RedirectingServer {
+        target: &'static str,
+        received: Mutex<Vec<(String, bool)>>,
+    }
+
+    #[async_trait]
+    impl HttpTransport for RedirectingServer {
+        async fn execute(&self, request: Request) -> Result<Response, Box<dyn Error>> {
+            let authorized = request.headers().contains_key(AUTHORIZATION);
+            self.received.lock().unwrap().push((request.url().to_string(), authorized));
+            let response = match request.url().path() {
+                WELL_KNOWN_PATH => http::Response::builder().status(301).header(LOCATION, self.target).body("")?,
+                "/caldav/" => http::Response::builder().status(301).header(LOCATION, "/dav/").body("")?,
+                _ => http::Response::builder().status(207).body("")?,
+            };
+            Ok(Response::from(response))
+        }
+    }
+
+    async fn follow_well_known(target: &'static str) -> (Option<Url>, Vec<(String, bool)>) {
+        let server = Arc::new(RedirectingServer { target, received: Mutex::new(Vec::new()) });
+        let mut resource = Resource::new("https://example.com/".parse().unwrap(), "john".to_string(), "secret".to_string());
+        resource.set_transport(Some(server.clone()));
+        let context_path = well_known_context_path(&resource, "").await.unwrap();
+        let received = server.received.lock().unwrap().clone();
+        (context_path, received)
+    }
+
+    #[tokio::test]
+    async fn test_credentials_are_not_sent_to_other_origins() {
+        // Redirections on the same server carry the credentials
+        let (context_path, received) = follow_well_known("https://example.com/caldav/").await;
+        assert_eq!(context_path.unwrap().as_str(), "https://example.com/dav/");
+        assert!(received.iter().all(|(_url, authorized)| *authorized));
+
+        // ...but not the ones to another server, nor the ones that downgrade to plain HTTP
+        for target in &["https://evil.example.net/dav/", "http://example.com/dav/"] {
+            let (context_path, received) = follow_well_known(target).await;
+            assert_eq!(context_path.unwrap().as_str(), *target);
+            assert_eq!(received, vec![
+                ("https://example.com/.well-known/caldav".to_string(), true),
+                (target.to_string(), false),
+            ]);
+        }
+
+        assert!(is_same_origin(&"https://example.com/dav/".parse().unwrap(), &"https://example.com:443/".parse().unwrap()));
+        assert!(is_same_origin(&"http://example.com/".parse().unwrap(), &"https://example.com/".parse().unwrap()) == false);
+    }
+
+    #[test]
+    #[cfg(feature = "dns_discovery")]
+    fn test_srv_discovery() {
+        assert_eq!(domain_of("john@example.com"), Some("example.com"));
+        assert_eq!(domain_of("example.com"), None);
+        assert_eq!(domain_of("john@"), None);
+
+        let record = |priority, weight, target: &str| SrvRecord { priority, weight, port: 443, target: target.to_string() };
+        let records = vec![record(10, 0, "backup.example.com."), record(0, 5, "light.example.com."), record(0, 60, "main.example.com.")];
+        assert_eq!(preferred_record(&records).unwrap().target, "main.example.com.");
+        assert!(preferred_record(&[]).is_none());
+
+        let texts = vec!["v=1".to_string(), "path=/remote.php/dav".to_string()];
+        assert_eq!(context_path(&texts).as_deref(), Some("/remote.php/dav"));
+        assert_eq!(service_url("main.example.com.", 443, context_path(&texts).as_deref()).unwrap().as_str(), "https://main.example.com/remote.php/dav");
+        assert_eq!(service_url("main.example.com.", 8443, None).unwrap().as_str(), "https://main.example.com:8443/");
+    }
+}
@@ -0,0 +1,476 @@
+//! Errors of this crate (see [`Error`]), and errors that are reported by CalDAV servers
+
+use std::fmt::{Display, Formatter};
+
+use chrono::{DateTime, Utc};
+use minidom::Element;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::calendar::Privileges;
+
+/// The errors of this crate.
+///
+/// The traits of this crate (e.g. [`BaseCalendar`](crate::traits::BaseCalendar)) return them as well. Implementors can turn their own errors into [`Error::Other`] (or into any more specific variant),
+/// and boxed errors can be turned into an `Error` with [`Error::from`], which recovers the errors this crate knows about (e.g. a [`ServerError`] becomes [`Error::Http`])
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Some iCal or vCard data is invalid
+    #[error("Invalid iCal or vCard data: {0}")]
+    Parse(#[source] Box<dyn std::error::Error + Send + Sync>),
+    /// The server has replied with an error
+    #[error("{source}")]
+    Http { status: u16, #[source] source: ServerError },
+    /// The server has refused the credentials, or they could not be obtained (e.g. an OAuth2 token could not be refreshed)
+    #[error("Authentication failed: {0}")]
+    Auth(#[source] Box<dyn std::error::Error + Send + Sync>),
+    /// The server has refused to overwrite an item, because it has been modified on the server since its version tag was known
+    #[error("Item {url} has been modified on the server in the meantime")]
+    Conflict { url: Url, #[source] source: ServerError },
+    /// The server could not be reached, or the connection has been interrupted
+    #[error("Network error: {0}")]
+    Network(#[from] reqwest::Error),
+    #[error(transparent)]
+    Offline(#[from] OfflineError),
+    #[error(transparent)]
+    ReadOnly(#[from] ReadOnlyError),
+    /// The local storage (e.g. the folder of a [`Cache`](crate::cache::Cache)) could not be read or written
+    #[error("Storage error: {0}")]
+    Storage(#[source] Box<dyn std::error::Error + Send + Sync>),
+    #[error("Invalid URL: {0}")]
+    InvalidUrl(#[from] url::ParseError),
+    /// An item or a calendar does not exist
+    #[error("{0} does not exist")]
+    NotFound(Url),
+    /// The operation does not make sense in the current state (e.g. completing a task that still has open subtasks)
+    #[error("{0}")]
+    InvalidOperation(String),
+    /// A sync has been cancelled (see [`CancellationToken`](crate::provider::sync_progress::CancellationToken))
+    #[error("The sync has been cancelled")]
+    Cancelled,
+    /// Any other error
+    #[error(transparent)]
+    Other(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl From<ServerError> for Error {
+    fn from(err: ServerError) -> Self {
+        match err.status() {
+            401 => Error::Auth(Box::new(err)),
+            status => Error::Http { status, source: err },
+        }
+    }
+}
+
+impl From<CancelledError> for Error {
+    fn from(_: CancelledError) -> Self {
+        Error::Cancelled
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Storage(Box::new(err))
+    }
+}
+
+impl From<minidom::Error> for Error {
+    fn from(err: minidom::Error) -> Self {
+        Error::Parse(Box::new(err))
+    }
+}
+
+impl From<&str> for Error {
+    fn from(message: &str) -> Self {
+        Error::Other(message.into())
+    }
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error::Other(message.into())
+    }
+}
+
+/// Returns early with the errors of this crate that may have been boxed along the way
+macro_rules! recover_known_errors {
+    ($err:expr) => {{
+        let err = match $err.downcast::<Error>() {
+            Ok(err) => return *err,
+            Err(err) => err,
+        };
+        let err = match err.downcast::<ServerError>() {
+            Ok(err) => return Error::from(*err),
+            Err(err) => err,
+        };
+        let err = match err.downcast::<OfflineError>() {
+            Ok(err) => return Error::Offline(*err),
+            Err(err) => err,
+        };
+        let err = match err.downcast::<ReadOnlyError>() {
+            Ok(err) => return Error::ReadOnly(*err),
+            Err(err) => err,
+        };
+        let err = match err.downcast::<CancelledError>() {
+            Ok(_) => return Error::Cancelled,
+            Err(err) => err,
+        };
+        let err = match err.downcast::<reqwest::Error>() {
+            Ok(err) => return Error::Network(*err),
+            Err(err) => err,
+        };
+        let err = match err.downcast::<url::ParseError>() {
+            Ok(err) => return Error::InvalidUrl(*err),
+            Err(err) => err,
+        };
+        match err.downcast::<std::io::Error>() {
+            Ok(err) => return Error::from(*err),
+            Err(err) => err,
+        }
+    }};
+}
+
+impl From<Box<dyn std::error::Error>> for Error {
+    /// Recover the errors this crate knows about, that have been boxed along the way (e.g. by the traits of this crate)
+    fn from(err: Box<dyn std::error::Error>) -> Self {
+        let err = recover_known_errors!(err);
+        Error::Other(shareable(err))
+    }
+}
+
+impl From<Box<dyn std::error::Error + Send + Sync>> for Error {
+    /// Recover the errors this crate knows about, that have been boxed along the way
+    fn from(err: Box<dyn std::error::Error + Send + Sync>) -> Self {
+        let err = recover_known_errors!(err);
+        Error::Other(err)
+    }
+}
+
+/// Turns a boxed error into one that can be sent across threads.
+///
+/// A `Box<dyn Error>` may hold anything that cannot be sent, so it is replaced by a copy of its message and of the messages of its sources (see [`SharedError`])
+pub(crate) fn shareable(err: Box<dyn std::error::Error>) -> Box<dyn std::error::Error + Send + Sync> {
+    Box::new(SharedError::new(&*err))
+}
+
+/// A copy of an error that can be sent across threads: its message, and a copy of its source (if any), so that [`source`](std::error::Error::source) chains are kept
+#[derive(Debug)]
+pub(crate) struct SharedError {
+    message: String,
+    source: Option<Box<SharedError>>,
+}
+
+impl SharedError {
+    fn new(err: &dyn std::error::Error) -> Self {
+        Self {
+            message: err.to_string(),
+            source: err.source().map(|source| Box::new(Self::new(source))),
+        }
+    }
+}
+
+impl Display for SharedError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for SharedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source.as_deref().map(|source| source as &(dyn std::error::Error + 'static))
+    }
+}
+
+
+
+/// What a CalDAV server complained about
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ServerErrorKind {
+    /// HTTP 507: the server is full, or the user has exceeded their quota
+    InsufficientStorage,
+    /// A WebDAV or CalDAV precondition has failed (e.g. `valid-calendar-data` or `max-resource-size`, see [RFC 4791](https://datatracker.ietf.org/doc/html/rfc4791#section-5.3.2.1)).
+    /// This contains the name of the failed precondition
+    Precondition(String),
+    /// Any other unexpected HTTP status
+    Other,
+}
+
+/// An error reply from a CalDAV server, with the explanation the server may have given
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ServerError {
+    status: u16,
+    kind: ServerErrorKind,
+    /// The human-readable message that comes with the reply (e.g. a `<s:message>` in SabreDAV-based servers such as Nextcloud)
+    message: Option<String>,
+}
+
+impl ServerError {
+    /// Build an error from an HTTP status code and the body of the reply
+    pub fn new(status: u16, body: &str) -> Self {
+        let mut kind = match status {
+            507 => ServerErrorKind::InsufficientStorage,
+            _ => ServerErrorKind::Other,
+        };
+        let mut message = None;
+
+        if let Ok(root) = body.parse::<Element>() {
+            if root.name() == "error" {
+                for child in root.children() {
+                    match child.name() {
+                        // SabreDAV-specific elements
+                        "message" => message = Some(child.text()).filter(|m| m.is_empty() == false),
+                        "exception" | "sabredav-version" | "file" | "line" | "code" | "header" => (),
+                        // Anything else is the name of a failed precondition
+                        other => if kind == ServerErrorKind::Other {
+                            kind = ServerErrorKind::Precondition(other.to_string());
+                        },
+                    }
+                }
+            }
+        }
+
+        Self { status, kind, message }
+    }
+
+    /// Build an error from a (failed) HTTP response. This consumes the response to read its body
+    pub(crate) async fn from_response(response: reqwest::Response) -> Self {
+        let status = response.status().as_u16();
+        let body = response.text().await.unwrap_or_default();
+        Self::new(status, &body)
+    }
+
+    pub fn status(&self) -> u16 { self.status }
+    pub fn kind(&self) -> &ServerErrorKind { &self.kind }
+    pub fn message(&self) -> Option<&str> { self.message.as_deref() }
+
+    /// Whether the server has refused a conditional request (HTTP 412), i.e. the item has been changed on the server since its version tag was known
+    pub fn is_edit_conflict(&self) -> bool {
+        self.status == 412
+    }
+
+    /// Whether sending the very same request again is pointless (e.g. the server refuses the content of an item).
+    ///
+    /// Items that are refused because of such errors need the user's attention, they should not be blindly retried at every sync
+    pub fn is_permanent(&self) -> bool {
+        if self.is_edit_conflict() {
+            // The same request will fail again, but the conflict can be resolved by the sync, without the user's attention
+            return false;
+        }
+        match self.kind {
+            ServerErrorKind::InsufficientStorage => true,
+            ServerErrorKind::Precondition(_) => true,
+            ServerErrorKind::Other => matches!(self.status, 400 | 403 | 413 | 415 | 422),
+        }
+    }
+}
+
+impl Display for ServerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            ServerErrorKind::InsufficientStorage => write!(f, "Insufficient storage on the server (HTTP {})", self.status)?,
+            ServerErrorKind::Precondition(name) => write!(f, "Server precondition {} failed (HTTP {})", name, self.status)?,
+            ServerErrorKind::Other => write!(f, "Unexpected HTTP status code {}", self.status)?,
+        }
+        if let Some(message) = &self.message {
+            write!(f, ": {}", message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ServerError {}
+
+
+
+/// The server could not be reached (e.g. because there is no network connection), while some data had to be downloaded from it
+#[derive(Clone, Debug)]
+pub struct OfflineError {
+    item_url: Url,
+    cause: String,
+}
+
+impl OfflineError {
+    pub fn new(item_url: Url, cause: String) -> Self {
+        Self { item_url, cause }
+    }
+
+    /// The item that could not be downloaded
+    pub fn item_url(&self) -> &Url { &self.item_url }
+
+    /// Whether an error means that the server could not be reached at all (rather than it replied with an error)
+    pub(crate) fn is_unreachable(err: &Error) -> bool {
+        match err {
+            Error::Network(err) => err.is_connect() || err.is_timeout(),
+            _ => false,
+        }
+    }
+}
+
+impl Display for OfflineError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unable to download {}, the server is unreachable ({})", self.item_url, self.cause)
+    }
+}
+
+impl std::error::Error for OfflineError {}
+
+
+
+/// A change has not been pushed to the server, because the current user is not allowed to make it in this calendar (see [`crate::traits::BaseCalendar::privileges`])
+#[derive(Clone, Debug)]
+pub struct ReadOnlyError {
+    calendar_url: Url,
+    missing: Privileges,
+}
+
+impl ReadOnlyError {
+    pub fn new(calendar_url: Url, missing: Privileges) -> Self {
+        Self { calendar_url, missing }
+    }
+
+    pub fn calendar_url(&self) -> &Url { &self.calendar_url }
+    /// The privileges the change would have needed
+    pub fn missing(&self) -> Privileges { self.missing }
+}
+
+impl Display for ReadOnlyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Calendar {} is read-only for the current user (missing privileges: {:?})", self.calendar_url, self.missing)
+    }
+}
+
+impl std::error::Error for ReadOnlyError {}
+
+
+
+/// The error that cancelled syncs stop with (see [`CancellationToken`](crate::provider::sync_progress::CancellationToken))
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CancelledError;
+
+impl Display for CancelledError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The sync has been cancelled")
+    }
+}
+
+impl std::error::Error for CancelledError {}
+
+
+
+/// Describes why an item has been refused by the server.
+///
+/// Such an item will not be pushed again until it is locally modified (see [`crate::traits::CompleteCalendar::mark_as_rejected`])
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Rejection {
+    error: ServerError,
+    /// The `last_modified` date of the item when it was refused
+    item_last_modified: DateTime<Utc>,
+}
+
+impl Rejection {
+    pub fn new(error: ServerError, item_last_modified: DateTime<Utc>) -> Self {
+        Self { error, item_last_modified }
+    }
+
+    pub fn error(&self) -> &ServerError { &self.error }
+    pub fn item_last_modified(&self) -> &DateTime<Utc> { &self.item_last_modified }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sabredav_error() {
+        let body = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:error xmlns:d="DAV:" xmlns:s="http://sabredav.org/ns" xmlns:cal="urn:ietf:params:xml:ns:caldav">
+  <s:exception>Sabre\DAV\Exception\BadRequest</s:exception>
+  <s:message>Calendar object is invalid: missing UID</s:message>
+  <cal:valid-calendar-data/>
+</d:error>"#;
+
+        let err = ServerError::new(400, body);
+        assert_eq!(err.kind(), &ServerErrorKind::Precondition("valid-calendar-data".to_string()));
+        assert_eq!(err.message(), Some("Calendar object is invalid: missing UID"));
+        assert!(err.is_permanent());
+    }
+
+    #[test]
+    fn test_insufficient_storage() {
+        let body = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:error xmlns:d="DAV:" xmlns:s="http://sabredav.org/ns">
+  <s:exception>Sabre\DAV\Exception\InsufficientStorage</s:exception>
+  <s:message></s:message>
+</d:error>"#;
+
+        let err = ServerError::new(507, body);
+        assert_eq!(err.kind(), &ServerErrorKind::InsufficientStorage);
+        assert!(err.is_permanent());
+
+        let err = ServerError::new(503, "Service unavailable");
+        assert_eq!(err.kind(), &ServerErrorKind::Other);
+        assert_eq!(err.message(), None);
+        assert!(err.is_permanent() == false);
+    }
+
+    #[test]
+    fn test_failed_if_match() {
+        let body = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:error xmlns:d="DAV:" xmlns:s="http://sabredav.org/ns">
+  <s:exception>Sabre\DAV\Exception\PreconditionFailed</s:exception>
+  <s:message>An If-Match header was specified, but none of the specified ETags matched.</s:message>
+  <s:header>If-Match</s:header>
+</d:error>"#;
+
+        let err = ServerError::new(412, body);
+        assert_eq!(err.kind(), &ServerErrorKind::Other);
+        assert!(err.is_edit_conflict());
+        assert!(err.is_permanent() == false);
+    }
+
+    #[test]
+    fn test_boxed_errors() {
+        let boxed: Box<dyn std::error::Error> = ServerError::new(503, "").into();
+        assert!(matches!(Error::from(boxed), Error::Http { status: 503, .. }));
+
+        let boxed: Box<dyn std::error::Error> = ServerError::new(401, "").into();
+        assert!(matches!(Error::from(boxed), Error::Auth(_)));
+
+        let boxed: Box<dyn std::error::Error> = Error::NotFound("https://caldav.com/shopping".parse().unwrap()).into();
+        assert!(matches!(Error::from(boxed), Error::NotFound(_)));
+
+        let boxed: Box<dyn std::error::Error> = CancelledError.into();
+        assert!(matches!(Error::from(boxed), Error::Cancelled));
+
+        let boxed: Box<dyn std::error::Error> = "Something went wrong".into();
+        let err = Error::from(boxed);
+        assert!(matches!(err, Error::Other(_)));
+        assert_eq!(err.to_string(), "Something went wrong");
+
+        let boxed: Box<dyn std::error::Error + Send + Sync> = ServerError::new(401, "").into();
+        assert!(matches!(Error::from(boxed), Error::Auth(_)));
+
+        let boxed: Box<dyn std::error::Error + Send + Sync> = "Something went wrong".into();
+        let err = Error::from(boxed);
+        assert!(matches!(err, Error::Other(_)));
+        assert_eq!(err.to_string(), "Something went wrong");
+    }
+
+    #[test]
+    fn test_shareable_errors_keep_their_sources() {
+        let boxed: Box<dyn std::error::Error> = Box::new(Error::Parse(Box::new(ServerError::new(400, ""))));
+        let shared = shareable(boxed);
+        assert_eq!(shared.to_string(), "Invalid iCal or vCard data: Unexpected HTTP status code 400");
+        let source = shared.source().unwrap();
+        assert_eq!(source.to_string(), "Unexpected HTTP status code 400");
+        assert!(source.source().is_none());
+    }
+
+    #[test]
+    fn test_errors_are_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Error>();
+    }
+}
@@ -1,15 +1,58 @@
 //! Calendar events (iCal `VEVENT` items)
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
 use ical::property::Property;
 use serde::{Deserialize, Serialize};
 use url::Url;
-use uuid::Uuid;
 
-use crate::item::SyncStatus;
-use crate::utils::random_url;
+use crate::item::{Classification, SyncStatus, Uid};
+use crate::alarm::Alarm;
+use crate::attachment::Attachment;
+use crate::attendee::{Attendee, Organizer};
+use crate::calendar::CalendarUrl;
+use crate::date_time::IcalDateTime;
+use crate::recurrence::{Occurrence, Recurrence};
 
-/// This struct currently does not support all-day events
+/// The status of an event (iCal `STATUS`)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum EventStatus {
+    Tentative,
+    Confirmed,
+    /// The event (or this instance of a recurring event) has been cancelled. Servers often keep such events, rather than deleting them
+    Cancelled,
+    /// Any other (e.g. `X-`) status
+    Other(String),
+}
+
+impl EventStatus {
+    pub fn as_ical_str(&self) -> &str {
+        match self {
+            EventStatus::Tentative => "TENTATIVE",
+            EventStatus::Confirmed => "CONFIRMED",
+            EventStatus::Cancelled => "CANCELLED",
+            EventStatus::Other(s) => s,
+        }
+    }
+}
+
+impl From<&str> for EventStatus {
+    fn from(s: &str) -> Self {
+        match s {
+            "TENTATIVE" => EventStatus::Tentative,
+            "CONFIRMED" => EventStatus::Confirmed,
+            "CANCELLED" => EventStatus::Cancelled,
+            other => EventStatus::Other(other.to_string()),
+        }
+    }
+}
+
+/// A calendar event.
+///
+/// All-day events are stored with their `start` and `end` dates at midnight UTC (`end` being excluded, as in iCal files).
+///
+/// Other events are stored in UTC as well, but they remember the timezone their dates were written in (see [`Self::timezone`]),
+/// or whether they were floating date-times (see [`Self::is_floating`])
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Event {
     /// The event URL
@@ -18,7 +61,7 @@ pub struct Event {
     /// Persistent, globally unique identifier for the calendar component
     /// The [RFC](https://tools.ietf.org/html/rfc5545#page-117) recommends concatenating a timestamp with the server's domain name.
     /// UUID are even better so we'll generate them, but we have to support tasks from the server, that may have any arbitrary strings here.
-    uid: String,
+    uid: Uid,
 
     /// SUMMARY
     name: String,
@@ -26,6 +69,19 @@ pub struct Event {
     /// DESCRIPTION
     description: Option<String>,
 
+    /// LOCATION
+    #[serde(default)]
+    location: Option<String>,
+    /// GEO, i.e. (latitude, longitude) in degrees
+    #[serde(default)]
+    geo: Option<(f64, f64)>,
+    /// STATUS
+    #[serde(default)]
+    status: Option<EventStatus>,
+    /// CLASS
+    #[serde(default)]
+    class: Option<Classification>,
+
     sync_status: SyncStatus,
 
     /// The PRODID, as defined in iCal files
@@ -35,6 +91,46 @@ pub struct Event {
     last_modified: DateTime<Utc>,
     start: DateTime<Utc>,
     end: DateTime<Utc>,
+    /// The TZID of DTSTART and DTEND, in case they are written in local time
+    #[serde(default)]
+    tzid: Option<String>,
+    /// Whether DTSTART and DTEND are floating date-times, i.e. written in local time without any TZID
+    #[serde(default)]
+    floating: bool,
+    /// Whether DTSTART and DTEND are dates rather than date-times
+    #[serde(default)]
+    all_day: bool,
+    /// Whether the end of this event is written as a DURATION rather than a DTEND
+    #[serde(default)]
+    duration_form: bool,
+    /// RRULE
+    #[serde(default)]
+    recurrence: Option<Recurrence>,
+    /// RECURRENCE-ID. This is set for modified instances of a recurring event (see [`Self::overrides`])
+    #[serde(default)]
+    recurrence_id: Option<DateTime<Utc>>,
+    /// The modified instances of this recurring event, that are stored in the same iCal resource
+    #[serde(default)]
+    overrides: Vec<Event>,
+
+    /// The reminders (VALARM components) of this item
+    #[serde(default)]
+    alarms: Vec<Alarm>,
+
+    /// ATTACH, i.e. documents attached to this item
+    #[serde(default)]
+    attachments: Vec<Attachment>,
+
+    /// ORGANIZER
+    #[serde(default)]
+    organizer: Option<Organizer>,
+    /// ATTENDEEs
+    #[serde(default)]
+    attendees: Vec<Attendee>,
+
+    /// CATEGORIES, i.e. tags of this item
+    #[serde(default)]
+    categories: Vec<String>,
 
     /// Extra parameters that have not been parsed from the iCal file (because they're not supported (yet) by this crate).
     /// They are needed to serialize this item into an equivalent iCal file
@@ -48,62 +144,14 @@ impl Event {
         end: DateTime<Utc>,
         parent_calendar_url: &Url,
     ) -> Self {
-        let new_url = random_url(parent_calendar_url);
-        let new_sync_status = SyncStatus::NotSynced;
-        let new_uid = Uuid::new_v4().to_hyphenated().to_string();
-        let new_creation_date = Some(Utc::now());
-        let new_last_modified = Utc::now();
-        let new_description = None;
-        let ical_prod_id = crate::ical::default_prod_id();
-        let extra_parameters = Vec::new();
-        Self::new_with_parameters(
-            name,
-            new_uid,
-            new_url,
-            new_description,
-            new_sync_status,
-            start,
-            end,
-            new_creation_date,
-            new_last_modified,
-            ical_prod_id,
-            extra_parameters,
-        )
-    }
-
-    pub fn new_with_parameters(
-        name: String,
-        uid: String,
-        url: Url,
-        description: Option<String>,
-        sync_status: SyncStatus,
-        start: DateTime<Utc>,
-        end: DateTime<Utc>,
-        creation_date: Option<DateTime<Utc>>,
-        last_modified: DateTime<Utc>,
-        ical_prod_id: String,
-        extra_parameters: Vec<Property>,
-    ) -> Self {
-        Self {
-            url,
-            uid,
-            name,
-            description,
-            sync_status,
-            start,
-            end,
-            creation_date,
-            last_modified,
-            ical_prod_id,
-            extra_parameters,
-        }
+        EventBuilder::new(name, start, end, parent_calendar_url).build_unchecked()
     }
 
     pub fn url(&self) -> &Url {
         &self.url
     }
 
-    pub fn uid(&self) -> &str {
+    pub fn uid(&self) -> &Uid {
         &self.uid
     }
 
@@ -111,6 +159,242 @@ impl Event {
         &self.name
     }
 
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    pub fn location(&self) -> Option<&str> {
+        self.location.as_deref()
+    }
+
+    /// The (latitude, longitude) of this event, in degrees
+    pub fn geo(&self) -> Option<(f64, f64)> {
+        self.geo
+    }
+
+    pub fn status(&self) -> Option<&EventStatus> {
+        self.status.as_ref()
+    }
+
+    /// Whether this event (or this instance of a recurring event) has been cancelled. Views (e.g. [`crate::grid`]) do not show such events
+    pub fn is_cancelled(&self) -> bool {
+        self.status == Some(EventStatus::Cancelled)
+    }
+
+    /// The access classification of this event (`None` means public)
+    pub fn classification(&self) -> Option<&Classification> {
+        self.class.as_ref()
+    }
+
+    /// Whether this event is private or confidential, i.e. whether apps should not show its details to other people than its owner
+    pub fn is_restricted(&self) -> bool {
+        self.class.as_ref().map(Classification::is_restricted).unwrap_or(false)
+    }
+
+    pub fn start(&self) -> &DateTime<Utc> {
+        &self.start
+    }
+
+    pub fn end(&self) -> &DateTime<Utc> {
+        &self.end
+    }
+
+    pub fn is_all_day(&self) -> bool {
+        self.all_day
+    }
+
+    /// How long this event lasts
+    pub fn duration(&self) -> Duration {
+        self.end - self.start
+    }
+
+    /// Whether the end of this event is written as a DURATION (rather than a DTEND) in iCal files
+    pub fn uses_duration(&self) -> bool {
+        self.duration_form
+    }
+
+    /// The TZID the start and end dates are written in (e.g. `Europe/Paris`), in case they are not written in UTC
+    pub fn tzid(&self) -> Option<&str> {
+        self.tzid.as_deref()
+    }
+
+    /// The timezone the start and end dates are written in, in case they are not written in UTC. \
+    /// Recurring events keep the same local time in this timezone, even across DST changes.
+    ///
+    /// This is `None` for TZIDs that are not IANA timezone names (see [`Self::tzid`])
+    pub fn timezone(&self) -> Option<Tz> {
+        self.tzid.as_deref().and_then(crate::ical::lookup_timezone)
+    }
+
+    /// Whether the start and end dates are floating date-times, i.e. that mean the same wall-clock time whatever the timezone of the user. \
+    /// In this case, [`Self::start`] and [`Self::end`] are this wall-clock time, read as UTC (see [`Self::start_date_time`] to resolve them in another timezone)
+    pub fn is_floating(&self) -> bool {
+        self.floating
+    }
+
+    /// The start date, as it is written in the iCal file. This makes no sense for all-day events
+    pub fn start_date_time(&self) -> IcalDateTime {
+        self.as_ical_date_time(&self.start)
+    }
+
+    /// The end date, as it is written in the iCal file. This makes no sense for all-day events
+    pub fn end_date_time(&self) -> IcalDateTime {
+        self.as_ical_date_time(&self.end)
+    }
+
+    fn as_ical_date_time(&self, dt: &DateTime<Utc>) -> IcalDateTime {
+        match (&self.tzid, self.floating) {
+            (_, true) => IcalDateTime::Floating(dt.naive_utc()),
+            (Some(tzid), false) => {
+                let date_time = match self.timezone() {
+                    Some(tz) => dt.with_timezone(&tz).naive_local(),
+                    None => dt.naive_utc(),
+                };
+                IcalDateTime::Local { date_time, tzid: tzid.clone() }
+            },
+            (None, false) => IcalDateTime::Utc(*dt),
+        }
+    }
+
+    /// The recurrence rule of this event, if this is a recurring event
+    pub fn recurrence(&self) -> Option<&Recurrence> {
+        self.recurrence.as_ref()
+    }
+
+    /// For modified instances of a recurring event, the original start date of the instance this replaces
+    pub fn recurrence_id(&self) -> Option<&DateTime<Utc>> {
+        self.recurrence_id.as_ref()
+    }
+
+    /// The modified instances of this recurring event (i.e. the other VEVENTs with a RECURRENCE-ID, stored in the same iCal resource)
+    pub fn overrides(&self) -> &[Event] {
+        &self.overrides
+    }
+
+    /// Set the modified instances of this recurring event, without changing its sync status (this is used when parsing iCal resources)
+    pub(crate) fn with_overrides(mut self, overrides: Vec<Event>) -> Self {
+        self.overrides = overrides;
+        self
+    }
+
+    /// Add (or replace) a modified instance of this recurring event.
+    /// This updates its "last modified" field
+    pub fn set_override(&mut self, instance: Event) -> Result<(), Box<dyn std::error::Error>> {
+        let recurrence_id = match instance.recurrence_id {
+            None => return Err("An override must have a RECURRENCE-ID".into()),
+            Some(id) => id,
+        };
+        if instance.uid != self.uid {
+            return Err(format!("An override must have the same UID as its recurring event ({})", self.uid).into());
+        }
+        self.update_sync_status();
+        self.update_last_modified();
+        self.overrides.retain(|o| o.recurrence_id != Some(recurrence_id));
+        self.overrides.push(instance);
+        Ok(())
+    }
+
+    /// Remove a modified instance of this recurring event, and return it.
+    /// This updates its "last modified" field
+    pub fn remove_override(&mut self, recurrence_id: &DateTime<Utc>) -> Option<Event> {
+        let index = self.overrides.iter().position(|o| o.recurrence_id.as_ref() == Some(recurrence_id))?;
+        self.update_sync_status();
+        self.update_last_modified();
+        Some(self.overrides.remove(index))
+    }
+
+    /// Whether this is a modified instance of a recurring event, that is stored without its recurring event (e.g. because it has been deleted)
+    pub fn is_orphaned_instance(&self) -> bool {
+        self.recurrence_id.is_some()
+    }
+
+    /// The modified instances of this event that do not replace any of its occurrences (e.g. because its RRULE has changed since they have been created)
+    pub fn orphaned_overrides(&self) -> Vec<&Event> {
+        self.overrides.iter()
+            .filter(|o| o.recurrence_id.map(|id| self.is_instance_start(&id)) != Some(true))
+            .collect()
+    }
+
+    /// Whether an occurrence of this event would start at a given date, if it had not been modified nor excluded
+    fn is_instance_start(&self, date: &DateTime<Utc>) -> bool {
+        if *date == self.start || self.property_dates("RDATE").contains(date) {
+            return true;
+        }
+        match &self.recurrence {
+            None => false,
+            Some(rule) => {
+                let mut instances = match self.timezone() {
+                    Some(tz) => rule.instances_in(self.start, &tz).skip_to(*date),
+                    None => rule.instances(self.start).skip_to(*date),
+                };
+                instances.find(|s| s >= date).as_ref() == Some(date)
+            },
+        }
+    }
+
+    /// Split this event into the instances that are stored in its resource: itself (without its modified instances), then its modified instances
+    pub(crate) fn into_instances(mut self) -> Vec<Event> {
+        let overrides = std::mem::take(&mut self.overrides);
+        std::iter::once(self).chain(overrides).collect()
+    }
+
+    /// A standalone copy of this event (with its own URL and UID, but without its modified instances), that is not synced yet.
+    /// This is used to keep modified instances that do not belong to any recurring event
+    pub fn detached_copy(&self, parent_calendar_url: &Url) -> Event {
+        let mut copy = self.clone();
+        copy.url = CalendarUrl::from(parent_calendar_url.clone()).random_item_url();
+        copy.uid = Uid::random();
+        copy.recurrence_id = None;
+        copy.overrides = Vec::new();
+        copy.sync_status = SyncStatus::NotSynced;
+        copy.update_last_modified();
+        copy
+    }
+
+    /// The occurrences of this event that happen (at least partly) between `start` (included) and `end` (excluded), in chronological order.
+    ///
+    /// For recurring events, this expands the RRULE, adds the RDATEs and removes the EXDATEs (see also [`Recurrence::instances`]). \
+    /// Non-recurring events have a single occurrence.
+    /// Instances that have been modified (see [`Self::overrides`]) are not included, since the overrides have their own dates.
+    pub fn occurrences_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> impl Iterator<Item = Occurrence> {
+        let duration = self.end - self.start;
+        // Occurrences that start before `start` may still overlap it
+        let first_start = start.checked_sub_signed(duration).unwrap_or(self.start);
+
+        let mut starts: Vec<DateTime<Utc>> = match &self.recurrence {
+            None => vec![self.start],
+            Some(rule) => match self.timezone() {
+                Some(tz) => rule.instances_in(self.start, &tz).skip_to(first_start).take_while(|s| *s < end).collect(),
+                None => rule.instances(self.start).skip_to(first_start).take_while(|s| *s < end).collect(),
+            },
+        };
+        starts.extend(self.property_dates("RDATE"));
+        let mut exception_dates = self.property_dates("EXDATE");
+        exception_dates.extend(self.overrides.iter().filter_map(|o| o.recurrence_id));
+        starts.retain(|s| exception_dates.contains(s) == false);
+        starts.sort();
+        starts.dedup();
+
+        starts.into_iter()
+            .map(move |s| Occurrence::new(s, s + duration))
+            .filter(move |occurrence| occurrence.overlaps(&start, &end))
+    }
+
+    /// Whether this event, or any of its modified instances, happens (at least partly) between `start` (included) and `end` (excluded). \
+    /// This is what CalDAV servers check for `time-range` filters
+    pub fn occurs_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> bool {
+        self.occurrences_between(start, end).next().is_some()
+            || self.overrides.iter().any(|instance| instance.occurs_between(start, end))
+    }
+
+    /// The dates of the (unparsed) properties that have a given name
+    fn property_dates(&self, name: &str) -> Vec<DateTime<Utc>> {
+        self.extra_parameters.iter()
+            .filter(|prop| prop.name == name)
+            .flat_map(crate::ical::parse_date_times_from_property)
+            .collect()
+    }
+
     pub fn ical_prod_id(&self) -> &str {
         &self.ical_prod_id
     }
@@ -130,8 +414,523 @@ impl Event {
         self.sync_status = new_status;
     }
 
+    /// Change the URL of this item, e.g. because it has been moved on the server
+    pub(crate) fn set_url(&mut self, new_url: Url) {
+        for instance in &mut self.overrides {
+            instance.set_url(new_url.clone());
+        }
+        self.url = new_url;
+    }
+
+    /// Change the UID of this item (and of its modified instances), e.g. to make it a distinct copy
+    pub(crate) fn set_uid(&mut self, new_uid: Uid) {
+        for instance in &mut self.overrides {
+            instance.set_uid(new_uid.clone());
+        }
+        self.uid = new_uid;
+    }
+
+    fn update_sync_status(&mut self) {
+        match &self.sync_status {
+            SyncStatus::NotSynced => return,
+            SyncStatus::LocallyModified(_) => return,
+            SyncStatus::Synced(prev_vt) => {
+                self.sync_status = SyncStatus::LocallyModified(prev_vt.clone());
+            }
+            SyncStatus::LocallyDeleted(_) => {
+                log::warn!("Trying to update an item that has previously been deleted. These changes will probably be ignored at next sync.");
+                return;
+            }
+        }
+    }
+
+    pub(crate) fn update_last_modified(&mut self) {
+        self.last_modified = Utc::now();
+    }
+
+    /// Drop what a deleted event does not need any more (see [`Item::drop_content`](crate::item::Item::drop_content)). \
+    /// Its dates, organizer, attendees and SEQUENCE are kept, so that its deletion can still be told to its attendees
+    pub(crate) fn drop_content(&mut self) -> bool {
+        let had_content = self.description.is_some() || self.location.is_some() || self.geo.is_some()
+            || self.alarms.is_empty() == false || self.attachments.is_empty() == false || self.categories.is_empty() == false
+            || self.extra_parameters.iter().any(|prop| prop.name != "SEQUENCE");
+        self.description = None;
+        self.location = None;
+        self.geo = None;
+        self.alarms.clear();
+        self.attachments.clear();
+        self.categories.clear();
+        self.extra_parameters.retain(|prop| prop.name == "SEQUENCE");
+        had_content
+    }
+
+    /// Set (or remove) the recurrence rule of this event.
+    /// This updates its "last modified" field
+    pub fn set_recurrence(&mut self, new_recurrence: Option<Recurrence>) {
+        self.update_sync_status();
+        self.update_last_modified();
+        self.recurrence = new_recurrence;
+    }
+
+    /// Set the timezone the start and end dates are written in (or write them in UTC).
+    /// This does not move the event, but this changes how its recurrences are expanded (see [`Self::timezone`]).
+    /// Floating events are no longer floating (see [`Self::resolve_floating`] to keep their wall-clock time instead).
+    /// This updates its "last modified" field
+    pub fn set_timezone(&mut self, new_timezone: Option<Tz>) {
+        self.update_sync_status();
+        self.update_last_modified();
+        self.tzid = new_timezone.map(|tz| tz.name().to_string());
+        self.floating = false;
+    }
+
+    /// Pin a floating event (see [`Self::is_floating`]) to a timezone, so that it keeps its wall-clock time in this timezone only.
+    /// This has no effect on events that are not floating.
+    /// This updates its "last modified" field
+    pub fn resolve_floating(&mut self, timezone: &Tz) {
+        if self.floating == false {
+            return;
+        }
+        self.update_sync_status();
+        self.update_last_modified();
+        self.start = self.start_date_time().resolve(timezone);
+        self.end = self.end_date_time().resolve(timezone);
+        self.tzid = Some(timezone.name().to_string());
+        self.floating = false;
+    }
+
+    /// Set (or remove) the location of this event.
+    /// This updates its "last modified" field
+    pub fn set_location(&mut self, new_location: Option<String>) {
+        self.update_sync_status();
+        self.update_last_modified();
+        self.location = new_location;
+    }
+
+    /// Set (or remove) the (latitude, longitude) of this event, in degrees.
+    /// This updates its "last modified" field
+    pub fn set_geo(&mut self, new_geo: Option<(f64, f64)>) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some((latitude, longitude)) = new_geo {
+            crate::utils::check_geo_position(latitude, longitude)?;
+        }
+        self.update_sync_status();
+        self.update_last_modified();
+        // An invalid GEO may have been kept as is, it is now superseded
+        self.extra_parameters.retain(|prop| prop.name != "GEO");
+        self.geo = new_geo;
+        Ok(())
+    }
+
+    /// Set (or remove) the status of this event.
+    /// This updates its "last modified" field
+    pub fn set_status(&mut self, new_status: Option<EventStatus>) {
+        self.update_sync_status();
+        self.update_last_modified();
+        self.status = new_status;
+    }
+
+    /// Set (or remove) the access classification of this event.
+    /// This updates its "last modified" field
+    pub fn set_classification(&mut self, new_class: Option<Classification>) {
+        self.update_sync_status();
+        self.update_last_modified();
+        self.class = new_class;
+    }
+
+    /// SEQUENCE, i.e. the revision number of this event, that organizers increase when they make significant changes (see [`crate::scheduling`]). This is 0 when it is missing
+    pub fn sequence(&self) -> u32 {
+        self.extra_parameters.iter()
+            .find(|prop| prop.name == "SEQUENCE")
+            .and_then(|prop| prop.value.as_deref())
+            .and_then(|value| value.trim().parse().ok())
+            .unwrap_or(0)
+    }
+
+    /// Set the revision number of this event.
+    /// This updates its "last modified" field
+    pub fn set_sequence(&mut self, new_sequence: u32) {
+        self.update_sync_status();
+        self.update_last_modified();
+        self.extra_parameters.retain(|prop| prop.name != "SEQUENCE");
+        self.extra_parameters.push(Property {
+            name: "SEQUENCE".to_string(),
+            params: None,
+            value: Some(new_sequence.to_string()),
+        });
+    }
+
+    /// Replace the reminders of this event.
+    /// This updates its "last modified" field
+    pub fn set_alarms(&mut self, new_alarms: Vec<Alarm>) {
+        self.update_sync_status();
+        self.update_last_modified();
+        self.alarms = new_alarms;
+    }
+
+    pub fn extra_parameters(&self) -> &[Property] {
+        &self.extra_parameters
+    }
+
+    /// The first property named `name` (case-insensitive) among the ones this crate does not handle, e.g. a custom `X-` property.
+    /// Its value is as it is written in the iCal file, i.e. TEXT values are still escaped
+    pub fn get_property(&self, name: &str) -> Option<&Property> {
+        self.extra_parameters.iter().find(|prop| prop.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Set a custom property (e.g. `X-MY-APP-COLOR`), replacing the ones with the same name.
+    /// `value` is written as is, so TEXT values must be escaped (see [`escape_text`](crate::ical::values::escape_text)), and it must not contain line breaks.
+    /// Parameter values are quoted when needed. This updates its "last modified" field
+    pub fn set_property(&mut self, name: String, value: String, params: Vec<(String, Vec<String>)>) -> Result<(), Box<dyn std::error::Error>> {
+        crate::utils::check_custom_property_name(&name)?;
+        crate::utils::check_custom_property_value(&value, &params)?;
+        self.update_sync_status();
+        self.update_last_modified();
+        self.extra_parameters.retain(|prop| prop.name.eq_ignore_ascii_case(&name) == false);
+        self.extra_parameters.push(Property {
+            name: name.to_ascii_uppercase(),
+            params: if params.is_empty() { None } else { Some(params) },
+            value: Some(value),
+        });
+        Ok(())
+    }
+
+    /// Remove the properties named `name` (case-insensitive) among the ones this crate does not handle, and return them.
+    /// This updates its "last modified" field, unless there was none
+    pub fn remove_property(&mut self, name: &str) -> Vec<Property> {
+        let (removed, kept): (Vec<Property>, Vec<Property>) = std::mem::take(&mut self.extra_parameters)
+            .into_iter()
+            .partition(|prop| prop.name.eq_ignore_ascii_case(name));
+        self.extra_parameters = kept;
+        if removed.is_empty() == false {
+            self.update_sync_status();
+            self.update_last_modified();
+        }
+        removed
+    }
+
+    pub fn categories(&self) -> &[String] {
+        &self.categories
+    }
+
+    /// Add a category to this item (if it does not have it already).
+    /// This updates its "last modified" field
+    pub fn add_category(&mut self, category: String) {
+        if self.categories.contains(&category) {
+            return;
+        }
+        self.update_sync_status();
+        self.update_last_modified();
+        self.categories.push(category);
+    }
+
+    /// Remove a category from this item, and return whether it had it.
+    /// This updates its "last modified" field
+    pub fn remove_category(&mut self, category: &str) -> bool {
+        if self.categories.iter().any(|c| c == category) == false {
+            return false;
+        }
+        self.update_sync_status();
+        self.update_last_modified();
+        self.categories.retain(|c| c != category);
+        true
+    }
+
+    pub fn attachments(&self) -> &[Attachment] {
+        &self.attachments
+    }
+
+    /// Attach a document to this item.
+    /// This updates its "last modified" field
+    pub fn add_attachment(&mut self, attachment: Attachment) {
+        self.update_sync_status();
+        self.update_last_modified();
+        self.attachments.push(attachment);
+    }
+
+    /// Remove an attachment from this item, and return whether it had it.
+    /// This updates its "last modified" field
+    pub fn remove_attachment(&mut self, attachment: &Attachment) -> bool {
+        if self.attachments.contains(attachment) == false {
+            return false;
+        }
+        self.update_sync_status();
+        self.update_last_modified();
+        self.attachments.retain(|a| a != attachment);
+        true
+    }
+
+    pub fn alarms(&self) -> &[Alarm] {
+        &self.alarms
+    }
+
+    pub fn organizer(&self) -> Option<&Organizer> {
+        self.organizer.as_ref()
+    }
+
+    pub fn attendees(&self) -> &[Attendee] {
+        &self.attendees
+    }
+
+    /// Set (or remove) the organizer of this event.
+    /// This updates its "last modified" field
+    pub fn set_organizer(&mut self, new_organizer: Option<Organizer>) {
+        self.update_sync_status();
+        self.update_last_modified();
+        self.organizer = new_organizer;
+    }
+
+    /// Replace the attendees of this event.
+    /// This updates its "last modified" field
+    pub fn set_attendees(&mut self, new_attendees: Vec<Attendee>) {
+        self.update_sync_status();
+        self.update_last_modified();
+        self.attendees = new_attendees;
+    }
+
     #[cfg(any(test, feature = "integration_tests"))]
-    pub fn has_same_observable_content_as(&self, _other: &Event) -> bool {
-        unimplemented!();
+    pub fn has_same_observable_content_as(&self, other: &Event) -> bool {
+        self.url == other.url
+        && self.uid == other.uid
+        && self.name == other.name
+        && self.description == other.description
+        && self.location == other.location
+        && self.geo == other.geo
+        && self.status == other.status
+        && self.class == other.class
+        && self.start == other.start
+        && self.end == other.end
+        && self.tzid == other.tzid
+        && self.floating == other.floating
+        && self.all_day == other.all_day
+        && self.duration_form == other.duration_form
+        && self.recurrence == other.recurrence
+        && self.recurrence_id == other.recurrence_id
+        && self.overrides.len() == other.overrides.len()
+        && self.overrides.iter().zip(&other.overrides).all(|(s, o)| s.has_same_observable_content_as(o))
+        && self.alarms == other.alarms
+        && self.attachments == other.attachments
+        && self.organizer == other.organizer
+        && self.attendees == other.attendees
+        && self.categories == other.categories
+        // sync status must be the same variant, but we ignore its embedded version tag
+        && std::mem::discriminant(&self.sync_status) == std::mem::discriminant(&other.sync_status)
+        // last modified dates are ignored (they are not totally mocked in integration tests)
+    }
+}
+
+
+
+/// A builder for [`Event`]s, which is more convenient than [`Event::new`] when more than the name and the dates are known.
+///
+/// Fields that are not set get the same default values as in [`Event::new`]
+#[derive(Clone, Debug)]
+pub struct EventBuilder {
+    name: String,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    tzid: Option<String>,
+    floating: bool,
+    duration_form: bool,
+    url: Url,
+    uid: Option<Uid>,
+    description: Option<String>,
+    location: Option<String>,
+    geo: Option<(f64, f64)>,
+    status: Option<EventStatus>,
+    class: Option<Classification>,
+    sync_status: SyncStatus,
+    all_day: bool,
+    recurrence: Option<Recurrence>,
+    recurrence_id: Option<DateTime<Utc>>,
+    creation_date: Option<DateTime<Utc>>,
+    last_modified: Option<DateTime<Utc>>,
+    ical_prod_id: Option<String>,
+    alarms: Vec<Alarm>,
+    attachments: Vec<Attachment>,
+    organizer: Option<Organizer>,
+    attendees: Vec<Attendee>,
+    categories: Vec<String>,
+    extra_parameters: Vec<Property>,
+}
+
+impl EventBuilder {
+    /// Start building an event that is not on a server yet (a random URL is picked in the given calendar)
+    pub fn new(name: String, start: DateTime<Utc>, end: DateTime<Utc>, parent_calendar_url: &Url) -> Self {
+        Self {
+            name, start, end,
+            tzid: None,
+            floating: false,
+            duration_form: false,
+            url: CalendarUrl::from(parent_calendar_url.clone()).random_item_url(),
+            uid: None,
+            description: None,
+            location: None,
+            geo: None,
+            status: None,
+            class: None,
+            sync_status: SyncStatus::NotSynced,
+            all_day: false,
+            recurrence: None,
+            recurrence_id: None,
+            creation_date: Some(Utc::now()),
+            last_modified: None,
+            ical_prod_id: None,
+            alarms: Vec::new(),
+            attachments: Vec::new(),
+            organizer: None,
+            attendees: Vec::new(),
+            categories: Vec::new(),
+            extra_parameters: Vec::new(),
+        }
+    }
+
+    pub fn with_url(mut self, url: Url) -> Self { self.url = url; self }
+    pub fn with_uid(mut self, uid: Uid) -> Self { self.uid = Some(uid); self }
+    pub fn with_description(mut self, description: String) -> Self { self.description = Some(description); self }
+    pub fn with_location(mut self, location: String) -> Self { self.location = Some(location); self }
+    /// Set the (latitude, longitude) of the event, in degrees
+    pub fn with_geo(mut self, latitude: f64, longitude: f64) -> Self { self.geo = Some((latitude, longitude)); self }
+    pub fn with_status(mut self, status: EventStatus) -> Self { self.status = Some(status); self }
+    pub fn with_classification(mut self, class: Classification) -> Self { self.class = Some(class); self }
+    pub fn with_sync_status(mut self, sync_status: SyncStatus) -> Self { self.sync_status = sync_status; self }
+    /// Make this an all-day event. `start` and `end` must then be at midnight UTC, `end` being excluded
+    pub fn all_day(mut self) -> Self { self.all_day = true; self }
+    /// Write the start and end dates in a given timezone (see [`Event::timezone`])
+    pub fn with_timezone(mut self, timezone: Tz) -> Self { self.tzid = Some(timezone.name().to_string()); self }
+    /// Keep whatever TZID a server wrote the dates in, even if it is not an IANA timezone name
+    pub(crate) fn with_tzid(mut self, tzid: String) -> Self { self.tzid = Some(tzid); self }
+    /// Make the start and end dates floating date-times (see [`Event::is_floating`]). `start` and `end` are then wall-clock times, read as UTC
+    pub fn floating(mut self) -> Self { self.floating = true; self }
+    /// Write the end of the event as a DURATION rather than a DTEND, the way the server did
+    pub(crate) fn duration_form(mut self) -> Self { self.duration_form = true; self }
+    pub fn with_recurrence(mut self, recurrence: Recurrence) -> Self { self.recurrence = Some(recurrence); self }
+    /// Make this a modified instance of a recurring event, that replaces the occurrence that started at `recurrence_id`
+    pub(crate) fn with_recurrence_id(mut self, recurrence_id: DateTime<Utc>) -> Self { self.recurrence_id = Some(recurrence_id); self }
+    pub fn with_creation_date(mut self, creation_date: Option<DateTime<Utc>>) -> Self { self.creation_date = creation_date; self }
+    pub fn with_last_modified(mut self, last_modified: DateTime<Utc>) -> Self { self.last_modified = Some(last_modified); self }
+    pub fn with_ical_prod_id(mut self, ical_prod_id: String) -> Self { self.ical_prod_id = Some(ical_prod_id); self }
+    pub fn with_alarm(mut self, alarm: Alarm) -> Self { self.alarms.push(alarm); self }
+    pub fn with_attachment(mut self, attachment: Attachment) -> Self { self.attachments.push(attachment); self }
+    pub fn with_organizer(mut self, organizer: Organizer) -> Self { self.organizer = Some(organizer); self }
+    pub fn with_attendee(mut self, attendee: Attendee) -> Self { self.attendees.push(attendee); self }
+    pub fn with_category(mut self, category: String) -> Self {
+        if self.categories.contains(&category) == false {
+            self.categories.push(category);
+        }
+        self
+    }
+    /// Add iCal properties that are not supported by this crate, so that they are written back to the server
+    pub fn with_extra_parameters(mut self, extra_parameters: Vec<Property>) -> Self { self.extra_parameters.extend(extra_parameters); self }
+
+    /// Build the event, checking that its fields are consistent
+    pub fn build(self) -> Result<Event, Box<dyn std::error::Error>> {
+        if self.name.trim().is_empty() {
+            return Err("An event must have a non-empty name".into());
+        }
+        if self.end <= self.start {
+            return Err(format!("An event must end after it starts (got {} to {})", self.start, self.end).into());
+        }
+        if let Some((latitude, longitude)) = self.geo {
+            crate::utils::check_geo_position(latitude, longitude)?;
+        }
+        if self.floating && self.tzid.is_some() {
+            return Err("An event cannot be both floating and written in a timezone".into());
+        }
+        Ok(self.build_unchecked())
+    }
+
+    /// Build the event as is, e.g. because its fields come from a server, that must be trusted
+    pub(crate) fn build_unchecked(self) -> Event {
+        Event {
+            url: self.url,
+            uid: self.uid.unwrap_or_else(Uid::random),
+            name: self.name,
+            description: self.description,
+            location: self.location,
+            geo: self.geo,
+            status: self.status,
+            class: self.class,
+            sync_status: self.sync_status,
+            start: self.start,
+            end: self.end,
+            tzid: self.tzid,
+            floating: self.floating,
+            all_day: self.all_day,
+            duration_form: self.duration_form,
+            recurrence: self.recurrence,
+            recurrence_id: self.recurrence_id,
+            overrides: Vec::new(),
+            creation_date: self.creation_date,
+            last_modified: self.last_modified.unwrap_or_else(Utc::now),
+            ical_prod_id: self.ical_prod_id.unwrap_or_else(crate::ical::default_prod_id),
+            alarms: self.alarms,
+            attachments: self.attachments,
+            organizer: self.organizer,
+            attendees: self.attendees,
+            categories: self.categories,
+            extra_parameters: self.extra_parameters,
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_event_builder() {
+        let cal_url: Url = "https://some.calend.ar/calendar/".parse().unwrap();
+        let start = Utc.ymd(2021, 3, 1).and_hms(9, 0, 0);
+
+        let event = EventBuilder::new("Standup".to_string(), start, start + chrono::Duration::minutes(15), &cal_url)
+            .with_location("Room 42".to_string())
+            .with_category("Work".to_string())
+            .with_category("Work".to_string())
+            .build()
+            .unwrap();
+        assert_eq!(event.name(), "Standup");
+        assert_eq!(event.location(), Some("Room 42"));
+        assert_eq!(event.categories(), &["Work".to_string()]);
+        assert_eq!(event.sync_status(), &SyncStatus::NotSynced);
+        assert!(CalendarUrl::from(cal_url.clone()).contains(event.url()));
+
+        assert!(EventBuilder::new("  ".to_string(), start, start + chrono::Duration::minutes(15), &cal_url).build().is_err());
+        assert!(EventBuilder::new("Backwards".to_string(), start, start - chrono::Duration::minutes(15), &cal_url).build().is_err());
+        assert!(EventBuilder::new("Nowhere".to_string(), start, start + chrono::Duration::minutes(15), &cal_url).with_geo(100.0, 0.0).build().is_err());
+    }
+
+    #[test]
+    fn test_custom_properties() {
+        let cal_url: Url = "https://some.calend.ar/calendar/".parse().unwrap();
+        let start = Utc.ymd(2021, 3, 1).and_hms(9, 0, 0);
+        let mut event = Event::new("Standup".to_string(), start, start + chrono::Duration::minutes(15), &cal_url);
+        event.set_sync_status(SyncStatus::Synced(crate::item::VersionTag::from("v1".to_string())));
+
+        event.set_property("x-my-app-color".to_string(), "#ff0000".to_string(), vec![("X-SCOPE".to_string(), vec!["ALL".to_string()])]).unwrap();
+        assert!(matches!(event.sync_status(), SyncStatus::LocallyModified(_)));
+        assert!(event.set_property("SUMMARY".to_string(), "Sneaky".to_string(), Vec::new()).is_err());
+        assert!(event.set_property("X-".to_string(), "Empty".to_string(), Vec::new()).is_err());
+        // Nothing can be injected into the iCal file
+        assert!(event.set_property("X-NOTE".to_string(), "Line\r\nSUMMARY:Sneaky".to_string(), Vec::new()).is_err());
+        assert!(event.set_property("X-NOTE".to_string(), "Note".to_string(), vec![("X-BY".to_string(), vec!["\"Quoted\"".to_string()])]).is_err());
+        assert!(event.set_property("X-NOTE".to_string(), "Note".to_string(), vec![("X-BY:".to_string(), Vec::new())]).is_err());
+        let targets = vec!["mailto:alice@example.com".to_string(), "Bob; Carol".to_string(), "Dave".to_string()];
+        event.set_property("X-NOTE".to_string(), "Note".to_string(), vec![("X-TARGETS".to_string(), targets.clone())]).unwrap();
+
+        let item = crate::Item::Event(event);
+        let ical = crate::ical::build_from(&item).unwrap();
+        assert!(ical.contains("\r\nX-MY-APP-COLOR;X-SCOPE=ALL:#ff0000\r\n"));
+        let parsed = crate::ical::parse(&ical, item.url().clone(), SyncStatus::NotSynced).unwrap();
+        let mut event = parsed.unwrap_event().clone();
+        assert_eq!(event.get_property("X-My-App-Color").and_then(|prop| prop.value.as_deref()), Some("#ff0000"));
+        let note = event.get_property("X-NOTE").unwrap();
+        assert_eq!(note.value.as_deref(), Some("Note"));
+        assert_eq!(note.params, Some(vec![("X-TARGETS".to_string(), targets)]));
+
+        assert_eq!(event.remove_property("X-MY-APP-COLOR").len(), 1);
+        assert!(event.get_property("X-MY-APP-COLOR").is_none());
+        assert!(event.remove_property("X-MY-APP-COLOR").is_empty());
     }
 }
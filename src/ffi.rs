@@ -0,0 +1,303 @@
+//! A C ABI, so that apps that are not written in Rust (e.g. GTK apps written in C, or Swift apps) can use this crate as their sync engine.
+//!
+//! This is only available with the `ffi` feature. The matching C header is `include/kitchen_fridge.h` (it is generated with `cbindgen --config cbindgen.toml --output include/kitchen_fridge.h`).
+//! Build a shared or a static library with `cargo rustc --release --features ffi --crate-type cdylib` (or `--crate-type staticlib`).
+//!
+//! Conventions:
+//! * every object that is returned by a `kf_*_new` (or another constructor) must be freed with the matching `kf_*_free` function
+//! * strings are UTF-8 and NUL-terminated. Strings that are given to these functions are only borrowed
+//! * functions that fail return `NULL` (or `false`), and the reason is available with [`kf_last_error`]
+//! * panics never unwind into the caller: they are caught, and reported as errors as well. The provider that was being used may then be in an inconsistent state, and should be freed
+//!
+//! Providers are not thread-safe: a given provider must not be used by several threads at once.
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::error::Error;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::Path;
+use std::ptr;
+
+use url::Url;
+
+use crate::cache::Cache;
+use crate::client::Client;
+use crate::item::SyncStatus;
+use crate::provider::SyncOptions;
+use crate::{CalDavProvider, Item};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = RefCell::new(None);
+}
+
+fn set_last_error(err: Box<dyn Error>) {
+    log::error!("{}", err);
+    let message = CString::new(err.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    let reason = match payload.downcast_ref::<&str>() {
+        Some(s) => s.to_string(),
+        None => match payload.downcast_ref::<String>() {
+            Some(s) => s.clone(),
+            None => String::from("unknown reason"),
+        },
+    };
+    format!("Internal error (panic): {}", reason)
+}
+
+/// Run `f`, and store its error (if any) so that it can be retrieved with [`kf_last_error`].
+/// Panics are caught and stored as errors too, because unwinding into C code is undefined behaviour
+fn catch<T, F: FnOnce() -> Result<T, Box<dyn Error>>>(f: F) -> Option<T> {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => Some(value),
+        Ok(Err(err)) => {
+            set_last_error(err);
+            None
+        },
+        Err(payload) => {
+            set_last_error(panic_message(payload).into());
+            None
+        },
+    }
+}
+
+unsafe fn read_str<'a>(s: *const c_char, what: &str) -> Result<&'a str, Box<dyn Error>> {
+    if s.is_null() {
+        return Err(format!("{} is NULL", what).into());
+    }
+    Ok(CStr::from_ptr(s).to_str()?)
+}
+
+fn to_c_string(s: &str) -> *mut c_char {
+    CString::new(s.replace('\0', " ")).unwrap_or_default().into_raw()
+}
+
+/// The message of the last error that happened in the current thread, or `NULL` if nothing failed yet.
+///
+/// The returned string is owned by the library, and is valid until the next call to a `kf_*` function in this thread
+#[no_mangle]
+pub extern "C" fn kf_last_error() -> *const c_char {
+    catch_unwind(|| {
+        LAST_ERROR.with(|last| match last.borrow().as_ref() {
+            None => ptr::null(),
+            Some(message) => message.as_ptr(),
+        })
+    })
+    .unwrap_or(ptr::null())
+}
+
+/// A provider, that syncs a CalDAV server with a local cache (see [`CalDavProvider`])
+pub struct KfProvider {
+    provider: CalDavProvider,
+    runtime: tokio::runtime::Runtime,
+}
+
+/// A task, as it is listed by [`kf_provider_tasks`]
+#[repr(C)]
+pub struct KfTask {
+    /// The URL of the task, that identifies it (e.g. in [`kf_provider_set_task_completion`])
+    pub url: *mut c_char,
+    pub calendar_url: *mut c_char,
+    pub name: *mut c_char,
+    pub completed: bool,
+}
+
+/// A list of tasks, that must be freed with [`kf_task_list_free`]
+#[repr(C)]
+pub struct KfTaskList {
+    pub tasks: *mut KfTask,
+    pub len: usize,
+}
+
+/// Create a provider for a CalDAV server, whose local cache is stored in `cache_folder` (which is created if needed). This does not start a connection.
+///
+/// Returns `NULL` on error
+///
+/// # Safety
+/// Every argument must be a valid NUL-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn kf_provider_new(url: *const c_char, username: *const c_char, password: *const c_char, cache_folder: *const c_char) -> *mut KfProvider {
+    catch(|| {
+        let client = Client::new(read_str(url, "url")?, read_str(username, "username")?, read_str(password, "password")?)?;
+        let cache_path = Path::new(read_str(cache_folder, "cache_folder")?);
+        let cache = match Cache::from_folder(cache_path) {
+            Ok(cache) => cache,
+            Err(err) => {
+                log::warn!("Invalid cache file: {}. Using a default cache", err);
+                Cache::new(cache_path)
+            },
+        };
+        let runtime = tokio::runtime::Runtime::new()?;
+        Ok(Box::into_raw(Box::new(KfProvider { provider: CalDavProvider::new(client, cache), runtime })))
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Free a provider (this saves its local cache). Freeing `NULL` does nothing
+///
+/// # Safety
+/// `provider` must have been returned by [`kf_provider_new`], and must not be used afterwards
+#[no_mangle]
+pub unsafe extern "C" fn kf_provider_free(provider: *mut KfProvider) {
+    catch(|| {
+        if provider.is_null() == false {
+            drop(Box::from_raw(provider));
+        }
+        Ok(())
+    });
+}
+
+/// Sync the local cache with the server, then save it. This blocks until the sync is over.
+///
+/// Returns whether the sync has completed without errors (a sync that has failed can safely be started again)
+///
+/// # Safety
+/// `provider` must have been returned by [`kf_provider_new`]
+#[no_mangle]
+pub unsafe extern "C" fn kf_provider_sync(provider: *mut KfProvider) -> bool {
+    catch(|| {
+        let kf = provider.as_mut().ok_or("provider is NULL")?;
+        let result = kf.runtime.block_on(kf.provider.sync_with(SyncOptions::default()));
+        kf.provider.local().save()?;
+        if result.is_success() == false {
+            return Err(result.error().unwrap_or("Some items could not be synced").into());
+        }
+        Ok(())
+    })
+    .is_some()
+}
+
+/// List the tasks of the local cache (tasks that have been deleted but not synced yet are left out).
+///
+/// Returns `NULL` on error
+///
+/// # Safety
+/// `provider` must have been returned by [`kf_provider_new`]
+#[no_mangle]
+pub unsafe extern "C" fn kf_provider_tasks(provider: *const KfProvider) -> *mut KfTaskList {
+    catch(|| {
+        let kf = provider.as_ref().ok_or("provider is NULL")?;
+        let mut tasks = Vec::new();
+        for (cal_url, cal) in kf.provider.local().get_calendars_sync()? {
+            let cal = cal.read().unwrap();
+            for (url, item) in cal.get_items_sync()? {
+                let task = match item {
+                    Item::Task(task) => task,
+                    _ => continue,
+                };
+                if let SyncStatus::LocallyDeleted(_) = task.sync_status() {
+                    continue;
+                }
+                tasks.push(KfTask {
+                    url: to_c_string(url.as_str()),
+                    calendar_url: to_c_string(cal_url.as_str()),
+                    name: to_c_string(task.name()),
+                    completed: task.completed(),
+                });
+            }
+        }
+
+        let tasks = tasks.into_boxed_slice();
+        let len = tasks.len();
+        let tasks = Box::into_raw(tasks) as *mut KfTask;
+        Ok(Box::into_raw(Box::new(KfTaskList { tasks, len })))
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Free a list of tasks. Freeing `NULL` does nothing
+///
+/// # Safety
+/// `list` must have been returned by [`kf_provider_tasks`], and must not be used afterwards
+#[no_mangle]
+pub unsafe extern "C" fn kf_task_list_free(list: *mut KfTaskList) {
+    catch(|| {
+        if list.is_null() {
+            return Ok(());
+        }
+        let list = Box::from_raw(list);
+        let tasks = Box::from_raw(ptr::slice_from_raw_parts_mut(list.tasks, list.len));
+        for task in tasks.iter() {
+            drop(CString::from_raw(task.url));
+            drop(CString::from_raw(task.calendar_url));
+            drop(CString::from_raw(task.name));
+        }
+        Ok(())
+    });
+}
+
+/// Complete (or un-complete) a task of the local cache, according to the subtask policy of the provider (see [`crate::provider::SubtaskCompletionPolicy`]).
+/// The change is pushed to the server during the next sync.
+///
+/// Returns `false` on error
+///
+/// # Safety
+/// `provider` must have been returned by [`kf_provider_new`], and `task_url` must be a valid NUL-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn kf_provider_set_task_completion(provider: *mut KfProvider, task_url: *const c_char, completed: bool) -> bool {
+    catch(|| {
+        let kf = provider.as_mut().ok_or("provider is NULL")?;
+        let task_url = Url::parse(read_str(task_url, "task_url")?)?;
+        kf.runtime.block_on(kf.provider.set_task_completion(&task_url, completed))?;
+        Ok(())
+    })
+    .is_some()
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::calendar::SupportedComponents;
+    use crate::task::Task;
+    use crate::traits::CalDavSource;
+
+    #[test]
+    fn test_ffi_tasks() {
+        let cache_path = CString::new("test_cache/ffi").unwrap();
+        let _ = std::fs::remove_dir_all("test_cache/ffi");
+        let url = CString::new("https://caldav.com/").unwrap();
+        let user = CString::new("john").unwrap();
+
+        unsafe {
+            assert!(kf_provider_new(ptr::null(), user.as_ptr(), user.as_ptr(), cache_path.as_ptr()).is_null());
+            assert_eq!(CStr::from_ptr(kf_last_error()).to_str().unwrap(), "url is NULL");
+
+            let provider = kf_provider_new(url.as_ptr(), user.as_ptr(), user.as_ptr(), cache_path.as_ptr());
+            assert!(provider.is_null() == false);
+
+            let cal_url = Url::parse("https://caldav.com/shopping").unwrap();
+            let task = Task::new(String::from("Milk"), false, &cal_url);
+            let task_url = CString::new(task.url().as_str()).unwrap();
+            {
+                let kf = &mut *provider;
+                let cal = kf.runtime.block_on(kf.provider.local_mut().create_calendar(cal_url.clone(), "Shopping".to_string(), SupportedComponents::TODO, None)).unwrap();
+                cal.write().unwrap().add_item_sync(Item::Task(task)).unwrap();
+            }
+
+            assert!(kf_provider_set_task_completion(provider, task_url.as_ptr(), true));
+            let list = kf_provider_tasks(provider);
+            assert_eq!((*list).len, 1);
+            let task = &*(*list).tasks;
+            assert_eq!(CStr::from_ptr(task.name).to_str().unwrap(), "Milk");
+            assert!(task.completed);
+            kf_task_list_free(list);
+
+            kf_provider_free(provider);
+        }
+    }
+
+    #[test]
+    fn test_ffi_panics_are_caught() {
+        let result: Option<()> = catch(|| panic!("something went wrong"));
+        assert!(result.is_none());
+        let message = unsafe { CStr::from_ptr(kf_last_error()) };
+        assert_eq!(message.to_str().unwrap(), "Internal error (panic): something went wrong");
+    }
+}
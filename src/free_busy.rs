@@ -0,0 +1,67 @@
+//! Free/busy time (iCal `VFREEBUSY`), as returned by CalDAV servers (see [`Client::free_busy`](crate::client::Client::free_busy))
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Why a period is busy (iCal `FBTYPE` parameter)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum FreeBusyType {
+    Busy,
+    /// The time cannot be scheduled (e.g. outside of working hours)
+    BusyUnavailable,
+    /// The time is taken by tentative events
+    BusyTentative,
+    /// Any other (e.g. `X-`) type. RFC5545 says they should be treated as busy
+    Other(String),
+}
+
+impl FreeBusyType {
+    pub fn as_ical_str(&self) -> &str {
+        match self {
+            FreeBusyType::Busy => "BUSY",
+            FreeBusyType::BusyUnavailable => "BUSY-UNAVAILABLE",
+            FreeBusyType::BusyTentative => "BUSY-TENTATIVE",
+            FreeBusyType::Other(s) => s,
+        }
+    }
+}
+
+impl From<&str> for FreeBusyType {
+    fn from(s: &str) -> Self {
+        match s {
+            "BUSY" => FreeBusyType::Busy,
+            "BUSY-UNAVAILABLE" => FreeBusyType::BusyUnavailable,
+            "BUSY-TENTATIVE" => FreeBusyType::BusyTentative,
+            other => FreeBusyType::Other(other.to_string()),
+        }
+    }
+}
+
+/// A period of time that is not available
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BusyPeriod {
+    start: DateTime<Utc>,
+    /// Excluded
+    end: DateTime<Utc>,
+    kind: FreeBusyType,
+}
+
+impl BusyPeriod {
+    pub fn new(start: DateTime<Utc>, end: DateTime<Utc>, kind: FreeBusyType) -> Self {
+        Self { start, end, kind }
+    }
+
+    pub fn start(&self) -> &DateTime<Utc> { &self.start }
+    pub fn end(&self) -> &DateTime<Utc> { &self.end }
+    pub fn kind(&self) -> &FreeBusyType { &self.kind }
+
+    /// Whether this period overlaps with the time between `start` (included) and `end` (excluded)
+    pub fn overlaps(&self, start: &DateTime<Utc>, end: &DateTime<Utc>) -> bool {
+        self.start < *end && *start < self.end
+    }
+}
+
+/// Whether the time between `start` (included) and `end` (excluded) is free, given some busy periods
+pub fn is_free(busy_periods: &[BusyPeriod], start: &DateTime<Utc>, end: &DateTime<Utc>) -> bool {
+    busy_periods.iter().all(|period| period.overlaps(start, end) == false)
+}
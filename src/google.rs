@@ -0,0 +1,902 @@
+//! A remote source that talks to the Google Calendar and Google Tasks REST APIs, rather than to the CalDAV endpoint of Google
+//!
+//! This is only available with the `google` feature. A [`GoogleSource`] can be the remote source of a [`Provider`](crate::provider::Provider) (see [`crate::GoogleProvider`]),
+//! for accounts whose CalDAV endpoint is not suitable (e.g. service accounts, that have no CalDAV access).
+//!
+//! Requests are authenticated with OAuth2 access tokens, that need the `https://www.googleapis.com/auth/calendar` and `https://www.googleapis.com/auth/tasks` scopes.
+//! They are usually given by a [`TokenProvider`](crate::auth::TokenProvider), that can be backed by any OAuth2 library (for user accounts, or for service accounts).
+//!
+//! Google calendars are mapped to calendars that support events, and Google task lists to calendars that support tasks. The mapping is not lossless:
+//! * only the main properties of items are synced (e.g. events keep their dates, recurrence rules, descriptions and locations, but reminders and attendees are only kept by Google)
+//! * modified instances of recurring events are downloaded, but changes to them are not pushed
+//! * Google Tasks chooses the IDs of new tasks by itself. Tasks that have been created locally are moved to a URL that is derived from their ID once they have been pushed
+//! * calendars cannot be created by a sync (Google chooses their IDs as well)
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex, RwLock};
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use csscolorparser::Color;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Method;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::auth::Authentication;
+use crate::calendar::{CalendarProperties, Privileges, SupportedComponents};
+use crate::error::ServerError;
+use crate::event::{EventBuilder, EventStatus};
+use crate::item::{Item, SyncStatus, Uid, VersionTag};
+use crate::recurrence::Recurrence;
+use crate::resource::Resource;
+use crate::task::{CompletionStatus, TaskBuilder};
+use crate::traits::{BaseCalendar, CalDavSource, DavCalendar};
+use crate::{Event, Task};
+
+static CALENDAR_API: &str = "https://www.googleapis.com/calendar/v3/";
+static TASKS_API: &str = "https://tasks.googleapis.com/tasks/v1/";
+static TASKS_HOST: &str = "tasks.googleapis.com";
+
+/// The private extended property of events that stores the last segment of their URL, when it differs from their Google ID
+static NAME_PROPERTY: &str = "kitchenFridgeName";
+
+/// Google calendars and task lists, as a [`CalDavSource`]
+#[derive(Debug)]
+pub struct GoogleSource {
+    /// The settings requests are sent with (credentials, retry policy, TLS settings, proxy, etc.)
+    resource: Resource,
+    /// Calendars that are not in the calendar list of the account, but that should be synced anyway
+    extra_calendar_ids: Vec<String>,
+    /// Whether task lists are synced
+    tasks_enabled: bool,
+
+    cached_calendars: Mutex<Option<HashMap<Url, Arc<RwLock<GoogleCalendar>>>>>,
+}
+
+impl GoogleSource {
+    /// A source for the calendars and task lists of the account `authentication` gives access to (usually [`Authentication::TokenProvider`])
+    pub fn new(authentication: Authentication) -> Self {
+        Self::with_resource(Resource::with_authentication(Url::parse(CALENDAR_API).unwrap(/* this is a valid constant */), authentication))
+    }
+
+    /// A source whose requests are sent with the same settings as `resource` (credentials, retry policy, TLS settings, proxy and transport). The URL of `resource` does not matter
+    pub fn with_resource(resource: Resource) -> Self {
+        Self {
+            resource,
+            extra_calendar_ids: Vec::new(),
+            tasks_enabled: true,
+            cached_calendars: Mutex::new(None),
+        }
+    }
+
+    /// Also sync a calendar that is not in the calendar list of the account, given its ID.
+    ///
+    /// This is useful for service accounts: calendars that are shared with them are not added to their calendar lists
+    pub fn add_calendar_id<S: ToString>(&mut self, id: S) {
+        self.extra_calendar_ids.push(id.to_string());
+        *self.cached_calendars.lock().unwrap() = None;
+    }
+
+    pub fn tasks_enabled(&self) -> bool { self.tasks_enabled }
+
+    /// Whether task lists are synced (this defaults to `true`). Service accounts usually have no access to Google Tasks
+    pub fn set_tasks_enabled(&mut self, enabled: bool) {
+        self.tasks_enabled = enabled;
+        *self.cached_calendars.lock().unwrap() = None;
+    }
+
+    async fn populate_calendars(&self) -> Result<(), Box<dyn Error>> {
+        if self.cached_calendars.lock().unwrap().is_some() {
+            return Ok(());
+        }
+
+        let mut calendars = HashMap::new();
+        let entries: Vec<CalendarListEntry> = list_all(&self.resource, api_url(CALENDAR_API, &["users", "me", "calendarList"]), Vec::new()).await?;
+        for entry in entries {
+            let calendar = GoogleCalendar::from_list_entry(entry, &self.resource);
+            calendars.insert(calendar.url().clone(), Arc::new(RwLock::new(calendar)));
+        }
+        for id in &self.extra_calendar_ids {
+            let text = api_request(&self.resource, Method::GET, api_url(CALENDAR_API, &["calendars", id.as_str()]), Vec::new(), None).await?;
+            let entry: CalendarListEntry = serde_json::from_str(&text)?;
+            let calendar = GoogleCalendar::from_list_entry(entry, &self.resource);
+            calendars.entry(calendar.url().clone()).or_insert_with(|| Arc::new(RwLock::new(calendar)));
+        }
+        if self.tasks_enabled {
+            let lists: Vec<TaskList> = list_all(&self.resource, api_url(TASKS_API, &["users", "@me", "lists"]), Vec::new()).await?;
+            for list in lists {
+                let url = api_url(TASKS_API, &["lists", list.id.as_str(), "tasks", ""]);
+                let calendar = GoogleCalendar::new(list.title.unwrap_or_default(), self.resource.with_url(url.clone()), SupportedComponents::TODO, None);
+                calendars.insert(url, Arc::new(RwLock::new(calendar)));
+            }
+        }
+
+        *self.cached_calendars.lock().unwrap() = Some(calendars);
+        Ok(())
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl CalDavSource<GoogleCalendar> for GoogleSource {
+    async fn get_calendars(&self) -> Result<HashMap<Url, Arc<RwLock<GoogleCalendar>>>, crate::Error> {
+        self.populate_calendars().await?;
+
+        match &*self.cached_calendars.lock().unwrap() {
+            Some(cals) => Ok(cals.clone()),
+            None => Err("No calendars available".into()),
+        }
+    }
+
+    async fn get_calendar(&self, url: &Url) -> Option<Arc<RwLock<GoogleCalendar>>> {
+        if let Err(err) = self.populate_calendars().await {
+            log::warn!("Unable to fetch calendars: {}", err);
+            return None;
+        }
+
+        self.cached_calendars.lock().unwrap()
+            .as_ref()
+            .and_then(|cals| cals.get(url))
+            .map(|cal| cal.clone())
+    }
+
+    async fn create_calendar(&mut self, url: Url, name: String, _supported_components: SupportedComponents, _color: Option<Color>) -> Result<Arc<RwLock<GoogleCalendar>>, crate::Error> {
+        Err(format!("Unable to create calendar {} ({}): Google chooses the URLs of new calendars, they must be created with Google Calendar or Google Tasks", name, url).into())
+    }
+
+    async fn delete_calendar(&mut self, url: &Url) -> Result<(), crate::Error> {
+        api_request(&self.resource, Method::DELETE, metadata_url(url)?, Vec::new(), None).await?;
+
+        if let Some(cals) = self.cached_calendars.lock().unwrap().as_mut() {
+            cals.remove(url);
+        }
+        Ok(())
+    }
+}
+
+
+
+/// A Google calendar (whose URL looks like `https://www.googleapis.com/calendar/v3/calendars/{id}/events/`),
+/// or a Google task list (whose URL looks like `https://tasks.googleapis.com/tasks/v1/lists/{id}/tasks/`)
+#[derive(Debug)]
+pub struct GoogleCalendar {
+    name: String,
+    resource: Resource,
+    supported_components: SupportedComponents,
+    color: Option<Color>,
+    description: Option<String>,
+    privileges: Privileges,
+
+    /// The items, as they have been listed by the last call to [`DavCalendar::get_item_version_tags`]
+    listed_items: Mutex<Option<HashMap<Url, Item>>>,
+    /// The IDs the items have in the API, by item URL
+    ids: Mutex<HashMap<Url, String>>,
+}
+
+impl GoogleCalendar {
+    fn from_list_entry(entry: CalendarListEntry, resource: &Resource) -> Self {
+        let url = api_url(CALENDAR_API, &["calendars", entry.id.as_str(), "events", ""]);
+        let name = entry.summary_override.or(entry.summary).unwrap_or_else(|| entry.id.clone());
+        let color = entry.background_color.and_then(|color| csscolorparser::parse(&color).ok());
+        let mut calendar = Self::new(name, resource.with_url(url), SupportedComponents::EVENT, color);
+        calendar.description = entry.description;
+        calendar.privileges = match entry.access_role.as_deref() {
+            Some("owner") => Privileges::all(),
+            Some("writer") => Privileges::READ | Privileges::WRITE_CONTENT | Privileges::BIND | Privileges::UNBIND,
+            Some("reader") | Some("freeBusyReader") => Privileges::READ,
+            _ => Privileges::default(),
+        };
+        calendar
+    }
+
+    fn is_task_list(&self) -> bool {
+        self.url().host_str() == Some(TASKS_HOST)
+    }
+
+    /// The URL items are listed (and created) at
+    fn items_url(&self) -> Url {
+        trim_slash(self.url().clone())
+    }
+
+    /// The URL of an item in the API
+    fn item_api_url(&self, item_url: &Url) -> Url {
+        let id = self.ids.lock().unwrap().get(item_url).cloned();
+        let id = id.unwrap_or_else(|| {
+            let name = last_segment(item_url);
+            match self.is_task_list() {
+                true => name,
+                false => event_id(&name),
+            }
+        });
+        child_url(self.url(), &id)
+    }
+
+    /// List every item of this calendar, and remember their IDs
+    async fn list_items(&self) -> Result<HashMap<Url, Item>, Box<dyn Error>> {
+        let (ids, items) = match self.is_task_list() {
+            false => {
+                let events: Vec<GoogleEvent> = list_all(&self.resource, self.items_url(), vec![("maxResults", "2500".to_string())]).await?;
+                events_to_items(events, self.url())
+            },
+            true => {
+                let query = vec![("maxResults", "100".to_string()), ("showCompleted", "true".to_string()), ("showHidden", "true".to_string())];
+                let tasks: Vec<GoogleTask> = list_all(&self.resource, self.items_url(), query).await?;
+                tasks_to_items(tasks, self.url())
+            },
+        };
+        self.ids.lock().unwrap().extend(ids);
+        Ok(items)
+    }
+
+    /// Send an item to the API, and return its new version tag
+    async fn push_item(&self, item: &Item, method: Method, url: Url, if_match: Option<&VersionTag>) -> Result<SyncStatus, Box<dyn Error>> {
+        let body = match item {
+            Item::Event(event) => {
+                // Only new events are given an ID
+                let id = match if_match {
+                    None => Some(event_id(&last_segment(item.url()))),
+                    Some(_) => None,
+                };
+                serde_json::to_string(&GoogleEvent::from_event(event, id, last_segment(item.url())))?
+            },
+            Item::Task(task) => serde_json::to_string(&GoogleTask::from_task(task))?,
+            Item::Journal(_) => return Err(format!("Google does not support journal entries (such as {})", item.url()).into()),
+            Item::Contact(_) => return Err(format!("Google Calendar does not support contacts (such as {})", item.url()).into()),
+        };
+
+        let mut request = self.resource.http()
+            .request(method, url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body);
+        if let Some(etag) = if_match {
+            request = request.header("If-Match", etag.as_str());
+        }
+        let response = self.resource.send(request).await?;
+        if response.status().is_success() == false {
+            return Err(ServerError::from_response(response).await.into());
+        }
+
+        let reply: ItemReply = serde_json::from_str(&response.text().await?)?;
+        self.ids.lock().unwrap().insert(item.url().clone(), reply.id);
+        Ok(SyncStatus::Synced(VersionTag::from(reply.etag)))
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl BaseCalendar for GoogleCalendar {
+    fn name(&self) -> &str { &self.name }
+    fn url(&self) -> &Url { self.resource.url() }
+    fn supported_components(&self) -> SupportedComponents {
+        self.supported_components
+    }
+    fn color(&self) -> Option<&Color> {
+        self.color.as_ref()
+    }
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+    fn privileges(&self) -> Privileges {
+        self.privileges
+    }
+
+    async fn add_item(&mut self, item: Item) -> Result<SyncStatus, crate::Error> {
+        let url = match &item {
+            // Importing (rather than inserting) events keeps their UIDs
+            Item::Event(_) => child_url(self.url(), "import"),
+            _ => self.items_url(),
+        };
+        Ok(self.push_item(&item, Method::POST, url, None).await?)
+    }
+
+    async fn update_item(&mut self, item: Item) -> Result<SyncStatus, crate::Error> {
+        let old_etag = match item.sync_status() {
+            SyncStatus::NotSynced => return Err("Cannot update an item that has not been synced already".into()),
+            SyncStatus::Synced(_) => return Err("Cannot update an item that has not changed".into()),
+            SyncStatus::LocallyModified(etag) => etag.clone(),
+            SyncStatus::LocallyDeleted(etag) => etag.clone(),
+        };
+        let url = self.item_api_url(item.url());
+        // Fields this crate does not know about (e.g. attendees or reminders) are kept by a PATCH
+        Ok(self.push_item(&item, Method::PATCH, url, Some(&old_etag)).await?)
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl DavCalendar for GoogleCalendar {
+    fn new(name: String, resource: Resource, supported_components: SupportedComponents, color: Option<Color>) -> Self {
+        Self {
+            name, resource, supported_components, color,
+            description: None,
+            privileges: Privileges::default(),
+            listed_items: Mutex::new(None),
+            ids: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn get_item_version_tags(&self) -> Result<HashMap<Url, VersionTag>, crate::Error> {
+        let items = self.list_items().await?;
+        let tags = items.iter()
+            .filter_map(|(url, item)| item.sync_status().version_tag().map(|tag| (url.clone(), tag.clone())))
+            .collect();
+        *self.listed_items.lock().unwrap() = Some(items);
+        Ok(tags)
+    }
+
+    async fn get_item_by_url(&self, url: &Url) -> Result<Option<Item>, crate::Error> {
+        let mut items = self.get_items_by_url(std::slice::from_ref(url)).await?;
+        Ok(items.pop().flatten())
+    }
+
+    async fn get_items_by_url(&self, urls: &[Url]) -> Result<Vec<Option<Item>>, crate::Error> {
+        // Listing items already downloads them entirely
+        if self.listed_items.lock().unwrap().is_none() {
+            self.get_item_version_tags().await?;
+        }
+        let listed = self.listed_items.lock().unwrap();
+        let listed = listed.as_ref().ok_or("Items have not been listed")?;
+        Ok(urls.iter().map(|url| listed.get(url).cloned()).collect())
+    }
+
+    fn added_item_url(&self, item_url: &Url) -> Option<Url> {
+        // Events keep their URLs, since their names are stored in their extended properties
+        if self.is_task_list() == false {
+            return None;
+        }
+        let mut ids = self.ids.lock().unwrap();
+        let id = ids.get(item_url)?.clone();
+        let url = child_url(self.url(), &id);
+        if url == *item_url {
+            return None;
+        }
+        ids.remove(item_url);
+        ids.insert(url.clone(), id);
+        Some(url)
+    }
+
+    async fn delete_item(&mut self, item_url: &Url) -> Result<(), crate::Error> {
+        api_request(&self.resource, Method::DELETE, self.item_api_url(item_url), Vec::new(), None).await?;
+        self.ids.lock().unwrap().remove(item_url);
+        Ok(())
+    }
+
+    async fn update_properties(&mut self, properties: &CalendarProperties) -> Result<(), crate::Error> {
+        let metadata_url = metadata_url(self.url())?;
+        if self.is_task_list() {
+            let body = serde_json::json!({ "title": properties.name });
+            api_request(&self.resource, Method::PATCH, metadata_url, Vec::new(), Some(body.to_string())).await?;
+        } else {
+            let body = serde_json::json!({ "summary": properties.name, "description": properties.description });
+            api_request(&self.resource, Method::PATCH, metadata_url, Vec::new(), Some(body.to_string())).await?;
+
+            // Colors are settings of the calendar list of the user, rather than properties of the calendar
+            if let Some(color) = &properties.color {
+                let list_url = Url::parse(&format!("{}users/me/calendarList/{}", CALENDAR_API, encoded_id(self.url())?))?;
+                let body = serde_json::json!({ "backgroundColor": color.to_hex_string(), "foregroundColor": "#000000" });
+                api_request(&self.resource, Method::PATCH, list_url, vec![("colorRgbFormat", "true".to_string())], Some(body.to_string())).await?;
+            }
+        }
+
+        // Google calendars have no order
+        self.name = properties.name.clone();
+        self.description = properties.description.clone();
+        self.color = properties.color.clone();
+        Ok(())
+    }
+}
+
+
+
+/// Send a request to the API, and return the body of its (successful) reply
+async fn api_request(resource: &Resource, method: Method, url: Url, query: Vec<(&'static str, String)>, body: Option<String>) -> Result<String, Box<dyn Error>> {
+    let mut request = resource.http()
+        .request(method, url)
+        .query(&query);
+    if let Some(body) = body {
+        request = request.header(CONTENT_TYPE, "application/json").body(body);
+    }
+    let response = resource.send(request).await?;
+    if response.status().is_success() == false {
+        return Err(ServerError::from_response(response).await.into());
+    }
+    Ok(response.text().await?)
+}
+
+/// Get every page of a list
+async fn list_all<T: DeserializeOwned + Send>(resource: &Resource, url: Url, query: Vec<(&'static str, String)>) -> Result<Vec<T>, Box<dyn Error>> {
+    let mut items = Vec::new();
+    let mut page_token = None;
+    loop {
+        let mut page_query = query.clone();
+        if let Some(token) = page_token {
+            page_query.push(("pageToken", token));
+        }
+        let text = api_request(resource, Method::GET, url.clone(), page_query, None).await?;
+        let page: Page<T> = serde_json::from_str(&text)?;
+        items.extend(page.items);
+        match page.next_page_token {
+            None => return Ok(items),
+            Some(token) => page_token = Some(token),
+        }
+    }
+}
+
+/// A URL of an API, from its (unencoded) path segments
+fn api_url(api: &str, segments: &[&str]) -> Url {
+    let mut url = Url::parse(api).unwrap(/* this is a valid constant */);
+    url.path_segments_mut().unwrap(/* this is an HTTP URL */).pop_if_empty().extend(segments);
+    url
+}
+
+fn child_url(parent: &Url, segment: &str) -> Url {
+    let mut url = parent.clone();
+    url.path_segments_mut().unwrap(/* this is an HTTP URL */).pop_if_empty().push(segment);
+    url
+}
+
+fn trim_slash(mut url: Url) -> Url {
+    url.path_segments_mut().unwrap(/* this is an HTTP URL */).pop_if_empty();
+    url
+}
+
+/// The ID of a calendar or a task list, from its URL (as it is encoded in this URL)
+fn encoded_id(calendar_url: &Url) -> Result<&str, Box<dyn Error>> {
+    // i.e. `calendar/v3/calendars/{id}/events/` or `tasks/v1/lists/{id}/tasks/`
+    calendar_url.path_segments()
+        .and_then(|mut segments| segments.nth(3))
+        .ok_or_else(|| format!("Invalid Google calendar URL {}", calendar_url).into())
+}
+
+/// The URL of the properties of a calendar or a task list
+fn metadata_url(calendar_url: &Url) -> Result<Url, Box<dyn Error>> {
+    match calendar_url.host_str() == Some(TASKS_HOST) {
+        true => Ok(Url::parse(&format!("{}users/@me/lists/{}", TASKS_API, encoded_id(calendar_url)?))?),
+        false => Ok(trim_slash(calendar_url.join("..")?)),
+    }
+}
+
+fn last_segment(url: &Url) -> String {
+    url.path_segments().and_then(|segments| segments.last()).unwrap_or_default().to_string()
+}
+
+/// The Google ID of an event, from the last segment of its URL.
+///
+/// Google only accepts lowercase letters from `a` to `v` and digits in IDs (e.g. hyphenated UUIDs are not valid IDs)
+fn event_id(name: &str) -> String {
+    let id = name.replace('-', "").to_ascii_lowercase();
+    let is_valid = id.len() >= 5 && id.chars().all(|c| matches!(c, '0'..='9' | 'a'..='v'));
+    match is_valid {
+        true => id,
+        false => uuid::Uuid::new_v4().to_simple().to_string(),
+    }
+}
+
+/// Turn the events of a calendar into items. Modified instances of recurring events are attached to their recurring events.
+///
+/// Returns the Google IDs of the items, and the items
+fn events_to_items(events: Vec<GoogleEvent>, calendar_url: &Url) -> (HashMap<Url, String>, HashMap<Url, Item>) {
+    let (instances, events): (Vec<_>, Vec<_>) = events.into_iter().partition(|event| event.recurring_event_id.is_some());
+
+    let mut ids = HashMap::new();
+    let mut urls = HashMap::new();
+    let mut etags: HashMap<Url, Vec<String>> = HashMap::new();
+    let mut parsed = HashMap::new();
+    for google_event in events {
+        let id = match google_event.id.clone() {
+            None => {
+                log::warn!("Ignoring an event without ID in {}", calendar_url);
+                continue;
+            },
+            Some(id) => id,
+        };
+        let name = google_event.extended_properties.private.get(NAME_PROPERTY).cloned().unwrap_or_else(|| id.clone());
+        let url = child_url(calendar_url, &name);
+        etags.insert(url.clone(), google_event.etag.iter().cloned().collect());
+        match google_event.to_event(url.clone(), None) {
+            Err(err) => log::warn!("Unable to read event {}: {}", id, err),
+            Ok(event) => {
+                urls.insert(id.clone(), url.clone());
+                ids.insert(url.clone(), id);
+                parsed.insert(url, event);
+            },
+        }
+    }
+
+    let mut overrides: HashMap<Url, Vec<Event>> = HashMap::new();
+    for google_instance in instances {
+        let url = match google_instance.recurring_event_id.as_ref().and_then(|id| urls.get(id)) {
+            None => continue,
+            Some(url) => url.clone(),
+        };
+        let etag = google_instance.etag.clone();
+        match google_instance.to_event(url.clone(), parsed.get(&url)) {
+            Err(err) => log::warn!("Unable to read a modified instance of {}: {}", url, err),
+            Ok(instance) => {
+                etags.entry(url.clone()).or_default().extend(etag);
+                overrides.entry(url).or_default().push(instance);
+            },
+        }
+    }
+
+    let mut items = HashMap::new();
+    for (url, mut event) in parsed {
+        if let Some(instances) = overrides.remove(&url) {
+            event = event.with_overrides(instances);
+        }
+        // Modified instances have their own ETags, that must change the version tags of their recurring events
+        if let Some(item_etags) = etags.get(&url).filter(|etags| etags.len() > 1) {
+            let tag = format!("{:x}", md5::compute(item_etags.join(",")));
+            event.set_sync_status(SyncStatus::Synced(VersionTag::from(tag)));
+        }
+        items.insert(url, Item::Event(event));
+    }
+
+    (ids, items)
+}
+
+/// Turn the tasks of a task list into items, whose URLs are derived from their Google IDs. Returns the Google IDs of the items, and the items
+fn tasks_to_items(tasks: Vec<GoogleTask>, calendar_url: &Url) -> (HashMap<Url, String>, HashMap<Url, Item>) {
+    let mut ids = HashMap::new();
+    let mut items = HashMap::new();
+    for google_task in tasks.into_iter().filter(|task| task.deleted == false) {
+        let id = match google_task.id.clone() {
+            None => {
+                log::warn!("Ignoring a task without ID in {}", calendar_url);
+                continue;
+            },
+            Some(id) => id,
+        };
+        let url = child_url(calendar_url, &id);
+        match google_task.to_task(url.clone()) {
+            Err(err) => log::warn!("Unable to read task {}: {}", id, err),
+            Ok(task) => {
+                ids.insert(url.clone(), id);
+                items.insert(url, Item::Task(task));
+            },
+        }
+    }
+    (ids, items)
+}
+
+
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Page<T> {
+    #[serde(default = "Vec::new")]
+    items: Vec<T>,
+    next_page_token: Option<String>,
+}
+
+/// An entry of the calendar list of the user (or a calendar, which has fewer fields)
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CalendarListEntry {
+    id: String,
+    summary: Option<String>,
+    summary_override: Option<String>,
+    description: Option<String>,
+    background_color: Option<String>,
+    access_role: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TaskList {
+    id: String,
+    title: Option<String>,
+}
+
+/// What the API replies when an item is created or modified
+#[derive(Deserialize)]
+struct ItemReply {
+    id: String,
+    etag: String,
+}
+
+/// A date (for all-day events) or a date-time
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GoogleTime {
+    date: Option<NaiveDate>,
+    date_time: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time_zone: Option<String>,
+}
+
+impl GoogleTime {
+    fn new(date: &DateTime<Utc>, all_day: bool, time_zone: Option<String>) -> Self {
+        match all_day {
+            true => Self { date: Some(date.naive_utc().date()), date_time: None, time_zone: None },
+            false => Self { date: None, date_time: Some(*date), time_zone },
+        }
+    }
+
+    /// The date-time (at midnight UTC for dates), and whether this is a date
+    fn to_utc(&self) -> Result<(DateTime<Utc>, bool), Box<dyn Error>> {
+        match (&self.date_time, &self.date) {
+            (Some(date_time), _) => Ok((*date_time, false)),
+            (None, Some(date)) => Ok((Utc.from_utc_datetime(&date.and_hms(0, 0, 0)), true)),
+            (None, None) => Err("Missing date".into()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct ExtendedProperties {
+    #[serde(default)]
+    private: HashMap<String, String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GoogleEvent {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    #[serde(skip_serializing)]
+    etag: Option<String>,
+    #[serde(rename = "iCalUID", skip_serializing_if = "Option::is_none")]
+    ical_uid: Option<String>,
+    summary: Option<String>,
+    description: Option<String>,
+    location: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<String>,
+    start: Option<GoogleTime>,
+    end: Option<GoogleTime>,
+    #[serde(default)]
+    recurrence: Vec<String>,
+    #[serde(default)]
+    extended_properties: ExtendedProperties,
+    #[serde(skip_serializing)]
+    created: Option<DateTime<Utc>>,
+    #[serde(skip_serializing)]
+    updated: Option<DateTime<Utc>>,
+    /// Set for modified instances of recurring events
+    #[serde(skip_serializing)]
+    recurring_event_id: Option<String>,
+    #[serde(skip_serializing)]
+    original_start_time: Option<GoogleTime>,
+}
+
+impl GoogleEvent {
+    /// `name` is the last segment of the URL of the event
+    fn from_event(event: &Event, id: Option<String>, name: String) -> Self {
+        // Google requires a timezone for recurring events
+        let time_zone = event.tzid().map(String::from)
+            .or_else(|| event.recurrence().map(|_| "UTC".to_string()));
+        let status = match event.status() {
+            None | Some(EventStatus::Other(_)) => None,
+            Some(status) => Some(status.as_ical_str().to_ascii_lowercase()),
+        };
+
+        Self {
+            ical_uid: id.as_ref().map(|_| event.uid().to_string()),
+            id,
+            summary: Some(event.name().to_string()),
+            description: event.description().map(String::from),
+            location: event.location().map(String::from),
+            status,
+            start: Some(GoogleTime::new(event.start(), event.is_all_day(), time_zone.clone())),
+            end: Some(GoogleTime::new(event.end(), event.is_all_day(), time_zone)),
+            recurrence: event.recurrence().map(|rule| vec![format!("RRULE:{}", rule)]).unwrap_or_default(),
+            extended_properties: ExtendedProperties { private: std::iter::once((NAME_PROPERTY.to_string(), name)).collect() },
+            ..Self::default()
+        }
+    }
+
+    /// Build an event (or a modified instance of `recurring_event`)
+    fn to_event(self, url: Url, recurring_event: Option<&Event>) -> Result<Event, Box<dyn Error>> {
+        let recurrence_id = self.original_start_time.as_ref().map(|time| time.to_utc()).transpose()?.map(|(date, _)| date);
+        let (start, all_day) = match (&self.start, recurring_event, recurrence_id) {
+            (Some(start), _, _) => start.to_utc()?,
+            // Cancelled instances only have their original start
+            (None, Some(recurring_event), Some(recurrence_id)) => (recurrence_id, recurring_event.is_all_day()),
+            _ => return Err("Missing start".into()),
+        };
+        let end = match (&self.end, recurring_event) {
+            (Some(end), _) => end.to_utc()?.0,
+            (None, Some(recurring_event)) => start + recurring_event.duration(),
+            (None, None) => return Err("Missing end".into()),
+        };
+        let uid = match recurring_event {
+            Some(recurring_event) => recurring_event.uid().clone(),
+            None => Uid::new(self.ical_uid.as_ref().or(self.id.as_ref()).ok_or("Missing UID")?)?,
+        };
+        let tzid = self.start.as_ref().filter(|start| start.date_time.is_some()).and_then(|start| start.time_zone.clone());
+        let recurrence = self.recurrence.iter()
+            .find_map(|line| line.strip_prefix("RRULE:"))
+            .map(|rule| rule.parse::<Recurrence>())
+            .transpose()?;
+        let status = self.status.map(|status| EventStatus::from(status.to_ascii_uppercase().as_str()));
+
+        let mut builder = EventBuilder::new(self.summary.unwrap_or_default(), start, end, &url)
+            .with_url(url)
+            .with_uid(uid)
+            .with_sync_status(SyncStatus::Synced(VersionTag::from(self.etag.unwrap_or_default())))
+            .with_creation_date(self.created)
+            .with_last_modified(self.updated.unwrap_or_else(Utc::now));
+        if let Some(description) = self.description {
+            builder = builder.with_description(description);
+        }
+        if let Some(location) = self.location {
+            builder = builder.with_location(location);
+        }
+        if let Some(status) = status {
+            builder = builder.with_status(status);
+        }
+        if let Some(tzid) = tzid {
+            builder = builder.with_tzid(tzid);
+        }
+        if all_day {
+            builder = builder.all_day();
+        }
+        if let Some(recurrence) = recurrence {
+            builder = builder.with_recurrence(recurrence);
+        }
+        if let Some(recurrence_id) = recurrence_id {
+            builder = builder.with_recurrence_id(recurrence_id);
+        }
+        // Whatever the server sent is kept as is, even if it does not pass the checks of `EventBuilder::build`
+        Ok(builder.build_unchecked())
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GoogleTask {
+    #[serde(skip_serializing)]
+    id: Option<String>,
+    #[serde(skip_serializing)]
+    etag: Option<String>,
+    title: Option<String>,
+    /// `needsAction` or `completed`
+    status: Option<String>,
+    completed: Option<DateTime<Utc>>,
+    /// Only the date of due dates is used
+    due: Option<DateTime<Utc>>,
+    #[serde(skip_serializing)]
+    updated: Option<DateTime<Utc>>,
+    #[serde(skip_serializing)]
+    parent: Option<String>,
+    #[serde(default, skip_serializing)]
+    deleted: bool,
+}
+
+impl GoogleTask {
+    fn from_task(task: &Task) -> Self {
+        let completed = match task.completion_status() {
+            CompletionStatus::Completed(date) => Some(date.unwrap_or_else(Utc::now)),
+            _ => None,
+        };
+        Self {
+            title: Some(task.name().to_string()),
+            status: Some(match completed { Some(_) => "completed", None => "needsAction" }.to_string()),
+            completed,
+            due: task.due().cloned(),
+            ..Self::default()
+        }
+    }
+
+    fn to_task(self, url: Url) -> Result<Task, Box<dyn Error>> {
+        // Google tasks have no UID, their ID is used instead
+        let uid = Uid::new(self.id.ok_or("Missing ID")?)?;
+        let completion_status = match self.status.as_deref() {
+            Some("completed") => CompletionStatus::Completed(self.completed),
+            _ => CompletionStatus::Uncompleted,
+        };
+        let parent = self.parent.map(Uid::new).transpose()?;
+
+        let mut builder = TaskBuilder::new(self.title.unwrap_or_default(), &url)
+            .with_url(url)
+            .with_uid(uid)
+            .with_completion_status(completion_status)
+            .with_sync_status(SyncStatus::Synced(VersionTag::from(self.etag.unwrap_or_default())))
+            .with_creation_date(None)
+            .with_last_modified(self.updated.unwrap_or_else(Utc::now));
+        if let Some(due) = self.due {
+            builder = builder.with_due(due, true);
+        }
+        if let Some(parent) = parent {
+            builder = builder.with_parent(parent);
+        }
+        Ok(builder.build_unchecked())
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use reqwest::{Request, Response};
+
+    use crate::transport::HttpTransport;
+
+    /// A server that replies with fixed JSON documents, and records the bodies of the requests it receives
+    struct FakeGoogle {
+        received: Mutex<Vec<(String, String, String)>>,
+    }
+
+    #[async_trait]
+    impl HttpTransport for FakeGoogle {
+        async fn execute(&self, request: Request) -> Result<Response, Box<dyn Error>> {
+            let body = request.body().and_then(|body| body.as_bytes()).map(|bytes| String::from_utf8_lossy(bytes).to_string()).unwrap_or_default();
+            self.received.lock().unwrap().push((request.method().to_string(), request.url().path().to_string(), body));
+            let reply = match (request.method().as_str(), request.url().path()) {
+                ("GET", "/calendar/v3/users/me/calendarList") => r##"{"items": [
+                    {"id": "john@example.com", "summary": "John", "backgroundColor": "#ff8000", "accessRole": "owner"},
+                    {"id": "en.usa#holiday@group.v.calendar.google.com", "summary": "Holidays", "accessRole": "reader"}
+                ]}"##,
+                ("GET", "/tasks/v1/users/@me/lists") => r#"{"items": [{"id": "list1", "title": "Groceries"}]}"#,
+                ("GET", "/calendar/v3/calendars/john@example.com/events") => r#"{"items": [
+                    {"id": "weekly1", "etag": "\"1\"", "iCalUID": "weekly@example.com", "summary": "Weekly meeting", "updated": "2021-03-01T10:00:00Z",
+                     "start": {"dateTime": "2021-03-01T10:00:00+01:00", "timeZone": "Europe/Paris"}, "end": {"dateTime": "2021-03-01T11:00:00+01:00", "timeZone": "Europe/Paris"},
+                     "recurrence": ["RRULE:FREQ=WEEKLY;COUNT=4"], "extendedProperties": {"private": {"kitchenFridgeName": "weekly-1"}}},
+                    {"id": "weekly1_20210308T090000Z", "etag": "\"2\"", "recurringEventId": "weekly1", "status": "cancelled",
+                     "originalStartTime": {"dateTime": "2021-03-08T10:00:00+01:00"}},
+                    {"id": "allday", "etag": "\"3\"", "iCalUID": "allday@example.com", "summary": "Day off", "start": {"date": "2021-03-05"}, "end": {"date": "2021-03-06"}}
+                ]}"#,
+                ("POST", "/tasks/v1/lists/list1/tasks") => r#"{"id": "server-id", "etag": "\"4\""}"#,
+                ("GET", "/tasks/v1/lists/list1/tasks") => r#"{"items": [{"id": "server-id", "etag": "\"4\"", "title": "Milk", "status": "needsAction"}]}"#,
+                _ => return Ok(Response::from(http::Response::builder().status(404).body(String::new())?)),
+            };
+            Ok(Response::from(http::Response::builder().status(200).body(reply.to_string())?))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_google_source() {
+        let server = Arc::new(FakeGoogle { received: Mutex::new(Vec::new()) });
+        let mut resource = Resource::with_authentication(Url::parse(CALENDAR_API).unwrap(), Authentication::bearer("t0k3n"));
+        resource.set_transport(Some(server.clone()));
+        let source = GoogleSource::with_resource(resource);
+
+        let calendars = source.get_calendars().await.unwrap();
+        assert_eq!(calendars.len(), 3);
+        let holidays_url = Url::parse("https://www.googleapis.com/calendar/v3/calendars/en.usa%23holiday@group.v.calendar.google.com/events/").unwrap();
+        assert_eq!(calendars[&holidays_url].read().unwrap().is_writable(), false);
+
+        let cal_url = Url::parse("https://www.googleapis.com/calendar/v3/calendars/john@example.com/events/").unwrap();
+        let calendar = source.get_calendar(&cal_url).await.unwrap();
+        let calendar = calendar.read().unwrap();
+        assert_eq!(calendar.name(), "John");
+        assert_eq!(calendar.supported_components(), SupportedComponents::EVENT);
+        let tags = calendar.get_item_version_tags().await.unwrap();
+        assert_eq!(tags.len(), 2);
+
+        let weekly_url = cal_url.join("weekly-1").unwrap();
+        let weekly = match calendar.get_item_by_url(&weekly_url).await.unwrap() {
+            Some(Item::Event(event)) => event,
+            other => panic!("Unexpected item {:?}", other),
+        };
+        assert_eq!(weekly.uid().as_str(), "weekly@example.com");
+        assert_eq!(weekly.tzid(), Some("Europe/Paris"));
+        assert_eq!(weekly.overrides().len(), 1);
+        assert!(weekly.overrides()[0].is_cancelled());
+        assert!(tags[&weekly_url] != VersionTag::from("\"1\"".to_string()));
+        assert_eq!(calendar.item_api_url(&weekly_url).path(), "/calendar/v3/calendars/john@example.com/events/weekly1");
+
+        let list_url = Url::parse("https://tasks.googleapis.com/tasks/v1/lists/list1/tasks/").unwrap();
+        let list = source.get_calendar(&list_url).await.unwrap();
+        let mut list = list.write().unwrap();
+        let task = Task::new("Milk".to_string(), false, &list_url);
+        let task_url = task.url().clone();
+        let status = list.add_item(Item::Task(task)).await.unwrap();
+        assert_eq!(status, SyncStatus::Synced(VersionTag::from("\"4\"".to_string())));
+        let (method, _path, body) = server.received.lock().unwrap().last().cloned().unwrap();
+        assert_eq!(method, "POST");
+        assert!(body.contains(r#""status":"needsAction""#));
+
+        // The task is moved to a URL that is derived from its ID, so that it is found again after a restart
+        let server_url = list_url.join("server-id").unwrap();
+        assert_eq!(list.added_item_url(&task_url), Some(server_url.clone()));
+        assert_eq!(list.item_api_url(&server_url).path(), "/tasks/v1/lists/list1/tasks/server-id");
+        let tags = list.get_item_version_tags().await.unwrap();
+        assert_eq!(tags.keys().collect::<Vec<_>>(), vec![&server_url]);
+    }
+}
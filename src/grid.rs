@@ -0,0 +1,275 @@
+//! Per-day views of calendar events, as displayed in month or week views
+
+use chrono::{DateTime, Datelike, NaiveDate, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use url::Url;
+
+use crate::Event;
+use crate::calendar::Privileges;
+
+/// The part of an event that happens on a given day
+#[derive(Clone, Debug)]
+pub struct GridEntry {
+    calendar_url: Url,
+    item_url: Url,
+    name: String,
+    /// When the event starts and ends on this day (clipped to the boundaries of the day). `None` for all-day events
+    times: Option<(DateTime<Tz>, DateTime<Tz>)>,
+    continued_from_previous_day: bool,
+    continues_next_day: bool,
+    /// The privileges of the calendar this event belongs to
+    privileges: Privileges,
+}
+
+impl GridEntry {
+    pub fn calendar_url(&self) -> &Url { &self.calendar_url }
+    pub fn item_url(&self) -> &Url { &self.item_url }
+    pub fn name(&self) -> &str { &self.name }
+    pub fn is_all_day(&self) -> bool { self.times.is_none() }
+    /// The start and end of the event on this day. `None` for all-day events
+    pub fn times(&self) -> Option<&(DateTime<Tz>, DateTime<Tz>)> { self.times.as_ref() }
+    /// Whether this entry is the continuation of an event that started on a previous day
+    pub fn is_continued_from_previous_day(&self) -> bool { self.continued_from_previous_day }
+    /// Whether this event goes on the day after
+    pub fn continues_next_day(&self) -> bool { self.continues_next_day }
+    /// Whether the server would accept modifications of this event (see [`crate::traits::CompleteCalendar::can_edit`])
+    pub fn can_edit(&self) -> bool { self.privileges.contains(Privileges::WRITE_CONTENT) }
+    /// Whether the server would accept the deletion of this event
+    pub fn can_delete(&self) -> bool { self.privileges.contains(Privileges::UNBIND) }
+}
+
+/// The events of a single day
+#[derive(Clone, Debug)]
+pub struct GridDay {
+    date: NaiveDate,
+    entries: Vec<GridEntry>,
+}
+
+impl GridDay {
+    fn new(date: NaiveDate) -> Self {
+        Self { date, entries: Vec::new() }
+    }
+
+    pub fn date(&self) -> NaiveDate { self.date }
+
+    /// All entries of this day: all-day entries come first, then the other ones, sorted by start time
+    pub fn entries(&self) -> &[GridEntry] { &self.entries }
+
+    pub fn all_day_entries(&self) -> impl Iterator<Item = &GridEntry> + '_ {
+        self.entries.iter().filter(|e| e.is_all_day())
+    }
+
+    pub fn timed_entries(&self) -> impl Iterator<Item = &GridEntry> + '_ {
+        self.entries.iter().filter(|e| e.is_all_day() == false)
+    }
+
+    fn sort(&mut self) {
+        self.entries.sort_by(|a, b| {
+            let key_a = a.times.as_ref().map(|(start, _)| start.with_timezone(&Utc));
+            let key_b = b.times.as_ref().map(|(start, _)| start.with_timezone(&Utc));
+            // `None` (all-day entries) sorts first
+            key_a.cmp(&key_b).then_with(|| a.name.cmp(&b.name))
+        });
+    }
+}
+
+/// The events of a whole month, bucketed per day in a given timezone. See [`crate::provider::Provider::grid`]
+#[derive(Clone, Debug)]
+pub struct MonthGrid {
+    timezone: Tz,
+    days: Vec<GridDay>,
+}
+
+impl MonthGrid {
+    /// Create an empty grid. This returns `None` in case `year` and `month` do not describe a valid month
+    pub fn new(year: i32, month: u32, timezone: Tz) -> Option<Self> {
+        let first_day = NaiveDate::from_ymd_opt(year, month, 1)?;
+        let days = std::iter::successors(Some(first_day), |d| d.succ_opt())
+            .take_while(|d| d.month() == month)
+            .map(GridDay::new)
+            .collect();
+        Some(Self { timezone, days })
+    }
+
+    pub fn timezone(&self) -> &Tz { &self.timezone }
+
+    pub fn first_day(&self) -> NaiveDate { self.days[0].date }
+    pub fn last_day(&self) -> NaiveDate { self.days[self.days.len() - 1].date }
+
+    /// All days of the month, in order
+    pub fn days(&self) -> &[GridDay] { &self.days }
+
+    pub fn day(&self, date: NaiveDate) -> Option<&GridDay> {
+        let index = (date - self.first_day()).num_days();
+        if index < 0 {
+            return None;
+        }
+        self.days.get(index as usize)
+    }
+
+    /// The days of the month, as rows of 7 days that start on `week_start`. \
+    /// Days of the first and last weeks that are not in this month are `None`
+    pub fn weeks(&self, week_start: Weekday) -> Vec<Vec<Option<&GridDay>>> {
+        let padding = (7 + self.first_day().weekday().num_days_from_monday() - week_start.num_days_from_monday()) % 7;
+        let mut cells: Vec<Option<&GridDay>> = std::iter::repeat(None).take(padding as usize)
+            .chain(self.days.iter().map(Some))
+            .collect();
+        while cells.len() % 7 != 0 {
+            cells.push(None);
+        }
+        cells.chunks(7).map(|week| week.to_vec()).collect()
+    }
+
+    /// Add every occurrence of an event to every day it spans over (if any of these days belong to this month). \
+    /// `privileges` are the ones of the calendar the event belongs to. Cancelled events (and cancelled instances of recurring events) are skipped
+    pub fn add_event(&mut self, calendar_url: &Url, privileges: Privileges, event: &Event) {
+        if event.is_cancelled() {
+            return;
+        }
+        // A day of margin makes sure all-day events (that do not depend on the timezone) are not missed
+        let margin = chrono::Duration::days(1);
+        let window_start = local_midnight(self.first_day(), &self.timezone).with_timezone(&Utc) - margin;
+        let window_end = local_midnight(self.last_day().succ(), &self.timezone).with_timezone(&Utc) + margin;
+
+        for occurrence in event.occurrences_between(window_start, window_end) {
+            self.add_occurrence(calendar_url, privileges, event, occurrence.start(), occurrence.end());
+        }
+        // Modified instances replace some of these occurrences
+        for instance in event.overrides() {
+            self.add_event(calendar_url, privileges, instance);
+        }
+    }
+
+    fn add_occurrence(&mut self, calendar_url: &Url, privileges: Privileges, event: &Event, start: &DateTime<Utc>, end: &DateTime<Utc>) {
+        let (first_date, last_date) = match event.is_all_day() {
+            // All-day events are not bound to any timezone: they are stored at midnight UTC
+            true => {
+                let first = start.naive_utc().date();
+                let last = end.naive_utc().date().pred().max(first);
+                (first, last)
+            },
+            false => {
+                let first = start.with_timezone(&self.timezone).date().naive_local();
+                // The end is excluded: an event that ends at midnight does not show up on the next day
+                let last_instant = std::cmp::max(*start, *end - chrono::Duration::nanoseconds(1));
+                let last = last_instant.with_timezone(&self.timezone).date().naive_local();
+                (first, last)
+            },
+        };
+
+        let from = first_date.max(self.first_day());
+        let to = last_date.min(self.last_day());
+        for date in std::iter::successors(Some(from), |d| d.succ_opt()).take_while(|d| *d <= to) {
+            let times = match event.is_all_day() {
+                true => None,
+                false => {
+                    let day_start = match date == first_date {
+                        true => start.with_timezone(&self.timezone),
+                        false => local_midnight(date, &self.timezone),
+                    };
+                    let day_end = match date == last_date {
+                        true => end.with_timezone(&self.timezone),
+                        false => local_midnight(date.succ(), &self.timezone),
+                    };
+                    Some((day_start, day_end))
+                },
+            };
+
+            let index = (date - self.first_day()).num_days() as usize;
+            self.days[index].entries.push(GridEntry {
+                calendar_url: calendar_url.clone(),
+                item_url: event.url().clone(),
+                name: event.name().to_string(),
+                times,
+                continued_from_previous_day: date != first_date,
+                continues_next_day: date != last_date,
+                privileges,
+            });
+        }
+    }
+
+    /// Sort the entries of every day. This must be called once every event has been added
+    pub(crate) fn sort(&mut self) {
+        for day in &mut self.days {
+            day.sort();
+        }
+    }
+}
+
+/// The first instant of a given day (which is usually, but not always, at 00:00)
+fn local_midnight(date: NaiveDate, timezone: &Tz) -> DateTime<Tz> {
+    (0..24)
+        .find_map(|hour| timezone.from_local_datetime(&date.and_hms(hour, 0, 0)).earliest())
+        .unwrap_or_else(|| timezone.from_utc_datetime(&date.and_hms(0, 0, 0)))
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::item::SyncStatus;
+
+    #[test]
+    fn test_grid_splits_events() {
+        let cal_url: Url = "https://some.calend.ar/calendar/".parse().unwrap();
+        let tz: Tz = "Europe/Paris".parse().unwrap();
+        let mut grid = MonthGrid::new(2021, 3, tz).unwrap();
+        assert_eq!(grid.days().len(), 31);
+
+        // 23:30 UTC on the 1st is already the 2nd in Paris
+        let late = Event::new("Late".to_string(), Utc.ymd(2021, 3, 1).and_hms(23, 30, 0), Utc.ymd(2021, 3, 2).and_hms(0, 30, 0), &cal_url);
+        // This one starts in February, and spans over 3 days
+        let long = Event::new("Long".to_string(), Utc.ymd(2021, 2, 28).and_hms(12, 0, 0), Utc.ymd(2021, 3, 2).and_hms(12, 0, 0), &cal_url);
+        grid.add_event(&cal_url, Privileges::all(), &late);
+        grid.add_event(&cal_url, Privileges::READ, &long);
+
+        let all_day_ical = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//test//EN\r\nBEGIN:VEVENT\r\nUID:holidays\r\nDTSTAMP:20210301T000000Z\r\nSUMMARY:Holidays\r\nDTSTART;VALUE=DATE:20210330\r\nDTEND;VALUE=DATE:20210402\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+        let item_url = cal_url.join("holidays.ics").unwrap();
+        let holidays = crate::ical::parse(all_day_ical, item_url, SyncStatus::NotSynced).unwrap().unwrap_event().clone();
+        assert!(holidays.is_all_day());
+        grid.add_event(&cal_url, Privileges::all(), &holidays);
+        grid.sort();
+
+        let day1 = grid.day(NaiveDate::from_ymd(2021, 3, 1)).unwrap();
+        assert_eq!(day1.entries().len(), 1);
+        assert_eq!(day1.entries()[0].name(), "Long");
+        assert!(day1.entries()[0].is_continued_from_previous_day());
+        assert!(day1.entries()[0].continues_next_day());
+        assert!(day1.entries()[0].can_edit() == false);
+        assert!(day1.entries()[0].can_delete() == false);
+        let (start, end) = day1.entries()[0].times().unwrap();
+        assert_eq!(*start, tz.ymd(2021, 3, 1).and_hms(0, 0, 0));
+        assert_eq!(*end, tz.ymd(2021, 3, 2).and_hms(0, 0, 0));
+
+        let day2 = grid.day(NaiveDate::from_ymd(2021, 3, 2)).unwrap();
+        let names: Vec<&str> = day2.entries().iter().map(|e| e.name()).collect();
+        // "Long" started the day before, so it comes first
+        assert_eq!(names, vec!["Long", "Late"]);
+        assert!(day2.entries()[0].continues_next_day() == false);
+
+        // All-day events do not depend on the timezone, and their end date is excluded
+        for d in &[30, 31] {
+            let day = grid.day(NaiveDate::from_ymd(2021, 3, *d)).unwrap();
+            assert_eq!(day.all_day_entries().count(), 1);
+        }
+        assert_eq!(grid.day(NaiveDate::from_ymd(2021, 3, 29)).unwrap().entries().len(), 0);
+        assert!(grid.day(NaiveDate::from_ymd(2021, 4, 1)).is_none());
+    }
+
+    #[test]
+    fn test_grid_weeks() {
+        let grid = MonthGrid::new(2021, 3, chrono_tz::UTC).unwrap();
+        // March 1st, 2021 is a Monday
+        let weeks = grid.weeks(Weekday::Mon);
+        assert_eq!(weeks.len(), 5);
+        assert_eq!(weeks[0][0].unwrap().date(), NaiveDate::from_ymd(2021, 3, 1));
+        assert!(weeks[4][3].is_none());
+
+        let weeks = grid.weeks(Weekday::Sun);
+        assert!(weeks[0][0].is_none());
+        assert_eq!(weeks[0][1].unwrap().date(), NaiveDate::from_ymd(2021, 3, 1));
+
+        assert!(MonthGrid::new(2021, 13, chrono_tz::UTC).is_none());
+    }
+}
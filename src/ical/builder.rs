@@ -2,31 +2,348 @@
 
 use std::error::Error;
 
-use chrono::{DateTime, Utc};
-use ics::properties::{Completed, Created, LastModified, PercentComplete, Status, Summary};
-use ics::{ICalendar, ToDo};
+use chrono::{DateTime, Datelike, Utc};
+use chrono_tz::Tz;
+use ics::properties::{Action, Attach, Attendee as IcsAttendee, CalScale, Categories, Class, Completed, Created, Description, DtEnd, DtStart, Due, Geo, LastModified, Location, Organizer as IcsOrganizer, PercentComplete, Priority, RecurrenceID, RelatedTo, Repeat, RRule, Status, Summary, Trigger, TzName};
+use ics::properties::Duration as IcsDuration;
+use ics::Alarm as IcsAlarm;
+use ics::parameters::{Encoding, FmtType, TzIDParam, Value};
+use ics::{Daylight, ICalendar, Journal as IcsJournal, Standard, TimeZone as IcsTimeZone, ToDo};
+use ics::Event as IcsEvent;
 use ics::components::Parameter as IcsParameter;
 use ics::components::Property as IcsProperty;
 use ical::property::Property as IcalProperty;
 
 use crate::Task;
+use crate::Event;
+use crate::Journal;
 use crate::item::Item;
+use crate::alarm::{Alarm, AlarmTrigger};
+use crate::attachment::Attachment;
+use crate::attendee::{Attendee, Organizer};
 use crate::task::CompletionStatus;
+use super::timezone::{Observance, VTimezone};
+use super::values::escape_text;
 
+/// Content lines should not be longer than this, excluding the line break (RFC 5545, section 3.1)
+const MAX_LINE_OCTETS: usize = 75;
 
-/// Create an iCal item from a `crate::item::Item`
+
+/// The properties of the `VCALENDAR` object that wraps iCal items
+///
+/// By default, items are wrapped with `VERSION:2.0` and their own PRODID.
+/// Apps that export items or send scheduling messages can use this to carry their own identity:
+/// ```
+/// # use kitchen_fridge::ical::CalendarEnvelope;
+/// let envelope = CalendarEnvelope::new("-//My Org//My App//EN".to_string())
+///     .with_cal_scale("GREGORIAN".to_string())
+///     .with_property("METHOD".to_string(), "PUBLISH".to_string());
+/// ```
+#[derive(Clone, Debug)]
+pub struct CalendarEnvelope {
+    version: String,
+    prod_id: String,
+    cal_scale: Option<String>,
+    extra_properties: Vec<IcalProperty>,
+}
+
+impl CalendarEnvelope {
+    pub fn new(prod_id: String) -> Self {
+        Self {
+            version: "2.0".to_string(),
+            prod_id,
+            cal_scale: None,
+            extra_properties: Vec::new(),
+        }
+    }
+
+    /// The envelope that is used when an item is serialized alone, that uses the item PRODID
+    pub fn for_item(item: &Item) -> Self {
+        Self::new(item.ical_prod_id().to_string())
+    }
+
+    pub fn with_version(mut self, version: String) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub fn with_prod_id(mut self, prod_id: String) -> Self {
+        self.prod_id = prod_id;
+        self
+    }
+
+    pub fn with_cal_scale(mut self, cal_scale: String) -> Self {
+        self.cal_scale = Some(cal_scale);
+        self
+    }
+
+    /// Add another VCALENDAR-level property (e.g. `METHOD`, or `X-WR-CALNAME`)
+    pub fn with_property(mut self, name: String, value: String) -> Self {
+        self.extra_properties.push(IcalProperty { name, params: None, value: Some(value) });
+        self
+    }
+
+    pub fn version(&self) -> &str { &self.version }
+    pub fn prod_id(&self) -> &str { &self.prod_id }
+    pub fn cal_scale(&self) -> Option<&str> { self.cal_scale.as_deref() }
+    pub fn extra_properties(&self) -> &[IcalProperty] { &self.extra_properties }
+
+    fn to_ics_calendar(&self) -> ICalendar<'_> {
+        let mut calendar = ICalendar::new(self.version.as_str(), self.prod_id.as_str());
+        if let Some(cal_scale) = &self.cal_scale {
+            calendar.push(CalScale::new(cal_scale.as_str()));
+        }
+        for ical_property in &self.extra_properties {
+            calendar.push(ical_to_ics_property(ical_property.clone()));
+        }
+        calendar
+    }
+}
+
+
+/// Create an iCal item (or a vCard, for contacts) from a `crate::item::Item`
 pub fn build_from(item: &Item) -> Result<String, Box<dyn Error>> {
     match item {
         Item::Task(t) => build_from_task(t),
-        _ => unimplemented!(),
+        Item::Event(e) => build_from_event(e),
+        Item::Journal(j) => build_from_journal(j),
+        Item::Contact(c) => super::vcard::build_from_contact(c),
+    }
+}
+
+/// Create a single iCal file that contains several items, wrapped in a custom `VCALENDAR` object
+pub fn build_from_items<'a, I>(items: I, envelope: &CalendarEnvelope) -> Result<String, Box<dyn Error>>
+where
+    I: IntoIterator<Item = &'a Item>,
+{
+    let items: Vec<&Item> = items.into_iter().collect();
+    let mut calendar = envelope.to_ics_calendar();
+    let events = items.iter().filter_map(|item| match item {
+        Item::Event(e) => Some(e),
+        _ => None,
+    });
+    for timezone in referenced_timezones(events) {
+        calendar.add_timezone(build_ics_timezone(&timezone));
+    }
+    for item in items {
+        add_item(&mut calendar, item);
+    }
+    Ok(fold_lines(&calendar.to_string()))
+}
+
+fn add_item<'a>(calendar: &mut ICalendar<'a>, item: &'a Item) {
+    match item {
+        Item::Task(t) => calendar.add_todo(build_ics_todo(t)),
+        Item::Event(e) => add_events(calendar, e),
+        Item::Journal(j) => calendar.add_journal(build_ics_journal(j)),
+        Item::Contact(c) => log::warn!("Contacts cannot be part of an iCal file, {} is left out", c.url()),
+    }
+}
+
+pub fn build_from_event(event: &Event) -> Result<String, Box<dyn Error>> {
+    let envelope = CalendarEnvelope::new(event.ical_prod_id().to_string());
+    let mut calendar = envelope.to_ics_calendar();
+    for timezone in referenced_timezones(std::iter::once(event)) {
+        calendar.add_timezone(build_ics_timezone(&timezone));
+    }
+    add_events(&mut calendar, event);
+
+    Ok(fold_lines(&calendar.to_string()))
+}
+
+fn add_events<'a>(calendar: &mut ICalendar<'a>, event: &'a Event) {
+    calendar.add_event(build_ics_event(event, event.is_all_day()));
+    // Modified instances of a recurring event belong to the same resource
+    for instance in event.overrides() {
+        calendar.add_event(build_ics_event(instance, event.is_all_day()));
+    }
+}
+
+/// The definitions of the IANA timezones that some events (and their overrides) refer to, so that they can be embedded into the iCal file.
+///
+/// Their DST rules are the ones in use in the year of their earliest event
+fn referenced_timezones<'a, I>(events: I) -> Vec<VTimezone>
+where
+    I: IntoIterator<Item = &'a Event>,
+{
+    // TZIDs, along with the earliest year they are used in
+    let mut used: Vec<(Tz, i32)> = Vec::new();
+    for event in events {
+        for e in std::iter::once(event).chain(event.overrides().iter()) {
+            if let Some(tz) = e.timezone() {
+                let year = e.start().with_timezone(&tz).year();
+                match used.iter_mut().find(|(used_tz, _)| *used_tz == tz) {
+                    Some((_, used_year)) => *used_year = (*used_year).min(year),
+                    None => used.push((tz, year)),
+                }
+            }
+        }
+    }
+    used.iter().map(|(tz, year)| VTimezone::from_tz(tz, *year)).collect()
+}
+
+fn build_ics_timezone(timezone: &VTimezone) -> IcsTimeZone<'static> {
+    let mut observances = timezone.observances().iter();
+    let mut ics_timezone = match observances.next() {
+        Some(o) if o.is_daylight() => IcsTimeZone::daylight(timezone.tzid().to_string(), build_ics_daylight(o)),
+        Some(o) => IcsTimeZone::standard(timezone.tzid().to_string(), build_ics_standard(o)),
+        // VTimezone always have at least one observance
+        None => IcsTimeZone::standard(timezone.tzid().to_string(), Standard::new("19700101T000000", "+0000", "+0000")),
+    };
+    for o in observances {
+        match o.is_daylight() {
+            true => ics_timezone.add_daylight(build_ics_daylight(o)),
+            false => ics_timezone.add_standard(build_ics_standard(o)),
+        }
+    }
+    ics_timezone
+}
+
+fn build_ics_standard(observance: &Observance) -> Standard<'static> {
+    let mut standard = Standard::new(
+        observance.start().format("%Y%m%dT%H%M%S").to_string(),
+        format_utc_offset(observance.offset_from().local_minus_utc()),
+        format_utc_offset(observance.offset_to().local_minus_utc()),
+    );
+    if let Some(rule) = observance.recurrence() {
+        standard.push(RRule::new(rule.to_string()));
+    }
+    if let Some(name) = observance.name() {
+        standard.push(TzName::new(name.to_string()));
+    }
+    standard
+}
+
+fn build_ics_daylight(observance: &Observance) -> Daylight<'static> {
+    let mut daylight = Daylight::new(
+        observance.start().format("%Y%m%dT%H%M%S").to_string(),
+        format_utc_offset(observance.offset_from().local_minus_utc()),
+        format_utc_offset(observance.offset_to().local_minus_utc()),
+    );
+    if let Some(rule) = observance.recurrence() {
+        daylight.push(RRule::new(rule.to_string()));
     }
+    if let Some(name) = observance.name() {
+        daylight.push(TzName::new(name.to_string()));
+    }
+    daylight
+}
+
+/// `all_day_recurrence` tells whether the recurring event this may be an override of is an all-day event
+fn build_ics_event<'a>(event: &'a Event, all_day_recurrence: bool) -> IcsEvent<'a> {
+    let s_last_modified = format_date_time(event.last_modified());
+
+    let mut ics_event = IcsEvent::new(
+        event.uid().as_str(),
+        s_last_modified.clone(),
+    );
+
+    event.creation_date().map(|dt|
+        ics_event.push(Created::new(format_date_time(dt)))
+    );
+    ics_event.push(LastModified::new(s_last_modified));
+    ics_event.push(Summary::new(escape_text(event.name())));
+    event.description().map(|desc|
+        ics_event.push(Description::new(escape_text(desc)))
+    );
+    event.location().map(|location|
+        ics_event.push(Location::new(escape_text(location)))
+    );
+    event.geo().map(|(latitude, longitude)|
+        ics_event.push(Geo::new(format!("{};{}", latitude, longitude)))
+    );
+    event.status().map(|status|
+        ics_event.push(Status::new(status.as_ical_str()))
+    );
+    event.classification().map(|class|
+        ics_event.push(Class::new(class.as_ical_str()))
+    );
+    if event.is_all_day() {
+        let mut dt_start = DtStart::new(format_date(event.start()));
+        dt_start.add(Value::DATE);
+        ics_event.push(dt_start);
+        if event.uses_duration() == false {
+            let mut dt_end = DtEnd::new(format_date(event.end()));
+            dt_end.add(Value::DATE);
+            ics_event.push(dt_end);
+        }
+    } else if let Some(tzid) = event.tzid() {
+        let mut dt_start = DtStart::new(format_local_date_time(event.start(), event.timezone()));
+        dt_start.add(TzIDParam::new(tzid));
+        ics_event.push(dt_start);
+        if event.uses_duration() == false {
+            let mut dt_end = DtEnd::new(format_local_date_time(event.end(), event.timezone()));
+            dt_end.add(TzIDParam::new(tzid));
+            ics_event.push(dt_end);
+        }
+    } else {
+        ics_event.push(DtStart::new(event.start_date_time().value()));
+        if event.uses_duration() == false {
+            ics_event.push(DtEnd::new(event.end_date_time().value()));
+        }
+    }
+    if event.uses_duration() {
+        ics_event.push(IcsDuration::new(format_duration(event.duration().num_seconds())));
+    }
+    event.recurrence().map(|rule|
+        ics_event.push(RRule::new(rule.to_string()))
+    );
+    if let Some(recurrence_id) = event.recurrence_id() {
+        // Its value type must be the same as the DTSTART of the recurring event
+        if all_day_recurrence {
+            let mut ics_recurrence_id = RecurrenceID::new(format_date(recurrence_id));
+            ics_recurrence_id.add(Value::DATE);
+            ics_event.push(ics_recurrence_id);
+        } else if let Some(tzid) = event.tzid() {
+            let mut ics_recurrence_id = RecurrenceID::new(format_local_date_time(recurrence_id, event.timezone()));
+            ics_recurrence_id.add(TzIDParam::new(tzid));
+            ics_event.push(ics_recurrence_id);
+        } else if event.is_floating() {
+            ics_event.push(RecurrenceID::new(format_date_time(recurrence_id)));
+        } else {
+            ics_event.push(RecurrenceID::new(format_utc_date_time(recurrence_id)));
+        }
+    }
+
+    if let Some(organizer) = event.organizer() {
+        ics_event.push(build_organizer(organizer));
+    }
+    for attendee in event.attendees() {
+        ics_event.push(build_attendee(attendee));
+    }
+    for attachment in event.attachments() {
+        ics_event.push(build_attachment(attachment));
+    }
+    if let Some(categories) = build_categories(event.categories()) {
+        ics_event.push(categories);
+    }
+
+    // Also add fields that we have not handled
+    for ical_property in event.extra_parameters() {
+        let ics_property = ical_to_ics_property(ical_property.clone());
+        ics_event.push(ics_property);
+    }
+
+    for alarm in event.alarms() {
+        ics_event.add_alarm(build_alarm(alarm));
+    }
+
+    ics_event
 }
 
 pub fn build_from_task(task: &Task) -> Result<String, Box<dyn Error>> {
+    let envelope = CalendarEnvelope::new(task.ical_prod_id().to_string());
+    let mut calendar = envelope.to_ics_calendar();
+    calendar.add_todo(build_ics_todo(task));
+
+    Ok(fold_lines(&calendar.to_string()))
+}
+
+fn build_ics_todo(task: &Task) -> ToDo<'_> {
     let s_last_modified = format_date_time(task.last_modified());
 
     let mut todo = ToDo::new(
-        task.uid(),
+        task.uid().as_str(),
         s_last_modified.clone(),
     );
 
@@ -34,19 +351,67 @@ pub fn build_from_task(task: &Task) -> Result<String, Box<dyn Error>> {
         todo.push(Created::new(format_date_time(dt)))
     );
     todo.push(LastModified::new(s_last_modified));
-    todo.push(Summary::new(task.name()));
+    todo.push(Summary::new(escape_text(task.name())));
+    if let Some(priority) = task.priority() {
+        todo.push(Priority::new(priority.to_string()));
+    }
+    task.geo().map(|(latitude, longitude)|
+        todo.push(Geo::new(format!("{};{}", latitude, longitude)))
+    );
+    task.classification().map(|class|
+        todo.push(Class::new(class.as_ical_str()))
+    );
+    task.parent().map(|parent|
+        todo.push(RelatedTo::new(parent.as_str()))
+    );
+    for attachment in task.attachments() {
+        todo.push(build_attachment(attachment));
+    }
+    match (task.due(), task.is_due_all_day()) {
+        (None, _) => (),
+        (Some(due), true) => {
+            let mut ics_due = Due::new(format_date(due));
+            ics_due.add(Value::DATE);
+            todo.push(ics_due);
+        },
+        (Some(_), false) => {
+            if let Some(due) = task.due_date_time() {
+                let mut ics_due = Due::new(due.value());
+                if let Some(tzid) = due.tzid() {
+                    ics_due.add(TzIDParam::new(tzid.to_string()));
+                }
+                todo.push(ics_due);
+            }
+        },
+    }
 
     match task.completion_status() {
-        CompletionStatus::Uncompleted => {
-            todo.push(Status::needs_action());
-        },
         CompletionStatus::Completed(completion_date) => {
             todo.push(PercentComplete::new("100"));
             completion_date.as_ref().map(|dt| todo.push(
-                Completed::new(format_date_time(dt))
+                match task.is_completion_floating() {
+                    true => Completed::new(format_date_time(dt)),
+                    false => Completed::new(format_utc_date_time(dt)),
+                }
             ));
             todo.push(Status::completed());
-        }
+        },
+        status => {
+            if let Some(percent_complete) = task.raw_percent_complete() {
+                todo.push(PercentComplete::new(percent_complete.to_string()));
+            }
+            todo.push(Status::new(status.as_ical_str()));
+        },
+    }
+    if let Some(categories) = build_categories(task.categories()) {
+        todo.push(categories);
+    }
+    let time_tracking = task.time_tracking();
+    if let Some(started_at) = time_tracking.started_at() {
+        todo.push(IcsProperty::new("X-TIMER-STARTED", format_utc_date_time(started_at)));
+    }
+    if time_tracking.total_duration() != chrono::Duration::zero() {
+        todo.push(IcsProperty::new("X-TIME-SPENT", format_duration(time_tracking.total_duration().num_seconds())));
     }
 
     // Also add fields that we have not handled
@@ -55,35 +420,290 @@ pub fn build_from_task(task: &Task) -> Result<String, Box<dyn Error>> {
         todo.push(ics_property);
     }
 
-    let mut calendar = ICalendar::new("2.0", task.ical_prod_id());
-    calendar.add_todo(todo);
+    for alarm in task.alarms() {
+        todo.add_alarm(build_alarm(alarm));
+    }
 
-    Ok(calendar.to_string())
+    todo
+}
+
+pub fn build_from_journal(journal: &Journal) -> Result<String, Box<dyn Error>> {
+    let envelope = CalendarEnvelope::new(journal.ical_prod_id().to_string());
+    let mut calendar = envelope.to_ics_calendar();
+    calendar.add_journal(build_ics_journal(journal));
+
+    Ok(fold_lines(&calendar.to_string()))
+}
+
+fn build_ics_journal(journal: &Journal) -> IcsJournal<'_> {
+    let s_last_modified = format_date_time(journal.last_modified());
+
+    let mut ics_journal = IcsJournal::new(
+        journal.uid().as_str(),
+        s_last_modified.clone(),
+    );
+
+    journal.creation_date().map(|dt|
+        ics_journal.push(Created::new(format_date_time(dt)))
+    );
+    ics_journal.push(LastModified::new(s_last_modified));
+    if journal.name().is_empty() == false {
+        ics_journal.push(Summary::new(escape_text(journal.name())));
+    }
+    journal.description().map(|desc|
+        ics_journal.push(Description::new(escape_text(desc)))
+    );
+    match (journal.start(), journal.is_all_day()) {
+        (None, _) => (),
+        (Some(start), true) => {
+            let mut dt_start = DtStart::new(format_date(start));
+            dt_start.add(Value::DATE);
+            ics_journal.push(dt_start);
+        },
+        (Some(start), false) => ics_journal.push(DtStart::new(format_date_time(start))),
+    }
+
+    // Also add fields that we have not handled
+    for ical_property in journal.extra_parameters() {
+        let ics_property = ical_to_ics_property(ical_property.clone());
+        ics_journal.push(ics_property);
+    }
+
+    ics_journal
+}
+
+/// Build a single CATEGORIES property out of a list of categories, escaping commas they may contain
+fn build_categories(categories: &[String]) -> Option<Categories<'static>> {
+    if categories.is_empty() {
+        return None;
+    }
+    let value = categories.iter()
+        .map(|category| escape_text(category))
+        .collect::<Vec<_>>()
+        .join(",");
+    Some(Categories::new(value))
+}
+
+fn build_alarm(alarm: &Alarm) -> IcsAlarm<'static> {
+    let trigger = match alarm.trigger() {
+        AlarmTrigger::Absolute(date) => {
+            let mut trigger = Trigger::new(format_utc_date_time(date));
+            trigger.add(Value::DATE_TIME);
+            trigger
+        },
+        AlarmTrigger::Relative { offset_seconds, related_to_end } => {
+            let mut trigger = Trigger::new(format_duration(*offset_seconds));
+            if *related_to_end {
+                trigger.add(IcsParameter::new("RELATED", "END"));
+            }
+            trigger
+        },
+        AlarmTrigger::Unparsed(prop) => {
+            let mut trigger = Trigger::new(prop.value.clone().unwrap_or_default());
+            for parameter in ics_parameters(prop) {
+                trigger.add(parameter);
+            }
+            trigger
+        },
+    };
+
+    let mut ics_alarm = IcsAlarm::new(Action::new(alarm.action().as_ical_str().to_string()), trigger);
+    alarm.description().map(|desc|
+        ics_alarm.push(Description::new(escape_text(desc)))
+    );
+    if let Some(repeat) = alarm.repeat() {
+        ics_alarm.push(Repeat::new(repeat.count.to_string()));
+        ics_alarm.push(IcsDuration::new(format_duration(repeat.interval_seconds)));
+    }
+    for ical_property in alarm.extra_parameters() {
+        ics_alarm.push(ical_to_ics_property(ical_property.clone()));
+    }
+    ics_alarm
+}
+
+fn build_organizer(organizer: &Organizer) -> IcsOrganizer<'static> {
+    let mut ics_organizer = IcsOrganizer::new(organizer.address().to_string());
+    if let Some(cn) = organizer.common_name() {
+        ics_organizer.add(IcsParameter::new("CN", quote_param_value(cn)));
+    }
+    for (key, values) in organizer.extra_parameters() {
+        ics_organizer.add(IcsParameter::new(key.clone(), quote_param_values(values)));
+    }
+    ics_organizer
+}
+
+fn build_attachment(attachment: &Attachment) -> Attach<'static> {
+    let mut ics_attach = match attachment {
+        Attachment::Uri { uri, .. } => Attach::new(uri.clone()),
+        Attachment::Binary { data, .. } => {
+            let mut ics_attach = Attach::new(base64::encode(data));
+            ics_attach.add(Encoding::Base64);
+            ics_attach.add(Value::BINARY);
+            ics_attach
+        },
+    };
+    if let Some(format_type) = attachment.format_type() {
+        ics_attach.add(FmtType::new(format_type.to_string()));
+    }
+    for (key, values) in attachment.extra_parameters() {
+        ics_attach.add(IcsParameter::new(key.clone(), quote_param_values(values)));
+    }
+    ics_attach
+}
+
+fn build_attendee(attendee: &Attendee) -> IcsAttendee<'static> {
+    let mut ics_attendee = IcsAttendee::new(attendee.address().to_string());
+    if let Some(cn) = attendee.common_name() {
+        ics_attendee.add(IcsParameter::new("CN", quote_param_value(cn)));
+    }
+    if let Some(status) = attendee.status() {
+        ics_attendee.add(IcsParameter::new("PARTSTAT", status.as_ical_str().to_string()));
+    }
+    if let Some(role) = attendee.role() {
+        ics_attendee.add(IcsParameter::new("ROLE", role.as_ical_str().to_string()));
+    }
+    if attendee.rsvp() {
+        ics_attendee.add(IcsParameter::new("RSVP", "TRUE"));
+    }
+    for (key, values) in attendee.extra_parameters() {
+        ics_attendee.add(IcsParameter::new(key.clone(), quote_param_values(values)));
+    }
+    ics_attendee
+}
+
+/// The values of a parameter, separated by commas (e.g. `DELEGATED-FROM="mailto:a@example.com","mailto:b@example.com"`)
+fn quote_param_values(values: &[String]) -> String {
+    values.iter().map(|value| quote_param_value(value)).collect::<Vec<_>>().join(",")
+}
+
+/// Parameter values that contain special characters (such as display names with commas) must be quoted
+pub(crate) fn quote_param_value(value: &str) -> String {
+    match value.contains(|c| c == ':' || c == ';' || c == ',') {
+        true => format!("\"{}\"", value),
+        false => value.to_string(),
+    }
+}
+
+/// Format a number of seconds as an iCal DURATION value (e.g. `-PT15M`)
+pub(crate) fn format_duration(seconds: i64) -> String {
+    let sign = if seconds < 0 { "-" } else { "" };
+    let seconds = seconds.abs();
+    if seconds == 0 {
+        return String::from("PT0S");
+    }
+    if seconds % (7 * 24 * 3600) == 0 {
+        return format!("{}P{}W", sign, seconds / (7 * 24 * 3600));
+    }
+
+    let (d, h, m, s) = (seconds / 86400, seconds % 86400 / 3600, seconds % 3600 / 60, seconds % 60);
+    let mut text = format!("{}P", sign);
+    if d > 0 {
+        text.push_str(&format!("{}D", d));
+    }
+    if h > 0 || m > 0 || s > 0 {
+        text.push('T');
+        // Hours, minutes and seconds must be contiguous (e.g. PT1H0M5S rather than PT1H5S)
+        if h > 0 {
+            text.push_str(&format!("{}H", h));
+        }
+        if m > 0 || (h > 0 && s > 0) {
+            text.push_str(&format!("{}M", m));
+        }
+        if s > 0 {
+            text.push_str(&format!("{}S", s));
+        }
+    }
+    text
+}
+
+fn format_utc_date_time(dt: &DateTime<Utc>) -> String {
+    dt.format("%Y%m%dT%H%M%SZ").to_string()
 }
 
 fn format_date_time(dt: &DateTime<Utc>) -> String {
     dt.format("%Y%m%dT%H%M%S").to_string()
 }
 
+/// Format a date-time in the local time of a timezone.
+/// Without a known timezone, this is written in UTC, just like the parser reads dates whose TZID is unknown
+fn format_local_date_time(dt: &DateTime<Utc>, timezone: Option<Tz>) -> String {
+    match timezone {
+        Some(tz) => dt.with_timezone(&tz).format("%Y%m%dT%H%M%S").to_string(),
+        None => format_date_time(dt),
+    }
+}
+
+/// Format a UTC offset, e.g. `+0100` or `-053000`
+fn format_utc_offset(seconds: i32) -> String {
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let seconds = seconds.abs();
+    match seconds % 60 {
+        0 => format!("{}{:02}{:02}", sign, seconds / 3600, (seconds % 3600) / 60),
+        s => format!("{}{:02}{:02}{:02}", sign, seconds / 3600, (seconds % 3600) / 60, s),
+    }
+}
+
+fn format_date(dt: &DateTime<Utc>) -> String {
+    dt.format("%Y%m%d").to_string()
+}
+
+/// Fold the content lines that are longer than 75 octets, without splitting UTF-8 characters (RFC 5545, section 3.1).
+/// Lines that are already folded are unfolded first, so that they are not folded twice
+pub(crate) fn fold_lines(content: &str) -> String {
+    let unfolded = content.replace("\r\n ", "").replace("\r\n\t", "");
+    let mut folded = String::with_capacity(unfolded.len() + unfolded.len() / MAX_LINE_OCTETS * 3);
+    for line in unfolded.split_terminator("\r\n") {
+        let mut line_octets = 0;
+        for c in line.chars() {
+            if line_octets + c.len_utf8() > MAX_LINE_OCTETS {
+                // The leading space of the continuation line counts in its length
+                folded.push_str("\r\n ");
+                line_octets = 1;
+            }
+            folded.push(c);
+            line_octets += c.len_utf8();
+        }
+        folded.push_str("\r\n");
+    }
+    folded
+}
 
 fn ical_to_ics_property(prop: IcalProperty) -> IcsProperty<'static> {
-    let mut ics_prop = match prop.value {
-        Some(value) => IcsProperty::new(prop.name, value),
-        None =>        IcsProperty::new(prop.name, ""),
+    let mut ics_prop = match prop.value.clone() {
+        Some(value) => IcsProperty::new(prop.name.clone(), value),
+        None =>        IcsProperty::new(prop.name.clone(), ""),
     };
-    prop.params.map(|v| {
-        for (key, vec_values) in v {
-            let values = vec_values.join(";");
-            ics_prop.add(IcsParameter::new(key, values));
-        }
-    });
+    for parameter in ics_parameters(&prop) {
+        ics_prop.add(parameter);
+    }
     ics_prop
 }
 
+fn ics_parameters(prop: &IcalProperty) -> Vec<IcsParameter<'static>> {
+    prop.params.iter().flatten()
+        .map(|(key, vec_values)| {
+            let values = vec_values.iter()
+                .map(|value| quote_param_value(value))
+                .collect::<Vec<_>>()
+                .join(",");
+            IcsParameter::new(key.clone(), values)
+        })
+        .collect()
+}
+
+/// Parameter values that contain `:`, `;` or `,` must be quoted (RFC 5545, section 3.2)
+fn quote_param_value(value: &str) -> String {
+    match value.contains(&[':', ';', ','][..]) {
+        true => format!("\"{}\"", value),
+        false => value.to_string(),
+    }
+}
+
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::TimeZone;
     use crate::Task;
     use crate::config::{ORG_NAME, PRODUCT_NAME};
 
@@ -101,7 +721,7 @@ mod tests {
             LAST-MODIFIED:{}\r\n\
             SUMMARY:This is a task with ÜTF-8 characters\r\n\
             PERCENT-COMPLETE:100\r\n\
-            COMPLETED:{}\r\n\
+            COMPLETED:{}Z\r\n\
             STATUS:COMPLETED\r\n\
             END:VTODO\r\n\
             END:VCALENDAR\r\n", ORG_NAME.lock().unwrap(), PRODUCT_NAME.lock().unwrap(), uid, s_now, s_now, s_now, s_now);
@@ -143,8 +763,75 @@ mod tests {
     }
 
     #[test]
-    #[ignore]
     fn test_ical_from_event() {
-        unimplemented!();
+        let cal_url = "http://my.calend.ar/id".parse().unwrap();
+        let start = Utc.ymd(2021, 9, 14).and_hms(18, 30, 0);
+        let end = Utc.ymd(2021, 9, 14).and_hms(20, 0, 0);
+
+        let event = Item::Event(Event::new(
+            String::from("Dinner at the Restaurant de l'Univers"), start, end, &cal_url
+        ));
+        let s_now = format_date_time(event.last_modified());
+
+        let ical = build_from(&event).unwrap();
+        let expected_ical = format!("BEGIN:VCALENDAR\r\n\
+            VERSION:2.0\r\n\
+            PRODID:-//{}//{}//EN\r\n\
+            BEGIN:VEVENT\r\n\
+            UID:{}\r\n\
+            DTSTAMP:{}\r\n\
+            CREATED:{}\r\n\
+            LAST-MODIFIED:{}\r\n\
+            SUMMARY:Dinner at the Restaurant de l'Univers\r\n\
+            DTSTART:20210914T183000Z\r\n\
+            DTEND:20210914T200000Z\r\n\
+            END:VEVENT\r\n\
+            END:VCALENDAR\r\n", ORG_NAME.lock().unwrap(), PRODUCT_NAME.lock().unwrap(), event.uid(), s_now, s_now, s_now);
+
+        assert_eq!(ical, expected_ical);
+    }
+
+    #[test]
+    fn test_ical_with_custom_envelope() {
+        let cal_url = "http://my.calend.ar/id".parse().unwrap();
+        let first = Item::Task(Task::new(String::from("Buy milk"), false, &cal_url));
+        let second = Item::Task(Task::new(String::from("Buy eggs"), true, &cal_url));
+
+        let envelope = CalendarEnvelope::new(String::from("-//Example Corp//Grocery List//EN"))
+            .with_cal_scale(String::from("GREGORIAN"))
+            .with_property(String::from("METHOD"), String::from("PUBLISH"));
+        let ical = build_from_items(vec![&first, &second], &envelope).unwrap();
+
+        assert!(ical.starts_with("BEGIN:VCALENDAR\r\n\
+            VERSION:2.0\r\n\
+            PRODID:-//Example Corp//Grocery List//EN\r\n\
+            CALSCALE:GREGORIAN\r\n\
+            METHOD:PUBLISH\r\n\
+            BEGIN:VTODO\r\n"));
+        assert_eq!(ical.matches("BEGIN:VTODO").count(), 2);
+    }
+
+    #[test]
+    fn test_ical_folding_and_escaping() {
+        let cal_url: url::Url = "http://my.calend.ar/id".parse().unwrap();
+        let start = Utc.ymd(2021, 9, 14).and_hms(18, 30, 0);
+        let end = Utc.ymd(2021, 9, 14).and_hms(20, 0, 0);
+        let mut event = Event::new(String::from("Dinner, drinks; and more"), start, end, &cal_url);
+        let location = "Chez Léon:\nÉcrevisses, œufs brouillés; crème brûlée. ".repeat(5);
+        event.set_location(Some(location.clone()));
+        let item = Item::Event(event);
+
+        let ical = build_from(&item).unwrap();
+        assert!(ical.contains("SUMMARY:Dinner\\, drinks\\; and more\r\n"));
+        assert!(ical.contains("LOCATION:Chez Léon:\\nÉcrevisses\\, œufs"));
+        assert!(ical.split("\r\n").all(|line| line.len() <= MAX_LINE_OCTETS));
+        assert!(ical.contains("\r\n "));
+        // Folding again does not change anything
+        assert_eq!(fold_lines(&ical), ical);
+
+        let parsed = crate::ical::parse(&ical, item.url().clone(), item.sync_status().clone()).unwrap();
+        let parsed = parsed.unwrap_event();
+        assert_eq!(parsed.name(), "Dinner, drinks; and more");
+        assert_eq!(parsed.location(), Some(location.as_str()));
     }
 }
@@ -0,0 +1,179 @@
+//! Decoding of the property values that legacy producers write with `ENCODING=QUOTED-PRINTABLE`, possibly in another `CHARSET` than UTF-8,
+//! and of the files they write in latin-1
+//!
+//! This can be disabled with [`DECODE_LEGACY_ENCODINGS`](crate::config::DECODE_LEGACY_ENCODINGS)
+
+use std::borrow::Cow;
+
+use ical::property::Property;
+
+fn is_enabled() -> bool {
+    *crate::config::DECODE_LEGACY_ENCODINGS.lock().unwrap()
+}
+
+/// Quoted-printable values may span several lines, with "soft" line breaks (a trailing `=`) that are not folded lines in the iCal sense.
+/// Join these lines, so that the `ical` crate sees a single content line
+pub(crate) fn join_soft_line_breaks(content: &str) -> Cow<'_, str> {
+    if is_enabled() == false || content.to_ascii_uppercase().contains("QUOTED-PRINTABLE") == false {
+        return Cow::Borrowed(content);
+    }
+
+    let mut joined = String::with_capacity(content.len());
+    let mut quoted_printable = false;
+    let mut soft_break = false;
+    for line in content.split_inclusive('\n') {
+        if soft_break == false && line.starts_with(|c| c == ' ' || c == '\t') == false {
+            // This line starts a new property
+            quoted_printable = is_quoted_printable_line(line);
+        }
+        let trimmed = line.trim_end_matches(|c| c == '\r' || c == '\n');
+        soft_break = quoted_printable && trimmed.ends_with('=');
+        match soft_break {
+            true => joined.push_str(&trimmed[..trimmed.len() - 1]),
+            false => joined.push_str(line),
+        }
+    }
+    Cow::Owned(joined)
+}
+
+fn is_quoted_printable_line(line: &str) -> bool {
+    let name_and_params = line.split(':').next().unwrap_or_default();
+    name_and_params.split(';').skip(1).any(|param| {
+        // vCard 2.1 also allows the bare `QUOTED-PRINTABLE` parameter
+        param.eq_ignore_ascii_case("ENCODING=QUOTED-PRINTABLE") || param.eq_ignore_ascii_case("QUOTED-PRINTABLE")
+    })
+}
+
+/// Decode the raw content of an iCal file (e.g. a downloaded feed). This is UTF-8, or latin-1 in case the content is not valid UTF-8 (unless legacy encodings are not decoded)
+pub(crate) fn decode_text(bytes: &[u8]) -> String {
+    match is_enabled() {
+        true => String::from_utf8(bytes.to_vec()).unwrap_or_else(|_| latin1(bytes)),
+        false => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+/// Decode the values of the quoted-printable properties into plain UTF-8 text, and remove their `ENCODING` and `CHARSET` parameters
+pub(crate) fn decode_properties(properties: &mut [Property]) {
+    if is_enabled() == false {
+        return;
+    }
+    for prop in properties {
+        decode_property(prop);
+    }
+}
+
+fn decode_property(prop: &mut Property) {
+    let params = match prop.params.as_mut() {
+        Some(params) => params,
+        None => return,
+    };
+    let quoted_printable = params.iter().any(|(name, values)| {
+        (name.eq_ignore_ascii_case("ENCODING") && values.iter().any(|v| v.eq_ignore_ascii_case("QUOTED-PRINTABLE")))
+        || name.eq_ignore_ascii_case("QUOTED-PRINTABLE")
+    });
+    if quoted_printable == false {
+        // Values that are not encoded have already been decoded into a Rust string, whatever their CHARSET (see `decode_text`)
+        return;
+    }
+    let value = match prop.value.as_deref() {
+        Some(value) => value,
+        None => return,
+    };
+    let charset = params.iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("CHARSET"))
+        .and_then(|(_, values)| values.first());
+
+    let decoded = match decode_charset(&decode_quoted_printable(value), charset.map(|c| c.as_str())) {
+        Some(decoded) => decoded,
+        None => {
+            log::warn!("Unsupported CHARSET {:?} for property {}, keeping its value as is", charset, prop.name);
+            return;
+        },
+    };
+    params.retain(|(name, _)| {
+        ["ENCODING", "CHARSET", "QUOTED-PRINTABLE"].iter().any(|param| name.eq_ignore_ascii_case(param)) == false
+    });
+    if params.is_empty() {
+        prop.params = None;
+    }
+    prop.value = Some(decoded);
+}
+
+/// Decode a quoted-printable value (RFC 2045, section 6.7). Invalid escape sequences are kept as they are
+fn decode_quoted_printable(value: &str) -> Vec<u8> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'=', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            },
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            },
+        }
+    }
+    decoded
+}
+
+/// Decode some bytes in a given charset. Without any (or with UTF-8), this is UTF-8, or latin-1 for bytes that are not valid UTF-8,
+/// since legacy producers sometimes label latin-1 values as UTF-8.
+///
+/// This returns `None` for unsupported charsets
+fn decode_charset(bytes: &[u8], charset: Option<&str>) -> Option<String> {
+    match charset.map(|c| c.to_ascii_lowercase()).as_deref() {
+        None | Some("utf-8") | Some("utf8") | Some("us-ascii") => Some(String::from_utf8(bytes.to_vec()).unwrap_or_else(|_| latin1(bytes))),
+        Some("iso-8859-1") | Some("latin1") | Some("latin-1") => Some(latin1(bytes)),
+        Some(_) => None,
+    }
+}
+
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| *b as char).collect()
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quoted_printable_decoding() {
+        let content = "SUMMARY:Plain=3D text\r\n\
+            DESCRIPTION;ENCODING=QUOTED-PRINTABLE;CHARSET=UTF-8:Caf=C3=A9 cr=C3=A8me=\r\n\
+            =20br=C3=BBl=C3=A9e\r\n\
+            LOCATION:Elsewhere\r\n";
+        let joined = join_soft_line_breaks(content);
+        assert_eq!(joined, "SUMMARY:Plain=3D text\r\n\
+            DESCRIPTION;ENCODING=QUOTED-PRINTABLE;CHARSET=UTF-8:Caf=C3=A9 cr=C3=A8me=20br=C3=BBl=C3=A9e\r\n\
+            LOCATION:Elsewhere\r\n");
+
+        assert_eq!(decode_quoted_printable("Caf=C3=A9 =3D 100=25=ZZ"), "Café = 100%=ZZ".as_bytes());
+        assert_eq!(decode_charset(&[0x43, 0x61, 0x66, 0xE9], Some("ISO-8859-1")), Some("Café".to_string()));
+        assert_eq!(decode_charset(&[0x43, 0x61, 0x66, 0xE9], None), Some("Café".to_string()));
+        assert_eq!(decode_charset(b"Caf\xC3\xA9", None), Some("Café".to_string()));
+        assert_eq!(decode_charset(&[0x43, 0x61, 0x66, 0xE9], Some("UTF-8")), Some("Café".to_string()));
+        assert_eq!(decode_text(&[0x43, 0x61, 0x66, 0xE9]), "Café");
+        assert_eq!(decode_text("Café".as_bytes()), "Café");
+        assert_eq!(decode_charset(b"Cafe", Some("KOI8-R")), None);
+
+        let mut properties = vec![Property {
+            name: "SUMMARY".to_string(),
+            params: Some(vec![
+                ("ENCODING".to_string(), vec!["QUOTED-PRINTABLE".to_string()]),
+                ("CHARSET".to_string(), vec!["ISO-8859-1".to_string()]),
+            ]),
+            value: Some("D=EEner au caf=E9".to_string()),
+        }];
+        decode_properties(&mut properties);
+        assert_eq!(properties[0].value.as_deref(), Some("Dîner au café"));
+        assert!(properties[0].params.is_none());
+    }
+}
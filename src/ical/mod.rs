@@ -1,11 +1,19 @@
-//! This module handles conversion between iCal files and internal representations
+//! This module handles conversion between iCal files (and vCards) and internal representations
 //!
 //! It is a wrapper around different Rust third-party libraries, since I haven't find any complete library that is able to parse _and_ generate iCal files
 
 mod parser;
-pub use parser::parse;
+pub use parser::{parse, parse_calendar, parse_free_busy};
+pub(crate) use parser::{lookup_timezone, parse_date_times_from_property};
 mod builder;
-pub use builder::build_from;
+pub use builder::{build_from, build_from_items, CalendarEnvelope};
+mod timezone;
+pub use timezone::{Observance, VTimezone};
+pub mod values;
+mod encoding;
+pub(crate) use encoding::decode_text;
+mod vcard;
+pub use vcard::build_from_contact;
 
 use crate::config::{ORG_NAME, PRODUCT_NAME};
 
@@ -53,4 +61,56 @@ mod tests {
             assert_eq!(left_parts, right_parts);
         }
     }
+
+    #[test]
+    fn test_duration_round_trip() {
+        let durations = [
+            ("-PT15M", -15 * 60),
+            ("P1W", 7 * 24 * 3600),
+            ("P1DT2H", 26 * 3600),
+            ("PT1H0M5S", 3605),
+            ("PT0S", 0),
+        ];
+        for (text, seconds) in &durations {
+            assert_eq!(parser::parse_duration(text).unwrap(), *seconds);
+            assert_eq!(builder::format_duration(*seconds), *text);
+        }
+
+        assert_eq!(parser::parse_duration("+P2D").unwrap(), 2 * 24 * 3600);
+        assert!(parser::parse_duration("P").is_err());
+        assert!(parser::parse_duration("PT5").is_err());
+        assert!(parser::parse_duration("P1H").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_calendar_export_import() {
+        use chrono::{TimeZone, Utc};
+        use url::Url;
+        use crate::{Event, Item, Task};
+        use crate::calendar::SupportedComponents;
+        use crate::calendar::cached_calendar::CachedCalendar;
+        use crate::traits::{BaseCalendar, CompleteCalendar};
+
+        let url = Url::parse("https://caldav.com/personal/").unwrap();
+        let mut cal = CachedCalendar::new("Personal".to_string(), url.clone(), SupportedComponents::TODO | SupportedComponents::EVENT, None);
+        cal.add_item(Item::Task(Task::new("Buy milk".to_string(), false, &url))).await.unwrap();
+        let start = Utc.ymd(2021, 4, 1).and_hms(9, 0, 0);
+        cal.add_item(Item::Event(Event::new("Meeting".to_string(), start, start + chrono::Duration::hours(1), &url))).await.unwrap();
+
+        let exported = cal.export_ics().unwrap();
+        assert!(exported.contains("X-WR-CALNAME:Personal"));
+
+        let other_url = Url::parse("https://caldav.com/backup/").unwrap();
+        let mut other = CachedCalendar::new("Backup".to_string(), other_url.clone(), SupportedComponents::TODO | SupportedComponents::EVENT, None);
+        let imported = other.import_ics(&exported).await.unwrap();
+        assert_eq!(imported.len(), 2);
+        assert!(imported.iter().all(|url| url.as_str().starts_with(other_url.as_str())));
+        let names: HashSet<&str> = other.iter_items().map(|(_url, item)| item.name()).collect();
+        assert_eq!(names, vec!["Buy milk", "Meeting"].into_iter().collect());
+        assert!(other.iter_items().all(|(_url, item)| item.sync_status() == &SyncStatus::NotSynced));
+
+        // Importing the same file again does not duplicate items
+        assert!(other.import_ics(&exported).await.unwrap().is_empty());
+        assert_eq!(other.item_count(), 2);
+    }
 }
@@ -1,25 +1,47 @@
 //! A module to parse ICal files
 
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::error::Error;
 
-use chrono::{DateTime, TimeZone, Utc};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
 use chrono_tz::Tz;
-use ical::parser::ical::component::{IcalCalendar, IcalEvent, IcalTodo};
+use ical::parser::ical::component::{IcalAlarm, IcalCalendar, IcalEvent, IcalJournal, IcalTimeZone, IcalTimeZoneTransitionType, IcalTodo};
 use ical::property::Property;
 use url::Url;
 
-use crate::item::SyncStatus;
-use crate::task::CompletionStatus;
+use crate::alarm::{Alarm, AlarmAction, AlarmRepeat, AlarmTrigger};
+use crate::attachment::Attachment;
+use crate::attendee::{Attendee, Organizer, ParticipantRole, ParticipationStatus};
+use crate::date_time::IcalDateTime;
+use crate::free_busy::{BusyPeriod, FreeBusyType};
+use crate::item::{Classification, SyncStatus, Uid};
+use crate::recurrence::Recurrence;
+use crate::task::{CompletionStatus, TaskBuilder, TimeTracking};
 use crate::Event;
+use crate::event::{EventBuilder, EventStatus};
 use crate::Item;
+use crate::Journal;
 use crate::Task;
+use super::timezone::{Observance, VTimezone};
+use super::values;
+use super::encoding;
 
-/// Parse an iCal file into the internal representation [`crate::Item`]
+/// Parse an iCal file (or a vCard, for the items of address books) into the internal representation [`crate::Item`]
 pub fn parse(
     content: &str,
     item_url: Url,
     sync_status: SyncStatus,
-) -> Result<Item, Box<dyn Error>> {
+) -> Result<Item, crate::Error> {
+    parse_item(content, item_url, sync_status).map_err(|err| crate::Error::Parse(crate::error::shareable(err)))
+}
+
+fn parse_item(content: &str, item_url: Url, sync_status: SyncStatus) -> Result<Item, Box<dyn Error>> {
+    if super::vcard::is_vcard(content) {
+        return super::vcard::parse(content, item_url, sync_status).map(Item::Contact);
+    }
+
+    let content = encoding::join_soft_line_breaks(content);
     let mut reader = ical::IcalParser::new(content.as_bytes());
     let parsed_item = match reader.next() {
         None => return Err(format!("Invalid iCal data to parse for item {}", item_url).into()),
@@ -37,25 +59,221 @@ pub fn parse(
         .map(|s| s.to_string())
         .unwrap_or_else(super::default_prod_id);
 
-    let item = match assert_single_type(&parsed_item)? {
-        CurrentType::Event(event) => {
-            Item::Event(parse_event(event, item_url, sync_status, ical_prod_id)?)
+    let item = with_embedded_timezones(parsed_item, &item_url, |parsed_item| {
+        parse_components(parsed_item, item_url.clone(), sync_status, ical_prod_id)
+    });
+    let item = item?;
+
+    // What to do with multiple items?
+    if reader.next().map(|r| r.is_ok()) == Some(true) {
+        return Err("Parsing multiple items are not supported".into());
+    }
+
+    Ok(item)
+}
+
+/// Parse an iCal file that contains any number of items (e.g. the export of a whole calendar, see [`CompleteCalendar::export_ics`](crate::traits::CompleteCalendar::export_ics)), as new items of the calendar `calendar_url`.
+///
+/// Components that share a UID (e.g. a recurring event and its modified instances) make a single item.
+/// Every item gets a new random URL, and is [`SyncStatus::NotSynced`]
+pub fn parse_calendar(content: &str, calendar_url: &Url) -> Result<Vec<Item>, crate::Error> {
+    parse_calendar_items(content, calendar_url).map_err(|err| crate::Error::Parse(crate::error::shareable(err)))
+}
+
+fn parse_calendar_items(content: &str, calendar_url: &Url) -> Result<Vec<Item>, Box<dyn Error>> {
+    let mut items = Vec::new();
+    let content = encoding::join_soft_line_breaks(content);
+    for parsed_calendar in ical::IcalParser::new(content.as_bytes()) {
+        let parsed_calendar = parsed_calendar.map_err(|err| format!("Unable to parse iCal data: {}", err))?;
+        let ical_prod_id = extract_ical_prod_id(&parsed_calendar)
+            .map(|s| s.to_string())
+            .unwrap_or_else(super::default_prod_id);
+
+        let parsed_items = with_embedded_timezones(parsed_calendar, calendar_url, |parsed_calendar| {
+            split_by_uid(parsed_calendar).into_iter()
+                .map(|single| parse_components(single, crate::utils::random_url(calendar_url), SyncStatus::NotSynced, ical_prod_id.clone()))
+                .collect::<Result<Vec<Item>, Box<dyn Error>>>()
+        });
+        items.extend(parsed_items?);
+    }
+    Ok(items)
+}
+
+/// Split the components of a VCALENDAR, so that every resulting VCALENDAR only contains the components of a single item (and the VTIMEZONEs of the original one)
+fn split_by_uid(mut parsed_calendar: IcalCalendar) -> Vec<IcalCalendar> {
+    let events = std::mem::take(&mut parsed_calendar.events);
+    let todos = std::mem::take(&mut parsed_calendar.todos);
+    let journals = std::mem::take(&mut parsed_calendar.journals);
+
+    let mut singles: Vec<IcalCalendar> = Vec::new();
+    let mut event_uids: HashMap<String, usize> = HashMap::new();
+    for event in events {
+        let uid = event.properties.iter().find(|prop| prop.name == "UID").and_then(|prop| prop.value.clone());
+        match uid.as_ref().and_then(|uid| event_uids.get(uid)) {
+            Some(index) => singles[*index].events.push(event),
+            None => {
+                if let Some(uid) = uid {
+                    event_uids.insert(uid, singles.len());
+                }
+                let mut single = parsed_calendar.clone();
+                single.events.push(event);
+                singles.push(single);
+            },
+        }
+    }
+    for todo in todos {
+        let mut single = parsed_calendar.clone();
+        single.todos.push(todo);
+        singles.push(single);
+    }
+    for journal in journals {
+        let mut single = parsed_calendar.clone();
+        single.journals.push(journal);
+        singles.push(single);
+    }
+    singles
+}
+
+/// Run `f`, while the VTIMEZONEs of `parsed_calendar` are used to resolve the TZIDs that chrono-tz does not know
+fn with_embedded_timezones<T, F>(parsed_calendar: IcalCalendar, source: &Url, f: F) -> T
+where
+    F: FnOnce(IcalCalendar) -> T,
+{
+    let embedded_timezones = parsed_calendar.timezones.iter()
+        .filter_map(|tz| match parse_timezone(tz) {
+            Ok(tz) => Some(tz),
+            Err(err) => {
+                log::warn!("Ignoring an invalid VTIMEZONE in {}: {}", source, err);
+                None
+            },
+        })
+        .collect();
+    EMBEDDED_TIMEZONES.with(|timezones| *timezones.borrow_mut() = embedded_timezones);
+    let result = f(parsed_calendar);
+    EMBEDDED_TIMEZONES.with(|timezones| timezones.borrow_mut().clear());
+    result
+}
+
+fn parse_components(mut parsed_item: IcalCalendar, item_url: Url, sync_status: SyncStatus, ical_prod_id: String) -> Result<Item, Box<dyn Error>> {
+    decode_legacy_encodings(&mut parsed_item);
+    let item = match assert_single_type(parsed_item)? {
+        CurrentType::Events(events) => {
+            Item::Event(parse_events(events, item_url, sync_status, ical_prod_id)?)
         }
         CurrentType::Todo(todo) => {
             Item::Task(parse_task(todo, item_url, sync_status, ical_prod_id)?)
         }
+        CurrentType::Journal(journal) => {
+            Item::Journal(parse_journal(journal, item_url, sync_status, ical_prod_id)?)
+        }
     };
+    Ok(item)
+}
 
-    // What to do with multiple items?
-    if reader.next().map(|r| r.is_ok()) == Some(true) {
-        return Err("Parsing multiple items are not supported".into());
+/// Decode the properties of the components of an item that are quoted-printable (see the `encoding` module)
+fn decode_legacy_encodings(parsed_item: &mut IcalCalendar) {
+    for event in parsed_item.events.iter_mut() {
+        encoding::decode_properties(&mut event.properties);
+        for alarm in event.alarms.iter_mut() {
+            encoding::decode_properties(&mut alarm.properties);
+        }
+    }
+    for todo in parsed_item.todos.iter_mut() {
+        encoding::decode_properties(&mut todo.properties);
+        for alarm in todo.alarms.iter_mut() {
+            encoding::decode_properties(&mut alarm.properties);
+        }
+    }
+    for journal in parsed_item.journals.iter_mut() {
+        encoding::decode_properties(&mut journal.properties);
     }
+}
 
-    Ok(item)
+/// Parse the VFREEBUSY components of an iCal file (e.g. the reply to a CalDAV `free-busy-query` REPORT) into busy periods, sorted by start date.
+///
+/// `FBTYPE=FREE` periods are skipped
+pub fn parse_free_busy(content: &str) -> Result<Vec<BusyPeriod>, crate::Error> {
+    parse_busy_periods(content).map_err(|err| crate::Error::Parse(crate::error::shareable(err)))
+}
+
+fn parse_busy_periods(content: &str) -> Result<Vec<BusyPeriod>, Box<dyn Error>> {
+    let mut busy_periods = Vec::new();
+    for calendar in ical::IcalParser::new(content.as_bytes()) {
+        let calendar = calendar.map_err(|err| format!("Unable to parse free/busy data: {}", err))?;
+        for prop in calendar.free_busys.iter().flat_map(|fb| fb.properties.iter()) {
+            if prop.name != "FREEBUSY" {
+                continue;
+            }
+            let kind = match property_param(prop, "FBTYPE").map(|t| t.trim()) {
+                Some("FREE") => continue,
+                Some(other) => FreeBusyType::from(other),
+                None => FreeBusyType::Busy,
+            };
+            let periods = values::parse_list(prop.value.as_deref().unwrap_or_default(), |v| values::parse_period(v, None))?;
+            for period in periods {
+                let start = period.start().to_utc();
+                let end = match &period {
+                    values::PeriodValue::Explicit { end, .. } => Some(end.to_utc()),
+                    values::PeriodValue::Duration { duration, .. } => start.checked_add_signed(*duration),
+                };
+                match end {
+                    Some(end) => busy_periods.push(BusyPeriod::new(start, end, kind.clone())),
+                    None => log::warn!("Ignoring busy period {:?}, whose end is out of range", period),
+                }
+            }
+        }
+    }
+    busy_periods.sort_by_key(|period| *period.start());
+    Ok(busy_periods)
+}
+
+/// Parse a VTIMEZONE component
+fn parse_timezone(timezone: &IcalTimeZone) -> Result<VTimezone, Box<dyn Error>> {
+    let tzid = timezone.properties.iter()
+        .find(|prop| prop.name == "TZID")
+        .and_then(|prop| prop.value.clone())
+        .ok_or("Missing TZID")?;
+
+    let mut observances = Vec::new();
+    for transition in &timezone.transitions {
+        let mut start = None;
+        let mut offset_from = None;
+        let mut offset_to = None;
+        let mut recurrence = None;
+        let mut name = None;
+        for prop in &transition.properties {
+            match prop.name.as_str() {
+                "DTSTART" => start = prop.value.as_deref().and_then(|s| NaiveDateTime::parse_from_str(s, "%Y%m%dT%H%M%S").ok()),
+                "TZOFFSETFROM" => offset_from = prop.value.as_deref().and_then(|s| values::parse_utc_offset(s.trim()).ok()),
+                "TZOFFSETTO" => offset_to = prop.value.as_deref().and_then(|s| values::parse_utc_offset(s.trim()).ok()),
+                "RRULE" => recurrence = prop.value.as_deref().and_then(|s| match s.parse::<Recurrence>() {
+                    Ok(rule) => Some(rule),
+                    Err(err) => {
+                        log::warn!("Ignoring an invalid RRULE in timezone {}: {}", tzid, err);
+                        None
+                    },
+                }),
+                "TZNAME" => name = prop.value.clone(),
+                _ => (),
+            }
+        }
+        let daylight = matches!(transition.transition, IcalTimeZoneTransitionType::DAYLIGHT);
+        match (start, offset_from, offset_to) {
+            (Some(start), Some(offset_from), Some(offset_to)) => {
+                observances.push(Observance::new(daylight, start, offset_from, offset_to, recurrence, name));
+            },
+            _ => return Err(format!("Invalid STANDARD or DAYLIGHT component in timezone {}", tzid).into()),
+        }
+    }
+
+    if observances.is_empty() {
+        return Err(format!("Timezone {} has no STANDARD nor DAYLIGHT component", tzid).into());
+    }
+    Ok(VTimezone::new(tzid, observances))
 }
 
 fn parse_task(
-    todo: &IcalTodo,
+    todo: IcalTodo,
     item_url: Url,
     sync_status: SyncStatus,
     ical_prod_id: String,
@@ -63,39 +281,58 @@ fn parse_task(
     let mut name = None;
     let mut uid = None;
     let mut completed = false;
+    let mut status = None;
     let mut last_modified = None;
     let mut completion_date = None;
+    let mut floating_completion = false;
     let mut creation_date = None;
-    let mut extra_parameters = Vec::new();
+    let mut priority = None;
+    let mut due = None;
+    let mut all_day_due = false;
+    let mut due_tzid = None;
+    let mut floating_due = false;
+    let mut percent_complete = None;
+    let mut parent = None;
+    let mut geo = None;
+    let mut class = None;
+    let mut start = None;
+    let mut duration = None;
+    let mut categories = Vec::new();
+    let mut attachments = Vec::new();
+    let mut timer_started_at = None;
+    let mut time_spent = 0;
+    let mut extra_parameters = Vec::with_capacity(todo.properties.len());
 
-    for prop in &todo.properties {
+    // Properties are moved rather than cloned, this matters when parsing large calendars
+    for prop in todo.properties {
         match prop.name.as_str() {
-            "SUMMARY" => name = prop.value.clone(),
-            "UID" => uid = prop.value.clone(),
+            "SUMMARY" => name = prop.value.as_deref().map(values::unescape_text),
+            "UID" => uid = prop.value,
             "DTSTAMP" => {
                 // The property can be specified once, but is not mandatory
                 // "This property specifies the date and time that the information associated with
                 //  the calendar component was last revised in the calendar store."
                 // "In the case of an iCalendar object that doesn't specify a "METHOD"
                 //  property [e.g.: VTODO and VEVENT], this property is equivalent to the "LAST-MODIFIED" property".
-                last_modified = parse_date_time_from_property(prop);
+                last_modified = parse_date_time_from_property(&prop);
             }
             "LAST-MODIFIED" => {
                 // The property can be specified once, but is not mandatory
                 // "This property specifies the date and time that the information associated with
                 //  the calendar component was last revised in the calendar store."
                 // In practise, for VEVENT and VTODO, this is generally the same value as DTSTAMP.
-                last_modified = parse_date_time_from_property(prop);
+                last_modified = parse_date_time_from_property(&prop);
             }
             "COMPLETED" => {
                 // The property can be specified once, but is not mandatory
                 // "This property defines the date and time that a to-do was
                 //  actually completed."
-                completion_date = parse_date_time_from_property(prop)
+                completion_date = parse_date_time_from_property(&prop);
+                floating_completion = is_floating(&prop);
             }
             "CREATED" => {
                 // The property can be specified once, but is not mandatory
-                creation_date = parse_date_time_from_property(prop)
+                creation_date = parse_date_time_from_property(&prop)
             }
             "STATUS" => {
                 // Possible values:
@@ -103,13 +340,98 @@ fn parse_task(
                 //   "COMPLETED"    ;Indicates to-do completed.
                 //   "IN-PROCESS"   ;Indicates to-do in process of.
                 //   "CANCELLED"    ;Indicates to-do was cancelled.
-                if prop.value.as_ref().map(|s| s.as_str()) == Some("COMPLETED") {
-                    completed = true;
+                match prop.value.as_deref().map(|s| s.trim()) {
+                    Some("COMPLETED") => completed = true,
+                    Some("IN-PROCESS") => status = Some(CompletionStatus::InProcess),
+                    Some("CANCELLED") => status = Some(CompletionStatus::Cancelled),
+                    _ => (),
+                }
+            }
+            "DUE" => {
+                match parse_date_from_property(&prop) {
+                    Some(date) => {
+                        due = Some(Utc.from_utc_date(&date).and_hms(0, 0, 0));
+                        all_day_due = true;
+                    },
+                    None => {
+                        due = parse_date_time_from_property(&prop);
+                        due_tzid = written_tzid(&prop);
+                        floating_due = is_floating(&prop);
+                    },
+                }
+            }
+            "PERCENT-COMPLETE" => {
+                // "The value is a positive integer between 0 and 100."
+                match prop.value.as_deref().map(|v| v.parse::<u8>()) {
+                    Some(Ok(p)) if p <= 100 => percent_complete = Some(p),
+                    _ => {
+                        log::warn!("Invalid PERCENT-COMPLETE {:?} for item {}, keeping it as is", prop.value, item_url);
+                        extra_parameters.push(prop);
+                    }
+                }
+            }
+            "DTSTART" => {
+                // DTSTART is not supported yet, but it is needed to compute the due date of tasks that have a DURATION
+                start = match parse_date_from_property(&prop) {
+                    Some(date) => Some((Utc.from_utc_date(&date).and_hms(0, 0, 0), true, None, false)),
+                    None => parse_date_time_from_property(&prop).map(|dt| (dt, false, written_tzid(&prop), is_floating(&prop))),
+                };
+                extra_parameters.push(prop);
+            }
+            "DURATION" => duration = Some(prop),
+            "PRIORITY" => {
+                // "A value of 0 specifies an undefined priority. A value of 1 is the highest priority. [...] A value of 9 is the lowest priority."
+                match prop.value.as_deref().map(|v| v.parse::<u8>()) {
+                    Some(Ok(0)) => priority = None,
+                    Some(Ok(p)) if p <= 9 => priority = Some(p),
+                    _ => {
+                        log::warn!("Invalid PRIORITY {:?} for item {}, keeping it as is", prop.value, item_url);
+                        extra_parameters.push(prop);
+                    }
+                }
+            }
+            "GEO" => {
+                match prop.value.as_deref().map(parse_geo) {
+                    Some(Ok(position)) => geo = Some(position),
+                    _ => {
+                        log::warn!("Invalid GEO {:?} for item {}, keeping it as is", prop.value, item_url);
+                        extra_parameters.push(prop);
+                    }
+                }
+            }
+            "CLASS" => class = prop.value.as_deref().map(|s| Classification::from(s.trim())),
+            "ATTACH" => {
+                match parse_attachment(&prop) {
+                    Ok(attachment) => attachments.push(attachment),
+                    Err(err) => {
+                        log::warn!("Unable to parse ATTACH of item {} ({}), keeping it as is", item_url, err);
+                        extra_parameters.push(prop);
+                    }
+                }
+            }
+            "RELATED-TO" => {
+                // "RELTYPE=PARENT" is the default relationship type
+                let is_parent = property_param(&prop, "RELTYPE").map(|t| t.eq_ignore_ascii_case("PARENT")).unwrap_or(true);
+                match prop.value.as_deref().map(Uid::new) {
+                    Some(Ok(parent_uid)) if is_parent && parent.is_none() => parent = Some(parent_uid),
+                    // Other relationship types (and additional parents) are not supported yet
+                    _ => extra_parameters.push(prop),
+                }
+            }
+            "CATEGORIES" => add_categories(&mut categories, &prop),
+            "X-TIMER-STARTED" => timer_started_at = parse_date_time_from_property(&prop),
+            "X-TIME-SPENT" => {
+                match prop.value.as_deref().map(parse_duration) {
+                    Some(Ok(seconds)) => time_spent = seconds,
+                    _ => {
+                        log::warn!("Unable to parse X-TIME-SPENT {:?} of item {}, keeping it as is", prop.value, item_url);
+                        extra_parameters.push(prop);
+                    }
                 }
             }
             _ => {
                 // This field is not supported. Let's store it anyway, so that we are able to re-create an identical iCal file
-                extra_parameters.push(prop.clone());
+                extra_parameters.push(prop);
             }
         }
     }
@@ -117,8 +439,9 @@ fn parse_task(
         Some(name) => name,
         None => return Err(format!("Missing name for item {}", item_url).into()),
     };
-    let uid = match uid {
-        Some(uid) => uid,
+    let uid = match uid.as_deref().map(Uid::new) {
+        Some(Ok(uid)) => uid,
+        Some(Err(err)) => return Err(format!("Invalid UID for item {}: {}", item_url, err).into()),
         None => return Err(format!("Missing UID for item {}", item_url).into()),
     };
     let last_modified = match last_modified {
@@ -136,72 +459,230 @@ fn parse_task(
             if completion_date.is_some() {
                 log::warn!("Task {:?} has an inconsistent content: its STATUS is not completed, yet it has a COMPLETED timestamp at {:?}", uid, completion_date);
             }
-            CompletionStatus::Uncompleted
+            status.unwrap_or(CompletionStatus::Uncompleted)
         }
         true => CompletionStatus::Completed(completion_date),
     };
+    // Completed tasks are always 100% complete
+    let percent_complete = percent_complete.filter(|_| completion_status.is_completed() == false);
 
-    Ok(Task::new_with_parameters(
-        name,
-        uid,
-        item_url,
-        completion_status,
-        sync_status,
-        creation_date,
-        last_modified,
-        ical_prod_id,
-        extra_parameters,
-    ))
+    // "In a "VTODO" calendar component the property ["DURATION"] may be used to specify a duration for the to-do,
+    //  in lieu of the "DUE" property."
+    // In this case, the due date is computed, and the task is serialized back with a DUE property instead
+    if let Some(duration_prop) = duration {
+        match (due, start, duration_prop.value.as_deref().map(parse_duration)) {
+            (None, Some((start, start_all_day, start_tzid, start_floating)), Some(Ok(seconds))) => {
+                due = Some(start.checked_add_signed(chrono::Duration::seconds(seconds))
+                    .ok_or_else(|| format!("DURATION is out of range for item {}", item_url))?);
+                all_day_due = start_all_day;
+                // The due date is written the same way as DTSTART
+                due_tzid = start_tzid;
+                floating_due = start_floating;
+            },
+            _ => extra_parameters.push(duration_prop),
+        }
+    }
+
+    let alarms = parse_alarms(todo.alarms, &item_url);
+    let mut builder = TaskBuilder::new(name, &item_url)
+        .with_url(item_url)
+        .with_uid(uid)
+        .with_completion_status(completion_status)
+        .with_sync_status(sync_status)
+        .with_creation_date(creation_date)
+        .with_last_modified(last_modified)
+        .with_ical_prod_id(ical_prod_id)
+        .with_time_tracking(TimeTracking::new(timer_started_at, time_spent))
+        .with_extra_parameters(extra_parameters);
+    for alarm in alarms {
+        builder = builder.with_alarm(alarm);
+    }
+    for attachment in attachments {
+        builder = builder.with_attachment(attachment);
+    }
+    for category in categories {
+        builder = builder.with_category(category);
+    }
+    if let Some(priority) = priority {
+        builder = builder.with_priority(priority);
+    }
+    if let Some(due) = due {
+        builder = builder.with_due(due, all_day_due);
+    }
+    if let Some(tzid) = due_tzid {
+        builder = builder.with_due_tzid(tzid);
+    }
+    if floating_due {
+        builder = builder.floating_due();
+    }
+    if floating_completion {
+        builder = builder.floating_completion();
+    }
+    if let Some(percent_complete) = percent_complete {
+        builder = builder.with_percent_complete(percent_complete);
+    }
+    if let Some(parent) = parent {
+        builder = builder.with_parent(parent);
+    }
+    if let Some((latitude, longitude)) = geo {
+        builder = builder.with_geo(latitude, longitude);
+    }
+    if let Some(class) = class {
+        builder = builder.with_classification(class);
+    }
+
+    // Whatever the server sent is kept as is, even if it does not pass the checks of `TaskBuilder::build`
+    Ok(builder.build_unchecked())
+}
+
+/// Parse the VEVENTs of a single resource: a recurring event may come with modified instances of it, that have the same UID and a RECURRENCE-ID
+fn parse_events(
+    events: Vec<IcalEvent>,
+    item_url: Url,
+    sync_status: SyncStatus,
+    ical_prod_id: String,
+) -> Result<Event, Box<dyn Error>> {
+    let mut master = None;
+    let mut overrides = Vec::with_capacity(events.len() - 1);
+    for ical_event in events {
+        let event = parse_event(ical_event, item_url.clone(), sync_status.clone(), ical_prod_id.clone())?;
+        match (event.recurrence_id(), &master) {
+            (Some(_), _) => overrides.push(event),
+            (None, None) => master = Some(event),
+            (None, Some(_)) => return Err(format!("Item {} contains several events without a RECURRENCE-ID", item_url).into()),
+        }
+    }
+
+    let master = match master {
+        Some(master) => master,
+        None => {
+            // The recurring event has probably been deleted. Let's keep its instances anyway (see `Event::is_orphaned_instance`)
+            log::warn!("Item {} only contains modified instances of a recurring event", item_url);
+            overrides.remove(0)
+        },
+    };
+    if overrides.iter().any(|o| o.uid() != master.uid()) {
+        return Err(format!("Item {} contains events with different UIDs", item_url).into());
+    }
+    Ok(master.with_overrides(overrides))
 }
 
 fn parse_event(
-    event: &IcalEvent,
+    event: IcalEvent,
     item_url: Url,
     sync_status: SyncStatus,
     ical_prod_id: String,
 ) -> Result<Event, Box<dyn Error>> {
     let mut name = None;
     let mut description = None;
+    let mut location = None;
+    let mut geo = None;
+    let mut class = None;
     let mut uid = None;
     let mut last_modified = None;
     let mut creation_date = None;
     let mut start = None;
     let mut end = None;
-    let mut extra_parameters = Vec::new();
+    let mut tzid = None;
+    let mut floating = false;
+    let mut all_day = false;
+    let mut duration = None;
+    let mut status = None;
+    let mut recurrence = None;
+    let mut recurrence_id = None;
+    let mut organizer = None;
+    let mut attendees = Vec::new();
+    let mut categories = Vec::new();
+    let mut attachments = Vec::new();
+    let mut extra_parameters = Vec::with_capacity(event.properties.len());
 
-    for prop in &event.properties {
+    for prop in event.properties {
         match prop.name.as_str() {
-            "SUMMARY" => name = prop.value.clone(),
-            "DESCRIPTION" => description = prop.value.clone(),
-            "UID" => uid = prop.value.clone(),
+            "SUMMARY" => name = prop.value.as_deref().map(values::unescape_text),
+            "DESCRIPTION" => description = prop.value.as_deref().map(values::unescape_text),
+            "LOCATION" => location = prop.value.as_deref().map(values::unescape_text),
+            "GEO" => {
+                match prop.value.as_deref().map(parse_geo) {
+                    Some(Ok(position)) => geo = Some(position),
+                    _ => {
+                        log::warn!("Invalid GEO {:?} for item {}, keeping it as is", prop.value, item_url);
+                        extra_parameters.push(prop);
+                    }
+                }
+            }
+            "CLASS" => class = prop.value.as_deref().map(|s| Classification::from(s.trim())),
+            "UID" => uid = prop.value,
             "DTSTAMP" => {
                 // The property can be specified once, but is not mandatory
                 // "This property specifies the date and time that the information associated with
                 //  the calendar component was last revised in the calendar store."
                 // "In the case of an iCalendar object that doesn't specify a "METHOD"
                 //  property [e.g.: VTODO and VEVENT], this property is equivalent to the "LAST-MODIFIED" property".
-                last_modified = parse_date_time_from_property(prop);
+                last_modified = parse_date_time_from_property(&prop);
             }
             "DTSTART" => {
-                start = parse_date_time_from_property(prop);
+                match parse_date_from_property(&prop) {
+                    Some(date) => {
+                        start = Some(Utc.from_utc_date(&date).and_hms(0, 0, 0));
+                        all_day = true;
+                    },
+                    None => {
+                        start = parse_date_time_from_property(&prop);
+                        tzid = written_tzid(&prop);
+                        floating = is_floating(&prop);
+                    },
+                }
             }
             "DTEND" => {
-                end = parse_date_time_from_property(prop);
+                end = match parse_date_from_property(&prop) {
+                    Some(date) => Some(Utc.from_utc_date(&date).and_hms(0, 0, 0)),
+                    None => parse_date_time_from_property(&prop),
+                };
             }
+            "DURATION" if duration.is_none() => duration = Some(prop),
+            "STATUS" => status = prop.value.as_deref().map(|s| EventStatus::from(s.trim())),
             "LAST-MODIFIED" => {
                 // The property can be specified once, but is not mandatory
                 // "This property specifies the date and time that the information associated with
                 //  the calendar component was last revised in the calendar store."
                 // In practise, for VEVENT and VTODO, this is generally the same value as DTSTAMP.
-                last_modified = parse_date_time_from_property(prop);
+                last_modified = parse_date_time_from_property(&prop);
             }
             "CREATED" => {
                 // The property can be specified once, but is not mandatory
-                creation_date = parse_date_time_from_property(prop)
+                creation_date = parse_date_time_from_property(&prop)
+            }
+            "RECURRENCE-ID" => {
+                recurrence_id = match parse_date_from_property(&prop) {
+                    Some(date) => Some(Utc.from_utc_date(&date).and_hms(0, 0, 0)),
+                    None => parse_date_time_from_property(&prop),
+                };
+            }
+            "ORGANIZER" if organizer.is_none() && prop.value.is_some() => organizer = Some(parse_organizer(prop)),
+            "ATTENDEE" if prop.value.is_some() => attendees.push(parse_attendee(prop)),
+            "ATTACH" => {
+                match parse_attachment(&prop) {
+                    Ok(attachment) => attachments.push(attachment),
+                    Err(err) => {
+                        log::warn!("Unable to parse ATTACH of item {} ({}), keeping it as is", item_url, err);
+                        extra_parameters.push(prop);
+                    }
+                }
+            }
+            "CATEGORIES" => add_categories(&mut categories, &prop),
+            "RRULE" if recurrence.is_none() => {
+                // "This property [...] SHOULD NOT be specified more than once."
+                match prop.value.as_deref().map(|v| v.parse::<Recurrence>()) {
+                    Some(Ok(rule)) => recurrence = Some(rule),
+                    _ => {
+                        log::warn!("Unable to parse RRULE {:?} of item {}, keeping it as is", prop.value, item_url);
+                        extra_parameters.push(prop);
+                    }
+                }
             }
             _ => {
                 // This field is not supported. Let's store it anyway, so that we are able to re-create an identical iCal file
-                extra_parameters.push(prop.clone());
+                extra_parameters.push(prop);
             }
         }
     }
@@ -209,8 +690,9 @@ fn parse_event(
         Some(name) => name,
         None => return Err(format!("Missing name for item {}", item_url).into()),
     };
-    let uid = match uid {
-        Some(uid) => uid,
+    let uid = match uid.as_deref().map(Uid::new) {
+        Some(Ok(uid)) => uid,
+        Some(Err(err)) => return Err(format!("Invalid UID for item {}: {}", item_url, err).into()),
         None => return Err(format!("Missing UID for item {}", item_url).into()),
     };
     let last_modified = match last_modified {
@@ -224,16 +706,214 @@ fn parse_event(
         }
     };
     let start = start.ok_or_else(|| format!("Missing DTSTART for item {}", item_url))?;
-    let end = end.ok_or_else(|| format!("Missing DTEND for item {}", item_url))?;
+    // "In a "VEVENT" calendar component the property ["DURATION"] may be used to specify a duration of the event,
+    //  instead of an explicit end DATE-TIME."
+    let mut duration_form = false;
+    if let Some(duration_prop) = duration {
+        match (end, duration_prop.value.as_deref().map(parse_duration)) {
+            (None, Some(Ok(seconds))) => {
+                end = Some(start.checked_add_signed(chrono::Duration::seconds(seconds))
+                    .ok_or_else(|| format!("DURATION is out of range for item {}", item_url))?);
+                duration_form = true;
+            },
+            _ => extra_parameters.push(duration_prop),
+        }
+    }
+    let end = match (end, all_day) {
+        (Some(end), _) => end,
+        // "For cases where a "VEVENT" calendar component specifies a "DTSTART" property with a DATE value type
+        //  but no "DTEND" nor "DURATION" property, the event's duration is taken to be one day."
+        (None, true) => start.checked_add_signed(chrono::Duration::days(1))
+            .ok_or_else(|| format!("DTSTART is out of range for item {}", item_url))?,
+        (None, false) => return Err(format!("Missing DTEND (or DURATION) for item {}", item_url).into()),
+    };
+
+    let alarms = parse_alarms(event.alarms, &item_url);
+
+    let mut builder = EventBuilder::new(name, start, end, &item_url)
+        .with_url(item_url)
+        .with_uid(uid)
+        .with_sync_status(sync_status)
+        .with_creation_date(creation_date)
+        .with_last_modified(last_modified)
+        .with_ical_prod_id(ical_prod_id)
+        .with_extra_parameters(extra_parameters);
+    for alarm in alarms {
+        builder = builder.with_alarm(alarm);
+    }
+    for attachment in attachments {
+        builder = builder.with_attachment(attachment);
+    }
+    for attendee in attendees {
+        builder = builder.with_attendee(attendee);
+    }
+    for category in categories {
+        builder = builder.with_category(category);
+    }
+    if let Some(description) = description {
+        builder = builder.with_description(description);
+    }
+    if let Some(location) = location {
+        builder = builder.with_location(location);
+    }
+    if let Some((latitude, longitude)) = geo {
+        builder = builder.with_geo(latitude, longitude);
+    }
+    if let Some(status) = status {
+        builder = builder.with_status(status);
+    }
+    if let Some(class) = class {
+        builder = builder.with_classification(class);
+    }
+    if let Some(tzid) = tzid {
+        builder = builder.with_tzid(tzid);
+    }
+    if floating {
+        builder = builder.floating();
+    }
+    if all_day {
+        builder = builder.all_day();
+    }
+    if duration_form {
+        builder = builder.duration_form();
+    }
+    if let Some(recurrence) = recurrence {
+        builder = builder.with_recurrence(recurrence);
+    }
+    if let Some(recurrence_id) = recurrence_id {
+        builder = builder.with_recurrence_id(recurrence_id);
+    }
+    if let Some(organizer) = organizer {
+        builder = builder.with_organizer(organizer);
+    }
+
+    // Whatever the server sent is kept as is, even if it does not pass the checks of `EventBuilder::build`
+    Ok(builder.build_unchecked())
+}
+
+fn parse_organizer(prop: Property) -> Organizer {
+    let mut common_name = None;
+    let mut extra_parameters = Vec::new();
+    for (name, values) in prop.params.unwrap_or_default() {
+        match name.as_str() {
+            "CN" => common_name = values.into_iter().next(),
+            _ => extra_parameters.push((name, values)),
+        }
+    }
+    Organizer::new_with_parameters(prop.value.unwrap_or_default(), common_name, extra_parameters)
+}
+
+fn parse_attendee(prop: Property) -> Attendee {
+    let mut common_name = None;
+    let mut status = None;
+    let mut role = None;
+    let mut rsvp = false;
+    let mut extra_parameters = Vec::new();
+    for (name, values) in prop.params.unwrap_or_default() {
+        let first = values.first().map(|v| v.as_str());
+        match name.as_str() {
+            "CN" => common_name = first.map(|v| v.to_string()),
+            "PARTSTAT" => status = first.map(ParticipationStatus::from),
+            "ROLE" => role = first.map(ParticipantRole::from),
+            "RSVP" => rsvp = first == Some("TRUE"),
+            _ => extra_parameters.push((name, values)),
+        }
+    }
+    Attendee::new_with_parameters(prop.value.unwrap_or_default(), common_name, status, role, rsvp, extra_parameters)
+}
+
+fn parse_attachment(prop: &Property) -> Result<Attachment, Box<dyn Error>> {
+    let value = prop.value.as_deref().ok_or("missing value")?;
+    let mut format_type = None;
+    let mut is_binary = false;
+    let mut extra_parameters = Vec::new();
+    for (name, values) in prop.params.clone().unwrap_or_default() {
+        let first = values.first().map(|v| v.as_str());
+        match name.as_str() {
+            "FMTTYPE" => format_type = first.map(|v| v.to_string()),
+            "VALUE" if first == Some("BINARY") => is_binary = true,
+            "ENCODING" if first == Some("BASE64") => is_binary = true,
+            "VALUE" | "ENCODING" => return Err(format!("unsupported {} {:?}", name, first).into()),
+            _ => extra_parameters.push((name, values)),
+        }
+    }
+
+    if is_binary {
+        // Folded lines may leave whitespace in the middle of the value
+        let encoded: String = value.chars().filter(|c| c.is_whitespace() == false).collect();
+        let data = base64::decode(&encoded)?;
+        Ok(Attachment::Binary { data, format_type, extra_parameters })
+    } else {
+        // The URI is kept as it is written, but it must be valid
+        Url::parse(value)?;
+        Ok(Attachment::Uri { uri: value.to_string(), format_type, extra_parameters })
+    }
+}
 
-    Ok(Event::new_with_parameters(
+fn parse_journal(
+    journal: IcalJournal,
+    item_url: Url,
+    sync_status: SyncStatus,
+    ical_prod_id: String,
+) -> Result<Journal, Box<dyn Error>> {
+    let mut name = None;
+    let mut description = None;
+    let mut uid = None;
+    let mut last_modified = None;
+    let mut creation_date = None;
+    let mut start = None;
+    let mut all_day = false;
+    let mut extra_parameters = Vec::with_capacity(journal.properties.len());
+
+    for prop in journal.properties {
+        match prop.name.as_str() {
+            "SUMMARY" => name = prop.value.as_deref().map(values::unescape_text),
+            // "DESCRIPTION" may occur several times in journals. Only the first one is handled
+            "DESCRIPTION" if description.is_none() => description = prop.value.as_deref().map(values::unescape_text),
+            "UID" => uid = prop.value,
+            "DTSTAMP" | "LAST-MODIFIED" => last_modified = parse_date_time_from_property(&prop),
+            "CREATED" => creation_date = parse_date_time_from_property(&prop),
+            "DTSTART" => {
+                match parse_date_from_property(&prop) {
+                    Some(date) => {
+                        start = Some(Utc.from_utc_date(&date).and_hms(0, 0, 0));
+                        all_day = true;
+                    },
+                    None => start = parse_date_time_from_property(&prop),
+                }
+            }
+            _ => {
+                // This field is not supported. Let's store it anyway, so that we are able to re-create an identical iCal file
+                extra_parameters.push(prop);
+            }
+        }
+    }
+    // "SUMMARY" is optional for journals
+    let name = name.unwrap_or_default();
+    let uid = match uid.as_deref().map(Uid::new) {
+        Some(Ok(uid)) => uid,
+        Some(Err(err)) => return Err(format!("Invalid UID for item {}: {}", item_url, err).into()),
+        None => return Err(format!("Missing UID for item {}", item_url).into()),
+    };
+    let last_modified = match last_modified {
+        Some(dt) => dt,
+        None => {
+            return Err(format!(
+                "Missing DTSTAMP for item {}, but this is required by RFC5545",
+                item_url
+            )
+            .into())
+        }
+    };
+
+    Ok(Journal::new_with_parameters(
         name,
         uid,
         item_url,
         description,
         sync_status,
         start,
-        end,
+        all_day,
         creation_date,
         last_modified,
         ical_prod_id,
@@ -241,27 +921,223 @@ fn parse_event(
     ))
 }
 
+fn parse_alarms(ical_alarms: Vec<IcalAlarm>, item_url: &Url) -> Vec<Alarm> {
+    ical_alarms.into_iter()
+        .filter_map(|ical_alarm| match parse_alarm(ical_alarm) {
+            Ok(alarm) => Some(alarm),
+            Err(err) => {
+                log::warn!("Ignoring an invalid alarm in item {}: {}", item_url, err);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Parse a VALARM. The properties that cannot be parsed (e.g. an invalid TRIGGER, or a REPEAT without a DURATION) are kept as they are, so that the alarm is written back unchanged. \
+/// Only alarms without an ACTION or a TRIGGER (that are both required, see RFC 5545, section 3.6.6) cannot be kept
+fn parse_alarm(ical_alarm: IcalAlarm) -> Result<Alarm, Box<dyn Error>> {
+    let mut action = None;
+    let mut trigger = None;
+    let mut description = None;
+    let mut repeat_count = None;
+    let mut repeat_interval = None;
+    let mut extra_parameters = Vec::new();
+
+    for prop in ical_alarm.properties {
+        match prop.name.as_str() {
+            "ACTION" => action = prop.value.as_deref().map(AlarmAction::from),
+            "TRIGGER" => {
+                trigger = Some(match parse_trigger(&prop) {
+                    Ok(parsed) => parsed,
+                    Err(err) => {
+                        log::warn!("Keeping an unparsable alarm trigger as is: {}", err);
+                        AlarmTrigger::Unparsed(prop)
+                    },
+                });
+            }
+            "DESCRIPTION" => description = prop.value.as_deref().map(values::unescape_text),
+            "REPEAT" => repeat_count = Some(prop),
+            "DURATION" => repeat_interval = Some(prop),
+            _ => extra_parameters.push(prop),
+        }
+    }
+
+    let action = action.ok_or("Missing ACTION")?;
+    let trigger = trigger.ok_or("Missing TRIGGER")?;
+    // "'duration' and 'repeat' are both OPTIONAL, and MUST NOT occur more than once each;
+    //  but if one occurs, so MUST the other."
+    let repeat = match (&repeat_count, &repeat_interval) {
+        (Some(count), Some(interval)) => parse_repeat(count, interval).ok(),
+        _ => None,
+    };
+    if repeat.is_none() {
+        extra_parameters.extend(repeat_count.into_iter().chain(repeat_interval));
+    }
+
+    Ok(Alarm::new_with_parameters(action, trigger, description, repeat, extra_parameters))
+}
+
+fn parse_trigger(prop: &Property) -> Result<AlarmTrigger, Box<dyn Error>> {
+    let value = prop.value.as_deref().ok_or("Empty TRIGGER")?;
+    let is_absolute = property_param(prop, "VALUE") == Some("DATE-TIME");
+    Ok(match is_absolute {
+        true => AlarmTrigger::Absolute(
+            parse_date_time_from_property(prop).ok_or_else(|| format!("Invalid TRIGGER date {}", value))?
+        ),
+        false => AlarmTrigger::Relative {
+            offset_seconds: parse_duration(value)?,
+            related_to_end: property_param(prop, "RELATED") == Some("END"),
+        },
+    })
+}
+
+fn parse_repeat(count: &Property, interval: &Property) -> Result<AlarmRepeat, Box<dyn Error>> {
+    Ok(AlarmRepeat {
+        count: count.value.as_deref().ok_or("Empty REPEAT")?.parse::<u32>()?,
+        interval_seconds: parse_duration(interval.value.as_deref().ok_or("Empty DURATION")?)?,
+    })
+}
+
+/// Parse an iCal DURATION value (e.g. `-PT15M`, `P1W`, or `P1DT2H`) into a number of seconds
+pub(crate) fn parse_duration(s: &str) -> Result<i64, Box<dyn Error>> {
+    Ok(values::parse_duration(s)?.num_seconds())
+}
+
+/// CATEGORIES may be specified several times, each one holding a comma-separated list
+fn add_categories(categories: &mut Vec<String>, property: &Property) {
+    for category in values::split_text_list(property.value.as_deref().unwrap_or_default()) {
+        if category.is_empty() == false && categories.contains(&category) == false {
+            categories.push(category);
+        }
+    }
+}
+
+/// Parse a GEO value, i.e. "latitude;longitude" (in degrees)
+fn parse_geo(value: &str) -> Result<(f64, f64), Box<dyn Error>> {
+    let mut parts = value.split(';');
+    let (latitude, longitude) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(lat), Some(lon), None) => (lat.trim().parse::<f64>()?, lon.trim().parse::<f64>()?),
+        _ => return Err(format!("Invalid GEO value {}", value).into()),
+    };
+    crate::utils::check_geo_position(latitude, longitude)?;
+    Ok((latitude, longitude))
+}
+
+fn property_param<'a>(property: &'a Property, name: &str) -> Option<&'a str> {
+    property.params.as_ref().and_then(|params| {
+        params
+            .iter()
+            .find(|(n, _)| n == name)
+            .and_then(|(_, v)| v.iter().next())
+            .map(|v| v.as_str())
+    })
+}
+
+/// Parse properties that have a DATE value (e.g. `DTSTART;VALUE=DATE:20210321`), as used by all-day events
+fn parse_date_from_property(property: &Property) -> Option<NaiveDate> {
+    let s: &str = property.value.as_deref()?;
+    if s.len() != 8 {
+        return None;
+    }
+    NaiveDate::parse_from_str(s, "%Y%m%d").ok()
+}
+
 fn parse_date_time_from_property(property: &Property) -> Option<DateTime<Utc>> {
-    use std::str::FromStr;
+    let s: &str = property.value.as_deref()?;
+    // Some clients add a TZID to UTC values, it is ignored rather than rejected
+    let date_time = values::parse_date_time(s, local_time_tzid(property).map(String::as_str)).ok()?;
+    Some(date_time_to_utc(&date_time))
+}
+
+/// Parse properties that can hold several comma-separated DATE or DATE-TIME values (e.g. `RDATE` or `EXDATE`).
+///
+/// DATE values are returned at midnight UTC, just like the dates of all-day events. Values that cannot be parsed (e.g. PERIOD values) are ignored
+pub(crate) fn parse_date_times_from_property(property: &Property) -> Vec<DateTime<Utc>> {
+    let tzid = property_tzid(property);
+    property.value.as_deref().unwrap_or_default()
+        .split(',')
+        .filter_map(|s| match s.len() {
+            8 => NaiveDate::parse_from_str(s, "%Y%m%d").ok().map(|d| Utc.from_utc_date(&d).and_hms(0, 0, 0)),
+            _ => values::parse_date_time(s, tzid.filter(|_| s.ends_with('Z') == false).map(String::as_str))
+                .ok()
+                .map(|date_time| date_time_to_utc(&date_time)),
+        })
+        .collect()
+}
 
-    let tzid: Option<&String> = property.params.as_ref().and_then(|params| {
+fn property_tzid(property: &Property) -> Option<&String> {
+    property.params.as_ref().and_then(|params| {
         params
             .iter()
             .find_map(|(n, v)| (n == "TZID").then(|| ()).and_then(|_| v.iter().next()))
-    });
+    })
+}
 
-    let s: &str = property.value.as_deref()?;
-    if let Ok(t) = Utc.datetime_from_str(s, "%Y%m%dT%H%M%SZ") {
-        return Some(t);
+/// The TZID of a DATE-TIME property, in case it is written in local time (rather than in UTC)
+fn local_time_tzid(property: &Property) -> Option<&String> {
+    match property.value.as_deref() {
+        Some(value) if value.ends_with('Z') == false => property_tzid(property),
+        _ => None,
+    }
+}
+
+/// The TZID a DATE-TIME property should be written back in, if any.
+/// TZIDs that are only defined by a VTIMEZONE of the file being parsed have been resolved into UTC, which is how they are written back
+fn written_tzid(property: &Property) -> Option<String> {
+    local_time_tzid(property)
+        .filter(|tzid| lookup_timezone(tzid).is_some() || is_embedded_timezone(tzid) == false)
+        .cloned()
+}
+
+/// Whether a DATE-TIME property is floating, i.e. written in local time without any TZID
+fn is_floating(property: &Property) -> bool {
+    match property.value.as_deref() {
+        Some(value) => value.ends_with('Z') == false && property_tzid(property).is_none(),
+        None => false,
     }
+}
 
-    if let Some(tz) = tzid.and_then(|tz| Tz::from_str(tz).ok()) {
-        if let Ok(t) = tz.datetime_from_str(s, "%Y%m%dT%H%M%S") {
-            return Some(t.with_timezone(&Utc));
+/// The instant a DATE-TIME value refers to. \
+/// Local times can also use the VTIMEZONEs of the file that is being parsed. Floating values (and values whose TZID is unknown) are read as UTC, so callers that care should check [`is_floating`]
+fn date_time_to_utc(date_time: &IcalDateTime) -> DateTime<Utc> {
+    if let IcalDateTime::Local { date_time: local, tzid } = date_time {
+        if lookup_timezone(tzid).is_none() {
+            let embedded = EMBEDDED_TIMEZONES.with(|timezones| {
+                timezones.borrow().iter().find(|tz| tz.tzid() == tzid).and_then(|tz| tz.to_utc(local))
+            });
+            if let Some(t) = embedded {
+                return t;
+            }
         }
     }
+    date_time.to_utc()
+}
+
+/// Whether a TZID is defined by a VTIMEZONE of the file that is being parsed
+fn is_embedded_timezone(tzid: &str) -> bool {
+    EMBEDDED_TIMEZONES.with(|timezones| timezones.borrow().iter().any(|tz| tz.tzid() == tzid))
+}
+
+thread_local! {
+    /// The VTIMEZONEs of the file that is being parsed (see [`parse`])
+    static EMBEDDED_TIMEZONES: RefCell<Vec<VTimezone>> = RefCell::new(Vec::new());
+
+    /// The same few TZIDs are looked up for every date of every item, so their results are cached. \
+    /// Only IANA names are cached, so that this cannot grow beyond the timezone database, whatever the TZIDs of the parsed files
+    static TIMEZONES: RefCell<HashMap<String, Tz>> = RefCell::new(HashMap::new());
+}
+
+pub(crate) fn lookup_timezone(tzid: &str) -> Option<Tz> {
+    use std::str::FromStr;
 
-    Utc.datetime_from_str(s, "%Y%m%dT%H%M%S").ok()
+    TIMEZONES.with(|cache| {
+        if let Some(tz) = cache.borrow().get(tzid) {
+            return Some(*tz);
+        }
+        let tz = Tz::from_str(tzid).ok()?;
+        cache.borrow_mut().insert(tzid.to_string(), tz);
+        Some(tz)
+    })
 }
 
 fn extract_ical_prod_id(item: &IcalCalendar) -> Option<&str> {
@@ -273,33 +1149,24 @@ fn extract_ical_prod_id(item: &IcalCalendar) -> Option<&str> {
     None
 }
 
-enum CurrentType<'a> {
-    Event(&'a IcalEvent),
-    Todo(&'a IcalTodo),
+enum CurrentType {
+    /// A single event, or a recurring event and its modified instances
+    Events(Vec<IcalEvent>),
+    Todo(IcalTodo),
+    Journal(IcalJournal),
 }
 
-fn assert_single_type<'a>(item: &'a IcalCalendar) -> Result<CurrentType<'a>, Box<dyn Error>> {
+fn assert_single_type(mut item: IcalCalendar) -> Result<CurrentType, Box<dyn Error>> {
     let n_events = item.events.len();
     let n_todos = item.todos.len();
     let n_journals = item.journals.len();
 
-    if n_events == 1 {
-        if n_todos != 0 || n_journals != 0 {
-            return Err("Only a single TODO or a single EVENT is supported".into());
-        } else {
-            return Ok(CurrentType::Event(&item.events[0]));
-        }
-    }
-
-    if n_todos == 1 {
-        if n_events != 0 || n_journals != 0 {
-            return Err("Only a single TODO or a single EVENT is supported".into());
-        } else {
-            return Ok(CurrentType::Todo(&item.todos[0]));
-        }
+    match (n_events, n_todos, n_journals) {
+        (n, 0, 0) if n >= 1 => Ok(CurrentType::Events(item.events)),
+        (0, 1, 0) => Ok(CurrentType::Todo(item.todos.remove(0))),
+        (0, 0, 1) => Ok(CurrentType::Journal(item.journals.remove(0))),
+        _ => Err("Only a single TODO, a single EVENT or a single JOURNAL is supported".into()),
     }
-
-    return Err("Only a single TODO or a single EVENT is supported".into());
 }
 
 #[cfg(test)]
@@ -367,10 +1234,157 @@ DTSTAMP:20210321T001600
 SUMMARY:Buy a gift for Mom
 END:VTODO
 END:VCALENDAR
+"#;
+
+    const EXAMPLE_RECURRING_EVENT: &str = r#"BEGIN:VCALENDAR
+VERSION:2.0
+PRODID:-//Nextcloud Calendar v2.3.4
+BEGIN:VEVENT
+UID:weekly-meeting@some-domain.com
+CREATED:20210321T001600Z
+LAST-MODIFIED:20210321T001600Z
+DTSTAMP:20210321T001600Z
+SUMMARY:Weekly meeting
+DTSTART:20210322T090000Z
+DTEND:20210322T100000Z
+RRULE:FREQ=WEEKLY;INTERVAL=2;UNTIL=20211231T000000Z;BYDAY=MO,WE
+END:VEVENT
+END:VCALENDAR
+"#;
+
+    const EXAMPLE_TASK_WITH_ALARMS: &str = r#"BEGIN:VCALENDAR
+VERSION:2.0
+PRODID:-//Nextcloud Tasks v0.13.6
+BEGIN:VTODO
+UID:0633de27-8c32-42be-bcb8-63bc879c6185
+CREATED:20210321T001600
+LAST-MODIFIED:20210321T001600
+DTSTAMP:20210321T001600
+SUMMARY:Water the plants
+BEGIN:VALARM
+ACTION:DISPLAY
+TRIGGER:-PT15M
+DESCRIPTION:Plants are thirsty
+REPEAT:2
+DURATION:PT5M
+END:VALARM
+BEGIN:VALARM
+ACTION:AUDIO
+TRIGGER;VALUE=DATE-TIME:20210322T080000Z
+X-APPLE-DEFAULT-ALARM:TRUE
+END:VALARM
+END:VTODO
+END:VCALENDAR
+"#;
+
+    const EXAMPLE_TASK_WITH_UNPARSABLE_ALARMS: &str = r#"BEGIN:VCALENDAR
+VERSION:2.0
+PRODID:-//Legacy Tasks v1.0
+BEGIN:VTODO
+UID:ab6ee1c7-0b80-4b2b-bf0d-a5fd3f4a7a56
+DTSTAMP:20210321T001600Z
+SUMMARY:Renew the passport
+BEGIN:VALARM
+ACTION:DISPLAY
+TRIGGER;RELATED=START:-15 minutes
+DESCRIPTION:Passport
+END:VALARM
+BEGIN:VALARM
+ACTION:DISPLAY
+TRIGGER:-PT1H
+REPEAT:many
+DURATION:PT5M
+END:VALARM
+BEGIN:VALARM
+TRIGGER:-PT1H
+END:VALARM
+END:VTODO
+END:VCALENDAR
+"#;
+
+    const EXAMPLE_RECURRING_EVENT_WITH_OVERRIDE: &str = r#"BEGIN:VCALENDAR
+VERSION:2.0
+PRODID:-//Nextcloud Calendar v2.3.4
+BEGIN:VEVENT
+UID:daily-standup@some-domain.com
+DTSTAMP:20210321T001600Z
+SUMMARY:Standup
+DTSTART:20210322T090000Z
+DTEND:20210322T091500Z
+RRULE:FREQ=DAILY;COUNT=5
+END:VEVENT
+BEGIN:VEVENT
+UID:daily-standup@some-domain.com
+DTSTAMP:20210321T001600Z
+RECURRENCE-ID:20210324T090000Z
+SUMMARY:Standup (moved)
+DTSTART:20210324T140000Z
+DTEND:20210324T141500Z
+END:VEVENT
+END:VCALENDAR
+"#;
+
+    const EXAMPLE_MEETING: &str = r#"BEGIN:VCALENDAR
+VERSION:2.0
+PRODID:-//Nextcloud Calendar v2.3.4
+BEGIN:VEVENT
+UID:meeting@some-domain.com
+DTSTAMP:20210321T001600Z
+SUMMARY:Budget review
+DTSTART:20210322T090000Z
+DTEND:20210322T100000Z
+ORGANIZER;CN=Alice:mailto:alice@example.com
+ATTENDEE;CN="Doe, John";PARTSTAT=ACCEPTED;ROLE=CHAIR:mailto:john@example.com
+ATTENDEE;RSVP=TRUE;CUTYPE=INDIVIDUAL:mailto:bob@example.com
+END:VEVENT
+END:VCALENDAR
+"#;
+
+    const EXAMPLE_TASK_WITH_TIME_TRACKING: &str = r#"BEGIN:VCALENDAR
+VERSION:2.0
+PRODID:-//Nextcloud Tasks v0.13.6
+BEGIN:VTODO
+UID:0633de27-8c32-42be-bcb8-63bc879c6185
+DTSTAMP:20210321T001600
+SUMMARY:Write the report
+X-TIMER-STARTED:20210321T090000Z
+X-TIME-SPENT:PT1H30M
+END:VTODO
+END:VCALENDAR
+"#;
+
+    const EXAMPLE_TASK_WITH_CATEGORIES: &str = r#"BEGIN:VCALENDAR
+VERSION:2.0
+PRODID:-//Nextcloud Tasks v0.13.6
+BEGIN:VTODO
+UID:0633de27-8c32-42be-bcb8-63bc879c6185
+DTSTAMP:20210321T001600
+SUMMARY:Paint the fence
+CATEGORIES:Home,Paint\, brushes and tools
+CATEGORIES:Garden,Home
+END:VTODO
+END:VCALENDAR
+"#;
+
+    const EXAMPLE_JOURNAL: &str = r#"BEGIN:VCALENDAR
+VERSION:2.0
+PRODID:-//Nextcloud Notes v4.0.0
+BEGIN:VJOURNAL
+UID:6f1c2a7e-journal@some-domain.com
+CREATED:20210321T001600
+LAST-MODIFIED:20210321T001600
+DTSTAMP:20210321T001600
+SUMMARY:Trip to the mountains
+DESCRIPTION:We saw a marmot
+DTSTART;VALUE=DATE:20210320
+CATEGORIES:Holidays
+END:VJOURNAL
+END:VCALENDAR
 "#;
 
     use super::*;
     use crate::item::VersionTag;
+    use crate::recurrence::Frequency;
 
     #[test]
     fn test_ical_parsing() {
@@ -435,6 +1449,28 @@ END:VCALENDAR
         assert_eq!(task.completion_status(), &CompletionStatus::Completed(None));
     }
 
+    #[test]
+    fn test_task_status_parsing() {
+        let item_url: Url = "http://some.id/for/testing".parse().unwrap();
+
+        for (ical_status, expected) in [
+            ("NEEDS-ACTION", CompletionStatus::Uncompleted),
+            ("IN-PROCESS", CompletionStatus::InProcess),
+            ("CANCELLED", CompletionStatus::Cancelled),
+        ] {
+            let ical = EXAMPLE_ICAL.replace("SUMMARY:", &format!("STATUS:{}\nSUMMARY:", ical_status));
+            let item = parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap();
+            let task = item.unwrap_task();
+            assert_eq!(task.completion_status(), &expected);
+            assert_eq!(task.completed(), false);
+
+            let ical = crate::ical::build_from(&item).unwrap();
+            assert!(ical.contains(&format!("STATUS:{}\r\n", ical_status)));
+            let reparsed = parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap();
+            assert_eq!(reparsed.unwrap_task().completion_status(), &expected);
+        }
+    }
+
     #[test]
     fn test_multiple_items_in_ical() {
         let version_tag = VersionTag::from(String::from("test-tag"));
@@ -444,4 +1480,638 @@ END:VCALENDAR
         let item = parse(EXAMPLE_MULTIPLE_ICAL, item_url.clone(), sync_status.clone());
         assert!(item.is_err());
     }
+
+    #[test]
+    fn test_recurring_event_parsing() {
+        let item_url: Url = "http://some.id/for/testing".parse().unwrap();
+
+        let item = parse(EXAMPLE_RECURRING_EVENT, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        let event = item.unwrap_event();
+        let rule = event.recurrence().unwrap();
+        assert_eq!(rule.frequency(), Frequency::Weekly);
+        assert_eq!(rule.interval(), 2);
+        assert!(event.extra_parameters().iter().all(|prop| prop.name != "RRULE"));
+
+        // The rule must survive a round trip
+        let ical = crate::ical::build_from(&item).unwrap();
+        assert!(ical.contains("RRULE:FREQ=WEEKLY;INTERVAL=2;UNTIL=20211231T000000Z;BYDAY=MO,WE\r\n"));
+        let reparsed = parse(&ical, item_url, SyncStatus::NotSynced).unwrap();
+        assert_eq!(reparsed.unwrap_event().recurrence(), Some(rule));
+    }
+
+    #[test]
+    fn test_alarms_parsing() {
+        let item_url: Url = "http://some.id/for/testing".parse().unwrap();
+
+        let item = parse(EXAMPLE_TASK_WITH_ALARMS, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        let alarms = item.unwrap_task().alarms();
+        assert_eq!(alarms.len(), 2);
+
+        assert_eq!(alarms[0].action(), &AlarmAction::Display);
+        assert_eq!(alarms[0].trigger(), &AlarmTrigger::Relative{ offset_seconds: -15*60, related_to_end: false });
+        assert_eq!(alarms[0].description(), Some("Plants are thirsty"));
+        assert_eq!(alarms[0].repeat(), Some(&AlarmRepeat{ count: 2, interval_seconds: 5*60 }));
+
+        assert_eq!(alarms[1].action(), &AlarmAction::Audio);
+        assert_eq!(alarms[1].trigger(), &AlarmTrigger::Absolute(Utc.ymd(2021, 3, 22).and_hms(8, 0, 0)));
+        assert_eq!(alarms[1].extra_parameters().len(), 1);
+
+        // Alarms must survive a round trip
+        let ical = crate::ical::build_from(&item).unwrap();
+        let reparsed = parse(&ical, item_url, SyncStatus::NotSynced).unwrap();
+        assert_eq!(reparsed.unwrap_task().alarms(), alarms);
+    }
+
+    #[test]
+    fn test_unparsable_alarms() {
+        let item_url: Url = "http://some.id/for/testing".parse().unwrap();
+
+        let item = parse(EXAMPLE_TASK_WITH_UNPARSABLE_ALARMS, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        let alarms = item.unwrap_task().alarms();
+        // The alarm without an ACTION is not a valid VALARM
+        assert_eq!(alarms.len(), 2);
+
+        match alarms[0].trigger() {
+            AlarmTrigger::Unparsed(prop) => assert_eq!(prop.value.as_deref(), Some("-15 minutes")),
+            other => panic!("Unexpected trigger {:?}", other),
+        }
+        assert_eq!(alarms[0].first_trigger_date(Some(&Utc::now()), None), None);
+        assert_eq!(alarms[1].repeat(), None);
+        assert_eq!(alarms[1].extra_parameters().len(), 2);
+
+        // Unparsable properties are written back unchanged
+        let ical = crate::ical::build_from(&item).unwrap();
+        assert!(ical.contains("TRIGGER;RELATED=START:-15 minutes\r\n"));
+        assert!(ical.contains("REPEAT:many\r\n"));
+        assert!(ical.contains("DURATION:PT5M\r\n"));
+        let reparsed = parse(&ical, item_url, SyncStatus::NotSynced).unwrap();
+        assert_eq!(reparsed.unwrap_task().alarms(), alarms);
+    }
+
+    #[test]
+    fn test_journal_parsing() {
+        let item_url: Url = "http://some.id/for/testing".parse().unwrap();
+
+        let item = parse(EXAMPLE_JOURNAL, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        assert!(item.is_journal());
+        let journal = item.unwrap_journal();
+        assert_eq!(journal.name(), "Trip to the mountains");
+        assert_eq!(journal.description(), Some("We saw a marmot"));
+        assert_eq!(journal.start(), Some(&Utc.ymd(2021, 3, 20).and_hms(0, 0, 0)));
+        assert!(journal.is_all_day());
+        assert_eq!(journal.extra_parameters().len(), 1);
+
+        let ical = crate::ical::build_from(&item).unwrap();
+        assert!(ical.contains("BEGIN:VJOURNAL\r\n"));
+        assert!(ical.contains("DTSTART;VALUE=DATE:20210320\r\n"));
+        let reparsed = parse(&ical, item_url, SyncStatus::NotSynced).unwrap();
+        assert!(reparsed.has_same_observable_content_as(&item));
+    }
+
+    #[test]
+    fn test_recurring_event_with_override_parsing() {
+        let item_url: Url = "http://some.id/for/testing".parse().unwrap();
+
+        let item = parse(EXAMPLE_RECURRING_EVENT_WITH_OVERRIDE, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        let event = item.unwrap_event();
+        assert_eq!(event.name(), "Standup");
+        assert!(event.recurrence_id().is_none());
+        assert_eq!(event.overrides().len(), 1);
+        let instance = &event.overrides()[0];
+        assert_eq!(instance.name(), "Standup (moved)");
+        assert_eq!(instance.recurrence_id(), Some(&Utc.ymd(2021, 3, 24).and_hms(9, 0, 0)));
+
+        // The modified instance replaces one of the occurrences
+        let window_start = Utc.ymd(2021, 3, 1).and_hms(0, 0, 0);
+        let window_end = Utc.ymd(2021, 4, 1).and_hms(0, 0, 0);
+        let starts: Vec<_> = event.occurrences_between(window_start, window_end).map(|o| *o.start()).collect();
+        assert_eq!(starts.len(), 4);
+        assert!(starts.contains(&Utc.ymd(2021, 3, 24).and_hms(9, 0, 0)) == false);
+
+        let ical = crate::ical::build_from(&item).unwrap();
+        assert_eq!(ical.matches("BEGIN:VEVENT").count(), 2);
+        let reparsed = parse(&ical, item_url, SyncStatus::NotSynced).unwrap();
+        assert!(reparsed.has_same_observable_content_as(&item));
+
+        // Overrides can be stored without their recurring event
+        let orphan = EXAMPLE_RECURRING_EVENT_WITH_OVERRIDE.replacen("RRULE:FREQ=DAILY;COUNT=5\n", "RECURRENCE-ID:20210322T090000Z\n", 1);
+        let orphan_url: Url = "http://some.id/for/testing".parse().unwrap();
+        let orphan = parse(&orphan, orphan_url.clone(), SyncStatus::NotSynced).unwrap();
+        let orphan_event = orphan.unwrap_event();
+        assert!(orphan_event.is_orphaned_instance());
+        assert_eq!(orphan_event.overrides().len(), 1);
+        let reparsed = parse(&crate::ical::build_from(&orphan).unwrap(), orphan_url, SyncStatus::NotSynced).unwrap();
+        assert!(reparsed.has_same_observable_content_as(&orphan));
+
+        // Overrides that do not replace any occurrence of their recurring event
+        assert!(event.orphaned_overrides().is_empty());
+        let mut event = event.clone();
+        event.set_recurrence(Some("FREQ=DAILY;COUNT=2".parse().unwrap()));
+        assert_eq!(event.orphaned_overrides().len(), 1);
+        let instance = event.remove_override(&Utc.ymd(2021, 3, 24).and_hms(9, 0, 0)).unwrap();
+        let promoted = instance.detached_copy(&"http://some.id/".parse().unwrap());
+        assert!(promoted.is_orphaned_instance() == false);
+        assert_ne!(promoted.uid(), event.uid());
+        assert!(event.overrides().is_empty());
+    }
+
+    #[test]
+    fn test_attendees_parsing() {
+        let item_url: Url = "http://some.id/for/testing".parse().unwrap();
+
+        let item = parse(EXAMPLE_MEETING, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        let event = item.unwrap_event();
+        let organizer = event.organizer().unwrap();
+        assert_eq!(organizer.address(), "mailto:alice@example.com");
+        assert_eq!(organizer.common_name(), Some("Alice"));
+
+        let attendees = event.attendees();
+        assert_eq!(attendees.len(), 2);
+        assert_eq!(attendees[0].common_name(), Some("Doe, John"));
+        assert_eq!(attendees[0].status(), Some(&ParticipationStatus::Accepted));
+        assert_eq!(attendees[0].role(), Some(&ParticipantRole::Chair));
+        assert_eq!(attendees[1].address(), "mailto:bob@example.com");
+        assert_eq!(attendees[1].effective_status(), ParticipationStatus::NeedsAction);
+        assert!(attendees[1].rsvp());
+        assert_eq!(attendees[1].extra_parameters().len(), 1);
+        assert!(event.extra_parameters().iter().all(|prop| prop.name != "ATTENDEE" && prop.name != "ORGANIZER"));
+
+        let ical = crate::ical::build_from(&item).unwrap();
+        let reparsed = parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        assert!(reparsed.has_same_observable_content_as(&item));
+
+        // Parameter values that contain colons must be quoted when they are built again
+        let ical = EXAMPLE_MEETING
+            .replace("ORGANIZER;CN=Alice:", "ORGANIZER;CN=Alice;SENT-BY=\"mailto:carol@example.com\":")
+            .replace("CUTYPE=INDIVIDUAL:", "CUTYPE=INDIVIDUAL;DELEGATED-FROM=\"mailto:dave@example.com\",\"mailto:eve@example.com\":");
+        let item = parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        let sent_by = ("SENT-BY".to_string(), vec!["mailto:carol@example.com".to_string()]);
+        assert_eq!(item.unwrap_event().organizer().unwrap().extra_parameters(), &[sent_by]);
+        let ical = crate::ical::build_from(&item).unwrap();
+        assert!(ical.contains("SENT-BY=\"mailto:carol@example.com\""));
+        let reparsed = parse(&ical, item_url, SyncStatus::NotSynced).unwrap();
+        assert_eq!(reparsed.unwrap_event().organizer(), item.unwrap_event().organizer());
+        assert_eq!(reparsed.unwrap_event().attendees(), item.unwrap_event().attendees());
+    }
+
+    #[test]
+    fn test_categories_parsing() {
+        let item_url: Url = "http://some.id/for/testing".parse().unwrap();
+
+        let mut item = parse(EXAMPLE_TASK_WITH_CATEGORIES, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        let task = item.unwrap_task_mut();
+        assert_eq!(task.categories(), &["Home", "Paint, brushes and tools", "Garden"]);
+        assert!(task.extra_parameters().iter().all(|prop| prop.name != "CATEGORIES"));
+
+        assert_eq!(task.remove_category("Garden"), true);
+        assert_eq!(task.remove_category("Garden"), false);
+        task.add_category("Fence; wooden".to_string());
+
+        let ical = crate::ical::build_from(&item).unwrap();
+        assert!(ical.contains("CATEGORIES:Home,Paint\\, brushes and tools,Fence\\; wooden\r\n"));
+        let reparsed = parse(&ical, item_url, SyncStatus::NotSynced).unwrap();
+        assert!(reparsed.has_same_observable_content_as(&item));
+    }
+
+    #[test]
+    fn test_time_tracking_parsing() {
+        let item_url: Url = "http://some.id/for/testing".parse().unwrap();
+
+        let mut item = parse(EXAMPLE_TASK_WITH_TIME_TRACKING, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        let task = item.unwrap_task_mut();
+        let started_at = Utc.ymd(2021, 3, 21).and_hms(9, 0, 0);
+        assert_eq!(task.time_tracking().started_at(), Some(&started_at));
+        assert_eq!(task.time_tracking().total_duration(), chrono::Duration::minutes(90));
+        assert_eq!(task.time_tracking().elapsed_at(started_at + chrono::Duration::minutes(10)), chrono::Duration::minutes(100));
+        assert!(task.extra_parameters().is_empty());
+
+        let ical = crate::ical::build_from(&item).unwrap();
+        assert!(ical.contains("X-TIMER-STARTED:20210321T090000Z\r\n"));
+        assert!(ical.contains("X-TIME-SPENT:PT1H30M\r\n"));
+        let reparsed = parse(&ical, item_url, SyncStatus::NotSynced).unwrap();
+        assert!(reparsed.has_same_observable_content_as(&item));
+
+        let task = item.unwrap_task_mut();
+        assert_eq!(task.start_timer(), false);
+        assert!(task.stop_timer().unwrap() > chrono::Duration::zero());
+        assert_eq!(task.stop_timer(), None);
+        assert_eq!(task.time_tracking().is_running(), false);
+        assert_eq!(task.start_timer(), true);
+    }
+
+    #[test]
+    fn test_priority_parsing() {
+        let item_url: Url = "http://some.id/for/testing".parse().unwrap();
+
+        for (line, expected) in &[("PRIORITY:1", Some(1)), ("PRIORITY:9", Some(9)), ("PRIORITY:0", None)] {
+            let ical = EXAMPLE_ICAL.replace("SUMMARY:", &format!("{}\nSUMMARY:", line));
+            let item = parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap();
+            assert_eq!(item.unwrap_task().priority(), *expected);
+            assert!(item.unwrap_task().extra_parameters().is_empty());
+        }
+
+        // Invalid values are kept as they are
+        let ical = EXAMPLE_ICAL.replace("SUMMARY:", "PRIORITY:12\nSUMMARY:");
+        let mut item = parse(&ical, item_url, SyncStatus::NotSynced).unwrap();
+        assert_eq!(item.unwrap_task().priority(), None);
+        assert_eq!(item.unwrap_task().extra_parameters().len(), 1);
+
+        assert!(item.unwrap_task_mut().set_priority(Some(10)).is_err());
+        item.unwrap_task_mut().set_priority(Some(2)).unwrap();
+        assert_eq!(item.unwrap_task().priority(), Some(2));
+        assert!(item.unwrap_task().extra_parameters().is_empty());
+    }
+
+    #[test]
+    fn test_due_parsing() {
+        let item_url: Url = "http://some.id/for/testing".parse().unwrap();
+
+        let ical = EXAMPLE_ICAL.replace("SUMMARY:", "DUE;VALUE=DATE:20210325\nSUMMARY:");
+        let item = parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        assert_eq!(item.unwrap_task().due(), Some(&Utc.ymd(2021, 3, 25).and_hms(0, 0, 0)));
+        assert!(item.unwrap_task().is_due_all_day());
+        let ical = crate::ical::build_from(&item).unwrap();
+        assert!(ical.contains("DUE;VALUE=DATE:20210325\r\n"));
+
+        let ical = EXAMPLE_ICAL.replace("SUMMARY:", "DUE:20210325T170000Z\nSUMMARY:");
+        let item = parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        assert_eq!(item.unwrap_task().due(), Some(&Utc.ymd(2021, 3, 25).and_hms(17, 0, 0)));
+        assert_eq!(item.unwrap_task().is_due_all_day(), false);
+
+        // The due date can be specified as a duration after DTSTART
+        let ical = EXAMPLE_ICAL.replace("SUMMARY:", "DTSTART:20210325T090000Z\nDURATION:PT2H\nSUMMARY:");
+        let mut item = parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        assert_eq!(item.unwrap_task().due(), Some(&Utc.ymd(2021, 3, 25).and_hms(11, 0, 0)));
+        let ical = crate::ical::build_from(&item).unwrap();
+        assert!(ical.contains("DUE:20210325T110000Z\r\n"));
+        assert!(ical.contains("DURATION") == false);
+        let reparsed = parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        assert!(reparsed.has_same_observable_content_as(&item));
+
+        // Floating and local due dates are written back the way they were read
+        let ical = EXAMPLE_ICAL.replace("SUMMARY:", "DTSTART:20210325T090000\nDURATION:PT2H\nSUMMARY:");
+        let item = parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        assert!(item.unwrap_task().is_due_floating());
+        let ical = crate::ical::build_from(&item).unwrap();
+        assert!(ical.contains("DUE:20210325T110000\r\n"));
+        let ical = EXAMPLE_ICAL.replace("SUMMARY:", "DUE;TZID=Europe/Paris:20210325T120000\nSUMMARY:");
+        let item = parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        assert_eq!(item.unwrap_task().due(), Some(&Utc.ymd(2021, 3, 25).and_hms(11, 0, 0)));
+        assert_eq!(item.unwrap_task().due_tzid(), Some("Europe/Paris"));
+        let ical = crate::ical::build_from(&item).unwrap();
+        assert!(ical.contains("DUE;TZID=Europe/Paris:20210325T120000\r\n"));
+        let reparsed = parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        assert!(reparsed.has_same_observable_content_as(&item));
+
+        item.unwrap_task_mut().set_due(None, true);
+        assert_eq!(item.unwrap_task().due(), None);
+        assert_eq!(item.unwrap_task().is_due_all_day(), false);
+
+        // Due dates that cannot be represented are errors, rather than panics
+        let ical = EXAMPLE_ICAL.replace("SUMMARY:", "DTSTART:20210325T090000Z\nDURATION:P99999999W\nSUMMARY:");
+        assert!(parse(&ical, item_url, SyncStatus::NotSynced).is_err());
+    }
+
+    #[test]
+    fn test_percent_complete_parsing() {
+        let item_url: Url = "http://some.id/for/testing".parse().unwrap();
+
+        let ical = EXAMPLE_ICAL.replace("SUMMARY:", "PERCENT-COMPLETE:40\nSUMMARY:");
+        let mut item = parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        assert_eq!(item.unwrap_task().percent_complete(), 40);
+        assert!(item.unwrap_task().extra_parameters().is_empty());
+        let ical = crate::ical::build_from(&item).unwrap();
+        assert!(ical.contains("PERCENT-COMPLETE:40\r\n"));
+
+        let task = item.unwrap_task_mut();
+        task.set_percent_complete(100);
+        assert!(task.completed());
+        task.set_percent_complete(70);
+        assert_eq!(task.completed(), false);
+        assert_eq!(task.percent_complete(), 70);
+        task.set_completion_status(CompletionStatus::Completed(None));
+        assert_eq!(task.percent_complete(), 100);
+        task.set_completion_status(CompletionStatus::Uncompleted);
+        assert_eq!(task.percent_complete(), 0);
+
+        // Completed tasks have a single PERCENT-COMPLETE
+        let item = parse(EXAMPLE_ICAL_COMPLETED, item_url, SyncStatus::NotSynced).unwrap();
+        assert_eq!(item.unwrap_task().percent_complete(), 100);
+        let ical = crate::ical::build_from(&item).unwrap();
+        assert_eq!(ical.matches("PERCENT-COMPLETE").count(), 1);
+    }
+
+    #[test]
+    fn test_location_parsing() {
+        let item_url: Url = "http://some.id/for/testing".parse().unwrap();
+
+        let ical = EXAMPLE_MEETING.replace("SUMMARY:", "LOCATION:Room 42 (second floor)\nSUMMARY:");
+        let item = parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        assert_eq!(item.unwrap_event().location(), Some("Room 42 (second floor)"));
+        assert!(item.unwrap_event().extra_parameters().iter().all(|prop| prop.name != "LOCATION"));
+
+        let ical = crate::ical::build_from(&item).unwrap();
+        assert!(ical.contains("LOCATION:Room 42 (second floor)\r\n"));
+        let reparsed = parse(&ical, item_url, SyncStatus::NotSynced).unwrap();
+        assert!(reparsed.has_same_observable_content_as(&item));
+    }
+
+    #[test]
+    fn test_quoted_printable_parsing() {
+        let item_url: Url = "http://some.id/for/testing".parse().unwrap();
+
+        let ical = EXAMPLE_MEETING.replace("SUMMARY:", "LOCATION;ENCODING=QUOTED-PRINTABLE;CHARSET=ISO-8859-1:Caf=E9 de la=\n gare d'=C9vian\nSUMMARY:");
+        let item = parse(&ical, item_url, SyncStatus::NotSynced).unwrap();
+        assert_eq!(item.unwrap_event().location(), Some("Café de la gare d'Évian"));
+        assert_eq!(item.unwrap_event().name(), "Budget review");
+    }
+
+    #[test]
+    fn test_parent_parsing() {
+        let item_url: Url = "http://some.id/for/testing".parse().unwrap();
+
+        let ical = EXAMPLE_ICAL.replace("SUMMARY:", "RELATED-TO:parent-uid@some-domain.com\nRELATED-TO;RELTYPE=SIBLING:sibling-uid@some-domain.com\nSUMMARY:");
+        let item = parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        let task = item.unwrap_task();
+        assert_eq!(task.parent().map(|uid| uid.as_str()), Some("parent-uid@some-domain.com"));
+        assert_eq!(task.extra_parameters().iter().filter(|prop| prop.name == "RELATED-TO").count(), 1);
+
+        let ical = crate::ical::build_from(&item).unwrap();
+        assert!(ical.contains("RELATED-TO:parent-uid@some-domain.com\r\n"));
+        let reparsed = parse(&ical, item_url, SyncStatus::NotSynced).unwrap();
+        assert!(reparsed.has_same_observable_content_as(&item));
+        assert_eq!(reparsed.unwrap_task().extra_parameters().len(), task.extra_parameters().len());
+    }
+
+    #[test]
+    fn test_timezone_parsing() {
+        let item_url: Url = "http://some.id/for/testing".parse().unwrap();
+
+        let ical = EXAMPLE_MEETING
+            .replace("DTSTART:20210322T090000Z", "DTSTART;TZID=Europe/Paris:20210322T100000")
+            .replace("DTEND:20210322T100000Z", "DTEND;TZID=Europe/Paris:20210322T110000");
+        let item = parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        let event = item.unwrap_event();
+        assert_eq!(event.start(), &Utc.ymd(2021, 3, 22).and_hms(9, 0, 0));
+        assert_eq!(event.tzid(), Some("Europe/Paris"));
+        assert_eq!(event.timezone(), Some(chrono_tz::Europe::Paris));
+
+        let ical = crate::ical::build_from(&item).unwrap();
+        assert!(ical.contains("DTSTART;TZID=Europe/Paris:20210322T100000\r\n"));
+        assert!(ical.contains("DTEND;TZID=Europe/Paris:20210322T110000\r\n"));
+        let reparsed = parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        assert!(reparsed.has_same_observable_content_as(&item));
+
+        // Dates in UTC have no timezone
+        let item = parse(EXAMPLE_MEETING, item_url, SyncStatus::NotSynced).unwrap();
+        assert_eq!(item.unwrap_event().tzid(), None);
+
+        // Unknown TZIDs are not cached
+        assert_eq!(lookup_timezone("Europe/Paris"), Some(chrono_tz::Europe::Paris));
+        assert_eq!(lookup_timezone("Custom timezone of some server"), None);
+        TIMEZONES.with(|cache| {
+            assert!(cache.borrow().contains_key("Europe/Paris"));
+            assert_eq!(cache.borrow().contains_key("Custom timezone of some server"), false);
+        });
+    }
+
+    #[test]
+    fn test_event_duration_parsing() {
+        let item_url: Url = "http://some.id/for/testing".parse().unwrap();
+
+        let ical = EXAMPLE_MEETING.replace("DTEND:20210322T100000Z", "DURATION:PT1H30M");
+        let item = parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        let event = item.unwrap_event();
+        assert_eq!(event.end(), &Utc.ymd(2021, 3, 22).and_hms(10, 30, 0));
+        assert!(event.uses_duration());
+
+        let ical = crate::ical::build_from(&item).unwrap();
+        assert!(ical.contains("DURATION:PT1H30M\r\n"));
+        assert!(ical.contains("DTEND") == false);
+        let reparsed = parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        assert!(reparsed.has_same_observable_content_as(&item));
+
+        let item = parse(EXAMPLE_MEETING, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        assert_eq!(item.unwrap_event().uses_duration(), false);
+
+        let ical = EXAMPLE_MEETING.replace("DTEND:20210322T100000Z", "DURATION:P99999999W");
+        assert!(parse(&ical, item_url.clone(), SyncStatus::NotSynced).is_err());
+
+        let ical = EXAMPLE_MEETING.replace("DTEND:20210322T100000Z\n", "");
+        assert!(parse(&ical, item_url, SyncStatus::NotSynced).is_err());
+    }
+
+    #[test]
+    fn test_event_status_parsing() {
+        let item_url: Url = "http://some.id/for/testing".parse().unwrap();
+
+        let ical = EXAMPLE_MEETING.replace("SUMMARY:Budget review\n", "SUMMARY:Budget review\nSTATUS:CANCELLED\n");
+        let item = parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        let event = item.unwrap_event();
+        assert_eq!(event.status(), Some(&EventStatus::Cancelled));
+        assert!(event.is_cancelled());
+
+        let ical = crate::ical::build_from(&item).unwrap();
+        assert!(ical.contains("STATUS:CANCELLED\r\n"));
+        let reparsed = parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        assert!(reparsed.has_same_observable_content_as(&item));
+
+        let item = parse(EXAMPLE_MEETING, item_url, SyncStatus::NotSynced).unwrap();
+        assert_eq!(item.unwrap_event().status(), None);
+        assert_eq!(item.unwrap_event().is_cancelled(), false);
+    }
+
+    #[test]
+    fn test_floating_date_times_parsing() {
+        let item_url: Url = "http://some.id/for/testing".parse().unwrap();
+
+        let ical = EXAMPLE_MEETING
+            .replace("DTSTART:20210322T090000Z", "DTSTART:20210322T090000")
+            .replace("DTEND:20210322T100000Z", "DTEND:20210322T100000");
+        let item = parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        let event = item.unwrap_event();
+        assert!(event.is_floating());
+        assert_eq!(event.start_date_time().resolve(&chrono_tz::Europe::Paris), Utc.ymd(2021, 3, 22).and_hms(8, 0, 0));
+
+        let ical = crate::ical::build_from(&item).unwrap();
+        assert!(ical.contains("DTSTART:20210322T090000\r\n"));
+        let reparsed = parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        assert!(reparsed.has_same_observable_content_as(&item));
+
+        let mut event = event.clone();
+        event.resolve_floating(&chrono_tz::Europe::Paris);
+        assert_eq!(event.is_floating(), false);
+        assert_eq!(event.start(), &Utc.ymd(2021, 3, 22).and_hms(8, 0, 0));
+        let ical = crate::ical::build_from(&Item::Event(event)).unwrap();
+        assert!(ical.contains("DTSTART;TZID=Europe/Paris:20210322T090000\r\n"));
+
+        // Dates in UTC are not floating
+        let item = parse(EXAMPLE_MEETING, item_url, SyncStatus::NotSynced).unwrap();
+        assert_eq!(item.unwrap_event().is_floating(), false);
+        assert!(crate::ical::build_from(&item).unwrap().contains("DTSTART:20210322T090000Z\r\n"));
+
+        // Tasks keep their floating due and completion dates, until they are resolved
+        let ical = EXAMPLE_ICAL_COMPLETED.replace("SUMMARY:", "DUE:20210402T120000\nSUMMARY:");
+        let item = parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        let task = item.unwrap_task();
+        assert!(task.is_due_floating());
+        assert!(task.is_completion_floating());
+        let ical = crate::ical::build_from(&item).unwrap();
+        assert!(ical.contains("DUE:20210402T120000\r\n"));
+        assert!(ical.contains("COMPLETED:20210402T081557\r\n"));
+
+        let mut task = task.clone();
+        task.resolve_floating(&chrono_tz::Europe::Paris);
+        assert_eq!(task.is_due_floating(), false);
+        assert_eq!(task.is_completion_floating(), false);
+        assert_eq!(task.due(), Some(&Utc.ymd(2021, 4, 2).and_hms(10, 0, 0)));
+        assert_eq!(task.completion_status(), &CompletionStatus::Completed(Some(Utc.ymd(2021, 4, 2).and_hms(6, 15, 57))));
+        let ical = crate::ical::build_from(&Item::Task(task)).unwrap();
+        assert!(ical.contains("DUE;TZID=Europe/Paris:20210402T120000\r\n"));
+        assert!(ical.contains("COMPLETED:20210402T061557Z\r\n"));
+    }
+
+    #[test]
+    fn test_vtimezone_parsing_and_generation() {
+        let item_url: Url = "http://some.id/for/testing".parse().unwrap();
+
+        // Outlook-style TZIDs are only defined by the VTIMEZONE that comes along
+        let ical = EXAMPLE_MEETING
+            .replace("BEGIN:VEVENT", "BEGIN:VTIMEZONE\nTZID:W. Europe Standard Time\nBEGIN:STANDARD\nDTSTART:16010101T030000\nTZOFFSETFROM:+0200\nTZOFFSETTO:+0100\nRRULE:FREQ=YEARLY;BYDAY=-1SU;BYMONTH=10\nEND:STANDARD\nBEGIN:DAYLIGHT\nDTSTART:16010101T020000\nTZOFFSETFROM:+0100\nTZOFFSETTO:+0200\nRRULE:FREQ=YEARLY;BYDAY=-1SU;BYMONTH=3\nEND:DAYLIGHT\nEND:VTIMEZONE\nBEGIN:VEVENT")
+            .replace("DTSTART:20210322T090000Z", "DTSTART;TZID=W. Europe Standard Time:20210329T100000")
+            .replace("DTEND:20210322T100000Z", "DTEND;TZID=W. Europe Standard Time:20210329T110000");
+        let item = parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        let event = item.unwrap_event();
+        assert_eq!(event.start(), &Utc.ymd(2021, 3, 29).and_hms(8, 0, 0));
+        assert_eq!(event.end(), &Utc.ymd(2021, 3, 29).and_hms(9, 0, 0));
+        // It is written back in UTC
+        assert_eq!(event.tzid(), None);
+
+        // IANA timezones are embedded when generating iCal files
+        let ical = EXAMPLE_MEETING
+            .replace("DTSTART:20210322T090000Z", "DTSTART;TZID=Europe/Paris:20210322T100000")
+            .replace("DTEND:20210322T100000Z", "DTEND;TZID=Europe/Paris:20210322T110000");
+        let item = parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        let ical = crate::ical::build_from(&item).unwrap();
+        assert!(ical.contains("BEGIN:VTIMEZONE\r\nTZID:Europe/Paris\r\n"));
+        assert!(ical.contains("BEGIN:DAYLIGHT\r\nDTSTART:20210328T020000\r\nTZOFFSETFROM:+0100\r\nTZOFFSETTO:+0200\r\n"));
+        assert!(ical.find("BEGIN:VTIMEZONE") < ical.find("BEGIN:VEVENT"));
+        let reparsed = parse(&ical, item_url, SyncStatus::NotSynced).unwrap();
+        assert!(reparsed.has_same_observable_content_as(&item));
+    }
+
+    #[test]
+    fn test_geo_parsing() {
+        let item_url: Url = "http://some.id/for/testing".parse().unwrap();
+
+        let ical = EXAMPLE_MEETING.replace("SUMMARY:", "GEO:37.386013;-122.082932\nSUMMARY:");
+        let item = parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        assert_eq!(item.unwrap_event().geo(), Some((37.386013, -122.082932)));
+        let ical = crate::ical::build_from(&item).unwrap();
+        assert!(ical.contains("GEO:37.386013;-122.082932\r\n"));
+        let reparsed = parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        assert!(reparsed.has_same_observable_content_as(&item));
+
+        // Out-of-range positions are kept as is
+        let ical = EXAMPLE_ICAL.replace("SUMMARY:", "GEO:91.0;10.0\nSUMMARY:");
+        let mut item = parse(&ical, item_url, SyncStatus::NotSynced).unwrap();
+        let task = item.unwrap_task_mut();
+        assert_eq!(task.geo(), None);
+        assert!(task.extra_parameters().iter().any(|prop| prop.name == "GEO"));
+        assert!(task.set_geo(Some((10.0, 181.0))).is_err());
+        task.set_geo(Some((-33.8688, 151.2093))).unwrap();
+        assert!(task.extra_parameters().iter().all(|prop| prop.name != "GEO"));
+        assert!(crate::ical::build_from(&item).unwrap().contains("GEO:-33.8688;151.2093\r\n"));
+    }
+
+    #[test]
+    fn test_free_busy_parsing() {
+        let ical = "BEGIN:VCALENDAR\r\n\
+            VERSION:2.0\r\n\
+            PRODID:-//Example Corp.//CalDAV Server//EN\r\n\
+            BEGIN:VFREEBUSY\r\n\
+            DTSTAMP:20060206T001102Z\r\n\
+            DTSTART:20060104T140000Z\r\n\
+            DTEND:20060105T220000Z\r\n\
+            FREEBUSY;FBTYPE=BUSY-TENTATIVE:20060104T150000Z/PT1H\r\n\
+            FREEBUSY:20060104T190000Z/20060104T200000Z,20060104T140000Z/PT30M\r\n\
+            FREEBUSY;FBTYPE=FREE:20060105T170000Z/PT1H\r\n\
+            END:VFREEBUSY\r\n\
+            END:VCALENDAR\r\n";
+        let busy = parse_free_busy(ical).unwrap();
+        assert_eq!(busy.len(), 3);
+        assert_eq!(busy[0], BusyPeriod::new(Utc.ymd(2006, 1, 4).and_hms(14, 0, 0), Utc.ymd(2006, 1, 4).and_hms(14, 30, 0), FreeBusyType::Busy));
+        assert_eq!(busy[1].kind(), &FreeBusyType::BusyTentative);
+        assert_eq!(busy[1].end(), &Utc.ymd(2006, 1, 4).and_hms(16, 0, 0));
+        assert_eq!(busy[2].start(), &Utc.ymd(2006, 1, 4).and_hms(19, 0, 0));
+
+        assert!(crate::free_busy::is_free(&busy, &Utc.ymd(2006, 1, 4).and_hms(16, 0, 0), &Utc.ymd(2006, 1, 4).and_hms(19, 0, 0)));
+        assert_eq!(crate::free_busy::is_free(&busy, &Utc.ymd(2006, 1, 4).and_hms(18, 0, 0), &Utc.ymd(2006, 1, 4).and_hms(19, 30, 0)), false);
+
+        assert!(parse_free_busy(&ical.replace("PT30M", "tomorrow")).is_err());
+        // Periods that end after the last date chrono can represent are skipped
+        let busy = parse_free_busy(&ical.replace("PT30M", "P99999999W")).unwrap();
+        assert_eq!(busy.len(), 2);
+    }
+
+    #[test]
+    fn test_classification_parsing() {
+        let item_url: Url = "http://some.id/for/testing".parse().unwrap();
+
+        let ical = EXAMPLE_MEETING.replace("SUMMARY:", "CLASS:CONFIDENTIAL\nSUMMARY:");
+        let item = parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        assert_eq!(item.unwrap_event().classification(), Some(&Classification::Confidential));
+        assert!(item.unwrap_event().is_restricted());
+        let ical = crate::ical::build_from(&item).unwrap();
+        assert!(ical.contains("CLASS:CONFIDENTIAL\r\n"));
+        let reparsed = parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        assert!(reparsed.has_same_observable_content_as(&item));
+
+        let mut item = parse(EXAMPLE_ICAL, item_url, SyncStatus::NotSynced).unwrap();
+        let task = item.unwrap_task_mut();
+        assert_eq!(task.classification(), None);
+        assert_eq!(task.is_restricted(), false);
+        task.set_classification(Some(Classification::from("X-SECRET")));
+        assert!(task.is_restricted());
+        assert!(crate::ical::build_from(&item).unwrap().contains("CLASS:X-SECRET\r\n"));
+    }
+
+    #[test]
+    fn test_attachments_parsing() {
+        let item_url: Url = "http://some.id/for/testing".parse().unwrap();
+
+        let ical = EXAMPLE_MEETING.replace("SUMMARY:", concat!(
+            "ATTACH;FMTTYPE=application/pdf;FILENAME=\"agenda; v2.pdf\":HTTPS://Example.com/agenda.pdf\n",
+            "ATTACH;FMTTYPE=text/plain;ENCODING=BASE64;VALUE=BINARY:SGVsbG8sIHdvcmxkIQ==\n",
+            "ATTACH:not a valid URI\n",
+            "SUMMARY:"));
+        let mut item = parse(&ical, item_url.clone(), SyncStatus::NotSynced).unwrap();
+        let event = item.unwrap_event();
+        let attachments = event.attachments();
+        assert_eq!(attachments.len(), 2);
+        assert_eq!(attachments[0].uri(), Some("HTTPS://Example.com/agenda.pdf"));
+        assert_eq!(attachments[0].url().unwrap().as_str(), "https://example.com/agenda.pdf");
+        assert_eq!(attachments[0].format_type(), Some("application/pdf"));
+        assert_eq!(attachments[0].extra_parameters().len(), 1);
+        assert_eq!(attachments[1].data(), Some(&b"Hello, world!"[..]));
+        assert_eq!(attachments[1].format_type(), Some("text/plain"));
+        assert_eq!(event.extra_parameters().iter().filter(|prop| prop.name == "ATTACH").count(), 1);
+
+        let ical = crate::ical::build_from(&item).unwrap();
+        assert!(ical.contains("ATTACH;ENCODING=BASE64;FMTTYPE=text/plain;VALUE=BINARY:"));
+        // URIs are written back verbatim, and parameter values with special characters are quoted
+        let unfolded = ical.replace("\r\n ", "");
+        assert!(unfolded.contains("FILENAME=\"agenda; v2.pdf\""));
+        assert!(unfolded.contains(":HTTPS://Example.com/agenda.pdf\r\n"));
+        let reparsed = parse(&ical, item_url, SyncStatus::NotSynced).unwrap();
+        assert!(reparsed.has_same_observable_content_as(&item));
+
+        if let Item::Event(event) = &mut item {
+            let pdf = event.attachments()[0].clone();
+            assert!(event.remove_attachment(&pdf));
+            assert_eq!(event.remove_attachment(&pdf), false);
+            event.add_attachment(Attachment::from_bytes(vec![0, 1, 2], None));
+            assert_eq!(event.attachments().len(), 2);
+        }
+    }
 }
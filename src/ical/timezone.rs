@@ -0,0 +1,193 @@
+//! Timezone definitions (iCal `VTIMEZONE` components)
+
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+
+use crate::recurrence::Recurrence;
+
+/// The definition of a timezone, as embedded in iCal files that reference it in their `TZID` parameters
+#[derive(Clone, Debug, PartialEq)]
+pub struct VTimezone {
+    tzid: String,
+    observances: Vec<Observance>,
+}
+
+/// A `STANDARD` or `DAYLIGHT` sub-component of a [`VTimezone`], i.e. a UTC offset that is in use from a given onset (that may be repeated by a RRULE)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Observance {
+    daylight: bool,
+    /// The local time of the first onset, in the offset that was in use before it
+    start: NaiveDateTime,
+    offset_from: FixedOffset,
+    offset_to: FixedOffset,
+    recurrence: Option<Recurrence>,
+    /// TZNAME, e.g. `CEST`
+    name: Option<String>,
+}
+
+impl Observance {
+    pub fn new(daylight: bool, start: NaiveDateTime, offset_from: FixedOffset, offset_to: FixedOffset, recurrence: Option<Recurrence>, name: Option<String>) -> Self {
+        Self { daylight, start, offset_from, offset_to, recurrence, name }
+    }
+
+    /// Whether this is a `DAYLIGHT` (rather than a `STANDARD`) observance
+    pub fn is_daylight(&self) -> bool { self.daylight }
+    pub fn start(&self) -> &NaiveDateTime { &self.start }
+    pub fn offset_from(&self) -> &FixedOffset { &self.offset_from }
+    pub fn offset_to(&self) -> &FixedOffset { &self.offset_to }
+    pub fn recurrence(&self) -> Option<&Recurrence> { self.recurrence.as_ref() }
+    pub fn name(&self) -> Option<&str> { self.name.as_deref() }
+
+    /// The last onset of this observance that happens before a given local time
+    fn last_onset_before(&self, local: &NaiveDateTime) -> Option<NaiveDateTime> {
+        if self.start > *local {
+            return None;
+        }
+        match &self.recurrence {
+            None => Some(self.start),
+            // Onsets are local times, but it does not matter to compute them as if they were UTC
+            Some(rule) => rule.instances(Utc.from_utc_datetime(&self.start))
+                .map(|onset| onset.naive_utc())
+                .take_while(|onset| onset <= local)
+                .last(),
+        }
+    }
+}
+
+impl VTimezone {
+    pub fn new(tzid: String, observances: Vec<Observance>) -> Self {
+        Self { tzid, observances }
+    }
+
+    pub fn tzid(&self) -> &str { &self.tzid }
+    pub fn observances(&self) -> &[Observance] { &self.observances }
+
+    /// The UTC offset that is in use at a given local time
+    pub fn offset_at(&self, local: &NaiveDateTime) -> Option<FixedOffset> {
+        let last_onset = self.observances.iter()
+            .filter_map(|o| o.last_onset_before(local).map(|onset| (onset, o.offset_to)))
+            .max_by_key(|(onset, _offset)| *onset);
+        match last_onset {
+            Some((_onset, offset)) => Some(offset),
+            // Before the first onset, the offset that was in use is the one it changes from
+            None => self.observances.iter().min_by_key(|o| o.start).map(|o| o.offset_from),
+        }
+    }
+
+    /// Convert a local time of this timezone into UTC
+    pub fn to_utc(&self, local: &NaiveDateTime) -> Option<DateTime<Utc>> {
+        let offset = self.offset_at(local)?;
+        Some(Utc.from_utc_datetime(&(*local - Duration::seconds(offset.local_minus_utc() as i64))))
+    }
+
+    /// The definition of an IANA timezone, built from the chrono-tz data.
+    ///
+    /// Its DST rules are the ones that are in use during a given `year`, they are assumed to repeat every year since then
+    /// (and its first onsets are in this year, so this should be the year of the earliest date that uses this definition)
+    pub fn from_tz(tz: &Tz, year: i32) -> Self {
+        let year_start = match NaiveDate::from_ymd_opt(year, 1, 1) {
+            Some(date) => date.and_hms(0, 0, 0),
+            None => return Self::fixed(tz, &Utc::now().naive_utc()),
+        };
+
+        let mut observances = Vec::new();
+        let mut day = year_start;
+        while day.year() == year {
+            let next_day = day + Duration::days(1);
+            if offset_of(tz, &day) != offset_of(tz, &next_day) {
+                let transition = first_change(tz, &day, &next_day);
+                observances.push(observance_at(tz, &transition));
+            }
+            day = next_day;
+        }
+
+        match observances.is_empty() {
+            true => Self::fixed(tz, &year_start),
+            false => Self::new(tz.name().to_string(), observances),
+        }
+    }
+
+    /// The definition of a timezone that has no DST (at least around `when`)
+    fn fixed(tz: &Tz, when: &NaiveDateTime) -> Self {
+        let offset = offset_of(tz, when);
+        let name = tz.from_utc_datetime(when).format("%Z").to_string();
+        let start = NaiveDate::from_ymd(1970, 1, 1).and_hms(0, 0, 0);
+        Self::new(tz.name().to_string(), vec![Observance::new(false, start, offset, offset, None, Some(name))])
+    }
+}
+
+fn offset_of(tz: &Tz, utc: &NaiveDateTime) -> FixedOffset {
+    tz.offset_from_utc_datetime(utc).fix()
+}
+
+/// The first minute (in UTC) between `from` and `to` where the offset of a timezone is not the one at `from`
+fn first_change(tz: &Tz, from: &NaiveDateTime, to: &NaiveDateTime) -> NaiveDateTime {
+    let initial = offset_of(tz, from);
+    let (mut low, mut high) = (0, (*to - *from).num_minutes());
+    while high - low > 1 {
+        let middle = (low + high) / 2;
+        match offset_of(tz, &(*from + Duration::minutes(middle))) == initial {
+            true => low = middle,
+            false => high = middle,
+        }
+    }
+    *from + Duration::minutes(high)
+}
+
+/// The observance that starts at a given transition (in UTC), repeated every year on the same weekday of the same week of the month
+fn observance_at(tz: &Tz, transition: &NaiveDateTime) -> Observance {
+    let offset_from = offset_of(tz, &(*transition - Duration::minutes(1)));
+    let offset_to = offset_of(tz, transition);
+    let onset = *transition + Duration::seconds(offset_from.local_minus_utc() as i64);
+
+    let days_in_month = match onset.month() {
+        12 => 31,
+        m => (NaiveDate::from_ymd(onset.year(), m + 1, 1) - NaiveDate::from_ymd(onset.year(), m, 1)).num_days() as u32,
+    };
+    let week = match onset.day() + 7 > days_in_month {
+        true => -1,
+        false => ((onset.day() - 1) / 7 + 1) as i32,
+    };
+    let weekday = match onset.weekday() {
+        chrono::Weekday::Mon => "MO",
+        chrono::Weekday::Tue => "TU",
+        chrono::Weekday::Wed => "WE",
+        chrono::Weekday::Thu => "TH",
+        chrono::Weekday::Fri => "FR",
+        chrono::Weekday::Sat => "SA",
+        chrono::Weekday::Sun => "SU",
+    };
+    let recurrence = format!("FREQ=YEARLY;BYMONTH={};BYDAY={}{}", onset.month(), week, weekday).parse().ok();
+    let name = tz.from_utc_datetime(transition).format("%Z").to_string();
+
+    Observance::new(offset_to.local_minus_utc() > offset_from.local_minus_utc(), onset, offset_from, offset_to, recurrence, Some(name))
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timezone_from_chrono_tz() {
+        let paris = VTimezone::from_tz(&chrono_tz::Europe::Paris, 2021);
+        assert_eq!(paris.tzid(), "Europe/Paris");
+        assert_eq!(paris.observances().len(), 2);
+        let summer = paris.observances().iter().find(|o| o.is_daylight()).unwrap();
+        assert_eq!(summer.start(), &NaiveDate::from_ymd(2021, 3, 28).and_hms(2, 0, 0));
+        assert_eq!(summer.offset_to().local_minus_utc(), 7200);
+        assert_eq!(summer.recurrence().unwrap().to_string(), "FREQ=YEARLY;BYDAY=-1SU;BYMONTH=3");
+        assert_eq!(summer.name(), Some("CEST"));
+
+        // The DST rules repeat in the next years
+        let local = NaiveDate::from_ymd(2023, 7, 14).and_hms(12, 0, 0);
+        assert_eq!(paris.to_utc(&local), Some(Utc.ymd(2023, 7, 14).and_hms(10, 0, 0)));
+        let local = NaiveDate::from_ymd(2023, 12, 25).and_hms(12, 0, 0);
+        assert_eq!(paris.to_utc(&local), Some(Utc.ymd(2023, 12, 25).and_hms(11, 0, 0)));
+
+        let tokyo = VTimezone::from_tz(&chrono_tz::Asia::Tokyo, 2021);
+        assert_eq!(tokyo.observances().len(), 1);
+        assert_eq!(tokyo.offset_at(&local).map(|o| o.local_minus_utc()), Some(9 * 3600));
+    }
+}
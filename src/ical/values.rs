@@ -0,0 +1,336 @@
+//! Parsers for the values of iCal properties (see [RFC 5545 §3.3](https://datatracker.ietf.org/doc/html/rfc5545#section-3.3))
+//!
+//! These are the parsers that are used for the properties this crate supports.
+//! Apps can use them for properties it does not handle (e.g. `X-` properties, that are kept in the `extra_parameters` of items)
+
+use std::fmt::{Display, Formatter};
+
+use chrono::{Duration, FixedOffset, NaiveDate};
+
+use crate::date_time::IcalDateTime;
+
+/// The types of values these parsers handle
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValueType {
+    Date,
+    DateTime,
+    Duration,
+    Period,
+    UtcOffset,
+}
+
+impl ValueType {
+    /// The name of this value type, as used in `VALUE=` parameters
+    pub fn as_ical_str(&self) -> &'static str {
+        match self {
+            ValueType::Date => "DATE",
+            ValueType::DateTime => "DATE-TIME",
+            ValueType::Duration => "DURATION",
+            ValueType::Period => "PERIOD",
+            ValueType::UtcOffset => "UTC-OFFSET",
+        }
+    }
+}
+
+/// A value that cannot be parsed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValueError {
+    value_type: ValueType,
+    value: String,
+    reason: &'static str,
+}
+
+impl ValueError {
+    fn new(value_type: ValueType, value: &str, reason: &'static str) -> Self {
+        Self { value_type, value: value.to_string(), reason }
+    }
+
+    /// The type the value was expected to have
+    pub fn value_type(&self) -> ValueType { self.value_type }
+    /// The invalid value
+    pub fn value(&self) -> &str { &self.value }
+    /// Why the value is invalid
+    pub fn reason(&self) -> &str { self.reason }
+}
+
+impl Display for ValueError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid {} value {:?}: {}", self.value_type.as_ical_str(), self.value, self.reason)
+    }
+}
+
+impl std::error::Error for ValueError {}
+
+
+
+/// Either a DATE or a DATE-TIME value (e.g. for `DTSTART`, `DUE` or `EXDATE` properties)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DateOrDateTime {
+    Date(NaiveDate),
+    DateTime(IcalDateTime),
+}
+
+/// A PERIOD value, that is either given by its start and its end, or by its start and its duration
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PeriodValue {
+    /// e.g. `19970101T180000Z/19970102T070000Z`
+    Explicit { start: IcalDateTime, end: IcalDateTime },
+    /// e.g. `19970101T180000Z/PT5H30M`
+    Duration { start: IcalDateTime, duration: Duration },
+}
+
+impl PeriodValue {
+    pub fn start(&self) -> &IcalDateTime {
+        match self {
+            PeriodValue::Explicit { start, .. } => start,
+            PeriodValue::Duration { start, .. } => start,
+        }
+    }
+}
+
+/// Parse a DATE value, e.g. `19970714`
+pub fn parse_date(value: &str) -> Result<NaiveDate, ValueError> {
+    if value.len() != 8 || value.chars().all(|c| c.is_ascii_digit()) == false {
+        return Err(ValueError::new(ValueType::Date, value, "expected 8 digits (YYYYMMDD)"));
+    }
+    NaiveDate::parse_from_str(value, "%Y%m%d")
+        .map_err(|_| ValueError::new(ValueType::Date, value, "this date does not exist"))
+}
+
+/// Parse a DATE-TIME value, e.g. `19980119T070000Z`. `tzid` is the `TZID` parameter of its property, if any
+pub fn parse_date_time(value: &str, tzid: Option<&str>) -> Result<IcalDateTime, ValueError> {
+    let (date, time) = match value.split_once('T') {
+        Some(parts) => parts,
+        None => return Err(ValueError::new(ValueType::DateTime, value, "missing the T separator between the date and the time")),
+    };
+    parse_date(date).map_err(|_| ValueError::new(ValueType::DateTime, value, "invalid date"))?;
+    let digits = time.strip_suffix('Z').unwrap_or(time);
+    if digits.len() != 6 || digits.chars().all(|c| c.is_ascii_digit()) == false {
+        return Err(ValueError::new(ValueType::DateTime, value, "expected 6 digits for the time (HHMMSS), optionally followed by Z"));
+    }
+    if time.ends_with('Z') && tzid.is_some() {
+        return Err(ValueError::new(ValueType::DateTime, value, "UTC times cannot have a TZID"));
+    }
+    IcalDateTime::parse(value, tzid)
+        .ok_or_else(|| ValueError::new(ValueType::DateTime, value, "this time does not exist"))
+}
+
+/// Parse a DATE or a DATE-TIME value, depending on its length
+pub fn parse_date_or_date_time(value: &str, tzid: Option<&str>) -> Result<DateOrDateTime, ValueError> {
+    match value.contains('T') {
+        true => parse_date_time(value, tzid).map(DateOrDateTime::DateTime),
+        false => parse_date(value).map(DateOrDateTime::Date),
+    }
+}
+
+/// The longest duration [`Duration`] can hold
+const MAX_DURATION_SECONDS: i64 = i64::MAX / 1000;
+
+/// Parse a DURATION value, e.g. `-PT15M` or `P1DT2H`
+pub fn parse_duration(value: &str) -> Result<Duration, ValueError> {
+    let invalid = |reason| ValueError::new(ValueType::Duration, value, reason);
+
+    let (sign, unsigned) = match value.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let designators = unsigned.strip_prefix('P').ok_or_else(|| invalid("missing the P prefix"))?;
+
+    let mut seconds = 0i64;
+    let mut number = String::new();
+    let mut in_time = false;
+    let mut has_components = false;
+    for c in designators.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' if in_time == false && number.is_empty() => in_time = true,
+            unit => {
+                let n: i64 = number.parse().map_err(|_| invalid("missing a number before a unit"))?;
+                let unit_seconds = match (unit, in_time) {
+                    ('W', false) => 7 * 24 * 3600,
+                    ('D', false) => 24 * 3600,
+                    ('H', true) => 3600,
+                    ('M', true) => 60,
+                    ('S', true) => 1,
+                    ('H', false) | ('M', false) | ('S', false) => return Err(invalid("hours, minutes and seconds must come after T")),
+                    ('W', true) | ('D', true) => return Err(invalid("weeks and days must come before T")),
+                    _ => return Err(invalid("unknown unit")),
+                };
+                seconds = n.checked_mul(unit_seconds)
+                    .and_then(|s| seconds.checked_add(s))
+                    .filter(|s| *s <= MAX_DURATION_SECONDS)
+                    .ok_or_else(|| invalid("too long"))?;
+                number.clear();
+                has_components = true;
+            }
+        }
+    }
+    if number.is_empty() == false {
+        return Err(invalid("missing a unit after the last number"));
+    }
+    if has_components == false {
+        return Err(invalid("empty duration"));
+    }
+
+    Ok(Duration::seconds(sign * seconds))
+}
+
+/// Parse a PERIOD value, e.g. `19970101T180000Z/PT5H30M`. `tzid` is the `TZID` parameter of its property, if any
+pub fn parse_period(value: &str, tzid: Option<&str>) -> Result<PeriodValue, ValueError> {
+    let (start, end) = value.split_once('/')
+        .ok_or_else(|| ValueError::new(ValueType::Period, value, "missing the / separator"))?;
+    let start = parse_date_time(start, tzid)
+        .map_err(|_| ValueError::new(ValueType::Period, value, "invalid start"))?;
+    if end.starts_with('P') || end.starts_with('+') || end.starts_with('-') {
+        let duration = parse_duration(end)
+            .map_err(|_| ValueError::new(ValueType::Period, value, "invalid duration"))?;
+        if duration < Duration::zero() {
+            return Err(ValueError::new(ValueType::Period, value, "durations of periods must be positive"));
+        }
+        Ok(PeriodValue::Duration { start, duration })
+    } else {
+        let end = parse_date_time(end, tzid)
+            .map_err(|_| ValueError::new(ValueType::Period, value, "invalid end"))?;
+        Ok(PeriodValue::Explicit { start, end })
+    }
+}
+
+/// Parse a UTC-OFFSET value, e.g. `+0100` or `-053000`
+pub fn parse_utc_offset(value: &str) -> Result<FixedOffset, ValueError> {
+    let invalid = |reason| ValueError::new(ValueType::UtcOffset, value, reason);
+
+    let (sign, digits) = match (value.strip_prefix('+'), value.strip_prefix('-')) {
+        (Some(digits), _) => (1, digits),
+        (_, Some(digits)) => (-1, digits),
+        _ => return Err(invalid("missing the + or - sign")),
+    };
+    if (digits.len() != 4 && digits.len() != 6) || digits.chars().all(|c| c.is_ascii_digit()) == false {
+        return Err(invalid("expected 4 or 6 digits (HHMM or HHMMSS)"));
+    }
+    let hours: i32 = digits[0..2].parse().map_err(|_| invalid("invalid hours"))?;
+    let minutes: i32 = digits[2..4].parse().map_err(|_| invalid("invalid minutes"))?;
+    let seconds: i32 = match digits.len() {
+        6 => digits[4..6].parse().map_err(|_| invalid("invalid seconds"))?,
+        _ => 0,
+    };
+    if minutes >= 60 || seconds >= 60 {
+        return Err(invalid("minutes and seconds must be lower than 60"));
+    }
+    if sign == -1 && hours == 0 && minutes == 0 && seconds == 0 {
+        return Err(invalid("-0000 is not allowed"));
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60 + seconds))
+        .ok_or_else(|| invalid("out of range"))
+}
+
+/// Parse a comma-separated list of values (e.g. `EXDATE:19960402T010000Z,19960403T010000Z`), with one of the parsers of this module.
+///
+/// This is not suited to lists of TEXT values, that may contain escaped commas (see [`split_text_list`])
+pub fn parse_list<T, F>(value: &str, parse: F) -> Result<Vec<T>, ValueError>
+where
+    F: Fn(&str) -> Result<T, ValueError>,
+{
+    value.split(',')
+        .map(|v| parse(v.trim()))
+        .collect()
+}
+
+/// Escape a TEXT value (backslashes, semicolons, commas and newlines), as required by RFC 5545
+pub fn escape_text(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            },
+            '\r' if chars.peek() == Some(&'\n') => (),
+            '\r' | '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Unescape a TEXT value (the `ical` crate does not)
+pub fn unescape_text(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') | Some('N') => unescaped.push('\n'),
+                Some(escaped) => unescaped.push(escaped),
+                None => unescaped.push('\\'),
+            },
+            c => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+/// Split a comma-separated list of TEXT values, and unescape them (the `ical` crate does not).
+/// Commas that are escaped (`\,`) are part of the values.
+pub fn split_text_list(value: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut current = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') | Some('N') => current.push('\n'),
+                Some(escaped) => current.push(escaped),
+                None => current.push('\\'),
+            },
+            ',' => values.push(std::mem::take(&mut current)),
+            c => current.push(c),
+        }
+    }
+    values.push(current);
+    values
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDateTime, TimeZone, Utc};
+
+    #[test]
+    fn test_value_parsers() {
+        assert_eq!(parse_date("20210321"), Ok(NaiveDate::from_ymd(2021, 3, 21)));
+        assert_eq!(parse_date("20210231").unwrap_err().reason(), "this date does not exist");
+
+        assert_eq!(parse_date_time("20210321T090000Z", None), Ok(IcalDateTime::Utc(Utc.ymd(2021, 3, 21).and_hms(9, 0, 0))));
+        let floating = NaiveDateTime::parse_from_str("20210321T090000", "%Y%m%dT%H%M%S").unwrap();
+        assert_eq!(parse_date_time("20210321T090000", None), Ok(IcalDateTime::Floating(floating)));
+        assert_eq!(parse_date_or_date_time("20210321", None), Ok(DateOrDateTime::Date(NaiveDate::from_ymd(2021, 3, 21))));
+        let err = parse_date_time("20210321T0900", None).unwrap_err();
+        assert_eq!(err.value_type(), ValueType::DateTime);
+        assert_eq!(err.to_string(), "Invalid DATE-TIME value \"20210321T0900\": expected 6 digits for the time (HHMMSS), optionally followed by Z");
+
+        assert_eq!(parse_duration("-PT15M"), Ok(Duration::minutes(-15)));
+        assert_eq!(parse_duration("P1H").unwrap_err().reason(), "hours, minutes and seconds must come after T");
+        assert_eq!(parse_duration("PT9999999999999999S").unwrap_err().reason(), "too long");
+        assert_eq!(parse_duration("-PT9999999999999999S").unwrap_err().reason(), "too long");
+
+        let period = parse_period("20210321T090000Z/PT1H30M", None).unwrap();
+        assert_eq!(period, PeriodValue::Duration { start: IcalDateTime::Utc(Utc.ymd(2021, 3, 21).and_hms(9, 0, 0)), duration: Duration::minutes(90) });
+        assert!(matches!(parse_period("20210321T090000Z/20210321T100000Z", None), Ok(PeriodValue::Explicit { .. })));
+        assert!(parse_period("20210321T090000Z", None).is_err());
+
+        assert_eq!(parse_utc_offset("-0530").map(|o| o.local_minus_utc()), Ok(-(5 * 3600 + 30 * 60)));
+        assert!(parse_utc_offset("-0000").is_err());
+
+        let dates = parse_list("20210321,20210322", parse_date).unwrap();
+        assert_eq!(dates.len(), 2);
+        assert!(parse_list("20210321,tomorrow", parse_date).is_err());
+        assert_eq!(split_text_list("a\\,b,c"), vec!["a,b".to_string(), "c".to_string()]);
+
+        let text = "Bring: bread, milk; and a \\ backslash\r\nSee you!";
+        assert_eq!(escape_text(text), "Bring: bread\\, milk\\; and a \\\\ backslash\\nSee you!");
+        assert_eq!(unescape_text(&escape_text(text)), text.replace("\r\n", "\n"));
+    }
+}
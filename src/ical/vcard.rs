@@ -0,0 +1,324 @@
+//! Conversion between vCards (as they are stored in CardDAV address books) and [`Contact`]s
+
+use std::error::Error;
+
+use chrono::{DateTime, TimeZone, Utc};
+use ical::property::Property;
+use url::Url;
+
+use crate::contact::Contact;
+use crate::item::{SyncStatus, Uid};
+use super::builder::quote_param_value;
+use super::values::{escape_text, unescape_text};
+
+/// The vCard version that is written for contacts that do not have any
+const DEFAULT_VERSION: &str = "3.0";
+
+/// Whether some item data is a vCard (rather than an iCal file)
+pub(crate) fn is_vcard(content: &str) -> bool {
+    content.trim_start().get(..11)
+        .map(|begin| begin.eq_ignore_ascii_case("BEGIN:VCARD"))
+        .unwrap_or(false)
+}
+
+/// Parse a vCard into a [`Contact`]
+pub(crate) fn parse(content: &str, item_url: Url, sync_status: SyncStatus) -> Result<Contact, Box<dyn Error>> {
+    let content = super::encoding::join_soft_line_breaks(content);
+    let mut reader = ical::VcardParser::new(content.as_bytes());
+    let mut vcard = match reader.next() {
+        None => return Err(format!("Invalid vCard data to parse for item {}", item_url).into()),
+        Some(Err(err)) => return Err(format!("Unable to parse vCard data for item {}: {}", item_url, err).into()),
+        Some(Ok(vcard)) => vcard,
+    };
+    if reader.next().map(|r| r.is_ok()) == Some(true) {
+        return Err("Parsing multiple vCards are not supported".into());
+    }
+
+    let mut full_name = None;
+    let mut uid = None;
+    let mut emails = Vec::new();
+    let mut phones = Vec::new();
+    let mut value_properties = Vec::new();
+    let mut organization = None;
+    let mut last_modified = None;
+    let mut ical_prod_id = None;
+    super::encoding::decode_properties(&mut vcard.properties);
+    let mut extra_parameters = Vec::with_capacity(vcard.properties.len());
+
+    for prop in vcard.properties {
+        match prop.name.to_ascii_uppercase().as_str() {
+            "FN" => full_name = prop.value.as_deref().map(unescape_text),
+            "UID" => uid = prop.value,
+            "EMAIL" => {
+                push_by_preference(&mut emails, &prop);
+                value_properties.push(prop);
+            },
+            "TEL" => {
+                push_by_preference(&mut phones, &prop);
+                value_properties.push(prop);
+            },
+            "ORG" => organization = prop.value
+                .filter(|org| org.trim_matches(';').is_empty() == false)
+                .map(|org| split_components(&org).join(";")),
+            "REV" => last_modified = prop.value.as_deref().and_then(parse_timestamp),
+            "PRODID" => ical_prod_id = prop.value,
+            _ => {
+                // This field is not supported. Let's store it anyway, so that we are able to re-create an identical vCard
+                extra_parameters.push(prop);
+            }
+        }
+    }
+
+    let full_name = match full_name {
+        Some(name) => name,
+        None => return Err(format!("Missing FN for item {}, but this is required by vCards", item_url).into()),
+    };
+    let uid = match uid.as_deref().map(Uid::new) {
+        Some(Ok(uid)) => uid,
+        Some(Err(err)) => return Err(format!("Invalid UID for item {}: {}", item_url, err).into()),
+        None => return Err(format!("Missing UID for item {}", item_url).into()),
+    };
+    // REV is optional. Contacts that have none are deemed older than any local change
+    let last_modified = last_modified.unwrap_or_else(|| Utc.timestamp(0, 0));
+    let ical_prod_id = ical_prod_id.unwrap_or_else(super::default_prod_id);
+
+    Ok(Contact::new_with_parameters(
+        full_name,
+        uid,
+        item_url,
+        emails,
+        phones,
+        organization,
+        sync_status,
+        None,
+        last_modified,
+        ical_prod_id,
+        extra_parameters,
+    ).with_value_properties(value_properties))
+}
+
+/// Create a vCard from a [`Contact`]
+pub fn build_from_contact(contact: &Contact) -> Result<String, Box<dyn Error>> {
+    let version = contact.extra_parameters().iter()
+        .find(|prop| prop.name.eq_ignore_ascii_case("VERSION"))
+        .and_then(|prop| prop.value.clone())
+        .unwrap_or_else(|| DEFAULT_VERSION.to_string());
+
+    // VERSION must come right after BEGIN
+    let mut lines = vec![
+        "BEGIN:VCARD".to_string(),
+        format!("VERSION:{}", version),
+        format!("PRODID:{}", contact.ical_prod_id()),
+        format!("UID:{}", contact.uid().as_str()),
+        format!("FN:{}", escape_text(contact.name())),
+    ];
+    let has_n = contact.extra_parameters().iter().any(|prop| prop.name.eq_ignore_ascii_case("N"));
+    if has_n == false && version == DEFAULT_VERSION {
+        // N is mandatory in vCard 3.0, even though we do not handle its components (yet)
+        lines.push("N:;;;;".to_string());
+    }
+    lines.extend(value_lines("EMAIL", contact.emails(), contact.value_properties(), &version));
+    lines.extend(value_lines("TEL", contact.phones(), contact.value_properties(), &version));
+    if let Some(org) = contact.organization() {
+        // ORG is a structured value, whose components are separated by semicolons
+        let components: Vec<String> = org.split(';').map(escape_text).collect();
+        lines.push(format!("ORG:{}", components.join(";")));
+    }
+    lines.push(format!("REV:{}", contact.last_modified().format("%Y%m%dT%H%M%SZ")));
+
+    // Also add fields that we have not handled
+    for prop in contact.extra_parameters() {
+        if prop.name.eq_ignore_ascii_case("VERSION") {
+            continue;
+        }
+        lines.push(property_line(prop));
+    }
+    lines.push("END:VCARD".to_string());
+
+    let mut vcard = lines.join("\r\n");
+    vcard.push_str("\r\n");
+    Ok(super::builder::fold_lines(&vcard))
+}
+
+/// The EMAIL or TEL lines of a contact.
+///
+/// Values keep the parameters (e.g. `TYPE=WORK`) of the property they have been parsed from. The first one is marked as preferred
+/// (with `TYPE=PREF` in vCard 3.0, `PREF=1` in vCard 4.0) if there are several of them
+fn value_lines(name: &str, values: &[String], originals: &[Property], version: &str) -> Vec<String> {
+    values.iter().enumerate()
+        .map(|(index, value)| {
+            let original = originals.iter()
+                .find(|prop| prop.name.eq_ignore_ascii_case(name) && prop.value.as_deref() == Some(value.as_str()));
+            let preferred = index == 0 && values.len() > 1;
+            let params = match original {
+                Some(prop) if is_preferred(prop) == preferred => prop.params.clone().unwrap_or_default(),
+                Some(prop) => with_preference(prop.params.clone().unwrap_or_default(), preferred, version),
+                None => with_preference(Vec::new(), preferred, version),
+            };
+            property_line(&Property { name: name.to_string(), params: Some(params), value: Some(value.clone()) })
+        })
+        .collect()
+}
+
+/// Add or remove the mark of the preferred value from the parameters of an EMAIL or TEL property
+fn with_preference(mut params: Vec<(String, Vec<String>)>, preferred: bool, version: &str) -> Vec<(String, Vec<String>)> {
+    for (name, values) in params.iter_mut() {
+        if name.eq_ignore_ascii_case("TYPE") {
+            values.retain(|v| v.eq_ignore_ascii_case("pref") == false);
+        }
+    }
+    params.retain(|(name, values)| {
+        let pref_1 = name.eq_ignore_ascii_case("PREF") && values.iter().any(|v| v == "1");
+        values.is_empty() == false && pref_1 == false
+    });
+
+    if preferred {
+        match version.starts_with('4') {
+            true => params.push(("PREF".to_string(), vec!["1".to_string()])),
+            false => match params.iter_mut().find(|(name, _)| name.eq_ignore_ascii_case("TYPE")) {
+                Some((_, values)) => values.push("PREF".to_string()),
+                None => params.push(("TYPE".to_string(), vec!["PREF".to_string()])),
+            },
+        }
+    }
+    params
+}
+
+/// Whether an EMAIL or TEL property is marked as the preferred one
+fn is_preferred(prop: &Property) -> bool {
+    prop.params.as_ref()
+        .map(|params| params.iter().any(|(name, values)| {
+            (name.eq_ignore_ascii_case("TYPE") && values.iter().any(|v| v.eq_ignore_ascii_case("pref")))
+            || (name.eq_ignore_ascii_case("PREF") && values.iter().any(|v| v == "1"))
+        }))
+        .unwrap_or(false)
+}
+
+/// Add the value of an EMAIL or TEL property, the preferred ones (`TYPE=PREF` in vCard 3.0, `PREF=1` in vCard 4.0) first
+fn push_by_preference(values: &mut Vec<String>, prop: &Property) {
+    let value = match &prop.value {
+        Some(value) if value.is_empty() == false => value.clone(),
+        _ => return,
+    };
+    match is_preferred(prop) {
+        true => values.insert(0, value),
+        false => values.push(value),
+    }
+}
+
+/// Parse a REV timestamp, that may be written in the basic (`20210321T001600Z`) or the extended (`2021-03-21T00:16:00Z`) format
+fn parse_timestamp(s: &str) -> Option<DateTime<Utc>> {
+    let basic: String = s.chars().filter(|c| *c != '-' && *c != ':').collect();
+    let basic = basic.trim_end_matches('Z');
+    Utc.datetime_from_str(basic, "%Y%m%dT%H%M%S").ok()
+}
+
+/// Split a structured value (e.g. ORG) into its unescaped components. Semicolons that are escaped (`\;`) are part of the components
+fn split_components(value: &str) -> Vec<String> {
+    let mut components = Vec::new();
+    let mut current = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                current.push(c);
+                if let Some(escaped) = chars.next() {
+                    current.push(escaped);
+                }
+            },
+            ';' => components.push(unescape_text(&std::mem::take(&mut current))),
+            c => current.push(c),
+        }
+    }
+    components.push(unescape_text(&current));
+    components
+}
+
+fn property_line(prop: &Property) -> String {
+    let mut line = prop.name.clone();
+    for (name, values) in prop.params.iter().flatten() {
+        let values: Vec<String> = values.iter().map(|value| quote_param_value(value)).collect();
+        line.push_str(&format!(";{}={}", name, values.join(",")));
+    }
+    line.push(':');
+    line.push_str(prop.value.as_deref().unwrap_or_default());
+    line
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE_VCARD: &str = "BEGIN:VCARD\r
+VERSION:3.0\r
+PRODID:-//Sabre//Sabre VObject 4.1.6//EN\r
+UID:2d6d7d0a-36a3-4f5e-a4c7-1f36b6b4bd0e\r
+FN:Jane Doe\r
+N:Doe;Jane;;;\r
+EMAIL;TYPE=WORK:jane@work.example.com\r
+EMAIL;TYPE=HOME,PREF:jane@example.com\r
+TEL;TYPE=CELL:+33 6 12 34 56 78\r
+ORG:Example Corp;Accounting\r
+REV:2021-03-21T00:16:00Z\r
+NOTE:Met at the conference\r
+END:VCARD\r
+";
+
+    #[test]
+    fn test_vcard_round_trip() {
+        assert!(is_vcard(EXAMPLE_VCARD));
+        assert!(is_vcard("BEGIN:VCALENDAR\r\nEND:VCALENDAR\r\n") == false);
+
+        let url: Url = "https://carddav.example.com/addressbooks/jane/contacts/jane.vcf".parse().unwrap();
+        let contact = parse(EXAMPLE_VCARD, url.clone(), SyncStatus::NotSynced).unwrap();
+        assert_eq!(contact.name(), "Jane Doe");
+        assert_eq!(contact.emails(), &["jane@example.com".to_string(), "jane@work.example.com".to_string()]);
+        assert_eq!(contact.phones(), &["+33 6 12 34 56 78".to_string()]);
+        assert_eq!(contact.organization(), Some("Example Corp;Accounting"));
+        assert_eq!(contact.last_modified(), &Utc.ymd(2021, 3, 21).and_hms(0, 16, 0));
+
+        let vcard = build_from_contact(&contact).unwrap();
+        assert!(vcard.starts_with("BEGIN:VCARD\r\nVERSION:3.0\r\n"));
+        assert!(vcard.contains("\r\nN:Doe;Jane;;;\r\n"));
+        assert!(vcard.contains("\r\nNOTE:Met at the conference\r\n"));
+        assert!(vcard.contains("\r\nEMAIL;TYPE=HOME,PREF:jane@example.com\r\nEMAIL;TYPE=WORK:jane@work.example.com\r\n"));
+        assert!(vcard.contains("\r\nTEL;TYPE=CELL:+33 6 12 34 56 78\r\n"));
+
+        let parsed_again = parse(&vcard, url, SyncStatus::NotSynced).unwrap();
+        assert!(parsed_again.has_same_observable_content_as(&contact));
+    }
+
+    #[test]
+    fn test_vcard_params_survive_edits() {
+        let url: Url = "https://carddav.example.com/addressbooks/jane/contacts/jane.vcf".parse().unwrap();
+        let mut contact = parse(EXAMPLE_VCARD, url.clone(), SyncStatus::NotSynced).unwrap();
+
+        // The work address becomes the preferred one
+        contact.set_emails(vec!["jane@work.example.com".to_string(), "jane@example.com".to_string(), "jd@example.com".to_string()]);
+        contact.set_name("Doe, Jane\nJr".to_string());
+        contact.set_organization(Some("Example, Inc.;Accounting".to_string()));
+        let vcard = build_from_contact(&contact).unwrap();
+        assert!(vcard.contains("\r\nEMAIL;TYPE=WORK,PREF:jane@work.example.com\r\nEMAIL;TYPE=HOME:jane@example.com\r\nEMAIL:jd@example.com\r\n"));
+        assert!(vcard.contains("\r\nTEL;TYPE=CELL:+33 6 12 34 56 78\r\n"));
+        assert!(vcard.contains("\r\nFN:Doe\\, Jane\\nJr\r\n"));
+        assert!(vcard.contains("\r\nORG:Example\\, Inc.;Accounting\r\n"));
+
+        let parsed_again = parse(&vcard, url.clone(), SyncStatus::NotSynced).unwrap();
+        assert_eq!(parsed_again.name(), "Doe, Jane\nJr");
+        assert_eq!(parsed_again.organization(), Some("Example, Inc.;Accounting"));
+        assert!(parsed_again.has_same_observable_content_as(&contact));
+
+        // vCard 4.0 marks the preferred values with PREF=1, and parameter values with special characters are quoted
+        let vcard_4 = EXAMPLE_VCARD
+            .replace("VERSION:3.0", "VERSION:4.0")
+            .replace("TYPE=HOME,PREF", "TYPE=HOME;PREF=1")
+            .replace("NOTE:", "NOTE;ALTREP=\"https://example.com/notes;1\":");
+        let mut contact = parse(&vcard_4, url, SyncStatus::NotSynced).unwrap();
+        contact.set_emails(vec!["jane@work.example.com".to_string(), "jane@example.com".to_string()]);
+        let vcard = build_from_contact(&contact).unwrap();
+        assert!(vcard.contains("\r\nEMAIL;TYPE=WORK;PREF=1:jane@work.example.com\r\nEMAIL;TYPE=HOME:jane@example.com\r\n"));
+        assert!(vcard.contains("\r\nNOTE;ALTREP=\"https://example.com/notes;1\":Met at the conference\r\n"));
+    }
+}
@@ -1,6 +1,12 @@
 //! CalDAV items (todo, events, journals...)
 // TODO: move Event and Task to nest them in crate::items::calendar::Calendar?
 
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+
 use serde::{Deserialize, Serialize};
 use url::Url;
 use chrono::{DateTime, Utc};
@@ -10,15 +16,19 @@ use chrono::{DateTime, Utc};
 pub enum Item {
     Event(crate::event::Event),
     Task(crate::task::Task),
+    Journal(crate::journal::Journal),
+    Contact(crate::contact::Contact),
 }
 
-/// Returns `task.$property_name` or `event.$property_name`, depending on whether self is a Task or an Event
+/// Returns `task.$property_name`, `event.$property_name`, `journal.$property_name` or `contact.$property_name`, depending on the kind of item
 macro_rules! synthetise_common_getter {
     ($property_name:ident, $return_type:ty) => {
         pub fn $property_name(&self) -> $return_type {
             match self {
                 Item::Event(e) => e.$property_name(),
                 Item::Task(t) => t.$property_name(),
+                Item::Journal(j) => j.$property_name(),
+                Item::Contact(c) => c.$property_name(),
             }
         }
     }
@@ -26,17 +36,63 @@ macro_rules! synthetise_common_getter {
 
 impl Item {
     synthetise_common_getter!(url, &Url);
-    synthetise_common_getter!(uid, &str);
+    synthetise_common_getter!(uid, &Uid);
     synthetise_common_getter!(name, &str);
     synthetise_common_getter!(creation_date, Option<&DateTime<Utc>>);
     synthetise_common_getter!(last_modified, &DateTime<Utc>);
     synthetise_common_getter!(sync_status, &SyncStatus);
     synthetise_common_getter!(ical_prod_id, &str);
 
+    /// The version tag (ETag) this item had on the server the last time it was synced (if it ever was)
+    pub fn last_known_version_tag(&self) -> Option<&VersionTag> {
+        self.sync_status().version_tag()
+    }
+
     pub fn set_sync_status(&mut self, new_status: SyncStatus) {
         match self {
             Item::Event(e) => e.set_sync_status(new_status),
             Item::Task(t) => t.set_sync_status(new_status),
+            Item::Journal(j) => j.set_sync_status(new_status),
+            Item::Contact(c) => c.set_sync_status(new_status),
+        }
+    }
+
+    /// Set the "last modified" date of this item to now
+    pub(crate) fn update_last_modified(&mut self) {
+        match self {
+            Item::Event(e) => e.update_last_modified(),
+            Item::Task(t) => t.update_last_modified(),
+            Item::Journal(j) => j.update_last_modified(),
+            Item::Contact(c) => c.update_last_modified(),
+        }
+    }
+
+    /// Drop the content of an item that has been marked for deletion, only keeping what is needed to push its deletion to the server. \
+    /// Returns whether something has been dropped
+    pub(crate) fn drop_content(&mut self) -> bool {
+        match self {
+            Item::Event(e) => e.drop_content(),
+            Item::Task(t) => t.drop_content(),
+            Item::Journal(j) => j.drop_content(),
+            Item::Contact(c) => c.drop_content(),
+        }
+    }
+
+    pub(crate) fn set_url(&mut self, new_url: Url) {
+        match self {
+            Item::Event(e) => e.set_url(new_url),
+            Item::Task(t) => t.set_url(new_url),
+            Item::Journal(j) => j.set_url(new_url),
+            Item::Contact(c) => c.set_url(new_url),
+        }
+    }
+
+    pub(crate) fn set_uid(&mut self, new_uid: Uid) {
+        match self {
+            Item::Event(e) => e.set_uid(new_uid),
+            Item::Task(t) => t.set_uid(new_uid),
+            Item::Journal(j) => j.set_uid(new_uid),
+            Item::Contact(c) => c.set_uid(new_uid),
         }
     }
 
@@ -54,6 +110,20 @@ impl Item {
         }
     }
 
+    pub fn is_journal(&self) -> bool {
+        match &self {
+            Item::Journal(_) => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_contact(&self) -> bool {
+        match &self {
+            Item::Contact(_) => true,
+            _ => false,
+        }
+    }
+
     /// Returns a mutable reference to the inner Task
     ///
     /// # Panics
@@ -76,11 +146,46 @@ impl Item {
         }
     }
 
+    /// Returns a reference to the inner Event
+    ///
+    /// # Panics
+    /// Panics if the inner item is not an Event
+    pub fn unwrap_event(&self) -> &crate::event::Event {
+        match self {
+            Item::Event(e) => e,
+            _ => panic!("Not an event"),
+        }
+    }
+
+    /// Returns a reference to the inner Journal
+    ///
+    /// # Panics
+    /// Panics if the inner item is not a Journal
+    pub fn unwrap_journal(&self) -> &crate::journal::Journal {
+        match self {
+            Item::Journal(j) => j,
+            _ => panic!("Not a journal"),
+        }
+    }
+
+    /// Returns a reference to the inner Contact
+    ///
+    /// # Panics
+    /// Panics if the inner item is not a Contact
+    pub fn unwrap_contact(&self) -> &crate::contact::Contact {
+        match self {
+            Item::Contact(c) => c,
+            _ => panic!("Not a contact"),
+        }
+    }
+
     #[cfg(any(test, feature = "integration_tests"))]
     pub fn has_same_observable_content_as(&self, other: &Item) -> bool {
         match (self, other) {
             (Item::Event(s), Item::Event(o)) => s.has_same_observable_content_as(o),
             (Item::Task(s),  Item::Task(o))  => s.has_same_observable_content_as(o),
+            (Item::Journal(s), Item::Journal(o)) => s.has_same_observable_content_as(o),
+            (Item::Contact(s), Item::Contact(o)) => s.has_same_observable_content_as(o),
             _ => false,
         }
     }
@@ -89,24 +194,167 @@ impl Item {
 
 
 
+/// A mutable reference to an item of a calendar (see [`CompleteCalendar::get_item_by_url_mut`](crate::traits::CompleteCalendar::get_item_by_url_mut)).
+///
+/// The item is flagged as modified the first time it is mutably dereferenced, so that its calendar only records actual changes
+#[derive(Debug)]
+pub struct ItemMut<'a> {
+    item: &'a mut Item,
+    modified: bool,
+    modified_items: &'a ModifiedItems,
+}
+
+impl<'a> ItemMut<'a> {
+    pub(crate) fn new(item: &'a mut Item, modified_items: &'a ModifiedItems) -> Self {
+        Self { item, modified: false, modified_items }
+    }
+
+    /// Turn this into a plain mutable reference. The item is considered as modified
+    pub fn into_mut(mut self) -> &'a mut Item {
+        self.flag_modified();
+        self.item
+    }
+
+    fn flag_modified(&mut self) {
+        if self.modified == false {
+            self.modified = true;
+            self.modified_items.0.lock().unwrap().insert(self.item.url().clone());
+        }
+    }
+}
+
+impl<'a> Deref for ItemMut<'a> {
+    type Target = Item;
+
+    fn deref(&self) -> &Item {
+        &*self.item
+    }
+}
+
+impl<'a> DerefMut for ItemMut<'a> {
+    fn deref_mut(&mut self) -> &mut Item {
+        self.flag_modified();
+        &mut *self.item
+    }
+}
+
+/// The URLs of the items of a calendar that have been modified through an [`ItemMut`]
+#[derive(Debug, Default)]
+pub(crate) struct ModifiedItems(Mutex<HashSet<Url>>);
+
+impl ModifiedItems {
+    /// Returns (and forgets) the URLs of the items that have been modified
+    pub(crate) fn take(&self) -> HashSet<Url> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+impl Clone for ModifiedItems {
+    fn clone(&self) -> Self {
+        Self(Mutex::new(self.0.lock().unwrap().clone()))
+    }
+}
+
+
+
+
+/// Persistent, globally unique identifier of a calendar component (its iCal `UID`)
+///
+/// As any iCal text value, UIDs are case-sensitive. They are only trimmed from their surrounding whitespace, that some producers leave around.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Uid(String);
+
+impl Uid {
+    /// Build a UID, checking it is not empty
+    pub fn new<S: AsRef<str>>(uid: S) -> Result<Self, Box<dyn Error>> {
+        let uid = uid.as_ref().trim();
+        if uid.is_empty() {
+            return Err("A UID cannot be empty".into());
+        }
+        Ok(Self(uid.to_string()))
+    }
+
+    /// Generate a new random UID
+    pub fn random() -> Self {
+        Self(uuid::Uuid::new_v4().to_hyphenated().to_string())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for Uid {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl AsRef<str> for Uid {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for Uid {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+
+
 /// A VersionTag is basically a CalDAV `ctag` or `etag`. Whenever it changes, this means the data has changed.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+///
+/// Version tags are compared using the "weak comparison" of [RFC 7232](https://datatracker.ietf.org/doc/html/rfc7232#section-2.3.2):
+/// two tags are equal in case their opaque parts match, even if any of them is weak (i.e. prefixed by `W/`).
+/// Some servers also return the very same ETag with or without its surrounding quotes depending on the request, these quotes are ignored as well.
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct VersionTag {
     tag: String
 }
 
+/// ETags are the version tags of items
+pub type ETag = VersionTag;
+
 impl From<String> for VersionTag {
     fn from(tag: String) -> VersionTag {
         Self { tag }
     }
 }
 
+impl PartialEq for VersionTag {
+    fn eq(&self, other: &Self) -> bool {
+        self.opaque_tag() == other.opaque_tag()
+    }
+}
+
+impl Eq for VersionTag {}
+
 impl VersionTag {
     /// Get the inner version tag (usually a WebDAV `ctag` or `etag`)
     pub fn as_str(&self) -> &str {
         &self.tag
     }
 
+    /// Whether this is a weak ETag (i.e. `W/"..."`)
+    pub fn is_weak(&self) -> bool {
+        self.tag.trim().starts_with("W/")
+    }
+
+    /// The opaque part of the tag, without its weakness indicator nor its quotes
+    pub fn opaque_tag(&self) -> &str {
+        let tag = self.tag.trim();
+        let tag = tag.strip_prefix("W/").unwrap_or(tag);
+        tag.trim_matches('"')
+    }
+
+    /// "Strong comparison" of two tags (see [RFC 7232](https://datatracker.ietf.org/doc/html/rfc7232#section-2.3.2)): both must be strong and have the same opaque tag
+    pub fn strong_eq(&self, other: &Self) -> bool {
+        self.is_weak() == false && other.is_weak() == false && self == other
+    }
+
     /// Generate a random VersionTag
     #[cfg(feature = "local_calendar_mocks_remote_calendars")]
     pub fn random() -> Self {
@@ -131,9 +379,91 @@ pub enum SyncStatus {
     LocallyDeleted(VersionTag),
 }
 impl SyncStatus {
+    /// The version tag of the item at the time it was last synced, or `None` if it has never been synced
+    pub fn version_tag(&self) -> Option<&VersionTag> {
+        match self {
+            SyncStatus::NotSynced => None,
+            SyncStatus::Synced(vt) | SyncStatus::LocallyModified(vt) | SyncStatus::LocallyDeleted(vt) => Some(vt),
+        }
+    }
+
     /// Generate a random SyncStatus::Synced
     #[cfg(feature = "local_calendar_mocks_remote_calendars")]
     pub fn random_synced() -> Self {
         Self::Synced(VersionTag::random())
     }
 }
+
+
+
+/// The access classification of an event or a task (iCal `CLASS`), i.e. how much of it its owner is willing to share.
+///
+/// This is only a marker, that servers do not enforce: apps are expected to respect it (e.g. hide the details of confidential items).
+/// Items without any `CLASS` are public
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Classification {
+    Public,
+    Private,
+    Confidential,
+    /// Any other (e.g. `X-`) classification. RFC5545 says they should be treated as private
+    Other(String),
+}
+
+impl Classification {
+    pub fn as_ical_str(&self) -> &str {
+        match self {
+            Classification::Public => "PUBLIC",
+            Classification::Private => "PRIVATE",
+            Classification::Confidential => "CONFIDENTIAL",
+            Classification::Other(s) => s,
+        }
+    }
+
+    /// Whether this is anything but public
+    pub fn is_restricted(&self) -> bool {
+        matches!(self, Classification::Public) == false
+    }
+}
+
+impl From<&str> for Classification {
+    fn from(s: &str) -> Self {
+        match s {
+            "PUBLIC" => Classification::Public,
+            "PRIVATE" => Classification::Private,
+            "CONFIDENTIAL" => Classification::Confidential,
+            other => Classification::Other(other.to_string()),
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_tag_comparison() {
+        let strong = VersionTag::from(String::from("\"0123456789abcdef\""));
+        let weak = VersionTag::from(String::from("W/\"0123456789abcdef\""));
+        let unquoted = VersionTag::from(String::from("0123456789abcdef"));
+        let other = VersionTag::from(String::from("\"fedcba9876543210\""));
+
+        assert_eq!(strong, weak);
+        assert_eq!(strong, unquoted);
+        assert_ne!(strong, other);
+        assert!(weak.is_weak());
+        assert!(strong.strong_eq(&unquoted));
+        assert!(strong.strong_eq(&weak) == false);
+    }
+
+    #[test]
+    fn test_uid() {
+        assert!(Uid::new("").is_err());
+        assert!(Uid::new("   ").is_err());
+        assert_eq!(Uid::new(" some-uid@example.com ").unwrap().as_str(), "some-uid@example.com");
+        // UIDs are case-sensitive
+        assert_ne!(Uid::new("ABC").unwrap(), Uid::new("abc").unwrap());
+        assert_ne!(Uid::random(), Uid::random());
+    }
+}
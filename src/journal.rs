@@ -0,0 +1,203 @@
+//! Journal entries (iCal `VJOURNAL` items), such as notes or diary entries
+
+use chrono::{DateTime, Utc};
+use ical::property::Property;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::item::{SyncStatus, Uid};
+use crate::calendar::CalendarUrl;
+
+/// A journal entry
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Journal {
+    /// The journal URL
+    url: Url,
+
+    /// Persistent, globally unique identifier for the calendar component
+    /// The [RFC](https://tools.ietf.org/html/rfc5545#page-117) recommends concatenating a timestamp with the server's domain name.
+    /// UUID are even better so we'll generate them, but we have to support journals from the server, that may have any arbitrary strings here.
+    uid: Uid,
+
+    /// SUMMARY. This is optional for journals, and empty if it is missing
+    name: String,
+
+    /// DESCRIPTION, i.e. the actual content of the entry
+    description: Option<String>,
+
+    sync_status: SyncStatus,
+
+    /// The PRODID, as defined in iCal files
+    ical_prod_id: String,
+
+    creation_date: Option<DateTime<Utc>>,
+    last_modified: DateTime<Utc>,
+    /// DTSTART, i.e. the date this entry is about (if any)
+    start: Option<DateTime<Utc>>,
+    /// Whether DTSTART is a date rather than a date-time (in which case it is stored at midnight UTC)
+    #[serde(default)]
+    all_day: bool,
+
+    /// Extra parameters that have not been parsed from the iCal file (because they're not supported (yet) by this crate).
+    /// They are needed to serialize this item into an equivalent iCal file
+    extra_parameters: Vec<Property>,
+}
+
+impl Journal {
+    /// Create a brand new journal entry that is not on a server yet.
+    /// This will pick a new (random) ID.
+    pub fn new(name: String, description: Option<String>, parent_calendar_url: &Url) -> Self {
+        let new_url = CalendarUrl::from(parent_calendar_url.clone()).random_item_url();
+        let new_sync_status = SyncStatus::NotSynced;
+        let new_uid = Uid::random();
+        let new_creation_date = Some(Utc::now());
+        let new_last_modified = Utc::now();
+        let ical_prod_id = crate::ical::default_prod_id();
+        let extra_parameters = Vec::new();
+        Self::new_with_parameters(
+            name,
+            new_uid,
+            new_url,
+            description,
+            new_sync_status,
+            None,
+            false,
+            new_creation_date,
+            new_last_modified,
+            ical_prod_id,
+            extra_parameters,
+        )
+    }
+
+    /// Create a new Journal instance, that may be synced on the server already
+    pub fn new_with_parameters(
+        name: String,
+        uid: Uid,
+        url: Url,
+        description: Option<String>,
+        sync_status: SyncStatus,
+        start: Option<DateTime<Utc>>,
+        all_day: bool,
+        creation_date: Option<DateTime<Utc>>,
+        last_modified: DateTime<Utc>,
+        ical_prod_id: String,
+        extra_parameters: Vec<Property>,
+    ) -> Self {
+        Self {
+            url,
+            uid,
+            name,
+            description,
+            sync_status,
+            start,
+            all_day,
+            creation_date,
+            last_modified,
+            ical_prod_id,
+            extra_parameters,
+        }
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+    pub fn uid(&self) -> &Uid {
+        &self.uid
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+    pub fn start(&self) -> Option<&DateTime<Utc>> {
+        self.start.as_ref()
+    }
+    pub fn is_all_day(&self) -> bool {
+        self.all_day
+    }
+    pub fn ical_prod_id(&self) -> &str {
+        &self.ical_prod_id
+    }
+    pub fn sync_status(&self) -> &SyncStatus {
+        &self.sync_status
+    }
+    pub fn last_modified(&self) -> &DateTime<Utc> {
+        &self.last_modified
+    }
+    pub fn creation_date(&self) -> Option<&DateTime<Utc>> {
+        self.creation_date.as_ref()
+    }
+    pub fn extra_parameters(&self) -> &[Property] {
+        &self.extra_parameters
+    }
+
+    #[cfg(any(test, feature = "integration_tests"))]
+    pub fn has_same_observable_content_as(&self, other: &Journal) -> bool {
+        self.url == other.url
+        && self.uid == other.uid
+        && self.name == other.name
+        && self.description == other.description
+        && self.start == other.start
+        && self.all_day == other.all_day
+        // sync status must be the same variant, but we ignore its embedded version tag
+        && std::mem::discriminant(&self.sync_status) == std::mem::discriminant(&other.sync_status)
+        // last modified dates are ignored (they are not totally mocked in integration tests)
+    }
+
+    pub fn set_sync_status(&mut self, new_status: SyncStatus) {
+        self.sync_status = new_status;
+    }
+
+    /// Change the URL of this item, e.g. because it has been moved on the server
+    pub(crate) fn set_url(&mut self, new_url: Url) {
+        self.url = new_url;
+    }
+
+    /// Change the UID of this item, e.g. to make it a distinct copy
+    pub(crate) fn set_uid(&mut self, new_uid: Uid) {
+        self.uid = new_uid;
+    }
+
+    fn update_sync_status(&mut self) {
+        match &self.sync_status {
+            SyncStatus::NotSynced => return,
+            SyncStatus::LocallyModified(_) => return,
+            SyncStatus::Synced(prev_vt) => {
+                self.sync_status = SyncStatus::LocallyModified(prev_vt.clone());
+            }
+            SyncStatus::LocallyDeleted(_) => {
+                log::warn!("Trying to update an item that has previously been deleted. These changes will probably be ignored at next sync.");
+                return;
+            }
+        }
+    }
+
+    pub(crate) fn update_last_modified(&mut self) {
+        self.last_modified = Utc::now();
+    }
+
+    /// Drop what a deleted journal does not need any more (see [`Item::drop_content`](crate::item::Item::drop_content))
+    pub(crate) fn drop_content(&mut self) -> bool {
+        let had_content = self.description.is_some() || self.extra_parameters.is_empty() == false;
+        self.description = None;
+        self.extra_parameters.clear();
+        had_content
+    }
+
+    /// Rename a journal entry.
+    /// This updates its "last modified" field
+    pub fn set_name(&mut self, new_name: String) {
+        self.update_sync_status();
+        self.update_last_modified();
+        self.name = new_name;
+    }
+
+    /// Change the content of a journal entry.
+    /// This updates its "last modified" field
+    pub fn set_description(&mut self, new_description: Option<String>) {
+        self.update_sync_status();
+        self.update_last_modified();
+        self.description = new_description;
+    }
+}
@@ -0,0 +1,222 @@
+//! A local cache for CalDAV data, that is stored in an embedded key-value store ([sled](https://docs.rs/sled)).
+//!
+//! This is only available with the `kv` Cargo feature.
+//!
+//! Every item is stored under its own key, and only the items that have changed are written (see [`crate::persistent_cache`]). \
+//! Changes are written atomically and flushed to disk right away, so that a crash never leaves the cache half-written.
+
+use std::error::Error;
+use std::path::Path;
+
+use url::Url;
+
+use crate::persistent_cache::{PersistentCache, PersistentCalendar};
+use crate::storage::{CacheStorage, CalendarRecord, ItemChange, ItemRecord};
+use crate::migration::{self, CURRENT_FORMAT_VERSION, FORMAT_VERSION_METADATA};
+
+const CALENDARS_TREE: &str = "calendars";
+const ITEMS_TREE: &str = "items";
+const METADATA_TREE: &str = "metadata";
+
+/// A [`PersistentCache`] that is stored in a key-value store
+pub type KvCache = PersistentCache<KvStorage>;
+/// A calendar of a [`KvCache`]
+pub type KvCalendar = PersistentCalendar<KvStorage>;
+
+/// A [`CacheStorage`] that uses a key-value store, with one key per item
+#[derive(Debug)]
+pub struct KvStorage {
+    db: sled::Db,
+    /// Calendars, by URL
+    calendars: sled::Tree,
+    /// Items, by calendar URL and item URL (see [`item_key`])
+    items: sled::Tree,
+    /// Data that is not tied to a calendar, by name
+    metadata: sled::Tree,
+}
+
+impl KvStorage {
+    /// Open (or create) a store in a folder
+    pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Self::from_db(sled::open(path)?)
+    }
+
+    /// Create a store that is deleted when it is dropped. This is mostly useful for tests
+    pub fn temporary() -> Result<Self, Box<dyn Error>> {
+        Self::from_db(sled::Config::new().temporary(true).open()?)
+    }
+
+    fn from_db(db: sled::Db) -> Result<Self, Box<dyn Error>> {
+        let calendars = db.open_tree(CALENDARS_TREE)?;
+        let items = db.open_tree(ITEMS_TREE)?;
+        let metadata = db.open_tree(METADATA_TREE)?;
+        let mut storage = Self { db, calendars, items, metadata };
+        storage.migrate()?;
+        Ok(storage)
+    }
+
+    /// Upgrade the items that have been written by older versions of this crate (see [`crate::migration`])
+    fn migrate(&mut self) -> Result<(), Box<dyn Error>> {
+        let stored_version = self.load_metadata(FORMAT_VERSION_METADATA)?;
+        let format_version = migration::parse_format_version(stored_version.as_deref())?;
+        if stored_version.is_some() && format_version == CURRENT_FORMAT_VERSION {
+            return Ok(());
+        }
+
+        if format_version != CURRENT_FORMAT_VERSION {
+            log::info!("Upgrading the cache from format version {} to {}", format_version, CURRENT_FORMAT_VERSION);
+            let mut batch = sled::Batch::default();
+            for entry in self.items.iter() {
+                let (key, value) = entry?;
+                let mut record: serde_json::Value = serde_json::from_slice(&value)?;
+                // Evicted items do not contain any serialized item
+                if let Some(item) = record.get_mut("Item").and_then(|record| record.get_mut("item")) {
+                    migration::migrate_item(item, format_version)?;
+                    batch.insert(key, serde_json::to_vec(&record)?);
+                }
+            }
+            self.items.apply_batch(batch)?;
+        }
+        self.save_metadata(FORMAT_VERSION_METADATA, CURRENT_FORMAT_VERSION.to_string().as_bytes())
+    }
+}
+
+/// The keys of the items of a calendar all start with this prefix
+fn calendar_prefix(calendar_url: &Url) -> Vec<u8> {
+    // URLs cannot contain line feeds
+    format!("{}\n", calendar_url).into_bytes()
+}
+
+fn item_key(calendar_url: &Url, item_url: &Url) -> Vec<u8> {
+    let mut key = calendar_prefix(calendar_url);
+    key.extend_from_slice(item_url.as_str().as_bytes());
+    key
+}
+
+impl CacheStorage for KvStorage {
+    fn load_calendars(&mut self) -> Result<Vec<CalendarRecord>, Box<dyn Error>> {
+        let mut calendars = Vec::new();
+        for entry in self.calendars.iter() {
+            let (key, value) = entry?;
+            match serde_json::from_slice(&value) {
+                Err(err) => log::error!("Unable to load calendar {} from the cache: {}", String::from_utf8_lossy(&key), err),
+                Ok(calendar) => calendars.push(calendar),
+            }
+        }
+        Ok(calendars)
+    }
+
+    fn load_items(&mut self, calendar_url: &Url) -> Result<Vec<ItemRecord>, Box<dyn Error>> {
+        let mut items = Vec::new();
+        for entry in self.items.scan_prefix(calendar_prefix(calendar_url)) {
+            let (key, value) = entry?;
+            match serde_json::from_slice(&value) {
+                Err(err) => log::error!("Unable to load item {} from the cache: {}", String::from_utf8_lossy(&key), err),
+                Ok(item) => items.push(item),
+            }
+        }
+        Ok(items)
+    }
+
+    fn save_calendar(&mut self, calendar: &CalendarRecord) -> Result<(), Box<dyn Error>> {
+        self.calendars.insert(calendar.url.as_str().as_bytes(), serde_json::to_vec(calendar)?)?;
+        self.db.flush()?;
+        Ok(())
+    }
+
+    fn delete_calendar(&mut self, calendar_url: &Url) -> Result<(), Box<dyn Error>> {
+        // Items are removed first, so that an interrupted deletion never leaves items without their calendar
+        let mut batch = sled::Batch::default();
+        for key in self.items.scan_prefix(calendar_prefix(calendar_url)).keys() {
+            batch.remove(key?);
+        }
+        self.items.apply_batch(batch)?;
+        self.calendars.remove(calendar_url.as_str().as_bytes())?;
+        self.db.flush()?;
+        Ok(())
+    }
+
+    fn save_items(&mut self, calendar_url: &Url, changes: Vec<ItemChange>) -> Result<(), Box<dyn Error>> {
+        let mut batch = sled::Batch::default();
+        for change in changes {
+            let key = item_key(calendar_url, change.url());
+            match change {
+                ItemChange::Saved(record) => batch.insert(key, serde_json::to_vec(&record)?),
+                ItemChange::Removed(_) => batch.remove(key),
+            }
+        }
+        self.items.apply_batch(batch)?;
+        self.db.flush()?;
+        Ok(())
+    }
+
+    fn load_metadata(&mut self, name: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        Ok(self.metadata.get(name.as_bytes())?.map(|data| data.to_vec()))
+    }
+
+    fn save_metadata(&mut self, name: &str, data: &[u8]) -> Result<(), Box<dyn Error>> {
+        self.metadata.insert(name.as_bytes(), data)?;
+        self.db.flush()?;
+        Ok(())
+    }
+}
+
+impl PersistentCache<KvStorage> {
+    /// Open (or create) a cache in a folder
+    pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Self::new(KvStorage::open(path)?)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+    use crate::traits::{BaseCalendar, CalDavSource, CompleteCalendar};
+    use crate::calendar::SupportedComponents;
+    use crate::item::{SyncStatus, VersionTag};
+    use crate::task::Task;
+    use crate::Item;
+
+    #[tokio::test]
+    async fn kv_cache_persistence() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let db_path = PathBuf::from(String::from("test_cache/kv_test"));
+        let _ = std::fs::remove_dir_all(&db_path);
+
+        let cal_url = Url::parse("https://caldav.com/shopping").unwrap();
+        let other_cal_url = Url::parse("https://caldav.com/shopping-2").unwrap();
+        let (kept_url, evicted_url) = {
+            let mut cache = KvCache::open(&db_path).unwrap();
+            let cal = cache.create_calendar(cal_url.clone(), "My shopping list".to_string(), SupportedComponents::TODO, None).await.unwrap();
+            // Its URL starts like the other one, their items must not be mixed up
+            cache.create_calendar(other_cal_url.clone(), "My other shopping list".to_string(), SupportedComponents::TODO, None).await.unwrap();
+            let mut cal = cal.write().unwrap();
+
+            let kept = Task::new(String::from("Milk"), false, &cal_url);
+            let kept_url = kept.url().clone();
+            cal.add_item(Item::Task(kept)).await.unwrap();
+
+            let mut evicted = Task::new(String::from("Eggs"), false, &cal_url);
+            let evicted_url = evicted.url().clone();
+            evicted.set_sync_status(SyncStatus::Synced(VersionTag::from(String::from("etag-1"))));
+            cal.add_item(Item::Task(evicted)).await.unwrap();
+            cal.evict_item(&evicted_url).unwrap();
+            (kept_url, evicted_url)
+        };
+
+        let cache = KvCache::open(&db_path).unwrap();
+        let cal = cache.get_calendar_sync(&cal_url).unwrap();
+        let cal = cal.read().unwrap();
+        assert_eq!(cal.name(), "My shopping list");
+        assert_eq!(cal.item_count(), 1);
+        assert_eq!(cal.get_item_by_url_sync(&kept_url).unwrap().name(), "Milk");
+        assert_eq!(cal.evicted_items().get(&evicted_url), Some(&VersionTag::from(String::from("etag-1"))));
+
+        let other_cal = cache.get_calendar_sync(&other_cal_url).unwrap();
+        assert_eq!(other_cal.read().unwrap().item_count(), 0);
+    }
+}
@@ -7,10 +7,13 @@
 //!
 //! ## Possible uses
 //!
-//! It provides a CalDAV client in the [`client`] module, that can be used as a stand-alone module.
+//! It provides a CalDAV client in the [`client`] module, that can be used as a stand-alone module. \
+//! This client can also sync the CardDAV address books of the same server (see [`Client::set_address_books_enabled`]), whose items are [`Contact`]s.
 //!
 //! Because the connection to the server may be slow, this crate also provides a local cache for CalDAV data in the [`cache`] module.
-//! This way, user-frendly apps are able to quicky display cached data on startup.
+//! This way, user-frendly apps are able to quicky display cached data on startup. \
+//! The [`persistent_cache`] module provides alternative caches that scale better to large calendars, stored in an SQLite database (with the `sqlite` feature), in a key-value store (with the `kv` feature), or as a vdir that other apps can read (see [`vdir_cache`]). \
+//! Caches can also be stored in any other database, by implementing the [`storage::CacheStorage`] trait.
 //!
 //! These two "data sources" (actual client and local cache) can be used together in a [`CalDavProvider`](CalDavProvider). \
 //! A `CalDavProvider` abstracts these two sources by merging them together into one virtual source. \
@@ -26,6 +29,11 @@
 //! ## Configuration options
 //!
 //! Have a look at the [`config`] module to see what default options can be overridden.
+//!
+//! ## Web browsers
+//!
+//! This crate also builds for `wasm32-unknown-unknown`, so that web apps can sync with CalDAV servers directly from the browser. \
+//! Requests are then sent with `fetch` (TLS and proxies are handled by the browser), and [`Cache`]s are kept in memory unless they are given another [`storage::CacheStorage`], since browsers have no file system.
 
 #![doc(html_logo_url = "https://raw.githubusercontent.com/daladim/kitchen-fridge/master/resources/kitchen-fridge.svg")]
 
@@ -38,6 +46,15 @@ pub mod task;
 pub use task::Task;
 pub mod event;
 pub use event::Event;
+pub mod journal;
+pub use journal::Journal;
+pub mod contact;
+pub use contact::Contact;
+pub mod recurrence;
+pub mod date_time;
+pub mod alarm;
+pub mod attendee;
+pub mod attachment;
 pub mod provider;
 pub mod mock_behaviour;
 
@@ -45,12 +62,66 @@ pub mod client;
 pub use client::Client;
 pub mod cache;
 pub use cache::Cache;
+pub mod storage;
+pub mod migration;
+pub mod persistent_cache;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_cache;
+#[cfg(feature = "kv")]
+pub mod kv_cache;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod vdir_cache;
+pub mod changelog;
 pub mod ical;
+pub mod grid;
+pub mod notification;
+pub mod free_busy;
+pub mod scheduling;
+pub mod webcal;
+#[cfg(feature = "google")]
+pub mod google;
+pub mod quota;
+#[cfg(feature = "webhook")]
+pub mod webhook;
+pub mod error;
+pub use error::Error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 
 pub mod config;
 pub mod utils;
 pub mod resource;
+pub mod auth;
+pub mod discovery;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod tls;
+pub mod proxy;
+pub mod transport;
+pub mod retry;
+pub mod rate_limit;
+mod runtime;
+#[cfg(feature = "tracing")]
+mod instrumentation;
 
 /// Unless you want another kind of Provider to write integration tests, you'll probably want this kind of Provider. \
 /// See alse the [`Provider` documentation](crate::provider::Provider)
 pub type CalDavProvider = provider::Provider<cache::Cache, calendar::cached_calendar::CachedCalendar, Client, calendar::remote_calendar::RemoteCalendar>;
+
+/// A [`CalDavProvider`] whose local cache is stored in an SQLite database (see the [`sqlite_cache`] module)
+#[cfg(feature = "sqlite")]
+pub type SqliteCalDavProvider = provider::Provider<sqlite_cache::SqliteCache, sqlite_cache::SqliteCalendar, Client, calendar::remote_calendar::RemoteCalendar>;
+
+/// A [`CalDavProvider`] whose local cache is stored in a key-value store (see the [`kv_cache`] module)
+#[cfg(feature = "kv")]
+pub type KvCalDavProvider = provider::Provider<kv_cache::KvCache, kv_cache::KvCalendar, Client, calendar::remote_calendar::RemoteCalendar>;
+
+/// A provider that syncs read-only iCal feeds into a local cache (see the [`webcal`] module)
+pub type WebcalProvider = provider::Provider<cache::Cache, calendar::cached_calendar::CachedCalendar, webcal::WebcalSource, webcal::WebcalCalendar>;
+
+/// A provider that syncs Google calendars and task lists with their REST APIs, rather than with CalDAV (see the [`google`] module)
+#[cfg(feature = "google")]
+pub type GoogleProvider = provider::Provider<cache::Cache, calendar::cached_calendar::CachedCalendar, google::GoogleSource, google::GoogleCalendar>;
+
+/// A [`CalDavProvider`] whose local cache is a vdir, that other apps (e.g. khal or todoman) can read (see the [`vdir_cache`] module)
+#[cfg(not(target_arch = "wasm32"))]
+pub type VdirCalDavProvider = provider::Provider<vdir_cache::VdirCache, vdir_cache::VdirCalendar, Client, calendar::remote_calendar::RemoteCalendar>;
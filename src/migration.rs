@@ -0,0 +1,108 @@
+//! Upgrades of the data that caches have stored with older versions of this crate.
+//!
+//! Caches store items (see [`crate::Item`]) in their serialized form. Whenever this form changes in a way that older data cannot be read any more
+//! (e.g. a field of [`crate::Task`] is renamed, or changes its type), a migration is added to this module, and [`CURRENT_FORMAT_VERSION`] is bumped accordingly. \
+//! Storages remember the format version their data has been written with. When they are opened, they run the migrations their data needs (see [`migrate_item`]),
+//! so that upgrading this crate does not force users to download their calendars again.
+//!
+//! Additions of fields that have a default value (`#[serde(default)]`) do not need any migration.
+
+use std::error::Error;
+
+use serde_json::Value;
+
+/// A migration rewrites a serialized item from a format version to the next one
+pub type ItemMigration = fn(&mut Value) -> Result<(), Box<dyn Error>>;
+
+/// The migrations of serialized items. \
+/// `ITEM_MIGRATIONS[0]` upgrades items from version 1 (the first format, that was used before versions were stored) to version 2, and so on
+const ITEM_MIGRATIONS: &[ItemMigration] = &[];
+
+/// The version of the format items are serialized with by this version of the crate
+pub const CURRENT_FORMAT_VERSION: u32 = 1 + ITEM_MIGRATIONS.len() as u32;
+
+/// The version of the data that has been stored before format versions were stored
+pub const LEGACY_FORMAT_VERSION: u32 = 1;
+
+/// The name of the metadata (see [`crate::storage::CacheStorage::load_metadata`]) that stores the format version of a storage, for storages that have no better place for it
+pub(crate) const FORMAT_VERSION_METADATA: &str = "format_version";
+
+/// Read the format version that has been stored as metadata (see [`FORMAT_VERSION_METADATA`])
+pub(crate) fn parse_format_version(metadata: Option<&[u8]>) -> Result<u32, Box<dyn Error>> {
+    let version = match metadata {
+        None => LEGACY_FORMAT_VERSION,
+        Some(data) => std::str::from_utf8(data)?.parse()?,
+    };
+    check_format_version(version)?;
+    Ok(version)
+}
+
+/// Returns an error in case data with this format version cannot be read by this version of the crate
+pub fn check_format_version(version: u32) -> Result<(), Box<dyn Error>> {
+    if version > CURRENT_FORMAT_VERSION {
+        return Err(format!("This cache has been written by a newer version of kitchen-fridge (format version {}, only versions up to {} are supported)", version, CURRENT_FORMAT_VERSION).into());
+    }
+    if version < LEGACY_FORMAT_VERSION {
+        return Err(format!("Invalid cache format version {}", version).into());
+    }
+    Ok(())
+}
+
+/// Upgrade a serialized item, that has been written with the format `from_version`, to the current format
+pub fn migrate_item(item: &mut Value, from_version: u32) -> Result<(), Box<dyn Error>> {
+    apply_migrations(item, from_version, ITEM_MIGRATIONS)
+}
+
+fn apply_migrations(item: &mut Value, from_version: u32, migrations: &[ItemMigration]) -> Result<(), Box<dyn Error>> {
+    if from_version < LEGACY_FORMAT_VERSION || from_version > LEGACY_FORMAT_VERSION + migrations.len() as u32 {
+        return Err(format!("Unable to migrate an item from format version {}", from_version).into());
+    }
+    let first_migration = (from_version - LEGACY_FORMAT_VERSION) as usize;
+    for migration in &migrations[first_migration..] {
+        migration(item)?;
+    }
+    Ok(())
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rename_name_to_summary(item: &mut Value) -> Result<(), Box<dyn Error>> {
+        let task = item.get_mut("Task").and_then(|task| task.as_object_mut()).ok_or("not a task")?;
+        if let Some(name) = task.remove("name") {
+            task.insert("summary".to_string(), name);
+        }
+        Ok(())
+    }
+
+    fn add_priority(item: &mut Value) -> Result<(), Box<dyn Error>> {
+        let task = item.get_mut("Task").and_then(|task| task.as_object_mut()).ok_or("not a task")?;
+        task.insert("priority".to_string(), Value::from(0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrations() {
+        let migrations: &[ItemMigration] = &[rename_name_to_summary, add_priority];
+
+        let mut item: Value = serde_json::from_str(r#"{"Task": {"name": "Milk"}}"#).unwrap();
+        apply_migrations(&mut item, 1, migrations).unwrap();
+        assert_eq!(item, serde_json::from_str::<Value>(r#"{"Task": {"summary": "Milk", "priority": 0}}"#).unwrap());
+
+        // Only the missing migrations are applied
+        let mut item: Value = serde_json::from_str(r#"{"Task": {"summary": "Eggs"}}"#).unwrap();
+        apply_migrations(&mut item, 2, migrations).unwrap();
+        assert_eq!(item, serde_json::from_str::<Value>(r#"{"Task": {"summary": "Eggs", "priority": 0}}"#).unwrap());
+
+        let mut item: Value = serde_json::from_str(r#"{"Task": {"summary": "Bread", "priority": 1}}"#).unwrap();
+        apply_migrations(&mut item, 3, migrations).unwrap();
+        assert_eq!(item, serde_json::from_str::<Value>(r#"{"Task": {"summary": "Bread", "priority": 1}}"#).unwrap());
+
+        assert!(apply_migrations(&mut item, 4, migrations).is_err());
+        assert!(check_format_version(CURRENT_FORMAT_VERSION).is_ok());
+        assert!(check_format_version(CURRENT_FORMAT_VERSION + 1).is_err());
+    }
+}
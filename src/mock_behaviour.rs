@@ -24,6 +24,10 @@ pub struct MockBehaviour {
     pub get_item_version_tags_behaviour: (u32, u32),
     pub get_item_by_url_behaviour: (u32, u32),
     pub delete_item_behaviour: (u32, u32),
+
+    /// Whether mocked calendars support sync tokens (just like servers that support `sync-collection` REPORTs). \
+    /// This is not affected by [`Self::suspend`]
+    pub supports_sync_tokens: bool,
 }
 
 impl MockBehaviour {
@@ -43,6 +47,7 @@ impl MockBehaviour {
             get_item_version_tags_behaviour: (0, n_fails),
             get_item_by_url_behaviour: (0, n_fails),
             delete_item_behaviour: (0, n_fails),
+            supports_sync_tokens: false,
         }
     }
 
@@ -0,0 +1,316 @@
+//! Notification-ready payloads for the reminders of events and tasks
+//!
+//! Daemons that watch calendars can map these payloads straight to desktop or mobile notification APIs
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use chrono_tz::Tz;
+use url::Url;
+
+use crate::{Event, Item, Task};
+use crate::alarm::{checked_seconds, Alarm, AlarmAction, AlarmRepeat};
+use crate::item::SyncStatus;
+
+/// Alarms are notified at most this many times after their first trigger, whatever their `REPEAT` says
+const MAX_REPETITIONS: u32 = 1000;
+
+/// What the user can do from a notification
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NotificationAction {
+    /// Mark the task as completed (this is only offered for tasks)
+    Complete,
+    /// Show the notification again later (see [`Notification::snoozed`])
+    Snooze,
+    /// Dismiss the notification
+    Dismiss,
+}
+
+impl NotificationAction {
+    /// A stable identifier, that notification APIs can send back when the user clicks the action
+    pub fn id(&self) -> &'static str {
+        match self {
+            NotificationAction::Complete => "complete",
+            NotificationAction::Snooze => "snooze",
+            NotificationAction::Dismiss => "dismiss",
+        }
+    }
+
+    /// The action matching an identifier returned by [`Self::id`]
+    pub fn from_id(id: &str) -> Option<Self> {
+        match id {
+            "complete" => Some(NotificationAction::Complete),
+            "snooze" => Some(NotificationAction::Snooze),
+            "dismiss" => Some(NotificationAction::Dismiss),
+            _ => None,
+        }
+    }
+}
+
+/// A reminder that is about to be triggered
+#[derive(Clone, Debug, PartialEq)]
+pub struct Notification {
+    item_url: Url,
+    title: String,
+    body: String,
+    fire_time: DateTime<Utc>,
+    /// Whether the alarm is an `AUDIO` alarm
+    play_sound: bool,
+    actions: Vec<NotificationAction>,
+}
+
+impl Notification {
+    pub fn item_url(&self) -> &Url { &self.item_url }
+    pub fn title(&self) -> &str { &self.title }
+    pub fn body(&self) -> &str { &self.body }
+    pub fn fire_time(&self) -> &DateTime<Utc> { &self.fire_time }
+    pub fn play_sound(&self) -> bool { self.play_sound }
+    pub fn actions(&self) -> &[NotificationAction] { &self.actions }
+
+    /// A stable identifier of this notification, so that notification APIs can replace or withdraw it
+    pub fn id(&self) -> String {
+        format!("{}#{}", self.item_url, self.fire_time.timestamp())
+    }
+
+    /// The same notification, to be shown again some time after `now`
+    pub fn snoozed(&self, now: DateTime<Utc>, delay: Duration) -> Self {
+        Self { fire_time: now + delay, ..self.clone() }
+    }
+}
+
+/// The notifications of some items that fire between `from` (included) and `until` (excluded), sorted by fire time.
+///
+/// Only `DISPLAY` and `AUDIO` alarms are notified. Completed tasks, cancelled events and items that are marked for deletion are skipped.
+/// Dates in the notification bodies are written in `timezone`
+pub fn notifications_between<'a, I>(items: I, from: DateTime<Utc>, until: DateTime<Utc>, timezone: &Tz) -> Vec<Notification>
+where
+    I: IntoIterator<Item = &'a Item>
+{
+    let mut notifications = Vec::new();
+    for item in items {
+        if let SyncStatus::LocallyDeleted(_) = item.sync_status() {
+            continue;
+        }
+        match item {
+            Item::Event(event) => {
+                add_event_notifications(&mut notifications, event, from, until, timezone);
+                for instance in event.overrides() {
+                    add_event_notifications(&mut notifications, instance, from, until, timezone);
+                }
+            },
+            Item::Task(task) => add_task_notifications(&mut notifications, task, from, until, timezone),
+            Item::Journal(_) | Item::Contact(_) => (),
+        }
+    }
+    notifications.sort_by(|a, b| a.fire_time.cmp(&b.fire_time));
+    notifications
+}
+
+fn add_event_notifications(notifications: &mut Vec<Notification>, event: &Event, from: DateTime<Utc>, until: DateTime<Utc>, timezone: &Tz) {
+    if event.is_cancelled() {
+        return;
+    }
+    // Alarms may fire way before (or after) their occurrences.
+    // Alarms whose offsets are out of range (e.g. because of a malformed iCal file) are skipped
+    let mut alarms = Vec::new();
+    let mut margin = Duration::zero();
+    for alarm in event.alarms().iter().filter(|a| is_notified(a)) {
+        let offset = match max_offset(alarm) {
+            Some(offset) if from.checked_sub_signed(offset).is_some() && until.checked_add_signed(offset).is_some() => offset,
+            _ => {
+                log::warn!("Skipping an alarm of {} that is out of range", event.url());
+                continue;
+            },
+        };
+        margin = margin.max(offset);
+        alarms.push(alarm);
+    }
+    if alarms.is_empty() {
+        return;
+    }
+
+    for occurrence in event.occurrences_between(from - margin, until + margin) {
+        let body = match event.is_all_day() {
+            true => format!("{} (all day)", occurrence.start().format("%A %e %B")),
+            false => format!("{} - {}",
+                timezone.from_utc_datetime(&occurrence.start().naive_utc()).format("%A %e %B, %H:%M"),
+                timezone.from_utc_datetime(&occurrence.end().naive_utc()).format("%H:%M")),
+        };
+        let body = match event.location() {
+            Some(location) => format!("{}\n{}", body, location),
+            None => body,
+        };
+        for alarm in &alarms {
+            for fire_time in fire_times(alarm, Some(occurrence.start()), Some(occurrence.end())) {
+                if fire_time >= from && fire_time < until {
+                    notifications.push(Notification {
+                        item_url: event.url().clone(),
+                        title: event.name().to_string(),
+                        body: with_alarm_description(&body, alarm, event.name()),
+                        fire_time,
+                        play_sound: alarm.action() == &AlarmAction::Audio,
+                        actions: vec![NotificationAction::Snooze, NotificationAction::Dismiss],
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn add_task_notifications(notifications: &mut Vec<Notification>, task: &Task, from: DateTime<Utc>, until: DateTime<Utc>, timezone: &Tz) {
+    if task.completed() {
+        return;
+    }
+    let body = match (task.due(), task.is_due_all_day()) {
+        (None, _) => String::new(),
+        (Some(due), true) => format!("Due {}", due.format("%A %e %B")),
+        (Some(due), false) => format!("Due {}", timezone.from_utc_datetime(&due.naive_utc()).format("%A %e %B, %H:%M")),
+    };
+    for alarm in task.alarms().iter().filter(|a| is_notified(a)) {
+        // DTSTART of tasks is not supported yet, alarms can only be relative to their DUE date
+        for fire_time in fire_times(alarm, None, task.due()) {
+            if fire_time >= from && fire_time < until {
+                notifications.push(Notification {
+                    item_url: task.url().clone(),
+                    title: task.name().to_string(),
+                    body: with_alarm_description(&body, alarm, task.name()),
+                    fire_time,
+                    play_sound: alarm.action() == &AlarmAction::Audio,
+                    actions: vec![NotificationAction::Complete, NotificationAction::Snooze, NotificationAction::Dismiss],
+                });
+            }
+        }
+    }
+}
+
+fn is_notified(alarm: &Alarm) -> bool {
+    matches!(alarm.action(), AlarmAction::Display | AlarmAction::Audio)
+}
+
+/// The first trigger of an alarm, then its repetitions (at most [`MAX_REPETITIONS`] of them). \
+/// This is empty when the alarm cannot be triggered, or when some of its fire times are out of range
+fn fire_times(alarm: &Alarm, item_start: Option<&DateTime<Utc>>, item_end: Option<&DateTime<Utc>>) -> Vec<DateTime<Utc>> {
+    let first = match alarm.first_trigger_date(item_start, item_end) {
+        None => return Vec::new(),
+        Some(date) => date,
+    };
+    let mut times = vec![first];
+    if let Some(repeat) = alarm.repeat() {
+        for i in 1..=repeat.count.min(MAX_REPETITIONS) {
+            match repetition_offset(repeat, i).and_then(|offset| first.checked_add_signed(offset)) {
+                Some(time) => times.push(time),
+                None => return Vec::new(),
+            }
+        }
+    }
+    times
+}
+
+/// How long after the first trigger the `i`-th repetition of an alarm fires, or `None` if this is out of range
+fn repetition_offset(repeat: &AlarmRepeat, i: u32) -> Option<Duration> {
+    checked_seconds(repeat.interval_seconds.checked_mul(i64::from(i))?)
+}
+
+/// How far from its occurrence an alarm can fire (including its repetitions), or `None` if this is out of range
+fn max_offset(alarm: &Alarm) -> Option<Duration> {
+    let offset = match alarm.trigger() {
+        crate::alarm::AlarmTrigger::Relative { offset_seconds, .. } => checked_seconds(offset_seconds.checked_abs()?)?,
+        crate::alarm::AlarmTrigger::Absolute(_) | crate::alarm::AlarmTrigger::Unparsed(_) => Duration::zero(),
+    };
+    let repetitions = match alarm.repeat() {
+        None => Duration::zero(),
+        Some(repeat) => checked_seconds(repeat.interval_seconds.checked_abs()?.checked_mul(i64::from(repeat.count.min(MAX_REPETITIONS)))?)?,
+    };
+    offset.checked_add(&repetitions)
+}
+
+/// Clients often set the alarm description to the item name (or to a placeholder), it is only worth showing when it adds something
+fn with_alarm_description(body: &str, alarm: &Alarm, item_name: &str) -> String {
+    match alarm.description() {
+        Some(desc) if desc.is_empty() == false && desc != item_name && desc != "Default Mozilla Description" => {
+            match body.is_empty() {
+                true => desc.to_string(),
+                false => format!("{}\n{}", body, desc),
+            }
+        },
+        _ => body.to_string(),
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alarm::{AlarmRepeat, AlarmTrigger};
+
+    #[test]
+    fn test_event_notifications() {
+        let cal_url: Url = "https://some.calend.ar/calendar/".parse().unwrap();
+        let tz: Tz = "Europe/Paris".parse().unwrap();
+        let start = Utc.ymd(2021, 3, 1).and_hms(9, 0, 0);
+        let mut event = Event::new("Standup".to_string(), start, start + Duration::minutes(15), &cal_url);
+        event.set_location(Some("Room 42".to_string()));
+        let mut alarm = Alarm::new(AlarmAction::Display, AlarmTrigger::before_start(Duration::minutes(10)));
+        alarm.set_repeat(Some(AlarmRepeat { count: 1, interval_seconds: 300 }));
+        event.set_alarms(vec![
+            alarm,
+            Alarm::new(AlarmAction::Email, AlarmTrigger::before_start(Duration::hours(1))),
+        ]);
+        event.set_recurrence(Some("FREQ=DAILY;COUNT=3".parse().unwrap()));
+        let items = vec![Item::Event(event)];
+
+        // The first occurrence fires at 8:50 and 8:55, the second one at 8:50 the next day
+        let notifications = notifications_between(&items, Utc.ymd(2021, 3, 1).and_hms(8, 52, 0), Utc.ymd(2021, 3, 2).and_hms(8, 51, 0), &tz);
+        let fire_times: Vec<DateTime<Utc>> = notifications.iter().map(|n| *n.fire_time()).collect();
+        assert_eq!(fire_times, vec![Utc.ymd(2021, 3, 1).and_hms(8, 55, 0), Utc.ymd(2021, 3, 2).and_hms(8, 50, 0)]);
+        assert_eq!(notifications[0].title(), "Standup");
+        assert_eq!(notifications[0].body(), "Monday  1 March, 10:00 - 10:15\nRoom 42");
+        assert_eq!(notifications[0].actions(), &[NotificationAction::Snooze, NotificationAction::Dismiss]);
+        assert_ne!(notifications[0].id(), notifications[1].id());
+
+        let snoozed = notifications[0].snoozed(*notifications[0].fire_time(), Duration::minutes(5));
+        assert_eq!(*snoozed.fire_time(), Utc.ymd(2021, 3, 1).and_hms(9, 0, 0));
+    }
+
+    #[test]
+    fn test_task_notifications() {
+        let cal_url: Url = "https://some.calend.ar/calendar/".parse().unwrap();
+        let due = Utc.ymd(2021, 3, 1).and_hms(17, 0, 0);
+        let mut task = Task::new("Pay the rent".to_string(), false, &cal_url);
+        task.set_due(Some(due), false);
+        let mut alarm = Alarm::new(AlarmAction::Audio, AlarmTrigger::Relative { offset_seconds: -3600, related_to_end: true });
+        alarm.set_description(Some("Before the bank closes".to_string()));
+        task.set_alarms(vec![alarm]);
+        let mut items = vec![Item::Task(task)];
+
+        let notifications = notifications_between(&items, due - Duration::days(1), due, &chrono_tz::UTC);
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(*notifications[0].fire_time(), due - Duration::hours(1));
+        assert_eq!(notifications[0].body(), "Due Monday  1 March, 17:00\nBefore the bank closes");
+        assert!(notifications[0].play_sound());
+        assert_eq!(NotificationAction::from_id(notifications[0].actions()[0].id()), Some(NotificationAction::Complete));
+
+        items[0].unwrap_task_mut().set_completion_status(crate::task::CompletionStatus::Completed(None));
+        assert!(notifications_between(&items, due - Duration::days(1), due, &chrono_tz::UTC).is_empty());
+    }
+
+    #[test]
+    fn test_out_of_range_alarms() {
+        let cal_url: Url = "https://some.calend.ar/calendar/".parse().unwrap();
+        let start = Utc.ymd(2021, 3, 1).and_hms(9, 0, 0);
+        let mut event = Event::new("Standup".to_string(), start, start + Duration::minutes(15), &cal_url);
+        let mut flood = Alarm::new(AlarmAction::Display, AlarmTrigger::before_start(Duration::minutes(10)));
+        flood.set_repeat(Some(AlarmRepeat { count: u32::MAX, interval_seconds: 1 }));
+        let mut overflowing = Alarm::new(AlarmAction::Display, AlarmTrigger::before_start(Duration::minutes(5)));
+        overflowing.set_repeat(Some(AlarmRepeat { count: 3, interval_seconds: i64::MAX / 2 }));
+        event.set_alarms(vec![
+            flood,
+            overflowing,
+            Alarm::new(AlarmAction::Display, AlarmTrigger::Relative { offset_seconds: i64::MIN, related_to_end: false }),
+        ]);
+        let items = vec![Item::Event(event)];
+
+        let notifications = notifications_between(&items, start - Duration::hours(1), start + Duration::hours(1), &chrono_tz::UTC);
+        assert_eq!(notifications.len(), 1 + MAX_REPETITIONS as usize);
+        assert_eq!(*notifications.last().unwrap().fire_time(), start - Duration::minutes(10) + Duration::seconds(MAX_REPETITIONS as i64));
+    }
+}
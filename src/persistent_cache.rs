@@ -0,0 +1,771 @@
+//! Local caches for CalDAV data, that write every change to a [`CacheStorage`](crate::storage::CacheStorage) as soon as it happens.
+//!
+//! Unlike [`crate::cache::Cache`], that rewrites every calendar whenever it is saved, these caches only write the items that have changed.
+//! This makes them better suited for large calendars (e.g. tens of thousands of items). \
+//! Storages are provided by Cargo features: an SQLite database (see [`crate::sqlite_cache`], with the `sqlite` feature) or an embedded key-value store (see [`crate::kv_cache`], with the `kv` feature).
+//!
+//! The items of a calendar are loaded from the storage the first time this calendar is used (e.g. with [`CalDavSource::get_calendar`]), and are then kept in memory (so that calendars can lend references to them, see [`CompleteCalendar`]).
+//! The storage is kept up to date as soon as they are added, updated or deleted. \
+//! Lookups that do not need the items of every calendar (e.g. [`PersistentCache::find_item_by_uid`]) are done by the storage. Apps that need even less memory can evict the items they do not use (see [`CompleteCalendar::evict_item`]):
+//! their content stays in the storage, and is read from it again when they are needed (they are still listed, queried, etc.).
+//! Items that are modified in place (e.g. with [`CompleteCalendar::get_item_by_url_mut`]) are written at the next change of their calendar, when their calendar is dropped, or when calling [`PersistentCache::flush`].
+//!
+//! Just like [`crate::cache::Cache`]s, these caches record the changes made to their items (see [`PersistentCache::changes_since`]), and can tell callbacks about them (see [`PersistentCache::on_item_changed`]). \
+//! Since calendars are loaded lazily, this only covers the calendars that have been loaded: loading one does not count as a change.
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::sync::{Arc, Mutex, RwLock};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use csscolorparser::Color;
+use once_cell::sync::OnceCell;
+use url::Url;
+
+use crate::traits::{BaseCalendar, CalDavSource, CompleteCalendar};
+use crate::calendar::cached_calendar::CachedCalendar;
+use crate::calendar::{CalendarProperties, ItemQuery, Privileges, SupportedComponents};
+use crate::item::{ItemMut, SyncStatus, VersionTag};
+use crate::error::{Rejection, ServerError};
+use crate::changelog::{self, ChangeKind, ChangeLog, ChangeRecord, SharedChangeLog};
+use crate::storage::{CacheStorage, CalendarRecord, ItemChange, ItemRecord};
+use crate::provider::calendar_selection::CalendarSelection;
+use crate::Item;
+
+/// The name of the metadata that stores the calendars that are to be deleted from the server
+const DELETED_CALENDARS_METADATA: &str = "deleted_calendars";
+const PENDING_MOVES_METADATA: &str = "pending_moves";
+const CALENDAR_SELECTION_METADATA: &str = "calendar_selection";
+const CHANGELOG_METADATA: &str = "changelog";
+
+/// A CalDAV source that writes its changes to a [`CacheStorage`] (see the [module documentation](crate::persistent_cache))
+#[derive(Debug)]
+pub struct PersistentCache<S: CacheStorage> {
+    storage: Arc<Mutex<S>>,
+    calendars: HashMap<Url, LazyCalendar<S>>,
+    /// Calendars that have been deleted, and whose deletion has not been pushed to the server yet
+    deleted_calendars: HashSet<Url>,
+    pending_moves: HashMap<Url, Url>,
+    /// The calendars that are synced (see [`Provider::set_calendar_selection`](crate::provider::Provider::set_calendar_selection))
+    calendar_selection: CalendarSelection,
+    /// Where the changes to the items are recorded (this is shared with the calendars, as soon as they are loaded)
+    change_log: SharedChangeLog,
+}
+
+/// A calendar of a [`PersistentCache`], whose items are only loaded the first time it is used
+#[derive(Debug)]
+struct LazyCalendar<S: CacheStorage> {
+    /// The calendar as it was stored when the cache was opened
+    record: CalendarRecord,
+    loaded: Mutex<Option<Arc<RwLock<PersistentCalendar<S>>>>>,
+}
+
+impl<S: CacheStorage> LazyCalendar<S> {
+    fn new(record: CalendarRecord) -> Self {
+        Self { record, loaded: Mutex::new(None) }
+    }
+
+    fn loaded(calendar: Arc<RwLock<PersistentCalendar<S>>>) -> Self {
+        let record = calendar.read().unwrap().calendar.record();
+        Self { record, loaded: Mutex::new(Some(calendar)) }
+    }
+
+    /// The calendar, whose items are loaded from `storage` in case this has not been done yet. Its changes are then recorded in `change_log`
+    fn get(&self, storage: &Arc<Mutex<S>>, change_log: &SharedChangeLog) -> Result<Arc<RwLock<PersistentCalendar<S>>>, Box<dyn Error>> {
+        let mut loaded = self.loaded.lock().unwrap();
+        if let Some(calendar) = &*loaded {
+            return Ok(calendar.clone());
+        }
+        let items = storage.lock().unwrap().load_items(&self.record.url)?;
+        let mut cached = CachedCalendar::from_records(self.record.clone(), items);
+        cached.set_change_log(Some(change_log.clone()));
+        let calendar = Arc::new(RwLock::new(PersistentCalendar {
+            calendar: cached,
+            storage: Some(storage.clone()),
+            dirty: HashSet::new(),
+            unloaded: HashSet::new(),
+            reloaded: OnceCell::new(),
+        }));
+        *loaded = Some(calendar.clone());
+        Ok(calendar)
+    }
+
+    /// The calendar, in case its items have been loaded already
+    fn get_if_loaded(&self) -> Option<Arc<RwLock<PersistentCalendar<S>>>> {
+        self.loaded.lock().unwrap().clone()
+    }
+}
+
+impl<S: CacheStorage> PersistentCache<S> {
+    /// Open a cache from a storage. Only its calendars are loaded, their items are loaded the first time they are used
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "cache_load", skip_all, err))]
+    pub fn new(mut storage: S) -> Result<Self, Box<dyn Error>> {
+        let calendars = storage.load_calendars()?.into_iter()
+            .map(|record| (record.url.clone(), LazyCalendar::new(record)))
+            .collect();
+        let deleted_calendars = match storage.load_metadata(DELETED_CALENDARS_METADATA)? {
+            None => HashSet::new(),
+            Some(data) => serde_json::from_slice(&data)?,
+        };
+        let pending_moves = match storage.load_metadata(PENDING_MOVES_METADATA)? {
+            None => HashMap::new(),
+            Some(data) => serde_json::from_slice(&data)?,
+        };
+        let calendar_selection = match storage.load_metadata(CALENDAR_SELECTION_METADATA)? {
+            None => CalendarSelection::All,
+            Some(data) => serde_json::from_slice(&data)?,
+        };
+
+        let change_log = Arc::new(Mutex::new(ChangeLog::load(&mut storage, CHANGELOG_METADATA)));
+
+        let storage = Arc::new(Mutex::new(storage));
+        Ok(Self { storage, calendars, deleted_calendars, pending_moves, calendar_selection, change_log })
+    }
+
+    fn write_deleted_calendars(&self) -> Result<(), Box<dyn Error>> {
+        self.storage.lock().unwrap().save_metadata(DELETED_CALENDARS_METADATA, &serde_json::to_vec(&self.deleted_calendars)?)
+    }
+
+    fn write_pending_moves(&self) -> Result<(), Box<dyn Error>> {
+        self.storage.lock().unwrap().save_metadata(PENDING_MOVES_METADATA, &serde_json::to_vec(&self.pending_moves)?)
+    }
+
+    /// The non-async version of [`crate::traits::CalDavSource::delete_calendar`]
+    pub fn delete_calendar_sync(&mut self, url: &Url) -> Result<(), Box<dyn Error>> {
+        let calendar = self.calendars.remove(url)
+            .ok_or_else(|| format!("Calendar {} does not exist", url))?;
+        // Apps that read the changelog must forget about its items, that have to be loaded to tell which they are
+        let item_urls = match calendar.get(&self.storage, &self.change_log) {
+            Err(err) => {
+                log::warn!("Unable to load the items of deleted calendar {}: {}", url, err);
+                HashSet::new()
+            },
+            Ok(calendar) => {
+                let mut calendar = calendar.write().unwrap();
+                // Make sure its pending changes are not written once it is deleted
+                calendar.storage = None;
+                let mut item_urls = calendar.calendar.get_item_urls_sync()?;
+                item_urls.extend(calendar.unloaded.iter().cloned());
+                item_urls
+            },
+        };
+        for item_url in &item_urls {
+            changelog::record_change(&self.change_log, url, item_url, ChangeKind::Deleted);
+        }
+        changelog::record_calendar_change(&self.change_log, url);
+
+        self.storage.lock().unwrap().delete_calendar(url)?;
+        self.deleted_calendars.insert(url.clone());
+        self.write_deleted_calendars()
+    }
+
+    /// The storage of this cache
+    pub fn storage(&self) -> &Mutex<S> {
+        &self.storage
+    }
+
+    /// Write every item that has been modified in place since it was last written, as well as the changelog
+    ///
+    /// Note that this is automatically called when `self` is `drop`ped
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "cache_flush", skip_all, err))]
+    pub fn flush(&self) -> Result<(), Box<dyn Error>> {
+        self.flush_items()?;
+        self.record_lent_changes();
+        let change_log = serde_json::to_vec(&*self.change_log.lock().unwrap())?;
+        self.storage.lock().unwrap().save_metadata(CHANGELOG_METADATA, &change_log)
+    }
+
+    fn flush_items(&self) -> Result<(), Box<dyn Error>> {
+        for cal in self.calendars.values().filter_map(LazyCalendar::get_if_loaded) {
+            cal.write().unwrap().flush()?;
+        }
+        Ok(())
+    }
+
+    /// Record the items that have been modified through mutable references since their calendar was last used (calendars that are currently locked are skipped)
+    fn record_lent_changes(&self) {
+        for cal in self.calendars.values().filter_map(LazyCalendar::get_if_loaded) {
+            if let Ok(cal) = cal.try_read() {
+                cal.calendar.record_lent_changes();
+            }
+        }
+    }
+
+    /// The sequence number of the latest change made to the items of this cache. See [`crate::cache::Cache::current_change_seq`]
+    pub fn current_change_seq(&self) -> u64 {
+        self.record_lent_changes();
+        self.change_log.lock().unwrap().current_seq()
+    }
+
+    /// The changes made to the items of this cache after the change numbered `seq`. See [`ChangeLog::changes_since`]
+    pub fn changes_since(&self, seq: u64) -> Option<Vec<ChangeRecord>> {
+        self.record_lent_changes();
+        self.change_log.lock().unwrap().changes_since(seq)
+    }
+
+    /// Register a callback that is called whenever an item is added or modified, be it locally or by a sync. See [`crate::cache::Cache::on_item_changed`]
+    pub fn on_item_changed<F: Fn(&ChangeRecord) + Send + Sync + 'static>(&self, callback: F) {
+        self.change_log.lock().unwrap().add_item_observer(ChangeKind::Updated, Arc::new(callback));
+    }
+
+    /// Register a callback that is called whenever an item is deleted (or marked for deletion), be it locally or by a sync. See [`crate::cache::Cache::on_item_changed`]
+    pub fn on_item_deleted<F: Fn(&ChangeRecord) + Send + Sync + 'static>(&self, callback: F) {
+        self.change_log.lock().unwrap().add_item_observer(ChangeKind::Deleted, Arc::new(callback));
+    }
+
+    /// Register a callback that is called with the URL of a calendar whenever it is created, deleted, or whenever its properties change. See [`crate::cache::Cache::on_item_changed`]
+    pub fn on_calendar_changed<F: Fn(&Url) + Send + Sync + 'static>(&self, callback: F) {
+        self.change_log.lock().unwrap().add_calendar_observer(Arc::new(callback));
+    }
+
+    /// Drop the content of the items that have been marked for deletion more than `retention` ago. See [`crate::cache::Cache::compact`]
+    pub fn compact(&self, retention: chrono::Duration) -> Result<usize, Box<dyn Error>> {
+        let deleted_before = Utc::now() - retention;
+        let mut compacted = 0;
+        for cal in self.get_calendars_sync()?.values() {
+            compacted += cal.write().unwrap().compact_tombstones_sync(&deleted_before)?;
+        }
+        Ok(compacted)
+    }
+
+    /// Find an item by its UID, using the storage (see [`CacheStorage::find_item_by_uid`]), so that calendars do not have to be loaded. \
+    /// Returns the URL of its calendar and its own URL.
+    pub fn find_item_by_uid(&self, uid: &str) -> Result<Option<(Url, Url)>, Box<dyn Error>> {
+        // Items that have been modified in place are written first, since their UID may have changed
+        self.flush_items()?;
+        self.storage.lock().unwrap().find_item_by_uid(uid)
+    }
+
+    /// The non-async version of [`crate::traits::CalDavSource::get_calendars`]. This loads the items of every calendar
+    pub fn get_calendars_sync(&self) -> Result<HashMap<Url, Arc<RwLock<PersistentCalendar<S>>>>, Box<dyn Error>> {
+        self.calendars.iter()
+            .map(|(url, cal)| Ok((url.clone(), cal.get(&self.storage, &self.change_log)?)))
+            .collect()
+    }
+
+    /// The non-async version of [`crate::traits::CalDavSource::get_calendar`]
+    pub fn get_calendar_sync(&self, url: &Url) -> Option<Arc<RwLock<PersistentCalendar<S>>>> {
+        match self.calendars.get(url)?.get(&self.storage, &self.change_log) {
+            Err(err) => {
+                log::error!("Unable to load the items of calendar {}: {}", url, err);
+                None
+            },
+            Ok(cal) => Some(cal),
+        }
+    }
+}
+
+impl<S: CacheStorage> Drop for PersistentCache<S> {
+    fn drop(&mut self) {
+        if let Err(err) = self.flush() {
+            log::error!("Unable to automatically flush the cache when it's no longer required: {}", err);
+        }
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl<S: CacheStorage> CalDavSource<PersistentCalendar<S>> for PersistentCache<S> {
+    async fn get_calendars(&self) -> Result<HashMap<Url, Arc<RwLock<PersistentCalendar<S>>>>, crate::Error> {
+        Ok(self.get_calendars_sync()?)
+    }
+
+    async fn get_calendar(&self, url: &Url) -> Option<Arc<RwLock<PersistentCalendar<S>>>> {
+        self.get_calendar_sync(url)
+    }
+
+    async fn create_calendar(&mut self, url: Url, name: String, supported_components: SupportedComponents, color: Option<Color>) -> Result<Arc<RwLock<PersistentCalendar<S>>>, crate::Error> {
+        log::debug!("Inserting local calendar {}", url);
+        if self.calendars.contains_key(&url) {
+            return Err("Attempt to insert calendar failed: there is alredy such a calendar.".into());
+        }
+
+        let mut cached: CachedCalendar = CompleteCalendar::new(name, url.clone(), supported_components, color);
+        cached.set_change_log(Some(self.change_log.clone()));
+        let calendar = PersistentCalendar {
+            calendar: cached,
+            storage: Some(self.storage.clone()),
+            dirty: HashSet::new(),
+            unloaded: HashSet::new(),
+            reloaded: OnceCell::new(),
+        };
+        calendar.write_properties()?;
+
+        let arc = Arc::new(RwLock::new(calendar));
+        self.calendars.insert(url.clone(), LazyCalendar::loaded(arc.clone()));
+        changelog::record_calendar_change(&self.change_log, &url);
+        Ok(arc)
+    }
+
+    async fn delete_calendar(&mut self, url: &Url) -> Result<(), crate::Error> {
+        Ok(self.delete_calendar_sync(url)?)
+    }
+
+    fn deleted_calendars(&self) -> HashSet<Url> {
+        self.deleted_calendars.clone()
+    }
+
+    fn forget_deleted_calendar(&mut self, url: &Url) {
+        if self.deleted_calendars.remove(url) {
+            if let Err(err) = self.write_deleted_calendars() {
+                log::error!("Unable to write the deleted calendars to the cache: {}", err);
+            }
+        }
+    }
+
+    fn pending_moves(&self) -> HashMap<Url, Url> {
+        self.pending_moves.clone()
+    }
+
+    fn record_move(&mut self, from: Url, to: Url) {
+        self.pending_moves.insert(from, to);
+        if let Err(err) = self.write_pending_moves() {
+            log::error!("Unable to write the pending moves to the cache: {}", err);
+        }
+    }
+
+    fn forget_move(&mut self, from: &Url) {
+        if self.pending_moves.remove(from).is_some() {
+            if let Err(err) = self.write_pending_moves() {
+                log::error!("Unable to write the pending moves to the cache: {}", err);
+            }
+        }
+    }
+
+    fn calendar_selection(&self) -> CalendarSelection {
+        self.calendar_selection.clone()
+    }
+
+    fn set_calendar_selection(&mut self, selection: CalendarSelection) -> Result<(), crate::Error> {
+        let data = serde_json::to_vec(&selection).map_err(|err| crate::Error::Storage(Box::new(err)))?;
+        self.storage.lock().unwrap().save_metadata(CALENDAR_SELECTION_METADATA, &data)?;
+        self.calendar_selection = selection;
+        Ok(())
+    }
+}
+
+
+
+/// A calendar used by [`PersistentCache`]s
+///
+/// It behaves like a [`CachedCalendar`], but writes every change to the storage of its cache
+#[derive(Debug)]
+pub struct PersistentCalendar<S: CacheStorage> {
+    calendar: CachedCalendar,
+    /// This is `None` for calendars that do not belong to any cache
+    storage: Option<Arc<Mutex<S>>>,
+    /// Items that may have been modified in place, and that must be written again
+    dirty: HashSet<Url>,
+    /// Items that have been evicted from memory (see [`CompleteCalendar::evict_item`]), but whose content is still in the storage
+    unloaded: HashSet<Url>,
+    /// The content of the `unloaded` items, that is read from the storage the first time a function that cannot put them back in memory needs them (e.g. [`CompleteCalendar::query`])
+    reloaded: OnceCell<HashMap<Url, Item>>,
+}
+
+impl<S: CacheStorage> PersistentCalendar<S> {
+    /// Write the properties of this calendar (but not its items)
+    fn write_properties(&self) -> Result<(), Box<dyn Error>> {
+        let storage = match &self.storage {
+            None => return Ok(()),
+            Some(storage) => storage,
+        };
+        storage.lock().unwrap().save_calendar(&self.calendar.record())
+    }
+
+    /// The current state of an item: its record if it is in this calendar (or has been evicted from it), or nothing if it has been deleted
+    fn current_state(&self, item_url: &Url) -> ItemChange {
+        match self.calendar.item_record(item_url) {
+            Some(record) => ItemChange::Saved(record),
+            None => ItemChange::Removed(item_url.clone()),
+        }
+    }
+
+    /// Write an item that has just changed, as well as the items that have been modified in place
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "cache_write", level = "trace", skip_all, fields(item = %item_url), err))]
+    fn write_change(&mut self, item_url: &Url) -> Result<(), Box<dyn Error>> {
+        self.dirty.insert(item_url.clone());
+        self.flush()
+    }
+
+    /// Write every item that has been modified in place since it was last written
+    pub fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(storage) = &self.storage {
+            if self.dirty.is_empty() {
+                return Ok(());
+            }
+            let changes = self.dirty.iter().map(|url| self.current_state(url)).collect();
+            storage.lock().unwrap().save_items(self.calendar.url(), changes)?;
+        }
+        self.dirty.clear();
+        Ok(())
+    }
+
+    /// Read the content of the `unloaded` items from the storage
+    fn read_unloaded_items(&self) -> Result<HashMap<Url, Item>, Box<dyn Error>> {
+        let storage = match &self.storage {
+            Some(storage) if self.unloaded.is_empty() == false => storage,
+            _ => return Ok(HashMap::new()),
+        };
+        let records = storage.lock().unwrap().load_items(self.calendar.url())?;
+        Ok(records.into_iter()
+            .filter_map(|record| match record {
+                ItemRecord::Item { item, .. } if self.unloaded.contains(item.url()) => Some((item.url().clone(), item)),
+                _ => None,
+            })
+            .collect()
+        )
+    }
+
+    /// The content of the `unloaded` items, that is read from the storage in case this has not been done yet
+    fn reloaded_items(&self) -> Option<&HashMap<Url, Item>> {
+        if self.unloaded.is_empty() {
+            return None;
+        }
+        match self.reloaded.get_or_try_init(|| self.read_unloaded_items()) {
+            Err(err) => {
+                log::error!("Unable to read the evicted items of {} from the storage: {}", self.calendar.url(), err);
+                None
+            },
+            Ok(items) => Some(items),
+        }
+    }
+
+    /// Put an item that has been evicted from memory back in memory, so that it can be modified
+    fn load_unloaded_item(&mut self, item_url: &Url) -> Result<(), Box<dyn Error>> {
+        if self.unloaded.contains(item_url) == false {
+            return Ok(());
+        }
+        let item = match self.reloaded.get_mut().and_then(|items| items.remove(item_url)) {
+            Some(item) => Some(item),
+            None => match &self.storage {
+                None => None,
+                Some(storage) => storage.lock().unwrap().load_item(self.calendar.url(), item_url)?,
+            },
+        };
+        self.unloaded.remove(item_url);
+        match item {
+            Some(item) => self.calendar.reload_item(item),
+            None => log::warn!("Evicted item {} is missing from the storage", item_url),
+        }
+        Ok(())
+    }
+
+    /// Put every item that has been evicted from memory back in memory
+    fn load_unloaded_items(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.unloaded.is_empty() {
+            return Ok(());
+        }
+        let mut items = match self.reloaded.take() {
+            Some(items) => items,
+            None => self.read_unloaded_items()?,
+        };
+        for item_url in self.unloaded.drain() {
+            match items.remove(&item_url) {
+                Some(item) => self.calendar.reload_item(item),
+                None => log::warn!("Evicted item {} is missing from the storage", item_url),
+            }
+        }
+        Ok(())
+    }
+
+    /// The non-async version of [`Self::add_item`]
+    pub fn add_item_sync(&mut self, item: Item) -> Result<SyncStatus, Box<dyn Error>> {
+        self.load_unloaded_item(item.url())?;
+        let item_url = item.url().clone();
+        let status = self.calendar.add_item_sync(item)?;
+        self.write_change(&item_url)?;
+        Ok(status)
+    }
+
+    /// The non-async version of [`Self::update_item`]
+    pub fn update_item_sync(&mut self, item: Item) -> Result<SyncStatus, Box<dyn Error>> {
+        self.load_unloaded_item(item.url())?;
+        let item_url = item.url().clone();
+        let status = self.calendar.update_item_sync(item)?;
+        self.write_change(&item_url)?;
+        Ok(status)
+    }
+
+    /// The non-async version of [`Self::get_item_by_url`]
+    pub fn get_item_by_url_sync<'a>(&'a self, url: &Url) -> Option<&'a Item> {
+        match self.unloaded.contains(url) {
+            true => self.reloaded_items()?.get(url),
+            false => self.calendar.get_item_by_url_sync(url),
+        }
+    }
+
+    /// The non-async version of [`Self::get_item_by_url_mut`]
+    pub fn get_item_by_url_mut_sync<'a>(&'a mut self, url: &Url) -> Option<ItemMut<'a>> {
+        if let Err(err) = self.load_unloaded_item(url) {
+            log::error!("Unable to read item {} from the storage: {}", url, err);
+        }
+        self.dirty.insert(url.clone());
+        self.calendar.get_item_by_url_mut_sync(url)
+    }
+
+    /// The non-async version of [`Self::mark_for_deletion`]
+    pub fn mark_for_deletion_sync(&mut self, item_url: &Url) -> Result<(), Box<dyn Error>> {
+        self.load_unloaded_item(item_url)?;
+        self.calendar.mark_for_deletion_sync(item_url)?;
+        self.write_change(item_url)
+    }
+
+    /// The non-async version of [`Self::immediately_delete_item`]
+    pub fn immediately_delete_item_sync(&mut self, item_url: &Url) -> Result<(), Box<dyn Error>> {
+        self.load_unloaded_item(item_url)?;
+        self.calendar.immediately_delete_item_sync(item_url)?;
+        self.write_change(item_url)
+    }
+
+    /// See [`CachedCalendar::compact_tombstones_sync`]
+    pub fn compact_tombstones_sync(&mut self, deleted_before: &DateTime<Utc>) -> Result<usize, Box<dyn Error>> {
+        let tombstones = self.calendar.compact_tombstones(deleted_before);
+        for url in &tombstones {
+            self.write_change(url)?;
+        }
+        Ok(tombstones.len())
+    }
+}
+
+impl<S: CacheStorage> Drop for PersistentCalendar<S> {
+    fn drop(&mut self) {
+        if let Err(err) = self.flush() {
+            log::error!("Unable to write the changes of calendar {}: {}", self.calendar.url(), err);
+        }
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl<S: CacheStorage> BaseCalendar for PersistentCalendar<S> {
+    fn name(&self) -> &str {
+        self.calendar.name()
+    }
+
+    fn url(&self) -> &Url {
+        self.calendar.url()
+    }
+
+    fn supported_components(&self) -> SupportedComponents {
+        self.calendar.supported_components()
+    }
+
+    fn color(&self) -> Option<&Color> {
+        self.calendar.color()
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.calendar.description()
+    }
+
+    fn order(&self) -> Option<i32> {
+        self.calendar.order()
+    }
+
+    fn privileges(&self) -> Privileges {
+        self.calendar.privileges()
+    }
+
+    async fn add_item(&mut self, item: Item) -> Result<SyncStatus, crate::Error> {
+        Ok(self.add_item_sync(item)?)
+    }
+
+    async fn update_item(&mut self, item: Item) -> Result<SyncStatus, crate::Error> {
+        Ok(self.update_item_sync(item)?)
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl<S: CacheStorage> CompleteCalendar for PersistentCalendar<S> {
+    /// Create a calendar that does not belong to any cache, and that is not stored anywhere. \
+    /// Use [`CalDavSource::create_calendar`] on a [`PersistentCache`] to create calendars that are actually stored
+    fn new(name: String, url: Url, supported_components: SupportedComponents, color: Option<Color>) -> Self {
+        Self {
+            calendar: CompleteCalendar::new(name, url, supported_components, color),
+            storage: None,
+            dirty: HashSet::new(),
+            unloaded: HashSet::new(),
+            reloaded: OnceCell::new(),
+        }
+    }
+
+    async fn get_item_urls(&self) -> Result<HashSet<Url>, crate::Error> {
+        let mut urls = self.calendar.get_item_urls_sync()?;
+        urls.extend(self.unloaded.iter().cloned());
+        Ok(urls)
+    }
+
+    async fn get_items(&self) -> Result<HashMap<Url, &Item>, crate::Error> {
+        let mut items = self.calendar.get_items_sync()?;
+        items.extend(self.reloaded_items().into_iter().flatten().map(|(url, item)| (url.clone(), item)));
+        Ok(items)
+    }
+
+    async fn get_items_mut(&mut self) -> Result<HashMap<Url, ItemMut<'_>>, crate::Error> {
+        self.load_unloaded_items()?;
+        self.dirty.extend(self.calendar.get_item_urls_sync()?);
+        Ok(self.calendar.get_items_mut_sync()?)
+    }
+
+    fn iter_items<'a>(&'a self) -> Box<dyn Iterator<Item = (&'a Url, &'a Item)> + 'a> {
+        Box::new(self.calendar.iter_items()
+            .chain(self.reloaded_items().into_iter().flatten())
+        )
+    }
+
+    fn iter_items_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = (&'a Url, ItemMut<'a>)> + 'a> {
+        if let Err(err) = self.load_unloaded_items() {
+            log::error!("Unable to read the evicted items of {} from the storage: {}", self.calendar.url(), err);
+        }
+        self.dirty.extend(self.calendar.iter_items().map(|(url, _)| url.clone()));
+        self.calendar.iter_items_mut()
+    }
+
+    fn item_count(&self) -> usize {
+        self.calendar.item_count() + self.unloaded.len()
+    }
+
+    fn query<'a>(&'a self, query: &ItemQuery) -> Vec<(&'a Url, &'a Item)> {
+        let mut items = self.calendar.query(query);
+        items.extend(self.reloaded_items().into_iter().flatten().filter(|(_url, item)| query.matches(item)));
+        items
+    }
+
+    async fn get_item_by_url<'a>(&'a self, url: &Url) -> Option<&'a Item> {
+        self.get_item_by_url_sync(url)
+    }
+
+    async fn get_item_by_url_mut<'a>(&'a mut self, url: &Url) -> Option<ItemMut<'a>> {
+        self.get_item_by_url_mut_sync(url)
+    }
+
+    async fn mark_for_deletion(&mut self, item_url: &Url) -> Result<(), crate::Error> {
+        Ok(self.mark_for_deletion_sync(item_url)?)
+    }
+
+    async fn immediately_delete_item(&mut self, item_url: &Url) -> Result<(), crate::Error> {
+        Ok(self.immediately_delete_item_sync(item_url)?)
+    }
+
+    fn mark_as_rejected(&mut self, item_url: &Url, error: ServerError) {
+        if let Err(err) = self.load_unloaded_item(item_url) {
+            log::error!("Unable to read item {} from the storage: {}", item_url, err);
+        }
+        self.calendar.mark_as_rejected(item_url, error);
+        if let Err(err) = self.write_change(item_url) {
+            log::error!("Unable to write the rejection of {}: {}", item_url, err);
+        }
+    }
+
+    fn rejection(&self, item_url: &Url) -> Option<&Rejection> {
+        self.calendar.rejection(item_url)
+    }
+
+    /// Drop the content of a synced item from memory. \
+    /// Unlike [`CachedCalendar`]s, calendars that belong to a [`PersistentCache`] keep it in their storage, and read it again when it is needed
+    /// (so that evicted items are still listed and queried, and do not need to be downloaded again)
+    fn evict_item(&mut self, item_url: &Url) -> Result<(), crate::Error> {
+        if self.storage.is_none() {
+            self.calendar.evict_item_sync(item_url)?;
+            return Ok(self.write_change(item_url)?);
+        }
+        if self.unloaded.contains(item_url) {
+            return Ok(());
+        }
+        match self.calendar.get_item_by_url_sync(item_url).map(|item| item.sync_status()) {
+            None => return Err(format!("Item {} is absent from this calendar", item_url).into()),
+            Some(SyncStatus::Synced(_)) => (),
+            Some(_) => return Err(format!("Item {} has local changes, it cannot be evicted", item_url).into()),
+        }
+        // The storage must be up to date before the content is dropped from memory
+        self.flush()?;
+        self.calendar.unload_item(item_url);
+        self.unloaded.insert(item_url.clone());
+        self.reloaded = OnceCell::new();
+        Ok(())
+    }
+
+    fn evicted_items(&self) -> &HashMap<Url, VersionTag> {
+        self.calendar.evicted_items()
+    }
+
+    fn update_evicted_item(&mut self, item_url: &Url, version_tag: Option<VersionTag>) {
+        self.calendar.update_evicted_item(item_url, version_tag);
+        if let Err(err) = self.write_change(item_url) {
+            log::error!("Unable to write the version tag of {}: {}", item_url, err);
+        }
+    }
+
+    fn set_privileges(&mut self, privileges: Privileges) {
+        self.calendar.set_privileges(privileges);
+        if let Err(err) = self.write_properties() {
+            log::error!("Unable to write the privileges of {}: {}", self.calendar.url(), err);
+        }
+    }
+
+    fn set_name(&mut self, name: String) {
+        self.calendar.set_name(name);
+        if let Err(err) = self.write_properties() {
+            log::error!("Unable to write the name of {}: {}", self.calendar.url(), err);
+        }
+    }
+
+    fn set_description(&mut self, description: Option<String>) {
+        self.calendar.set_description(description);
+        if let Err(err) = self.write_properties() {
+            log::error!("Unable to write the description of {}: {}", self.calendar.url(), err);
+        }
+    }
+
+    fn set_color(&mut self, color: Option<Color>) {
+        self.calendar.set_color(color);
+        if let Err(err) = self.write_properties() {
+            log::error!("Unable to write the color of {}: {}", self.calendar.url(), err);
+        }
+    }
+
+    fn set_order(&mut self, order: Option<i32>) {
+        self.calendar.set_order(order);
+        if let Err(err) = self.write_properties() {
+            log::error!("Unable to write the order of {}: {}", self.calendar.url(), err);
+        }
+    }
+
+    fn has_modified_properties(&self) -> bool {
+        self.calendar.has_modified_properties()
+    }
+
+    fn set_synced_properties(&mut self, properties: CalendarProperties) {
+        self.calendar.set_synced_properties(properties);
+        if let Err(err) = self.write_properties() {
+            log::error!("Unable to write the properties of {}: {}", self.calendar.url(), err);
+        }
+    }
+
+    fn sync_token(&self) -> Option<&str> {
+        self.calendar.sync_token()
+    }
+
+    fn set_sync_token(&mut self, sync_token: Option<String>) {
+        self.calendar.set_sync_token(sync_token);
+        if let Err(err) = self.write_properties() {
+            log::error!("Unable to write the sync token of {}: {}", self.calendar.url(), err);
+        }
+    }
+
+    fn can_edit(&self, item_url: &Url) -> bool {
+        match self.unloaded.contains(item_url) {
+            true => self.calendar.privileges().contains(Privileges::WRITE_CONTENT),
+            false => self.calendar.can_edit(item_url),
+        }
+    }
+
+    fn can_delete(&self, item_url: &Url) -> bool {
+        match self.unloaded.contains(item_url) {
+            true => self.calendar.privileges().contains(Privileges::UNBIND),
+            false => self.calendar.can_delete(item_url),
+        }
+    }
+}
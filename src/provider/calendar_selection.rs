@@ -0,0 +1,109 @@
+//! Which calendars syncs handle (see [`Provider::set_calendar_selection`](crate::provider::Provider::set_calendar_selection))
+
+use std::collections::HashSet;
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// The calendars syncs handle, by URL. This is stored in the local cache, so that it survives restarts of the app
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CalendarSelection {
+    /// Every calendar is synced
+    All,
+    /// Only these calendars are synced
+    Only(HashSet<Url>),
+    /// Every calendar is synced, except these ones
+    AllExcept(HashSet<Url>),
+}
+
+impl CalendarSelection {
+    /// Whether a calendar is synced
+    pub fn includes(&self, url: &Url) -> bool {
+        match self {
+            CalendarSelection::All => true,
+            CalendarSelection::Only(urls) => urls.contains(url),
+            CalendarSelection::AllExcept(urls) => urls.contains(url) == false,
+        }
+    }
+
+    /// Make sure a calendar is synced
+    pub fn include(&mut self, url: Url) {
+        match self {
+            CalendarSelection::All => (),
+            CalendarSelection::Only(urls) => { urls.insert(url); },
+            CalendarSelection::AllExcept(urls) => { urls.remove(&url); },
+        }
+    }
+
+    /// Make sure a calendar is not synced
+    pub fn exclude(&mut self, url: Url) {
+        match self {
+            CalendarSelection::All => *self = CalendarSelection::AllExcept(std::iter::once(url).collect()),
+            CalendarSelection::Only(urls) => { urls.remove(&url); },
+            CalendarSelection::AllExcept(urls) => { urls.insert(url); },
+        }
+    }
+}
+
+impl Default for CalendarSelection {
+    fn default() -> Self {
+        Self::All
+    }
+}
+
+/// A function that tells whether a calendar is synced, from its URL and its name (e.g. to skip every calendar whose name starts with "Shared").
+///
+/// Unlike [`CalendarSelection`]s, filters cannot be stored. They must be set again every time a [`Provider`](crate::provider::Provider) is created
+#[derive(Clone)]
+pub struct CalendarFilter(Arc<dyn Fn(&Url, &str) -> bool + Send + Sync>);
+
+impl CalendarFilter {
+    pub fn new<F>(accepts: F) -> Self
+    where
+        F: Fn(&Url, &str) -> bool + Send + Sync + 'static
+    {
+        Self(Arc::new(accepts))
+    }
+
+    /// Whether a calendar is synced
+    pub fn accepts(&self, url: &Url, name: &str) -> bool {
+        (self.0)(url, name)
+    }
+}
+
+impl Debug for CalendarFilter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CalendarFilter(<function>)")
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calendar_selection() {
+        let shopping = Url::parse("https://caldav.com/shopping/").unwrap();
+        let work = Url::parse("https://caldav.com/work/").unwrap();
+
+        let mut selection = CalendarSelection::default();
+        assert!(selection.includes(&shopping));
+        selection.exclude(work.clone());
+        assert!(selection.includes(&shopping));
+        assert_eq!(selection.includes(&work), false);
+        selection.include(work.clone());
+        assert!(selection.includes(&work));
+
+        let mut selection = CalendarSelection::Only(HashSet::new());
+        assert_eq!(selection.includes(&shopping), false);
+        selection.include(shopping.clone());
+        assert!(selection.includes(&shopping));
+        assert_eq!(selection.includes(&work), false);
+        selection.exclude(shopping.clone());
+        assert_eq!(selection.includes(&shopping), false);
+    }
+}
@@ -0,0 +1,293 @@
+//! Rules to tell whether a remote change actually changed anything, and which version of an item is the most recent one
+//!
+//! Some servers rewrite items every time they are fetched (e.g. re-ordering their properties, or adding `X-` properties), so that their version tags change without any actual modification.
+//! Without these rules, such items would be reported as remote changes at every sync.
+//!
+//! Likewise, the clocks of the devices and of the server may be off, so that last modification dates cannot always be trusted.
+
+use std::collections::HashSet;
+use std::error::Error;
+
+use chrono::Duration;
+use itertools::{EitherOrBoth, Itertools};
+
+use crate::Item;
+
+/// The `X-` properties that this crate writes itself (e.g. for time tracking), which are never ignored by [`ComparisonRules::ignoring_x_properties`]
+const OWN_X_PROPERTIES: [&str; 2] = ["X-TIMER-STARTED", "X-TIME-SPENT"];
+
+/// What is ignored when comparing the local and the remote versions of an item that has changed on the server.
+///
+/// Providers use [`ComparisonRules::strict`] by default. Use [`Provider::set_comparison_rules`](crate::provider::Provider::set_comparison_rules) to suit the quirks of a given server.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ComparisonRules {
+    /// Names of the properties that are ignored (e.g. `DTSTAMP`)
+    ignored_properties: HashSet<String>,
+    /// Whether every `X-` property is ignored (except the ones of this crate, and `significant_properties`)
+    ignore_x_properties: bool,
+    /// Names of the `X-` properties that are compared even though `ignore_x_properties` is set
+    significant_properties: HashSet<String>,
+    /// Whether the order of properties (and sub-components) is ignored
+    ignore_property_order: bool,
+    /// Last modification dates that are closer than this are not trusted to tell which version is the most recent one
+    clock_skew_tolerance_seconds: i64,
+}
+
+/// Which version of an item is the most recent one (see [`ComparisonRules::most_recent`])
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MostRecent {
+    Local,
+    Remote,
+    /// There is no evidence either way (e.g. their last modification dates are too close to tell)
+    Unknown,
+}
+
+impl ComparisonRules {
+    /// Nothing is ignored: two versions are equivalent only in case they are serialized into the very same iCal data
+    pub fn strict() -> Self {
+        Self::default()
+    }
+
+    /// Rules that suit most servers that rewrite items: `DTSTAMP`, `LAST-MODIFIED`, `X-` properties and the order of properties are ignored.
+    ///
+    /// The `X-` properties this crate writes (e.g. `X-TIME-SPENT`) are still compared. Use [`Self::with_significant_property`] for the custom properties an app sets
+    pub fn lenient() -> Self {
+        Self::strict()
+            .with_ignored_property("DTSTAMP")
+            .with_ignored_property("LAST-MODIFIED")
+            .ignoring_x_properties()
+            .ignoring_property_order()
+    }
+
+    /// Ignore a given property (e.g. `LAST-MODIFIED`, or `X-MOZ-GENERATION`)
+    pub fn with_ignored_property(mut self, name: &str) -> Self {
+        self.ignored_properties.insert(name.to_ascii_uppercase());
+        self
+    }
+
+    /// Ignore every `X-` property, except the ones this crate writes itself and the ones given to [`Self::with_significant_property`]
+    pub fn ignoring_x_properties(mut self) -> Self {
+        self.ignore_x_properties = true;
+        self
+    }
+
+    /// Keep comparing an `X-` property even when [`Self::ignoring_x_properties`] is used (e.g. a custom property the app sets with [`Task::set_property`](crate::Task::set_property))
+    pub fn with_significant_property(mut self, name: &str) -> Self {
+        self.significant_properties.insert(name.to_ascii_uppercase());
+        self
+    }
+
+    /// Ignore the order of properties and sub-components (e.g. alarms)
+    pub fn ignoring_property_order(mut self) -> Self {
+        self.ignore_property_order = true;
+        self
+    }
+
+    /// Do not trust last modification dates that are closer than `tolerance`, since the clocks of the devices and of the server may be off
+    pub fn with_clock_skew_tolerance(mut self, tolerance: Duration) -> Self {
+        self.clock_skew_tolerance_seconds = tolerance.num_seconds().abs();
+        self
+    }
+
+    pub fn ignored_properties(&self) -> &HashSet<String> { &self.ignored_properties }
+    pub fn ignores_x_properties(&self) -> bool { self.ignore_x_properties }
+    pub fn significant_properties(&self) -> &HashSet<String> { &self.significant_properties }
+    pub fn ignores_property_order(&self) -> bool { self.ignore_property_order }
+    pub fn clock_skew_tolerance(&self) -> Duration { Duration::seconds(self.clock_skew_tolerance_seconds) }
+
+    fn is_ignored(&self, property_name: &str) -> bool {
+        if self.ignored_properties.contains(property_name) {
+            return true;
+        }
+        self.ignore_x_properties
+            && property_name.starts_with("X-")
+            && OWN_X_PROPERTIES.contains(&property_name) == false
+            && self.significant_properties.contains(property_name) == false
+    }
+
+    /// Whether two versions of an item are equivalent, according to these rules. Sync statuses are not compared
+    pub fn are_equivalent(&self, left: &Item, right: &Item) -> bool {
+        match (crate::ical::build_from(left), crate::ical::build_from(right)) {
+            (Ok(left), Ok(right)) => self.normalize(&left) == self.normalize(&right),
+            _ => false,
+        }
+    }
+
+    /// Tell which version of an item has been modified most recently.
+    ///
+    /// Their `SEQUENCE` numbers are preferred (they are bumped by every significant revision, whatever the clock of the device).
+    /// Otherwise, their last modification dates are compared, unless they are within the clock skew tolerance
+    pub fn most_recent(&self, local: &Item, remote: &Item) -> MostRecent {
+        if let (Some(local_seq), Some(remote_seq)) = (sequence(local), sequence(remote)) {
+            if local_seq != remote_seq {
+                return match local_seq > remote_seq {
+                    true => MostRecent::Local,
+                    false => MostRecent::Remote,
+                };
+            }
+        }
+
+        // iCal dates have a precision of one second
+        let delta = local.last_modified().timestamp() - remote.last_modified().timestamp();
+        if delta.abs() <= self.clock_skew_tolerance_seconds {
+            MostRecent::Unknown
+        } else if delta > 0 {
+            MostRecent::Local
+        } else {
+            MostRecent::Remote
+        }
+    }
+
+    /// The names of the properties that differ between two versions of an item (ignoring what these rules ignore), sorted alphabetically
+    pub fn changed_properties(&self, left: &Item, right: &Item) -> Result<Vec<String>, Box<dyn Error>> {
+        let left = crate::ical::build_from(left)?;
+        let right = crate::ical::build_from(right)?;
+        let mut left_lines = unfold(&left);
+        let mut right_lines = unfold(&right);
+        left_lines.sort();
+        right_lines.sort();
+
+        let mut changed = Vec::new();
+        for line in left_lines.iter().merge_join_by(right_lines.iter(), |l, r| l.cmp(r)) {
+            if let EitherOrBoth::Left(line) | EitherOrBoth::Right(line) = line {
+                let name = property_name(line);
+                if name != "BEGIN" && name != "END" && self.is_ignored(&name) == false {
+                    changed.push(name);
+                }
+            }
+        }
+        changed.sort();
+        changed.dedup();
+        Ok(changed)
+    }
+
+    /// The lines of an iCal file, unfolded, without the ignored properties, and possibly sorted within every component
+    fn normalize(&self, ical: &str) -> Vec<String> {
+        // The lines of the components that are being read
+        let mut stack: Vec<Vec<String>> = vec![Vec::new()];
+        for line in unfold(ical) {
+            let name = property_name(&line);
+            match name.as_str() {
+                "BEGIN" => stack.push(vec![line]),
+                "END" => {
+                    let mut component = stack.pop().unwrap_or_default();
+                    if self.ignore_property_order && component.len() > 1 {
+                        component[1..].sort();
+                    }
+                    component.push(line);
+                    if stack.is_empty() {
+                        stack.push(Vec::new());
+                    }
+                    if let Some(parent) = stack.last_mut() {
+                        parent.push(component.join("\r\n"));
+                    }
+                },
+                _ if self.is_ignored(&name) => (),
+                _ => {
+                    if let Some(component) = stack.last_mut() {
+                        component.push(line);
+                    }
+                },
+            }
+        }
+        stack.into_iter().flatten().collect()
+    }
+}
+
+/// The (unfolded) lines of an iCal file
+fn unfold(ical: &str) -> Vec<String> {
+    ical.replace("\r\n ", "").replace("\r\n\t", "")
+        .split("\r\n")
+        .filter(|line| line.is_empty() == false)
+        .map(|line| line.to_string())
+        .collect()
+}
+
+/// The SEQUENCE of an item, i.e. its revision number
+fn sequence(item: &Item) -> Option<u32> {
+    let extra_parameters = match item {
+        Item::Event(e) => e.extra_parameters(),
+        Item::Task(t) => t.extra_parameters(),
+        Item::Journal(j) => j.extra_parameters(),
+        Item::Contact(c) => c.extra_parameters(),
+    };
+    extra_parameters.iter()
+        .find(|prop| prop.name == "SEQUENCE")
+        .and_then(|prop| prop.value.as_deref())
+        .and_then(|value| value.trim().parse().ok())
+}
+
+fn property_name(line: &str) -> String {
+    line.split(|c| c == ';' || c == ':').next().unwrap_or_default().to_ascii_uppercase()
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::item::SyncStatus;
+
+    const ORIGINAL: &str = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//server//EN\r\nBEGIN:VTODO\r\nUID:some-uid\r\nDTSTAMP:20210321T001600Z\r\nSUMMARY:Do it\r\nX-FOO:1\r\nX-BAR:2\r\nEND:VTODO\r\nEND:VCALENDAR\r\n";
+    const REWRITTEN: &str = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//server//EN\r\nBEGIN:VTODO\r\nUID:some-uid\r\nDTSTAMP:20210322T080000Z\r\nSUMMARY:Do it\r\nX-BAR:2\r\nX-FOO:1\r\nX-SERVER-JUNK:1234\r\nEND:VTODO\r\nEND:VCALENDAR\r\n";
+
+    #[test]
+    fn test_comparison_rules() {
+        let url: url::Url = "http://some.id/for/testing".parse().unwrap();
+        let original = crate::ical::parse(ORIGINAL, url.clone(), SyncStatus::NotSynced).unwrap();
+        let rewritten = crate::ical::parse(REWRITTEN, url.clone(), SyncStatus::NotSynced).unwrap();
+
+        assert!(ComparisonRules::strict().are_equivalent(&original, &original));
+        assert_eq!(ComparisonRules::strict().are_equivalent(&original, &rewritten), false);
+        assert!(ComparisonRules::lenient().are_equivalent(&original, &rewritten));
+
+        // Only ignoring the X- properties is not enough, since DTSTAMP (hence the last modification date) has changed as well
+        let rules = ComparisonRules::strict().ignoring_x_properties();
+        assert_eq!(rules.are_equivalent(&original, &rewritten), false);
+        let rules = rules.with_ignored_property("dtstamp").with_ignored_property("last-modified");
+        assert!(rules.are_equivalent(&original, &rewritten));
+
+        // Actual changes are not ignored
+        let renamed = crate::ical::parse(&REWRITTEN.replace("SUMMARY:Do it", "SUMMARY:Do it now"), url, SyncStatus::NotSynced).unwrap();
+        assert_eq!(ComparisonRules::lenient().are_equivalent(&original, &renamed), false);
+        assert_eq!(ComparisonRules::lenient().changed_properties(&original, &renamed).unwrap(), vec!["SUMMARY".to_string()]);
+        assert_eq!(ComparisonRules::strict().changed_properties(&original, &renamed).unwrap(),
+            vec!["DTSTAMP".to_string(), "LAST-MODIFIED".to_string(), "SUMMARY".to_string(), "X-SERVER-JUNK".to_string()]);
+    }
+
+    #[test]
+    fn test_significant_x_properties() {
+        let url: url::Url = "http://some.id/for/testing".parse().unwrap();
+        let original = crate::ical::parse(ORIGINAL, url.clone(), SyncStatus::NotSynced).unwrap();
+
+        // The properties of this crate are never ignored...
+        let tracked = crate::ical::parse(&ORIGINAL.replace("X-FOO:1", "X-TIME-SPENT:PT1H"), url.clone(), SyncStatus::NotSynced).unwrap();
+        assert_eq!(ComparisonRules::lenient().are_equivalent(&original, &tracked), false);
+        assert_eq!(ComparisonRules::lenient().changed_properties(&original, &tracked).unwrap(), vec!["X-TIME-SPENT".to_string()]);
+
+        // ...and neither are the ones the app cares about
+        let modified = crate::ical::parse(&ORIGINAL.replace("X-FOO:1", "X-FOO:2"), url, SyncStatus::NotSynced).unwrap();
+        assert!(ComparisonRules::lenient().are_equivalent(&original, &modified));
+        let rules = ComparisonRules::lenient().with_significant_property("x-foo");
+        assert_eq!(rules.are_equivalent(&original, &modified), false);
+    }
+
+    #[test]
+    fn test_most_recent() {
+        let url: url::Url = "http://some.id/for/testing".parse().unwrap();
+        let original = crate::ical::parse(ORIGINAL, url.clone(), SyncStatus::NotSynced).unwrap();
+        // This one has been written 32 hours later...
+        let rewritten = crate::ical::parse(REWRITTEN, url.clone(), SyncStatus::NotSynced).unwrap();
+
+        assert_eq!(ComparisonRules::strict().most_recent(&rewritten, &original), MostRecent::Local);
+        assert_eq!(ComparisonRules::strict().most_recent(&original, &rewritten), MostRecent::Remote);
+        assert_eq!(ComparisonRules::strict().most_recent(&original, &original), MostRecent::Unknown);
+        let tolerant = ComparisonRules::strict().with_clock_skew_tolerance(Duration::days(2));
+        assert_eq!(tolerant.most_recent(&original, &rewritten), MostRecent::Unknown);
+
+        // ...but SEQUENCE numbers are trusted more than clocks
+        let revised = crate::ical::parse(&ORIGINAL.replace("SUMMARY:", "SEQUENCE:2\r\nSUMMARY:"), url.clone(), SyncStatus::NotSynced).unwrap();
+        let skewed = crate::ical::parse(&REWRITTEN.replace("SUMMARY:", "SEQUENCE:1\r\nSUMMARY:"), url, SyncStatus::NotSynced).unwrap();
+        assert_eq!(ComparisonRules::strict().most_recent(&revised, &skewed), MostRecent::Local);
+    }
+}
@@ -0,0 +1,150 @@
+//! What a sync does to items that have been changed both locally and on the server since the last sync (see [`Provider::set_conflict_resolution`](crate::provider::Provider::set_conflict_resolution))
+
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+use url::Url;
+
+use crate::Item;
+
+/// How an item has been changed on both ends
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictKind {
+    /// The item has been modified locally and on the server
+    BothModified,
+    /// The item has been deleted locally, and modified on the server
+    LocallyDeleted,
+    /// The item has been modified locally, and deleted from the server
+    RemotelyDeleted,
+}
+
+/// Which version of an item has been kept
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictOutcome {
+    KeptLocal,
+    KeptRemote,
+    /// The server version has been kept, and the local version has been saved as a new item (with its own URL and UID). \
+    /// This only makes sense for [`ConflictKind::BothModified`]: for other kinds of conflicts, this means the version that has not been deleted is kept
+    KeptBoth,
+}
+
+/// An item that has been changed both locally and on the server since the last sync
+#[derive(Clone, Debug)]
+pub struct Conflict {
+    url: Url,
+    kind: ConflictKind,
+    local: Item,
+    remote: Option<Item>,
+}
+
+impl Conflict {
+    pub(crate) fn new(url: Url, kind: ConflictKind, local: Item, remote: Option<Item>) -> Self {
+        Self { url, kind, local, remote }
+    }
+
+    pub fn url(&self) -> &Url { &self.url }
+    pub fn kind(&self) -> ConflictKind { self.kind }
+    /// The local version of the item
+    pub fn local(&self) -> &Item { &self.local }
+    /// The server version of the item. This is `None` in case it has been deleted from the server.
+    ///
+    /// It is only downloaded for [`ConflictResolution::Custom`] policies
+    pub fn remote(&self) -> Option<&Item> { self.remote.as_ref() }
+}
+
+/// A conflict that has been resolved by a sync (see [`SyncResult::conflicts`](crate::provider::sync_result::SyncResult::conflicts))
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResolvedConflict {
+    calendar_url: Url,
+    item_url: Url,
+    kind: ConflictKind,
+    outcome: ConflictOutcome,
+}
+
+impl ResolvedConflict {
+    pub(crate) fn new(calendar_url: Url, item_url: Url, kind: ConflictKind, outcome: ConflictOutcome) -> Self {
+        Self { calendar_url, item_url, kind, outcome }
+    }
+
+    pub fn calendar_url(&self) -> &Url { &self.calendar_url }
+    pub fn item_url(&self) -> &Url { &self.item_url }
+    pub fn kind(&self) -> ConflictKind { self.kind }
+    pub fn outcome(&self) -> ConflictOutcome { self.outcome }
+}
+
+/// What a sync does to items that have been changed both locally and on the server since the last sync
+#[derive(Clone)]
+pub enum ConflictResolution {
+    /// The server version is kept, local changes are lost
+    PreferRemote,
+    /// The local version is pushed to the server, overwriting the remote changes (or re-creating items that have been deleted from the server)
+    PreferLocal,
+    /// The server version is kept, and the local version is saved as a new item (that is pushed to the server as well).
+    /// Deletions never win: the version that has not been deleted is kept
+    KeepBoth,
+    /// A function that decides for every conflict
+    Custom(Arc<dyn Fn(&Conflict) -> ConflictOutcome + Send + Sync>),
+}
+
+impl ConflictResolution {
+    /// A policy that calls `decide` for every conflict
+    pub fn custom<F>(decide: F) -> Self
+    where
+        F: Fn(&Conflict) -> ConflictOutcome + Send + Sync + 'static
+    {
+        ConflictResolution::Custom(Arc::new(decide))
+    }
+
+    /// Whether the server version of the conflicting items is needed to resolve conflicts
+    pub(crate) fn needs_remote_version(&self) -> bool {
+        matches!(self, ConflictResolution::Custom(_))
+    }
+
+    /// Which version of an item is kept
+    pub(crate) fn resolve(&self, conflict: &Conflict) -> ConflictOutcome {
+        let outcome = match self {
+            ConflictResolution::Custom(decide) => decide(conflict),
+            _ => self.fixed_outcome().unwrap(/* not a custom policy */),
+        };
+        Self::applicable_outcome(conflict.kind, outcome)
+    }
+
+    /// Which version of an item would be kept, in case this can be told without calling a `Custom` function (which dry runs must not do, since it may have side effects)
+    pub(crate) fn planned_outcome(&self, kind: ConflictKind) -> Option<ConflictOutcome> {
+        self.fixed_outcome().map(|outcome| Self::applicable_outcome(kind, outcome))
+    }
+
+    fn fixed_outcome(&self) -> Option<ConflictOutcome> {
+        match self {
+            ConflictResolution::PreferRemote => Some(ConflictOutcome::KeptRemote),
+            ConflictResolution::PreferLocal => Some(ConflictOutcome::KeptLocal),
+            ConflictResolution::KeepBoth => Some(ConflictOutcome::KeptBoth),
+            ConflictResolution::Custom(_) => None,
+        }
+    }
+
+    fn applicable_outcome(kind: ConflictKind, outcome: ConflictOutcome) -> ConflictOutcome {
+        match (kind, outcome) {
+            (ConflictKind::LocallyDeleted, ConflictOutcome::KeptBoth) => ConflictOutcome::KeptRemote,
+            (ConflictKind::RemotelyDeleted, ConflictOutcome::KeptBoth) => ConflictOutcome::KeptLocal,
+            (_, outcome) => outcome,
+        }
+    }
+}
+
+impl Default for ConflictResolution {
+    fn default() -> Self {
+        Self::PreferRemote
+    }
+}
+
+impl Debug for ConflictResolution {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConflictResolution::PreferRemote => write!(f, "PreferRemote"),
+            ConflictResolution::PreferLocal => write!(f, "PreferLocal"),
+            ConflictResolution::KeepBoth => write!(f, "KeepBoth"),
+            ConflictResolution::Custom(_) => write!(f, "Custom(<function>)"),
+        }
+    }
+}
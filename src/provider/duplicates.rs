@@ -0,0 +1,104 @@
+//! Items of a calendar that share the same UID, e.g. because they have been imported twice (see [`Provider::find_duplicates`](crate::provider::Provider::find_duplicates))
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use url::Url;
+
+use crate::item::{Item, SyncStatus, Uid};
+
+/// Items of the same calendar that share the same UID.
+///
+/// Modified instances of a recurring event that are stored apart from it are not duplicates, since they have their own `RECURRENCE-ID`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Duplicates {
+    calendar_url: Url,
+    uid: Uid,
+    /// The URLs of the items, and when they have last been modified. The most recently modified one comes first
+    items: Vec<(Url, DateTime<Utc>)>,
+}
+
+impl Duplicates {
+    pub fn calendar_url(&self) -> &Url { &self.calendar_url }
+    pub fn uid(&self) -> &Uid { &self.uid }
+
+    /// The URLs of the duplicated items, the most recently modified first
+    pub fn urls(&self) -> impl Iterator<Item = &Url> {
+        self.items.iter().map(|(url, _)| url)
+    }
+
+    /// The URL of the most recently modified item, which is usually the one to keep
+    pub fn most_recent(&self) -> &Url {
+        &self.items[0].0 // there are always at least two items
+    }
+
+    pub(crate) fn contains(&self, url: &Url) -> bool {
+        self.items.iter().any(|(item_url, _)| item_url == url)
+    }
+}
+
+/// Find the items that share the same UID among the items of a calendar. Items that are marked for deletion are left out
+pub(crate) fn find_duplicates<'a, I>(calendar_url: &Url, items: I) -> Vec<Duplicates>
+where
+    I: Iterator<Item = (&'a Url, &'a Item)>,
+{
+    let mut by_uid: HashMap<(&Uid, Option<&DateTime<Utc>>), Vec<(Url, DateTime<Utc>)>> = HashMap::new();
+    for (url, item) in items {
+        if let SyncStatus::LocallyDeleted(_) = item.sync_status() {
+            continue;
+        }
+        let recurrence_id = match item {
+            Item::Event(event) => event.recurrence_id(),
+            _ => None,
+        };
+        by_uid.entry((item.uid(), recurrence_id))
+            .or_default()
+            .push((url.clone(), *item.last_modified()));
+    }
+
+    let mut duplicates: Vec<Duplicates> = by_uid.into_iter()
+        .filter(|(_key, items)| items.len() > 1)
+        .map(|((uid, _recurrence_id), mut items)| {
+            items.sort_by(|(url_a, date_a), (url_b, date_b)| date_b.cmp(date_a).then_with(|| url_a.cmp(url_b)));
+            Duplicates { calendar_url: calendar_url.clone(), uid: uid.clone(), items }
+        })
+        .collect();
+    duplicates.sort_by(|a, b| a.uid.as_str().cmp(b.uid.as_str()));
+    duplicates
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::item::VersionTag;
+    use crate::task::Task;
+
+    #[test]
+    fn test_find_duplicates() {
+        let cal_url: Url = "https://caldav.com/shopping/".parse().unwrap();
+        let original = Task::new("Buy milk".to_string(), false, &cal_url);
+        let mut imported = original.clone();
+        imported.set_url(cal_url.join("imported.ics").unwrap());
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        imported.update_last_modified();
+        let other = Task::new("Buy bread".to_string(), false, &cal_url);
+
+        let items: HashMap<Url, Item> = vec![original.clone(), imported.clone(), other]
+            .into_iter()
+            .map(|task| (task.url().clone(), Item::Task(task)))
+            .collect();
+        let duplicates = find_duplicates(&cal_url, items.iter());
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].uid(), original.uid());
+        assert_eq!(duplicates[0].most_recent(), imported.url());
+        assert_eq!(duplicates[0].urls().count(), 2);
+
+        // Items that are marked for deletion are not duplicates any more
+        let mut items = items;
+        items.get_mut(imported.url()).unwrap().set_sync_status(SyncStatus::LocallyDeleted(VersionTag::from("tag".to_string())));
+        assert!(find_duplicates(&cal_url, items.iter()).is_empty());
+    }
+}
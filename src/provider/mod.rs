@@ -3,21 +3,49 @@
 //! It is also responsible for syncing them together
 
 use std::error::Error;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::borrow::Cow;
 use std::marker::PhantomData;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, RwLock};
 use std::fmt::{Display, Formatter};
 
 use url::Url;
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+use csscolorparser::Color;
 use itertools::Itertools;
+use futures_util::StreamExt;
 
 use crate::traits::{BaseCalendar, CalDavSource, DavCalendar};
 use crate::traits::CompleteCalendar;
-use crate::item::SyncStatus;
+use crate::Event;
+use crate::item::{Item, SyncStatus, Uid, VersionTag};
+use crate::task::{CompletionStatus, Task};
+use crate::calendar::{CalendarUrl, CollectionChanges, Privileges, SearchFilter, SupportedComponents};
+use crate::grid::MonthGrid;
+use crate::notification::Notification;
+use crate::scheduling::{self, ItipMessage};
+use crate::error::{OfflineError, ReadOnlyError, ServerError};
 
 pub mod sync_progress;
 use sync_progress::SyncProgress;
-use sync_progress::{FeedbackSender, SyncEvent};
+use sync_progress::{CancellationToken, FeedbackSender, SyncEvent, SyncObserver, SyncPhase};
+pub mod comparison;
+use comparison::{ComparisonRules, MostRecent};
+pub mod pending_changes;
+use pending_changes::{PendingChange, PendingChangeKind};
+pub mod sync_report;
+use sync_report::{Snapshot, SyncReport};
+pub mod conflict;
+use conflict::{Conflict, ConflictKind, ConflictOutcome, ConflictResolution, ResolvedConflict};
+pub mod sync_result;
+use sync_result::{ItemOperation, SyncDirection, SyncResult};
+pub mod sync_plan;
+use sync_plan::SyncPlan;
+pub mod calendar_selection;
+use calendar_selection::{CalendarFilter, CalendarSelection};
+pub mod duplicates;
+use duplicates::Duplicates;
 
 /// How many items will be batched in a single HTTP request when downloading from the server
 #[cfg(not(test))]
@@ -25,6 +53,99 @@ const DOWNLOAD_BATCH_SIZE: usize = 30;
 /// How many items will be batched in a single HTTP request when downloading from the server
 #[cfg(test)]
 const DOWNLOAD_BATCH_SIZE: usize = 3;
+/// How many batches are downloaded at the same time by default, see [`Provider::set_download_parallelism`]
+const DEFAULT_DOWNLOAD_PARALLELISM: usize = 4;
+
+/// What the sync of a pair of calendars needs: the calendars, and the settings of the provider (see `Provider::sync_context`)
+struct SyncContext<'a, T, U> {
+    local_handle: &'a RwLock<T>,
+    remote_handle: &'a RwLock<U>,
+    comparison_rules: &'a ComparisonRules,
+    conflict_resolution: &'a ConflictResolution,
+    window: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    download_parallelism: usize,
+    scheduling_address: Option<&'a str>,
+}
+
+/// What differs between the local and the remote versions of a calendar, see `Provider::find_differences`
+struct Differences {
+    /// Items that have been deleted locally, and that will be deleted from the server
+    local_del: HashSet<Url>,
+    /// Items that have been deleted from the server, and that will be deleted locally
+    remote_del: HashSet<Url>,
+    local_changes: HashSet<Url>,
+    remote_changes: HashSet<Url>,
+    local_additions: HashSet<Url>,
+    remote_additions: HashSet<Url>,
+    /// Items that may have been uploaded by an interrupted sync, and that must be checked against the server
+    interrupted_uploads: HashSet<Url>,
+    /// Items that have been changed on both ends, and the version tag they have on the server (if they still exist there)
+    conflicts: Vec<(Url, ConflictKind, Option<VersionTag>)>,
+    /// The new version tags of the evicted items (`None` for the ones that have been deleted from the server)
+    evicted_changes: Vec<(Url, Option<VersionTag>)>,
+    /// The sync token to save in case the sync succeeds
+    new_sync_token: Option<String>,
+}
+
+/// What the sync knows of a local calendar, read at the beginning of the sync of this calendar. \
+/// This lets the sync compare the calendar with the server without keeping it locked, and tell which items have been changed in the meantime
+struct LocalState {
+    url: Url,
+    name: String,
+    sync_token: Option<String>,
+    items: HashMap<Url, ItemState>,
+    evicted_items: HashMap<Url, VersionTag>,
+    rejections: HashMap<Url, String>,
+    /// Whether some items have been changed locally since this state has been read, and have been left as they were by the sync
+    changed_meanwhile: bool,
+}
+
+impl LocalState {
+    fn new<T: CompleteCalendar>(cal: &T) -> Self {
+        let items: HashMap<Url, ItemState> = cal.iter_items()
+            .map(|(url, item)| (url.clone(), ItemState::new(item)))
+            .collect();
+        let rejections = items.keys()
+            .filter_map(|url| cal.rejection(url).map(|rejection| (url.clone(), rejection.error().to_string())))
+            .collect();
+        Self {
+            url: cal.url().clone(),
+            name: cal.name().to_string(),
+            sync_token: cal.sync_token().map(String::from),
+            items,
+            evicted_items: cal.evicted_items().clone(),
+            rejections,
+            changed_meanwhile: false,
+        }
+    }
+
+    /// Whether `item` (the current local version of `url`) is the way it was when this state has been read
+    fn is_unchanged(&mut self, url: &Url, item: Option<&Item>) -> bool {
+        let unchanged = item.map(ItemState::new).as_ref() == self.items.get(url);
+        if unchanged == false {
+            self.changed_meanwhile = true;
+        }
+        unchanged
+    }
+}
+
+/// What tells whether a local item has been changed
+#[derive(Clone, Debug, PartialEq)]
+struct ItemState {
+    sync_status: SyncStatus,
+    last_modified: DateTime<Utc>,
+    is_event: bool,
+}
+
+impl ItemState {
+    fn new(item: &Item) -> Self {
+        Self {
+            sync_status: item.sync_status().clone(),
+            last_modified: *item.last_modified(),
+            is_event: item.is_event(),
+        }
+    }
+}
 
 // I am too lazy to actually make `fetch_and_apply` generic over an async closure.
 // Let's work around by passing an enum, so that `fetch_and_apply` will know what to do
@@ -43,10 +164,101 @@ impl Display for BatchDownloadType {
 }
 
 
+/// What completing a task does to its subtasks (i.e. the tasks that are `RELATED-TO` it), see [`Provider::set_task_completion`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SubtaskCompletionPolicy {
+    /// Tasks are completed independently of their subtasks
+    Independent,
+    /// Completing a task also completes its open subtasks
+    CompleteChildren,
+    /// A task cannot be completed while it has open subtasks
+    BlockWhileChildrenOpen,
+}
+
+impl Default for SubtaskCompletionPolicy {
+    fn default() -> Self {
+        Self::Independent
+    }
+}
+
+/// What a sync does to modified instances of recurring events that do not belong to any recurring event (see [`Event::is_orphaned_instance`] and [`Event::orphaned_overrides`]).
+///
+/// Instances that are stored in their own resource are reattached to their recurring event whenever it can be found in the same calendar, unless the policy is [`Self::Keep`].
+/// Repairs are made locally after a sync, and they are pushed to the server at the next sync
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OrphanedInstancePolicy {
+    /// Leave them as they are
+    Keep,
+    /// Only reattach them to their recurring event, when it can be found
+    Reattach,
+    /// Turn the instances that cannot be reattached into standalone events (with their own UIDs)
+    Promote,
+    /// Hide the instances that cannot be reattached from the views of the provider (e.g. [`Provider::grid`]). They are still listed by [`Provider::orphaned_instances`]
+    Quarantine,
+}
+
+impl Default for OrphanedInstancePolicy {
+    fn default() -> Self {
+        Self::Keep
+    }
+}
+
+/// The time range a sync pulls events for, relative to the time of the sync (e.g. from a month ago to a year from now), see [`Provider::set_sync_window`].
+///
+/// Tasks and journals are always synced, whatever their dates
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SyncWindow {
+    past: chrono::Duration,
+    future: chrono::Duration,
+}
+
+impl SyncWindow {
+    /// A window that starts `past` before the sync, and ends `future` after it
+    pub fn new(past: chrono::Duration, future: chrono::Duration) -> Self {
+        Self { past, future }
+    }
+
+    pub fn past(&self) -> chrono::Duration { self.past }
+    pub fn future(&self) -> chrono::Duration { self.future }
+
+    /// The (start, end) dates this window covers, for a sync that happens at `now`
+    pub fn range_at(&self, now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+        (now - self.past, now + self.future)
+    }
+}
+
+/// How a sync is run (see [`Provider::sync_with`]), e.g. `SyncOptions { cancellation: Some(token), ..Default::default() }`
+#[derive(Clone, Default)]
+pub struct SyncOptions {
+    /// Told what the sync is doing (e.g. "fetching item 12 of 80"), so that frontends can show a progress bar
+    pub observer: Option<Arc<dyn SyncObserver>>,
+    /// Stops the sync as soon as it is cancelled
+    pub cancellation: Option<CancellationToken>,
+    /// Only sync these calendars (or every selected calendar if this is `None`)
+    pub calendars: Option<Vec<Url>>,
+    /// Only compute what the sync would do, without changing anything, neither locally nor on the server
+    pub dry_run: bool,
+}
+
+impl std::fmt::Debug for SyncOptions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SyncOptions")
+            .field("observer", &self.observer.is_some())
+            .field("cancellation", &self.cancellation)
+            .field("calendars", &self.calendars)
+            .field("dry_run", &self.dry_run)
+            .finish()
+    }
+}
+
 /// A data source that combines two `CalDavSource`s, which is able to sync both sources.
 ///
 /// Usually, you will only need to use a provider between a server and a local cache, that is to say a [`CalDavProvider`](crate::CalDavProvider), i.e. a `Provider<Cache, CachedCalendar, Client, RemoteCalendar>`. \
 /// However, providers can be used for integration tests, where the remote source is mocked by a `Cache`.
+///
+/// Apps can keep using the calendars of the local source (see [`CalDavSource::get_calendars`]) while a sync is running in the background. Calendars are synced one at a time, and a sync:
+/// * only locks a local calendar for reading while it is listing the differences with the server (which is usually the longest part of a sync), so that it can still be read meanwhile,
+/// * then locks it for writing while the changes are applied. Changes that apps have made in the meantime are taken into account, and will not be overwritten.
 #[derive(Debug)]
 pub struct Provider<L, T, R, U>
 where
@@ -59,6 +271,26 @@ where
     remote: R,
     /// The local cache
     local: L,
+    /// The timezone that is used to compute views of the data (e.g. what "today" is, or which day an event belongs to)
+    display_timezone: Tz,
+    /// What completing a task does to its subtasks
+    subtask_policy: SubtaskCompletionPolicy,
+    /// What is ignored when telling whether an item has actually changed on the server
+    comparison_rules: ComparisonRules,
+    /// What syncs do to items that have been changed on both ends
+    conflict_resolution: ConflictResolution,
+    /// What syncs do to orphaned instances of recurring events
+    orphan_policy: OrphanedInstancePolicy,
+    /// The time range syncs pull events for (all of them if this is `None`)
+    sync_window: Option<SyncWindow>,
+    /// How many batches of items syncs download at the same time
+    download_parallelism: usize,
+    /// Which calendars syncs handle, on top of the [`CalendarSelection`] that is stored in `local`
+    calendar_filter: Option<CalendarFilter>,
+    /// The cal-address of the user, whose events syncs send iTIP messages for (none if this is `None`)
+    scheduling_address: Option<String>,
+    /// What the last sync (that was not a dry run) has done
+    last_sync_result: Option<SyncResult>,
 
     phantom_t: PhantomData<T>,
     phantom_u: PhantomData<U>,
@@ -74,9 +306,19 @@ where
     /// Create a provider.
     ///
     /// `remote` is usually a [`Client`](crate::client::Client), `local` is usually a [`Cache`](crate::cache::Cache).
-    /// However, both can be interchangeable. The only difference is that `remote` always wins in case of a sync conflict
+    /// However, both can be interchangeable. The only difference is that `remote` wins in case of a sync conflict, unless another [`ConflictResolution`] is set
     pub fn new(remote: R, local: L) -> Self {
         Self { remote, local,
+            display_timezone: crate::utils::system_timezone(),
+            subtask_policy: SubtaskCompletionPolicy::default(),
+            comparison_rules: ComparisonRules::strict(),
+            conflict_resolution: ConflictResolution::default(),
+            orphan_policy: OrphanedInstancePolicy::default(),
+            sync_window: None,
+            download_parallelism: DEFAULT_DOWNLOAD_PARALLELISM,
+            calendar_filter: None,
+            scheduling_address: None,
+            last_sync_result: None,
             phantom_t: PhantomData, phantom_u: PhantomData,
         }
     }
@@ -91,142 +333,1878 @@ where
     /// Usually, you should rather use the `local` source, which (usually) is a much faster local cache.
     /// To be sure `local` accurately mirrors the `remote` source, you can run [`Provider::sync`]
     pub fn remote(&self) -> &R { &self.remote }
+    /// Returns both data sources (`remote`, then `local`), e.g. to sync the same local cache with another remote source (see [`crate::webcal`])
+    pub fn into_parts(self) -> (R, L) { (self.remote, self.local) }
+
+    /// The timezone that is used by date-related views (such as [`Self::grid`]). \
+    /// This defaults to the timezone of the system (see [`crate::utils::system_timezone`])
+    pub fn display_timezone(&self) -> &Tz { &self.display_timezone }
+    /// Change the timezone that is used by date-related views
+    pub fn set_display_timezone(&mut self, timezone: Tz) {
+        self.display_timezone = timezone;
+    }
+
+    /// What completing a task does to its subtasks. This defaults to [`SubtaskCompletionPolicy::Independent`]
+    pub fn subtask_policy(&self) -> SubtaskCompletionPolicy { self.subtask_policy }
+    /// Change what completing a task does to its subtasks
+    pub fn set_subtask_policy(&mut self, policy: SubtaskCompletionPolicy) {
+        self.subtask_policy = policy;
+    }
+
+    /// What is ignored when telling whether an item has actually changed on the server, and how much clocks are trusted. This defaults to [`ComparisonRules::strict`]
+    pub fn comparison_rules(&self) -> &ComparisonRules { &self.comparison_rules }
+    /// Change what is ignored when telling whether an item has actually changed on the server, and how much clocks are trusted.
+    ///
+    /// This is useful for servers that rewrite items (e.g. by adding `X-` properties) every time they are fetched, which would otherwise be reported as changes at every sync,
+    /// or for devices whose clocks may be off
+    pub fn set_comparison_rules(&mut self, rules: ComparisonRules) {
+        self.comparison_rules = rules;
+    }
+
+    /// What syncs do to items that have been changed both locally and on the server since the last sync. This defaults to [`ConflictResolution::PreferRemote`]
+    pub fn conflict_resolution(&self) -> &ConflictResolution { &self.conflict_resolution }
+    /// Change what syncs do to items that have been changed both locally and on the server since the last sync.
+    ///
+    /// The conflicts a sync has resolved are listed by [`SyncResult::conflicts`]
+    pub fn set_conflict_resolution(&mut self, resolution: ConflictResolution) {
+        self.conflict_resolution = resolution;
+    }
+
+    /// What syncs do to orphaned instances of recurring events. This defaults to [`OrphanedInstancePolicy::Keep`]
+    pub fn orphan_policy(&self) -> OrphanedInstancePolicy { self.orphan_policy }
+    /// Change what syncs do to orphaned instances of recurring events
+    pub fn set_orphan_policy(&mut self, policy: OrphanedInstancePolicy) {
+        self.orphan_policy = policy;
+    }
+
+    /// The time range syncs pull events for. This defaults to `None`, i.e. every event is synced
+    pub fn sync_window(&self) -> Option<&SyncWindow> { self.sync_window.as_ref() }
+    /// Only pull the events that happen in a time range around the time of the sync (or every event if `window` is `None`).
+    ///
+    /// This saves listing (and downloading) the whole history of large calendars. Local copies of events that are not listed are left as they are,
+    /// they are only checked against the server when they have local changes to push. Their deletions from the server are only applied by syncs that
+    /// list every item (e.g. without a window), or by servers that support sync tokens
+    pub fn set_sync_window(&mut self, window: Option<SyncWindow>) {
+        self.sync_window = window;
+    }
+
+    /// How many batches of items syncs download at the same time. This defaults to 4
+    pub fn download_parallelism(&self) -> usize { self.download_parallelism }
+    /// Change how many batches of items syncs download at the same time (1 downloads them one after the other).
+    ///
+    /// Raising it makes syncs with high-latency servers much faster, at the cost of more simultaneous requests
+    pub fn set_download_parallelism(&mut self, parallelism: usize) {
+        self.download_parallelism = parallelism.max(1);
+    }
+
+    /// The calendars syncs handle. This defaults to [`CalendarSelection::All`]
+    pub fn calendar_selection(&self) -> CalendarSelection { self.local.calendar_selection() }
+    /// Change the calendars syncs handle (e.g. to only sync a few of the dozens of calendars that have been shared with the user).
+    ///
+    /// This is stored in the `local` source, so that it survives restarts of the app. Local copies of calendars that are no longer synced are kept as they are
+    pub fn set_calendar_selection(&mut self, selection: CalendarSelection) -> Result<(), crate::Error> {
+        self.local.set_calendar_selection(selection)
+    }
+    /// Sync a calendar at the next syncs (see [`Self::set_calendar_selection`])
+    pub fn include_calendar(&mut self, url: Url) -> Result<(), crate::Error> {
+        let mut selection = self.local.calendar_selection();
+        selection.include(url);
+        self.local.set_calendar_selection(selection)
+    }
+    /// Stop syncing a calendar (see [`Self::set_calendar_selection`])
+    pub fn exclude_calendar(&mut self, url: Url) -> Result<(), crate::Error> {
+        let mut selection = self.local.calendar_selection();
+        selection.exclude(url);
+        self.local.set_calendar_selection(selection)
+    }
+
+    /// The function that tells which calendars syncs handle, on top of the [`CalendarSelection`]. This defaults to `None`, i.e. every selected calendar is synced
+    pub fn calendar_filter(&self) -> Option<&CalendarFilter> { self.calendar_filter.as_ref() }
+    /// Only sync the calendars `filter` accepts (among the ones of the [`CalendarSelection`]). Unlike the selection, this is not stored
+    pub fn set_calendar_filter(&mut self, filter: Option<CalendarFilter>) {
+        self.calendar_filter = filter;
+    }
+
+    /// The cal-address of the user (e.g. `mailto:john@example.com`), whose events syncs send iTIP messages for. This defaults to `None`, i.e. syncs send no message
+    pub fn scheduling_address(&self) -> Option<&str> { self.scheduling_address.as_deref() }
+    /// Make syncs send iTIP messages (see [`crate::scheduling`]) about the events `address` organizes, when they push their creation, change or deletion to the server (or stop sending them if `address` is `None`).
+    ///
+    /// Messages are sent with [`CalDavSource::send_scheduling_message`] of the remote source, and the SEQUENCE of events is increased when their attendees must be told about a change. \
+    /// Only enable this for servers that do not schedule events by themselves, otherwise attendees would be invited twice. Pushing a change then also downloads the server version of the event, to tell what has changed
+    pub fn set_scheduling_address(&mut self, address: Option<String>) {
+        self.scheduling_address = address;
+    }
+
+    /// Whether syncs handle a calendar (see [`Self::set_calendar_selection`] and [`Self::set_calendar_filter`])
+    fn is_calendar_selected(&self, selection: &CalendarSelection, url: &Url, name: &str) -> bool {
+        selection.includes(url)
+            && self.calendar_filter.as_ref().map(|filter| filter.accepts(url, name)).unwrap_or(true)
+    }
+
+    /// What syncing `local_handle` with `remote_handle` needs, only syncing the items in `window` (if any)
+    fn sync_context<'a>(&'a self, local_handle: &'a RwLock<T>, remote_handle: &'a RwLock<U>, window: Option<(DateTime<Utc>, DateTime<Utc>)>) -> SyncContext<'a, T, U> {
+        SyncContext {
+            local_handle,
+            remote_handle,
+            comparison_rules: &self.comparison_rules,
+            conflict_resolution: &self.conflict_resolution,
+            window,
+            download_parallelism: self.download_parallelism,
+            scheduling_address: self.scheduling_address.as_deref(),
+        }
+    }
+
+    /// What the last sync (if any) has done (see [`Self::sync_with`])
+    pub fn last_sync_result(&self) -> Option<&SyncResult> { self.last_sync_result.as_ref() }
+
+    /// What the last sync (if any) has brought from the server: new events, tasks completed remotely, deleted items, etc.
+    ///
+    /// This is meant to be shown to end users (e.g. as a digest notification, see [`SyncReport::digest`])
+    pub fn last_sync_report(&self) -> Option<&SyncReport> { self.last_sync_result.as_ref().map(SyncResult::report) }
+
+    /// The current date, in the display timezone
+    pub fn today(&self) -> NaiveDate {
+        Utc::now().with_timezone(&self.display_timezone).date().naive_local()
+    }
+
+    /// Returns the events of every `local` calendar that happen during a given month, bucketed per day in the display timezone.
+    ///
+    /// Events that span over several days are split, so that every day they cover gets its own entry
+    pub async fn grid(&self, year: i32, month: u32) -> Result<MonthGrid, crate::Error> {
+        let mut grid = MonthGrid::new(year, month, self.display_timezone)
+            .ok_or_else(|| crate::Error::InvalidOperation(format!("Invalid month {}-{}", year, month)))?;
+
+        for (cal_url, cal) in self.local.get_calendars().await? {
+            let cal = cal.read().unwrap();
+            for (_url, item) in cal.iter_items_filtered(SearchFilter::Events) {
+                if let SyncStatus::LocallyDeleted(_) = item.sync_status() {
+                    continue;
+                }
+                if let Some(item) = self.visible_item(item) {
+                    if let Item::Event(event) = item.as_ref() {
+                        grid.add_event(&cal_url, cal.privileges(), event);
+                    }
+                }
+            }
+        }
+
+        grid.sort();
+        Ok(grid)
+    }
+
+    /// Returns the reminders of every `local` item that fire between `from` (included) and `until` (excluded), sorted by fire time.
+    ///
+    /// Dates in the notification bodies are written in the display timezone (see [`crate::notification`])
+    pub async fn notifications_between(&self, from: DateTime<Utc>, until: DateTime<Utc>) -> Result<Vec<Notification>, crate::Error> {
+        let mut notifications = Vec::new();
+        for (_cal_url, cal) in self.local.get_calendars().await? {
+            let cal = cal.read().unwrap();
+            let items: Vec<Cow<Item>> = cal.iter_items()
+                .filter_map(|(_url, item)| self.visible_item(item))
+                .collect();
+            notifications.extend(crate::notification::notifications_between(
+                items.iter().map(|item| item.as_ref()), from, until, &self.display_timezone
+            ));
+        }
+        notifications.sort_by(|a, b| a.fire_time().cmp(b.fire_time()));
+        Ok(notifications)
+    }
+
+    /// Returns the modified instances of recurring events that do not belong to any recurring event, in every `local` calendar
+    /// (see [`Event::is_orphaned_instance`] and [`Event::orphaned_overrides`])
+    pub async fn orphaned_instances(&self) -> Result<Vec<Event>, crate::Error> {
+        let mut orphans = Vec::new();
+        for (_cal_url, cal) in self.local.get_calendars().await? {
+            let cal = cal.read().unwrap();
+            for (_url, item) in cal.iter_items_filtered(SearchFilter::Events) {
+                if let Item::Event(event) = item {
+                    if let SyncStatus::LocallyDeleted(_) = event.sync_status() {
+                        continue;
+                    }
+                    match event.is_orphaned_instance() {
+                        true => orphans.extend(event.clone().into_instances()),
+                        false => orphans.extend(event.orphaned_overrides().into_iter().cloned()),
+                    }
+                }
+            }
+        }
+        Ok(orphans)
+    }
+
+    /// The version of an item that views should show, according to the [`OrphanedInstancePolicy`] of this provider
+    fn visible_item<'a>(&self, item: &'a Item) -> Option<Cow<'a, Item>> {
+        let event = match (self.orphan_policy, item) {
+            (OrphanedInstancePolicy::Quarantine, Item::Event(event)) => event,
+            _ => return Some(Cow::Borrowed(item)),
+        };
+        if event.is_orphaned_instance() {
+            return None;
+        }
+        let orphaned: Vec<DateTime<Utc>> = event.orphaned_overrides().iter()
+            .filter_map(|instance| instance.recurrence_id().cloned())
+            .collect();
+        if orphaned.is_empty() {
+            return Some(Cow::Borrowed(item));
+        }
+        let mut visible = event.clone();
+        for recurrence_id in &orphaned {
+            visible.remove_override(recurrence_id);
+        }
+        Some(Cow::Owned(Item::Event(visible)))
+    }
 
     /// Performs a synchronisation between `local` and `remote`, and provide feeedback to the user about the progress.
     ///
     /// This bidirectional sync applies additions/deletions made on a source to the other source.
-    /// In case of conflicts (the same item has been modified on both ends since the last sync, `remote` always wins).
+    /// In case of conflicts (the same item has been modified on both ends since the last sync), the [`ConflictResolution`] of this provider tells which version wins (`remote` by default).
     ///
     /// It returns whether the sync was totally successful (details about errors are logged using the `log::*` macros).
     /// In case errors happened, the sync might have been partially executed but your data will never be correupted (either locally nor in the server).
     /// Simply run this function again, it will re-start a sync, picking up where it failed.
     pub async fn sync_with_feedback(&mut self, feedback_sender: FeedbackSender) -> bool {
         let mut progress = SyncProgress::new_with_feedback_channel(feedback_sender);
-        self.run_sync(&mut progress).await
+        self.run_sync(&mut progress, None).await.is_success()
+    }
+
+    /// Performs a synchronisation between `local` and `remote`, and tell `observer` what it is doing (e.g. "fetching item 12 of 80"), so that frontends can show a progress bar.
+    ///
+    /// See [`Self::sync_with_feedback`], and [`Self::sync_with`] to also get what the sync has done
+    pub async fn sync_with_observer(&mut self, observer: Arc<dyn SyncObserver>) -> bool {
+        self.sync_with(SyncOptions { observer: Some(observer), ..Default::default() }).await.is_success()
+    }
+
+    /// Performs a synchronisation between `local` and `remote`, that stops as soon as `token` is cancelled.
+    ///
+    /// Cancelled syncs stop between two network operations, so that local items are never left half-synced: what has not been synced yet will be at the next sync.
+    /// They return `false`, just like syncs that fail. See [`Self::sync_with_feedback`]
+    pub async fn sync_with_cancellation(&mut self, token: CancellationToken) -> bool {
+        self.sync_with(SyncOptions { cancellation: Some(token), ..Default::default() }).await.is_success()
     }
 
     /// Performs a synchronisation between `local` and `remote`, without giving any feedback.
     ///
-    /// See [`Self::sync_with_feedback`]
-    pub async fn sync(&mut self) -> bool {
-        let mut progress = SyncProgress::new();
-        self.run_sync(&mut progress).await
+    /// See [`Self::sync_with_feedback`]
+    pub async fn sync(&mut self) -> bool {
+        self.sync_with(SyncOptions::default()).await.is_success()
+    }
+
+    /// Performs a synchronisation of some calendars only, without giving any feedback.
+    ///
+    /// See [`Self::sync_with_feedback`]
+    pub async fn sync_calendars(&mut self, calendars: &[Url]) -> bool {
+        self.sync_with(SyncOptions { calendars: Some(calendars.to_vec()), ..Default::default() }).await.is_success()
+    }
+
+    /// Performs a synchronisation between `local` and `remote` as told by `options`, and returns what it has done to every calendar: the items that have been synced (and which way),
+    /// the conflicts, the items that could not be synced (with the reason why), and what has been brought from the server.
+    ///
+    /// * An `observer` is told what the sync is doing, so that frontends can show a progress bar.
+    /// * Once its `cancellation` token is cancelled, the sync stops between two network operations, so that local items are never left half-synced:
+    ///   what has not been synced yet will be at the next sync. Cancelled syncs are not successful, just like syncs that fail.
+    /// * A `dry_run` computes what a sync would do (e.g. to ask for a confirmation before destructive syncs, see [`SyncResult::is_destructive`]), without changing anything.
+    ///   Conflicts are not submitted to [`ConflictResolution::Custom`] functions, they are listed as undecided instead. Note that the server may still change before the actual sync
+    ///
+    /// See [`Self::sync_with_feedback`]
+    pub async fn sync_with(&mut self, options: SyncOptions) -> SyncResult {
+        let mut progress = match options.observer {
+            Some(observer) => SyncProgress::new_with_observer(observer),
+            None => SyncProgress::new(),
+        };
+        if let Some(token) = options.cancellation {
+            progress = progress.with_cancellation(token);
+        }
+        let only: Option<HashSet<Url>> = options.calendars.map(|urls| urls.into_iter().collect());
+        match options.dry_run {
+            false => self.run_sync(&mut progress, only.as_ref()).await,
+            true => self.run_dry_sync(&mut progress, only.as_ref()).await,
+        }
+    }
+
+    /// Compute what a sync would do (what would be uploaded, downloaded and deleted, and how conflicts would be resolved), without changing anything, neither locally nor on the server.
+    ///
+    /// This is useful to ask for a confirmation before destructive syncs (see [`SyncPlan::is_destructive`]). Note that the server may still change before the actual sync
+    pub async fn sync_plan(&self) -> Result<SyncPlan, crate::Error> {
+        let mut progress = SyncProgress::new();
+        self.run_dry_sync_inner(&mut progress, None).await?;
+        Ok(SyncPlan::from_dry_run(progress.result()))
+    }
+
+    /// Compute what a sync would do, without changing anything, neither locally nor on the server
+    async fn run_dry_sync(&self, progress: &mut SyncProgress, only: Option<&HashSet<Url>>) -> SyncResult {
+        if let Err(err) = self.run_dry_sync_inner(progress, only).await {
+            progress.error(&format!("Unable to tell what a sync would do: {}", err));
+            progress.result_mut().set_error(err.to_string());
+        }
+        progress.phase(SyncPhase::Finished{ success: progress.is_success() });
+        let success = progress.is_success();
+        let result = progress.result_mut();
+        result.set_success(success);
+        result.set_dry_run(true);
+        result.clone()
+    }
+
+    async fn run_dry_sync_inner(&self, progress: &mut SyncProgress, only: Option<&HashSet<Url>>) -> Result<(), Box<dyn Error>> {
+        progress.phase(SyncPhase::ListingCalendars);
+        let window = self.sync_window.map(|w| w.range_at(Utc::now()));
+        let cals_local = self.local.get_calendars().await?;
+        let cals_remote = self.remote.get_calendars().await?;
+        let selection = self.local.calendar_selection();
+
+        for (cal_url, remote_handle) in &cals_remote {
+            progress.check_cancelled()?;
+            let remote_name = remote_handle.read().unwrap().name().to_string();
+            if only.map(|set| set.contains(cal_url)) == Some(false) || self.is_calendar_selected(&selection, cal_url, &remote_name) == false {
+                continue;
+            }
+            progress.calendar_started(cal_url, &remote_name);
+            match cals_local.get(cal_url) {
+                None => {
+                    // This calendar would be created locally, with every remote item
+                    let remote_items = {
+                        let cal_remote = remote_handle.read().unwrap();
+                        match window {
+                            None => cal_remote.get_item_version_tags().await?,
+                            Some((start, end)) => cal_remote.get_item_version_tags_between(start, end).await?,
+                        }
+                    };
+                    for url in remote_items.keys() {
+                        progress.item_synced(url, ItemOperation::Added, SyncDirection::Pulled);
+                    }
+                },
+                Some(local_handle) => {
+                    let local_state = LocalState::new(&*local_handle.read().unwrap());
+                    progress.phase(SyncPhase::ListingItems{ calendar: local_state.name.clone() });
+                    let differences = {
+                        let cal_remote = remote_handle.read().unwrap();
+                        Self::find_differences(&local_state, &*cal_remote, window, progress).await?
+                    };
+                    Self::plan_differences(differences, &self.sync_context(local_handle, remote_handle, window), progress).await;
+                },
+            }
+        }
+
+        for (cal_url, cal_local) in &cals_local {
+            if cals_remote.contains_key(cal_url) || only.map(|set| set.contains(cal_url)) == Some(false) {
+                continue;
+            }
+            // This calendar would be created on the server, with every local item
+            let cal_local = cal_local.read().unwrap();
+            if self.is_calendar_selected(&selection, cal_url, cal_local.name()) == false {
+                continue;
+            }
+            progress.calendar_started(cal_url, cal_local.name());
+            for (url, item) in cal_local.iter_items() {
+                if matches!(item.sync_status(), SyncStatus::LocallyDeleted(_)) == false {
+                    progress.item_synced(url, ItemOperation::Added, SyncDirection::Pushed);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Run a sync that has been requested by a call to a [`WebhookServer`](crate::webhook::WebhookServer)
+    #[cfg(feature = "webhook")]
+    pub async fn handle_sync_request(&mut self, request: &crate::webhook::SyncRequest) -> bool {
+        match request {
+            crate::webhook::SyncRequest::All => self.sync().await,
+            crate::webhook::SyncRequest::Calendars(urls) => self.sync_calendars(urls).await,
+        }
+    }
+
+    /// Create a calendar in the `local` source, at a URL chosen by the `remote` source (see [`CalDavSource::new_calendar_url`]).
+    ///
+    /// Items can be added to it right away. The calendar (and its items) will be created in the `remote` source at the next sync (e.g. with a `MKCALENDAR` request for a CalDAV server)
+    pub async fn create_calendar(&mut self, name: String, color: Option<Color>, supported_components: SupportedComponents) -> Result<Arc<RwLock<T>>, crate::Error> {
+        let url = self.remote.new_calendar_url().await?;
+        self.local.create_calendar(url, name, supported_components, color).await
+    }
+
+    /// Fetch the current version of an item from the `remote` source, without applying it to the `local` source.
+    ///
+    /// This is useful to show conflict dialogs ("server version vs my version"): compare the [`VersionTag`](crate::item::VersionTag) of the returned item
+    /// with the last-known one of the local item (see [`Item::last_known_version_tag`]) to tell whether it has been modified on the server since the last sync.
+    ///
+    /// This returns `Ok(None)` in case the item does not exist in the `remote` source (any more)
+    pub async fn fetch_remote(&self, item_url: &Url) -> Result<Option<Item>, crate::Error> {
+        let cal_url = self.calendar_url_of(item_url).await?
+            .ok_or_else(|| crate::Error::NotFound(item_url.clone()))?;
+        let cal_remote = self.remote.get_calendar(&cal_url).await
+            .ok_or_else(|| crate::Error::NotFound(cal_url.clone()))?;
+        let cal_remote = cal_remote.read().unwrap();
+        cal_remote.get_item_by_url(item_url).await
+    }
+
+    /// Returns the local changes that will be pushed to the server at the next sync, so that they can be reviewed (and possibly discarded, see [`Self::discard_local_change`]).
+    ///
+    /// This only reads the sync statuses of the local items (locally deleted items are kept until their deletion is pushed), so that this also works offline.
+    /// See [`Self::changed_properties`] to compare a modified item to its version on the server
+    pub async fn pending_changes(&self) -> Result<Vec<PendingChange>, crate::Error> {
+        let mut changes = Vec::new();
+        for (cal_url, cal_local) in self.local.get_calendars().await? {
+            let cal_local = cal_local.read().unwrap();
+            for (_url, item) in cal_local.iter_items() {
+                let kind = match item.sync_status() {
+                    SyncStatus::NotSynced => PendingChangeKind::Addition,
+                    SyncStatus::LocallyModified(_) => PendingChangeKind::Modification,
+                    SyncStatus::LocallyDeleted(_) => PendingChangeKind::Deletion,
+                    SyncStatus::Synced(_) => continue,
+                };
+                changes.push(PendingChange::new(cal_url.clone(), kind, item.clone()));
+            }
+        }
+        Ok(changes)
+    }
+
+    /// The names of the iCal properties (e.g. `SUMMARY`, `DUE`) that differ between the local version of an item and its current version on the server, that is downloaded.
+    /// Properties that are ignored by the [`ComparisonRules`] of this provider are not reported.
+    ///
+    /// This is empty for items that are not on the server (any more)
+    pub async fn changed_properties(&self, item_url: &Url) -> Result<Vec<String>, crate::Error> {
+        let cal_url = self.calendar_url_of(item_url).await?
+            .ok_or_else(|| crate::Error::NotFound(item_url.clone()))?;
+        let cal_local = self.local.get_calendar(&cal_url).await
+            .ok_or_else(|| crate::Error::NotFound(cal_url.clone()))?;
+        let local_item = cal_local.read().unwrap().get_item_by_url(item_url).await
+            .cloned()
+            .ok_or_else(|| crate::Error::NotFound(item_url.clone()))?;
+
+        match self.fetch_remote(item_url).await? {
+            None => Ok(Vec::new()),
+            Some(remote_item) => Ok(self.comparison_rules.changed_properties(&remote_item, &local_item)?),
+        }
+    }
+
+    /// Revert the local change of an item (see [`Self::pending_changes`]), so that it is not pushed to the server at the next sync.
+    ///
+    /// Items that have been locally created are deleted. Items that have been locally modified or deleted are restored to their current version on the server
+    /// (or deleted, in case they have been deleted from the server in the meantime)
+    pub async fn discard_local_change(&self, item_url: &Url) -> Result<(), crate::Error> {
+        let cal_url = self.calendar_url_of(item_url).await?
+            .ok_or_else(|| crate::Error::NotFound(item_url.clone()))?;
+        let cal_local = self.local.get_calendar(&cal_url).await
+            .ok_or_else(|| crate::Error::NotFound(cal_url.clone()))?;
+        let sync_status = cal_local.read().unwrap().get_item_by_url(item_url).await
+            .map(|item| item.sync_status().clone())
+            .ok_or_else(|| crate::Error::NotFound(item_url.clone()))?;
+
+        match sync_status {
+            SyncStatus::Synced(_) => Err(crate::Error::InvalidOperation(format!("Item {} has no local change to discard", item_url))),
+            SyncStatus::NotSynced => cal_local.write().unwrap().immediately_delete_item(item_url).await,
+            SyncStatus::LocallyModified(_) | SyncStatus::LocallyDeleted(_) => {
+                let remote_item = self.fetch_remote(item_url).await?;
+                let mut cal_local = cal_local.write().unwrap();
+                match remote_item {
+                    None => cal_local.immediately_delete_item(item_url).await,
+                    Some(item) => cal_local.update_item(item).await.map(|_| ()),
+                }
+            },
+        }
+    }
+
+    /// Move an item of the `local` source to another of its calendars, keeping its UID. This returns the new URL of the item.
+    ///
+    /// The item is moved locally right away. The next sync moves it on the server as well,
+    /// or deletes it from its former calendar then creates it again in its new one, in case the server cannot move it
+    pub async fn move_item(&mut self, item_url: &Url, target_calendar: &Url) -> Result<Url, crate::Error> {
+        let source_calendar = self.calendar_url_of(item_url).await?
+            .ok_or_else(|| crate::Error::NotFound(item_url.clone()))?;
+        if &source_calendar == target_calendar {
+            return Ok(item_url.clone());
+        }
+        let cal_source = self.local.get_calendar(&source_calendar).await
+            .ok_or_else(|| crate::Error::NotFound(source_calendar.clone()))?;
+        let cal_target = self.local.get_calendar(target_calendar).await
+            .ok_or_else(|| crate::Error::NotFound(target_calendar.clone()))?;
+
+        let mut item = cal_source.read().unwrap().get_item_by_url(item_url).await
+            .cloned()
+            .ok_or_else(|| crate::Error::NotFound(item_url.clone()))?;
+        let is_on_server = match item.sync_status() {
+            SyncStatus::LocallyDeleted(_) => return Err(crate::Error::InvalidOperation(format!("Item {} has been deleted", item_url))),
+            SyncStatus::NotSynced => false,
+            SyncStatus::Synced(_) | SyncStatus::LocallyModified(_) => true,
+        };
+        {
+            let cal_target = cal_target.read().unwrap();
+            let supported = match &item {
+                Item::Event(_) => cal_target.supports_events(),
+                Item::Task(_) => cal_target.supports_todo(),
+                Item::Journal(_) => cal_target.supports_journals(),
+                Item::Contact(_) => cal_target.supports_contacts(),
+            };
+            if supported == false {
+                return Err(crate::Error::InvalidOperation(format!("Calendar {} cannot hold item {}", target_calendar, item_url)));
+            }
+        }
+
+        let new_url = CalendarUrl::from(target_calendar.clone()).random_item_url();
+        item.set_url(new_url.clone());
+        item.set_sync_status(SyncStatus::NotSynced);
+        cal_target.write().unwrap().add_item(item).await?;
+        cal_source.write().unwrap().mark_for_deletion(item_url).await?;
+
+        // In case this item has already been moved since the last sync, only its URL on the server and its latest URL matter
+        let origin = self.local.pending_moves().into_iter()
+            .find(|(_from, to)| to == item_url)
+            .map(|(from, _to)| from);
+        match (origin, is_on_server) {
+            (Some(origin), _) => self.local.record_move(origin, new_url.clone()),
+            (None, true) => self.local.record_move(item_url.clone(), new_url.clone()),
+            (None, false) => {},
+        }
+        Ok(new_url)
+    }
+
+    /// Returns the items that share the same UID, in every `local` calendar. This usually happens after an import went wrong.
+    ///
+    /// Every sync also reports them (see [`sync_result::CalendarResult::duplicates`])
+    pub async fn find_duplicates(&self) -> Result<Vec<Duplicates>, crate::Error> {
+        let mut duplicates = Vec::new();
+        for (cal_url, cal) in self.local.get_calendars().await? {
+            let cal = cal.read().unwrap();
+            duplicates.extend(duplicates::find_duplicates(&cal_url, cal.iter_items()));
+        }
+        Ok(duplicates)
+    }
+
+    /// Only keep one of the items that share the same UID (e.g. [`Duplicates::most_recent`]). The other ones are marked for deletion, and will be deleted from the server at the next sync
+    pub async fn merge_duplicates(&self, duplicates: &Duplicates, keep: &Url) -> Result<(), crate::Error> {
+        if duplicates.contains(keep) == false {
+            return Err(crate::Error::InvalidOperation(format!("Item {} is not one of the duplicates of UID {}", keep, duplicates.uid().as_str())));
+        }
+        let cal = self.local.get_calendar(duplicates.calendar_url()).await
+            .ok_or_else(|| crate::Error::NotFound(duplicates.calendar_url().clone()))?;
+        let mut cal = cal.write().unwrap();
+        for url in duplicates.urls().filter(|url| *url != keep) {
+            // Some of them may have been deleted since they have been found
+            if cal.get_item_by_url(url).await.is_some() {
+                cal.mark_for_deletion(url).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Complete (or un-complete) a task of the `local` source, applying the [`SubtaskCompletionPolicy`] of this provider to its subtasks.
+    ///
+    /// Every resulting modification is applied at once, so that they are all pushed to the server during the next sync.
+    /// This returns the URLs of the tasks that have been modified
+    pub async fn set_task_completion(&self, task_url: &Url, completed: bool) -> Result<Vec<Url>, crate::Error> {
+        let cal_url = self.calendar_url_of(task_url).await?
+            .ok_or_else(|| crate::Error::NotFound(task_url.clone()))?;
+        let cal_local = self.local.get_calendar(&cal_url).await
+            .ok_or_else(|| crate::Error::NotFound(cal_url.clone()))?;
+        let mut cal_local = cal_local.write().unwrap();
+
+        let mut task = match cal_local.get_item_by_url(task_url).await {
+            Some(Item::Task(task)) => task.clone(),
+            Some(_) => return Err(crate::Error::InvalidOperation(format!("Item {} is not a task", task_url))),
+            None => return Err(crate::Error::NotFound(task_url.clone())),
+        };
+        let mut subtasks: Vec<Task> = task.subtasks(
+                cal_local.iter_items()
+                    .filter(|(_url, item)| matches!(item.sync_status(), SyncStatus::LocallyDeleted(_)) == false)
+                    .filter_map(|(_url, item)| match item {
+                        Item::Task(t) => Some(t),
+                        _ => None,
+                    })
+            )
+            .into_iter()
+            .cloned()
+            .collect();
+
+        let mut modified_subtasks = Vec::new();
+        if completed == false {
+            task.set_completion_status(CompletionStatus::Uncompleted);
+        } else {
+            match self.subtask_policy {
+                SubtaskCompletionPolicy::Independent => {
+                    task.set_completion_status(CompletionStatus::Completed(Some(Utc::now())));
+                },
+                SubtaskCompletionPolicy::BlockWhileChildrenOpen => {
+                    let open_subtasks = subtasks.iter().filter(|t| t.completed() == false).count();
+                    if open_subtasks > 0 {
+                        return Err(crate::Error::InvalidOperation(format!("Task {} cannot be completed while it has {} open subtask(s)", task_url, open_subtasks)));
+                    }
+                    task.set_completion_status(CompletionStatus::Completed(Some(Utc::now())));
+                },
+                SubtaskCompletionPolicy::CompleteChildren => {
+                    modified_subtasks = task.complete_with_children(subtasks.iter_mut());
+                },
+            }
+        }
+
+        let mut modified = vec![task_url.clone()];
+        cal_local.update_item(Item::Task(task)).await?;
+        for subtask in subtasks.into_iter().filter(|t| modified_subtasks.contains(t.url())) {
+            modified.push(subtask.url().clone());
+            cal_local.update_item(Item::Task(subtask)).await?;
+        }
+        Ok(modified)
+    }
+
+    /// Make sure the content of an item is available in the `local` source.
+    ///
+    /// In case it has been evicted (see [`CompleteCalendar::evict_item`]), it is downloaded again from the `remote` source.
+    /// Evicted items are absent from local calendars until then, even from their listings (e.g. [`CompleteCalendar::get_items`]), unless these calendars read them from their storage (e.g. [`PersistentCalendar`](crate::persistent_cache::PersistentCalendar)).
+    /// This returns an [`Error::Offline`](crate::Error::Offline) in case the server cannot be reached
+    pub async fn ensure_loaded(&self, item_url: &Url) -> Result<(), crate::Error> {
+        for (cal_url, cal_local) in self.local.get_calendars().await? {
+            {
+                let cal_local = cal_local.read().unwrap();
+                if cal_local.get_item_by_url(item_url).await.is_some() {
+                    return Ok(());
+                }
+                if cal_local.evicted_items().contains_key(item_url) == false {
+                    continue;
+                }
+            }
+
+            let offline = |err: crate::Error| -> crate::Error {
+                match OfflineError::is_unreachable(&err) {
+                    true => OfflineError::new(item_url.clone(), err.to_string()).into(),
+                    false => err,
+                }
+            };
+            let cal_remote = self.remote.get_calendars().await.map_err(offline)?
+                .remove(&cal_url)
+                .ok_or_else(|| crate::Error::NotFound(cal_url.clone()))?;
+            let remote_item = cal_remote.read().unwrap().get_item_by_url(item_url).await.map_err(offline)?;
+
+            let mut cal_local = cal_local.write().unwrap();
+            return match remote_item {
+                None => {
+                    cal_local.update_evicted_item(item_url, None);
+                    Err(crate::Error::NotFound(item_url.clone()))
+                },
+                Some(item) => {
+                    cal_local.add_item(item).await?;
+                    Ok(())
+                },
+            };
+        }
+        Err(crate::Error::NotFound(item_url.clone()))
+    }
+
+    /// Returns an item of the `local` source, downloading its content in case it has been evicted (see [`Self::ensure_loaded`])
+    pub async fn load_item(&self, item_url: &Url) -> Result<Item, crate::Error> {
+        self.ensure_loaded(item_url).await?;
+        let cal_url = self.calendar_url_of(item_url).await?
+            .ok_or_else(|| crate::Error::NotFound(item_url.clone()))?;
+        let cal_local = self.local.get_calendar(&cal_url).await
+            .ok_or_else(|| crate::Error::NotFound(cal_url.clone()))?;
+        let cal_local = cal_local.read().unwrap();
+        cal_local.get_item_by_url(item_url).await
+            .cloned()
+            .ok_or_else(|| crate::Error::NotFound(item_url.clone()))
+    }
+
+    /// Find the calendar an item belongs to: the local calendar that contains it, or the remote calendar its URL is a child of
+    async fn calendar_url_of(&self, item_url: &Url) -> Result<Option<Url>, Box<dyn Error>> {
+        for (cal_url, cal_local) in self.local.get_calendars().await? {
+            if cal_local.read().unwrap().get_item_by_url(item_url).await.is_some() {
+                return Ok(Some(cal_url));
+            }
+        }
+        let cal_url = self.remote.get_calendars().await?
+            .keys()
+            .find(|cal_url| item_url.as_str().starts_with(cal_url.as_str()))
+            .cloned();
+        Ok(cal_url)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "sync", skip_all))]
+    async fn run_sync(&mut self, progress: &mut SyncProgress, only: Option<&HashSet<Url>>) -> SyncResult {
+        let before = self.local_snapshot(only).await;
+        if let Err(err) = self.run_sync_inner(progress, only).await {
+            progress.error(&format!("Sync terminated because of an error: {}", err));
+            progress.result_mut().set_error(err.to_string());
+        }
+        let after = self.local_snapshot(only).await;
+        progress.feedback(SyncEvent::Finished{ success: progress.is_success() });
+        progress.phase(SyncPhase::Finished{ success: progress.is_success() });
+        let success = progress.is_success();
+        let result = progress.result_mut();
+        result.set_success(success);
+        let conflicts = result.conflicts().cloned().collect();
+        result.set_report(SyncReport::new(success, &before, &after, conflicts));
+        self.last_sync_result = Some(result.clone());
+        result.clone()
+    }
+
+    /// The state of the local items of every calendar (or only the ones in `only`), to tell what a sync has changed
+    async fn local_snapshot(&self, only: Option<&HashSet<Url>>) -> Snapshot {
+        let mut snapshot = Snapshot::new();
+        let cals = match self.local.get_calendars().await {
+            Ok(cals) => cals,
+            Err(err) => {
+                log::warn!("Unable to list the local calendars: {}", err);
+                return snapshot;
+            },
+        };
+        for (cal_url, cal) in cals {
+            if only.map(|set| set.contains(&cal_url)) == Some(false) {
+                continue;
+            }
+            let cal = cal.read().unwrap();
+            for (_url, item) in cal.iter_items() {
+                snapshot.add(&cal_url, item);
+            }
+        }
+        snapshot
+    }
+
+    /// Sync every calendar, or only the ones in `only`
+    async fn run_sync_inner(&mut self, progress: &mut SyncProgress, only: Option<&HashSet<Url>>) -> Result<(), Box<dyn Error>> {
+        progress.info("Starting a sync.");
+        progress.feedback(SyncEvent::Started);
+        progress.phase(SyncPhase::ListingCalendars);
+
+        let window = self.sync_window.map(|w| w.range_at(Utc::now()));
+        let selection = self.local.calendar_selection();
+
+        // Push the deletions of local calendars
+        let deleted_calendars = self.local.deleted_calendars();
+        for cal_url in &deleted_calendars {
+            progress.check_cancelled()?;
+            if only.map(|set| set.contains(cal_url)) == Some(false) {
+                continue;
+            }
+            progress.debug(&format!("> Pushing the deletion of calendar {} to the server", cal_url));
+            match self.remote.delete_calendar(cal_url).await {
+                Err(err) if is_already_deleted(&err) == false => {
+                    progress.warn(&format!("Unable to delete remote calendar {}: {}. Will retry at the next sync", cal_url, err));
+                    progress.result_mut().calendar_failed(cal_url, err.to_string());
+                },
+                _ => self.local.forget_deleted_calendar(cal_url),
+            }
+        }
+
+        self.push_moves(only, progress).await?;
+
+        // Sync every remote calendar
+        let cals_remote = self.remote.get_calendars().await?;
+        // Calendars that are on the server must never be deleted locally, even if their sync fails (e.g. because of a network error)
+        let remote_cal_urls: HashSet<Url> = cals_remote.keys().cloned().collect();
+        for (cal_url, cal_remote) in cals_remote {
+            progress.check_cancelled()?;
+            if only.map(|set| set.contains(&cal_url)) == Some(false) {
+                continue;
+            }
+            if deleted_calendars.contains(&cal_url) {
+                // Its deletion could not be pushed. It must not be downloaded again
+                continue;
+            }
+            let cal_name = cal_remote.read().unwrap().name().to_string();
+            if self.is_calendar_selected(&selection, &cal_url, &cal_name) == false {
+                progress.debug(&format!("Calendar {} is not selected for syncs, skipping it", cal_name));
+                continue;
+            }
+            let counterpart = match self.get_or_insert_local_counterpart_calendar(&cal_url, cal_remote.clone()).await {
+                Err(err) => {
+                    progress.warn(&format!("Unable to get or insert local counterpart calendar for {} ({}). Skipping this time", cal_url, err));
+                    progress.result_mut().calendar_failed(&cal_url, err.to_string());
+                    continue;
+                },
+                Ok(arc) => arc,
+            };
+
+            let mut outbox = Vec::new();
+            let synced = Self::sync_calendar_pair(&self.sync_context(&counterpart, &cal_remote, window), &mut outbox, progress).await;
+            self.send_scheduling_messages(outbox, progress).await;
+            if let Err(err) = synced {
+                progress.warn(&format!("Unable to sync calendar {}: {}, skipping this time.", cal_url, err));
+                progress.result_mut().calendar_failed(&cal_url, err.to_string());
+                continue;
+            }
+        }
+
+        // Sync every local calendar that would not be in the remote yet
+        let cals_local = self.local.get_calendars().await?;
+        for (cal_url, cal_local) in cals_local {
+            progress.check_cancelled()?;
+            if remote_cal_urls.contains(&cal_url) || only.map(|set| set.contains(&cal_url)) == Some(false) {
+                continue;
+            }
+            let cal_name = cal_local.read().unwrap().name().to_string();
+            if self.is_calendar_selected(&selection, &cal_url, &cal_name) == false {
+                continue;
+            }
+
+            if Self::has_been_synced(&*cal_local.read().unwrap()) {
+                // This calendar has been deleted from the server (local changes it may contain are lost, just like for remote deletions of items)
+                progress.info(&format!("Calendar {} has been deleted from the server, deleting it locally", cal_url));
+                match self.local.delete_calendar(&cal_url).await {
+                    Err(err) => {
+                        progress.warn(&format!("Unable to delete local calendar {}: {}", cal_url, err));
+                        progress.result_mut().calendar_failed(&cal_url, err.to_string());
+                    },
+                    // There is nothing to delete from the server
+                    Ok(()) => self.local.forget_deleted_calendar(&cal_url),
+                }
+                continue;
+            }
+
+            let counterpart = match self.get_or_insert_remote_counterpart_calendar(&cal_url, cal_local.clone()).await {
+                Err(err) => {
+                    progress.warn(&format!("Unable to get or insert remote counterpart calendar for {} ({}). Skipping this time", cal_url, err));
+                    progress.result_mut().calendar_failed(&cal_url, err.to_string());
+                    continue;
+                },
+                Ok(arc) => arc,
+            };
+
+            let mut outbox = Vec::new();
+            let synced = Self::sync_calendar_pair(&self.sync_context(&cal_local, &counterpart, window), &mut outbox, progress).await;
+            self.send_scheduling_messages(outbox, progress).await;
+            if let Err(err) = synced {
+                progress.warn(&format!("Unable to sync calendar {}: {}, skipping this time.", cal_url, err));
+                progress.result_mut().calendar_failed(&cal_url, err.to_string());
+                continue;
+            }
+        }
+
+        // Repair the orphaned instances of recurring events
+        progress.check_cancelled()?;
+        if self.orphan_policy != OrphanedInstancePolicy::Keep {
+            for (cal_url, cal_local) in self.local.get_calendars().await? {
+                if only.map(|set| set.contains(&cal_url)) == Some(false) {
+                    continue;
+                }
+                let mut cal_local = cal_local.write().unwrap();
+                if self.is_calendar_selected(&selection, &cal_url, cal_local.name()) == false {
+                    continue;
+                }
+                Self::repair_orphaned_instances(&mut *cal_local, &cal_url, self.orphan_policy, progress).await;
+            }
+        }
+
+        progress.info("Sync ended");
+
+        Ok(())
+    }
+
+
+    /// Push the local changes to the properties of a calendar (name, description, color and order), or pull the remote ones if there are no local changes
+    async fn sync_calendar_properties(local_handle: &RwLock<T>, remote_handle: &RwLock<U>, progress: &mut SyncProgress) {
+        let (modified, properties) = {
+            let cal_local = local_handle.read().unwrap();
+            (cal_local.has_modified_properties(), cal_local.properties())
+        };
+        if modified {
+            progress.debug(&format!("> Pushing the new properties of calendar {}", properties.name));
+            let (remote_url, remote_privileges) = {
+                let cal_remote = remote_handle.read().unwrap();
+                (cal_remote.url().clone(), cal_remote.privileges())
+            };
+            if let Err(err) = check_privileges(&remote_url, remote_privileges, Privileges::WRITE_PROPERTIES) {
+                progress.warn(&format!("Unable to update the properties of calendar {}: {}", properties.name, err));
+                return;
+            }
+            let updated = remote_handle.write().unwrap().update_properties(&properties).await;
+            match updated {
+                Err(err) => progress.warn(&format!("Unable to update the properties of calendar {}: {}. Will retry at the next sync", properties.name, err)),
+                Ok(()) => {
+                    let mut cal_local = local_handle.write().unwrap();
+                    // Properties that have been changed again during the upload are pushed at the next sync
+                    if cal_local.properties() == properties {
+                        cal_local.set_synced_properties(properties);
+                    }
+                },
+            }
+        } else {
+            let remote_properties = remote_handle.read().unwrap().properties();
+            let mut cal_local = local_handle.write().unwrap();
+            if cal_local.has_modified_properties() == false && cal_local.properties() != remote_properties {
+                progress.debug(&format!("< Calendar {} has new properties on the server", remote_properties.name));
+                cal_local.set_synced_properties(remote_properties);
+            }
+        }
+    }
+
+    /// Whether a local calendar has already been synced with the server (otherwise, it has been locally created, and must be created on the server)
+    fn has_been_synced(cal_local: &T) -> bool {
+        cal_local.sync_token().is_some()
+            || cal_local.evicted_items().is_empty() == false
+            || cal_local.iter_items().any(|(_url, item)| item.sync_status().version_tag().is_some())
+    }
+
+    /// Reattach the orphaned instances of recurring events to their recurring events, or promote them to standalone events, depending on `policy`
+    async fn repair_orphaned_instances(cal_local: &mut T, cal_url: &Url, policy: OrphanedInstancePolicy, progress: &mut SyncProgress) {
+        // Resources that only contain modified instances
+        let orphans: Vec<Event> = cal_local.iter_items()
+            .filter_map(|(_url, item)| match item {
+                Item::Event(event) if event.is_orphaned_instance() && matches!(event.sync_status(), SyncStatus::LocallyDeleted(_)) == false => Some(event.clone()),
+                _ => None,
+            })
+            .collect();
+
+        for orphan in orphans {
+            let master = cal_local.iter_items()
+                .find_map(|(_url, item)| match item {
+                    Item::Event(event) if event.uid() == orphan.uid()
+                        && event.is_orphaned_instance() == false
+                        && matches!(event.sync_status(), SyncStatus::LocallyDeleted(_)) == false => Some(event.clone()),
+                    _ => None,
+                });
+            let orphan_url = orphan.url().clone();
+
+            let repaired: Vec<Item> = match (master, policy) {
+                (Some(mut master), _) => {
+                    progress.info(&format!("Reattaching the modified instances of {} to their recurring event {}", orphan_url, master.url()));
+                    for mut instance in orphan.into_instances() {
+                        instance.set_url(master.url().clone());
+                        if let Err(err) = master.set_override(instance) {
+                            progress.error(&format!("Unable to reattach an instance of {}: {}", orphan_url, err));
+                        }
+                    }
+                    vec![Item::Event(master)]
+                },
+                (None, OrphanedInstancePolicy::Promote) => {
+                    progress.info(&format!("Turning the modified instances of {} into standalone events", orphan_url));
+                    orphan.into_instances().iter()
+                        .map(|instance| Item::Event(instance.detached_copy(cal_url)))
+                        .collect()
+                },
+                (None, _) => continue,
+            };
+
+            for item in repaired {
+                let result = match cal_local.get_item_by_url(item.url()).await.is_some() {
+                    true => cal_local.update_item(item).await,
+                    false => cal_local.add_item(item).await,
+                };
+                if let Err(err) = result {
+                    progress.error(&format!("Unable to repair orphaned instances of {}: {}", orphan_url, err));
+                }
+            }
+            if let Err(err) = cal_local.mark_for_deletion(&orphan_url).await {
+                progress.error(&format!("Unable to delete orphaned instances {}: {}", orphan_url, err));
+            }
+        }
+
+        // Modified instances that no longer replace any occurrence of their recurring event
+        if policy != OrphanedInstancePolicy::Promote {
+            return;
+        }
+        let masters: Vec<Event> = cal_local.iter_items()
+            .filter_map(|(_url, item)| match item {
+                Item::Event(event) if event.orphaned_overrides().is_empty() == false
+                    && matches!(event.sync_status(), SyncStatus::LocallyDeleted(_)) == false => Some(event.clone()),
+                _ => None,
+            })
+            .collect();
+        for mut master in masters {
+            let orphaned: Vec<DateTime<Utc>> = master.orphaned_overrides().iter()
+                .filter_map(|instance| instance.recurrence_id().cloned())
+                .collect();
+            progress.info(&format!("Turning {} modified instances of {} into standalone events", orphaned.len(), master.url()));
+            for recurrence_id in &orphaned {
+                if let Some(instance) = master.remove_override(recurrence_id) {
+                    if let Err(err) = cal_local.add_item(Item::Event(instance.detached_copy(cal_url))).await {
+                        progress.error(&format!("Unable to promote an instance of {}: {}", master.url(), err));
+                    }
+                }
+            }
+            let master_url = master.url().clone();
+            if let Err(err) = cal_local.update_item(Item::Event(master)).await {
+                progress.error(&format!("Unable to update recurring event {}: {}", master_url, err));
+            }
+        }
+    }
+
+    async fn get_or_insert_local_counterpart_calendar(&mut self, cal_url: &Url, needle: Arc<RwLock<U>>) -> Result<Arc<RwLock<T>>, Box<dyn Error>> {
+        get_or_insert_counterpart_calendar("local", &mut self.local, cal_url, needle).await
+    }
+    async fn get_or_insert_remote_counterpart_calendar(&mut self, cal_url: &Url, needle: Arc<RwLock<T>>) -> Result<Arc<RwLock<U>>, Box<dyn Error>> {
+        get_or_insert_counterpart_calendar("remote", &mut self.remote, cal_url, needle).await
+    }
+
+
+    /// Move the items on the server that have been moved between local calendars (see [`Self::move_item`]).
+    ///
+    /// Moves that cannot be pushed are forgotten. The sync of the calendars then deletes these items from the server, and creates them again at their new URLs
+    async fn push_moves(&mut self, only: Option<&HashSet<Url>>, progress: &mut SyncProgress) -> Result<(), Box<dyn Error>> {
+        for (from, to) in self.local.pending_moves() {
+            progress.check_cancelled()?;
+            let (source_calendar, target_calendar) = match (self.calendar_url_of(&from).await?, self.calendar_url_of(&to).await?) {
+                (Some(source), Some(target)) => (source, target),
+                _ => {
+                    self.local.forget_move(&from);
+                    continue;
+                },
+            };
+            if only.map(|set| set.contains(&source_calendar) && set.contains(&target_calendar)) == Some(false) {
+                continue;
+            }
+
+            // The move is only valid as long as the item is still a tombstone in its former calendar, and has not been uploaded to its new one
+            let version_tag = match (self.local_sync_status(&source_calendar, &from).await, self.local_sync_status(&target_calendar, &to).await) {
+                (Some(SyncStatus::LocallyDeleted(tag)), Some(SyncStatus::NotSynced)) => tag,
+                _ => {
+                    self.local.forget_move(&from);
+                    continue;
+                },
+            };
+            let cal_remote = match self.remote.get_calendar(&source_calendar).await {
+                Some(cal) => cal,
+                None => {
+                    self.local.forget_move(&from);
+                    continue;
+                },
+            };
+
+            progress.debug(&format!("> Moving item {} to {} on the server", from, to));
+            let moved = cal_remote.write().unwrap().move_item(&from, &version_tag, &to).await;
+            match moved {
+                Err(err) => {
+                    progress.info(&format!("Unable to move item {} to {} on the server ({}). It will be deleted then created again", from, to, err));
+                },
+                Ok(new_tag) => {
+                    // A moved item usually keeps its version tag, since its content has not changed.
+                    // Its local content is pushed anyway by the sync of its new calendar, since it may have changed since the last sync
+                    let new_tag = new_tag.unwrap_or(version_tag);
+                    if let Some(cal) = self.local.get_calendar(&target_calendar).await {
+                        if let Some(mut item) = cal.write().unwrap().get_item_by_url_mut(&to).await {
+                            item.set_sync_status(SyncStatus::LocallyModified(new_tag));
+                        }
+                    }
+                    if let Some(cal) = self.local.get_calendar(&source_calendar).await {
+                        if let Err(err) = cal.write().unwrap().immediately_delete_item(&from).await {
+                            progress.error(&format!("Unable to permanently delete local item {}: {}", from, err));
+                        }
+                    }
+                },
+            }
+            self.local.forget_move(&from);
+        }
+        Ok(())
+    }
+
+    /// The sync status of an item of a local calendar
+    async fn local_sync_status(&self, cal_url: &Url, item_url: &Url) -> Option<SyncStatus> {
+        let cal = self.local.get_calendar(cal_url).await?;
+        let cal = cal.read().unwrap();
+        cal.get_item_by_url(item_url).await.map(|item| item.sync_status().clone())
+    }
+
+    /// The iTIP messages of the events whose creation, change or deletion has been pushed are appended to `outbox`, if a `scheduling_address` is given (see [`Self::set_scheduling_address`]).
+    ///
+    /// The local calendar is not locked while the server is queried: the differences are found from a copy of its state, and each change is applied under a short write lock.
+    /// Local items that have been changed in the meantime (e.g. by an app) are left as they are, the next sync handles them
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "sync_calendar", skip_all, fields(calendar = tracing::field::Empty)))]
+    async fn sync_calendar_pair(ctx: &SyncContext<'_, T, U>, outbox: &mut Vec<ItipMessage>, progress: &mut SyncProgress) -> Result<(), Box<dyn Error>> {
+        let SyncContext { local_handle, remote_handle, comparison_rules, window, scheduling_address, .. } = *ctx;
+        let privileges = remote_handle.read().unwrap().privileges();
+        local_handle.write().unwrap().set_privileges(privileges);
+        Self::sync_calendar_properties(local_handle, remote_handle, progress).await;
+        let (cal_url, cal_name) = {
+            let cal_local = local_handle.read().unwrap();
+            (cal_local.url().clone(), cal_local.name().to_string())
+        };
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("calendar", &cal_url.as_str());
+        progress.calendar_started(&cal_url, &cal_name);
+
+        progress.info(&format!("Syncing calendar {}", cal_name));
+        progress.reset_counter();
+        progress.feedback(SyncEvent::InProgress{
+            calendar: cal_name.clone(),
+            items_done_already: 0,
+            details: "started".to_string()
+        });
+
+        // Step 1 - find the differences
+        // Listing the remote items is usually the longest part of a sync. Meanwhile, the local calendar is not locked at all, so that apps can still use it
+        progress.debug("Finding the differences to sync...");
+        progress.phase(SyncPhase::ListingItems{ calendar: cal_name.clone() });
+        let mut local_state = LocalState::new(&*local_handle.read().unwrap());
+        let differences = {
+            let cal_remote = remote_handle.read().unwrap();
+            Self::find_differences(&local_state, &*cal_remote, window, progress).await?
+        };
+        let Differences {
+            mut local_del, mut remote_del, mut local_changes, mut remote_changes,
+            mut local_additions, mut remote_additions, interrupted_uploads,
+            conflicts, evicted_changes, new_sync_token,
+        } = differences;
+
+        {
+            let mut cal_local = local_handle.write().unwrap();
+            for (url, version_tag) in evicted_changes {
+                // The item may have been downloaded again in the meantime
+                if cal_local.evicted_items().contains_key(&url) {
+                    cal_local.update_evicted_item(&url, version_tag);
+                }
+            }
+        }
+
+        for (url, kind, remote_tag) in conflicts {
+            let (local_item, remote_item, outcome) = match Self::resolve_conflict(ctx, &url, kind, None, progress).await {
+                None => continue,
+                Some(resolved) => resolved,
+            };
+            let mut cal_local = local_handle.write().unwrap();
+            if local_state.is_unchanged(&url, cal_local.get_item_by_url(&url).await) == false {
+                progress.debug(&format!("> Item {} has been changed locally during the sync, its conflict will be resolved at the next sync", url));
+                continue;
+            }
+            if let (ConflictKind::BothModified, Some(remote_item)) = (kind, &remote_item) {
+                if comparison_rules.are_equivalent(&local_item, remote_item) {
+                    // Both ends have made the same change, there is nothing to resolve
+                    progress.debug(&format!("> Item {} has been modified the same way locally and on the server", url));
+                    if let Some(mut item) = cal_local.get_item_by_url_mut(&url).await {
+                        item.set_sync_status(remote_item.sync_status().clone());
+                    }
+                    continue;
+                }
+            }
+            progress.info(&format!("Conflict: item {} ({:?}) is resolved as {:?}", url, kind, outcome));
+
+            match (kind, outcome) {
+                (ConflictKind::RemotelyDeleted, ConflictOutcome::KeptRemote) => {
+                    remote_del.insert(url.clone());
+                },
+                (_, ConflictOutcome::KeptRemote) => match remote_item {
+                    // The server version has already been downloaded to resolve the conflict
+                    Some(remote_item) => Self::apply_remote_version(&mut *cal_local, remote_item, comparison_rules, progress).await,
+                    None => { remote_changes.insert(url.clone()); },
+                },
+                (ConflictKind::RemotelyDeleted, _) => {
+                    // Re-create the item on the server
+                    if let Some(mut item) = cal_local.get_item_by_url_mut(&url).await {
+                        item.set_sync_status(SyncStatus::NotSynced);
+                    }
+                    local_additions.insert(url.clone());
+                },
+                (ConflictKind::LocallyDeleted, _) => {
+                    // Overwriting the server version is done by telling the server the latest version tag
+                    if let (Some(mut item), Some(remote_tag)) = (cal_local.get_item_by_url_mut(&url).await, remote_tag) {
+                        item.set_sync_status(SyncStatus::LocallyDeleted(remote_tag));
+                    }
+                    local_del.insert(url.clone());
+                },
+                (ConflictKind::BothModified, ConflictOutcome::KeptLocal) => {
+                    if let (Some(mut item), Some(remote_tag)) = (cal_local.get_item_by_url_mut(&url).await, remote_tag) {
+                        item.set_sync_status(SyncStatus::LocallyModified(remote_tag));
+                    }
+                    local_changes.insert(url.clone());
+                },
+                (ConflictKind::BothModified, ConflictOutcome::KeptBoth) => {
+                    let mut copy = local_item;
+                    copy.set_url(CalendarUrl::from(cal_url.clone()).random_item_url());
+                    copy.set_uid(Uid::random());
+                    copy.set_sync_status(SyncStatus::NotSynced);
+                    let copy_url = copy.url().clone();
+                    match cal_local.add_item(copy).await {
+                        Ok(_) => { local_additions.insert(copy_url); },
+                        Err(err) => progress.error(&format!("Unable to save a copy of conflicting item {}: {}", url, err)),
+                    }
+                    match remote_item {
+                        Some(remote_item) => Self::apply_remote_version(&mut *cal_local, remote_item, comparison_rules, progress).await,
+                        None => { remote_changes.insert(url.clone()); },
+                    }
+                },
+            }
+            progress.record_conflict(ResolvedConflict::new(cal_url.clone(), url, kind, outcome));
+        }
+
+
+        Self::recover_interrupted_uploads(
+            interrupted_uploads,
+            &mut local_changes,
+            &mut remote_changes,
+            &mut local_state,
+            ctx,
+            progress,
+        ).await;
+
+        Self::detect_moves(
+            &mut remote_del,
+            &mut remote_additions,
+            &mut local_changes,
+            &mut local_additions,
+            &mut local_state,
+            ctx,
+            progress,
+        ).await;
+
+
+        // Step 2 - commit changes
+        progress.trace("Committing changes...");
+        progress.phase(SyncPhase::Deleting{ calendar: cal_name.clone(), done: 0, total: local_del.len() + remote_del.len() });
+        for url_del in local_del {
+            progress.check_cancelled()?;
+            progress.debug(&format!("> Pushing local deletion {} to the server", url_del));
+            let tombstone = local_handle.read().unwrap().get_item_by_url(&url_del).await.cloned();
+            progress.increment_counter(1);
+            progress.feedback(SyncEvent::InProgress{
+                calendar: cal_name.clone(),
+                items_done_already: progress.counter(),
+                details: tombstone.as_ref().map(|item| item.name().to_string()).unwrap_or_default(),
+            });
+            if matches!(tombstone.as_ref().map(Item::sync_status), Some(SyncStatus::LocallyDeleted(_))) == false {
+                progress.debug(&format!("> Item {} is no longer deleted locally, it will be synced at the next sync", url_del));
+                local_state.changed_meanwhile = true;
+                progress.advance_phase(1);
+                continue;
+            }
+            if let Err(err) = check_privileges(&cal_url, privileges, Privileges::UNBIND) {
+                progress.warn(&format!("Unable to delete remote item {}: {}", url_del, err));
+                progress.item_failed(&url_del, err.to_string());
+                progress.advance_phase(1);
+                continue;
+            }
+
+            let cancellations = Self::scheduling_messages(scheduling_address, tombstone.as_ref(), None, progress);
+            let deleted = remote_handle.write().unwrap().delete_item(&url_del).await;
+            let deleted = match deleted {
+                // The item has already been deleted from the server, this confirms the deletion as well
+                Err(err) if is_already_deleted(&err) => {
+                    progress.debug(&format!("> {} was already absent from the server", url_del));
+                    Ok(())
+                },
+                other => other,
+            };
+            match deleted {
+                Err(err) => {
+                    progress.warn(&format!("Unable to delete remote item {}: {}", url_del, err));
+                    progress.item_failed(&url_del, err.to_string());
+                },
+                Ok(()) => {
+                    progress.item_synced(&url_del, ItemOperation::Deleted, SyncDirection::Pushed);
+                    outbox.extend(cancellations);
+                    // Change the local copy from "marked to deletion" to "actually deleted"
+                    let purged = local_handle.write().unwrap().immediately_delete_item(&url_del).await;
+                    if let Err(err) = purged {
+                        progress.error(&format!("Unable to permanently delete local item {}: {}", url_del, err));
+                    }
+                },
+            }
+            progress.advance_phase(1);
+        }
+
+        for url_del in remote_del {
+            progress.check_cancelled()?;
+            progress.debug(&format!("> Applying remote deletion {} locally", url_del));
+            let mut cal_local = local_handle.write().unwrap();
+            let local_item = cal_local.get_item_by_url(&url_del).await;
+            progress.increment_counter(1);
+            progress.feedback(SyncEvent::InProgress{
+                calendar: cal_name.clone(),
+                items_done_already: progress.counter(),
+                details: local_item.map(|item| item.name().to_string()).unwrap_or_default(),
+            });
+            if local_state.is_unchanged(&url_del, local_item) == false {
+                progress.debug(&format!("> Item {} has been changed locally during the sync, it will be synced at the next sync", url_del));
+                progress.advance_phase(1);
+                continue;
+            }
+            match cal_local.immediately_delete_item(&url_del).await {
+                Err(err) => {
+                    progress.warn(&format!("Unable to delete local item {}: {}", url_del, err));
+                    progress.item_failed(&url_del, err.to_string());
+                },
+                Ok(()) => progress.item_synced(&url_del, ItemOperation::Deleted, SyncDirection::Pulled),
+            }
+            progress.advance_phase(1);
+        }
+
+        progress.phase(SyncPhase::Fetching{ calendar: cal_name.clone(), done: 0, total: remote_additions.len() + remote_changes.len() });
+        Self::download_and_apply(
+            BatchDownloadType::RemoteAdditions,
+            remote_additions,
+            &mut local_state,
+            &ComparisonRules::strict(),
+            ctx,
+            progress,
+        ).await;
+
+        Self::download_and_apply(
+            BatchDownloadType::RemoteChanges,
+            remote_changes,
+            &mut local_state,
+            comparison_rules,
+            ctx,
+            progress,
+        ).await;
+        progress.check_cancelled()?;
+
+
+        progress.phase(SyncPhase::Pushing{ calendar: cal_name.clone(), done: 0, total: local_additions.len() + local_changes.len() });
+        for url_add in local_additions {
+            progress.check_cancelled()?;
+            progress.debug(&format!("> Pushing local addition {} to the server", url_add));
+            let local_item = local_handle.read().unwrap().get_item_by_url(&url_add).await.cloned();
+            progress.increment_counter(1);
+            progress.feedback(SyncEvent::InProgress{
+                calendar: cal_name.clone(),
+                items_done_already: progress.counter(),
+                details: local_item.as_ref().map(|item| item.name().to_string()).unwrap_or_default(),
+            });
+            let mut item = match local_item {
+                Some(item) if item.sync_status() == &SyncStatus::NotSynced => item,
+                _ => {
+                    progress.debug(&format!("> Item {} has been changed locally during the sync, it will be synced at the next sync", url_add));
+                    local_state.changed_meanwhile = true;
+                    progress.advance_phase(1);
+                    continue;
+                },
+            };
+            if let Err(err) = check_privileges(&cal_url, privileges, Privileges::BIND) {
+                progress.warn(&format!("Unable to add item {} to remote calendar: {}", url_add, err));
+                progress.item_failed(&url_add, err.to_string());
+                progress.advance_phase(1);
+                continue;
+            }
+
+            let state_before = ItemState::new(&item);
+            // `item` is a copy: the local item only gets its new SEQUENCE (if any) once the server accepted it
+            let invitations = Self::scheduling_messages(scheduling_address, None, Some(&mut item), progress);
+            let added = remote_handle.write().unwrap().add_item(item.clone()).await;
+            let mut assigned_url = None;
+            match added {
+                Err(err) => {
+                    progress.error(&format!("Unable to add item {} to remote calendar: {}", url_add, err));
+                    progress.item_failed(&url_add, err.to_string());
+                    if let Some(server_error) = permanent_server_error(err) {
+                        local_handle.write().unwrap().mark_as_rejected(&url_add, server_error);
+                    }
+                },
+                Ok(new_ss) => {
+                    // Update local sync status
+                    item.set_sync_status(new_ss);
+                    Self::save_uploaded_item(&mut *local_handle.write().unwrap(), item, &state_before, progress).await;
+                    progress.item_synced(&url_add, ItemOperation::Added, SyncDirection::Pushed);
+                    outbox.extend(invitations);
+                    assigned_url = remote_handle.read().unwrap().added_item_url(&url_add);
+                },
+            }
+            if let Some(new_url) = assigned_url {
+                // The server has chosen another URL, the local item must follow it so that the next syncs match them
+                progress.debug(&format!("> Item {} has been given URL {} by the server", url_add, new_url));
+                let mut cal_local = local_handle.write().unwrap();
+                if let Some(mut moved_item) = cal_local.get_item_by_url(&url_add).await.cloned() {
+                    moved_item.set_url(new_url.clone());
+                    match cal_local.add_item(moved_item).await {
+                        Err(err) => progress.error(&format!("Unable to move local item {} to {}: {}", url_add, new_url, err)),
+                        Ok(_) => if let Err(err) = cal_local.immediately_delete_item(&url_add).await {
+                            progress.error(&format!("Unable to delete local item {} that has been moved to {}: {}", url_add, new_url, err));
+                        },
+                    }
+                }
+            }
+            progress.advance_phase(1);
+        }
+
+        let mut edit_conflicts = Vec::new();
+        for url_change in local_changes {
+            progress.check_cancelled()?;
+            progress.debug(&format!("> Pushing local change {} to the server", url_change));
+            let local_item = local_handle.read().unwrap().get_item_by_url(&url_change).await.cloned();
+            progress.increment_counter(1);
+            progress.feedback(SyncEvent::InProgress{
+                calendar: cal_name.clone(),
+                items_done_already: progress.counter(),
+                details: local_item.as_ref().map(|item| item.name().to_string()).unwrap_or_default(),
+            });
+            let mut item = match local_item {
+                Some(item) if matches!(item.sync_status(), SyncStatus::LocallyModified(_)) => item,
+                _ => {
+                    progress.debug(&format!("> Item {} has been changed locally during the sync, it will be synced at the next sync", url_change));
+                    local_state.changed_meanwhile = true;
+                    progress.advance_phase(1);
+                    continue;
+                },
+            };
+            if let Err(err) = check_privileges(&cal_url, privileges, Privileges::WRITE_CONTENT) {
+                progress.warn(&format!("Unable to update item {} in remote calendar: {}", url_change, err));
+                progress.item_failed(&url_change, err.to_string());
+                progress.advance_phase(1);
+                continue;
+            }
+            let server_version = match (scheduling_address, &item) {
+                (Some(address), Item::Event(event)) if scheduling::is_organized_by(event, address) => {
+                    // This tells which attendees have been uninvited, and whether the change is significant
+                    let fetched = remote_handle.read().unwrap().get_item_by_url(&url_change).await;
+                    match fetched {
+                        Ok(item) => item,
+                        Err(err) => {
+                            progress.warn(&format!("Unable to fetch the server version of {}, every attendee will be invited again: {}", url_change, err));
+                            None
+                        },
+                    }
+                },
+                _ => None,
+            };
+
+            let state_before = ItemState::new(&item);
+            // Same as for additions, the bumped SEQUENCE is only saved locally after a successful upload
+            let updates = Self::scheduling_messages(scheduling_address, server_version.as_ref(), Some(&mut item), progress);
+            let updated = remote_handle.write().unwrap().update_item(item.clone()).await;
+            match updated {
+                Err(err) if is_edit_conflict(&err) => {
+                    progress.info(&format!("Item {} has been modified on the server in the meantime", url_change));
+                    edit_conflicts.push(url_change.clone());
+                },
+                Err(err) => {
+                    progress.error(&format!("Unable to update item {} in remote calendar: {}", url_change, err));
+                    progress.item_failed(&url_change, err.to_string());
+                    if let Some(server_error) = permanent_server_error(err) {
+                        local_handle.write().unwrap().mark_as_rejected(&url_change, server_error);
+                    }
+                },
+                Ok(new_ss) => {
+                    // Update local sync status
+                    item.set_sync_status(new_ss);
+                    Self::save_uploaded_item(&mut *local_handle.write().unwrap(), item, &state_before, progress).await;
+                    progress.item_synced(&url_change, ItemOperation::Updated, SyncDirection::Pushed);
+                    outbox.extend(updates);
+                },
+            }
+            progress.advance_phase(1);
+        }
+
+        for url in edit_conflicts {
+            match Self::resolve_edit_conflict(ctx, &url, progress).await {
+                Err(err) => {
+                    progress.warn(&format!("Unable to resolve the conflict on item {}: {}", url, err));
+                    progress.item_failed(&url, err.to_string());
+                },
+                Ok(outcome) => {
+                    progress.info(&format!("Conflict: item {} ({:?}) is resolved as {:?}", url, ConflictKind::BothModified, outcome));
+                    progress.record_conflict(ResolvedConflict::new(cal_url.clone(), url, ConflictKind::BothModified, outcome));
+                },
+            }
+        }
+
+        // The sync token is only saved when everything went fine, otherwise the next sync may miss the changes that could not be applied
+        // (this includes the items that have been skipped, because they have been changed locally during the sync)
+        if progress.is_success() && local_state.changed_meanwhile == false {
+            local_handle.write().unwrap().set_sync_token(new_sync_token);
+        }
+
+        let duplicates = duplicates::find_duplicates(&cal_url, local_handle.read().unwrap().iter_items());
+        if duplicates.is_empty() == false {
+            progress.info(&format!("Calendar {} contains {} UID(s) that are used by several items", cal_name, duplicates.len()));
+            progress.record_duplicates(duplicates);
+        }
+
+        Ok(())
+    }
+
+    /// Save the new sync status of an item that has just been uploaded, along with what the upload has changed in it (e.g. its SEQUENCE). \
+    /// In case the local item has been changed during the upload, its latest version is kept instead, and will be pushed at the next sync
+    async fn save_uploaded_item(cal_local: &mut T, uploaded: Item, state_before: &ItemState, progress: &mut SyncProgress) {
+        let url = uploaded.url().clone();
+        match cal_local.get_item_by_url_mut(&url).await {
+            None => progress.debug(&format!("> Item {} has been deleted locally during its upload", url)),
+            Some(mut item) if &ItemState::new(&item) == state_before => *item = uploaded,
+            Some(mut item) => {
+                progress.debug(&format!("> Item {} has been changed locally during its upload, its latest changes will be pushed at the next sync", url));
+                let new_status = match (item.sync_status(), uploaded.sync_status().version_tag().cloned()) {
+                    (_, None) => uploaded.sync_status().clone(),
+                    (SyncStatus::LocallyDeleted(_), Some(tag)) => SyncStatus::LocallyDeleted(tag),
+                    (_, Some(tag)) => SyncStatus::LocallyModified(tag),
+                };
+                item.set_sync_status(new_status);
+            },
+        }
+    }
+
+    /// The iTIP messages the organizer of an event must send, when its creation (if `old` is `None`), change or deletion (if `new` is `None`) is pushed to the server.
+    ///
+    /// Nothing is sent about the events that are not organized by `scheduling_address`. The SEQUENCE of `new` is increased if its attendees must be told about the change
+    fn scheduling_messages(scheduling_address: Option<&str>, old: Option<&Item>, new: Option<&mut Item>, progress: &mut SyncProgress) -> Vec<ItipMessage> {
+        let address = match scheduling_address {
+            None => return Vec::new(),
+            Some(address) => address,
+        };
+        let old = match old {
+            Some(Item::Event(event)) => Some(event),
+            _ => None,
+        };
+        let mut new = match new {
+            Some(Item::Event(event)) => Some(event),
+            _ => None,
+        };
+        let organized = new.as_deref().or(old).map(|event| scheduling::is_organized_by(event, address)).unwrap_or(false);
+        if organized == false {
+            return Vec::new();
+        }
+
+        if let (Some(old), Some(new)) = (old, new.as_deref_mut()) {
+            scheduling::update_sequence(old, new);
+        }
+        match scheduling::messages_for_change(old, new.as_deref()) {
+            Ok(messages) => messages,
+            Err(err) => {
+                progress.warn(&format!("Unable to schedule an event: {}", err));
+                Vec::new()
+            },
+        }
     }
 
-    async fn run_sync(&mut self, progress: &mut SyncProgress) -> bool {
-        if let Err(err) = self.run_sync_inner(progress).await {
-            progress.error(&format!("Sync terminated because of an error: {}", err));
+    /// Send the iTIP messages of the events a sync has pushed (see [`Self::set_scheduling_address`])
+    async fn send_scheduling_messages(&self, outbox: Vec<ItipMessage>, progress: &mut SyncProgress) {
+        for message in outbox {
+            let method = message.method().as_ical_str();
+            match self.remote.send_scheduling_message(&message).await {
+                Err(err) => progress.warn(&format!("Unable to send a {} to {}: {}", method, message.recipients().join(", "), err)),
+                Ok(statuses) => {
+                    for status in statuses.iter().filter(|status| status.is_success() == false) {
+                        progress.warn(&format!("A {} could not be delivered to {}: {}", method, status.recipient(), status.request_status()));
+                    }
+                },
+            }
         }
-        progress.feedback(SyncEvent::Finished{ success: progress.is_success() });
-        progress.is_success()
     }
 
-    async fn run_sync_inner(&mut self, progress: &mut SyncProgress) -> Result<(), Box<dyn Error>> {
-        progress.info("Starting a sync.");
-        progress.feedback(SyncEvent::Started);
+    /// Record what a sync would do about `differences` (see [`SyncOptions::dry_run`]).
+    ///
+    /// This mirrors what `sync_calendar_pair` does, without changing anything. `Custom` conflict resolutions are not called, since they may have side effects:
+    /// their conflicts are recorded as undecided instead (see [`CalendarResult::undecided_conflicts`](sync_result::CalendarResult::undecided_conflicts))
+    async fn plan_differences(differences: Differences, ctx: &SyncContext<'_, T, U>, progress: &mut SyncProgress) {
+        let SyncContext { local_handle, remote_handle, comparison_rules, conflict_resolution, .. } = *ctx;
+        let Differences {
+            mut local_del, mut remote_del, mut local_changes, mut remote_changes,
+            mut local_additions, mut remote_additions, interrupted_uploads,
+            conflicts, ..
+        } = differences;
+        let cal_url = local_handle.read().unwrap().url().clone();
 
-        let mut handled_calendars = HashSet::new();
+        for (url, kind, _remote_tag) in conflicts {
+            let outcome = match conflict_resolution.planned_outcome(kind) {
+                None => {
+                    progress.record_undecided_conflict(&url);
+                    continue;
+                },
+                Some(outcome) => outcome,
+            };
+            match (kind, outcome) {
+                (ConflictKind::RemotelyDeleted, ConflictOutcome::KeptRemote) => { remote_del.insert(url.clone()); },
+                (_, ConflictOutcome::KeptRemote) => { remote_changes.insert(url.clone()); },
+                (ConflictKind::RemotelyDeleted, _) => { local_additions.insert(url.clone()); },
+                (ConflictKind::LocallyDeleted, _) => { local_del.insert(url.clone()); },
+                (ConflictKind::BothModified, ConflictOutcome::KeptLocal) => { local_changes.insert(url.clone()); },
+                (ConflictKind::BothModified, ConflictOutcome::KeptBoth) => {
+                    remote_changes.insert(url.clone());
+                    // The local version would be pushed as a copy, at a URL the sync chooses
+                    local_additions.insert(url.clone());
+                },
+            }
+            progress.record_conflict(ResolvedConflict::new(cal_url.clone(), url, kind, outcome));
+        }
 
-        // Sync every remote calendar
-        let cals_remote = self.remote.get_calendars().await?;
-        for (cal_url, cal_remote) in cals_remote {
-            let counterpart = match self.get_or_insert_local_counterpart_calendar(&cal_url, cal_remote.clone()).await {
+        // Just like `recover_interrupted_uploads`, the most recent version of the items whose upload has been interrupted wins
+        let interrupted_uploads: Vec<Url> = interrupted_uploads.into_iter().collect();
+        for batch in interrupted_uploads.chunks(DOWNLOAD_BATCH_SIZE) {
+            let downloaded = remote_handle.read().unwrap().get_items_by_url(batch).await;
+            let remote_items = match downloaded {
                 Err(err) => {
-                    progress.warn(&format!("Unable to get or insert local counterpart calendar for {} ({}). Skipping this time", cal_url, err));
+                    progress.warn(&format!("Unable to check whether {:?} have been uploaded already: {}", batch, err));
                     continue;
                 },
-                Ok(arc) => arc,
+                Ok(items) => items,
             };
-
-            if let Err(err) = Self::sync_calendar_pair(counterpart, cal_remote, progress).await {
-                progress.warn(&format!("Unable to sync calendar {}: {}, skipping this time.", cal_url, err));
-                continue;
+            let cal_local = local_handle.read().unwrap();
+            for (url, remote_item) in batch.iter().zip(remote_items) {
+                let (local_item, remote_item) = match (cal_local.get_item_by_url(url).await, remote_item) {
+                    (Some(local_item), Some(remote_item)) if local_item.uid() == remote_item.uid() => (local_item, remote_item),
+                    _ => continue,
+                };
+                if comparison_rules.are_equivalent(local_item, &remote_item) {
+                    continue;
+                }
+                match comparison_rules.most_recent(local_item, &remote_item) {
+                    MostRecent::Local => { local_changes.insert(url.clone()); },
+                    MostRecent::Remote | MostRecent::Unknown => { remote_changes.insert(url.clone()); },
+                }
             }
-            handled_calendars.insert(cal_url);
         }
 
-        // Sync every local calendar that would not be in the remote yet
-        let cals_local = self.local.get_calendars().await?;
-        for (cal_url, cal_local) in cals_local {
-            if handled_calendars.contains(&cal_url) {
+        // Just like `detect_moves`, items that have been moved on the server are not deleted and re-added
+        let moves = Self::find_moves(&remote_del, &remote_additions, ctx, progress).await;
+        for (old_url, remote_item) in moves {
+            let new_url = remote_item.url().clone();
+            remote_del.remove(&old_url);
+            remote_additions.remove(&new_url);
+            let local_item = local_handle.read().unwrap().get_item_by_url(&old_url).await.cloned();
+            let conflicting = match &local_item {
+                Some(local_item) => matches!(local_item.sync_status(), SyncStatus::LocallyModified(_)) && comparison_rules.are_equivalent(local_item, &remote_item) == false,
+                None => false,
+            };
+            if conflicting == false {
+                remote_changes.insert(new_url);
                 continue;
             }
-
-            let counterpart = match self.get_or_insert_remote_counterpart_calendar(&cal_url, cal_local.clone()).await {
-                Err(err) => {
-                    progress.warn(&format!("Unable to get or insert remote counterpart calendar for {} ({}). Skipping this time", cal_url, err));
+            let outcome = match conflict_resolution.planned_outcome(ConflictKind::BothModified) {
+                None => {
+                    progress.record_undecided_conflict(&old_url);
                     continue;
                 },
-                Ok(arc) => arc,
+                Some(outcome) => outcome,
             };
-
-            if let Err(err) = Self::sync_calendar_pair(cal_local, counterpart, progress).await {
-                progress.warn(&format!("Unable to sync calendar {}: {}, skipping this time.", cal_url, err));
-                continue;
+            match outcome {
+                ConflictOutcome::KeptLocal => { local_changes.insert(new_url); },
+                ConflictOutcome::KeptRemote => { remote_changes.insert(new_url); },
+                ConflictOutcome::KeptBoth => {
+                    remote_changes.insert(new_url);
+                    local_additions.insert(old_url.clone());
+                },
             }
+            progress.record_conflict(ResolvedConflict::new(cal_url.clone(), old_url, ConflictKind::BothModified, outcome));
         }
 
-        progress.info("Sync ended");
-
-        Ok(())
+        let planned = vec![
+            (local_additions, ItemOperation::Added, SyncDirection::Pushed),
+            (local_changes, ItemOperation::Updated, SyncDirection::Pushed),
+            (local_del, ItemOperation::Deleted, SyncDirection::Pushed),
+            (remote_additions, ItemOperation::Added, SyncDirection::Pulled),
+            (remote_changes, ItemOperation::Updated, SyncDirection::Pulled),
+            (remote_del, ItemOperation::Deleted, SyncDirection::Pulled),
+        ];
+        for (urls, operation, direction) in planned {
+            for url in urls {
+                progress.item_synced(&url, operation, direction);
+            }
+        }
     }
 
-
-    async fn get_or_insert_local_counterpart_calendar(&mut self, cal_url: &Url, needle: Arc<Mutex<U>>) -> Result<Arc<Mutex<T>>, Box<dyn Error>> {
-        get_or_insert_counterpart_calendar("local", &mut self.local, cal_url, needle).await
+    /// Tell which version of a conflicting item `conflict_resolution` keeps. Returns the local version (and the server version, in case it had to be downloaded) as well, or `None` in case this cannot be told. \
+    /// `remote_item` is the server version, in case it has been downloaded already
+    async fn resolve_conflict(ctx: &SyncContext<'_, T, U>, url: &Url, kind: ConflictKind, remote_item: Option<Item>, progress: &mut SyncProgress) -> Option<(Item, Option<Item>, ConflictOutcome)> {
+        let SyncContext { local_handle, remote_handle, conflict_resolution, .. } = *ctx;
+        let local_item = local_handle.read().unwrap().get_item_by_url(url).await.cloned();
+        let local_item = match local_item {
+            None => {
+                progress.error(&format!("Inconsistent state: missing task {} from the local tasks", url));
+                return None;
+            },
+            Some(item) => item,
+        };
+        let remote_item = match remote_item.is_none() && conflict_resolution.needs_remote_version() && kind != ConflictKind::RemotelyDeleted {
+            false => remote_item,
+            true => {
+                let fetched = remote_handle.read().unwrap().get_item_by_url(url).await;
+                match fetched {
+                    Ok(item) => item,
+                    Err(err) => {
+                        progress.warn(&format!("Unable to download the server version of conflicting item {}: {}. Skipping it this time", url, err));
+                        return None;
+                    },
+                }
+            },
+        };
+        let outcome = conflict_resolution.resolve(&Conflict::new(url.clone(), kind, local_item.clone(), remote_item.clone()));
+        Some((local_item, remote_item, outcome))
     }
-    async fn get_or_insert_remote_counterpart_calendar(&mut self, cal_url: &Url, needle: Arc<Mutex<T>>) -> Result<Arc<Mutex<U>>, Box<dyn Error>> {
-        get_or_insert_counterpart_calendar("remote", &mut self.remote, cal_url, needle).await
+
+    /// Replace the local version of an item with a server version that has already been downloaded, unless they are equivalent (in which case only the version tag is updated)
+    async fn apply_remote_version(cal_local: &mut T, remote_item: Item, comparison_rules: &ComparisonRules, progress: &mut SyncProgress) {
+        let url = remote_item.url().clone();
+        let unchanged = cal_local.get_item_by_url(&url).await
+            .map(|local_item| comparison_rules.are_equivalent(local_item, &remote_item))
+            .unwrap_or(false);
+        let result = match unchanged {
+            false => cal_local.update_item(remote_item).await.map(|_| ()),
+            true => match cal_local.get_item_by_url_mut(&url).await {
+                None => Err(format!("Item {} has vanished from the local calendar", url).into()),
+                Some(mut local_item) => {
+                    local_item.set_sync_status(remote_item.sync_status().clone());
+                    Ok(())
+                },
+            },
+        };
+        match result {
+            Err(err) => {
+                progress.error(&format!("Not able to update local item {}: {}", url, err));
+                progress.item_failed(&url, err.to_string());
+            },
+            Ok(()) => progress.item_synced(&url, ItemOperation::Updated, SyncDirection::Pulled),
+        }
     }
 
+    /// Resolve the conflict on an item whose upload has been refused by the server, because it has been modified on the server since the differences have been computed. \
+    /// Rather than overwriting the server version, this is resolved like any other [`ConflictKind::BothModified`] conflict
+    async fn resolve_edit_conflict(ctx: &SyncContext<'_, T, U>, url: &Url, progress: &mut SyncProgress) -> Result<ConflictOutcome, Box<dyn Error>> {
+        let SyncContext { local_handle, remote_handle, conflict_resolution, .. } = *ctx;
+        let remote_item = remote_handle.read().unwrap().get_item_by_url(url).await?;
+        let remote_item = match remote_item {
+            None => return Err("The item has been deleted from the server in the meantime. This will be handled at the next sync".into()),
+            Some(item) => item,
+        };
+        let remote_tag = match remote_item.sync_status() {
+            SyncStatus::Synced(tag) => tag.clone(),
+            _ => return Err(format!("Inconsistency: remote item {} has no version tag", url).into()),
+        };
+        let local_item = local_handle.read().unwrap().get_item_by_url(url).await.cloned();
+        let local_item = match local_item {
+            None => return Err(format!("Inconsistent state: missing task {} from the local tasks", url).into()),
+            Some(item) => item,
+        };
+        let state_before = ItemState::new(&local_item);
+        let outcome = conflict_resolution.resolve(&Conflict::new(url.clone(), ConflictKind::BothModified, local_item.clone(), Some(remote_item.clone())));
 
-    async fn sync_calendar_pair(cal_local: Arc<Mutex<T>>, cal_remote: Arc<Mutex<U>>, progress: &mut SyncProgress) -> Result<(), Box<dyn Error>> {
-        let mut cal_remote = cal_remote.lock().unwrap();
-        let mut cal_local = cal_local.lock().unwrap();
-        let cal_name = cal_local.name().to_string();
+        match outcome {
+            ConflictOutcome::KeptLocal => {
+                // Overwriting the server version is done by telling the server the latest version tag
+                let mut item = local_item;
+                item.set_sync_status(SyncStatus::LocallyModified(remote_tag));
+                let new_ss = remote_handle.write().unwrap().update_item(item.clone()).await?;
+                item.set_sync_status(new_ss);
+                Self::save_uploaded_item(&mut *local_handle.write().unwrap(), item, &state_before, progress).await;
+                progress.item_synced(url, ItemOperation::Updated, SyncDirection::Pushed);
+            },
+            ConflictOutcome::KeptRemote => {
+                let mut cal_local = local_handle.write().unwrap();
+                Self::check_unchanged(&*cal_local, url, &state_before).await?;
+                cal_local.update_item(remote_item).await?;
+                progress.item_synced(url, ItemOperation::Updated, SyncDirection::Pulled);
+            },
+            ConflictOutcome::KeptBoth => {
+                let mut copy = local_item;
+                let cal_url = local_handle.read().unwrap().url().clone();
+                copy.set_url(CalendarUrl::from(cal_url).random_item_url());
+                copy.set_uid(Uid::random());
+                copy.set_sync_status(SyncStatus::NotSynced);
+                let copy_url = copy.url().clone();
+                let copy_ss = remote_handle.write().unwrap().add_item(copy.clone()).await?;
+                copy.set_sync_status(copy_ss);
+                let mut cal_local = local_handle.write().unwrap();
+                cal_local.add_item(copy).await?;
+                progress.item_synced(&copy_url, ItemOperation::Added, SyncDirection::Pushed);
 
-        progress.info(&format!("Syncing calendar {}", cal_name));
-        progress.reset_counter();
-        progress.feedback(SyncEvent::InProgress{
-            calendar: cal_name.clone(),
-            items_done_already: 0,
-            details: "started".to_string()
-        });
+                Self::check_unchanged(&*cal_local, url, &state_before).await?;
+                cal_local.update_item(remote_item).await?;
+                progress.item_synced(url, ItemOperation::Updated, SyncDirection::Pulled);
+            },
+        }
+        Ok(outcome)
+    }
 
-        // Step 1 - find the differences
-        progress.debug("Finding the differences to sync...");
+    /// Returns an error in case a local item is no longer the way it was when the sync has read it, because it has been changed in the meantime (e.g. by an app)
+    async fn check_unchanged(cal_local: &T, url: &Url, state_before: &ItemState) -> Result<(), Box<dyn Error>> {
+        match cal_local.get_item_by_url(url).await.map(ItemState::new) {
+            Some(state) if &state == state_before => Ok(()),
+            _ => Err(format!("Item {} has been changed locally in the meantime. This will be handled at the next sync", url).into()),
+        }
+    }
+
+    /// Compare the local and the remote versions of a calendar. This does not change anything (apart from the log and the feedback of `progress`)
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(calendar = %local.url)))]
+    async fn find_differences(local: &LocalState, cal_remote: &U, window: Option<(DateTime<Utc>, DateTime<Utc>)>, progress: &mut SyncProgress) -> Result<Differences, Box<dyn Error>> {
+        let cal_name = local.name.clone();
         let mut local_del = HashSet::new();
         let mut remote_del = HashSet::new();
         let mut local_changes = HashSet::new();
         let mut remote_changes = HashSet::new();
         let mut local_additions = HashSet::new();
         let mut remote_additions = HashSet::new();
+        let mut interrupted_uploads = HashSet::new();
+        let mut conflicts = Vec::new();
+
+        // Only ask for what has changed since the last sync when the server supports it (RFC 6578), and list every item otherwise
+        let changes = match local.sync_token.as_deref() {
+            None => None,
+            Some(token) => match cal_remote.get_changes_since(token).await {
+                Ok(changes) => changes,
+                Err(err) => {
+                    progress.info(&format!("Unable to get what has changed in calendar {} since the last sync ({}). Listing all its items instead", cal_name, err));
+                    None
+                },
+            },
+        };
+        let incremental = changes.is_some();
+        let (mut remote_items, new_sync_token) = match changes {
+            Some(changes) => {
+                progress.debug(&format!("{} items have changed and {} have been deleted on the server since the last sync", changes.changed().len(), changes.deleted().len()));
+                (Self::remote_version_tags_after(local, &changes), Some(changes.sync_token().to_string()))
+            },
+            None => {
+                // The token is fetched before listing the items, so that the next sync cannot miss any change that happens in the meantime
+                let sync_token = match cal_remote.get_sync_token().await {
+                    Ok(token) => token,
+                    Err(err) => {
+                        progress.info(&format!("Unable to get the sync token of calendar {}: {}", cal_name, err));
+                        None
+                    },
+                };
+                let items = match window {
+                    None => cal_remote.get_item_version_tags().await?,
+                    Some((start, end)) => cal_remote.get_item_version_tags_between(start, end).await?,
+                };
+                (items, sync_token)
+            },
+        };
 
-        let remote_items = cal_remote.get_item_version_tags().await?;
+        // The server does not list the events that are out of the sync window.
+        // Events that are missing from such a listing may have been moved out of the window on the server, so their local copies are left as they are
+        // (only a full listing or a sync-collection tells they have been deleted), and the ones with local changes are checked one by one
+        let mut out_of_window = HashSet::new();
+        if let (Some(_), false) = (window, incremental) {
+            let mut to_check = Vec::new();
+            for (url, state) in &local.items {
+                if state.is_event == false || remote_items.contains_key(url) {
+                    continue;
+                }
+                match &state.sync_status {
+                    SyncStatus::Synced(_) => { out_of_window.insert(url.clone()); },
+                    SyncStatus::LocallyModified(_) | SyncStatus::LocallyDeleted(_) => to_check.push(url.clone()),
+                    SyncStatus::NotSynced => (),
+                }
+            }
+            for batch in to_check.chunks(DOWNLOAD_BATCH_SIZE) {
+                for item in cal_remote.get_items_by_url(batch).await?.into_iter().flatten() {
+                    if let Some(vt) = item.sync_status().version_tag() {
+                        remote_items.insert(item.url().clone(), vt.clone());
+                    }
+                }
+            }
+        }
         progress.feedback(SyncEvent::InProgress{
             calendar: cal_name.clone(),
             items_done_already: 0,
             details: format!("{} remote items", remote_items.len()),
         });
 
-        let mut local_items_to_handle = cal_local.get_item_urls().await?;
+        // Evicted items are not downloaded again, only their version tags are kept up to date
+        // (evicted items that are not listed by a windowed enumeration may just be out of the window)
+        let mut evicted_changes: Vec<(Url, Option<VersionTag>)> = local.evicted_items.keys()
+            .filter(|url| (window.is_none() || incremental) && remote_items.contains_key(url) == false)
+            .map(|url| (url.clone(), None))
+            .collect();
+
+        let mut local_items_to_handle: HashSet<Url> = local.items.keys().cloned().collect();
+        local_items_to_handle.retain(|url| out_of_window.contains(url) == false);
         for (url, remote_tag) in remote_items {
             progress.trace(&format!("***** Considering remote item {}...", url));
-            match cal_local.get_item_by_url(&url).await {
+            match local.items.get(&url) {
                 None => {
-                    // This was created on the remote
-                    progress.debug(&format!("*   {} is a remote addition", url));
-                    remote_additions.insert(url);
+                    match local.evicted_items.get(&url) {
+                        Some(evicted_tag) => {
+                            if evicted_tag != &remote_tag {
+                                progress.debug(&format!("*   {} has been evicted, and changed on the remote", url));
+                                evicted_changes.push((url, Some(remote_tag)));
+                            }
+                        },
+                        None => {
+                            // This was created on the remote
+                            progress.debug(&format!("*   {} is a remote addition", url));
+                            remote_additions.insert(url);
+                        },
+                    }
                 },
                 Some(local_item) => {
                     if local_items_to_handle.remove(&url) == false {
                         progress.error(&format!("Inconsistent state: missing task {} from the local tasks", url));
                     }
 
-                    match local_item.sync_status() {
+                    match &local_item.sync_status {
                         SyncStatus::NotSynced => {
-                            progress.error(&format!("URL reuse between remote and local sources ({}). Ignoring this item in the sync", url));
-                            continue;
+                            // Either a previous sync has been interrupted after uploading this item but before its local sync status was saved,
+                            // or this is a URL reuse. This will be checked against the server
+                            progress.debug(&format!("*   {} may be an interrupted upload", url));
+                            interrupted_uploads.insert(url);
                         },
                         SyncStatus::Synced(local_tag) => {
                             if &remote_tag != local_tag {
@@ -238,12 +2216,15 @@ where
                         SyncStatus::LocallyModified(local_tag) => {
                             if &remote_tag == local_tag {
                                 // This has been changed locally
+                                if let Some(rejection) = local.rejections.get(&url) {
+                                    progress.info(&format!("Local change {} has previously been refused by the server ({}). It will not be pushed until it is modified again", url, rejection));
+                                    continue;
+                                }
                                 progress.debug(&format!("*   {} is a local change", url));
                                 local_changes.insert(url);
                             } else {
-                                progress.info(&format!("Conflict: task {} has been modified in both sources. Using the remote version.", url));
-                                progress.debug(&format!("*   {} is considered a remote change", url));
-                                remote_changes.insert(url);
+                                progress.debug(&format!("*   {} has been modified in both sources", url));
+                                conflicts.push((url, ConflictKind::BothModified, Some(remote_tag)));
                             }
                         },
                         SyncStatus::LocallyDeleted(local_tag) => {
@@ -252,9 +2233,8 @@ where
                                 progress.debug(&format!("*   {} is a local deletion", url));
                                 local_del.insert(url);
                             } else {
-                                progress.info(&format!("Conflict: task {} has been locally deleted and remotely modified. Reverting to the remote version.", url));
-                                progress.debug(&format!("*   {} is a considered a remote change", url));
-                                remote_changes.insert(url);
+                                progress.debug(&format!("*   {} has been locally deleted and remotely modified", url));
+                                conflicts.push((url, ConflictKind::LocallyDeleted, Some(remote_tag)));
                             }
                         },
                     }
@@ -265,7 +2245,7 @@ where
         // Also iterate on the local tasks that are not on the remote
         for url in local_items_to_handle {
             progress.trace(&format!("##### Considering local item {}...", url));
-            let local_item = match cal_local.get_item_by_url(&url).await {
+            let local_item = match local.items.get(&url) {
                 None => {
                     progress.error(&format!("Inconsistent state: missing task {} from the local tasks", url));
                     continue;
@@ -273,7 +2253,7 @@ where
                 Some(item) => item,
             };
 
-            match local_item.sync_status() {
+            match &local_item.sync_status {
                 SyncStatus::Synced(_) => {
                     // This item has been removed from the remote
                     progress.debug(&format!("#   {} is a deletion from the server", url));
@@ -281,6 +2261,10 @@ where
                 },
                 SyncStatus::NotSynced => {
                     // This item has just been locally created
+                    if let Some(rejection) = local.rejections.get(&url) {
+                        progress.info(&format!("Local addition {} has previously been refused by the server ({}). It will not be pushed until it is modified again", url, rejection));
+                        continue;
+                    }
                     progress.debug(&format!("#   {} has been locally created", url));
                     local_additions.insert(url);
                 },
@@ -290,178 +2274,374 @@ where
                     remote_del.insert(url);
                 },
                 SyncStatus::LocallyModified(_) => {
-                    progress.info(&format!("Conflict: item {} has been deleted from the server and locally modified. Deleting the local copy", url));
-                    remote_del.insert(url);
+                    progress.debug(&format!("#   {} has been deleted from the server and locally modified", url));
+                    conflicts.push((url, ConflictKind::RemotelyDeleted, None));
                 },
             }
         }
 
+        Ok(Differences {
+            local_del, remote_del, local_changes, remote_changes,
+            local_additions, remote_additions, interrupted_uploads,
+            conflicts, evicted_changes, new_sync_token,
+        })
+    }
 
-        // Step 2 - commit changes
-        progress.trace("Committing changes...");
-        for url_del in local_del {
-            progress.debug(&format!("> Pushing local deletion {} to the server", url_del));
-            progress.increment_counter(1);
-            progress.feedback(SyncEvent::InProgress{
-                calendar: cal_name.clone(),
-                items_done_already: progress.counter(),
-                details: Self::item_name(&cal_local, &url_del).await,
-            });
+    /// The version tags of the remote items, given what the server had at the last sync (i.e. what the local items know of it) and what has changed since then
+    fn remote_version_tags_after(local: &LocalState, changes: &CollectionChanges) -> HashMap<Url, VersionTag> {
+        let mut remote_items: HashMap<Url, VersionTag> = local.items.iter()
+            .filter_map(|(url, state)| state.sync_status.version_tag().map(|tag| (url.clone(), tag.clone())))
+            .chain(local.evicted_items.iter().map(|(url, tag)| (url.clone(), tag.clone())))
+            .collect();
+        for url in changes.deleted() {
+            remote_items.remove(url);
+        }
+        remote_items.extend(changes.changed().iter().map(|(url, tag)| (url.clone(), tag.clone())));
+        remote_items
+    }
+
+
+    async fn item_name(cal: &T, url: &Url) -> String {
+        cal.get_item_by_url(url).await.map(|item| item.name()).unwrap_or_default().to_string()
+    }
 
-            match cal_remote.delete_item(&url_del).await {
+    /// Repair the sync status of local items that have been uploaded during a previous sync, that has been interrupted before their new status could be saved. \
+    /// Such items are still `NotSynced` locally, but they exist on the server already.
+    ///
+    /// In case the server has exactly our version, the local item is simply marked as synced.
+    /// Otherwise, the most recent version wins (this is either a local or a remote change)
+    async fn recover_interrupted_uploads(
+        mut interrupted_uploads: HashSet<Url>,
+        local_changes: &mut HashSet<Url>,
+        remote_changes: &mut HashSet<Url>,
+        local_state: &mut LocalState,
+        ctx: &SyncContext<'_, T, U>,
+        progress: &mut SyncProgress,
+    ) {
+        let SyncContext { local_handle, remote_handle, comparison_rules, .. } = *ctx;
+        for batch in interrupted_uploads.drain().chunks(DOWNLOAD_BATCH_SIZE).into_iter() {
+            let urls: Vec<Url> = batch.collect();
+            let downloaded = remote_handle.read().unwrap().get_items_by_url(&urls).await;
+            let remote_items = match downloaded {
                 Err(err) => {
-                    progress.warn(&format!("Unable to delete remote item {}: {}", url_del, err));
-                },
-                Ok(()) => {
-                    // Change the local copy from "marked to deletion" to "actually deleted"
-                    if let Err(err) = cal_local.immediately_delete_item(&url_del).await {
-                        progress.error(&format!("Unable to permanently delete local item {}: {}", url_del, err));
-                    }
+                    progress.warn(&format!("Unable to check whether {:?} have been uploaded already: {}. Skipping them.", urls, err));
+                    continue;
                 },
-            }
-        }
+                Ok(items) => items,
+            };
 
-        for url_del in remote_del {
-            progress.debug(&format!("> Applying remote deletion {} locally", url_del));
-            progress.increment_counter(1);
-            progress.feedback(SyncEvent::InProgress{
-                calendar: cal_name.clone(),
-                items_done_already: progress.counter(),
-                details: Self::item_name(&cal_local, &url_del).await,
-            });
-            if let Err(err) = cal_local.immediately_delete_item(&url_del).await {
-                progress.warn(&format!("Unable to delete local item {}: {}", url_del, err));
-            }
-        }
+            let mut cal_local = local_handle.write().unwrap();
+            for (url, remote_item) in urls.into_iter().zip(remote_items) {
+                let remote_item = match remote_item {
+                    None => {
+                        progress.error(&format!("Inconsistency: item {} has vanished from the remote end", url));
+                        continue;
+                    },
+                    Some(item) => item,
+                };
+                if local_state.is_unchanged(&url, cal_local.get_item_by_url(&url).await) == false {
+                    progress.debug(&format!("> Item {} has been changed locally during the sync, it will be synced at the next sync", url));
+                    continue;
+                }
+                let mut local_item = match cal_local.get_item_by_url_mut(&url).await {
+                    None => {
+                        progress.error(&format!("Inconsistent state: missing task {} from the local tasks", url));
+                        continue;
+                    },
+                    Some(item) => item,
+                };
 
-        Self::apply_remote_additions(
-            remote_additions,
-            &mut *cal_local,
-            &mut *cal_remote,
-            progress,
-            &cal_name
-        ).await;
+                if local_item.uid() != remote_item.uid() {
+                    progress.error(&format!("URL reuse between remote and local sources ({}). Ignoring this item in the sync", url));
+                    continue;
+                }
 
-        Self::apply_remote_changes(
-            remote_changes,
-            &mut *cal_local,
-            &mut *cal_remote,
-            progress,
-            &cal_name
-        ).await;
+                let remote_tag = match remote_item.sync_status() {
+                    SyncStatus::Synced(tag) => tag.clone(),
+                    _ => {
+                        progress.error(&format!("Inconsistency: remote item {} has no version tag", url));
+                        continue;
+                    },
+                };
 
+                // The content of both versions is a better evidence than their dates, that may come from different clocks
+                if comparison_rules.are_equivalent(&local_item, &remote_item) {
+                    progress.info(&format!("Item {} had already been uploaded by an interrupted sync. Marking it as synced", url));
+                    local_item.set_sync_status(SyncStatus::Synced(remote_tag));
+                    continue;
+                }
+                match comparison_rules.most_recent(&local_item, &remote_item) {
+                    MostRecent::Local => {
+                        progress.info(&format!("Item {} had already been uploaded by an interrupted sync, and has been locally modified since then", url));
+                        local_item.set_sync_status(SyncStatus::LocallyModified(remote_tag));
+                        local_changes.insert(url);
+                    },
+                    MostRecent::Remote => {
+                        progress.info(&format!("Item {} had already been uploaded by an interrupted sync, and has been remotely modified since then. Using the remote version.", url));
+                        remote_changes.insert(url);
+                    },
+                    MostRecent::Unknown => {
+                        // Just like any other conflict, the remote version wins
+                        progress.info(&format!("Item {} had already been uploaded by an interrupted sync, and it is unclear which version is the most recent one. Using the remote version.", url));
+                        remote_changes.insert(url);
+                    },
+                }
+            }
+        }
+    }
 
-        for url_add in local_additions {
-            progress.debug(&format!("> Pushing local addition {} to the server", url_add));
-            progress.increment_counter(1);
-            progress.feedback(SyncEvent::InProgress{
-                calendar: cal_name.clone(),
-                items_done_already: progress.counter(),
-                details: Self::item_name(&cal_local, &url_add).await,
-            });
-            match cal_local.get_item_by_url_mut(&url_add).await {
-                None => {
-                    progress.error(&format!("Inconsistency: created item {} has been marked for upload but is locally missing", url_add));
+    /// Detect items that have been moved on the server, i.e. deleted from a URL and re-created at another URL with the same UID. \
+    /// They are moved locally as well (rather than deleted and re-added), so that local changes that have not been synced yet are not lost.
+    ///
+    /// In case the item has also been modified locally, this is a conflict, that is resolved like any other one (see [`Provider::set_conflict_resolution`])
+    async fn detect_moves(
+        remote_del: &mut HashSet<Url>,
+        remote_additions: &mut HashSet<Url>,
+        local_changes: &mut HashSet<Url>,
+        local_additions: &mut HashSet<Url>,
+        local_state: &mut LocalState,
+        ctx: &SyncContext<'_, T, U>,
+        progress: &mut SyncProgress,
+    ) {
+        let SyncContext { local_handle, comparison_rules, .. } = *ctx;
+        let moves = Self::find_moves(remote_del, remote_additions, ctx, progress).await;
+        let cal_url = local_handle.read().unwrap().url().clone();
+        for (old_url, remote_item) in moves {
+            let new_url = remote_item.url().clone();
+            let remote_tag = match remote_item.sync_status() {
+                SyncStatus::Synced(tag) => tag.clone(),
+                _ => {
+                    progress.error(&format!("Inconsistency: remote item {} has no version tag", new_url));
                     continue;
                 },
-                Some(item) => {
-                    match cal_remote.add_item(item.clone()).await {
-                        Err(err) => progress.error(&format!("Unable to add item {} to remote calendar: {}", url_add, err)),
-                        Ok(new_ss) => {
-                            // Update local sync status
-                            item.set_sync_status(new_ss);
-                        },
+            };
+            // Local changes that are not the same as the remote ones conflict with them
+            let local_item = local_handle.read().unwrap().get_item_by_url(&old_url).await.cloned();
+            let outcome = match local_item {
+                Some(local_item) if matches!(local_item.sync_status(), SyncStatus::LocallyModified(_)) && comparison_rules.are_equivalent(&local_item, &remote_item) == false => {
+                    match Self::resolve_conflict(ctx, &old_url, ConflictKind::BothModified, Some(remote_item.clone()), progress).await {
+                        None => continue,
+                        Some((_local_item, _remote_item, outcome)) => Some(outcome),
                     }
                 },
+                _ => None,
             };
-        }
 
-        for url_change in local_changes {
-            progress.debug(&format!("> Pushing local change {} to the server", url_change));
-            progress.increment_counter(1);
-            progress.feedback(SyncEvent::InProgress{
-                calendar: cal_name.clone(),
-                items_done_already: progress.counter(),
-                details: Self::item_name(&cal_local, &url_change).await,
-            });
-            match cal_local.get_item_by_url_mut(&url_change).await {
-                None => {
-                    progress.error(&format!("Inconsistency: modified item {} has been marked for upload but is locally missing", url_change));
-                    continue;
+            let mut cal_local = local_handle.write().unwrap();
+            let current = cal_local.get_item_by_url(&old_url).await;
+            if local_state.is_unchanged(&old_url, current) == false {
+                progress.debug(&format!("> Item {} has been changed locally during the sync, its move will be handled at the next sync", old_url));
+                continue;
+            }
+            let mut local_item = match current {
+                None => continue,
+                Some(item) => item.clone(),
+            };
+            if let Some(outcome) = outcome {
+                progress.info(&format!("Conflict: item {} (moved to {} on the server) is resolved as {:?}", old_url, new_url, outcome));
+            }
+
+            let moved_item = match outcome {
+                Some(ConflictOutcome::KeptLocal) => {
+                    progress.info(&format!("Item {} has been moved to {} on the server. Its local changes will be pushed there", old_url, new_url));
+                    local_item.set_url(new_url.clone());
+                    local_item.set_sync_status(SyncStatus::LocallyModified(remote_tag));
+                    local_item
+                },
+                Some(ConflictOutcome::KeptBoth) => {
+                    progress.info(&format!("Item {} has been moved to {} on the server. Its local version is saved as a new item", old_url, new_url));
+                    let mut copy = local_item;
+                    copy.set_url(CalendarUrl::from(cal_url.clone()).random_item_url());
+                    copy.set_uid(Uid::random());
+                    copy.set_sync_status(SyncStatus::NotSynced);
+                    let copy_url = copy.url().clone();
+                    match cal_local.add_item(copy).await {
+                        Ok(_) => { local_additions.insert(copy_url); },
+                        Err(err) => progress.error(&format!("Unable to save a copy of conflicting item {}: {}", old_url, err)),
+                    }
+                    remote_item
+                },
+                Some(ConflictOutcome::KeptRemote) | None => {
+                    progress.info(&format!("Item {} has been moved to {} on the server", old_url, new_url));
+                    remote_item
                 },
-                Some(item) => {
-                    match cal_remote.update_item(item.clone()).await {
-                        Err(err) => progress.error(&format!("Unable to update item {} in remote calendar: {}", url_change, err)),
-                        Ok(new_ss) => {
-                            // Update local sync status
-                            item.set_sync_status(new_ss);
-                        },
-                    };
-                }
             };
-        }
 
-        Ok(())
+            if let Err(err) = cal_local.add_item(moved_item).await {
+                progress.error(&format!("Unable to move local item {} to {}: {}", old_url, new_url, err));
+                continue;
+            }
+            if let Err(err) = cal_local.immediately_delete_item(&old_url).await {
+                progress.error(&format!("Unable to delete local item {} that has been moved to {}: {}", old_url, new_url, err));
+            }
+            remote_del.remove(&old_url);
+            remote_additions.remove(&new_url);
+            match outcome {
+                Some(ConflictOutcome::KeptLocal) => { local_changes.insert(new_url); },
+                _ => progress.item_synced(&new_url, ItemOperation::Updated, SyncDirection::Pulled),
+            }
+            if let Some(outcome) = outcome {
+                progress.record_conflict(ResolvedConflict::new(cal_url.clone(), old_url, ConflictKind::BothModified, outcome));
+            }
+        }
     }
 
+    /// Find the items that have been moved on the server (see [`Self::detect_moves`]). Returns their local URLs, along with their server versions (at their new URLs)
+    async fn find_moves(remote_del: &HashSet<Url>, remote_additions: &HashSet<Url>, ctx: &SyncContext<'_, T, U>, progress: &mut SyncProgress) -> Vec<(Url, Item)> {
+        let SyncContext { local_handle, remote_handle, .. } = *ctx;
+        if remote_del.is_empty() || remote_additions.is_empty() {
+            return Vec::new();
+        }
 
-    async fn item_name(cal: &T, url: &Url) -> String {
-        cal.get_item_by_url(url).await.map(|item| item.name()).unwrap_or_default().to_string()
-    }
+        let mut vanished = HashMap::new();
+        {
+            let cal_local = local_handle.read().unwrap();
+            for url in remote_del.iter() {
+                if let Some(item) = cal_local.get_item_by_url(url).await {
+                    match item.sync_status() {
+                        SyncStatus::Synced(_) | SyncStatus::LocallyModified(_) => { vanished.insert(item.uid().clone(), url.clone()); },
+                        // Items that are deleted on both ends do not matter
+                        SyncStatus::NotSynced | SyncStatus::LocallyDeleted(_) => (),
+                    }
+                }
+            }
+        }
+        if vanished.is_empty() {
+            return Vec::new();
+        }
 
-    async fn apply_remote_additions(
-        mut remote_additions: HashSet<Url>,
-        cal_local: &mut T,
-        cal_remote: &mut U,
-        progress: &mut SyncProgress,
-        cal_name: &str
-    ) {
-        for batch in remote_additions.drain().chunks(DOWNLOAD_BATCH_SIZE).into_iter() {
-            Self::fetch_batch_and_apply(BatchDownloadType::RemoteAdditions, batch, cal_local, cal_remote, progress, cal_name).await;
+        // We have to download the remote additions to know their UIDs. They are downloaded again when they are applied, but moves should be rare enough
+        let mut moves = Vec::new();
+        let additions: Vec<Url> = remote_additions.iter().cloned().collect();
+        for batch in additions.chunks(DOWNLOAD_BATCH_SIZE) {
+            let downloaded = remote_handle.read().unwrap().get_items_by_url(batch).await;
+            let remote_items = match downloaded {
+                Err(err) => {
+                    progress.warn(&format!("Unable to check whether {:?} have been moved: {}. They will be considered as new items.", batch, err));
+                    continue;
+                },
+                Ok(items) => items,
+            };
+            for remote_item in remote_items.into_iter().flatten() {
+                if let Some(old_url) = vanished.remove(remote_item.uid()) {
+                    moves.push((old_url, remote_item));
+                }
+            }
         }
+        moves
     }
 
-    async fn apply_remote_changes(
-        mut remote_changes: HashSet<Url>,
-        cal_local: &mut T,
-        cal_remote: &mut U,
+    /// Download items by batches, `parallelism` batches at a time, and apply every batch locally as soon as it has been downloaded
+    async fn download_and_apply(
+        batch_type: BatchDownloadType,
+        mut urls: HashSet<Url>,
+        local_state: &mut LocalState,
+        comparison_rules: &ComparisonRules,
+        ctx: &SyncContext<'_, T, U>,
         progress: &mut SyncProgress,
-        cal_name: &str
     ) {
-        for batch in remote_changes.drain().chunks(DOWNLOAD_BATCH_SIZE).into_iter() {
-            Self::fetch_batch_and_apply(BatchDownloadType::RemoteChanges, batch, cal_local, cal_remote, progress, cal_name).await;
+        let batches: Vec<Vec<Url>> = urls.drain()
+            .chunks(DOWNLOAD_BATCH_SIZE).into_iter()
+            .map(|batch| batch.collect())
+            .collect();
+
+        if progress.check_cancelled().is_err() {
+            return;
+        }
+        let cancellation = progress.cancellation().cloned();
+        let cal_remote = ctx.remote_handle.read().unwrap();
+        let cal_remote = &*cal_remote;
+        let mut downloads = futures_util::stream::iter(batches)
+            .map(|batch| {
+                let cancellation = cancellation.clone();
+                async move {
+                    // Batches that have not been started yet are not downloaded once the sync has been cancelled
+                    let result = match cancellation.map(|token| token.is_cancelled()) {
+                        Some(true) => Err(crate::Error::Cancelled),
+                        _ => cal_remote.get_items_by_url(&batch).await,
+                    };
+                    (batch, result)
+                }
+            })
+            .buffer_unordered(ctx.download_parallelism.max(1));
+
+        while let Some((batch, result)) = downloads.next().await {
+            if progress.check_cancelled().is_err() {
+                // Dropping the stream aborts the downloads that are in progress
+                return;
+            }
+            let batch_len = batch.len();
+            let mut cal_local = ctx.local_handle.write().unwrap();
+            Self::apply_batch(&batch_type, batch, result, &mut *cal_local, local_state, comparison_rules, progress).await;
+            progress.advance_phase(batch_len);
         }
     }
 
-    async fn fetch_batch_and_apply<I: Iterator<Item = Url>>(
-        batch_type: BatchDownloadType,
-        remote_additions: I,
+    async fn apply_batch(
+        batch_type: &BatchDownloadType,
+        list_of_additions: Vec<Url>,
+        download_result: Result<Vec<Option<Item>>, crate::Error>,
         cal_local: &mut T,
-        cal_remote: &mut U,
+        local_state: &mut LocalState,
+        comparison_rules: &ComparisonRules,
         progress: &mut SyncProgress,
-        cal_name: &str
     ) {
-        progress.debug(&format!("> Applying a batch of {} locally", batch_type) /* too bad Chunks does not implement ExactSizeIterator, that could provide useful debug info. See https://github.com/rust-itertools/itertools/issues/171 */);
+        progress.debug(&format!("> Applying a batch of {} {} locally", list_of_additions.len(), batch_type));
 
-        let list_of_additions: Vec<Url> = remote_additions.map(|url| url.clone()).collect();
-        match cal_remote.get_items_by_url(&list_of_additions).await {
+        match download_result {
             Err(err) => {
                 progress.warn(&format!("Unable to get the batch of {} {:?}: {}. Skipping them.", batch_type, list_of_additions, err));
+                for url in &list_of_additions {
+                    progress.item_failed(url, err.to_string());
+                }
             },
             Ok(items) => {
-                for item in items {
+                for (url, item) in list_of_additions.iter().zip(items) {
                     match item {
                         None => {
-                            progress.error(&format!("Inconsistency: an item from the batch has vanished from the remote end"));
+                            progress.error(&format!("Inconsistency: item {} from the batch has vanished from the remote end", url));
+                            progress.item_failed(url, "The item has vanished from the server".to_string());
                             continue;
                         },
                         Some(new_item) => {
+                            if local_state.is_unchanged(new_item.url(), cal_local.get_item_by_url(new_item.url()).await) == false {
+                                progress.debug(&format!("> Item {} has been changed locally during the sync, it will be synced at the next sync", new_item.url()));
+                                continue;
+                            }
+                            let mut operation = match batch_type {
+                                BatchDownloadType::RemoteAdditions => Some(ItemOperation::Added),
+                                BatchDownloadType::RemoteChanges => Some(ItemOperation::Updated),
+                            };
                             let local_update_result = match batch_type {
                                 BatchDownloadType::RemoteAdditions => cal_local.add_item(new_item.clone()).await,
-                                BatchDownloadType::RemoteChanges => cal_local.update_item(new_item.clone()).await,
+                                BatchDownloadType::RemoteChanges => {
+                                    let unchanged = cal_local.get_item_by_url(new_item.url()).await
+                                        .map(|local_item| comparison_rules.are_equivalent(local_item, &new_item))
+                                        .unwrap_or(false);
+                                    match unchanged {
+                                        false => cal_local.update_item(new_item.clone()).await,
+                                        true => {
+                                            // Only the version tag has changed (e.g. because the server has rewritten the item), the local version is kept
+                                            progress.debug(&format!("> Item {} has not actually changed on the server", new_item.url()));
+                                            operation = None;
+                                            match cal_local.get_item_by_url_mut(new_item.url()).await {
+                                                None => Err(format!("Item {} has vanished from the local calendar", new_item.url()).into()),
+                                                Some(mut local_item) => {
+                                                    local_item.set_sync_status(new_item.sync_status().clone());
+                                                    Ok(new_item.sync_status().clone())
+                                                },
+                                            }
+                                        },
+                                    }
+                                },
                             };
-                            if let Err(err) = local_update_result {
-                                progress.error(&format!("Not able to add item {} to local calendar: {}", new_item.url(), err));
+                            match (local_update_result, operation) {
+                                (Err(err), _) => {
+                                    progress.error(&format!("Not able to add item {} to local calendar: {}", new_item.url(), err));
+                                    progress.item_failed(new_item.url(), err.to_string());
+                                },
+                                (Ok(_), Some(operation)) => progress.item_synced(new_item.url(), operation, SyncDirection::Pulled),
+                                (Ok(_), None) => (),
                             }
                         },
                     }
@@ -469,12 +2649,12 @@ where
 
                 // Notifying every item at the same time would not make sense. Let's notify only one of them
                 let one_item_name = match list_of_additions.get(0) {
-                    Some(url) => Self::item_name(&cal_local, &url).await,
+                    Some(url) => Self::item_name(&*cal_local, &url).await,
                     None => String::from("<unable to get the name of the first batched item>"),
                 };
                 progress.increment_counter(list_of_additions.len());
                 progress.feedback(SyncEvent::InProgress{
-                    calendar: cal_name.to_string(),
+                    calendar: local_state.name.clone(),
                     items_done_already: progress.counter(),
                     details: one_item_name,
                 });
@@ -484,8 +2664,38 @@ where
 }
 
 
-async fn get_or_insert_counterpart_calendar<H, N, I>(haystack_descr: &str, haystack: &mut H, cal_url: &Url, needle: Arc<Mutex<N>>)
-    -> Result<Arc<Mutex<I>>, Box<dyn Error>>
+/// Returns the server error contained in `err`, in case it means the server will never accept this request
+fn permanent_server_error(err: crate::Error) -> Option<ServerError> {
+    match err {
+        crate::Error::Http { source, .. } if source.is_permanent() => Some(source),
+        _ => None,
+    }
+}
+
+/// Returns an error in case `privileges` (the ones of the current user in a calendar) lack some of the `needed` ones
+fn check_privileges(calendar_url: &Url, privileges: Privileges, needed: Privileges) -> Result<(), ReadOnlyError> {
+    match privileges.contains(needed) {
+        true => Ok(()),
+        false => Err(ReadOnlyError::new(calendar_url.clone(), needed - privileges)),
+    }
+}
+
+/// Whether `err` means that the server has refused to overwrite changes made by someone else (see [`ServerError::is_edit_conflict`])
+fn is_edit_conflict(err: &crate::Error) -> bool {
+    match err {
+        crate::Error::Conflict { .. } => true,
+        crate::Error::Http { source, .. } => source.is_edit_conflict(),
+        _ => false,
+    }
+}
+
+/// Whether `err` means that the item to delete was not on the server (any more)
+fn is_already_deleted(err: &crate::Error) -> bool {
+    matches!(err, crate::Error::Http { status: 404, .. } | crate::Error::Http { status: 410, .. })
+}
+
+async fn get_or_insert_counterpart_calendar<H, N, I>(haystack_descr: &str, haystack: &mut H, cal_url: &Url, needle: Arc<RwLock<N>>)
+    -> Result<Arc<RwLock<I>>, Box<dyn Error>>
 where
     H: CalDavSource<I>,
     I: BaseCalendar,
@@ -498,7 +2708,7 @@ where
 
         // This calendar does not exist locally yet, let's add it
         log::debug!("Adding a {} calendar {}", haystack_descr, cal_url);
-        let src = needle.lock().unwrap();
+        let src = needle.read().unwrap();
         let name = src.name().to_string();
         let supported_comps = src.supported_components();
         let color = src.color();
@@ -508,7 +2718,7 @@ where
             supported_comps,
             color.cloned(),
         ).await{
-            return Err(err);
+            return Err(err.into());
         }
     }
 }
@@ -0,0 +1,40 @@
+//! Local changes that have not been pushed to the server yet (see [`Provider::pending_changes`](crate::provider::Provider::pending_changes))
+
+use url::Url;
+
+use crate::item::VersionTag;
+use crate::Item;
+
+/// What kind of local change is waiting to be pushed to the server
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PendingChangeKind {
+    /// The item has been created locally
+    Addition,
+    /// The item has been modified locally
+    Modification,
+    /// The item has been deleted locally
+    Deletion,
+}
+
+/// A local change that will be pushed to the server at the next sync
+#[derive(Clone, Debug)]
+pub struct PendingChange {
+    calendar_url: Url,
+    kind: PendingChangeKind,
+    local: Item,
+}
+
+impl PendingChange {
+    pub(crate) fn new(calendar_url: Url, kind: PendingChangeKind, local: Item) -> Self {
+        Self { calendar_url, kind, local }
+    }
+
+    pub fn calendar_url(&self) -> &Url { &self.calendar_url }
+    pub fn item_url(&self) -> &Url { self.local.url() }
+    pub fn kind(&self) -> PendingChangeKind { self.kind }
+    /// The local version of the item (for deletions, this is the item as it was when it has been deleted)
+    pub fn local(&self) -> &Item { &self.local }
+
+    /// The version tag of the item the last time it was synced, or `None` for items that have never been synced
+    pub fn last_synced_version_tag(&self) -> Option<&VersionTag> { self.local.last_known_version_tag() }
+}
@@ -0,0 +1,117 @@
+//! What a sync would do, computed without changing anything (see [`Provider::sync_plan`](crate::provider::Provider::sync_plan))
+
+use url::Url;
+
+use crate::provider::conflict::{ConflictOutcome, ResolvedConflict};
+use crate::provider::sync_result::{CalendarResult, ItemOperation, SyncDirection, SyncResult};
+
+/// What a sync would do to a calendar
+#[derive(Clone, Debug, PartialEq)]
+pub struct CalendarPlan {
+    calendar_url: Url,
+    name: String,
+    pub(crate) to_upload: Vec<Url>,
+    pub(crate) to_download: Vec<Url>,
+    pub(crate) to_delete_remotely: Vec<Url>,
+    pub(crate) to_delete_locally: Vec<Url>,
+    pub(crate) conflicts: Vec<ResolvedConflict>,
+    pub(crate) undecided_conflicts: Vec<Url>,
+}
+
+impl CalendarPlan {
+    pub(crate) fn new(calendar_url: Url, name: String) -> Self {
+        Self {
+            calendar_url, name,
+            to_upload: Vec::new(),
+            to_download: Vec::new(),
+            to_delete_remotely: Vec::new(),
+            to_delete_locally: Vec::new(),
+            conflicts: Vec::new(),
+            undecided_conflicts: Vec::new(),
+        }
+    }
+
+    pub fn calendar_url(&self) -> &Url { &self.calendar_url }
+    pub fn name(&self) -> &str { &self.name }
+    /// The local items that would be pushed to the server (additions and changes)
+    pub fn to_upload(&self) -> &[Url] { &self.to_upload }
+    /// The remote items that would be downloaded (additions and changes)
+    pub fn to_download(&self) -> &[Url] { &self.to_download }
+    /// The items that would be deleted from the server
+    pub fn to_delete_remotely(&self) -> &[Url] { &self.to_delete_remotely }
+    /// The items that would be deleted from the local source
+    pub fn to_delete_locally(&self) -> &[Url] { &self.to_delete_locally }
+    /// The items that have been changed on both ends, and which version would be kept. Their resolution is already included in the other lists
+    pub fn conflicts(&self) -> &[ResolvedConflict] { &self.conflicts }
+    /// The conflicting items whose fate is decided by a [`ConflictResolution::Custom`](crate::provider::conflict::ConflictResolution::Custom) policy, which is only called by the actual sync
+    pub fn undecided_conflicts(&self) -> &[Url] { &self.undecided_conflicts }
+
+    pub fn is_empty(&self) -> bool {
+        self.to_upload.is_empty() && self.to_download.is_empty()
+            && self.to_delete_remotely.is_empty() && self.to_delete_locally.is_empty()
+            && self.conflicts.is_empty() && self.undecided_conflicts.is_empty()
+    }
+
+    /// The plan of a calendar, from what a dry run would do to it
+    fn from_dry_run(result: &CalendarResult) -> Self {
+        let mut plan = Self::new(result.calendar_url().clone(), result.name().to_string());
+        for item in result.synced() {
+            let list = match (item.direction(), item.operation()) {
+                (SyncDirection::Pushed, ItemOperation::Deleted) => &mut plan.to_delete_remotely,
+                (SyncDirection::Pushed, _) => &mut plan.to_upload,
+                (SyncDirection::Pulled, ItemOperation::Deleted) => &mut plan.to_delete_locally,
+                (SyncDirection::Pulled, _) => &mut plan.to_download,
+            };
+            list.push(item.url().clone());
+        }
+        plan.conflicts = result.conflicts().to_vec();
+        plan.undecided_conflicts = result.undecided_conflicts().to_vec();
+        plan.sort();
+        plan
+    }
+
+    /// Sort every list, so that plans are easier to show (and to compare)
+    pub(crate) fn sort(&mut self) {
+        self.to_upload.sort();
+        self.to_download.sort();
+        self.to_delete_remotely.sort();
+        self.to_delete_locally.sort();
+        self.conflicts.sort_by(|a, b| a.item_url().cmp(b.item_url()));
+        self.undecided_conflicts.sort();
+    }
+}
+
+/// What a sync would do, e.g. to ask for a confirmation before running it
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SyncPlan {
+    calendars: Vec<CalendarPlan>,
+}
+
+impl SyncPlan {
+    pub(crate) fn new(mut calendars: Vec<CalendarPlan>) -> Self {
+        calendars.retain(|plan| plan.is_empty() == false);
+        calendars.sort_by(|a, b| a.calendar_url().cmp(b.calendar_url()));
+        Self { calendars }
+    }
+
+    /// The plan of every calendar, from what a dry run would do (see [`SyncOptions::dry_run`](crate::provider::SyncOptions::dry_run))
+    pub(crate) fn from_dry_run(result: &SyncResult) -> Self {
+        Self::new(result.calendars().iter().map(CalendarPlan::from_dry_run).collect())
+    }
+
+    /// The plans of the calendars a sync would change
+    pub fn calendars(&self) -> &[CalendarPlan] { &self.calendars }
+
+    /// Whether a sync would not change anything
+    pub fn is_empty(&self) -> bool { self.calendars.is_empty() }
+
+    /// Whether a sync would delete items (on either end), or lose some changes because of conflicts
+    pub fn is_destructive(&self) -> bool {
+        self.calendars.iter().any(|plan| {
+            plan.to_delete_remotely.is_empty() == false
+                || plan.to_delete_locally.is_empty() == false
+                || plan.conflicts.iter().any(|conflict| conflict.outcome() != ConflictOutcome::KeptBoth)
+                || plan.undecided_conflicts.is_empty() == false
+        })
+    }
+}
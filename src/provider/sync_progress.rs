@@ -1,6 +1,15 @@
 //! Utilities to track the progression of a sync
 
 use std::fmt::{Display, Error, Formatter};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use url::Url;
+
+use crate::error::CancelledError;
+use crate::provider::conflict::ResolvedConflict;
+use crate::provider::duplicates::Duplicates;
+use crate::provider::sync_result::{ItemOperation, SyncDirection, SyncResult};
 
 /// An event that happens during a sync
 #[derive(Clone, Debug)]
@@ -37,6 +46,80 @@ impl Default for SyncEvent {
 
 
 
+/// A step of a sync, as reported to [`SyncObserver`]s.
+///
+/// `total` is the number of items this step handles in the current calendar, `done` is how many of them have been handled already
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SyncPhase {
+    /// Listing the calendars of both sources
+    ListingCalendars,
+    /// Finding the differences between the local and the remote versions of a calendar
+    ListingItems { calendar: String },
+    /// Applying deletions (from the local source to the server, and from the server to the local source)
+    Deleting { calendar: String, done: usize, total: usize },
+    /// Downloading the items that have been added or changed on the server
+    Fetching { calendar: String, done: usize, total: usize },
+    /// Pushing local additions and changes to the server
+    Pushing { calendar: String, done: usize, total: usize },
+    /// The sync is over
+    Finished { success: bool },
+}
+
+impl SyncPhase {
+    fn advance(&mut self, increment: usize) {
+        match self {
+            SyncPhase::Deleting { done, total, .. }
+            | SyncPhase::Fetching { done, total, .. }
+            | SyncPhase::Pushing { done, total, .. } => *done = (*done + increment).min(*total),
+            _ => (),
+        }
+    }
+}
+
+/// Something that is told about the progress of a sync, e.g. to show a progress bar (see [`Provider::sync_with_observer`](crate::provider::Provider::sync_with_observer)).
+///
+/// This is implemented for closures, e.g. `Arc::new(|phase: &SyncPhase| println!("{:?}", phase))`
+pub trait SyncObserver: Send + Sync {
+    /// Called whenever the sync enters a new phase, and whenever an item (or a batch of items) is done
+    fn on_phase(&self, phase: &SyncPhase);
+}
+
+impl<F> SyncObserver for F
+where
+    F: Fn(&SyncPhase) + Send + Sync
+{
+    fn on_phase(&self, phase: &SyncPhase) {
+        self(phase)
+    }
+}
+
+
+
+/// A handle to abort a running sync (see [`Provider::sync_with_cancellation`](crate::provider::Provider::sync_with_cancellation)).
+///
+/// Clones share the same state, so that a clone can be kept (e.g. by a "Cancel" button) while the sync runs
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask the sync to stop. It stops before its next network operation, leaving every item in a consistent state (what has not been synced yet will be at the next sync)
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+
+
 /// See [`feedback_channel`]
 pub type FeedbackSender = tokio::sync::watch::Sender<SyncEvent>;
 /// See [`feedback_channel`]
@@ -55,13 +138,39 @@ pub struct SyncProgress {
     n_errors: u32,
     feedback_channel: Option<FeedbackSender>,
     counter: usize,
+    result: SyncResult,
+    /// The calendar that is being synced
+    current_calendar: Option<Url>,
+    observer: Option<Arc<dyn SyncObserver>>,
+    phase: Option<SyncPhase>,
+    cancellation: Option<CancellationToken>,
 }
 impl SyncProgress {
     pub fn new() -> Self {
-        Self { n_errors: 0, feedback_channel: None, counter: 0 }
+        Self { n_errors: 0, feedback_channel: None, counter: 0, result: SyncResult::default(), current_calendar: None, observer: None, phase: None, cancellation: None }
+    }
+    pub fn new_with_observer(observer: Arc<dyn SyncObserver>) -> Self {
+        Self { n_errors: 0, feedback_channel: None, counter: 0, result: SyncResult::default(), current_calendar: None, observer: Some(observer), phase: None, cancellation: None }
     }
     pub fn new_with_feedback_channel(channel: FeedbackSender) -> Self {
-        Self { n_errors: 0, feedback_channel: Some(channel), counter: 0 }
+        Self { n_errors: 0, feedback_channel: Some(channel), counter: 0, result: SyncResult::default(), current_calendar: None, observer: None, phase: None, cancellation: None }
+    }
+
+    /// Make this sync stop whenever `token` is cancelled
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+    /// Returns an error in case the sync has been cancelled
+    pub fn check_cancelled(&self) -> Result<(), CancelledError> {
+        match self.cancellation.as_ref().map(|token| token.is_cancelled()) {
+            Some(true) => Err(CancelledError),
+            _ => Ok(()),
+        }
+    }
+    /// The token that cancels this sync, if any
+    pub(crate) fn cancellation(&self) -> Option<&CancellationToken> {
+        self.cancellation.as_ref()
     }
 
     /// Reset the user-info counter
@@ -85,6 +194,47 @@ impl SyncProgress {
         self.n_errors == 0
     }
 
+    /// What the sync has done so far
+    pub fn result(&self) -> &SyncResult {
+        &self.result
+    }
+    pub(crate) fn result_mut(&mut self) -> &mut SyncResult {
+        &mut self.result
+    }
+    /// Start syncing a calendar. Items are then reported as part of this calendar
+    pub fn calendar_started(&mut self, url: &Url, name: &str) {
+        self.result.calendar_started(url, name);
+        self.current_calendar = Some(url.clone());
+    }
+    /// Keep track of an item of the current calendar that has been synced
+    pub fn item_synced(&mut self, url: &Url, operation: ItemOperation, direction: SyncDirection) {
+        if let Some(cal_url) = &self.current_calendar {
+            self.result.item_synced(cal_url, url.clone(), operation, direction);
+        }
+    }
+    /// Keep track of an item of the current calendar that could not be synced
+    pub fn item_failed(&mut self, url: &Url, error: String) {
+        if let Some(cal_url) = &self.current_calendar {
+            self.result.item_failed(cal_url, url.clone(), error);
+        }
+    }
+    /// Keep track of a conflict that has been resolved
+    pub fn record_conflict(&mut self, conflict: ResolvedConflict) {
+        self.result.conflict_resolved(conflict);
+    }
+    /// Keep track of a conflicting item of the current calendar, whose fate a dry run cannot tell
+    pub fn record_undecided_conflict(&mut self, url: &Url) {
+        if let Some(cal_url) = &self.current_calendar {
+            self.result.conflict_undecided(cal_url, url.clone());
+        }
+    }
+    /// Keep track of the items of the current calendar that share the same UID
+    pub fn record_duplicates(&mut self, duplicates: Vec<Duplicates>) {
+        if let Some(cal_url) = &self.current_calendar {
+            self.result.duplicates_found(cal_url, duplicates);
+        }
+    }
+
     /// Log an error
     pub fn error(&mut self, text: &str) {
         log::error!("{}", text);
@@ -107,6 +257,24 @@ impl SyncProgress {
     pub fn trace(&mut self, text: &str) {
         log::trace!("{}", text);
     }
+    /// Enter a new phase, and tell the observer (if any)
+    pub fn phase(&mut self, phase: SyncPhase) {
+        #[cfg(feature = "tracing")]
+        tracing::info!(phase = ?phase, "sync phase");
+        if let Some(observer) = &self.observer {
+            observer.on_phase(&phase);
+        }
+        self.phase = Some(phase);
+    }
+    /// Mark some items of the current phase as done, and tell the observer (if any)
+    pub fn advance_phase(&mut self, increment: usize) {
+        if let Some(phase) = &mut self.phase {
+            phase.advance(increment);
+            if let Some(observer) = &self.observer {
+                observer.on_phase(phase);
+            }
+        }
+    }
     /// Send an event as a feedback to the listener (if any).
     pub fn feedback(&mut self, event: SyncEvent) {
         self.feedback_channel
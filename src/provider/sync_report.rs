@@ -0,0 +1,273 @@
+//! What a sync has brought from the server, in a form that can be shown to end users (see [`Provider::last_sync_report`](crate::provider::Provider::last_sync_report))
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Utc};
+use url::Url;
+
+use crate::item::{Item, SyncStatus, VersionTag};
+use crate::provider::conflict::ResolvedConflict;
+
+/// The type of an item
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ItemKind {
+    Event,
+    Task,
+    Journal,
+    Contact,
+}
+
+/// How an item has been changed on the server
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RemoteChangeKind {
+    Added,
+    Modified,
+    /// A task has been marked as completed
+    Completed,
+    Deleted,
+}
+
+/// An item that has been changed on the server, and that a sync has applied locally
+#[derive(Clone, Debug, PartialEq)]
+pub struct RemoteChange {
+    calendar_url: Url,
+    item_url: Url,
+    kind: RemoteChangeKind,
+    item_kind: ItemKind,
+    name: String,
+    start: Option<DateTime<Utc>>,
+    author: Option<String>,
+}
+
+impl RemoteChange {
+    pub fn calendar_url(&self) -> &Url { &self.calendar_url }
+    pub fn item_url(&self) -> &Url { &self.item_url }
+    pub fn kind(&self) -> RemoteChangeKind { self.kind }
+    pub fn item_kind(&self) -> ItemKind { self.item_kind }
+    /// The name of the item (the one it had before it was deleted, for deleted items)
+    pub fn name(&self) -> &str { &self.name }
+    /// The start date of events, or the due date of tasks
+    pub fn start(&self) -> Option<&DateTime<Utc>> { self.start.as_ref() }
+    /// Who is responsible for this item, as far as we can tell (i.e. the name or the address of the organizer of an event)
+    pub fn author(&self) -> Option<&str> { self.author.as_deref() }
+}
+
+/// The summary of a sync
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SyncReport {
+    success: bool,
+    changes: Vec<RemoteChange>,
+    conflicts: Vec<ResolvedConflict>,
+}
+
+impl SyncReport {
+    /// Compare the local items before and after a sync. Only the changes that come from the server are reported
+    pub(crate) fn new(success: bool, before: &Snapshot, after: &Snapshot, conflicts: Vec<ResolvedConflict>) -> Self {
+        let mut changes = Vec::new();
+        for (url, new) in &after.items {
+            if new.locally_changed {
+                continue;
+            }
+            let kind = match before.items.get(url) {
+                None => RemoteChangeKind::Added,
+                Some(old) if old.locally_changed => continue,
+                Some(old) if old.version_tag == new.version_tag => continue,
+                Some(old) if old.completed == false && new.completed => RemoteChangeKind::Completed,
+                Some(_) => RemoteChangeKind::Modified,
+            };
+            changes.push(new.to_change(url, kind));
+        }
+        for (url, old) in &before.items {
+            if old.locally_changed == false && after.items.contains_key(url) == false {
+                changes.push(old.to_change(url, RemoteChangeKind::Deleted));
+            }
+        }
+        changes.sort_by(|a, b| (&a.calendar_url, &a.item_url).cmp(&(&b.calendar_url, &b.item_url)));
+
+        Self { success, changes, conflicts }
+    }
+
+    /// Whether the sync was totally successful
+    pub fn is_success(&self) -> bool { self.success }
+
+    /// Every change that has been made on the server
+    pub fn changes(&self) -> &[RemoteChange] { &self.changes }
+
+    pub fn is_empty(&self) -> bool { self.changes.is_empty() }
+
+    /// The items that had been changed both locally and on the server, and which version the sync has kept (see [`Provider::set_conflict_resolution`](crate::provider::Provider::set_conflict_resolution))
+    pub fn conflicts(&self) -> &[ResolvedConflict] { &self.conflicts }
+
+    fn changes_of(&self, kind: RemoteChangeKind) -> impl Iterator<Item = &RemoteChange> {
+        self.changes.iter().filter(move |c| c.kind == kind)
+    }
+
+    /// The events that have been added on the server, and that start between `from` (included) and `until` (excluded), e.g. "this week"
+    pub fn added_events_between(&self, from: DateTime<Utc>, until: DateTime<Utc>) -> Vec<&RemoteChange> {
+        self.changes_of(RemoteChangeKind::Added)
+            .filter(|c| c.item_kind == ItemKind::Event)
+            .filter(|c| matches!(c.start, Some(start) if start >= from && start < until))
+            .collect()
+    }
+
+    /// The tasks that have been completed on the server
+    pub fn completed_tasks(&self) -> Vec<&RemoteChange> {
+        self.changes_of(RemoteChangeKind::Completed).collect()
+    }
+
+    /// The items that have been deleted on the server
+    pub fn deleted_items(&self) -> Vec<&RemoteChange> {
+        self.changes_of(RemoteChangeKind::Deleted).collect()
+    }
+
+    /// Short English sentences that summarize these changes, e.g. for a digest notification (`2 new events added by Alice`)
+    pub fn digest(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        let mut added_by: BTreeMap<Option<&str>, usize> = BTreeMap::new();
+        for change in self.changes_of(RemoteChangeKind::Added).filter(|c| c.item_kind == ItemKind::Event) {
+            *added_by.entry(change.author()).or_default() += 1;
+        }
+        for (author, count) in added_by {
+            let events = plural(count, "new event", "new events");
+            lines.push(match author {
+                Some(author) => format!("{} added by {}", events, author),
+                None => format!("{} added", events),
+            });
+        }
+
+        let other_additions = self.changes_of(RemoteChangeKind::Added).filter(|c| c.item_kind != ItemKind::Event).count();
+        if other_additions > 0 {
+            lines.push(format!("{} added", plural(other_additions, "new item", "new items")));
+        }
+        let completed = self.changes_of(RemoteChangeKind::Completed).count();
+        if completed > 0 {
+            lines.push(format!("{} completed", plural(completed, "task", "tasks")));
+        }
+        let modified = self.changes_of(RemoteChangeKind::Modified).count();
+        if modified > 0 {
+            lines.push(format!("{} modified", plural(modified, "item", "items")));
+        }
+        let deleted = self.changes_of(RemoteChangeKind::Deleted).count();
+        if deleted > 0 {
+            lines.push(format!("{} deleted", plural(deleted, "item", "items")));
+        }
+        lines
+    }
+}
+
+fn plural(count: usize, singular: &str, plural: &str) -> String {
+    match count {
+        1 => format!("1 {}", singular),
+        n => format!("{} {}", n, plural),
+    }
+}
+
+
+
+/// What is needed to tell what a sync has changed in the local items
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Snapshot {
+    items: HashMap<Url, ItemState>,
+}
+
+#[derive(Clone, Debug)]
+struct ItemState {
+    calendar_url: Url,
+    item_kind: ItemKind,
+    name: String,
+    start: Option<DateTime<Utc>>,
+    author: Option<String>,
+    completed: bool,
+    version_tag: Option<VersionTag>,
+    /// Whether the item has changes that have not been pushed to the server yet
+    locally_changed: bool,
+}
+
+impl ItemState {
+    fn to_change(&self, item_url: &Url, kind: RemoteChangeKind) -> RemoteChange {
+        RemoteChange {
+            calendar_url: self.calendar_url.clone(),
+            item_url: item_url.clone(),
+            kind,
+            item_kind: self.item_kind,
+            name: self.name.clone(),
+            start: self.start,
+            author: self.author.clone(),
+        }
+    }
+}
+
+impl Snapshot {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn add(&mut self, calendar_url: &Url, item: &Item) {
+        let (item_kind, start, author, completed) = match item {
+            Item::Event(e) => (
+                ItemKind::Event,
+                Some(*e.start()),
+                e.organizer().map(|o| o.common_name().unwrap_or_else(|| o.address().trim_start_matches("mailto:")).to_string()),
+                false,
+            ),
+            Item::Task(t) => (ItemKind::Task, t.due().cloned(), None, t.completed()),
+            Item::Journal(_) => (ItemKind::Journal, None, None, false),
+            Item::Contact(_) => (ItemKind::Contact, None, None, false),
+        };
+        let state = ItemState {
+            calendar_url: calendar_url.clone(),
+            item_kind,
+            name: item.name().to_string(),
+            start,
+            author,
+            completed,
+            version_tag: item.sync_status().version_tag().cloned(),
+            locally_changed: matches!(item.sync_status(), SyncStatus::Synced(_)) == false,
+        };
+        self.items.insert(item.url().clone(), state);
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Event, Task};
+
+    #[test]
+    fn test_sync_report() {
+        let cal_url: Url = "https://some.calend.ar/calendar/".parse().unwrap();
+        let start = Utc::now();
+        let mut meeting = Event::new("Meeting".to_string(), start, start + chrono::Duration::hours(1), &cal_url);
+        meeting.set_organizer(Some(crate::attendee::Organizer::new("mailto:alice@example.com".to_string(), Some("Alice".to_string()))));
+        meeting.set_sync_status(SyncStatus::random_synced());
+        let mut lunch = Event::new("Lunch".to_string(), start, start + chrono::Duration::hours(1), &cal_url);
+        lunch.set_organizer(meeting.organizer().cloned());
+        lunch.set_sync_status(SyncStatus::random_synced());
+        let mut task = Task::new("Pay the rent".to_string(), false, &cal_url);
+        task.set_sync_status(SyncStatus::random_synced());
+        let local_task = Task::new("Not pushed yet".to_string(), false, &cal_url);
+
+        let mut before = Snapshot::new();
+        before.add(&cal_url, &Item::Task(task.clone()));
+        before.add(&cal_url, &Item::Task(local_task.clone()));
+
+        let mut completed_task = task.clone();
+        completed_task.set_completion_status(crate::task::CompletionStatus::Completed(None));
+        completed_task.set_sync_status(SyncStatus::random_synced());
+        let mut after = Snapshot::new();
+        after.add(&cal_url, &Item::Event(meeting));
+        after.add(&cal_url, &Item::Event(lunch));
+        after.add(&cal_url, &Item::Task(completed_task));
+
+        let report = SyncReport::new(true, &before, &after, Vec::new());
+        assert_eq!(report.changes().len(), 3);
+        assert_eq!(report.added_events_between(start - chrono::Duration::days(1), start + chrono::Duration::days(7)).len(), 2);
+        assert_eq!(report.completed_tasks()[0].name(), "Pay the rent");
+        // Local changes are not reported
+        assert!(report.deleted_items().is_empty());
+        assert_eq!(report.digest(), vec!["2 new events added by Alice".to_string(), "1 task completed".to_string()]);
+    }
+}
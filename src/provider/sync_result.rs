@@ -0,0 +1,207 @@
+//! What a sync has done, calendar by calendar, including what has failed (see [`Provider::sync_with`](crate::provider::Provider::sync_with))
+
+use url::Url;
+
+use crate::provider::conflict::{ConflictOutcome, ResolvedConflict};
+use crate::provider::duplicates::Duplicates;
+use crate::provider::sync_report::SyncReport;
+
+/// Which way an item has been synced
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncDirection {
+    /// From the local source to the server
+    Pushed,
+    /// From the server to the local source
+    Pulled,
+}
+
+/// What has been done to an item
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ItemOperation {
+    Added,
+    Updated,
+    Deleted,
+}
+
+/// An item that has been synced
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyncedItem {
+    url: Url,
+    operation: ItemOperation,
+    direction: SyncDirection,
+}
+
+impl SyncedItem {
+    pub fn url(&self) -> &Url { &self.url }
+    pub fn operation(&self) -> ItemOperation { self.operation }
+    pub fn direction(&self) -> SyncDirection { self.direction }
+}
+
+/// An item that could not be synced. It will be retried at the next sync (unless the server has rejected it, see [`crate::error::Rejection`])
+#[derive(Clone, Debug, PartialEq)]
+pub struct FailedItem {
+    url: Url,
+    error: String,
+}
+
+impl FailedItem {
+    pub fn url(&self) -> &Url { &self.url }
+    pub fn error(&self) -> &str { &self.error }
+}
+
+/// What a sync has done to a calendar
+#[derive(Clone, Debug, PartialEq)]
+pub struct CalendarResult {
+    calendar_url: Url,
+    name: String,
+    synced: Vec<SyncedItem>,
+    conflicts: Vec<ResolvedConflict>,
+    undecided_conflicts: Vec<Url>,
+    failed: Vec<FailedItem>,
+    duplicates: Vec<Duplicates>,
+    /// Why the sync of this calendar has been aborted (if it has)
+    error: Option<String>,
+}
+
+impl CalendarResult {
+    fn new(calendar_url: Url, name: String) -> Self {
+        Self { calendar_url, name, synced: Vec::new(), conflicts: Vec::new(), undecided_conflicts: Vec::new(), failed: Vec::new(), duplicates: Vec::new(), error: None }
+    }
+
+    pub fn calendar_url(&self) -> &Url { &self.calendar_url }
+    pub fn name(&self) -> &str { &self.name }
+    /// Every item that has been synced
+    pub fn synced(&self) -> &[SyncedItem] { &self.synced }
+    /// The items that had been changed on both ends, and which version has been kept
+    pub fn conflicts(&self) -> &[ResolvedConflict] { &self.conflicts }
+    /// For dry runs with a [`ConflictResolution::Custom`](crate::provider::conflict::ConflictResolution::Custom) policy: the conflicting items, whose fate is only decided by the actual sync
+    pub fn undecided_conflicts(&self) -> &[Url] { &self.undecided_conflicts }
+    /// The items that could not be synced
+    pub fn failed(&self) -> &[FailedItem] { &self.failed }
+    /// The items that share the same UID once this calendar has been synced (see [`Provider::merge_duplicates`](crate::provider::Provider::merge_duplicates))
+    pub fn duplicates(&self) -> &[Duplicates] { &self.duplicates }
+    /// Why the sync of this calendar has been aborted, in case it has
+    pub fn error(&self) -> Option<&str> { self.error.as_deref() }
+
+    /// How many items have been added, updated or deleted (on either end)
+    pub fn count(&self, operation: ItemOperation) -> usize {
+        self.synced.iter().filter(|item| item.operation == operation).count()
+    }
+    pub fn added(&self) -> usize { self.count(ItemOperation::Added) }
+    pub fn updated(&self) -> usize { self.count(ItemOperation::Updated) }
+    pub fn deleted(&self) -> usize { self.count(ItemOperation::Deleted) }
+
+    /// Whether this calendar has been totally synced
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty() && self.error.is_none()
+    }
+}
+
+/// What a sync has done, calendar by calendar, and what it has brought from the server.
+///
+/// For dry runs (see [`SyncOptions::dry_run`](crate::provider::SyncOptions::dry_run)), this is what the sync would do: the items it would sync, and how it would resolve conflicts
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SyncResult {
+    success: bool,
+    dry_run: bool,
+    calendars: Vec<CalendarResult>,
+    /// What the sync has brought from the server
+    report: SyncReport,
+    /// Why the whole sync has been aborted (if it has)
+    error: Option<String>,
+}
+
+impl SyncResult {
+    /// Whether the sync was totally successful
+    pub fn is_success(&self) -> bool { self.success }
+    /// Whether this only tells what a sync would do
+    pub fn is_dry_run(&self) -> bool { self.dry_run }
+    pub fn calendars(&self) -> &[CalendarResult] { &self.calendars }
+    pub fn calendar(&self, url: &Url) -> Option<&CalendarResult> {
+        self.calendars.iter().find(|cal| &cal.calendar_url == url)
+    }
+    /// Why the whole sync has been aborted, in case it has
+    pub fn error(&self) -> Option<&str> { self.error.as_deref() }
+
+    /// Every item that could not be synced, in every calendar
+    pub fn failed_items(&self) -> impl Iterator<Item = &FailedItem> {
+        self.calendars.iter().flat_map(|cal| cal.failed.iter())
+    }
+
+    /// Whether nothing has been (or would be) synced
+    pub fn is_empty(&self) -> bool {
+        self.calendars.iter().all(|cal| cal.synced.is_empty() && cal.conflicts.is_empty() && cal.undecided_conflicts.is_empty())
+    }
+
+    /// Whether the sync deletes items (on either end), or loses some changes because of conflicts. This is useful to ask for a confirmation after a dry run
+    pub fn is_destructive(&self) -> bool {
+        self.calendars.iter().any(|cal| {
+            cal.synced.iter().any(|item| item.operation == ItemOperation::Deleted)
+                || cal.conflicts.iter().any(|conflict| conflict.outcome() != ConflictOutcome::KeptBoth)
+                || cal.undecided_conflicts.is_empty() == false
+        })
+    }
+
+    /// The items that had been changed both locally and on the server, and which version the sync has kept (see [`Provider::set_conflict_resolution`](crate::provider::Provider::set_conflict_resolution))
+    pub fn conflicts(&self) -> impl Iterator<Item = &ResolvedConflict> {
+        self.calendars.iter().flat_map(|cal| cal.conflicts.iter())
+    }
+
+    /// What the sync has brought from the server, in a form that can be shown to end users. This is empty for dry runs
+    pub fn report(&self) -> &SyncReport { &self.report }
+
+    pub(crate) fn set_success(&mut self, success: bool) {
+        self.success = success;
+    }
+
+    pub(crate) fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+    }
+
+    pub(crate) fn set_report(&mut self, report: SyncReport) {
+        self.report = report;
+    }
+
+    pub(crate) fn set_error(&mut self, error: String) {
+        self.error = Some(error);
+    }
+
+    /// The result of a calendar, which is created if needed
+    fn calendar_mut(&mut self, url: &Url, name: &str) -> &mut CalendarResult {
+        match self.calendars.iter().position(|cal| &cal.calendar_url == url) {
+            Some(index) => &mut self.calendars[index],
+            None => {
+                self.calendars.push(CalendarResult::new(url.clone(), name.to_string()));
+                self.calendars.last_mut().unwrap(/* just pushed */)
+            },
+        }
+    }
+
+    pub(crate) fn calendar_started(&mut self, url: &Url, name: &str) {
+        self.calendar_mut(url, name);
+    }
+
+    pub(crate) fn calendar_failed(&mut self, url: &Url, error: String) {
+        self.calendar_mut(url, "").error = Some(error);
+    }
+
+    pub(crate) fn item_synced(&mut self, calendar_url: &Url, url: Url, operation: ItemOperation, direction: SyncDirection) {
+        self.calendar_mut(calendar_url, "").synced.push(SyncedItem { url, operation, direction });
+    }
+
+    pub(crate) fn item_failed(&mut self, calendar_url: &Url, url: Url, error: String) {
+        self.calendar_mut(calendar_url, "").failed.push(FailedItem { url, error });
+    }
+
+    pub(crate) fn conflict_resolved(&mut self, conflict: ResolvedConflict) {
+        self.calendar_mut(conflict.calendar_url(), "").conflicts.push(conflict);
+    }
+
+    pub(crate) fn conflict_undecided(&mut self, calendar_url: &Url, url: Url) {
+        self.calendar_mut(calendar_url, "").undecided_conflicts.push(url);
+    }
+
+    pub(crate) fn duplicates_found(&mut self, calendar_url: &Url, duplicates: Vec<Duplicates>) {
+        self.calendar_mut(calendar_url, "").duplicates = duplicates;
+    }
+}
@@ -0,0 +1,109 @@
+//! Which proxy requests to CalDAV servers go through (see [`Client::set_proxy`](crate::client::Client::set_proxy))
+
+use std::error::Error;
+use std::fmt::{Debug, Formatter};
+
+use reqwest::ClientBuilder;
+use url::Url;
+
+/// The proxy of a [`Client`](crate::client::Client).
+///
+/// `socks5://` and `socks5h://` proxies (e.g. Tor) are only supported with the `socks` feature
+#[derive(Clone, PartialEq)]
+pub enum ProxyConfig {
+    /// Use the proxies of the `HTTP_PROXY` and `HTTPS_PROXY` environment variables (or their lowercase versions), if they are set. This is the default
+    System,
+    /// Connect directly to servers, even if proxy environment variables are set
+    Direct,
+    /// Send every request through a proxy (e.g. `http://proxy.example.com:3128` or `socks5h://127.0.0.1:9050`)
+    Url {
+        url: Url,
+        /// The username and password, for proxies that require authentication
+        credentials: Option<(String, String)>,
+    },
+}
+
+impl Default for ProxyConfig {
+    fn default() -> Self { ProxyConfig::System }
+}
+
+impl ProxyConfig {
+    /// A proxy that every request is sent through. This fails if its scheme is not supported
+    pub fn url(url: &str) -> Result<Self, Box<dyn Error>> {
+        let url = Url::parse(url)?;
+        match url.scheme() {
+            "http" | "https" => (),
+            "socks5" | "socks5h" => {
+                if cfg!(feature = "socks") == false {
+                    return Err(format!("SOCKS proxies (such as {}) are only supported with the `socks` feature", url).into());
+                }
+            },
+            scheme => return Err(format!("Unsupported proxy scheme {}", scheme).into()),
+        }
+        Ok(ProxyConfig::Url { url, credentials: None })
+    }
+
+    /// Authenticate to the proxy (this has no effect unless this is a [`ProxyConfig::Url`])
+    pub fn with_credentials<S: ToString, T: ToString>(self, username: S, password: T) -> Self {
+        match self {
+            ProxyConfig::Url { url, .. } => ProxyConfig::Url { url, credentials: Some((username.to_string(), password.to_string())) },
+            other => other,
+        }
+    }
+
+    /// Apply this setting to an HTTP client that is being built
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn apply(&self, builder: ClientBuilder) -> Result<ClientBuilder, Box<dyn Error>> {
+        match self {
+            ProxyConfig::System => Ok(builder),
+            ProxyConfig::Direct => Ok(builder.no_proxy()),
+            ProxyConfig::Url { url, credentials } => {
+                let mut proxy = reqwest::Proxy::all(url.clone())?;
+                if let Some((username, password)) = credentials {
+                    proxy = proxy.basic_auth(username, password);
+                }
+                Ok(builder.proxy(proxy))
+            },
+        }
+    }
+
+    /// Web browsers use their own proxy settings, that cannot be overridden
+    #[cfg(target_arch = "wasm32")]
+    pub(crate) fn apply(&self, builder: ClientBuilder) -> Result<ClientBuilder, Box<dyn Error>> {
+        match self {
+            ProxyConfig::System => Ok(builder),
+            _ => Err("Proxies cannot be set in web browsers".into()),
+        }
+    }
+}
+
+impl Debug for ProxyConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProxyConfig::System => write!(f, "System"),
+            ProxyConfig::Direct => write!(f, "Direct"),
+            ProxyConfig::Url { url, credentials } => f.debug_struct("Url")
+                .field("url", &url.as_str())
+                .field("credentials", &credentials.as_ref().map(|(username, _)| (username, "<hidden>")))
+                .finish(),
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proxy_config() {
+        let proxy = ProxyConfig::url("http://proxy.example.com:3128").unwrap().with_credentials("john", "secret");
+        assert!(format!("{:?}", proxy).contains("secret") == false);
+        assert!(proxy.apply(reqwest::Client::builder()).is_ok());
+
+        assert!(ProxyConfig::url("ftp://proxy.example.com").is_err());
+        assert_eq!(ProxyConfig::url("socks5h://127.0.0.1:9050").is_ok(), cfg!(feature = "socks"));
+        assert_eq!(ProxyConfig::Direct.with_credentials("john", "secret"), ProxyConfig::Direct);
+    }
+}
@@ -0,0 +1,78 @@
+//! Storage quotas of WebDAV collections (see [RFC 4331](https://datatracker.ietf.org/doc/html/rfc4331) and [`Client::quota`](crate::client::Client::quota))
+
+use minidom::Element;
+
+use crate::utils::find_elem;
+
+/// How much storage space is used and available in a collection (e.g. the calendar home set of the user), as advertised by the server.
+///
+/// Servers are free not to report any of them
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Quota {
+    used_bytes: Option<u64>,
+    available_bytes: Option<u64>,
+}
+
+impl Quota {
+    pub fn new(used_bytes: Option<u64>, available_bytes: Option<u64>) -> Self {
+        Self { used_bytes, available_bytes }
+    }
+
+    /// Read the `quota-used-bytes` and `quota-available-bytes` properties of a PROPFIND `response` (or of any element that contains them)
+    pub(crate) fn from_response(response: &Element) -> Self {
+        let bytes = |name: &str| find_elem(response, name).and_then(|el| el.text().trim().parse::<u64>().ok());
+        Self::new(bytes("quota-used-bytes"), bytes("quota-available-bytes"))
+    }
+
+    /// The space that is used, in bytes
+    pub fn used_bytes(&self) -> Option<u64> { self.used_bytes }
+    /// The space that is still available, in bytes
+    pub fn available_bytes(&self) -> Option<u64> { self.available_bytes }
+
+    /// The total space, in bytes (if the server has reported both the used and the available space)
+    pub fn total_bytes(&self) -> Option<u64> {
+        Some(self.used_bytes?.saturating_add(self.available_bytes?))
+    }
+
+    /// Whether the server has reported anything
+    pub fn is_known(&self) -> bool {
+        self.used_bytes.is_some() || self.available_bytes.is_some()
+    }
+
+    /// Whether uploading `size` more bytes would exceed the quota (this is `false` when the available space is unknown)
+    pub fn would_exceed(&self, size: u64) -> bool {
+        self.available_bytes.map(|available| size > available).unwrap_or(false)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quota_parsing() {
+        let xml = r#"<d:response xmlns:d="DAV:">
+            <d:href>/calendars/john/</d:href>
+            <d:propstat>
+                <d:prop>
+                    <d:quota-used-bytes>1500</d:quota-used-bytes>
+                    <d:quota-available-bytes> 500 </d:quota-available-bytes>
+                </d:prop>
+                <d:status>HTTP/1.1 200 OK</d:status>
+            </d:propstat>
+        </d:response>"#;
+        let quota = Quota::from_response(&xml.parse().unwrap());
+        assert_eq!(quota.used_bytes(), Some(1500));
+        assert_eq!(quota.available_bytes(), Some(500));
+        assert_eq!(quota.total_bytes(), Some(2000));
+        assert!(quota.would_exceed(501));
+        assert_eq!(quota.would_exceed(500), false);
+
+        let xml = r#"<d:response xmlns:d="DAV:"><d:href>/calendars/john/</d:href></d:response>"#;
+        let quota = Quota::from_response(&xml.parse().unwrap());
+        assert_eq!(quota.is_known(), false);
+        assert_eq!(quota.would_exceed(u64::MAX), false);
+    }
+}
@@ -0,0 +1,114 @@
+//! Client-side pacing of HTTP requests, for servers that throttle aggressive clients (see [`Client::set_rate_limit`](crate::client::Client::set_rate_limit))
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::runtime::{sleep, Instant};
+
+/// The longest a request waits for a token, whatever the rate limit is
+const MAX_DELAY: Duration = Duration::from_secs(3600);
+
+/// How many requests may be sent to a server
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    requests_per_second: f64,
+    burst: u32,
+}
+
+impl RateLimit {
+    /// At most `requests_per_second` requests per second on average, but up to `burst` requests can be sent at once after a quiet period. \
+    /// This fails if `requests_per_second` is not a positive finite number
+    pub fn new(requests_per_second: f64, burst: u32) -> Result<Self, crate::Error> {
+        if requests_per_second.is_finite() == false || requests_per_second <= 0.0 {
+            return Err(crate::Error::InvalidOperation(format!("Invalid rate limit: {} requests per second", requests_per_second)));
+        }
+        Ok(Self {
+            requests_per_second,
+            burst: burst.max(1),
+        })
+    }
+
+    pub fn requests_per_second(&self) -> f64 { self.requests_per_second }
+    pub fn burst(&self) -> u32 { self.burst }
+}
+
+/// A token bucket that is shared by every request of a client
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    limit: RateLimit,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        let bucket = Bucket { tokens: limit.burst as f64, last_refill: Instant::now() };
+        Self { limit, bucket: Mutex::new(bucket) }
+    }
+
+    pub(crate) fn limit(&self) -> RateLimit { self.limit }
+
+    /// Take a token if one is available, or tell how long to wait until there is one
+    fn try_acquire(&self, now: Instant) -> Result<(), Duration> {
+        let mut bucket = self.bucket.lock().unwrap();
+        let elapsed = now.saturating_duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.limit.requests_per_second).min(self.limit.burst as f64);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            let delay = Duration::try_from_secs_f64((1.0 - bucket.tokens) / self.limit.requests_per_second).unwrap_or(MAX_DELAY);
+            Err(delay.min(MAX_DELAY))
+        }
+    }
+
+    /// Wait until a request can be sent
+    pub(crate) async fn acquire(&self) {
+        while let Err(delay) = self.try_acquire(Instant::now()) {
+            sleep(delay).await;
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let limiter = RateLimiter::new(RateLimit::new(2.0, 3).unwrap());
+        let start = Instant::now();
+        for _ in 0..3 {
+            assert_eq!(limiter.try_acquire(start), Ok(()));
+        }
+        assert_eq!(limiter.try_acquire(start), Err(Duration::from_millis(500)));
+        assert_eq!(limiter.try_acquire(start + Duration::from_millis(500)), Ok(()));
+        // Tokens do not pile up beyond the burst size
+        for _ in 0..3 {
+            assert_eq!(limiter.try_acquire(start + Duration::from_secs(60)), Ok(()));
+        }
+        assert!(limiter.try_acquire(start + Duration::from_secs(60)).is_err());
+    }
+
+    #[test]
+    fn test_invalid_rate_limits() {
+        for rps in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(RateLimit::new(rps, 1).is_err());
+        }
+
+        // Very slow rates do not make requests wait forever
+        let limiter = RateLimiter::new(RateLimit::new(f64::MIN_POSITIVE, 1).unwrap());
+        let start = Instant::now();
+        assert_eq!(limiter.try_acquire(start), Ok(()));
+        assert_eq!(limiter.try_acquire(start), Err(MAX_DELAY));
+    }
+}
@@ -0,0 +1,930 @@
+//! Recurrence rules (iCal `RRULE` values, see [RFC 5545](https://datatracker.ietf.org/doc/html/rfc5545#section-3.3.10))
+
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use chrono::{Datelike, DateTime, NaiveDate, NaiveDateTime, Offset, TimeZone, Timelike, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+/// How often a recurrence rule repeats
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Frequency {
+    Secondly,
+    Minutely,
+    Hourly,
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl Frequency {
+    fn as_ical_str(&self) -> &'static str {
+        match self {
+            Frequency::Secondly => "SECONDLY",
+            Frequency::Minutely => "MINUTELY",
+            Frequency::Hourly => "HOURLY",
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+            Frequency::Yearly => "YEARLY",
+        }
+    }
+
+    /// The singular name of the time unit
+    fn unit(&self) -> &'static str {
+        match self {
+            Frequency::Secondly => "second",
+            Frequency::Minutely => "minute",
+            Frequency::Hourly => "hour",
+            Frequency::Daily => "day",
+            Frequency::Weekly => "week",
+            Frequency::Monthly => "month",
+            Frequency::Yearly => "year",
+        }
+    }
+
+    /// The longest a period can last, in seconds (DST changes included)
+    fn max_period_seconds(&self) -> i64 {
+        match self {
+            Frequency::Secondly => 1,
+            Frequency::Minutely => 60,
+            Frequency::Hourly => 3600,
+            Frequency::Daily => 25 * 3600,
+            Frequency::Weekly => 7 * 24 * 3600 + 3600,
+            Frequency::Monthly => 31 * 24 * 3600 + 3600,
+            Frequency::Yearly => 366 * 24 * 3600 + 3600,
+        }
+    }
+}
+
+impl FromStr for Frequency {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "SECONDLY" => Ok(Frequency::Secondly),
+            "MINUTELY" => Ok(Frequency::Minutely),
+            "HOURLY" => Ok(Frequency::Hourly),
+            "DAILY" => Ok(Frequency::Daily),
+            "WEEKLY" => Ok(Frequency::Weekly),
+            "MONTHLY" => Ok(Frequency::Monthly),
+            "YEARLY" => Ok(Frequency::Yearly),
+            other => Err(format!("Invalid recurrence frequency {}", other).into()),
+        }
+    }
+}
+
+/// When a recurrence rule stops
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RecurrenceEnd {
+    /// The rule repeats forever
+    Never,
+    /// The rule repeats a given number of times
+    Count(u32),
+    /// The rule repeats until a given (inclusive) date-time
+    Until(DateTime<Utc>),
+    /// The rule repeats until a given (inclusive) date, for all-day items
+    UntilDate(NaiveDate),
+}
+
+/// A day of the week, optionally with its position in the month or year (e.g. `2MO` for "the second Monday", `-1FR` for "the last Friday")
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WeekdayNum {
+    pub ordinal: Option<i8>,
+    pub weekday: Weekday,
+}
+
+impl FromStr for WeekdayNum {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() < 2 || s.is_char_boundary(s.len() - 2) == false {
+            return Err(format!("Invalid weekday {}", s).into());
+        }
+        let (ordinal, day) = s.split_at(s.len() - 2);
+        let weekday = parse_weekday(day)?;
+        let ordinal = match ordinal {
+            "" => None,
+            n => Some(n.parse()?),
+        };
+        Ok(Self { ordinal, weekday })
+    }
+}
+
+impl Display for WeekdayNum {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(n) = self.ordinal {
+            write!(f, "{}", n)?;
+        }
+        write!(f, "{}", weekday_to_ical(self.weekday))
+    }
+}
+
+fn parse_weekday(s: &str) -> Result<Weekday, Box<dyn Error>> {
+    match s {
+        "MO" => Ok(Weekday::Mon),
+        "TU" => Ok(Weekday::Tue),
+        "WE" => Ok(Weekday::Wed),
+        "TH" => Ok(Weekday::Thu),
+        "FR" => Ok(Weekday::Fri),
+        "SA" => Ok(Weekday::Sat),
+        "SU" => Ok(Weekday::Sun),
+        other => Err(format!("Invalid weekday {}", other).into()),
+    }
+}
+
+fn weekday_to_ical(day: Weekday) -> &'static str {
+    match day {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+
+
+/// A recurrence rule, as described by an iCal `RRULE` property
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Recurrence {
+    frequency: Frequency,
+    interval: u32,
+    end: RecurrenceEnd,
+
+    by_second: Vec<u8>,
+    by_minute: Vec<u8>,
+    by_hour: Vec<u8>,
+    by_day: Vec<WeekdayNum>,
+    by_month_day: Vec<i8>,
+    by_year_day: Vec<i16>,
+    by_week_no: Vec<i8>,
+    by_month: Vec<u8>,
+    by_set_pos: Vec<i16>,
+    week_start: Option<Weekday>,
+
+    /// Rule parts that are not supported by this crate (e.g. `X-` extensions). They are kept so that the rule can be serialized back into an equivalent one
+    extra_parts: Vec<(String, String)>,
+}
+
+impl Recurrence {
+    /// Create a rule that repeats forever, every `interval` periods
+    pub fn new(frequency: Frequency, interval: u32) -> Self {
+        Self {
+            frequency,
+            interval: interval.max(1),
+            end: RecurrenceEnd::Never,
+            by_second: Vec::new(),
+            by_minute: Vec::new(),
+            by_hour: Vec::new(),
+            by_day: Vec::new(),
+            by_month_day: Vec::new(),
+            by_year_day: Vec::new(),
+            by_week_no: Vec::new(),
+            by_month: Vec::new(),
+            by_set_pos: Vec::new(),
+            week_start: None,
+            extra_parts: Vec::new(),
+        }
+    }
+
+    pub fn frequency(&self) -> Frequency { self.frequency }
+    pub fn interval(&self) -> u32 { self.interval }
+    pub fn end(&self) -> &RecurrenceEnd { &self.end }
+    pub fn by_second(&self) -> &[u8] { &self.by_second }
+    pub fn by_minute(&self) -> &[u8] { &self.by_minute }
+    pub fn by_hour(&self) -> &[u8] { &self.by_hour }
+    pub fn by_day(&self) -> &[WeekdayNum] { &self.by_day }
+    pub fn by_month_day(&self) -> &[i8] { &self.by_month_day }
+    pub fn by_year_day(&self) -> &[i16] { &self.by_year_day }
+    pub fn by_week_no(&self) -> &[i8] { &self.by_week_no }
+    pub fn by_month(&self) -> &[u8] { &self.by_month }
+    pub fn by_set_pos(&self) -> &[i16] { &self.by_set_pos }
+    pub fn week_start(&self) -> Option<Weekday> { self.week_start }
+
+    pub fn set_end(&mut self, end: RecurrenceEnd) {
+        self.end = end;
+    }
+    pub fn set_by_day(&mut self, by_day: Vec<WeekdayNum>) {
+        self.by_day = by_day;
+    }
+    pub fn set_by_month_day(&mut self, by_month_day: Vec<i8>) {
+        self.by_month_day = by_month_day;
+    }
+    pub fn set_by_month(&mut self, by_month: Vec<u8>) {
+        self.by_month = by_month;
+    }
+
+    /// Iterate over the start dates of the instances of this rule, in chronological order. \
+    /// `dtstart` is the start of the recurring item, it always is the first instance.
+    ///
+    /// Note that instances are computed in UTC, which means that instances that cross a DST change will be off by the DST offset
+    /// (see [`Self::instances_in`] to avoid this).
+    /// This may never end, in case the rule repeats forever.
+    pub fn instances(&self, dtstart: DateTime<Utc>) -> Instances<'_> {
+        self.instances_with(dtstart, None)
+    }
+
+    /// Iterate over the start dates of the instances of this rule, computed in the local time of a timezone (so that e.g. a daily rule always
+    /// happens at the same local time, even across DST changes). See [`Self::instances`]
+    pub fn instances_in(&self, dtstart: DateTime<Utc>, timezone: &Tz) -> Instances<'_> {
+        self.instances_with(dtstart, Some(*timezone))
+    }
+
+    fn instances_with(&self, dtstart: DateTime<Utc>, timezone: Option<Tz>) -> Instances<'_> {
+        let mut buffer = VecDeque::new();
+        buffer.push_back(dtstart);
+        Instances {
+            rule: self,
+            dtstart,
+            timezone,
+            period: 0,
+            buffer,
+            emitted: 0,
+            empty_periods: 0,
+            done: false,
+        }
+    }
+
+    /// Whether a given date is after the end of this rule (UNTIL)
+    fn is_after_until(&self, dt: &DateTime<Utc>) -> bool {
+        match &self.end {
+            RecurrenceEnd::Until(until) => dt > until,
+            RecurrenceEnd::UntilDate(until) => dt.naive_utc().date() > *until,
+            _ => false,
+        }
+    }
+
+    /// The instances that happen during the `period`-th period after `dtstart`, sorted.
+    /// They are computed in the local time of `timezone` (or in UTC if there is none).
+    /// This returns `None` in case the period is out of the representable range of dates
+    fn period_instances(&self, dtstart: &DateTime<Utc>, timezone: Option<&Tz>, period: u32) -> Option<Vec<DateTime<Utc>>> {
+        let start = match timezone {
+            Some(tz) => dtstart.with_timezone(tz).naive_local(),
+            None => dtstart.naive_utc(),
+        };
+        let steps = i32::try_from(period.checked_mul(self.interval)?).ok()?;
+
+        let mut instances = match self.frequency {
+            Frequency::Secondly | Frequency::Minutely | Frequency::Hourly => {
+                let unit_seconds = match self.frequency {
+                    Frequency::Secondly => 1,
+                    Frequency::Minutely => 60,
+                    _ => 3600,
+                };
+                let instant = start.checked_add_signed(chrono::Duration::seconds(unit_seconds.checked_mul(i64::from(steps))?))?;
+                let matches = self.matches_day(&instant.date(), None)
+                    && (self.by_hour.is_empty() || self.by_hour.contains(&(instant.hour() as u8)))
+                    && (self.by_minute.is_empty() || self.by_minute.contains(&(instant.minute() as u8)))
+                    && (self.by_second.is_empty() || self.by_second.contains(&(instant.second() as u8)));
+                match matches {
+                    true => vec![instant],
+                    false => Vec::new(),
+                }
+            },
+            _ => {
+                let (first_day, n_days) = match self.frequency {
+                    Frequency::Daily => (start.date().checked_add_signed(chrono::Duration::days(i64::from(steps)))?, 1),
+                    Frequency::Weekly => {
+                        let week_start = self.week_start.unwrap_or(Weekday::Mon);
+                        let offset = (7 + start.weekday().num_days_from_monday() - week_start.num_days_from_monday()) % 7;
+                        let first = start.date()
+                            .checked_sub_signed(chrono::Duration::days(offset as i64))?
+                            .checked_add_signed(chrono::Duration::weeks(i64::from(steps)))?;
+                        (first, 7)
+                    },
+                    Frequency::Monthly => {
+                        let months = start.year() as i64 * 12 + start.month0() as i64 + i64::from(steps);
+                        let first = NaiveDate::from_ymd_opt(months.div_euclid(12) as i32, months.rem_euclid(12) as u32 + 1, 1)?;
+                        (first, days_in_month(&first))
+                    },
+                    _ => {
+                        let first = NaiveDate::from_ymd_opt(start.year().checked_add(steps)?, 1, 1)?;
+                        (first, days_in_year(&first))
+                    },
+                };
+
+                let hours = default_if_empty(&self.by_hour, start.hour() as u8);
+                let minutes = default_if_empty(&self.by_minute, start.minute() as u8);
+                let seconds = default_if_empty(&self.by_second, start.second() as u8);
+
+                let mut instances = Vec::new();
+                let mut day = first_day;
+                for _ in 0..n_days {
+                    if self.matches_day(&day, Some(&start.date())) {
+                        for h in &hours {
+                            for m in &minutes {
+                                for s in &seconds {
+                                    if let Some(t) = day.and_hms_opt(*h as u32, *m as u32, *s as u32) {
+                                        instances.push(t);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    day = day.succ_opt()?;
+                }
+                instances
+            },
+        };
+
+        instances.sort();
+        if self.by_set_pos.is_empty() == false {
+            let n = instances.len() as i64;
+            let mut selected: Vec<_> = self.by_set_pos.iter()
+                .filter_map(|pos| {
+                    let index = match *pos {
+                        p if p > 0 => p as i64 - 1,
+                        p => n + p as i64,
+                    };
+                    match index >= 0 && index < n {
+                        true => Some(instances[index as usize]),
+                        false => None,
+                    }
+                })
+                .collect();
+            selected.sort();
+            selected.dedup();
+            instances = selected;
+        }
+
+        Some(instances.into_iter().filter_map(|dt| to_utc(&dt, timezone)).collect())
+    }
+
+    /// Whether a day is allowed by the BYxxx rules. \
+    /// In case `implicit_from` is given, missing rules are derived from this start date (e.g. a `FREQ=MONTHLY` rule without `BYMONTHDAY` nor `BYDAY` repeats on the day of month of its start date)
+    fn matches_day(&self, day: &NaiveDate, implicit_from: Option<&NaiveDate>) -> bool {
+        if let Some(start) = implicit_from {
+            let no_day_rules = self.by_month_day.is_empty() && self.by_day.is_empty() && self.by_year_day.is_empty();
+            let implicitly_matches = match self.frequency {
+                Frequency::Yearly if no_day_rules && self.by_week_no.is_empty() == false => day.weekday() == start.weekday(),
+                Frequency::Yearly if no_day_rules => {
+                    (self.by_month.is_empty() == false || day.month() == start.month()) && day.day() == start.day()
+                },
+                Frequency::Monthly if no_day_rules => day.day() == start.day(),
+                Frequency::Weekly if self.by_day.is_empty() => day.weekday() == start.weekday(),
+                _ => true,
+            };
+            if implicitly_matches == false {
+                return false;
+            }
+        }
+
+        if self.by_month.is_empty() == false && self.by_month.contains(&(day.month() as u8)) == false {
+            return false;
+        }
+        if self.by_week_no.is_empty() == false {
+            let week = day.iso_week();
+            let weeks_in_year = NaiveDate::from_isoywd_opt(week.year(), 53, Weekday::Mon).map(|_| 53).unwrap_or(52);
+            let matches = week.year() == day.year() && self.by_week_no.iter().any(|n| {
+                *n as i32 == week.week() as i32 || *n as i32 == week.week() as i32 - weeks_in_year - 1
+            });
+            if matches == false {
+                return false;
+            }
+        }
+        if self.by_year_day.is_empty() == false {
+            let ordinal = day.ordinal() as i32;
+            let n_days = days_in_year(day) as i32;
+            if self.by_year_day.iter().any(|n| *n as i32 == ordinal || *n as i32 == ordinal - n_days - 1) == false {
+                return false;
+            }
+        }
+        if self.by_month_day.is_empty() == false {
+            let d = day.day() as i32;
+            let n_days = days_in_month(day) as i32;
+            if self.by_month_day.iter().any(|n| *n as i32 == d || *n as i32 == d - n_days - 1) == false {
+                return false;
+            }
+        }
+        if self.by_day.is_empty() == false {
+            // Ordinals are relative to the month in monthly rules (or yearly rules that are restricted to some months), and relative to the year in yearly rules
+            let (position, position_from_end) = match (self.frequency, self.by_month.is_empty()) {
+                (Frequency::Yearly, true) => {
+                    let ordinal0 = day.ordinal0() as i32;
+                    (ordinal0 / 7 + 1, -((days_in_year(day) as i32 - ordinal0 - 1) / 7 + 1))
+                },
+                _ => {
+                    let day0 = day.day0() as i32;
+                    (day0 / 7 + 1, -((days_in_month(day) as i32 - day0 - 1) / 7 + 1))
+                },
+            };
+            let ordinals_apply = matches!(self.frequency, Frequency::Monthly | Frequency::Yearly);
+            let matches = self.by_day.iter().any(|wd| {
+                wd.weekday == day.weekday() && match (wd.ordinal, ordinals_apply) {
+                    (Some(n), true) => n as i32 == position || n as i32 == position_from_end,
+                    _ => true,
+                }
+            });
+            if matches == false {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Describe this rule in plain English, e.g. "Every 2 weeks on Mon, Wed until Dec 31, 2021"
+    pub fn to_human_string(&self, hints: &HumanizationHints) -> String {
+        let mut text = match self.interval {
+            1 => match self.frequency {
+                Frequency::Daily => String::from("Daily"),
+                Frequency::Weekly => String::from("Weekly"),
+                Frequency::Monthly => String::from("Monthly"),
+                Frequency::Yearly => String::from("Yearly"),
+                other => format!("Every {}", other.unit()),
+            },
+            n => format!("Every {} {}s", n, self.frequency.unit()),
+        };
+
+        if self.by_month.is_empty() == false {
+            let months: Vec<String> = self.by_month.iter().map(|m| month_name(*m, hints)).collect();
+            text.push_str(&format!(" in {}", join_list(&months)));
+        }
+
+        if self.by_day.is_empty() == false {
+            let days: Vec<String> = self.by_day.iter()
+                .map(|d| match d.ordinal {
+                    None => weekday_name(d.weekday, hints),
+                    Some(n) => format!("the {} {}", ordinal_name(n as i32), weekday_name(d.weekday, hints)),
+                })
+                .collect();
+            text.push_str(&format!(" on {}", join_list(&days)));
+        }
+
+        if self.by_month_day.is_empty() == false {
+            let days: Vec<String> = self.by_month_day.iter().map(|d| ordinal_name(*d as i32)).collect();
+            text.push_str(&format!(" on the {}", join_list(&days)));
+        }
+
+        match &self.end {
+            RecurrenceEnd::Never => (),
+            RecurrenceEnd::Count(1) => text.push_str(", once"),
+            RecurrenceEnd::Count(n) => text.push_str(&format!(", {} times", n)),
+            RecurrenceEnd::Until(dt) => text.push_str(&format!(" until {}", dt.format(&hints.date_format))),
+            RecurrenceEnd::UntilDate(d) => text.push_str(&format!(" until {}", d.format(&hints.date_format))),
+        }
+
+        text
+    }
+}
+
+/// An iterator over the start dates of the instances of a recurrence rule. See [`Recurrence::instances`]
+pub struct Instances<'a> {
+    rule: &'a Recurrence,
+    dtstart: DateTime<Utc>,
+    timezone: Option<Tz>,
+    period: u32,
+    buffer: VecDeque<DateTime<Utc>>,
+    emitted: u32,
+    /// How many periods in a row did not produce any instance
+    empty_periods: u32,
+    done: bool,
+}
+
+impl Instances<'_> {
+    /// Skip the periods that surely end before `date`, so that the instances around it are found without expanding every previous period. \
+    /// Some instances before `date` may still be returned. Rules that end after a COUNT are not skipped, since their instances have to be counted from DTSTART
+    pub fn skip_to(mut self, date: DateTime<Utc>) -> Self {
+        if matches!(self.rule.end, RecurrenceEnd::Count(_)) || self.period > 0 {
+            return self;
+        }
+        // Local times may be shifted by DST changes, which is covered by this margin
+        let elapsed = (date - self.dtstart - chrono::Duration::hours(2)).num_seconds();
+        let period_seconds = self.rule.frequency.max_period_seconds() * i64::from(self.rule.interval);
+        let periods = (elapsed / period_seconds).saturating_sub(1);
+        if periods > 0 {
+            self.period = u32::try_from(periods).unwrap_or(u32::MAX);
+        }
+        self
+    }
+}
+
+/// Rules that never match any date (e.g. `FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=30`) are given up after this many periods without instances
+const MAX_EMPTY_PERIODS: u32 = 1000;
+
+impl Iterator for Instances<'_> {
+    type Item = DateTime<Utc>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.done == false {
+            if let Some(dt) = self.buffer.pop_front() {
+                let count_reached = matches!(self.rule.end, RecurrenceEnd::Count(n) if self.emitted >= n);
+                if count_reached || self.rule.is_after_until(&dt) {
+                    self.done = true;
+                    return None;
+                }
+                self.emitted += 1;
+                return Some(dt);
+            }
+
+            if self.empty_periods >= MAX_EMPTY_PERIODS {
+                self.done = true;
+                return None;
+            }
+            match self.rule.period_instances(&self.dtstart, self.timezone.as_ref(), self.period) {
+                None => self.done = true,
+                Some(instances) => {
+                    // DTSTART has already been returned
+                    let dtstart = self.dtstart;
+                    let len_before = self.buffer.len();
+                    self.buffer.extend(instances.into_iter().filter(|dt| *dt > dtstart));
+                    match self.buffer.len() == len_before {
+                        true => self.empty_periods += 1,
+                        false => self.empty_periods = 0,
+                    }
+                },
+            }
+            self.period += 1;
+        }
+        None
+    }
+}
+
+/// Convert a local time into UTC. Local times that do not exist (because they are skipped by a DST change) are shifted by the length of the gap, as RFC 5545 requires
+pub(crate) fn to_utc(local: &NaiveDateTime, timezone: Option<&Tz>) -> Option<DateTime<Utc>> {
+    let tz = match timezone {
+        None => return Some(Utc.from_utc_datetime(local)),
+        Some(tz) => tz,
+    };
+    match tz.from_local_datetime(local).earliest() {
+        Some(dt) => Some(dt.with_timezone(&Utc)),
+        None => {
+            // Interpret it with the offset from before the gap
+            let before_gap = tz.from_local_datetime(&(*local - chrono::Duration::hours(3))).earliest()?;
+            let offset = before_gap.offset().fix().local_minus_utc();
+            Some(Utc.from_utc_datetime(&(*local - chrono::Duration::seconds(offset as i64))))
+        },
+    }
+}
+
+fn default_if_empty(values: &[u8], default: u8) -> Vec<u8> {
+    match values.is_empty() {
+        true => vec![default],
+        false => values.to_vec(),
+    }
+}
+
+fn days_in_month(day: &NaiveDate) -> u32 {
+    let (year, month) = match day.month() {
+        12 => (day.year() + 1, 1),
+        m => (day.year(), m + 1),
+    };
+    NaiveDate::from_ymd_opt(year, month, 1)
+        .and_then(|first_of_next| first_of_next.pred_opt())
+        .map(|last| last.day())
+        .unwrap_or(31)
+}
+
+fn days_in_year(day: &NaiveDate) -> u32 {
+    match NaiveDate::from_ymd_opt(day.year(), 12, 31) {
+        Some(last) => last.ordinal(),
+        None => 365,
+    }
+}
+
+
+
+/// A single instance of a (possibly recurring) event
+#[derive(Clone, Debug, PartialEq)]
+pub struct Occurrence {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+}
+
+impl Occurrence {
+    pub fn new(start: DateTime<Utc>, end: DateTime<Utc>) -> Self {
+        Self { start, end }
+    }
+
+    pub fn start(&self) -> &DateTime<Utc> { &self.start }
+    pub fn end(&self) -> &DateTime<Utc> { &self.end }
+
+    /// Whether this occurrence happens (at least partly) between `start` (included) and `end` (excluded)
+    pub fn overlaps(&self, start: &DateTime<Utc>, end: &DateTime<Utc>) -> bool {
+        match self.start == self.end {
+            // Zero-duration events
+            true => &self.start >= start && &self.start < end,
+            false => &self.start < end && &self.end > start,
+        }
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = Box<dyn Error>;
+
+    /// Parse the value of an `RRULE` property (e.g. `FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,WE`)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut frequency = None;
+        let mut rule = Self::new(Frequency::Daily, 1);
+
+        for part in s.trim().split(';').filter(|p| p.is_empty() == false) {
+            let (name, value) = match part.find('=') {
+                None => return Err(format!("Invalid recurrence rule part {}", part).into()),
+                Some(i) => (&part[..i], &part[i+1..]),
+            };
+            match name.to_ascii_uppercase().as_str() {
+                "FREQ" => frequency = Some(value.parse()?),
+                "INTERVAL" => rule.interval = value.parse::<u32>()?.max(1),
+                "COUNT" => rule.end = RecurrenceEnd::Count(value.parse()?),
+                "UNTIL" => rule.end = parse_until(value)?,
+                "BYSECOND" => rule.by_second = parse_list(value)?,
+                "BYMINUTE" => rule.by_minute = parse_list(value)?,
+                "BYHOUR" => rule.by_hour = parse_list(value)?,
+                "BYDAY" => rule.by_day = parse_list(value)?,
+                "BYMONTHDAY" => rule.by_month_day = parse_list(value)?,
+                "BYYEARDAY" => rule.by_year_day = parse_list(value)?,
+                "BYWEEKNO" => rule.by_week_no = parse_list(value)?,
+                "BYMONTH" => rule.by_month = parse_list(value)?,
+                "BYSETPOS" => rule.by_set_pos = parse_list(value)?,
+                "WKST" => rule.week_start = Some(parse_weekday(value)?),
+                _ => rule.extra_parts.push((name.to_string(), value.to_string())),
+            }
+        }
+
+        rule.frequency = frequency.ok_or("Missing FREQ in recurrence rule")?;
+        Ok(rule)
+    }
+}
+
+impl Display for Recurrence {
+    /// Format this rule as the value of an `RRULE` property
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "FREQ={}", self.frequency.as_ical_str())?;
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+        match &self.end {
+            RecurrenceEnd::Never => (),
+            RecurrenceEnd::Count(n) => write!(f, ";COUNT={}", n)?,
+            RecurrenceEnd::Until(dt) => write!(f, ";UNTIL={}", dt.format("%Y%m%dT%H%M%SZ"))?,
+            RecurrenceEnd::UntilDate(d) => write!(f, ";UNTIL={}", d.format("%Y%m%d"))?,
+        }
+        write_list(f, "BYSECOND", &self.by_second)?;
+        write_list(f, "BYMINUTE", &self.by_minute)?;
+        write_list(f, "BYHOUR", &self.by_hour)?;
+        write_list(f, "BYDAY", &self.by_day)?;
+        write_list(f, "BYMONTHDAY", &self.by_month_day)?;
+        write_list(f, "BYYEARDAY", &self.by_year_day)?;
+        write_list(f, "BYWEEKNO", &self.by_week_no)?;
+        write_list(f, "BYMONTH", &self.by_month)?;
+        write_list(f, "BYSETPOS", &self.by_set_pos)?;
+        if let Some(day) = self.week_start {
+            write!(f, ";WKST={}", weekday_to_ical(day))?;
+        }
+        for (name, value) in &self.extra_parts {
+            write!(f, ";{}={}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_until(value: &str) -> Result<RecurrenceEnd, Box<dyn Error>> {
+    if let Ok(dt) = Utc.datetime_from_str(value, "%Y%m%dT%H%M%SZ") {
+        return Ok(RecurrenceEnd::Until(dt));
+    }
+    // Floating date-times are not allowed here by the RFC, but some producers emit them anyway
+    if let Ok(dt) = Utc.datetime_from_str(value, "%Y%m%dT%H%M%S") {
+        return Ok(RecurrenceEnd::Until(dt));
+    }
+    Ok(RecurrenceEnd::UntilDate(NaiveDate::parse_from_str(value, "%Y%m%d")?))
+}
+
+fn parse_list<T>(value: &str) -> Result<Vec<T>, Box<dyn Error>>
+where
+    T: FromStr,
+    T::Err: Into<Box<dyn Error>>,
+{
+    value.split(',')
+        .map(|v| v.trim().parse::<T>().map_err(|err| err.into()))
+        .collect()
+}
+
+fn write_list<T: Display>(f: &mut Formatter<'_>, name: &str, values: &[T]) -> std::fmt::Result {
+    if values.is_empty() {
+        return Ok(());
+    }
+    write!(f, ";{}=", name)?;
+    for (i, value) in values.iter().enumerate() {
+        if i != 0 {
+            write!(f, ",")?;
+        }
+        write!(f, "{}", value)?;
+    }
+    Ok(())
+}
+
+
+
+/// Options that tweak the output of [`Recurrence::to_human_string`]
+#[derive(Clone, Debug)]
+pub struct HumanizationHints {
+    /// Use abbreviated names for days and months ("Mon" rather than "Monday")
+    pub short_names: bool,
+    /// The `strftime`-like format of the dates (e.g. the end of the recurrence)
+    pub date_format: String,
+}
+
+impl Default for HumanizationHints {
+    fn default() -> Self {
+        Self {
+            short_names: true,
+            date_format: String::from("%b %-d, %Y"),
+        }
+    }
+}
+
+fn weekday_name(day: Weekday, hints: &HumanizationHints) -> String {
+    let name = match day {
+        Weekday::Mon => "Monday",
+        Weekday::Tue => "Tuesday",
+        Weekday::Wed => "Wednesday",
+        Weekday::Thu => "Thursday",
+        Weekday::Fri => "Friday",
+        Weekday::Sat => "Saturday",
+        Weekday::Sun => "Sunday",
+    };
+    match hints.short_names {
+        true => name[..3].to_string(),
+        false => name.to_string(),
+    }
+}
+
+fn month_name(month: u8, hints: &HumanizationHints) -> String {
+    const MONTHS: [&str; 12] = ["January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November", "December"];
+    let name = match MONTHS.get((month as usize).wrapping_sub(1)) {
+        None => return format!("month #{}", month),
+        Some(name) => name,
+    };
+    match hints.short_names {
+        true => name[..3].to_string(),
+        false => name.to_string(),
+    }
+}
+
+/// "1st", "2nd", "last", "2nd to last"...
+fn ordinal_name(n: i32) -> String {
+    match n {
+        -1 => String::from("last"),
+        n if n < 0 => format!("{} to last", ordinal_name(-n)),
+        n => {
+            let suffix = match (n % 10, n % 100) {
+                (_, 11..=13) => "th",
+                (1, _) => "st",
+                (2, _) => "nd",
+                (3, _) => "rd",
+                _ => "th",
+            };
+            format!("{}{}", n, suffix)
+        },
+    }
+}
+
+fn join_list(items: &[String]) -> String {
+    items.join(", ")
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_format() {
+        let rules = [
+            "FREQ=DAILY",
+            "FREQ=WEEKLY;INTERVAL=2;UNTIL=20211231T000000Z;BYDAY=MO,WE",
+            "FREQ=MONTHLY;COUNT=10;BYDAY=-1FR",
+            "FREQ=YEARLY;UNTIL=20301231;BYMONTHDAY=15;BYMONTH=1,6;WKST=SU;X-SOMETHING=else",
+        ];
+        for rule in &rules {
+            let parsed: Recurrence = rule.parse().unwrap();
+            assert_eq!(parsed.to_string(), *rule);
+        }
+
+        let parsed: Recurrence = "FREQ=MONTHLY;BYDAY=2TU;COUNT=3".parse().unwrap();
+        assert_eq!(parsed.frequency(), Frequency::Monthly);
+        assert_eq!(parsed.end(), &RecurrenceEnd::Count(3));
+        assert_eq!(parsed.by_day(), &[WeekdayNum{ ordinal: Some(2), weekday: Weekday::Tue }]);
+
+        assert!("INTERVAL=2".parse::<Recurrence>().is_err());
+        assert!("FREQ=FORTNIGHTLY".parse::<Recurrence>().is_err());
+    }
+
+    #[test]
+    fn test_human_string() {
+        let hints = HumanizationHints::default();
+        let humanize = |rule: &str| rule.parse::<Recurrence>().unwrap().to_human_string(&hints);
+
+        assert_eq!(humanize("FREQ=DAILY"), "Daily");
+        assert_eq!(humanize("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,WE;UNTIL=20211231T000000Z"), "Every 2 weeks on Mon, Wed until Dec 31, 2021");
+        assert_eq!(humanize("FREQ=MONTHLY;BYDAY=-1FR;COUNT=10"), "Monthly on the last Fri, 10 times");
+        assert_eq!(humanize("FREQ=MONTHLY;BYMONTHDAY=1,22"), "Monthly on the 1st, 22nd");
+        assert_eq!(humanize("FREQ=YEARLY;BYMONTH=6;COUNT=1"), "Yearly in Jun, once");
+
+        let long_hints = HumanizationHints{ short_names: false, date_format: String::from("%B %-d") };
+        let rule: Recurrence = "FREQ=WEEKLY;BYDAY=TU;UNTIL=20211231".parse().unwrap();
+        assert_eq!(rule.to_human_string(&long_hints), "Weekly on Tuesday until December 31");
+    }
+
+    #[test]
+    fn test_instances() {
+        let instances = |rule: &str, dtstart: DateTime<Utc>| -> Vec<DateTime<Utc>> {
+            let rule: Recurrence = rule.parse().unwrap();
+            rule.instances(dtstart).take(10).collect()
+        };
+        let at_nine = |y, m, d| Utc.ymd(y, m, d).and_hms(9, 0, 0);
+
+        assert_eq!(
+            instances("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,WE;COUNT=5", at_nine(2021, 3, 1)),
+            vec![at_nine(2021, 3, 1), at_nine(2021, 3, 3), at_nine(2021, 3, 15), at_nine(2021, 3, 17), at_nine(2021, 3, 29)]
+        );
+        assert_eq!(
+            instances("FREQ=MONTHLY;BYDAY=-1FR;COUNT=3", at_nine(2021, 1, 29)),
+            vec![at_nine(2021, 1, 29), at_nine(2021, 2, 26), at_nine(2021, 3, 26)]
+        );
+        // Months without a 31st are skipped
+        assert_eq!(
+            instances("FREQ=MONTHLY;COUNT=3", at_nine(2021, 1, 31)),
+            vec![at_nine(2021, 1, 31), at_nine(2021, 3, 31), at_nine(2021, 5, 31)]
+        );
+        assert_eq!(
+            instances("FREQ=YEARLY;UNTIL=20290101", at_nine(2020, 2, 29)),
+            vec![at_nine(2020, 2, 29), at_nine(2024, 2, 29), at_nine(2028, 2, 29)]
+        );
+        // The last weekday of the month
+        assert_eq!(
+            instances("FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=-1;COUNT=2", at_nine(2021, 1, 29)),
+            vec![at_nine(2021, 1, 29), at_nine(2021, 2, 26)]
+        );
+        assert_eq!(instances("FREQ=DAILY;BYHOUR=9,18", at_nine(2021, 3, 1))[..3], [
+            at_nine(2021, 3, 1), Utc.ymd(2021, 3, 1).and_hms(18, 0, 0), at_nine(2021, 3, 2),
+        ]);
+        // Rules that never match do not loop forever
+        assert_eq!(instances("FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=30", at_nine(2021, 1, 1)), vec![at_nine(2021, 1, 1)]);
+    }
+
+    #[test]
+    fn test_skip_to() {
+        let dtstart = Utc.ymd(2000, 1, 1).and_hms(9, 0, 0);
+        let date = Utc.ymd(2021, 3, 1).and_hms(9, 7, 0);
+
+        let rule: Recurrence = "FREQ=MINUTELY;INTERVAL=15".parse().unwrap();
+        assert_eq!(rule.instances(dtstart).skip_to(date).find(|s| *s >= date), Some(Utc.ymd(2021, 3, 1).and_hms(9, 15, 0)));
+
+        // Skipping does not lose any instance after the date
+        let rule: Recurrence = "FREQ=WEEKLY;BYDAY=MO,FR".parse().unwrap();
+        let expected: Vec<_> = rule.instances(dtstart).filter(|s| *s >= date).take(5).collect();
+        let skipped: Vec<_> = rule.instances(dtstart).skip_to(date).filter(|s| *s >= date).take(5).collect();
+        assert_eq!(skipped, expected);
+
+        // Huge intervals end the rule, rather than wrapping around to past dates
+        let rule: Recurrence = "FREQ=YEARLY;INTERVAL=4000000000".parse().unwrap();
+        assert_eq!(rule.instances(dtstart).collect::<Vec<_>>(), vec![dtstart]);
+    }
+
+    #[test]
+    fn test_event_occurrences() {
+        let ical = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//test//EN\r\nBEGIN:VEVENT\r\n\
+            UID:standup\r\nDTSTAMP:20210301T000000Z\r\nSUMMARY:Stand-up meeting\r\n\
+            DTSTART:20210301T090000Z\r\nDTEND:20210301T100000Z\r\n\
+            RRULE:FREQ=DAILY;COUNT=5\r\nEXDATE:20210302T090000Z\r\nRDATE:20210310T090000Z\r\n\
+            END:VEVENT\r\nEND:VCALENDAR\r\n";
+        let url = "https://some.calend.ar/calendar/standup.ics".parse().unwrap();
+        let item = crate::ical::parse(ical, url, crate::item::SyncStatus::NotSynced).unwrap();
+        let event = item.unwrap_event();
+
+        let starts: Vec<u32> = event.occurrences_between(Utc.ymd(2021, 3, 1).and_hms(9, 30, 0), Utc.ymd(2021, 4, 1).and_hms(0, 0, 0))
+            .map(|occurrence| occurrence.start().day())
+            .collect();
+        assert_eq!(starts, vec![1, 3, 4, 5, 10]);
+
+        let occurrence = event.occurrences_between(Utc.ymd(2021, 3, 10).and_hms(0, 0, 0), Utc.ymd(2021, 3, 11).and_hms(0, 0, 0)).next().unwrap();
+        assert_eq!(occurrence.end(), &Utc.ymd(2021, 3, 10).and_hms(10, 0, 0));
+    }
+
+    #[test]
+    fn test_instances_across_dst() {
+        let tz: Tz = "Europe/Paris".parse().unwrap();
+        let rule: Recurrence = "FREQ=DAILY;COUNT=3".parse().unwrap();
+        // 9:00 in Paris, the day before the switch to summer time
+        let dtstart = Utc.ymd(2021, 3, 27).and_hms(8, 0, 0);
+
+        let in_utc: Vec<_> = rule.instances(dtstart).collect();
+        assert_eq!(in_utc[2], Utc.ymd(2021, 3, 29).and_hms(8, 0, 0));
+        let in_paris: Vec<_> = rule.instances_in(dtstart, &tz).collect();
+        assert_eq!(in_paris, vec![dtstart, Utc.ymd(2021, 3, 28).and_hms(7, 0, 0), Utc.ymd(2021, 3, 29).and_hms(7, 0, 0)]);
+
+        // 2:30 does not exist on the day of the switch, this instance is shifted to 3:30
+        let dtstart = tz.ymd(2021, 3, 27).and_hms(2, 30, 0).with_timezone(&Utc);
+        let in_paris: Vec<_> = rule.instances_in(dtstart, &tz).collect();
+        assert_eq!(in_paris[1], tz.ymd(2021, 3, 28).and_hms(3, 30, 0).with_timezone(&Utc));
+    }
+}
@@ -1,26 +1,209 @@
+use std::error::Error;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use reqwest::{ClientBuilder, RequestBuilder, Response, StatusCode};
+use reqwest::header::AUTHORIZATION;
 use url::Url;
 
-/// Just a wrapper around a URL and credentials
+use crate::auth::{self, Authentication, DigestChallenge, DigestSession};
+use crate::rate_limit::{RateLimit, RateLimiter};
+use crate::retry::RetryPolicy;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::tls::TlsConfig;
+use crate::proxy::ProxyConfig;
+use crate::transport::HttpTransport;
+
+/// Just a wrapper around a URL and credentials (and how requests to it are sent)
 #[derive(Clone, Debug)]
 pub struct Resource {
     url: Url,
-    username: String,
-    password: String,
+    authentication: Authentication,
+    /// Shared by every resource that is combined from this one, so that a Digest challenge is only received once
+    digest: Arc<DigestSession>,
+    /// Whether the server has asked for Basic authentication. Until then, Basic credentials are not sent (this is shared just like `digest`)
+    basic_challenged: Arc<AtomicBool>,
+    retry_policy: RetryPolicy,
+    /// Shared by every resource that is combined from this one
+    rate_limiter: Option<Arc<RateLimiter>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    tls_config: TlsConfig,
+    proxy: ProxyConfig,
+    /// The HTTP client requests are sent with. It is built from `tls_config` and `proxy`, and shared by every resource that is combined from this one (so that connections are re-used)
+    http: reqwest::Client,
+    /// The transport requests are sent with, when they are not sent by `http`
+    transport: Option<Arc<dyn HttpTransport>>,
 }
 
 impl Resource {
+    /// A resource that uses HTTP Basic authentication
     pub fn new(url: Url, username: String, password: String) -> Self {
-        Self { url, username, password }
+        Self::with_authentication(url, Authentication::Basic { username, password })
+    }
+
+    pub fn with_authentication(url: Url, authentication: Authentication) -> Self {
+        Self {
+            url, authentication,
+            digest: Arc::new(DigestSession::default()),
+            basic_challenged: Arc::new(AtomicBool::new(false)),
+            retry_policy: RetryPolicy::default(),
+            rate_limiter: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            tls_config: TlsConfig::default(),
+            proxy: ProxyConfig::default(),
+            http: reqwest::Client::new(),
+            transport: None,
+        }
     }
 
     pub fn url(&self) -> &Url { &self.url }
-    pub fn username(&self) -> &String { &self.username }
-    pub fn password(&self) -> &String { &self.password }
+    pub fn authentication(&self) -> &Authentication { &self.authentication }
+    pub fn retry_policy(&self) -> &RetryPolicy { &self.retry_policy }
+
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = policy;
+    }
+
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        self.rate_limiter.as_ref().map(|limiter| limiter.limit())
+    }
+
+    pub fn set_rate_limit(&mut self, limit: Option<RateLimit>) {
+        self.rate_limiter = limit.map(|limit| Arc::new(RateLimiter::new(limit)));
+    }
 
-    /// Build a new Resource by keeping the same credentials, scheme and server from `base` but changing the path part
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn tls_config(&self) -> &TlsConfig { &self.tls_config }
+
+    /// Change the TLS settings of this resource. This fails if they cannot be used (e.g. if the TLS library does not support the client certificate)
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_tls_config(&mut self, config: TlsConfig) -> Result<(), Box<dyn Error>> {
+        let previous = std::mem::replace(&mut self.tls_config, config);
+        self.rebuild_http().map_err(|err| {
+            self.tls_config = previous;
+            err
+        })
+    }
+
+    pub fn proxy(&self) -> &ProxyConfig { &self.proxy }
+
+    /// Change the proxy requests to this resource go through. This fails if the proxy cannot be used
+    pub fn set_proxy(&mut self, proxy: ProxyConfig) -> Result<(), Box<dyn Error>> {
+        let previous = std::mem::replace(&mut self.proxy, proxy);
+        self.rebuild_http().map_err(|err| {
+            self.proxy = previous;
+            err
+        })
+    }
+
+    pub fn transport(&self) -> Option<&Arc<dyn HttpTransport>> { self.transport.as_ref() }
+
+    /// Send requests with another transport (or with the HTTP client that is built from the TLS and proxy settings, if `transport` is `None`)
+    pub fn set_transport(&mut self, transport: Option<Arc<dyn HttpTransport>>) {
+        self.transport = transport;
+    }
+
+    fn rebuild_http(&mut self) -> Result<(), Box<dyn Error>> {
+        self.http = self.http_builder()?.build()?;
+        Ok(())
+    }
+
+    /// The HTTP client requests to this resource should be built with (they are sent with the transport of this resource, if any)
+    pub(crate) fn http(&self) -> &reqwest::Client { &self.http }
+
+    /// A builder for HTTP clients that have the same settings as the one of this resource (for requests that need other settings, e.g. ones that do not follow redirections)
+    pub(crate) fn http_builder(&self) -> Result<ClientBuilder, Box<dyn Error>> {
+        let builder = reqwest::Client::builder();
+        // In web browsers, TLS is handled by the browser itself
+        #[cfg(not(target_arch = "wasm32"))]
+        let builder = self.tls_config.apply(builder);
+        self.proxy.apply(builder)
+    }
+
+    /// Send a request with the credentials of this resource, according to its retry policy and its rate limit
+    pub(crate) async fn send(&self, request: RequestBuilder) -> Result<Response, Box<dyn Error>> {
+        #[cfg(feature = "tracing")]
+        {
+            let span = crate::instrumentation::request_span(&request);
+            let result = tracing::Instrument::instrument(self.send_authorized(request), span.clone()).await;
+            crate::instrumentation::record_response(&span, &result);
+            return result;
+        }
+
+        #[cfg(not(feature = "tracing"))]
+        return self.send_authorized(request).await;
+    }
+
+    /// Send a request without the credentials of this resource (e.g. to another server, that they are not meant for), according to its retry policy and its rate limit
+    pub(crate) async fn send_without_credentials(&self, request: RequestBuilder) -> Result<Response, Box<dyn Error>> {
+        self.retry_policy.send(request, self.rate_limiter.as_deref(), self.transport.as_deref()).await
+    }
+
+    async fn send_authorized(&self, request: RequestBuilder) -> Result<Response, Box<dyn Error>> {
+        // Kept in case the credentials have to be refreshed (this is not possible for requests with streamed bodies)
+        let copy = request.try_clone();
+        let basic_sent = self.basic_challenged.load(Ordering::SeqCst);
+
+        let request = self.authorize(request).await?;
+        let response = self.retry_policy.send(request, self.rate_limiter.as_deref(), self.transport.as_deref()).await?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+        let copy = match copy {
+            None => return Ok(response),
+            Some(copy) => copy,
+        };
+
+        let send_again = match (&self.authentication, DigestChallenge::from_response(&response)) {
+            (Authentication::Basic { .. }, Some(challenge)) => {
+                log::debug!("{} asks for Digest authentication", response.url());
+                self.digest.set_challenge(challenge);
+                true
+            },
+            (Authentication::Basic { .. }, None) if auth::asks_for_basic(&response) => {
+                self.basic_challenged.store(true, Ordering::SeqCst);
+                // In case Basic credentials have been sent already, they have been refused
+                basic_sent == false
+            },
+            _ => self.authentication.refresh().await,
+        };
+        if send_again == false {
+            return Ok(response);
+        }
+        log::info!("{} asks for credentials, sending the request again with them", response.url());
+        let request = self.authorize(copy).await?;
+        Ok(self.retry_policy.send(request, self.rate_limiter.as_deref(), self.transport.as_deref()).await?)
+    }
+
+    /// Add the credentials to a request. \
+    /// Usernames and passwords are only added once the server has sent a challenge (Digest ones, if the server has sent a Digest challenge)
+    async fn authorize(&self, request: RequestBuilder) -> Result<RequestBuilder, Box<dyn Error>> {
+        if let Authentication::Basic { username, password } = &self.authentication {
+            // Digest credentials depend on the method and the URL of the request
+            let built = request.try_clone().and_then(|copy| copy.build().ok());
+            if let Some(built) = built {
+                if let Some(header) = self.digest.authorization(username, password, built.method().as_str(), built.url()) {
+                    return Ok(request.header(AUTHORIZATION, header));
+                }
+            }
+            if self.basic_challenged.load(Ordering::SeqCst) == false {
+                return Ok(request);
+            }
+        }
+        self.authentication.apply(request).await
+    }
+
+    /// Build a new Resource by keeping the same credentials, scheme, server (and retry policy, rate limit, TLS settings, proxy and transport) from `base` but changing the path part
     pub fn combine(&self, new_path: &str) -> Resource {
         let mut built = (*self).clone();
         built.url.set_path(&new_path);
         built
     }
+
+    /// Build a new Resource by keeping the same credentials (and retry policy, rate limit, TLS settings, proxy and transport) from `base`, at another URL (that may be on another server)
+    pub fn with_url(&self, url: Url) -> Resource {
+        let mut built = (*self).clone();
+        built.url = url;
+        built
+    }
 }
@@ -0,0 +1,208 @@
+//! Retries of HTTP requests that failed because of transient errors (see [`Client::set_retry_policy`](crate::client::Client::set_retry_policy))
+
+use std::error::Error;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use reqwest::{Request, RequestBuilder, Response, StatusCode};
+use reqwest::header::{IF_NONE_MATCH, RETRY_AFTER};
+
+use crate::rate_limit::RateLimiter;
+use crate::runtime::sleep;
+use crate::transport::{HttpTransport, is_connect_error, is_transient_error};
+
+/// How requests that fail because of transient errors (connection failures, timeouts, `429 Too Many Requests`, `503 Service Unavailable`, etc.) are retried.
+///
+/// The delay between two attempts doubles every time (exponential backoff), unless the server tells how long to wait with a `Retry-After` header.
+///
+/// Requests that must not be applied twice (`POST`, `DELETE`, `MKCALENDAR`, and creations with `PUT` and `If-None-Match: *`) are only retried when the server cannot have processed them,
+/// i.e. after connection failures, `429 Too Many Requests` and `503 Service Unavailable`. After a timeout, such a request may have succeeded, and sending it again would fail.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// How many times a request is sent at most (including the first attempt)
+    max_attempts: u32,
+    /// The delay before the first retry
+    initial_backoff: Duration,
+    /// The longest delay between two attempts (this also caps the delays that are asked by servers)
+    max_backoff: Duration,
+    /// Whether `Retry-After` headers are honored
+    honor_retry_after: bool,
+}
+
+impl Default for RetryPolicy {
+    /// Three attempts, starting with a delay of half a second
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            honor_retry_after: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that retries a request up to `max_attempts - 1` times
+    pub fn new(max_attempts: u32) -> Self {
+        Self { max_attempts: max_attempts.max(1), ..Self::default() }
+    }
+
+    /// A policy that never retries
+    pub fn none() -> Self {
+        Self::new(1)
+    }
+
+    /// Wait `initial` before the first retry, then twice as long before every other retry, but never longer than `max`
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    /// Whether the `Retry-After` headers sent by servers are honored (this defaults to `true`)
+    pub fn honoring_retry_after(mut self, honor: bool) -> Self {
+        self.honor_retry_after = honor;
+        self
+    }
+
+    pub fn max_attempts(&self) -> u32 { self.max_attempts }
+    pub fn initial_backoff(&self) -> Duration { self.initial_backoff }
+    pub fn max_backoff(&self) -> Duration { self.max_backoff }
+    pub fn honors_retry_after(&self) -> bool { self.honor_retry_after }
+
+    /// The delay before the `attempt`-th retry (starting at 1)
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff.checked_mul(factor).unwrap_or(self.max_backoff).min(self.max_backoff)
+    }
+
+    /// Send a request (with `transport`, or with the HTTP client it has been built with, and waiting for `rate_limiter` every time, if any), and send it again in case it fails because of a transient error
+    pub(crate) async fn send(&self, request: RequestBuilder, rate_limiter: Option<&RateLimiter>, transport: Option<&dyn HttpTransport>) -> Result<Response, Box<dyn Error>> {
+        let idempotent = request.try_clone()
+            .and_then(|copy| copy.build().ok())
+            .map(|request| is_idempotent(&request))
+            .unwrap_or(true);
+        let mut attempt = 1;
+        loop {
+            if let Some(limiter) = rate_limiter {
+                limiter.acquire().await;
+            }
+
+            // Requests with streamed bodies cannot be cloned, hence cannot be retried
+            let this_attempt = match request.try_clone() {
+                Some(copy) if attempt < self.max_attempts => copy,
+                _ => return send_once(request, transport).await,
+            };
+
+            let delay = match send_once(this_attempt, transport).await {
+                Ok(response) if is_transient(response.status()) && (idempotent || is_unprocessed(response.status())) => {
+                    let asked = match self.honor_retry_after {
+                        true => retry_after(&response, Utc::now()),
+                        false => None,
+                    };
+                    log::info!("{} replied with {}, retrying (attempt {}/{})", response.url(), response.status(), attempt + 1, self.max_attempts);
+                    #[cfg(feature = "tracing")]
+                    tracing::info!(status = response.status().as_u16(), attempt = attempt + 1, max_attempts = self.max_attempts, "transient HTTP status, retrying");
+                    asked.map(|delay| delay.min(self.max_backoff)).unwrap_or_else(|| self.backoff(attempt))
+                },
+                Ok(response) => return Ok(response),
+                Err(err) if is_transient_error(err.as_ref()) && (idempotent || is_connect_error(err.as_ref())) => {
+                    log::info!("Request failed ({}), retrying (attempt {}/{})", err, attempt + 1, self.max_attempts);
+                    #[cfg(feature = "tracing")]
+                    tracing::info!(error = %err, attempt = attempt + 1, max_attempts = self.max_attempts, "transient error, retrying");
+                    self.backoff(attempt)
+                },
+                Err(err) => return Err(err),
+            };
+
+            sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+async fn send_once(request: RequestBuilder, transport: Option<&dyn HttpTransport>) -> Result<Response, Box<dyn Error>> {
+    match transport {
+        None => Ok(request.send().await?),
+        Some(transport) => transport.execute(request.build()?).await,
+    }
+}
+
+/// Whether a status means the request may succeed if it is sent again later
+fn is_transient(status: StatusCode) -> bool {
+    matches!(status,
+        StatusCode::REQUEST_TIMEOUT
+        | StatusCode::TOO_MANY_REQUESTS
+        | StatusCode::BAD_GATEWAY
+        | StatusCode::SERVICE_UNAVAILABLE
+        | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// Whether a status means the server has not processed the request at all
+fn is_unprocessed(status: StatusCode) -> bool {
+    matches!(status, StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE)
+}
+
+/// Whether sending a request twice has the same effect as sending it once
+fn is_idempotent(request: &Request) -> bool {
+    match request.method().as_str() {
+        "POST" | "DELETE" | "MKCALENDAR" => false,
+        "PUT" => request.headers().get(IF_NONE_MATCH).map(|value| value.as_bytes() == b"*") != Some(true),
+        _ => true,
+    }
+}
+
+/// The delay a server asks for in its `Retry-After` header (either a number of seconds, or an HTTP date)
+fn retry_after(response: &Response, now: DateTime<Utc>) -> Option<Duration> {
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?;
+    parse_retry_after(value, now)
+}
+
+fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?.with_timezone(&Utc);
+    Some((date - now).to_std().unwrap_or(Duration::from_secs(0)))
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_retry_policy() {
+        let policy = RetryPolicy::new(5).with_backoff(Duration::from_secs(1), Duration::from_secs(5));
+        assert_eq!(policy.backoff(1), Duration::from_secs(1));
+        assert_eq!(policy.backoff(2), Duration::from_secs(2));
+        assert_eq!(policy.backoff(3), Duration::from_secs(4));
+        assert_eq!(policy.backoff(4), Duration::from_secs(5));
+        assert_eq!(RetryPolicy::new(0).max_attempts(), 1);
+
+        let now = Utc.ymd(2021, 3, 22).and_hms(9, 0, 0);
+        assert_eq!(parse_retry_after("120", now), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after("Mon, 22 Mar 2021 09:00:30 GMT", now), Some(Duration::from_secs(30)));
+        assert_eq!(parse_retry_after("Mon, 22 Mar 2021 08:00:00 GMT", now), Some(Duration::from_secs(0)));
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn test_idempotent_requests() {
+        let client = reqwest::Client::new();
+        let url = "https://example.com/dav/task.ics";
+        let request = |method: &str| client.request(method.parse().unwrap(), url);
+
+        assert!(is_idempotent(&request("PROPFIND").build().unwrap()));
+        assert!(is_idempotent(&request("PUT").build().unwrap()));
+        assert!(is_idempotent(&request("PUT").header(reqwest::header::IF_MATCH, "\"etag\"").build().unwrap()));
+        assert_eq!(is_idempotent(&request("PUT").header(IF_NONE_MATCH, "*").build().unwrap()), false);
+        assert_eq!(is_idempotent(&request("DELETE").build().unwrap()), false);
+        assert_eq!(is_idempotent(&request("MKCALENDAR").build().unwrap()), false);
+        assert_eq!(is_idempotent(&request("POST").build().unwrap()), false);
+    }
+}
@@ -0,0 +1,277 @@
+//! Scheduling of events with attendees, with iTIP messages (see [RFC 5546](https://datatracker.ietf.org/doc/html/rfc5546))
+//!
+//! Organizers send `REQUEST`s to invite attendees (or to tell them an event has changed), and `CANCEL`s when an event is cancelled (or when some attendees are uninvited). Attendees answer with `REPLY`s. \
+//! These messages can be delivered by the CalDAV server, through the scheduling Outbox of the user (see [`Client::send_scheduling_message`](crate::client::Client::send_scheduling_message) and [RFC 6638](https://datatracker.ietf.org/doc/html/rfc6638)),
+//! or sent by email as iMIP attachments (see [`ItipMessage::content_type`] and [RFC 6047](https://datatracker.ietf.org/doc/html/rfc6047)). \
+//! Messages that are sent to the user are delivered to their scheduling Inbox (see [`Client::schedule_inbox`](crate::client::Client::schedule_inbox)).
+//!
+//! Syncs can send these messages by themselves, for the events the user organizes (see [`Provider::set_scheduling_address`](crate::provider::Provider::set_scheduling_address)). \
+//! Note that many servers (e.g. Nextcloud) schedule events by themselves when they are stored into a calendar ("implicit scheduling"). Sending messages to their Outbox as well would deliver invitations twice.
+
+use std::error::Error;
+
+use crate::attendee::{Attendee, ParticipationStatus};
+use crate::event::{Event, EventStatus};
+use crate::ical::CalendarEnvelope;
+use crate::Item;
+
+/// The kind of an iTIP message (iCal `METHOD`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ItipMethod {
+    /// An invitation to an event, or an update of an event attendees have already been invited to
+    Request,
+    /// The answer of an attendee to an invitation
+    Reply,
+    /// The cancellation of an event, or the uninvitation of some attendees
+    Cancel,
+}
+
+impl ItipMethod {
+    pub fn as_ical_str(&self) -> &'static str {
+        match self {
+            ItipMethod::Request => "REQUEST",
+            ItipMethod::Reply => "REPLY",
+            ItipMethod::Cancel => "CANCEL",
+        }
+    }
+}
+
+/// An iTIP message, ready to be sent to its recipients
+#[derive(Clone, Debug)]
+pub struct ItipMessage {
+    method: ItipMethod,
+    /// The cal-address of the sender
+    originator: String,
+    /// The cal-addresses of the recipients
+    recipients: Vec<String>,
+    /// The iCal content of the message, including its `METHOD`
+    content: String,
+}
+
+impl ItipMessage {
+    pub fn method(&self) -> ItipMethod { self.method }
+    pub fn originator(&self) -> &str { &self.originator }
+    pub fn recipients(&self) -> &[String] { &self.recipients }
+    pub fn content(&self) -> &str { &self.content }
+
+    /// The MIME type of this message, e.g. to attach it to an email
+    pub fn content_type(&self) -> String {
+        format!("text/calendar; charset=utf-8; method={}", self.method.as_ical_str())
+    }
+
+    /// Invite the attendees of an event (or tell them the event has changed).
+    ///
+    /// The organizer is not sent the message, even if they also are an attendee
+    pub fn request(event: &Event) -> Result<Self, Box<dyn Error>> {
+        let organizer = organizer_address(event)?;
+        let recipients = event.attendees().iter()
+            .map(|attendee| attendee.address().to_string())
+            .filter(|address| same_address(address, &organizer) == false)
+            .collect();
+        Self::new(ItipMethod::Request, organizer, recipients, event.clone())
+    }
+
+    /// Cancel an event, for every attendee (if `recipients` is `None`), or only uninvite some of them.
+    ///
+    /// Attendees must be told about a higher SEQUENCE than the one they know of, hence the SEQUENCE of `event` is expected to have been increased (see [`update_sequence`])
+    pub fn cancel(event: &Event, recipients: Option<&[String]>) -> Result<Self, Box<dyn Error>> {
+        let organizer = organizer_address(event)?;
+        let is_recipient = |attendee: &Attendee| {
+            same_address(attendee.address(), &organizer) == false
+            && recipients.map(|recipients| recipients.iter().any(|r| same_address(r, attendee.address()))).unwrap_or(true)
+        };
+
+        let mut cancelled = event.clone();
+        let attendees: Vec<Attendee> = event.attendees().iter().filter(|attendee| is_recipient(attendee)).cloned().collect();
+        let addresses = attendees.iter().map(|attendee| attendee.address().to_string()).collect();
+        if recipients.is_none() {
+            cancelled.set_status(Some(EventStatus::Cancelled));
+        }
+        cancelled.set_attendees(attendees);
+        Self::new(ItipMethod::Cancel, organizer, addresses, cancelled)
+    }
+
+    /// Answer an invitation, on behalf of the attendee whose cal-address is `attendee_address`
+    pub fn reply(event: &Event, attendee_address: &str, status: ParticipationStatus) -> Result<Self, Box<dyn Error>> {
+        let organizer = organizer_address(event)?;
+        let mut attendee = event.attendees().iter()
+            .find(|attendee| same_address(attendee.address(), attendee_address))
+            .cloned()
+            .ok_or_else(|| format!("{} is not an attendee of {}", attendee_address, event.url()))?;
+        attendee.set_status(Some(status));
+        attendee.set_rsvp(false);
+
+        // A reply only tells about the attendee who answers
+        let mut reply = event.clone();
+        reply.set_attendees(vec![attendee]);
+        Self::new(ItipMethod::Reply, attendee_address.to_string(), vec![organizer], reply)
+    }
+
+    fn new(method: ItipMethod, originator: String, recipients: Vec<String>, event: Event) -> Result<Self, Box<dyn Error>> {
+        let envelope = CalendarEnvelope::new(event.ical_prod_id().to_string())
+            .with_property("METHOD".to_string(), method.as_ical_str().to_string());
+        let item = Item::Event(event);
+        let content = crate::ical::build_from_items(std::iter::once(&item), &envelope)?;
+        Ok(Self { method, originator, recipients, content })
+    }
+}
+
+/// Whether an iTIP message has been delivered to one of its recipients, as reported by the server
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeliveryStatus {
+    /// The cal-address of the recipient
+    recipient: String,
+    /// The iTIP `REQUEST-STATUS`, e.g. `2.0;Success` or `3.7;Invalid calendar user`
+    request_status: String,
+}
+
+impl DeliveryStatus {
+    pub fn new(recipient: String, request_status: String) -> Self {
+        Self { recipient, request_status }
+    }
+
+    pub fn recipient(&self) -> &str { &self.recipient }
+    pub fn request_status(&self) -> &str { &self.request_status }
+
+    /// Whether the message has been delivered (i.e. its status is in the `2.x` class)
+    pub fn is_success(&self) -> bool {
+        self.request_status.starts_with("2.")
+    }
+}
+
+/// Whether a change to an event must be told to its attendees with a higher SEQUENCE, and increase it in `new` if needed.
+///
+/// As per RFC 5546, this is the case when the event is rescheduled (its start, end or recurrence changes) or when its status changes.
+/// Uninviting attendees increases it as well, so that the `CANCEL` they are sent supersedes their invitation.
+/// Returns whether the SEQUENCE has been increased
+pub fn update_sequence(old: &Event, new: &mut Event) -> bool {
+    let attendee_removed = old.attendees().iter()
+        .any(|attendee| new.attendees().iter().any(|a| same_address(a.address(), attendee.address())) == false);
+    let significant = old.start() != new.start()
+        || old.end() != new.end()
+        || old.recurrence() != new.recurrence()
+        || old.status() != new.status()
+        || attendee_removed;
+
+    if significant && new.sequence() <= old.sequence() {
+        new.set_sequence(old.sequence() + 1);
+        return true;
+    }
+    false
+}
+
+/// The messages the organizer of an event must send, when this event is created (`old` is `None`), changed, or deleted (`new` is `None`).
+///
+/// Events without attendees need no message. When an event is changed, [`update_sequence`] should be called beforehand
+pub fn messages_for_change(old: Option<&Event>, new: Option<&Event>) -> Result<Vec<ItipMessage>, Box<dyn Error>> {
+    let mut messages = Vec::new();
+    match (old, new) {
+        (None, None) => (),
+        (None, Some(new)) => {
+            if new.attendees().is_empty() == false {
+                messages.push(ItipMessage::request(new)?);
+            }
+        },
+        (Some(old), None) => {
+            if old.attendees().is_empty() == false {
+                let mut deleted = old.clone();
+                deleted.set_sequence(old.sequence() + 1);
+                messages.push(ItipMessage::cancel(&deleted, None)?);
+            }
+        },
+        (Some(old), Some(new)) => {
+            let removed: Vec<String> = old.attendees().iter()
+                .map(|attendee| attendee.address().to_string())
+                .filter(|address| new.attendees().iter().any(|a| same_address(a.address(), address)) == false)
+                .collect();
+            if removed.is_empty() == false {
+                // Uninvited attendees are only told about them being removed
+                let mut uninvited = new.clone();
+                uninvited.set_attendees(old.attendees().to_vec());
+                messages.push(ItipMessage::cancel(&uninvited, Some(&removed))?);
+            }
+            if new.attendees().is_empty() == false {
+                messages.push(ItipMessage::request(new)?);
+            }
+        },
+    }
+    Ok(messages.into_iter().filter(|message| message.recipients.is_empty() == false).collect())
+}
+
+/// Whether `address` is the cal-address of the organizer of `event`
+pub fn is_organized_by(event: &Event, address: &str) -> bool {
+    event.organizer().map(|organizer| same_address(organizer.address(), address)).unwrap_or(false)
+}
+
+fn organizer_address(event: &Event) -> Result<String, Box<dyn Error>> {
+    event.organizer()
+        .map(|organizer| organizer.address().to_string())
+        .ok_or_else(|| format!("Event {} has no organizer, it cannot be scheduled", event.url()).into())
+}
+
+/// Whether two cal-addresses are the same (the `mailto:` scheme and email addresses are case-insensitive)
+fn same_address(left: &str, right: &str) -> bool {
+    left.eq_ignore_ascii_case(right)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::{Duration, TimeZone, Utc};
+    use url::Url;
+
+    use crate::attendee::Organizer;
+    use crate::event::EventBuilder;
+
+    #[test]
+    fn test_itip_messages() {
+        let cal_url = Url::parse("https://caldav.com/calendars/john/work/").unwrap();
+        let start = Utc.ymd(2021, 4, 1).and_hms(9, 0, 0);
+        let end = Utc.ymd(2021, 4, 1).and_hms(10, 0, 0);
+        let event = EventBuilder::new("Meeting".to_string(), start, end, &cal_url)
+            .with_organizer(Organizer::new("mailto:john@example.com".to_string(), None))
+            .with_attendee(Attendee::new("mailto:John@example.com".to_string()))
+            .with_attendee(Attendee::new("mailto:jane@example.com".to_string()))
+            .with_attendee(Attendee::new("mailto:bob@example.com".to_string()))
+            .build().unwrap();
+
+        let created = messages_for_change(None, Some(&event)).unwrap();
+        assert_eq!(created.len(), 1);
+        assert_eq!(created[0].method(), ItipMethod::Request);
+        assert_eq!(created[0].recipients(), &["mailto:jane@example.com".to_string(), "mailto:bob@example.com".to_string()]);
+        assert!(created[0].content().contains("METHOD:REQUEST\r\n"));
+
+        // Moving the event one hour later, and uninviting Bob
+        let mut moved = EventBuilder::new("Meeting".to_string(), start + Duration::hours(1), end + Duration::hours(1), &cal_url)
+            .with_url(event.url().clone())
+            .with_uid(event.uid().clone())
+            .with_organizer(event.organizer().unwrap().clone())
+            .with_attendee(event.attendees()[0].clone())
+            .with_attendee(event.attendees()[1].clone())
+            .build().unwrap();
+        assert!(update_sequence(&event, &mut moved));
+        assert_eq!(moved.sequence(), 1);
+        assert!(update_sequence(&event, &mut moved) == false);
+
+        let updated = messages_for_change(Some(&event), Some(&moved)).unwrap();
+        assert_eq!(updated.len(), 2);
+        assert_eq!(updated[0].method(), ItipMethod::Cancel);
+        assert_eq!(updated[0].recipients(), &["mailto:bob@example.com".to_string()]);
+        assert!(updated[0].content().contains("SEQUENCE:1\r\n"));
+        assert_eq!(updated[1].recipients(), &["mailto:jane@example.com".to_string()]);
+
+        let reply = ItipMessage::reply(&moved, "mailto:jane@example.com", ParticipationStatus::Accepted).unwrap();
+        assert_eq!(reply.originator(), "mailto:jane@example.com");
+        assert_eq!(reply.recipients(), &["mailto:john@example.com".to_string()]);
+        assert!(reply.content().contains("PARTSTAT=ACCEPTED"));
+        assert!(reply.content().contains("bob@example.com") == false);
+
+        let deleted = messages_for_change(Some(&moved), None).unwrap();
+        assert_eq!(deleted[0].method(), ItipMethod::Cancel);
+        assert!(deleted[0].content().contains("STATUS:CANCELLED\r\n"));
+        assert!(deleted[0].content().contains("SEQUENCE:2\r\n"));
+    }
+}
@@ -0,0 +1,398 @@
+//! A local cache for CalDAV data, that is stored in an SQLite database.
+//!
+//! This is only available with the `sqlite` Cargo feature.
+//!
+//! Every item is stored in its own row, and only the items that have changed are written (see [`crate::persistent_cache`]). \
+//! Items can also be looked up by UID using an index of the database (see [`PersistentCache::find_item_by_uid`]), without loading every calendar.
+
+use std::error::Error;
+use std::path::Path;
+
+use csscolorparser::Color;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::persistent_cache::{PersistentCache, PersistentCalendar};
+use crate::storage::{CacheStorage, CalendarRecord, ItemChange, ItemRecord};
+use crate::calendar::{Privileges, SupportedComponents};
+use crate::item::VersionTag;
+use crate::error::Rejection;
+use crate::migration::{self, CURRENT_FORMAT_VERSION, FORMAT_VERSION_METADATA};
+use crate::Item;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS calendars (
+        url         TEXT PRIMARY KEY,
+        name        TEXT NOT NULL,
+        properties  TEXT NOT NULL,
+        sync_token  TEXT
+    );
+    CREATE TABLE IF NOT EXISTS items (
+        url           TEXT PRIMARY KEY,
+        calendar_url  TEXT NOT NULL REFERENCES calendars(url) ON DELETE CASCADE,
+        uid           TEXT NOT NULL,
+        content       TEXT NOT NULL,
+        rejection     TEXT
+    );
+    CREATE INDEX IF NOT EXISTS items_by_calendar ON items(calendar_url);
+    CREATE INDEX IF NOT EXISTS items_by_uid ON items(uid);
+    CREATE TABLE IF NOT EXISTS evicted_items (
+        url           TEXT PRIMARY KEY,
+        calendar_url  TEXT NOT NULL REFERENCES calendars(url) ON DELETE CASCADE,
+        version_tag   TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS metadata (
+        name  TEXT PRIMARY KEY,
+        data  BLOB NOT NULL
+    );
+";
+
+/// A [`PersistentCache`] that is stored in an SQLite database
+pub type SqliteCache = PersistentCache<SqliteStorage>;
+/// A calendar of a [`SqliteCache`]
+pub type SqliteCalendar = PersistentCalendar<SqliteStorage>;
+
+/// The properties of a calendar, that are stored as JSON so that new ones can be added without changing the schema
+#[derive(Serialize, Deserialize)]
+struct CalendarProperties {
+    supported_components: SupportedComponents,
+    color: Option<Color>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    order: Option<i32>,
+    #[serde(default)]
+    properties_modified: bool,
+    #[serde(default)]
+    privileges: Privileges,
+}
+
+/// A [`CacheStorage`] that uses an SQLite database, with one row per item
+#[derive(Debug)]
+pub struct SqliteStorage {
+    connection: Connection,
+}
+
+impl SqliteStorage {
+    /// Open (or create) a database file
+    pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    /// Create a database that only lives in memory. This is mostly useful for tests
+    pub fn in_memory() -> Result<Self, Box<dyn Error>> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(connection: Connection) -> Result<Self, Box<dyn Error>> {
+        connection.execute_batch("PRAGMA foreign_keys = ON;")?;
+        connection.execute_batch(SCHEMA)?;
+        let mut storage = Self { connection };
+        storage.migrate()?;
+        Ok(storage)
+    }
+
+    /// Upgrade the items that have been written by older versions of this crate (see [`crate::migration`])
+    fn migrate(&mut self) -> Result<(), Box<dyn Error>> {
+        let stored_version = self.load_metadata(FORMAT_VERSION_METADATA)?;
+        let format_version = migration::parse_format_version(stored_version.as_deref())?;
+        if stored_version.is_some() && format_version == CURRENT_FORMAT_VERSION {
+            return Ok(());
+        }
+
+        let transaction = self.connection.transaction()?;
+        if format_version != CURRENT_FORMAT_VERSION {
+            log::info!("Upgrading the cache from format version {} to {}", format_version, CURRENT_FORMAT_VERSION);
+            let mut migrated = Vec::new();
+            {
+                let mut statement = transaction.prepare("SELECT url, content FROM items")?;
+                let mut rows = statement.query([])?;
+                while let Some(row) = rows.next()? {
+                    let mut item: serde_json::Value = serde_json::from_str(&row.get::<_, String>(1)?)?;
+                    migration::migrate_item(&mut item, format_version)?;
+                    migrated.push((row.get::<_, String>(0)?, serde_json::to_string(&item)?));
+                }
+            }
+            for (url, content) in migrated {
+                transaction.execute("UPDATE items SET content = ?2 WHERE url = ?1", params![url, content])?;
+            }
+        }
+        transaction.execute(
+            "INSERT OR REPLACE INTO metadata (name, data) VALUES (?1, ?2)",
+            params![FORMAT_VERSION_METADATA, CURRENT_FORMAT_VERSION.to_string().as_bytes()],
+        )?;
+        transaction.commit()?;
+        Ok(())
+    }
+}
+
+impl CacheStorage for SqliteStorage {
+    fn load_calendars(&mut self) -> Result<Vec<CalendarRecord>, Box<dyn Error>> {
+        let mut calendars = Vec::new();
+
+        let mut statement = self.connection.prepare("SELECT url, name, properties, sync_token FROM calendars")?;
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            let url: String = row.get(0)?;
+            let properties: CalendarProperties = match serde_json::from_str(&row.get::<_, String>(2)?) {
+                Err(err) => {
+                    log::error!("Unable to load calendar {} from the cache: {}", url, err);
+                    continue;
+                },
+                Ok(properties) => properties,
+            };
+            calendars.push(CalendarRecord {
+                url: Url::parse(&url)?,
+                name: row.get(1)?,
+                supported_components: properties.supported_components,
+                color: properties.color,
+                description: properties.description,
+                order: properties.order,
+                properties_modified: properties.properties_modified,
+                privileges: properties.privileges,
+                sync_token: row.get(3)?,
+            });
+        }
+
+        Ok(calendars)
+    }
+
+    fn load_items(&mut self, calendar_url: &Url) -> Result<Vec<ItemRecord>, Box<dyn Error>> {
+        let mut items = Vec::new();
+
+        let mut statement = self.connection.prepare("SELECT url, content, rejection FROM items WHERE calendar_url = ?1")?;
+        let mut rows = statement.query(params![calendar_url.as_str()])?;
+        while let Some(row) = rows.next()? {
+            let item: Item = match serde_json::from_str(&row.get::<_, String>(1)?) {
+                Err(err) => {
+                    log::error!("Unable to load item {} from the cache: {}", row.get::<_, String>(0)?, err);
+                    continue;
+                },
+                Ok(item) => item,
+            };
+            let rejection: Option<Rejection> = match row.get::<_, Option<String>>(2)? {
+                None => None,
+                // An unreadable rejection is not worth losing the item
+                Some(rejection) => serde_json::from_str(&rejection).ok(),
+            };
+            items.push(ItemRecord::Item { item, rejection });
+        }
+
+        let mut statement = self.connection.prepare("SELECT url, version_tag FROM evicted_items WHERE calendar_url = ?1")?;
+        let mut rows = statement.query(params![calendar_url.as_str()])?;
+        while let Some(row) = rows.next()? {
+            items.push(ItemRecord::Evicted {
+                url: Url::parse(&row.get::<_, String>(0)?)?,
+                version_tag: VersionTag::from(row.get::<_, String>(1)?),
+            });
+        }
+
+        Ok(items)
+    }
+
+    fn save_calendar(&mut self, calendar: &CalendarRecord) -> Result<(), Box<dyn Error>> {
+        let properties = CalendarProperties {
+            supported_components: calendar.supported_components,
+            color: calendar.color.clone(),
+            description: calendar.description.clone(),
+            order: calendar.order,
+            properties_modified: calendar.properties_modified,
+            privileges: calendar.privileges,
+        };
+        self.connection.execute(
+            "INSERT INTO calendars (url, name, properties, sync_token) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(url) DO UPDATE SET name = ?2, properties = ?3, sync_token = ?4",
+            params![calendar.url.as_str(), calendar.name, serde_json::to_string(&properties)?, calendar.sync_token],
+        )?;
+        Ok(())
+    }
+
+    fn delete_calendar(&mut self, calendar_url: &Url) -> Result<(), Box<dyn Error>> {
+        // Its items are deleted as well (see `ON DELETE CASCADE` in the schema)
+        self.connection.execute("DELETE FROM calendars WHERE url = ?1", params![calendar_url.as_str()])?;
+        Ok(())
+    }
+
+    fn save_items(&mut self, calendar_url: &Url, changes: Vec<ItemChange>) -> Result<(), Box<dyn Error>> {
+        let transaction = self.connection.transaction()?;
+        for change in changes {
+            let item_url = change.url().as_str().to_string();
+            match change {
+                ItemChange::Saved(ItemRecord::Item { item, rejection }) => {
+                    let rejection = match rejection {
+                        None => None,
+                        Some(rejection) => Some(serde_json::to_string(&rejection)?),
+                    };
+                    transaction.execute(
+                        "INSERT OR REPLACE INTO items (url, calendar_url, uid, content, rejection) VALUES (?1, ?2, ?3, ?4, ?5)",
+                        params![item_url, calendar_url.as_str(), item.uid().as_str(), serde_json::to_string(&item)?, rejection],
+                    )?;
+                    transaction.execute("DELETE FROM evicted_items WHERE url = ?1", params![item_url])?;
+                },
+                ItemChange::Saved(ItemRecord::Evicted { version_tag, .. }) => {
+                    transaction.execute("DELETE FROM items WHERE url = ?1", params![item_url])?;
+                    transaction.execute(
+                        "INSERT OR REPLACE INTO evicted_items (url, calendar_url, version_tag) VALUES (?1, ?2, ?3)",
+                        params![item_url, calendar_url.as_str(), version_tag.as_str()],
+                    )?;
+                },
+                ItemChange::Removed(_) => {
+                    transaction.execute("DELETE FROM items WHERE url = ?1", params![item_url])?;
+                    transaction.execute("DELETE FROM evicted_items WHERE url = ?1", params![item_url])?;
+                },
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn load_metadata(&mut self, name: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        Ok(self.connection.query_row(
+            "SELECT data FROM metadata WHERE name = ?1",
+            params![name],
+            |row| row.get(0),
+        ).optional()?)
+    }
+
+    fn save_metadata(&mut self, name: &str, data: &[u8]) -> Result<(), Box<dyn Error>> {
+        self.connection.execute("INSERT OR REPLACE INTO metadata (name, data) VALUES (?1, ?2)", params![name, data])?;
+        Ok(())
+    }
+
+    /// This uses an index of the database
+    fn load_item(&mut self, calendar_url: &Url, item_url: &Url) -> Result<Option<Item>, Box<dyn Error>> {
+        let content: Option<String> = self.connection.query_row(
+            "SELECT content FROM items WHERE calendar_url = ?1 AND url = ?2",
+            params![calendar_url.as_str(), item_url.as_str()],
+            |row| row.get(0),
+        ).optional()?;
+
+        match content {
+            None => Ok(None),
+            Some(content) => Ok(Some(serde_json::from_str(&content)?)),
+        }
+    }
+
+    fn find_item_by_uid(&mut self, uid: &str) -> Result<Option<(Url, Url)>, Box<dyn Error>> {
+        let found: Option<(String, String)> = self.connection.query_row(
+            "SELECT calendar_url, url FROM items WHERE uid = ?1 LIMIT 1",
+            params![uid],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).optional()?;
+
+        match found {
+            None => Ok(None),
+            Some((calendar_url, item_url)) => Ok(Some((Url::parse(&calendar_url)?, Url::parse(&item_url)?))),
+        }
+    }
+}
+
+impl PersistentCache<SqliteStorage> {
+    /// Open (or create) a cache in a database file
+    pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Self::new(SqliteStorage::open(path)?)
+    }
+
+    /// Create a cache that only lives in memory. This is mostly useful for tests
+    pub fn in_memory() -> Result<Self, Box<dyn Error>> {
+        Self::new(SqliteStorage::in_memory()?)
+    }
+
+    /// Find the calendar an item belongs to, using an index of the database
+    pub fn find_calendar_of(&self, item_url: &Url) -> Result<Option<Url>, Box<dyn Error>> {
+        let storage = self.storage().lock().unwrap();
+        let found: Option<String> = storage.connection.query_row(
+            "SELECT calendar_url FROM items WHERE url = ?1 UNION SELECT calendar_url FROM evicted_items WHERE url = ?1",
+            params![item_url.as_str()],
+            |row| row.get(0),
+        ).optional()?;
+
+        match found {
+            None => Ok(None),
+            Some(calendar_url) => Ok(Some(Url::parse(&calendar_url)?)),
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+    use crate::traits::{BaseCalendar, CalDavSource, CompleteCalendar};
+    use crate::calendar::ItemQuery;
+    use crate::item::SyncStatus;
+    use crate::task::Task;
+
+    #[tokio::test]
+    async fn sqlite_cache_persistence() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let db_path = PathBuf::from(String::from("test_cache/sqlite_test.db"));
+        std::fs::create_dir_all(db_path.parent().unwrap()).unwrap();
+        let _ = std::fs::remove_file(&db_path);
+
+        let cal_url = Url::parse("https://caldav.com/bucket-list").unwrap();
+        let (kept_url, kept_uid, deleted_url) = {
+            let mut cache = SqliteCache::open(&db_path).unwrap();
+            let cal = cache.create_calendar(cal_url.clone(), "My bucket list".to_string(), SupportedComponents::TODO, None).await.unwrap();
+            let mut cal = cal.write().unwrap();
+
+            let kept = Task::new(String::from("Attend a concert of JS Bach"), false, &cal_url);
+            let (kept_url, kept_uid) = (kept.url().clone(), kept.uid().to_string());
+            cal.add_item(Item::Task(kept)).await.unwrap();
+            let deleted = Task::new(String::from("Climb the Lighthouse of Alexandria"), true, &cal_url);
+            let deleted_url = deleted.url().clone();
+            cal.add_item(Item::Task(deleted)).await.unwrap();
+            cal.immediately_delete_item(&deleted_url).await.unwrap();
+
+            // Modified in place, this is written when the cache is dropped
+            match cal.get_item_by_url_mut(&kept_url).await.unwrap().into_mut() {
+                Item::Task(task) => task.set_name(String::from("Attend two concerts of JS Bach")),
+                _ => unreachable!(),
+            }
+            cal.set_sync_token(Some("http://sabre.io/ns/sync/42".to_string()));
+            (kept_url, kept_uid, deleted_url)
+        };
+
+        let cache = SqliteCache::open(&db_path).unwrap();
+        let cal = cache.get_calendar_sync(&cal_url).unwrap();
+        let cal = cal.read().unwrap();
+        assert_eq!(cal.name(), "My bucket list");
+        assert_eq!(cal.sync_token(), Some("http://sabre.io/ns/sync/42"));
+        assert_eq!(cal.item_count(), 1);
+        assert_eq!(cal.get_item_by_url_sync(&kept_url).unwrap().name(), "Attend two concerts of JS Bach");
+        assert!(cal.get_item_by_url_sync(&deleted_url).is_none());
+
+        assert_eq!(cache.find_item_by_uid(&kept_uid).unwrap(), Some((cal_url.clone(), kept_url.clone())));
+        assert_eq!(cache.find_calendar_of(&kept_url).unwrap(), Some(cal_url));
+        assert_eq!(cache.find_item_by_uid("unknown").unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn sqlite_cache_eviction() {
+        let mut cache = SqliteCache::in_memory().unwrap();
+        let cal_url = Url::parse("https://caldav.com/bucket-list").unwrap();
+        let cal = cache.create_calendar(cal_url.clone(), "My bucket list".to_string(), SupportedComponents::TODO, None).await.unwrap();
+        let mut cal = cal.write().unwrap();
+
+        let task = Task::new(String::from("See the Hanging Gardens of Babylon"), false, &cal_url);
+        let task_url = task.url().clone();
+        cal.add_item(Item::Task(task)).await.unwrap();
+        cal.get_item_by_url_mut(&task_url).await.unwrap().set_sync_status(SyncStatus::Synced(VersionTag::from(String::from("v1"))));
+        cal.evict_item(&task_url).unwrap();
+
+        // Evicted items are read from the storage when they are needed, they do not have to be downloaded again
+        assert!(cal.evicted_items().is_empty());
+        assert_eq!(cal.item_count(), 1);
+        assert_eq!(cal.get_item_by_url(&task_url).await.unwrap().name(), "See the Hanging Gardens of Babylon");
+        assert_eq!(cal.query(&ItemQuery::new().with_text("babylon")).len(), 1);
+        assert_eq!(cal.get_item_urls().await.unwrap().len(), 1);
+
+        cal.get_item_by_url_mut(&task_url).await.unwrap().unwrap_task_mut().set_name(String::from("See the Colossus of Rhodes"));
+        assert_eq!(cal.item_count(), 1);
+        assert_eq!(cal.get_item_by_url(&task_url).await.unwrap().name(), "See the Colossus of Rhodes");
+    }
+}
@@ -0,0 +1,252 @@
+//! Where local caches store their data.
+//!
+//! Caches ([`crate::cache::Cache`] and [`crate::persistent_cache::PersistentCache`]) implement the whole [`CompleteCalendar`](crate::traits::CompleteCalendar) contract (sync statuses, rejections, evictions...),
+//! and delegate loading and saving their data to a [`CacheStorage`]. \
+//! Apps that want to store their cache in their own database only have to implement this trait, e.g. `Cache::from_storage(MyStorage::new())`.
+//!
+//! This crate provides these storages:
+//! * [`crate::cache::FolderStorage`], that stores every calendar in a JSON file (this is the default storage of [`crate::cache::Cache`])
+//! * [`MemoryStorage`], that only keeps its data in memory (this is the default storage of [`crate::cache::Cache`] in web browsers)
+//! * [`crate::sqlite_cache::SqliteStorage`], that stores every item in a row of an SQLite database (with the `sqlite` feature)
+//! * [`crate::kv_cache::KvStorage`], that stores every item under its own key in an embedded key-value store (with the `kv` feature)
+//! * [`crate::vdir_cache::VdirStorage`], that stores every item in its own `.ics` file, in the layout of vdirsyncer
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt::Debug;
+
+use csscolorparser::Color;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::calendar::{Privileges, SupportedComponents};
+use crate::item::VersionTag;
+use crate::error::Rejection;
+use crate::Item;
+
+/// A calendar, as it is stored (without its items)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CalendarRecord {
+    pub url: Url,
+    pub name: String,
+    pub supported_components: SupportedComponents,
+    pub color: Option<Color>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub order: Option<i32>,
+    /// Whether the name, the description, the color or the order have been changed since the last sync
+    #[serde(default)]
+    pub properties_modified: bool,
+    #[serde(default)]
+    pub privileges: Privileges,
+    #[serde(default)]
+    pub sync_token: Option<String>,
+}
+
+/// An item, as it is stored
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum ItemRecord {
+    /// An item, and why the server refused it (if it did)
+    Item { item: Item, rejection: Option<Rejection> },
+    /// An item whose content has been evicted (see [`CompleteCalendar::evict_item`](crate::traits::CompleteCalendar::evict_item))
+    Evicted { url: Url, version_tag: VersionTag },
+}
+
+impl ItemRecord {
+    pub fn url(&self) -> &Url {
+        match self {
+            ItemRecord::Item { item, .. } => item.url(),
+            ItemRecord::Evicted { url, .. } => url,
+        }
+    }
+}
+
+/// A change to write to a storage
+#[derive(Clone, Debug)]
+pub enum ItemChange {
+    /// The item has been added or updated (or evicted)
+    Saved(ItemRecord),
+    /// The item has been deleted
+    Removed(Url),
+}
+
+impl ItemChange {
+    pub fn url(&self) -> &Url {
+        match self {
+            ItemChange::Saved(record) => record.url(),
+            ItemChange::Removed(url) => url,
+        }
+    }
+}
+
+/// Where a cache stores its data (see the [module documentation](crate::storage))
+pub trait CacheStorage: Debug + Send {
+    /// Returns every calendar of this storage (without their items)
+    fn load_calendars(&mut self) -> Result<Vec<CalendarRecord>, Box<dyn Error>>;
+
+    /// Returns every item of a calendar
+    fn load_items(&mut self, calendar_url: &Url) -> Result<Vec<ItemRecord>, Box<dyn Error>>;
+
+    /// Add a calendar, or update its properties (this does not change its items)
+    fn save_calendar(&mut self, calendar: &CalendarRecord) -> Result<(), Box<dyn Error>>;
+
+    /// Remove a calendar and all of its items. Removing a calendar that is not stored is not an error
+    fn delete_calendar(&mut self, calendar_url: &Url) -> Result<(), Box<dyn Error>>;
+
+    /// Write changes to the items of a calendar. \
+    /// They should be written all at once (e.g. in a single transaction), so that an interrupted write never leaves only some of them written
+    fn save_items(&mut self, calendar_url: &Url, changes: Vec<ItemChange>) -> Result<(), Box<dyn Error>>;
+
+    /// Add or update a calendar, and replace all of its items.
+    ///
+    /// The default implementation saves the calendar, then removes the items that are no longer in it and saves the other ones.
+    /// Storages that can write a whole calendar at once should rather override it
+    fn replace_calendar(&mut self, calendar: &CalendarRecord, items: Vec<ItemRecord>) -> Result<(), Box<dyn Error>> {
+        self.save_calendar(calendar)?;
+
+        let kept: HashSet<&Url> = items.iter().map(|item| item.url()).collect();
+        let mut changes: Vec<ItemChange> = self.load_items(&calendar.url)?
+            .iter()
+            .filter(|stored| kept.contains(stored.url()) == false)
+            .map(|stored| ItemChange::Removed(stored.url().clone()))
+            .collect();
+        changes.extend(items.iter().cloned().map(ItemChange::Saved));
+        self.save_items(&calendar.url, changes)
+    }
+
+    /// Returns an item of a calendar, or `None` if it is not stored (or if only its version tag is, see [`ItemRecord::Evicted`])
+    ///
+    /// The default implementation loads every item of the calendar. Storages that can read a single item should rather override it
+    fn load_item(&mut self, calendar_url: &Url, item_url: &Url) -> Result<Option<Item>, Box<dyn Error>> {
+        Ok(self.load_items(calendar_url)?.into_iter()
+            .find_map(|record| match record {
+                ItemRecord::Item { item, .. } if item.url() == item_url => Some(item),
+                _ => None,
+            }))
+    }
+
+    /// Find an item by its UID. Returns the URL of its calendar and its own URL.
+    ///
+    /// The default implementation loads the items of every calendar. Storages that can look up UIDs faster (e.g. with an index) should rather override it
+    fn find_item_by_uid(&mut self, uid: &str) -> Result<Option<(Url, Url)>, Box<dyn Error>> {
+        for calendar in self.load_calendars()? {
+            let found = self.load_items(&calendar.url)?.into_iter()
+                .find_map(|record| match record {
+                    ItemRecord::Item { item, .. } if item.uid().as_str() == uid => Some(item.url().clone()),
+                    _ => None,
+                });
+            if let Some(item_url) = found {
+                return Ok(Some((calendar.url, item_url)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns data that is not tied to a calendar (e.g. the sync states or the changelog of a [`crate::cache::Cache`]), or `None` if nothing has been saved under this name.
+    ///
+    /// The default implementation does not store anything. This is not an issue: such data is rebuilt when it is missing (e.g. by a full sync)
+    fn load_metadata(&mut self, _name: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        Ok(None)
+    }
+
+    /// Store data that is not tied to a calendar (see [`CacheStorage::load_metadata`])
+    ///
+    /// The default implementation does not store anything
+    fn save_metadata(&mut self, _name: &str, _data: &[u8]) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+/// A storage that keeps everything in memory, hence that is lost when it is dropped.
+///
+/// This is the default storage of [`crate::cache::Cache`]s in web browsers, and is handy in tests
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStorage {
+    calendars: HashMap<Url, CalendarRecord>,
+    items: HashMap<Url, HashMap<Url, ItemRecord>>,
+    metadata: HashMap<String, Vec<u8>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl CacheStorage for MemoryStorage {
+    fn load_calendars(&mut self) -> Result<Vec<CalendarRecord>, Box<dyn Error>> {
+        Ok(self.calendars.values().cloned().collect())
+    }
+
+    fn load_items(&mut self, calendar_url: &Url) -> Result<Vec<ItemRecord>, Box<dyn Error>> {
+        Ok(self.items.get(calendar_url).map(|items| items.values().cloned().collect()).unwrap_or_default())
+    }
+
+    fn save_calendar(&mut self, calendar: &CalendarRecord) -> Result<(), Box<dyn Error>> {
+        self.calendars.insert(calendar.url.clone(), calendar.clone());
+        Ok(())
+    }
+
+    fn delete_calendar(&mut self, calendar_url: &Url) -> Result<(), Box<dyn Error>> {
+        self.calendars.remove(calendar_url);
+        self.items.remove(calendar_url);
+        Ok(())
+    }
+
+    fn save_items(&mut self, calendar_url: &Url, changes: Vec<ItemChange>) -> Result<(), Box<dyn Error>> {
+        let items = self.items.entry(calendar_url.clone()).or_default();
+        for change in changes {
+            match change {
+                ItemChange::Saved(record) => { items.insert(record.url().clone(), record); },
+                ItemChange::Removed(url) => { items.remove(&url); },
+            }
+        }
+        Ok(())
+    }
+
+    fn load_metadata(&mut self, name: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        Ok(self.metadata.get(name).cloned())
+    }
+
+    fn save_metadata(&mut self, name: &str, data: &[u8]) -> Result<(), Box<dyn Error>> {
+        self.metadata.insert(name.to_string(), data.to_vec());
+        Ok(())
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::cache::Cache;
+    use crate::traits::{BaseCalendar, CalDavSource};
+    use crate::task::Task;
+
+    #[tokio::test]
+    async fn test_custom_storage() {
+        let cal_url = Url::parse("https://caldav.com/shopping").unwrap();
+        let mut cache = Cache::with_storage(MemoryStorage::default());
+        let cal = cache.create_calendar(cal_url.clone(), "My shopping list".to_string(), SupportedComponents::TODO, None).await.unwrap();
+        let deleted_url = {
+            let mut cal = cal.write().unwrap();
+            cal.add_item(Item::Task(Task::new(String::from("Milk"), false, &cal_url))).await.unwrap();
+            let deleted = Task::new(String::from("Eggs"), false, &cal_url);
+            let deleted_url = deleted.url().clone();
+            cal.add_item(Item::Task(deleted)).await.unwrap();
+            deleted_url
+        };
+        cache.save().unwrap();
+        assert_eq!(cache.storage().lock().unwrap().items[&cal_url].len(), 2);
+
+        // Items that have been deleted since the last save are removed from the storage
+        cal.write().unwrap().immediately_delete_item_sync(&deleted_url).unwrap();
+        cache.save().unwrap();
+        let storage = cache.storage().lock().unwrap().clone();
+        assert_eq!(storage.items[&cal_url].len(), 1);
+
+        let retrieved_cache = Cache::from_storage(storage).unwrap();
+        assert!(cache.has_same_observable_content_as(&retrieved_cache).await.unwrap());
+    }
+}
@@ -1,24 +1,34 @@
 //! To-do tasks (iCal `VTODO` item)
 
+use std::error::Error;
+
 use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
 use ical::property::Property;
 use serde::{Deserialize, Serialize};
 use url::Url;
-use uuid::Uuid;
 
-use crate::item::SyncStatus;
-use crate::utils::random_url;
+use crate::item::{Classification, SyncStatus, Uid};
+use crate::alarm::Alarm;
+use crate::attachment::Attachment;
+use crate::calendar::CalendarUrl;
+use crate::date_time::IcalDateTime;
 
 /// RFC5545 defines the completion as several optional fields, yet some combinations make no sense.
 /// This enum provides an API that forbids such impossible combinations.
 ///
 /// * `COMPLETED` is an optional timestamp that tells whether this task is completed
-/// * `STATUS` is an optional field, that can be set to `NEEDS-ACTION`, `COMPLETED`, or others.
+/// * `STATUS` is an optional field, that can be set to `NEEDS-ACTION`, `IN-PROCESS`, `COMPLETED` or `CANCELLED`.
 /// Even though having a `COMPLETED` date but a `STATUS:NEEDS-ACTION` is theorically possible, it obviously makes no sense. This API ensures this cannot happen
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum CompletionStatus {
     Completed(Option<DateTime<Utc>>),
+    /// `NEEDS-ACTION` (this is also how tasks that have no `STATUS` are read)
     Uncompleted,
+    /// `IN-PROCESS`, i.e. work on this task has started, but it is not completed yet
+    InProcess,
+    /// `CANCELLED`. Cancelled tasks are not completed
+    Cancelled,
 }
 impl CompletionStatus {
     pub fn is_completed(&self) -> bool {
@@ -27,6 +37,60 @@ impl CompletionStatus {
             _ => false,
         }
     }
+
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self, CompletionStatus::Cancelled)
+    }
+
+    /// The value of the `STATUS` property for this status
+    pub fn as_ical_str(&self) -> &'static str {
+        match self {
+            CompletionStatus::Completed(_) => "COMPLETED",
+            CompletionStatus::Uncompleted => "NEEDS-ACTION",
+            CompletionStatus::InProcess => "IN-PROCESS",
+            CompletionStatus::Cancelled => "CANCELLED",
+        }
+    }
+}
+
+/// Time spent on a task, for time-tracking apps.
+///
+/// This is not part of RFC5545, it is stored in X-properties (`X-TIMER-STARTED` and `X-TIME-SPENT`) that other clients preserve
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TimeTracking {
+    /// When the currently running timer has been started (if any)
+    started_at: Option<DateTime<Utc>>,
+    /// The time spent during the previous (i.e. stopped) timers, in seconds
+    total_seconds: i64,
+}
+
+impl TimeTracking {
+    pub fn new(started_at: Option<DateTime<Utc>>, total_seconds: i64) -> Self {
+        Self { started_at, total_seconds }
+    }
+
+    pub fn started_at(&self) -> Option<&DateTime<Utc>> {
+        self.started_at.as_ref()
+    }
+    pub fn is_running(&self) -> bool {
+        self.started_at.is_some()
+    }
+    /// The time spent during the previous (i.e. stopped) timers
+    pub fn total_duration(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.total_seconds)
+    }
+    /// The time spent on this task, including the currently running timer (if any)
+    pub fn elapsed_at(&self, now: DateTime<Utc>) -> chrono::Duration {
+        let running = match self.started_at {
+            Some(start) if start < now => now - start,
+            _ => chrono::Duration::zero(),
+        };
+        self.total_duration() + running
+    }
+    /// Whether there is nothing to track (in which case no X-property is needed)
+    pub fn is_empty(&self) -> bool {
+        self.started_at.is_none() && self.total_seconds == 0
+    }
 }
 
 /// A to-do task
@@ -38,7 +102,7 @@ pub struct Task {
     /// Persistent, globally unique identifier for the calendar component
     /// The [RFC](https://tools.ietf.org/html/rfc5545#page-117) recommends concatenating a timestamp with the server's domain name.
     /// UUID are even better so we'll generate them, but we have to support tasks from the server, that may have any arbitrary strings here.
-    uid: String,
+    uid: Uid,
 
     /// The sync status of this item
     sync_status: SyncStatus,
@@ -49,13 +113,64 @@ pub struct Task {
     last_modified: DateTime<Utc>,
     /// The completion status of this task
     completion_status: CompletionStatus,
+    /// Whether the completion date is a floating date-time, i.e. written in local time without any TZID
+    #[serde(default)]
+    floating_completion: bool,
+    /// PERCENT-COMPLETE of uncompleted tasks (completed tasks are always 100% complete)
+    #[serde(default)]
+    percent_complete: Option<u8>,
 
     /// The display name of the task
     name: String,
 
+    /// The UID of the task this one is a subtask of (`RELATED-TO`, with the default `RELTYPE=PARENT`)
+    #[serde(default)]
+    parent: Option<Uid>,
+
+    /// GEO, i.e. (latitude, longitude) in degrees
+    #[serde(default)]
+    geo: Option<(f64, f64)>,
+
+    /// CLASS
+    #[serde(default)]
+    class: Option<Classification>,
+
+    /// PRIORITY, from 1 (highest) to 9 (lowest). `None` means undefined (`PRIORITY:0` in iCal files)
+    #[serde(default)]
+    priority: Option<u8>,
+
+    /// DUE, i.e. when this task should be completed
+    #[serde(default)]
+    due: Option<DateTime<Utc>>,
+    /// Whether DUE is a date rather than a date-time (in which case it is stored at midnight UTC)
+    #[serde(default)]
+    all_day_due: bool,
+    /// The TZID DUE is written in, in case it is not written in UTC
+    #[serde(default)]
+    due_tzid: Option<String>,
+    /// Whether DUE is a floating date-time, i.e. written in local time without any TZID
+    #[serde(default)]
+    floating_due: bool,
+
     /// The PRODID, as defined in iCal files
     ical_prod_id: String,
 
+    /// The reminders (VALARM components) of this item
+    #[serde(default)]
+    alarms: Vec<Alarm>,
+
+    /// ATTACH, i.e. documents attached to this item
+    #[serde(default)]
+    attachments: Vec<Attachment>,
+
+    /// CATEGORIES, i.e. tags of this item
+    #[serde(default)]
+    categories: Vec<String>,
+
+    /// Time spent on this task (opt-in: it stays empty unless a timer is started)
+    #[serde(default)]
+    time_tracking: TimeTracking,
+
     /// Extra parameters that have not been parsed from the iCal file (because they're not supported (yet) by this crate).
     /// They are needed to serialize this item into an equivalent iCal file
     extra_parameters: Vec<Property>,
@@ -64,66 +179,77 @@ pub struct Task {
 impl Task {
     /// Create a brand new Task that is not on a server yet.
     /// This will pick a new (random) task ID.
+    ///
+    /// See [`TaskBuilder`] to set more fields
     pub fn new(name: String, completed: bool, parent_calendar_url: &Url) -> Self {
-        let new_url = random_url(parent_calendar_url);
-        let new_sync_status = SyncStatus::NotSynced;
-        let new_uid = Uuid::new_v4().to_hyphenated().to_string();
-        let new_creation_date = Some(Utc::now());
-        let new_last_modified = Utc::now();
-        let new_completion_status = if completed {
+        let completion_status = if completed {
             CompletionStatus::Completed(Some(Utc::now()))
         } else {
             CompletionStatus::Uncompleted
         };
-        let ical_prod_id = crate::ical::default_prod_id();
-        let extra_parameters = Vec::new();
-        Self::new_with_parameters(
-            name,
-            new_uid,
-            new_url,
-            new_completion_status,
-            new_sync_status,
-            new_creation_date,
-            new_last_modified,
-            ical_prod_id,
-            extra_parameters,
-        )
-    }
-
-    /// Create a new Task instance, that may be synced on the server already
-    pub fn new_with_parameters(
-        name: String,
-        uid: String,
-        new_url: Url,
-        completion_status: CompletionStatus,
-        sync_status: SyncStatus,
-        creation_date: Option<DateTime<Utc>>,
-        last_modified: DateTime<Utc>,
-        ical_prod_id: String,
-        extra_parameters: Vec<Property>,
-    ) -> Self {
-        Self {
-            url: new_url,
-            uid,
-            name,
-            completion_status,
-            sync_status,
-            creation_date,
-            last_modified,
-            ical_prod_id,
-            extra_parameters,
-        }
+        TaskBuilder::new(name, parent_calendar_url)
+            .with_completion_status(completion_status)
+            .build_unchecked()
     }
 
     pub fn url(&self) -> &Url {
         &self.url
     }
-    pub fn uid(&self) -> &str {
+    pub fn uid(&self) -> &Uid {
         &self.uid
     }
     pub fn name(&self) -> &str {
         &self.name
     }
+    /// The UID of the task this one is a subtask of (if any)
+    pub fn parent(&self) -> Option<&Uid> {
+        self.parent.as_ref()
+    }
+    /// The (latitude, longitude) of this task, in degrees
+    pub fn geo(&self) -> Option<(f64, f64)> {
+        self.geo
+    }
+    /// The access classification of this task (`None` means public)
+    pub fn classification(&self) -> Option<&Classification> {
+        self.class.as_ref()
+    }
+    /// Whether this task is private or confidential, i.e. whether apps should not show its details to other people than its owner
+    pub fn is_restricted(&self) -> bool {
+        self.class.as_ref().map(Classification::is_restricted).unwrap_or(false)
+    }
+    pub fn priority(&self) -> Option<u8> {
+        self.priority
+    }
+    pub fn due(&self) -> Option<&DateTime<Utc>> {
+        self.due.as_ref()
+    }
+    pub fn is_due_all_day(&self) -> bool {
+        self.all_day_due
+    }
+    /// The TZID the due date is written in (e.g. `Europe/Paris`), in case it is not written in UTC
+    pub fn due_tzid(&self) -> Option<&str> {
+        self.due_tzid.as_deref()
+    }
+    /// Whether the due date is a floating date-time, i.e. that means the same wall-clock time whatever the timezone of the user. \
+    /// In this case, [`Self::due`] is this wall-clock time, read as UTC
+    pub fn is_due_floating(&self) -> bool {
+        self.floating_due
+    }
+    /// The due date, as it is written in the iCal file. This makes no sense for all-day due dates
+    pub fn due_date_time(&self) -> Option<IcalDateTime> {
+        let due = self.due.as_ref()?;
+        Some(match (&self.due_tzid, self.floating_due) {
+            (_, true) => IcalDateTime::Floating(due.naive_utc()),
+            (Some(tzid), false) => {
+                let date_time = match crate::ical::lookup_timezone(tzid) {
+                    Some(tz) => due.with_timezone(&tz).naive_local(),
+                    None => due.naive_utc(),
+                };
+                IcalDateTime::Local { date_time, tzid: tzid.clone() }
+            },
+            (None, false) => IcalDateTime::Utc(*due),
+        })
+    }
     pub fn completed(&self) -> bool {
         self.completion_status.is_completed()
     }
@@ -142,19 +268,53 @@ impl Task {
     pub fn completion_status(&self) -> &CompletionStatus {
         &self.completion_status
     }
+    /// Whether the completion date is a floating date-time (see [`Self::is_due_floating`])
+    pub fn is_completion_floating(&self) -> bool {
+        self.floating_completion
+    }
+    /// How much of this task has been done, in percent. Completed tasks are always 100% complete
+    pub fn percent_complete(&self) -> u8 {
+        match self.completion_status {
+            CompletionStatus::Completed(_) => 100,
+            _ => self.percent_complete.unwrap_or(0),
+        }
+    }
+    /// The PERCENT-COMPLETE value of an uncompleted task, as it is written in the iCal file (if any)
+    pub fn raw_percent_complete(&self) -> Option<u8> {
+        self.percent_complete
+    }
     pub fn extra_parameters(&self) -> &[Property] {
         &self.extra_parameters
     }
+    pub fn alarms(&self) -> &[Alarm] {
+        &self.alarms
+    }
+    pub fn time_tracking(&self) -> &TimeTracking {
+        &self.time_tracking
+    }
 
     #[cfg(any(test, feature = "integration_tests"))]
     pub fn has_same_observable_content_as(&self, other: &Task) -> bool {
         self.url == other.url
         && self.uid == other.uid
         && self.name == other.name
+        && self.priority == other.priority
+        && self.parent == other.parent
+        && self.geo == other.geo
+        && self.class == other.class
+        && self.due == other.due
+        && self.all_day_due == other.all_day_due
+        && self.due_tzid == other.due_tzid
+        && self.floating_due == other.floating_due
         // sync status must be the same variant, but we ignore its embedded version tag
         && std::mem::discriminant(&self.sync_status) == std::mem::discriminant(&other.sync_status)
+        && self.alarms == other.alarms
+        && self.attachments == other.attachments
+        && self.categories == other.categories
+        && self.time_tracking == other.time_tracking
         // completion status must be the same variant, but we ignore its embedded completion date (they are not totally mocked in integration tests)
         && std::mem::discriminant(&self.completion_status) == std::mem::discriminant(&other.completion_status)
+        && self.percent_complete() == other.percent_complete()
         // last modified dates are ignored (they are not totally mocked in integration tests)
     }
 
@@ -162,6 +322,16 @@ impl Task {
         self.sync_status = new_status;
     }
 
+    /// Change the URL of this item, e.g. because it has been moved on the server
+    pub(crate) fn set_url(&mut self, new_url: Url) {
+        self.url = new_url;
+    }
+
+    /// Change the UID of this item, e.g. to make it a distinct copy
+    pub(crate) fn set_uid(&mut self, new_uid: Uid) {
+        self.uid = new_uid;
+    }
+
     fn update_sync_status(&mut self) {
         match &self.sync_status {
             SyncStatus::NotSynced => return,
@@ -176,10 +346,23 @@ impl Task {
         }
     }
 
-    fn update_last_modified(&mut self) {
+    pub(crate) fn update_last_modified(&mut self) {
         self.last_modified = Utc::now();
     }
 
+    /// Drop what a deleted task does not need any more (see [`Item::drop_content`](crate::item::Item::drop_content))
+    pub(crate) fn drop_content(&mut self) -> bool {
+        let had_content = self.geo.is_some() || self.alarms.is_empty() == false || self.attachments.is_empty() == false
+            || self.categories.is_empty() == false || self.time_tracking != TimeTracking::default() || self.extra_parameters.is_empty() == false;
+        self.geo = None;
+        self.alarms.clear();
+        self.attachments.clear();
+        self.categories.clear();
+        self.time_tracking = TimeTracking::default();
+        self.extra_parameters.clear();
+        had_content
+    }
+
     /// Rename a task.
     /// This updates its "last modified" field
     pub fn set_name(&mut self, new_name: String) {
@@ -195,11 +378,275 @@ impl Task {
         self.name = new_name;
     }
 
-    /// Set the completion status
+    /// Make this task a subtask of another one (given its UID), or a top-level task.
+    /// This updates its "last modified" field
+    pub fn set_parent(&mut self, new_parent: Option<Uid>) {
+        self.update_sync_status();
+        self.update_last_modified();
+        self.parent = new_parent;
+    }
+
+    /// Set (or remove) the (latitude, longitude) of this task, in degrees.
+    /// This updates its "last modified" field
+    pub fn set_geo(&mut self, new_geo: Option<(f64, f64)>) -> Result<(), Box<dyn Error>> {
+        if let Some((latitude, longitude)) = new_geo {
+            crate::utils::check_geo_position(latitude, longitude)?;
+        }
+        self.update_sync_status();
+        self.update_last_modified();
+        // An invalid GEO may have been kept as is, it is now superseded
+        self.extra_parameters.retain(|prop| prop.name != "GEO");
+        self.geo = new_geo;
+        Ok(())
+    }
+
+    /// The first property named `name` (case-insensitive) among the ones this crate does not handle, e.g. a custom `X-` property.
+    /// Its value is as it is written in the iCal file, i.e. TEXT values are still escaped
+    pub fn get_property(&self, name: &str) -> Option<&Property> {
+        self.extra_parameters.iter().find(|prop| prop.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Set a custom property (e.g. `X-MY-APP-COLOR`), replacing the ones with the same name.
+    /// `value` is written as is, so TEXT values must be escaped (see [`escape_text`](crate::ical::values::escape_text)), and it must not contain line breaks.
+    /// Parameter values are quoted when needed. This updates its "last modified" field
+    pub fn set_property(&mut self, name: String, value: String, params: Vec<(String, Vec<String>)>) -> Result<(), Box<dyn Error>> {
+        crate::utils::check_custom_property_name(&name)?;
+        crate::utils::check_custom_property_value(&value, &params)?;
+        if name.eq_ignore_ascii_case("X-TIMER-STARTED") || name.eq_ignore_ascii_case("X-TIME-SPENT") {
+            return Err(format!("{} is handled by the time tracking of tasks, see Task::start_timer", name).into());
+        }
+        self.update_sync_status();
+        self.update_last_modified();
+        self.extra_parameters.retain(|prop| prop.name.eq_ignore_ascii_case(&name) == false);
+        self.extra_parameters.push(Property {
+            name: name.to_ascii_uppercase(),
+            params: if params.is_empty() { None } else { Some(params) },
+            value: Some(value),
+        });
+        Ok(())
+    }
+
+    /// Remove the properties named `name` (case-insensitive) among the ones this crate does not handle, and return them.
+    /// This updates its "last modified" field, unless there was none
+    pub fn remove_property(&mut self, name: &str) -> Vec<Property> {
+        let (removed, kept): (Vec<Property>, Vec<Property>) = std::mem::take(&mut self.extra_parameters)
+            .into_iter()
+            .partition(|prop| prop.name.eq_ignore_ascii_case(name));
+        self.extra_parameters = kept;
+        if removed.is_empty() == false {
+            self.update_sync_status();
+            self.update_last_modified();
+        }
+        removed
+    }
+
+    /// Set (or remove) the access classification of this task.
+    /// This updates its "last modified" field
+    pub fn set_classification(&mut self, new_class: Option<Classification>) {
+        self.update_sync_status();
+        self.update_last_modified();
+        self.class = new_class;
+    }
+
+    /// Returns the (direct and indirect) subtasks of this task, among a set of tasks (usually the other tasks of its calendar)
+    pub fn subtasks<'a, I>(&self, tasks: I) -> Vec<&'a Task>
+    where
+        I: IntoIterator<Item = &'a Task>
+    {
+        let tasks: Vec<&Task> = tasks.into_iter().collect();
+        let indices = subtask_indices(&self.uid, tasks.iter().map(|t| (&t.uid, t.parent.as_ref())));
+        indices.into_iter().map(|i| tasks[i]).collect()
+    }
+
+    /// Mark this task as completed, as well as its (direct and indirect) subtasks that are not completed yet.
+    /// `tasks` is where subtasks are looked for (usually the other tasks of its calendar).
+    /// This returns the URLs of the subtasks that have been completed, and updates their "last modified" fields
+    pub fn complete_with_children<'a, I>(&mut self, tasks: I) -> Vec<Url>
+    where
+        I: IntoIterator<Item = &'a mut Task>
+    {
+        let mut tasks: Vec<&mut Task> = tasks.into_iter().collect();
+        let indices = subtask_indices(&self.uid, tasks.iter().map(|t| (&t.uid, t.parent.as_ref())));
+
+        let completion_date = Utc::now();
+        let mut completed = Vec::new();
+        for i in indices {
+            let subtask = &mut tasks[i];
+            if subtask.completed() == false {
+                subtask.set_completion_status(CompletionStatus::Completed(Some(completion_date)));
+                completed.push(subtask.url.clone());
+            }
+        }
+        if self.completed() == false {
+            self.set_completion_status(CompletionStatus::Completed(Some(completion_date)));
+        }
+        completed
+    }
+
+    /// Set the priority of a task, from 1 (highest) to 9 (lowest), or `None` to make it undefined.
+    /// This updates its "last modified" field
+    pub fn set_priority(&mut self, new_priority: Option<u8>) -> Result<(), Box<dyn Error>> {
+        if let Some(p) = new_priority {
+            if (1..=9).contains(&p) == false {
+                return Err(format!("Invalid priority {}, it must be between 1 and 9", p).into());
+            }
+        }
+        self.update_sync_status();
+        self.update_last_modified();
+        // An invalid PRIORITY may have been kept as is, it is now superseded
+        self.extra_parameters.retain(|prop| prop.name != "PRIORITY");
+        self.priority = new_priority;
+        Ok(())
+    }
+
+    /// Set (or remove) the due date of a task. `all_day` tells whether only the date of `new_due` matters.
+    /// The due date keeps its timezone (if any), but is no longer floating.
+    /// This updates its "last modified" field
+    pub fn set_due(&mut self, new_due: Option<DateTime<Utc>>, all_day: bool) {
+        self.update_sync_status();
+        self.update_last_modified();
+        // "Either "DUE" or "DURATION" MAY appear in a "VTODO", but "DUE" and "DURATION" MUST NOT occur in the same "VTODO""
+        self.extra_parameters.retain(|prop| prop.name != "DURATION");
+        self.due = new_due;
+        self.all_day_due = new_due.is_some() && all_day;
+        self.floating_due = false;
+    }
+
+    /// Replace the reminders of this task.
+    /// This updates its "last modified" field
+    pub fn set_alarms(&mut self, new_alarms: Vec<Alarm>) {
+        self.update_sync_status();
+        self.update_last_modified();
+        self.alarms = new_alarms;
+    }
+
+    pub fn categories(&self) -> &[String] {
+        &self.categories
+    }
+
+    /// Add a category to this item (if it does not have it already).
+    /// This updates its "last modified" field
+    pub fn add_category(&mut self, category: String) {
+        if self.categories.contains(&category) {
+            return;
+        }
+        self.update_sync_status();
+        self.update_last_modified();
+        self.categories.push(category);
+    }
+
+    /// Remove a category from this item, and return whether it had it.
+    /// This updates its "last modified" field
+    pub fn remove_category(&mut self, category: &str) -> bool {
+        if self.categories.iter().any(|c| c == category) == false {
+            return false;
+        }
+        self.update_sync_status();
+        self.update_last_modified();
+        self.categories.retain(|c| c != category);
+        true
+    }
+
+    pub fn attachments(&self) -> &[Attachment] {
+        &self.attachments
+    }
+
+    /// Attach a document to this item.
+    /// This updates its "last modified" field
+    pub fn add_attachment(&mut self, attachment: Attachment) {
+        self.update_sync_status();
+        self.update_last_modified();
+        self.attachments.push(attachment);
+    }
+
+    /// Remove an attachment from this item, and return whether it had it.
+    /// This updates its "last modified" field
+    pub fn remove_attachment(&mut self, attachment: &Attachment) -> bool {
+        if self.attachments.contains(attachment) == false {
+            return false;
+        }
+        self.update_sync_status();
+        self.update_last_modified();
+        self.attachments.retain(|a| a != attachment);
+        true
+    }
+
+    /// Start a time-tracking timer, and return `false` if one was already running.
+    /// This updates its "last modified" field
+    pub fn start_timer(&mut self) -> bool {
+        if self.time_tracking.is_running() {
+            return false;
+        }
+        self.update_sync_status();
+        self.update_last_modified();
+        self.time_tracking.started_at = Some(Utc::now());
+        true
+    }
+
+    /// Stop the running time-tracking timer, add its duration to the total time spent, and return this duration (or `None` if no timer was running).
+    /// This updates its "last modified" field
+    pub fn stop_timer(&mut self) -> Option<chrono::Duration> {
+        let started_at = self.time_tracking.started_at?;
+        self.update_sync_status();
+        self.update_last_modified();
+        let elapsed = std::cmp::max(Utc::now() - started_at, chrono::Duration::zero());
+        self.time_tracking.started_at = None;
+        self.time_tracking.total_seconds += elapsed.num_seconds();
+        Some(elapsed)
+    }
+
+    /// Pin the floating due and completion dates of this task (see [`Self::is_due_floating`]) to a timezone, so that they keep their wall-clock time in this timezone only.
+    /// This has no effect on dates that are not floating.
+    /// This updates its "last modified" field
+    pub fn resolve_floating(&mut self, timezone: &Tz) {
+        if self.floating_due == false && self.floating_completion == false {
+            return;
+        }
+        self.update_sync_status();
+        self.update_last_modified();
+        if self.floating_due {
+            self.due = self.due_date_time().map(|due| due.resolve(timezone));
+            self.due_tzid = Some(timezone.name().to_string());
+            self.floating_due = false;
+        }
+        if self.floating_completion {
+            if let CompletionStatus::Completed(Some(completion_date)) = &mut self.completion_status {
+                *completion_date = IcalDateTime::Floating(completion_date.naive_utc()).resolve(timezone);
+            }
+            // RFC5545 requires completion dates to be written in UTC
+            self.floating_completion = false;
+        }
+    }
+
+    /// Set how much of this task has been done, in percent (values over 100 are capped).
+    /// Setting it to 100 marks the task as completed, setting it to less than 100 marks a completed task as uncompleted (other statuses are kept).
+    /// This updates its "last modified" field
+    pub fn set_percent_complete(&mut self, new_percent_complete: u8) {
+        self.update_sync_status();
+        self.update_last_modified();
+        let new_percent_complete = std::cmp::min(new_percent_complete, 100);
+        if new_percent_complete == 100 {
+            if self.completion_status.is_completed() == false {
+                self.completion_status = CompletionStatus::Completed(Some(Utc::now()));
+                self.floating_completion = false;
+            }
+            self.percent_complete = None;
+        } else {
+            if self.completion_status.is_completed() {
+                self.completion_status = CompletionStatus::Uncompleted;
+            }
+            self.percent_complete = Some(new_percent_complete);
+        }
+    }
+
+    /// Set the completion status.
+    /// Un-completing a task resets its percent-complete
     pub fn set_completion_status(&mut self, new_completion_status: CompletionStatus) {
         self.update_sync_status();
         self.update_last_modified();
+        self.percent_complete = None;
         self.completion_status = new_completion_status;
+        self.floating_completion = false;
     }
     #[cfg(feature = "local_calendar_mocks_remote_calendars")]
     /// Set the completion status, but forces a "master" SyncStatus, just like CalDAV servers are always "masters"
@@ -208,6 +655,236 @@ impl Task {
         new_completion_status: CompletionStatus,
     ) {
         self.sync_status = SyncStatus::random_synced();
+        self.percent_complete = None;
         self.completion_status = new_completion_status;
+        self.floating_completion = false;
+    }
+}
+
+/// Returns the indices of the (direct and indirect) children of `root`, given the `(uid, parent uid)` of a set of tasks.
+/// Tasks that are (wrongly) their own ancestors are only visited once
+fn subtask_indices<'a, I>(root: &Uid, relations: I) -> Vec<usize>
+where
+    I: Iterator<Item = (&'a Uid, Option<&'a Uid>)>
+{
+    let relations: Vec<(&Uid, Option<&Uid>)> = relations.collect();
+    let mut visited = vec![false; relations.len()];
+    let mut found = Vec::new();
+    let mut to_visit = vec![root];
+    while let Some(parent_uid) = to_visit.pop() {
+        for (i, (uid, parent)) in relations.iter().enumerate() {
+            if visited[i] == false && *parent == Some(parent_uid) && *uid != root {
+                visited[i] = true;
+                found.push(i);
+                to_visit.push(*uid);
+            }
+        }
+    }
+    found
+}
+
+
+
+/// A builder for [`Task`]s, including tasks that are already on a server.
+///
+/// Fields that are not set get the same default values as in [`Task::new`]
+#[derive(Clone, Debug)]
+pub struct TaskBuilder {
+    name: String,
+    url: Url,
+    uid: Option<Uid>,
+    completion_status: CompletionStatus,
+    floating_completion: bool,
+    sync_status: SyncStatus,
+    creation_date: Option<DateTime<Utc>>,
+    last_modified: Option<DateTime<Utc>>,
+    ical_prod_id: Option<String>,
+    alarms: Vec<Alarm>,
+    attachments: Vec<Attachment>,
+    priority: Option<u8>,
+    due: Option<(DateTime<Utc>, bool)>,
+    due_tzid: Option<String>,
+    floating_due: bool,
+    percent_complete: Option<u8>,
+    parent: Option<Uid>,
+    geo: Option<(f64, f64)>,
+    class: Option<Classification>,
+    categories: Vec<String>,
+    time_tracking: TimeTracking,
+    extra_parameters: Vec<Property>,
+}
+
+impl TaskBuilder {
+    /// Start building a task that is not on a server yet (a random URL is picked in the given calendar)
+    pub fn new(name: String, parent_calendar_url: &Url) -> Self {
+        Self {
+            name,
+            url: CalendarUrl::from(parent_calendar_url.clone()).random_item_url(),
+            uid: None,
+            completion_status: CompletionStatus::Uncompleted,
+            floating_completion: false,
+            sync_status: SyncStatus::NotSynced,
+            creation_date: Some(Utc::now()),
+            last_modified: None,
+            ical_prod_id: None,
+            alarms: Vec::new(),
+            attachments: Vec::new(),
+            priority: None,
+            due: None,
+            due_tzid: None,
+            floating_due: false,
+            percent_complete: None,
+            parent: None,
+            geo: None,
+            class: None,
+            categories: Vec::new(),
+            time_tracking: TimeTracking::default(),
+            extra_parameters: Vec::new(),
+        }
+    }
+
+    pub fn with_url(mut self, url: Url) -> Self { self.url = url; self }
+    pub fn with_uid(mut self, uid: Uid) -> Self { self.uid = Some(uid); self }
+    pub fn with_completion_status(mut self, completion_status: CompletionStatus) -> Self { self.completion_status = completion_status; self }
+    /// Make the completion date a floating date-time (see [`Task::is_completion_floating`]). It is then a wall-clock time, read as UTC
+    pub fn floating_completion(mut self) -> Self { self.floating_completion = true; self }
+    pub fn with_sync_status(mut self, sync_status: SyncStatus) -> Self { self.sync_status = sync_status; self }
+    pub fn with_creation_date(mut self, creation_date: Option<DateTime<Utc>>) -> Self { self.creation_date = creation_date; self }
+    pub fn with_last_modified(mut self, last_modified: DateTime<Utc>) -> Self { self.last_modified = Some(last_modified); self }
+    pub fn with_ical_prod_id(mut self, ical_prod_id: String) -> Self { self.ical_prod_id = Some(ical_prod_id); self }
+    pub fn with_alarm(mut self, alarm: Alarm) -> Self { self.alarms.push(alarm); self }
+    pub fn with_attachment(mut self, attachment: Attachment) -> Self { self.attachments.push(attachment); self }
+    /// Set the priority, from 1 (highest) to 9 (lowest)
+    pub fn with_priority(mut self, priority: u8) -> Self { self.priority = Some(priority); self }
+    /// Set the due date. `all_day` tells whether only the date of `due` matters
+    pub fn with_due(mut self, due: DateTime<Utc>, all_day: bool) -> Self { self.due = Some((due, all_day)); self }
+    /// Write the due date in a given timezone (see [`Task::due_tzid`])
+    pub fn with_due_timezone(mut self, timezone: Tz) -> Self { self.due_tzid = Some(timezone.name().to_string()); self }
+    /// Keep whatever TZID a server wrote the due date in, even if it is not an IANA timezone name
+    pub(crate) fn with_due_tzid(mut self, tzid: String) -> Self { self.due_tzid = Some(tzid); self }
+    /// Make the due date a floating date-time (see [`Task::is_due_floating`]). `due` is then a wall-clock time, read as UTC
+    pub fn floating_due(mut self) -> Self { self.floating_due = true; self }
+    pub fn with_percent_complete(mut self, percent_complete: u8) -> Self { self.percent_complete = Some(percent_complete); self }
+    /// Make this task a subtask of another one (given its UID)
+    pub fn with_parent(mut self, parent: Uid) -> Self { self.parent = Some(parent); self }
+    /// Set the (latitude, longitude) of the task, in degrees
+    pub fn with_geo(mut self, latitude: f64, longitude: f64) -> Self { self.geo = Some((latitude, longitude)); self }
+    pub fn with_classification(mut self, class: Classification) -> Self { self.class = Some(class); self }
+    pub fn with_category(mut self, category: String) -> Self {
+        if self.categories.contains(&category) == false {
+            self.categories.push(category);
+        }
+        self
+    }
+    pub fn with_time_tracking(mut self, time_tracking: TimeTracking) -> Self { self.time_tracking = time_tracking; self }
+    /// Add iCal properties that are not supported by this crate, so that they are written back to the server
+    pub fn with_extra_parameters(mut self, extra_parameters: Vec<Property>) -> Self { self.extra_parameters.extend(extra_parameters); self }
+
+    /// Build the task, checking that its fields are consistent
+    pub fn build(self) -> Result<Task, Box<dyn Error>> {
+        if self.name.trim().is_empty() {
+            return Err("A task must have a non-empty name".into());
+        }
+        if let Some(p) = self.priority {
+            if (1..=9).contains(&p) == false {
+                return Err(format!("Invalid priority {}, it must be between 1 and 9", p).into());
+            }
+        }
+        match self.percent_complete {
+            Some(p) if p >= 100 => return Err(format!("Invalid percent-complete {}, completed tasks should rather be given a completion status", p).into()),
+            Some(_) if self.completion_status.is_completed() => return Err("Completed tasks cannot have a percent-complete".into()),
+            _ => (),
+        }
+        if let Some((latitude, longitude)) = self.geo {
+            crate::utils::check_geo_position(latitude, longitude)?;
+        }
+        if self.floating_due && self.due_tzid.is_some() {
+            return Err("A due date cannot be both floating and written in a timezone".into());
+        }
+        Ok(self.build_unchecked())
+    }
+
+    /// Build the task as is, e.g. because its fields come from a server, that must be trusted
+    pub(crate) fn build_unchecked(self) -> Task {
+        let (due, all_day_due) = match self.due {
+            Some((due, all_day)) => (Some(due), all_day),
+            None => (None, false),
+        };
+
+        Task {
+            url: self.url,
+            uid: self.uid.unwrap_or_else(Uid::random),
+            name: self.name,
+            completion_status: self.completion_status,
+            floating_completion: self.floating_completion,
+            sync_status: self.sync_status,
+            creation_date: self.creation_date,
+            last_modified: self.last_modified.unwrap_or_else(Utc::now),
+            ical_prod_id: self.ical_prod_id.unwrap_or_else(crate::ical::default_prod_id),
+            alarms: self.alarms,
+            attachments: self.attachments,
+            priority: self.priority,
+            due,
+            all_day_due,
+            due_tzid: self.due_tzid,
+            floating_due: self.floating_due,
+            percent_complete: self.percent_complete,
+            parent: self.parent,
+            geo: self.geo,
+            class: self.class,
+            categories: self.categories,
+            time_tracking: self.time_tracking,
+            extra_parameters: self.extra_parameters,
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_task_builder() {
+        let cal_url: Url = "https://some.calend.ar/calendar/".parse().unwrap();
+        let parent = Task::new("Parent".to_string(), false, &cal_url);
+
+        let task = TaskBuilder::new("Child".to_string(), &cal_url)
+            .with_parent(parent.uid().clone())
+            .with_priority(1)
+            .with_percent_complete(30)
+            .build()
+            .unwrap();
+        assert_eq!(task.name(), "Child");
+        assert_eq!(task.parent(), Some(parent.uid()));
+        assert_eq!(task.priority(), Some(1));
+        assert_eq!(task.percent_complete(), 30);
+        assert_eq!(task.completed(), false);
+        assert_ne!(task.uid(), parent.uid());
+
+        assert!(TaskBuilder::new("".to_string(), &cal_url).build().is_err());
+        assert!(TaskBuilder::new("Urgent".to_string(), &cal_url).with_priority(10).build().is_err());
+        assert!(TaskBuilder::new("Done".to_string(), &cal_url)
+            .with_completion_status(CompletionStatus::Completed(None))
+            .with_percent_complete(50)
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn test_custom_properties() {
+        let cal_url: Url = "https://some.calend.ar/calendar/".parse().unwrap();
+        let mut task = Task::new("Write report".to_string(), false, &cal_url);
+
+        task.set_property("X-APPLE-SORT-ORDER".to_string(), "42".to_string(), Vec::new()).unwrap();
+        task.set_property("X-APPLE-SORT-ORDER".to_string(), "43".to_string(), Vec::new()).unwrap();
+        assert_eq!(task.extra_parameters().len(), 1);
+        assert_eq!(task.get_property("x-apple-sort-order").and_then(|prop| prop.value.as_deref()), Some("43"));
+        // Time tracking is not a custom property
+        assert!(task.set_property("X-TIME-SPENT".to_string(), "PT1H".to_string(), Vec::new()).is_err());
+
+        assert_eq!(task.remove_property("X-APPLE-SORT-ORDER").len(), 1);
+        assert!(task.extra_parameters().is_empty());
     }
 }
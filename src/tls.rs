@@ -0,0 +1,82 @@
+//! How the identity of CalDAV servers is checked, and how clients prove theirs (see [`Client::set_tls_config`](crate::client::Client::set_tls_config))
+
+use std::error::Error;
+use std::fmt::{Debug, Formatter};
+
+use reqwest::{Certificate, ClientBuilder, Identity};
+
+/// The TLS settings of a [`Client`](crate::client::Client).
+///
+/// By default, servers must have a certificate that is trusted by the system.
+/// With the `rustls` feature, TLS is provided by [rustls](https://docs.rs/rustls) rather than by the TLS library of the system
+#[derive(Clone, Default)]
+pub struct TlsConfig {
+    /// Certificate authorities that are trusted on top of the ones of the system
+    root_certificates: Vec<Certificate>,
+    /// The certificate (and private key) the client presents to servers that ask for one
+    identity: Option<Identity>,
+    accept_invalid_certificates: bool,
+}
+
+impl TlsConfig {
+    /// Also trust the certificates that are signed by a certificate authority (e.g. the self-signed certificate of a home server), in PEM format
+    pub fn with_root_certificate_pem(mut self, pem: &[u8]) -> Result<Self, Box<dyn Error>> {
+        self.root_certificates.push(Certificate::from_pem(pem)?);
+        Ok(self)
+    }
+
+    /// Also trust the certificates that are signed by a certificate authority, in DER format
+    pub fn with_root_certificate_der(mut self, der: &[u8]) -> Result<Self, Box<dyn Error>> {
+        self.root_certificates.push(Certificate::from_der(der)?);
+        Ok(self)
+    }
+
+    /// Present a client certificate to servers that ask for one.
+    ///
+    /// Identities are built with `reqwest::Identity::from_pkcs12_der` (with the TLS library of the system) or `reqwest::Identity::from_pem` (with the `rustls` feature)
+    pub fn with_identity(mut self, identity: Identity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Accept any certificate, even expired ones, or ones for other hosts.
+    ///
+    /// **This makes connections vulnerable to man-in-the-middle attacks.** This is only meant for test servers on a local network
+    pub fn danger_accept_invalid_certificates(mut self, accept: bool) -> Self {
+        self.accept_invalid_certificates = accept;
+        self
+    }
+
+    pub fn root_certificate_count(&self) -> usize { self.root_certificates.len() }
+    pub fn has_identity(&self) -> bool { self.identity.is_some() }
+    pub fn accepts_invalid_certificates(&self) -> bool { self.accept_invalid_certificates }
+
+    /// Apply these settings to an HTTP client that is being built
+    pub(crate) fn apply(&self, mut builder: ClientBuilder) -> ClientBuilder {
+        #[cfg(feature = "rustls")]
+        {
+            builder = builder.use_rustls_tls();
+        }
+        for certificate in &self.root_certificates {
+            builder = builder.add_root_certificate(certificate.clone());
+        }
+        if let Some(identity) = &self.identity {
+            builder = builder.identity(identity.clone());
+        }
+        if self.accept_invalid_certificates {
+            log::warn!("TLS certificates are not verified. Connections are not secure");
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+        builder
+    }
+}
+
+impl Debug for TlsConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsConfig")
+            .field("root_certificates", &self.root_certificates.len())
+            .field("identity", &self.identity.is_some())
+            .field("accept_invalid_certificates", &self.accept_invalid_certificates)
+            .finish()
+    }
+}
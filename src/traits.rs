@@ -1,40 +1,111 @@
 //! Traits used by multiple structs in this crate
 
-use std::error::Error;
 use std::collections::{HashMap, HashSet};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, RwLock};
 
 use async_trait::async_trait;
 use csscolorparser::Color;
+use chrono::{DateTime, Utc};
 use url::Url;
 
 use crate::item::SyncStatus;
 use crate::item::Item;
+use crate::item::ItemMut;
 use crate::item::VersionTag;
 use crate::calendar::SupportedComponents;
+use crate::calendar::{ItemOrder, ItemQuery, SearchFilter};
+use crate::calendar::CollectionChanges;
+use crate::calendar::Privileges;
+use crate::calendar::CalendarProperties;
 use crate::resource::Resource;
+use crate::ical::CalendarEnvelope;
+use crate::provider::calendar_selection::CalendarSelection;
+use crate::error::{Rejection, ServerError};
+use crate::scheduling::{DeliveryStatus, ItipMessage};
 
 /// This trait must be implemented by data sources (either local caches or remote CalDAV clients)
 ///
 /// Note that some concrete types (e.g. [`crate::cache::Cache`]) can also provide non-async versions of these functions
-#[async_trait]
+///
+/// Calendars are handed out behind `Arc<RwLock<_>>`s, so that they can be shared between threads (e.g. a GUI thread and a background sync).
+/// Any number of readers can access a calendar at the same time. Do not hold a lock across long operations, since writers (including a sync) wait for every reader to release it.
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 pub trait CalDavSource<T: BaseCalendar> {
     /// Returns the current calendars that this source contains
     /// This function may trigger an update (that can be a long process, or that can even fail, e.g. in case of a remote server)
-    async fn get_calendars(&self) -> Result<HashMap<Url, Arc<Mutex<T>>>, Box<dyn Error>>;
+    async fn get_calendars(&self) -> Result<HashMap<Url, Arc<RwLock<T>>>, crate::Error>;
     /// Returns the calendar matching the URL
-    async fn get_calendar(&self, url: &Url) -> Option<Arc<Mutex<T>>>;
+    async fn get_calendar(&self, url: &Url) -> Option<Arc<RwLock<T>>>;
     /// Create a calendar if it did not exist, and return it
     async fn create_calendar(&mut self, url: Url, name: String, supported_components: SupportedComponents, color: Option<Color>)
-        -> Result<Arc<Mutex<T>>, Box<dyn Error>>;
+        -> Result<Arc<RwLock<T>>, crate::Error>;
+
+    /// Returns a URL a new calendar can be created at (e.g. in the calendar home set of a CalDAV server).
+    ///
+    /// The default implementation returns an error, for sources that do not decide where their calendars are
+    async fn new_calendar_url(&self) -> Result<Url, crate::Error> {
+        Err("This source cannot choose URLs for new calendars".into())
+    }
+
+    /// Delete a calendar and all of its items.
+    ///
+    /// Local sources (e.g. caches) remember the calendars they have deleted (see [`Self::deleted_calendars`]),
+    /// so that a [`Provider`](crate::provider::Provider) also deletes them from its remote source at the next sync
+    async fn delete_calendar(&mut self, url: &Url) -> Result<(), crate::Error>;
+
+    /// The calendars that have been deleted from this source, and whose deletion has not been pushed to the server yet.
+    ///
+    /// The default implementation does not remember anything
+    fn deleted_calendars(&self) -> HashSet<Url> {
+        HashSet::new()
+    }
+
+    /// Forget about a deleted calendar (see [`Self::deleted_calendars`]), once its deletion has been pushed to the server
+    fn forget_deleted_calendar(&mut self, _url: &Url) {}
+
+    /// The items that have been moved from a calendar to another one, and whose move has not been pushed to the server yet (as a map from their URL on the server to their new URL).
+    ///
+    /// The default implementation does not remember anything
+    fn pending_moves(&self) -> HashMap<Url, Url> {
+        HashMap::new()
+    }
+
+    /// Remember that the item at `from` on the server has been moved to `to` (see [`Self::pending_moves`]).
+    ///
+    /// The default implementation does not remember anything, so that moves are pushed as a deletion followed by a creation
+    fn record_move(&mut self, _from: Url, _to: Url) {}
+
+    /// Forget about a move (see [`Self::pending_moves`]), once it has been pushed to the server
+    fn forget_move(&mut self, _from: &Url) {}
 
-    // Removing a calendar is not supported yet
+    /// The calendars a [`Provider`](crate::provider::Provider) syncs, when this is its local source.
+    ///
+    /// The default implementation does not store any selection, so that every calendar is synced
+    fn calendar_selection(&self) -> CalendarSelection {
+        CalendarSelection::All
+    }
+
+    /// Store the calendars a [`Provider`](crate::provider::Provider) syncs (see [`Self::calendar_selection`]).
+    ///
+    /// The default implementation returns an error, for sources that cannot store it
+    fn set_calendar_selection(&mut self, _selection: CalendarSelection) -> Result<(), crate::Error> {
+        Err("This source cannot store a calendar selection".into())
+    }
+
+    /// Send an iTIP message (see [`crate::scheduling`]) to its recipients, e.g. through the scheduling Outbox of a CalDAV server.
+    ///
+    /// The default implementation returns an error, for sources that cannot deliver messages
+    async fn send_scheduling_message(&self, _message: &ItipMessage) -> Result<Vec<DeliveryStatus>, crate::Error> {
+        Err("This source cannot send scheduling messages".into())
+    }
 }
 
 /// This trait contains functions that are common to all calendars
 ///
 /// Note that some concrete types (e.g. [`crate::calendar::cached_calendar::CachedCalendar`]) can also provide non-async versions of these functions
-#[async_trait]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 pub trait BaseCalendar {
     /// Returns the calendar name
     fn name(&self) -> &str;
@@ -48,14 +119,39 @@ pub trait BaseCalendar {
     /// Returns the user-defined color of this calendar
     fn color(&self) -> Option<&Color>;
 
+    /// Returns the description of this calendar
+    fn description(&self) -> Option<&str> {
+        None
+    }
+
+    /// Returns where this calendar should be displayed among the other ones (lower values come first)
+    fn order(&self) -> Option<i32> {
+        None
+    }
+
+    /// Returns the name, the description, the color and the order of this calendar
+    fn properties(&self) -> CalendarProperties {
+        CalendarProperties {
+            name: self.name().to_string(),
+            description: self.description().map(String::from),
+            color: self.color().cloned(),
+            order: self.order(),
+        }
+    }
+
+    /// Returns what the current user is allowed to do in this calendar
+    fn privileges(&self) -> Privileges {
+        Privileges::all()
+    }
+
     /// Add an item into this calendar, and return its new sync status.
     /// For local calendars, the sync status is not modified.
     /// For remote calendars, the sync status is updated by the server
-    async fn add_item(&mut self, item: Item) -> Result<SyncStatus, Box<dyn Error>>;
+    async fn add_item(&mut self, item: Item) -> Result<SyncStatus, crate::Error>;
 
     /// Update an item that already exists in this calendar and returns its new `SyncStatus`
     /// This replaces a given item at a given URL
-    async fn update_item(&mut self, item: Item) -> Result<SyncStatus, Box<dyn Error>>;
+    async fn update_item(&mut self, item: Item) -> Result<SyncStatus, crate::Error>;
 
     /// Returns whether this calDAV calendar supports to-do items
     fn supports_todo(&self) -> bool {
@@ -66,32 +162,97 @@ pub trait BaseCalendar {
     fn supports_events(&self) -> bool {
         self.supported_components().contains(crate::calendar::SupportedComponents::EVENT)
     }
+
+    /// Returns whether this calDAV calendar supports journal entries
+    fn supports_journals(&self) -> bool {
+        self.supported_components().contains(crate::calendar::SupportedComponents::JOURNAL)
+    }
+
+    /// Returns whether this collection is a CardDAV address book, that holds contacts
+    fn supports_contacts(&self) -> bool {
+        self.supported_components().contains(crate::calendar::SupportedComponents::CONTACT)
+    }
+
+    /// Returns whether the server would accept new items into this calendar
+    fn can_add_items(&self) -> bool {
+        self.privileges().contains(Privileges::BIND)
+    }
+
+    /// Returns whether the current user can change the items of this calendar (add, modify or delete some of them). \
+    /// Syncs do not push any change to calendars that are not writable (e.g. subscriptions, or calendars that have been shared read-only)
+    fn is_writable(&self) -> bool {
+        self.privileges().intersects(Privileges::BIND | Privileges::WRITE_CONTENT | Privileges::UNBIND)
+    }
 }
 
 
 /// Functions availabe for calendars that are backed by a CalDAV server
 ///
 /// Note that some concrete types (e.g. [`crate::calendar::cached_calendar::CachedCalendar`]) can also provide non-async versions of these functions
-#[async_trait]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 pub trait DavCalendar : BaseCalendar {
     /// Create a new calendar
     fn new(name: String, resource: Resource, supported_components: SupportedComponents, color: Option<Color>) -> Self;
 
     /// Get the URLs and the version tags of every item in this calendar
-    async fn get_item_version_tags(&self) -> Result<HashMap<Url, VersionTag>, Box<dyn Error>>;
+    async fn get_item_version_tags(&self) -> Result<HashMap<Url, VersionTag>, crate::Error>;
+
+    /// Get the URLs and the version tags of the items in this calendar, leaving out the events that do not happen between `start` (included) and `end` (excluded).
+    /// Tasks and journals are always listed.
+    ///
+    /// The default implementation does not leave out anything
+    async fn get_item_version_tags_between(&self, _start: DateTime<Utc>, _end: DateTime<Utc>) -> Result<HashMap<Url, VersionTag>, crate::Error> {
+        self.get_item_version_tags().await
+    }
 
     /// Returns a particular item
-    async fn get_item_by_url(&self, url: &Url) -> Result<Option<Item>, Box<dyn Error>>;
+    async fn get_item_by_url(&self, url: &Url) -> Result<Option<Item>, crate::Error>;
 
-    /// Returns a set of items.
+    /// Returns a set of items, in the same order as `urls` (`None` for the items that do not exist).
     /// This is usually faster than calling multiple consecutive [`DavCalendar::get_item_by_url`], since it only issues one HTTP request.
-    async fn get_items_by_url(&self, urls: &[Url]) -> Result<Vec<Option<Item>>, Box<dyn Error>>;
+    async fn get_items_by_url(&self, urls: &[Url]) -> Result<Vec<Option<Item>>, crate::Error>;
 
     /// Delete an item
-    async fn delete_item(&mut self, item_url: &Url) -> Result<(), Box<dyn Error>>;
+    async fn delete_item(&mut self, item_url: &Url) -> Result<(), crate::Error>;
+
+    /// Move an item to another URL (usually in another calendar of the same server), keeping its content and its UID. \
+    /// The server refuses to move it in case it has been modified since `version_tag`.
+    /// This returns the version tag of the item at its new URL, if the server has told it.
+    ///
+    /// The default implementation returns an error, for calendars that cannot move items
+    async fn move_item(&mut self, _item_url: &Url, _version_tag: &VersionTag, _destination: &Url) -> Result<Option<VersionTag>, crate::Error> {
+        Err("This calendar cannot move items".into())
+    }
+
+    /// The URL the server has given to an item that has just been added with [`BaseCalendar::add_item`], in case it differs from the URL it has been added with
+    /// (e.g. for servers that choose the identifiers of new items by themselves). The local copy of the item is then moved to this URL.
+    ///
+    /// The default implementation returns `None`, i.e. items keep their URLs
+    fn added_item_url(&self, _item_url: &Url) -> Option<Url> {
+        None
+    }
+
+    /// Change the name, the description, the color and the order of this calendar
+    async fn update_properties(&mut self, properties: &CalendarProperties) -> Result<(), crate::Error>;
 
-    /// Get the URLs of all current items in this calendar
-    async fn get_item_urls(&self) -> Result<HashSet<Url>, Box<dyn Error>> {
+    /// The current sync token of this calendar (see [RFC 6578](https://datatracker.ietf.org/doc/html/rfc6578)), or `None` if it does not support `sync-collection` REPORTs.
+    ///
+    /// The default implementation returns `None`
+    async fn get_sync_token(&self) -> Result<Option<String>, crate::Error> {
+        Ok(None)
+    }
+
+    /// What has changed in this calendar since a previous sync token. \
+    /// This returns `None` in case this is not supported (or in case the server no longer accepts this token), in which case [`DavCalendar::get_item_version_tags`] should rather be used.
+    ///
+    /// The default implementation returns `None`
+    async fn get_changes_since(&self, _sync_token: &str) -> Result<Option<CollectionChanges>, crate::Error> {
+        Ok(None)
+    }
+
+    /// Get the URLs of all current items in this calendar (except the evicted ones)
+    async fn get_item_urls(&self) -> Result<HashSet<Url>, crate::Error> {
         let items = self.get_item_version_tags().await?;
         Ok(items.iter()
             .map(|(url, _tag)| url.clone())
@@ -108,31 +269,176 @@ pub trait DavCalendar : BaseCalendar {
 /// Usually, these are local calendars fully backed by a local folder
 ///
 /// Note that some concrete types (e.g. [`crate::calendar::cached_calendar::CachedCalendar`]) can also provide non-async versions of these functions
-#[async_trait]
+///
+/// Items whose content has been evicted (see [`CompleteCalendar::evict_item`]) are not returned by any function of this trait that lists or gets items
+/// (e.g. [`CompleteCalendar::get_items`], [`CompleteCalendar::iter_items`], [`CompleteCalendar::query`] or [`CompleteCalendar::get_item_by_url`]).
+/// Callers that need them must list them with [`CompleteCalendar::evicted_items`], and download them with [`Provider::ensure_loaded`](crate::provider::Provider::ensure_loaded). \
+/// Calendars that keep evicted items in a storage (e.g. [`PersistentCalendar`](crate::persistent_cache::PersistentCalendar)) read them from it on demand instead, so that they are still listed
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 pub trait CompleteCalendar : BaseCalendar {
     /// Create a new calendar
     fn new(name: String, url: Url, supported_components: SupportedComponents, color: Option<Color>) -> Self;
 
-    /// Get the URLs of all current items in this calendar
-    async fn get_item_urls(&self) -> Result<HashSet<Url>, Box<dyn Error>>;
+    /// Get the URLs of all current items in this calendar (except the evicted ones)
+    async fn get_item_urls(&self) -> Result<HashSet<Url>, crate::Error>;
 
-    /// Returns all items that this calendar contains
-    async fn get_items(&self) -> Result<HashMap<Url, &Item>, Box<dyn Error>>;
+    /// Returns all items that this calendar contains (except the evicted ones)
+    async fn get_items(&self) -> Result<HashMap<Url, &Item>, crate::Error>;
 
     /// Returns all items that this calendar contains
-    async fn get_items_mut(&mut self) -> Result<HashMap<Url, &mut Item>, Box<dyn Error>>;
+    async fn get_items_mut(&mut self) -> Result<HashMap<Url, ItemMut<'_>>, crate::Error>;
 
-    /// Returns a particular item
+    /// Iterate over the items of this calendar, without copying them (nor their URLs) into a new collection.
+    /// This is usually what you want to display the content of a calendar.
+    fn iter_items<'a>(&'a self) -> Box<dyn Iterator<Item = (&'a Url, &'a Item)> + 'a>;
+
+    /// Iterate over the items of this calendar, without copying them (nor their URLs) into a new collection.
+    fn iter_items_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = (&'a Url, ItemMut<'a>)> + 'a>;
+
+    /// Returns the number of items this calendar contains (including the ones that are marked for deletion, but not the evicted ones)
+    fn item_count(&self) -> usize;
+
+    /// Iterate over the items of this calendar that match a filter.
+    ///
+    /// Calendars may contain several kinds of items (e.g. both events and tasks), even if they do not advertise they support them.
+    fn iter_items_filtered<'a>(&'a self, filter: SearchFilter) -> Box<dyn Iterator<Item = (&'a Url, &'a Item)> + 'a> {
+        Box::new(self.iter_items().filter(move |(_url, item)| filter.matches(item)))
+    }
+
+    /// Returns the number of items of this calendar that match a filter
+    fn count_items(&self, filter: SearchFilter) -> usize {
+        self.iter_items_filtered(filter).count()
+    }
+
+    /// Returns the items of this calendar that match a query (e.g. the tasks of a category that are not completed yet), in no particular order.
+    ///
+    /// The default implementation goes through every item. Implementors are encouraged to keep an index of their items instead
+    fn query<'a>(&'a self, query: &ItemQuery) -> Vec<(&'a Url, &'a Item)> {
+        self.iter_items().filter(|(_url, item)| query.matches(item)).collect()
+    }
+
+    /// Iterate over the items of this calendar that match a query, sorted by `order`.
+    /// Only references to the items are sorted, the items themselves are not copied
+    fn iter_sorted<'a>(&'a self, query: &ItemQuery, order: ItemOrder) -> Box<dyn Iterator<Item = (&'a Url, &'a Item)> + 'a> {
+        let mut items = self.query(query);
+        items.sort_unstable_by(|a, b| order.compare(*a, *b));
+        Box::new(items.into_iter())
+    }
+
+    /// Returns (at most) `limit` items that match a query, after skipping the `offset` first ones in the given `order`. \
+    /// This is meant for UIs that lazily display large calendars one page at a time: only the items before the end of the page are sorted
+    fn query_page<'a>(&'a self, query: &ItemQuery, order: ItemOrder, offset: usize, limit: usize) -> Vec<(&'a Url, &'a Item)> {
+        let mut items = self.query(query);
+        order.sort_first(&mut items, offset.saturating_add(limit));
+        items.into_iter().skip(offset).collect()
+    }
+
+    /// Returns a particular item. This returns `None` for evicted items, that must be downloaded again first (see [`Provider::ensure_loaded`](crate::provider::Provider::ensure_loaded))
     async fn get_item_by_url<'a>(&'a self, url: &Url) -> Option<&'a Item>;
 
-    /// Returns a particular item
-    async fn get_item_by_url_mut<'a>(&'a mut self, url: &Url) -> Option<&'a mut Item>;
+    /// Returns a particular item. It is only recorded as modified in case it is actually mutated (see [`ItemMut`])
+    async fn get_item_by_url_mut<'a>(&'a mut self, url: &Url) -> Option<ItemMut<'a>>;
 
     /// Mark an item for deletion.
     /// This is required so that the upcoming sync will know it should also also delete this task from the server
     /// (and then call [`CompleteCalendar::immediately_delete_item`] once it has been successfully deleted on the server)
-    async fn mark_for_deletion(&mut self, item_id: &Url) -> Result<(), Box<dyn Error>>;
+    async fn mark_for_deletion(&mut self, item_id: &Url) -> Result<(), crate::Error>;
 
     /// Immediately remove an item. See [`CompleteCalendar::mark_for_deletion`]
-    async fn immediately_delete_item(&mut self, item_id: &Url) -> Result<(), Box<dyn Error>>;
+    async fn immediately_delete_item(&mut self, item_id: &Url) -> Result<(), crate::Error>;
+
+    /// Remember that the server has refused an item (e.g. because its content is invalid, or because the server is full).
+    /// Such an item needs the user's attention: it will not be pushed again at the next syncs, until it is locally modified again
+    fn mark_as_rejected(&mut self, item_url: &Url, error: ServerError);
+
+    /// Returns why the server refused the current version of this item, if it did (see [`CompleteCalendar::mark_as_rejected`])
+    fn rejection(&self, item_url: &Url) -> Option<&Rejection>;
+
+    /// Drop the content of a synced item (e.g. to save space), only remembering its URL and its version tag. \
+    /// From then on, the item is absent from the items this calendar lists or returns, until it is downloaded again with [`Provider::ensure_loaded`](crate::provider::Provider::ensure_loaded):
+    /// this is not done automatically. Calendars that still have the content in a storage may read it from there on demand instead (see [`PersistentCalendar`](crate::persistent_cache::PersistentCalendar)).
+    ///
+    /// Items that have local changes cannot be evicted
+    fn evict_item(&mut self, item_url: &Url) -> Result<(), crate::Error>;
+
+    /// The URLs and the version tags of the items whose content has been evicted (see [`CompleteCalendar::evict_item`])
+    fn evicted_items(&self) -> &HashMap<Url, VersionTag>;
+
+    /// Update the version tag of an evicted item (e.g. because it has changed on the server), or forget about it in case `version_tag` is `None`
+    fn update_evicted_item(&mut self, item_url: &Url, version_tag: Option<VersionTag>);
+
+    /// Remember what the current user is allowed to do in this calendar (this is usually copied from the remote calendar during a sync)
+    fn set_privileges(&mut self, privileges: Privileges);
+
+    /// Rename this calendar. This will be pushed to the server at the next sync
+    fn set_name(&mut self, name: String);
+
+    /// Change the description of this calendar. This will be pushed to the server at the next sync
+    fn set_description(&mut self, description: Option<String>);
+
+    /// Change the color of this calendar. This will be pushed to the server at the next sync
+    fn set_color(&mut self, color: Option<Color>);
+
+    /// Change where this calendar should be displayed among the other ones. This will be pushed to the server at the next sync
+    fn set_order(&mut self, order: Option<i32>);
+
+    /// Whether the name, the description, the color or the order of this calendar have been changed since the last sync
+    fn has_modified_properties(&self) -> bool;
+
+    /// Set the name, the description, the color and the order of this calendar, as they are on the server (this is usually called during a sync). \
+    /// Once this has been called, [`CompleteCalendar::has_modified_properties`] returns `false`
+    fn set_synced_properties(&mut self, properties: CalendarProperties);
+
+    /// The sync token the remote counterpart of this calendar had at the end of the last sync (if it supports them, see [`DavCalendar::get_sync_token`])
+    fn sync_token(&self) -> Option<&str>;
+
+    /// Remember the sync token of the remote counterpart of this calendar, so that the next sync only asks for what has changed since then
+    fn set_sync_token(&mut self, sync_token: Option<String>);
+
+    /// Returns whether the server would accept modifications of this item. \
+    /// UIs can use this to disable editing items of shared calendars the user has read-only access to.
+    fn can_edit(&self, item_url: &Url) -> bool {
+        self.iter_items().any(|(url, _)| url == item_url)
+            && self.privileges().contains(Privileges::WRITE_CONTENT)
+    }
+
+    /// Returns whether the server would accept the deletion of this item
+    fn can_delete(&self, item_url: &Url) -> bool {
+        self.iter_items().any(|(url, _)| url == item_url)
+            && self.privileges().contains(Privileges::UNBIND)
+    }
+
+    /// Export the items of this calendar (except the ones that are marked for deletion) into a single iCal file, e.g. for backups or for other apps
+    fn export_ics(&self) -> Result<String, crate::Error> {
+        let envelope = CalendarEnvelope::new(crate::ical::default_prod_id())
+            .with_property("X-WR-CALNAME".to_string(), self.name().to_string());
+        let items = self.iter_items()
+            .filter(|(_url, item)| matches!(item.sync_status(), SyncStatus::LocallyDeleted(_)) == false)
+            .map(|(_url, item)| item);
+        Ok(crate::ical::build_from_items(items, &envelope)?)
+    }
+
+    /// Add the items of an iCal file (e.g. a backup made by [`CompleteCalendar::export_ics`], or the export of another app) to this calendar.
+    /// They are [`SyncStatus::NotSynced`], so that they are pushed to the server at the next sync.
+    ///
+    /// Items whose UID is already in this calendar are skipped, so that importing the same file twice does not duplicate them.
+    /// This returns the URLs of the items that have been added
+    async fn import_ics(&mut self, content: &str) -> Result<Vec<Url>, crate::Error> {
+        let existing_uids: HashSet<String> = self.iter_items()
+            .map(|(_url, item)| item.uid().as_str().to_string())
+            .collect();
+
+        let mut added = Vec::new();
+        for item in crate::ical::parse_calendar(content, self.url())? {
+            if existing_uids.contains(item.uid().as_str()) {
+                log::info!("Not importing {}, whose UID is already in calendar {}", item.uid().as_str(), self.url());
+                continue;
+            }
+            let url = item.url().clone();
+            self.add_item(item).await?;
+            added.push(url);
+        }
+        Ok(added)
+    }
 }
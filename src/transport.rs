@@ -0,0 +1,122 @@
+//! How HTTP requests are actually sent (see [`Client::set_transport`](crate::client::Client::set_transport))
+//!
+//! By default, requests are sent by an HTTP client that is built from the TLS and proxy settings of the [`Client`](crate::client::Client).
+//! Another [`HttpTransport`] can be used instead, e.g. a `reqwest::Client` that is shared with the rest of an application, or a fake server for tests that do not need any network.
+
+use std::error::Error;
+use std::fmt::{Debug, Formatter};
+
+use async_trait::async_trait;
+use reqwest::{Request, Response};
+
+/// Something that sends HTTP requests.
+///
+/// Requests already carry their credentials, and are retried and paced by the [`Client`](crate::client::Client) (according to its retry policy and rate limit).
+/// Transports should not follow redirections by themselves, since HTTP clients replace redirected `PROPFIND` requests with `GET` ones (this matters for the discovery of the CalDAV service, see [`crate::discovery`]).
+///
+/// Fake transports can build their responses from `http::Response`s, e.g. `reqwest::Response::from(http::Response::builder().status(207).body(xml)?)`
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+pub trait HttpTransport: Send + Sync {
+    async fn execute(&self, request: Request) -> Result<Response, Box<dyn Error>>;
+}
+
+impl Debug for dyn HttpTransport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<HttpTransport>")
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl HttpTransport for reqwest::Client {
+    async fn execute(&self, request: Request) -> Result<Response, Box<dyn Error>> {
+        Ok(reqwest::Client::execute(self, request).await?)
+    }
+}
+
+/// Whether an error of a transport is worth sending the request again (e.g. a connection failure)
+pub(crate) fn is_transient_error(err: &(dyn Error + 'static)) -> bool {
+    match err.downcast_ref::<reqwest::Error>() {
+        #[cfg(not(target_arch = "wasm32"))]
+        Some(err) => err.is_connect() || err.is_timeout(),
+        // Browsers do not tell why a request failed
+        #[cfg(target_arch = "wasm32")]
+        Some(err) => err.is_request(),
+        None => false,
+    }
+}
+
+/// Whether an error of a transport means that the request has not reached the server (hence has not been processed)
+pub(crate) fn is_connect_error(err: &(dyn Error + 'static)) -> bool {
+    match err.downcast_ref::<reqwest::Error>() {
+        #[cfg(not(target_arch = "wasm32"))]
+        Some(err) => err.is_connect(),
+        // Browsers do not tell why a request failed
+        #[cfg(target_arch = "wasm32")]
+        Some(_) => false,
+        None => false,
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    use crate::resource::Resource;
+    use crate::retry::RetryPolicy;
+
+    /// A server that replies with the given statuses, and records the requests it receives
+    struct FakeServer {
+        statuses: Mutex<Vec<u16>>,
+        received: Mutex<Vec<(String, String)>>,
+    }
+
+    #[async_trait]
+    impl HttpTransport for FakeServer {
+        async fn execute(&self, request: Request) -> Result<Response, Box<dyn Error>> {
+            self.received.lock().unwrap().push((request.method().to_string(), request.url().to_string()));
+            let status = self.statuses.lock().unwrap().remove(0);
+            Ok(Response::from(http::Response::builder().status(status).body("")?))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fake_transport() {
+        let server = Arc::new(FakeServer {
+            statuses: Mutex::new(vec![503, 207]),
+            received: Mutex::new(Vec::new()),
+        });
+        let mut resource = Resource::new("https://example.com/dav/".parse().unwrap(), "john".into(), "secret".into());
+        resource.set_retry_policy(RetryPolicy::new(2).with_backoff(std::time::Duration::from_millis(1), std::time::Duration::from_millis(1)));
+        resource.set_transport(Some(server.clone()));
+
+        let request = resource.http().request("PROPFIND".parse().unwrap(), resource.url().clone());
+        let response = resource.send(request).await.unwrap();
+        assert_eq!(response.status().as_u16(), 207);
+        assert_eq!(*server.received.lock().unwrap(), vec![
+            ("PROPFIND".to_string(), "https://example.com/dav/".to_string()),
+            ("PROPFIND".to_string(), "https://example.com/dav/".to_string()),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_non_idempotent_requests_are_not_retried_after_timeouts() {
+        let server = Arc::new(FakeServer {
+            statuses: Mutex::new(vec![503, 504, 201]),
+            received: Mutex::new(Vec::new()),
+        });
+        let mut resource = Resource::new("https://example.com/dav/".parse().unwrap(), "john".into(), "secret".into());
+        resource.set_retry_policy(RetryPolicy::new(3).with_backoff(std::time::Duration::from_millis(1), std::time::Duration::from_millis(1)));
+        resource.set_transport(Some(server.clone()));
+
+        // The creation may have succeeded when the gateway timed out: it is not sent a third time
+        let request = resource.http().put(resource.url().join("task.ics").unwrap()).header(reqwest::header::IF_NONE_MATCH, "*");
+        let response = resource.send(request).await.unwrap();
+        assert_eq!(response.status().as_u16(), 504);
+        assert_eq!(server.received.lock().unwrap().len(), 2);
+    }
+}
@@ -1,10 +1,12 @@
 //! Some utility functions
 
+use std::error::Error;
 use std::collections::{HashMap, HashSet};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, RwLock};
 use std::hash::Hash;
 use std::io::{stdin, stdout, Read, Write};
 
+use chrono_tz::Tz;
 use minidom::Element;
 use url::Url;
 
@@ -62,31 +64,27 @@ pub fn print_xml(element: &Element) {
 }
 
 /// A debug utility that pretty-prints calendars
-pub async fn print_calendar_list<C>(cals: &HashMap<Url, Arc<Mutex<C>>>)
+pub async fn print_calendar_list<C>(cals: &HashMap<Url, Arc<RwLock<C>>>)
 where
     C: CompleteCalendar,
 {
     for (url, cal) in cals {
-        println!("CAL {} ({})", cal.lock().unwrap().name(), url);
-        match cal.lock().unwrap().get_items().await {
-            Err(_err) => continue,
-            Ok(map) => {
-                for (_, item) in map {
-                    print_task(item);
-                }
-            },
+        let cal = cal.read().unwrap();
+        println!("CAL {} ({})", cal.name(), url);
+        for (_, item) in cal.iter_items() {
+            print_task(item);
         }
     }
 }
 
 /// A debug utility that pretty-prints calendars
-pub async fn print_dav_calendar_list<C>(cals: &HashMap<Url, Arc<Mutex<C>>>)
+pub async fn print_dav_calendar_list<C>(cals: &HashMap<Url, Arc<RwLock<C>>>)
 where
     C: DavCalendar,
 {
     for (url, cal) in cals {
-        println!("CAL {} ({})", cal.lock().unwrap().name(), url);
-        match cal.lock().unwrap().get_item_version_tags().await {
+        println!("CAL {} ({})", cal.read().unwrap().name(), url);
+        match cal.read().unwrap().get_item_version_tags().await {
             Err(_err) => continue,
             Ok(map) => {
                 for (url, version_tag) in map {
@@ -109,7 +107,33 @@ pub fn print_task(item: &Item) {
             };
             println!("    {}{} {}\t{}", completion, sync, task.name(), task.url());
         },
-        _ => return,
+        Item::Event(event) => {
+            let sync = match event.sync_status() {
+                SyncStatus::NotSynced => ".",
+                SyncStatus::Synced(_) => "=",
+                SyncStatus::LocallyModified(_) => "~",
+                SyncStatus::LocallyDeleted(_) =>  "x",
+            };
+            println!("    ⌚{} {} ({} - {})\t{}", sync, event.name(), event.start(), event.end(), event.url());
+        },
+        Item::Journal(journal) => {
+            let sync = match journal.sync_status() {
+                SyncStatus::NotSynced => ".",
+                SyncStatus::Synced(_) => "=",
+                SyncStatus::LocallyModified(_) => "~",
+                SyncStatus::LocallyDeleted(_) =>  "x",
+            };
+            println!("    ✎{} {}\t{}", sync, journal.name(), journal.url());
+        },
+        Item::Contact(contact) => {
+            let sync = match contact.sync_status() {
+                SyncStatus::NotSynced => ".",
+                SyncStatus::Synced(_) => "=",
+                SyncStatus::LocallyModified(_) => "~",
+                SyncStatus::LocallyDeleted(_) =>  "x",
+            };
+            println!("    ☎{} {}\t{}", sync, contact.name(), contact.url());
+        },
     }
 }
 
@@ -141,6 +165,62 @@ where
 }
 
 
+/// Escape text, so that it can be inserted in an XML document (e.g. the body of a WebDAV request)
+pub fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Check that a `GEO` position (in degrees) is valid: latitude between -90 and 90, longitude between -180 and 180
+pub fn check_geo_position(latitude: f64, longitude: f64) -> Result<(), Box<dyn Error>> {
+    if (-90.0..=90.0).contains(&latitude) == false {
+        return Err(format!("Invalid latitude {}, it must be between -90 and 90", latitude).into());
+    }
+    if (-180.0..=180.0).contains(&longitude) == false {
+        return Err(format!("Invalid longitude {}, it must be between -180 and 180", longitude).into());
+    }
+    Ok(())
+}
+
+/// Check that a property name is the name of a custom property (e.g. `X-MY-APP-COLOR`), that can be set on items (see e.g. [`Event::set_property`](crate::Event::set_property))
+pub fn check_custom_property_name(name: &str) -> Result<(), Box<dyn Error>> {
+    let is_custom = name.len() > 2
+        && name.get(..2).map(|prefix| prefix.eq_ignore_ascii_case("X-")) == Some(true)
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    if is_custom == false {
+        return Err(format!("Invalid property name {:?}, only custom properties (whose names start with X-) can be set", name).into());
+    }
+    Ok(())
+}
+
+/// Check that a custom property can be written as is to an iCal file (see e.g. [`Event::set_property`](crate::Event::set_property)): \
+/// its value and its parameters must not span several lines, parameter names must be tokens, and parameter values must not contain double quotes (RFC 5545, section 3.1)
+pub fn check_custom_property_value(value: &str, params: &[(String, Vec<String>)]) -> Result<(), Box<dyn Error>> {
+    let is_control = |c: char| c.is_control() && c != '\t';
+    if value.contains(is_control) {
+        return Err(format!("Invalid property value {:?}, it must not contain line breaks or control characters", value).into());
+    }
+    for (param, values) in params {
+        if param.is_empty() || param.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') == false {
+            return Err(format!("Invalid parameter name {:?}", param).into());
+        }
+        if let Some(bad) = values.iter().find(|v| v.contains(is_control) || v.contains('"')) {
+            return Err(format!("Invalid value {:?} for parameter {}, it must not contain double quotes, line breaks or control characters", bad, param).into());
+        }
+    }
+    Ok(())
+}
+
 /// Wait for the user to press enter
 pub fn pause() {
     let mut stdout = stdout();
@@ -151,7 +231,27 @@ pub fn pause() {
 
 
 /// Generate a random URL with a given prefix
+///
+/// See also [`CalendarUrl::random_item_url`](crate::calendar::CalendarUrl::random_item_url)
 pub fn random_url(parent_calendar: &Url) -> Url {
-    let random = uuid::Uuid::new_v4().to_hyphenated().to_string();
-    parent_calendar.join(&random).unwrap(/* this cannot panic since we've just created a string that is a valid URL */)
+    crate::calendar::CalendarUrl::from(parent_calendar.clone()).random_item_url()
+}
+
+
+/// The timezone of the system this code runs on.
+///
+/// This is only detected when the `system_timezone` feature is enabled, and defaults to UTC otherwise (or in case detection fails)
+pub fn system_timezone() -> Tz {
+    #[cfg(feature = "system_timezone")]
+    {
+        match iana_time_zone::get_timezone() {
+            Ok(name) => match name.parse::<Tz>() {
+                Ok(tz) => return tz,
+                Err(err) => log::warn!("Unknown system timezone {}: {}", name, err),
+            },
+            Err(err) => log::warn!("Unable to detect the system timezone: {}", err),
+        }
+    }
+
+    Tz::UTC
 }
@@ -0,0 +1,489 @@
+//! A local cache for CalDAV data, that is stored as a [vdir](https://vdirsyncer.pimutils.org/en/stable/vdir.html): a folder per calendar, that contains an `.ics` file per item.
+//!
+//! This is the layout [vdirsyncer](https://vdirsyncer.pimutils.org) writes, so that apps that read it (e.g. [khal](https://lostpackets.de/khal/) or [todoman](https://todoman.readthedocs.io)) can use the calendars kitchen-fridge syncs, and vice versa:
+//! * the name and the color of a calendar are stored in its `displayname` and `color` files
+//! * items are stored in `<name>.ics` files (or `<name>.vcf` files for contacts), whose name is the last segment of their URL
+//! * what is not part of the vdir format (the URL of the calendar, the sync statuses of its items...) is stored in a hidden `.kitchen-fridge.json` file in every calendar folder
+//!
+//! Items that other apps create, modify or rename are picked up when the cache is loaded, and pushed to the server at the next sync.
+//! Items whose files other apps delete are deleted from the server at the next sync.
+//! Calendar folders that have not been created by this crate are ignored, since their URL on the server is unknown.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::cache::write_atomically;
+use crate::calendar::SupportedComponents;
+use crate::contact::Contact;
+use crate::error::Rejection;
+use crate::event::Event;
+use crate::item::{Item, SyncStatus, Uid, VersionTag};
+use crate::journal::Journal;
+use crate::task::Task;
+use crate::persistent_cache::{PersistentCache, PersistentCalendar};
+use crate::storage::{CacheStorage, CalendarRecord, ItemChange, ItemRecord};
+
+/// The hidden file of a calendar folder, that stores what the vdir format cannot
+const STATE_FILE: &str = ".kitchen-fridge.json";
+const DISPLAYNAME_FILE: &str = "displayname";
+const COLOR_FILE: &str = "color";
+/// Data that is not tied to a calendar is stored in this hidden folder
+const METADATA_FOLDER: &str = ".kitchen-fridge";
+const ITEM_EXTENSION: &str = "ics";
+/// Contacts of address books are stored in `.vcf` files instead
+const CONTACT_EXTENSION: &str = "vcf";
+
+/// A [`PersistentCache`] that is stored as a vdir
+pub type VdirCache = PersistentCache<VdirStorage>;
+/// A calendar of a [`VdirCache`]
+pub type VdirCalendar = PersistentCalendar<VdirStorage>;
+
+/// What is stored in the `.kitchen-fridge.json` file of a calendar folder
+#[derive(Debug, Serialize, Deserialize)]
+struct CollectionState {
+    calendar: CalendarRecord,
+    /// By file name
+    #[serde(default)]
+    items: HashMap<String, ItemState>,
+    #[serde(default)]
+    evicted_items: HashMap<Url, VersionTag>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ItemState {
+    url: Url,
+    sync_status: SyncStatus,
+    #[serde(default)]
+    rejection: Option<Rejection>,
+    /// When the file was written by this crate, to tell whether another app has modified it since then
+    #[serde(default)]
+    modified: Option<SystemTime>,
+    /// The UID and the kind of the item, to be able to push its deletion in case another app deletes its file
+    #[serde(default)]
+    uid: Option<Uid>,
+    #[serde(default)]
+    component: Option<SupportedComponents>,
+}
+
+/// A [`CacheStorage`] that stores calendars as a vdir (see the [module documentation](crate::vdir_cache))
+#[derive(Debug)]
+pub struct VdirStorage {
+    root: PathBuf,
+    /// The folder of every calendar, by URL
+    folders: HashMap<Url, PathBuf>,
+}
+
+impl VdirStorage {
+    /// Use (or create) a vdir in a folder
+    pub fn open(root: &Path) -> Result<Self, Box<dyn Error>> {
+        fs::create_dir_all(root)?;
+        Ok(Self { root: PathBuf::from(root), folders: HashMap::new() })
+    }
+
+    /// The folder this storage writes to
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The folder of a calendar, that is picked (from the last segment of its URL) if it does not have one yet
+    fn folder(&mut self, calendar_url: &Url) -> PathBuf {
+        if let Some(folder) = self.folders.get(calendar_url) {
+            return folder.clone();
+        }
+        let base_name = match last_segment(calendar_url) {
+            "" => "calendar".to_string(),
+            segment => sanitize_filename::sanitize(segment),
+        };
+        let mut name = base_name.clone();
+        let mut suffix = 1;
+        while self.root.join(&name).exists() || self.folders.values().any(|folder| folder.ends_with(&name)) {
+            suffix += 1;
+            name = format!("{}-{}", base_name, suffix);
+        }
+        let folder = self.root.join(name);
+        self.folders.insert(calendar_url.clone(), folder.clone());
+        folder
+    }
+
+    fn read_state(folder: &Path) -> Result<Option<CollectionState>, Box<dyn Error>> {
+        match fs::read(folder.join(STATE_FILE)) {
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+            Ok(content) => Ok(Some(serde_json::from_slice(&content)?)),
+        }
+    }
+
+    fn write_state(folder: &Path, state: &CollectionState) -> Result<(), Box<dyn Error>> {
+        write_atomically(&folder.join(STATE_FILE), |writer| Ok(serde_json::to_writer(writer, state)?))
+    }
+
+    /// The state of a calendar, that must already be stored
+    fn existing_state(&mut self, calendar_url: &Url) -> Result<(PathBuf, CollectionState), Box<dyn Error>> {
+        let folder = self.folder(calendar_url);
+        let state = Self::read_state(&folder)?
+            .ok_or_else(|| format!("Calendar {} is absent from {:?}", calendar_url, self.root))?;
+        Ok((folder, state))
+    }
+
+    /// Read a calendar folder, taking into account the changes other apps have made to its name and its color
+    fn read_calendar(folder: &Path) -> Result<Option<CalendarRecord>, Box<dyn Error>> {
+        let mut record = match Self::read_state(folder)? {
+            None => return Ok(None),
+            Some(state) => state.calendar,
+        };
+        if let Some(name) = read_text(&folder.join(DISPLAYNAME_FILE))? {
+            if name != record.name {
+                record.name = name;
+                record.properties_modified = true;
+            }
+        }
+        if let Some(color) = read_text(&folder.join(COLOR_FILE))? {
+            match csscolorparser::parse(&color) {
+                Err(err) => log::warn!("Ignoring the invalid color of {:?}: {}", folder, err),
+                Ok(color) => if record.color.as_ref().map(|c| c.to_hex_string()) != Some(color.to_hex_string()) {
+                    record.color = Some(color);
+                    record.properties_modified = true;
+                },
+            }
+        }
+        Ok(Some(record))
+    }
+}
+
+/// The last non-empty segment of the path of a URL
+fn last_segment(url: &Url) -> &str {
+    url.path_segments()
+        .and_then(|segments| segments.rev().find(|segment| segment.is_empty() == false))
+        .unwrap_or("")
+}
+
+/// The name of the file of an item
+fn item_file_name(item_url: &Url) -> String {
+    let name = sanitize_filename::sanitize(last_segment(item_url));
+    match Path::new(&name).extension().and_then(|ext| ext.to_str()) {
+        Some(ITEM_EXTENSION) | Some(CONTACT_EXTENSION) => name,
+        _ => format!("{}.{}", name, ITEM_EXTENSION),
+    }
+}
+
+/// The content of a small text file, without its trailing line feed
+fn read_text(path: &Path) -> Result<Option<String>, Box<dyn Error>> {
+    match fs::read_to_string(path) {
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+        Ok(text) => Ok(Some(text.trim().to_string())),
+    }
+}
+
+fn write_text(path: &Path, text: Option<&str>) -> Result<(), Box<dyn Error>> {
+    match text {
+        None => match fs::remove_file(path) {
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err.into()),
+            Ok(()) => Ok(()),
+        },
+        Some(text) => write_atomically(path, |writer| Ok(writeln!(writer, "{}", text)?)),
+    }
+}
+
+fn modification_date(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+fn component_of(item: &Item) -> SupportedComponents {
+    match item {
+        Item::Event(_) => SupportedComponents::EVENT,
+        Item::Task(_) => SupportedComponents::TODO,
+        Item::Journal(_) => SupportedComponents::JOURNAL,
+        Item::Contact(_) => SupportedComponents::CONTACT,
+    }
+}
+
+/// An item marked for deletion, that stands for an item whose file has been deleted by another app. \
+/// Its content is lost, but its deletion can still be pushed to the server. Returns `None` for states written by older versions of this crate, that do not tell the UID of their items
+fn tombstone(calendar_url: &Url, file_name: &str, item_state: ItemState, version_tag: VersionTag) -> Option<Item> {
+    let uid = item_state.uid?;
+    let name = file_name.to_string();
+    let mut item = match item_state.component? {
+        SupportedComponents::EVENT => Item::Event(Event::new(name, Utc::now(), Utc::now(), calendar_url)),
+        SupportedComponents::TODO => Item::Task(Task::new(name, false, calendar_url)),
+        SupportedComponents::JOURNAL => Item::Journal(Journal::new(name, None, calendar_url)),
+        SupportedComponents::CONTACT => Item::Contact(Contact::new(name, calendar_url)),
+        _ => return None,
+    };
+    item.set_url(item_state.url);
+    item.set_uid(uid);
+    item.set_sync_status(SyncStatus::LocallyDeleted(version_tag));
+    Some(item)
+}
+
+impl CacheStorage for VdirStorage {
+    fn load_calendars(&mut self) -> Result<Vec<CalendarRecord>, Box<dyn Error>> {
+        self.folders.clear();
+        let mut calendars = Vec::new();
+        for entry in fs::read_dir(&self.root)? {
+            let folder = entry?.path();
+            if folder.is_dir() == false || folder.ends_with(METADATA_FOLDER) {
+                continue;
+            }
+            match Self::read_calendar(&folder) {
+                Err(err) => log::error!("Unable to load calendar {:?}: {}", folder, err),
+                Ok(None) => log::info!("Ignoring {:?}, that has not been created by kitchen-fridge", folder),
+                Ok(Some(record)) => {
+                    self.folders.insert(record.url.clone(), folder);
+                    calendars.push(record);
+                },
+            }
+        }
+        Ok(calendars)
+    }
+
+    fn load_items(&mut self, calendar_url: &Url) -> Result<Vec<ItemRecord>, Box<dyn Error>> {
+        let (folder, mut state) = self.existing_state(calendar_url)?;
+        let mut items = Vec::new();
+        for entry in fs::read_dir(&folder)? {
+            let path = entry?.path();
+            match path.extension().and_then(|ext| ext.to_str()) {
+                Some(ITEM_EXTENSION) | Some(CONTACT_EXTENSION) => (),
+                _ => continue,
+            }
+            let file_name = match path.file_name().and_then(|name| name.to_str()) {
+                None => continue,
+                Some(name) => name.to_string(),
+            };
+
+            let (url, sync_status, rejection) = match state.items.remove(&file_name) {
+                // This file has been created by another app
+                None => (calendar_url.join(&file_name)?, SyncStatus::NotSynced, None),
+                Some(item_state) => {
+                    let modified_elsewhere = item_state.modified.is_some() && item_state.modified != modification_date(&path);
+                    let sync_status = match item_state.sync_status {
+                        SyncStatus::Synced(version_tag) if modified_elsewhere => SyncStatus::LocallyModified(version_tag),
+                        sync_status => sync_status,
+                    };
+                    (item_state.url, sync_status, item_state.rejection)
+                },
+            };
+            let content = fs::read_to_string(&path)?;
+            match crate::ical::parse(&content, url, sync_status) {
+                Err(err) => log::error!("Unable to load item {:?}: {}", path, err),
+                Ok(item) => items.push(ItemRecord::Item { item, rejection }),
+            }
+        }
+
+        // The files that are left have been deleted by another app
+        for (file_name, item_state) in state.items {
+            let version_tag = match &item_state.sync_status {
+                // This has never been pushed to the server
+                SyncStatus::NotSynced => continue,
+                SyncStatus::Synced(tag) | SyncStatus::LocallyModified(tag) | SyncStatus::LocallyDeleted(tag) => tag.clone(),
+            };
+            let url = item_state.url.clone();
+            match tombstone(calendar_url, &file_name, item_state, version_tag) {
+                None => log::info!("Item {} has been deleted by another app, but its deletion cannot be pushed. It will be downloaded again at the next sync", url),
+                Some(item) => items.push(ItemRecord::Item { item, rejection: None }),
+            }
+        }
+        items.extend(state.evicted_items.into_iter()
+            .map(|(url, version_tag)| ItemRecord::Evicted { url, version_tag }));
+        Ok(items)
+    }
+
+    fn save_calendar(&mut self, calendar: &CalendarRecord) -> Result<(), Box<dyn Error>> {
+        let folder = self.folder(&calendar.url);
+        fs::create_dir_all(&folder)?;
+        let state = match Self::read_state(&folder)? {
+            None => CollectionState { calendar: calendar.clone(), items: HashMap::new(), evicted_items: HashMap::new() },
+            Some(mut state) => {
+                state.calendar = calendar.clone();
+                state
+            },
+        };
+        write_text(&folder.join(DISPLAYNAME_FILE), Some(&calendar.name))?;
+        write_text(&folder.join(COLOR_FILE), calendar.color.as_ref().map(|color| color.to_hex_string()).as_deref())?;
+        Self::write_state(&folder, &state)
+    }
+
+    fn delete_calendar(&mut self, calendar_url: &Url) -> Result<(), Box<dyn Error>> {
+        let folder = match self.folders.remove(calendar_url) {
+            None => return Ok(()),
+            Some(folder) => folder,
+        };
+        match fs::remove_dir_all(&folder) {
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err.into()),
+            Ok(()) => Ok(()),
+        }
+    }
+
+    /// Item files are written first, then the state of the calendar, so that an interrupted save only leaves items that look modified (and that are pushed again at the next sync)
+    fn save_items(&mut self, calendar_url: &Url, changes: Vec<ItemChange>) -> Result<(), Box<dyn Error>> {
+        let (folder, mut state) = self.existing_state(calendar_url)?;
+        for change in changes {
+            let file_name = item_file_name(change.url());
+            let path = folder.join(&file_name);
+            state.evicted_items.remove(change.url());
+            match change {
+                ItemChange::Saved(ItemRecord::Item { item, rejection }) => {
+                    let content = crate::ical::build_from(&item)?;
+                    write_atomically(&path, |writer| Ok(writer.write_all(content.as_bytes())?))?;
+                    state.items.insert(file_name, ItemState {
+                        url: item.url().clone(),
+                        sync_status: item.sync_status().clone(),
+                        rejection,
+                        modified: modification_date(&path),
+                        uid: Some(item.uid().clone()),
+                        component: Some(component_of(&item)),
+                    });
+                },
+                ItemChange::Saved(ItemRecord::Evicted { url, version_tag }) => {
+                    state.items.remove(&file_name);
+                    let _ = fs::remove_file(&path);
+                    state.evicted_items.insert(url, version_tag);
+                },
+                ItemChange::Removed(_) => {
+                    state.items.remove(&file_name);
+                    match fs::remove_file(&path) {
+                        Err(err) if err.kind() == ErrorKind::NotFound => (),
+                        Err(err) => return Err(err.into()),
+                        Ok(()) => (),
+                    }
+                },
+            }
+        }
+        Self::write_state(&folder, &state)
+    }
+
+    fn load_metadata(&mut self, name: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        match fs::read(self.root.join(METADATA_FOLDER).join(name)) {
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+            Ok(data) => Ok(Some(data)),
+        }
+    }
+
+    fn save_metadata(&mut self, name: &str, data: &[u8]) -> Result<(), Box<dyn Error>> {
+        let folder = self.root.join(METADATA_FOLDER);
+        fs::create_dir_all(&folder)?;
+        write_atomically(&folder.join(name), |writer| Ok(writer.write_all(data)?))
+    }
+}
+
+impl PersistentCache<VdirStorage> {
+    /// Open (or create) a cache in a vdir
+    pub fn open(root: &Path) -> Result<Self, Box<dyn Error>> {
+        Self::new(VdirStorage::open(root)?)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::{Arc, Mutex};
+
+    use crate::traits::{BaseCalendar, CalDavSource, CompleteCalendar};
+
+    #[tokio::test]
+    async fn vdir_cache_persistence() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let root = PathBuf::from(String::from("test_cache/vdir_test"));
+        let _ = fs::remove_dir_all(&root);
+
+        let cal_url = Url::parse("https://caldav.com/calendars/john/shopping/").unwrap();
+        let (synced_url, deleted_url, deleted_elsewhere_url) = {
+            let mut cache = VdirCache::open(&root).unwrap();
+            let cal = cache.create_calendar(cal_url.clone(), "My shopping list".to_string(), SupportedComponents::TODO, Some(csscolorparser::parse("#ff8000").unwrap())).await.unwrap();
+            let mut cal = cal.write().unwrap();
+
+            let mut synced = Task::new(String::from("Milk"), false, &cal_url);
+            synced.set_sync_status(SyncStatus::Synced(VersionTag::from(String::from("v1"))));
+            let synced_url = synced.url().clone();
+            cal.add_item(Item::Task(synced)).await.unwrap();
+            let mut deleted_elsewhere = Task::new(String::from("Butter"), false, &cal_url);
+            deleted_elsewhere.set_sync_status(SyncStatus::Synced(VersionTag::from(String::from("v2"))));
+            let deleted_elsewhere_url = deleted_elsewhere.url().clone();
+            cal.add_item(Item::Task(deleted_elsewhere)).await.unwrap();
+            let deleted = Task::new(String::from("Eggs"), false, &cal_url);
+            let deleted_url = deleted.url().clone();
+            cal.add_item(Item::Task(deleted)).await.unwrap();
+            cal.immediately_delete_item(&deleted_url).await.unwrap();
+            (synced_url, deleted_url, deleted_elsewhere_url)
+        };
+
+        let folder = root.join("shopping");
+        assert_eq!(read_text(&folder.join(DISPLAYNAME_FILE)).unwrap().as_deref(), Some("My shopping list"));
+        assert_eq!(read_text(&folder.join(COLOR_FILE)).unwrap().as_deref(), Some("#ff8000"));
+        assert!(folder.join(item_file_name(&synced_url)).exists());
+        assert_eq!(folder.join(item_file_name(&deleted_url)).exists(), false);
+
+        // Another app (e.g. todoman) renames the calendar, adds a task and deletes another one
+        fs::write(folder.join(DISPLAYNAME_FILE), "Groceries\n").unwrap();
+        let deleted_elsewhere_uid = {
+            let content = fs::read_to_string(folder.join(item_file_name(&deleted_elsewhere_url))).unwrap();
+            crate::ical::parse(&content, deleted_elsewhere_url.clone(), SyncStatus::NotSynced).unwrap().uid().clone()
+        };
+        fs::remove_file(folder.join(item_file_name(&deleted_elsewhere_url))).unwrap();
+        fs::write(folder.join("added-elsewhere.ics"), "BEGIN:VCALENDAR\nVERSION:2.0\nPRODID:-//todoman\nBEGIN:VTODO\nUID:added-elsewhere\nSUMMARY:Bread\nEND:VTODO\nEND:VCALENDAR\n").unwrap();
+
+        let cache = VdirCache::open(&root).unwrap();
+        let cal = cache.get_calendar(&cal_url).await.unwrap();
+        let cal = cal.read().unwrap();
+        assert_eq!(cal.name(), "Groceries");
+        assert!(cal.has_modified_properties());
+        assert_eq!(cal.item_count(), 3);
+        assert!(matches!(cal.get_item_by_url(&synced_url).await.unwrap().sync_status(), SyncStatus::Synced(_)));
+        let tombstone = cal.get_item_by_url(&deleted_elsewhere_url).await.unwrap();
+        assert_eq!(tombstone.sync_status(), &SyncStatus::LocallyDeleted(VersionTag::from(String::from("v2"))));
+        assert_eq!(tombstone.uid(), &deleted_elsewhere_uid);
+        assert!(tombstone.is_task());
+        let added = cal.get_item_by_url(&cal_url.join("added-elsewhere.ics").unwrap()).await.unwrap();
+        assert_eq!(added.name(), "Bread");
+        assert_eq!(added.sync_status(), &SyncStatus::NotSynced);
+    }
+
+    #[tokio::test]
+    async fn vdir_cache_change_hooks() {
+        let root = PathBuf::from(String::from("test_cache/vdir_change_hooks"));
+        let _ = fs::remove_dir_all(&root);
+
+        let cal_url = Url::parse("https://caldav.com/calendars/john/chores/").unwrap();
+        let (item_url, seq) = {
+            let mut cache = VdirCache::open(&root).unwrap();
+            let calendars = Arc::new(Mutex::new(Vec::new()));
+            let calendars_ = calendars.clone();
+            cache.on_calendar_changed(move |url| calendars_.lock().unwrap().push(url.clone()));
+
+            let cal = cache.create_calendar(cal_url.clone(), "Chores".to_string(), SupportedComponents::TODO, None).await.unwrap();
+            let task = Task::new(String::from("Vacuum"), false, &cal_url);
+            let item_url = task.url().clone();
+            cal.write().unwrap().add_item(Item::Task(task)).await.unwrap();
+            assert_eq!(*calendars.lock().unwrap(), vec![cal_url.clone()]);
+            assert_eq!(cache.changes_since(0).unwrap().len(), 1);
+            (item_url, cache.current_change_seq())
+        };
+
+        // The changelog is kept, and the changes of calendars that are loaded lazily are recorded as well
+        let cache = VdirCache::open(&root).unwrap();
+        assert_eq!(cache.current_change_seq(), seq);
+        let deleted = Arc::new(Mutex::new(Vec::new()));
+        let deleted_ = deleted.clone();
+        cache.on_item_deleted(move |record| deleted_.lock().unwrap().push(record.item_url().clone()));
+
+        let cal = cache.get_calendar(&cal_url).await.unwrap();
+        assert!(cache.changes_since(seq).unwrap().is_empty());
+        cal.write().unwrap().mark_for_deletion(&item_url).await.unwrap();
+        assert_eq!(*deleted.lock().unwrap(), vec![item_url]);
+        assert_eq!(cache.changes_since(seq).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,344 @@
+//! Read-only subscriptions to iCal feeds (e.g. holidays or sports schedules), that are published as plain `.ics` files over HTTP(S)
+//!
+//! A [`WebcalSource`] can be the remote source of a [`Provider`](crate::provider::Provider), so that feeds are synced into a local cache, just like CalDAV calendars.
+//! Feeds are only downloaded again when they have changed (thanks to their `ETag` or `Last-Modified` headers).
+//!
+//! To merge subscriptions into the same cache as CalDAV calendars, sync this cache with both sources in turn (see [`Provider::into_parts`](crate::provider::Provider::into_parts)),
+//! and make sure each provider leaves the calendars of the other source alone (see [`WebcalSource::calendar_filter`] and [`WebcalSource::other_calendars_filter`]).
+//! Otherwise, each provider would believe the calendars of the other one have been deleted from its server.
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::sync::{Arc, Mutex, RwLock};
+
+use async_trait::async_trait;
+use csscolorparser::Color;
+use reqwest::header::{HeaderName, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
+use url::Url;
+
+use crate::auth::Authentication;
+use crate::calendar::{CalendarProperties, Privileges, SupportedComponents};
+use crate::error::{ReadOnlyError, ServerError};
+use crate::item::{Item, SyncStatus, VersionTag};
+use crate::provider::calendar_selection::CalendarFilter;
+use crate::resource::Resource;
+use crate::traits::{BaseCalendar, CalDavSource, DavCalendar};
+
+/// A set of subscriptions to iCal feeds. This is a read-only [`CalDavSource`]: calendars cannot be created or deleted, use [`Self::subscribe`] and [`Self::unsubscribe`] instead
+#[derive(Debug)]
+pub struct WebcalSource {
+    /// The settings feeds are downloaded with (credentials, retry policy, TLS settings, proxy, etc.)
+    template: Resource,
+    calendars: HashMap<Url, Arc<RwLock<WebcalCalendar>>>,
+}
+
+impl WebcalSource {
+    /// A source for public feeds, that are downloaded without any credentials
+    pub fn new() -> Self {
+        Self::with_template(Resource::with_authentication(Url::parse("https://localhost/").unwrap(), Authentication::None))
+    }
+
+    /// A source whose feeds are downloaded with the same settings as `template` (e.g. `Resource::new(url, username, password)` for feeds that require credentials, with a given proxy)
+    pub fn with_template(template: Resource) -> Self {
+        Self { template, calendars: HashMap::new() }
+    }
+
+    /// Subscribe to a feed. `webcal://` URLs are downloaded over HTTPS.
+    ///
+    /// The feed is only downloaded at the next sync
+    pub fn subscribe(&mut self, url: &str, name: String, color: Option<Color>) -> Result<Arc<RwLock<WebcalCalendar>>, Box<dyn Error>> {
+        let url = feed_url(url)?;
+        let calendar = Arc::new(RwLock::new(WebcalCalendar::new(name, self.template.with_url(url.clone()), SupportedComponents::all(), color)));
+        self.calendars.insert(url, calendar.clone());
+        Ok(calendar)
+    }
+
+    /// Stop following a feed. Its local copy is deleted at the next sync
+    pub fn unsubscribe(&mut self, url: &Url) -> Option<Arc<RwLock<WebcalCalendar>>> {
+        self.calendars.remove(url)
+    }
+
+    /// The URLs of the feeds of this source
+    pub fn subscriptions(&self) -> HashSet<Url> {
+        self.calendars.keys().cloned().collect()
+    }
+
+    /// A filter for the provider that syncs this source, so that it only handles the current subscriptions
+    pub fn calendar_filter(&self) -> CalendarFilter {
+        let subscriptions = self.subscriptions();
+        CalendarFilter::new(move |url, _name| subscriptions.contains(url))
+    }
+
+    /// A filter for the providers that sync other sources into the same local cache, so that they leave the current subscriptions alone
+    pub fn other_calendars_filter(&self) -> CalendarFilter {
+        let subscriptions = self.subscriptions();
+        CalendarFilter::new(move |url, _name| subscriptions.contains(url) == false)
+    }
+}
+
+impl Default for WebcalSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The URL a feed is downloaded from
+fn feed_url(url: &str) -> Result<Url, Box<dyn Error>> {
+    let url = match url.strip_prefix("webcal://") {
+        Some(rest) => Url::parse(&format!("https://{}", rest))?,
+        None => Url::parse(url)?,
+    };
+    match url.scheme() {
+        "http" | "https" => Ok(url),
+        scheme => Err(format!("Unsupported scheme {} for feed {}", scheme, url).into()),
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl CalDavSource<WebcalCalendar> for WebcalSource {
+    async fn get_calendars(&self) -> Result<HashMap<Url, Arc<RwLock<WebcalCalendar>>>, crate::Error> {
+        Ok(self.calendars.clone())
+    }
+
+    async fn get_calendar(&self, url: &Url) -> Option<Arc<RwLock<WebcalCalendar>>> {
+        self.calendars.get(url).cloned()
+    }
+
+    async fn create_calendar(&mut self, url: Url, _name: String, _supported_components: SupportedComponents, _color: Option<Color>) -> Result<Arc<RwLock<WebcalCalendar>>, crate::Error> {
+        Err(format!("Cannot create calendar {}: feeds are read-only (use WebcalSource::subscribe instead)", url).into())
+    }
+
+    async fn delete_calendar(&mut self, url: &Url) -> Result<(), crate::Error> {
+        Err(format!("Cannot delete calendar {}: feeds are read-only (use WebcalSource::unsubscribe instead)", url).into())
+    }
+}
+
+
+
+/// What has been downloaded from a feed
+#[derive(Debug, Default)]
+struct FeedState {
+    /// Whether the feed has been downloaded at least once
+    fetched: bool,
+    items: HashMap<Url, Item>,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+/// A read-only calendar, whose items come from an iCal feed (see [`WebcalSource`]).
+///
+/// Items are identified by the URL of the feed, followed by their UID as a fragment (e.g. `https://example.com/holidays.ics#new-year@example.com`)
+#[derive(Debug)]
+pub struct WebcalCalendar {
+    name: String,
+    resource: Resource,
+    supported_components: SupportedComponents,
+    color: Option<Color>,
+
+    feed: Mutex<FeedState>,
+}
+
+impl WebcalCalendar {
+    /// Download the feed, unless it has not changed since the last time it was downloaded
+    async fn fetch(&self) -> Result<(), Box<dyn Error>> {
+        let (etag, last_modified) = {
+            let feed = self.feed.lock().unwrap();
+            (feed.etag.clone(), feed.last_modified.clone())
+        };
+
+        let mut request = self.resource.http().get(self.resource.url().clone());
+        if let Some(etag) = etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        let response = self.resource.send(request).await?;
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            log::debug!("Feed {} has not changed", self.url());
+            return Ok(());
+        }
+        if response.status().is_success() == false {
+            return Err(ServerError::from_response(response).await.into());
+        }
+
+        let header = |name: HeaderName| response.headers().get(name).and_then(|value| value.to_str().ok()).map(String::from);
+        let etag = header(ETAG);
+        let last_modified = header(LAST_MODIFIED);
+        // Legacy feeds are sometimes served in latin-1, whatever their Content-Type says
+        let content = crate::ical::decode_text(&response.bytes().await?);
+        let items = parse_feed(&content, self.url())?;
+
+        // Note: the mutex cannot be locked during this whole async function, but concurrent fetches would only waste a request
+        *self.feed.lock().unwrap() = FeedState { fetched: true, items, etag, last_modified };
+        Ok(())
+    }
+
+    /// Download the feed, unless it has been downloaded already
+    async fn ensure_fetched(&self) -> Result<(), Box<dyn Error>> {
+        if self.feed.lock().unwrap().fetched {
+            return Ok(());
+        }
+        self.fetch().await
+    }
+
+    fn read_only_error(&self, missing: Privileges) -> crate::Error {
+        ReadOnlyError::new(self.url().clone(), missing).into()
+    }
+}
+
+/// Parse the items of a feed, and give them stable URLs and version tags
+fn parse_feed(content: &str, feed_url: &Url) -> Result<HashMap<Url, Item>, Box<dyn Error>> {
+    let mut items = HashMap::new();
+    for mut item in crate::ical::parse_calendar(content, feed_url)? {
+        let mut url = feed_url.clone();
+        url.set_fragment(Some(item.uid().as_str()));
+        item.set_url(url.clone());
+
+        // Feeds do not have a version tag per item, a hash of their content is used instead
+        let ical_text = crate::ical::build_from(&item)?;
+        let version_tag = VersionTag::from(format!("{:x}", md5::compute(ical_text)));
+        item.set_sync_status(SyncStatus::Synced(version_tag));
+        items.insert(url, item);
+    }
+    Ok(items)
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl BaseCalendar for WebcalCalendar {
+    fn name(&self) -> &str { &self.name }
+    fn url(&self) -> &Url { self.resource.url() }
+    fn supported_components(&self) -> SupportedComponents {
+        self.supported_components
+    }
+    fn color(&self) -> Option<&Color> {
+        self.color.as_ref()
+    }
+    fn privileges(&self) -> Privileges {
+        Privileges::READ
+    }
+
+    async fn add_item(&mut self, _item: Item) -> Result<SyncStatus, crate::Error> {
+        Err(self.read_only_error(Privileges::BIND))
+    }
+
+    async fn update_item(&mut self, _item: Item) -> Result<SyncStatus, crate::Error> {
+        Err(self.read_only_error(Privileges::WRITE_CONTENT))
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl DavCalendar for WebcalCalendar {
+    fn new(name: String, resource: Resource, supported_components: SupportedComponents, color: Option<Color>) -> Self {
+        Self {
+            name, resource, supported_components, color,
+            feed: Mutex::new(FeedState::default()),
+        }
+    }
+
+    async fn get_item_version_tags(&self) -> Result<HashMap<Url, VersionTag>, crate::Error> {
+        self.fetch().await?;
+        Ok(self.feed.lock().unwrap().items.iter()
+            .filter_map(|(url, item)| item.sync_status().version_tag().map(|tag| (url.clone(), tag.clone())))
+            .collect())
+    }
+
+    async fn get_item_by_url(&self, url: &Url) -> Result<Option<Item>, crate::Error> {
+        self.ensure_fetched().await?;
+        Ok(self.feed.lock().unwrap().items.get(url).cloned())
+    }
+
+    async fn get_items_by_url(&self, urls: &[Url]) -> Result<Vec<Option<Item>>, crate::Error> {
+        self.ensure_fetched().await?;
+        let feed = self.feed.lock().unwrap();
+        Ok(urls.iter().map(|url| feed.items.get(url).cloned()).collect())
+    }
+
+    async fn delete_item(&mut self, _item_url: &Url) -> Result<(), crate::Error> {
+        Err(self.read_only_error(Privileges::UNBIND))
+    }
+
+    async fn update_properties(&mut self, _properties: &CalendarProperties) -> Result<(), crate::Error> {
+        Err(self.read_only_error(Privileges::WRITE_PROPERTIES))
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use reqwest::{Request, Response};
+
+    use crate::transport::HttpTransport;
+
+    const FEED: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:-//Example//Holidays//EN\r
+BEGIN:VEVENT\r
+UID:new-year@example.com\r
+DTSTAMP:20210101T000000Z\r
+DTSTART;VALUE=DATE:20220101\r
+SUMMARY:New Year's Day\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:christmas@example.com\r
+DTSTAMP:20210101T000000Z\r
+DTSTART;VALUE=DATE:20211225\r
+SUMMARY:Christmas\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+    /// A server that serves `FEED`, and replies `304 Not Modified` to requests that already have its ETag
+    struct FeedServer {
+        requests: Mutex<u32>,
+    }
+
+    #[async_trait]
+    impl HttpTransport for FeedServer {
+        async fn execute(&self, request: Request) -> Result<Response, Box<dyn Error>> {
+            *self.requests.lock().unwrap() += 1;
+            let response = match request.headers().get(IF_NONE_MATCH).map(|etag| etag.as_bytes()) {
+                Some(b"\"v1\"") => http::Response::builder().status(304).body(String::new())?,
+                _ => http::Response::builder().status(200).header(ETAG, "\"v1\"").body(FEED.to_string())?,
+            };
+            Ok(Response::from(response))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_webcal_feed() {
+        let server = Arc::new(FeedServer { requests: Mutex::new(0) });
+        let mut source = WebcalSource::new();
+        let calendar = source.subscribe("webcal://example.com/holidays.ics", "Holidays".to_string(), None).unwrap();
+        calendar.write().unwrap().resource.set_transport(Some(server.clone()));
+        let feed_url = Url::parse("https://example.com/holidays.ics").unwrap();
+        assert!(source.calendar_filter().accepts(&feed_url, "Holidays"));
+        assert!(source.other_calendars_filter().accepts(&feed_url, "Holidays") == false);
+
+        let calendar = source.get_calendar(&feed_url).await.unwrap();
+        let mut calendar = calendar.write().unwrap();
+        let tags = calendar.get_item_version_tags().await.unwrap();
+        assert_eq!(tags.len(), 2);
+
+        // The feed has not changed, it is not downloaded again
+        assert_eq!(calendar.get_item_version_tags().await.unwrap(), tags);
+        assert_eq!(*server.requests.lock().unwrap(), 2);
+
+        let christmas_url = Url::parse("https://example.com/holidays.ics#christmas@example.com").unwrap();
+        let christmas = calendar.get_item_by_url(&christmas_url).await.unwrap().unwrap();
+        assert_eq!(christmas.name(), "Christmas");
+        assert_eq!(christmas.sync_status(), &SyncStatus::Synced(tags[&christmas_url].clone()));
+
+        let err = calendar.delete_item(&christmas_url).await.unwrap_err();
+        assert!(matches!(err, crate::Error::ReadOnly(_)));
+        assert!(source.create_calendar(feed_url, "Other".to_string(), SupportedComponents::EVENT, None).await.is_err());
+    }
+}
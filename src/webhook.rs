@@ -0,0 +1,230 @@
+//! A minimal HTTP listener, that external systems (e.g. Nextcloud Flow, cron jobs or home automation) can call to trigger a sync.
+//!
+//! This is only available with the `webhook` Cargo feature.
+//!
+//! The listener does not sync anything by itself: it forwards [`SyncRequest`]s to a channel, so that the app decides when (and with which provider) to run them:
+//! ```no_run
+//! # async fn example(mut provider: kitchen_fridge::CalDavProvider) -> std::io::Result<()> {
+//! use kitchen_fridge::webhook::WebhookServer;
+//!
+//! let (sender, mut requests) = tokio::sync::mpsc::channel(8);
+//! let server = WebhookServer::bind("127.0.0.1:8642").await?;
+//! tokio::spawn(server.run(sender));
+//!
+//! while let Some(request) = requests.recv().await {
+//!     provider.handle_sync_request(&request).await;
+//!     provider.local().save_to_folder().ok();
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Requests are `POST /sync` (to sync every calendar) or `POST /sync?calendar=<url>&calendar=<url>` (to sync only some calendars).
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::sync::mpsc::Sender;
+use tokio::sync::Semaphore;
+use url::Url;
+
+/// Requests larger than this are refused
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+/// Callers that take longer than this to send their request are disconnected
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+/// At most this many connections are handled at the same time, further ones wait until one of them is closed
+const MAX_CONNECTIONS: usize = 16;
+
+/// What a webhook call asks to sync
+#[derive(Clone, Debug, PartialEq)]
+pub enum SyncRequest {
+    /// Sync every calendar
+    All,
+    /// Only sync these calendars
+    Calendars(Vec<Url>),
+}
+
+/// An HTTP listener that turns calls to `/sync` into [`SyncRequest`]s
+pub struct WebhookServer {
+    listener: TcpListener,
+    token: Option<String>,
+}
+
+impl WebhookServer {
+    pub async fn bind<A: ToSocketAddrs>(addr: A) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        Ok(Self { listener, token: None })
+    }
+
+    /// Require callers to provide this token, either as an `Authorization: Bearer <token>` header, or as a `token=<token>` query parameter
+    pub fn set_token(&mut self, token: Option<String>) {
+        self.token = token;
+    }
+
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accept connections until the receiving end of `requests` is dropped
+    pub async fn run(self, requests: Sender<SyncRequest>) -> std::io::Result<()> {
+        let connections = Arc::new(Semaphore::new(MAX_CONNECTIONS));
+        loop {
+            let permit = connections.clone().acquire_owned().await.unwrap(/* the semaphore is never closed */);
+            let (stream, peer) = self.listener.accept().await?;
+            if requests.is_closed() {
+                return Ok(());
+            }
+            let requests = requests.clone();
+            let token = self.token.clone();
+            tokio::spawn(async move {
+                if let Err(err) = handle_connection(stream, token.as_deref(), requests).await {
+                    log::warn!("Unable to handle webhook call from {}: {}", peer, err);
+                }
+                drop(permit);
+            });
+        }
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, token: Option<&str>, requests: Sender<SyncRequest>) -> std::io::Result<()> {
+    let head = match tokio::time::timeout(READ_TIMEOUT, read_request_head(&mut stream)).await {
+        Err(_elapsed) => return respond(&mut stream, "408 Request Timeout").await,
+        Ok(head) => match head? {
+            None => return respond(&mut stream, "413 Payload Too Large").await,
+            Some(head) => head,
+        },
+    };
+
+    let status = match parse_request(&head, token) {
+        Err(status) => status,
+        Ok(request) => {
+            log::info!("Webhook call: {:?}", request);
+            match requests.send(request).await {
+                Ok(()) => "202 Accepted",
+                Err(_) => "503 Service Unavailable",
+            }
+        },
+    };
+    respond(&mut stream, status).await
+}
+
+/// Read the request line and headers (the body, if any, is ignored). Returns `None` if they are too large
+async fn read_request_head(stream: &mut TcpStream) -> std::io::Result<Option<String>> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 1024];
+    loop {
+        let n = stream.read(&mut chunk).await?;
+        buffer.extend_from_slice(&chunk[..n]);
+        if let Some(end) = find_subsequence(&buffer, b"\r\n\r\n") {
+            buffer.truncate(end);
+            return Ok(Some(String::from_utf8_lossy(&buffer).into_owned()));
+        }
+        if n == 0 {
+            return Ok(Some(String::from_utf8_lossy(&buffer).into_owned()));
+        }
+        if buffer.len() > MAX_REQUEST_SIZE {
+            return Ok(None);
+        }
+    }
+}
+
+fn find_subsequence(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+/// Parse the head of an HTTP request into a [`SyncRequest`], or into the HTTP status to reply with
+fn parse_request(head: &str, token: Option<&str>) -> Result<SyncRequest, &'static str> {
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default();
+    let target = request_line.next().ok_or("400 Bad Request")?;
+
+    // Only the path and the query matter, let's use a dummy base to parse them
+    let url = Url::parse("http://localhost/").unwrap(/* this is a valid URL */)
+        .join(target)
+        .map_err(|_| "400 Bad Request")?;
+    if url.path() != "/sync" {
+        return Err("404 Not Found");
+    }
+    if method != "POST" {
+        return Err("405 Method Not Allowed");
+    }
+
+    if let Some(expected) = token {
+        let bearer = format!("Bearer {}", expected);
+        let in_headers = lines
+            .filter_map(|line| line.split_once(':'))
+            .any(|(name, value)| name.trim().eq_ignore_ascii_case("Authorization") && constant_time_eq(value.trim(), &bearer));
+        let in_query = url.query_pairs().any(|(k, v)| k == "token" && constant_time_eq(&v, expected));
+        if in_headers == false && in_query == false {
+            return Err("401 Unauthorized");
+        }
+    }
+
+    let mut calendars = Vec::new();
+    for (key, value) in url.query_pairs() {
+        if key == "calendar" {
+            calendars.push(value.parse().map_err(|_| "400 Bad Request")?);
+        }
+    }
+    match calendars.is_empty() {
+        true => Ok(SyncRequest::All),
+        false => Ok(SyncRequest::Calendars(calendars)),
+    }
+}
+
+/// Compare a secret without telling how many of its first bytes are correct through the time this takes (only its length may leak)
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+async fn respond(stream: &mut TcpStream, status: &str) -> std::io::Result<()> {
+    let response = format!("HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request() {
+        assert_eq!(parse_request("POST /sync HTTP/1.1\r\nHost: localhost", None), Ok(SyncRequest::All));
+        assert_eq!(
+            parse_request("POST /sync?calendar=https%3A%2F%2Fcal.example%2Fwork%2F HTTP/1.1", None),
+            Ok(SyncRequest::Calendars(vec!["https://cal.example/work/".parse().unwrap()]))
+        );
+        assert_eq!(parse_request("GET /sync HTTP/1.1", None), Err("405 Method Not Allowed"));
+        assert_eq!(parse_request("POST /other HTTP/1.1", None), Err("404 Not Found"));
+
+        assert_eq!(parse_request("POST /sync HTTP/1.1", Some("s3cret")), Err("401 Unauthorized"));
+        assert_eq!(parse_request("POST /sync?token=s3cret HTTP/1.1", Some("s3cret")), Ok(SyncRequest::All));
+        assert_eq!(parse_request("POST /sync HTTP/1.1\r\nauthorization: Bearer s3cret", Some("s3cret")), Ok(SyncRequest::All));
+        assert_eq!(parse_request("POST /sync?token=s3creT HTTP/1.1", Some("s3cret")), Err("401 Unauthorized"));
+
+        assert!(constant_time_eq("s3cret", "s3cret"));
+        assert!(constant_time_eq("s3cret", "s3cre") == false);
+        assert!(constant_time_eq("s3cret", "S3cret") == false);
+    }
+
+    #[tokio::test]
+    async fn test_webhook_call() {
+        let server = WebhookServer::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+        let (sender, mut requests) = tokio::sync::mpsc::channel(1);
+        tokio::spawn(server.run(sender));
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(b"POST /sync HTTP/1.1\r\nHost: localhost\r\n\r\n").await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 202"));
+        assert_eq!(requests.recv().await, Some(SyncRequest::All));
+    }
+}
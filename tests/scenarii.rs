@@ -9,7 +9,7 @@
 #![cfg(feature = "local_calendar_mocks_remote_calendars")]
 
 use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, RwLock};
 use std::error::Error;
 use url::Url;
 
@@ -23,8 +23,8 @@ use kitchen_fridge::traits::DavCalendar;
 use kitchen_fridge::cache::Cache;
 use kitchen_fridge::Item;
 use kitchen_fridge::item::SyncStatus;
-use kitchen_fridge::Task;
-use kitchen_fridge::task::CompletionStatus;
+use kitchen_fridge::item::Uid;
+use kitchen_fridge::task::{CompletionStatus, TaskBuilder};
 use kitchen_fridge::calendar::cached_calendar::CachedCalendar;
 use kitchen_fridge::provider::Provider;
 use kitchen_fridge::mock_behaviour::MockBehaviour;
@@ -39,6 +39,21 @@ pub enum LocatedState {
     Remote(ItemState),
     /// Item is synced at both locations,
     BothSynced(ItemState),
+    /// Item has been uploaded to the remote source, but a crash prevented its local sync status from being updated
+    InterruptedUpload(ItemState),
+}
+
+impl LocatedState {
+    /// The state of the item, in case it exists
+    fn state(&self) -> Option<&ItemState> {
+        match self {
+            LocatedState::None => None,
+            LocatedState::Local(state) |
+            LocatedState::Remote(state) |
+            LocatedState::BothSynced(state) |
+            LocatedState::InterruptedUpload(state) => Some(state),
+        }
+    }
 }
 
 pub struct ItemState {
@@ -376,11 +391,12 @@ pub fn scenarii_basic() -> Vec<ItemScenario> {
             initial_state: LocatedState::None,
             local_changes_to_apply: Vec::new(),
             remote_changes_to_apply: vec![ChangeToApply::Create(third_cal.clone(), Item::Task(
-                Task::new_with_parameters(
-                    String::from("Task Q, created on the server"),
-                    url_q.to_string(), url_q,
-                    CompletionStatus::Uncompleted,
-                    SyncStatus::random_synced(), Some(Utc::now()), Utc::now(), "prod_id".to_string(), Vec::new() )
+                TaskBuilder::new(String::from("Task Q, created on the server"), &third_cal)
+                    .with_uid(Uid::new(&url_q).unwrap())
+                    .with_url(url_q)
+                    .with_sync_status(SyncStatus::random_synced())
+                    .with_ical_prod_id("prod_id".to_string())
+                    .build().unwrap()
             ))],
             after_sync: LocatedState::BothSynced( ItemState{
                 calendar: third_cal.clone(),
@@ -396,11 +412,11 @@ pub fn scenarii_basic() -> Vec<ItemScenario> {
             url: url_r.clone(),
             initial_state: LocatedState::None,
             local_changes_to_apply: vec![ChangeToApply::Create(third_cal.clone(), Item::Task(
-                Task::new_with_parameters(
-                    String::from("Task R, created locally"),
-                    url_r.to_string(), url_r,
-                    CompletionStatus::Uncompleted,
-                    SyncStatus::NotSynced, Some(Utc::now()), Utc::now(), "prod_id".to_string(), Vec::new() )
+                TaskBuilder::new(String::from("Task R, created locally"), &third_cal)
+                    .with_uid(Uid::new(&url_r).unwrap())
+                    .with_url(url_r)
+                    .with_ical_prod_id("prod_id".to_string())
+                    .build().unwrap()
             ))],
             remote_changes_to_apply: Vec::new(),
             after_sync: LocatedState::BothSynced( ItemState{
@@ -573,13 +589,12 @@ pub fn scenarii_transient_task() -> Vec<ItemScenario> {
             url: url_transient.clone(),
             initial_state: LocatedState::None,
             local_changes_to_apply: vec![
-                ChangeToApply::Create(cal, Item::Task(
-                    Task::new_with_parameters(
-                        String::from("A transient task that will be deleted before the sync"),
-                        url_transient.to_string(), url_transient,
-                        CompletionStatus::Uncompleted,
-                        SyncStatus::NotSynced, Some(Utc::now()), Utc::now(),
-                        "prod_id".to_string(), Vec::new() )
+                ChangeToApply::Create(cal.clone(), Item::Task(
+                    TaskBuilder::new(String::from("A transient task that will be deleted before the sync"), &cal)
+                        .with_uid(Uid::new(&url_transient).unwrap())
+                        .with_url(url_transient)
+                        .with_ical_prod_id("prod_id".to_string())
+                        .build().unwrap()
                 )),
 
                 ChangeToApply::Rename(String::from("A new name")),
@@ -595,6 +610,34 @@ pub fn scenarii_transient_task() -> Vec<ItemScenario> {
 }
 
 
+/// This scenario tests items that have been uploaded by a sync that crashed before the local sync statuses could be saved
+pub fn scenarii_interrupted_upload() -> Vec<ItemScenario> {
+    let mut tasks = Vec::new();
+
+    let cal = Url::from("https://some.calend.ar/interrupted/".parse().unwrap());
+
+    tasks.push(
+        ItemScenario {
+            url: random_url(&cal),
+            initial_state: LocatedState::InterruptedUpload( ItemState{
+                calendar: cal.clone(),
+                name: String::from("An uploaded task"),
+                completed: false,
+            }),
+            local_changes_to_apply: Vec::new(),
+            remote_changes_to_apply: Vec::new(),
+            after_sync: LocatedState::BothSynced( ItemState{
+                calendar: cal.clone(),
+                name: String::from("An uploaded task"),
+                completed: false,
+            }),
+        }
+    );
+
+    tasks
+}
+
+
 /// Build a `Provider` that contains the data (defined in the given scenarii) before sync
 pub async fn populate_test_provider_before_sync(scenarii: &[ItemScenario], mock_behaviour: Arc<Mutex<MockBehaviour>>) -> Provider<Cache, CachedCalendar, Cache, CachedCalendar> {
     let mut provider = populate_test_provider(scenarii, mock_behaviour, false).await;
@@ -626,6 +669,10 @@ async fn populate_test_provider(scenarii: &[ItemScenario], mock_behaviour: Arc<M
                 (s, SyncStatus::random_synced())
             }
             LocatedState::BothSynced(s) => (s, SyncStatus::random_synced()),
+            LocatedState::InterruptedUpload(s) => {
+                assert!(populate_for_final_state == false, "You are not supposed to expect an item in this state after sync");
+                (s, SyncStatus::NotSynced)
+            },
         };
 
         let now = Utc::now();
@@ -635,28 +682,34 @@ async fn populate_test_provider(scenarii: &[ItemScenario], mock_behaviour: Arc<M
         };
 
         let new_item = Item::Task(
-            Task::new_with_parameters(
-                state.name.clone(),
-                item.url.to_string(),
-                item.url.clone(),
-                completion_status,
-                sync_status,
-                Some(now),
-                now,
-                "prod_id".to_string(), Vec::new(),
-            ));
+            TaskBuilder::new(state.name.clone(), &state.calendar)
+                .with_uid(Uid::new(&item.url).unwrap())
+                .with_url(item.url.clone())
+                .with_completion_status(completion_status)
+                .with_sync_status(sync_status)
+                .with_creation_date(Some(now))
+                .with_last_modified(now)
+                .with_ical_prod_id("prod_id".to_string())
+                .build().unwrap()
+            );
 
         match required_state {
             LocatedState::None => panic!("Should not happen, we've continued already"),
             LocatedState::Local(s) => {
-                get_or_insert_calendar(&mut local,  &s.calendar).await.unwrap().lock().unwrap().add_item(new_item).await.unwrap();
+                get_or_insert_calendar(&mut local,  &s.calendar).await.unwrap().write().unwrap().add_item(new_item).await.unwrap();
             },
             LocatedState::Remote(s) => {
-                get_or_insert_calendar(&mut remote, &s.calendar).await.unwrap().lock().unwrap().add_item(new_item).await.unwrap();
+                get_or_insert_calendar(&mut remote, &s.calendar).await.unwrap().write().unwrap().add_item(new_item).await.unwrap();
             },
             LocatedState::BothSynced(s) => {
-                get_or_insert_calendar(&mut local,  &s.calendar).await.unwrap().lock().unwrap().add_item(new_item.clone()).await.unwrap();
-                get_or_insert_calendar(&mut remote, &s.calendar).await.unwrap().lock().unwrap().add_item(new_item).await.unwrap();
+                get_or_insert_calendar(&mut local,  &s.calendar).await.unwrap().write().unwrap().add_item(new_item.clone()).await.unwrap();
+                get_or_insert_calendar(&mut remote, &s.calendar).await.unwrap().write().unwrap().add_item(new_item).await.unwrap();
+            },
+            LocatedState::InterruptedUpload(s) => {
+                let mut uploaded_item = new_item.clone();
+                uploaded_item.set_sync_status(SyncStatus::random_synced());
+                get_or_insert_calendar(&mut local,  &s.calendar).await.unwrap().write().unwrap().add_item(new_item).await.unwrap();
+                get_or_insert_calendar(&mut remote, &s.calendar).await.unwrap().write().unwrap().add_item(uploaded_item).await.unwrap();
             },
         }
     }
@@ -667,12 +720,7 @@ async fn populate_test_provider(scenarii: &[ItemScenario], mock_behaviour: Arc<M
 async fn apply_changes_on_provider(provider: &mut Provider<Cache, CachedCalendar, Cache, CachedCalendar>, scenarii: &[ItemScenario]) {
     // Apply changes to each item
     for item in scenarii {
-        let initial_calendar_url = match &item.initial_state {
-            LocatedState::None => None,
-            LocatedState::Local(state) => Some(state.calendar.clone()),
-            LocatedState::Remote(state) => Some(state.calendar.clone()),
-            LocatedState::BothSynced(state) => Some(state.calendar.clone()),
-        };
+        let initial_calendar_url = item.initial_state.state().map(|state| state.calendar.clone());
 
         let mut calendar_url = initial_calendar_url.clone();
         for local_change in &item.local_changes_to_apply {
@@ -687,7 +735,7 @@ async fn apply_changes_on_provider(provider: &mut Provider<Cache, CachedCalendar
 }
 
 async fn get_or_insert_calendar(source: &mut Cache, url: &Url)
-    -> Result<Arc<Mutex<CachedCalendar>>, Box<dyn Error>>
+    -> Result<Arc<RwLock<CachedCalendar>>, kitchen_fridge::Error>
 {
     match source.get_calendar(url).await {
         Some(cal) => Ok(cal),
@@ -729,8 +777,8 @@ where
     C: CompleteCalendar + DavCalendar, // in this test, we're using a calendar that mocks both kinds
 {
     let cal = source.get_calendar(calendar_url).await.unwrap();
-    let mut cal = cal.lock().unwrap();
-    let task = cal.get_item_by_url_mut(item_url).await.unwrap().unwrap_task_mut();
+    let mut cal = cal.write().unwrap();
+    let task = cal.get_item_by_url_mut(item_url).await.unwrap().into_mut().unwrap_task_mut();
 
     match change {
         ChangeToApply::Rename(new_name) => {
@@ -777,8 +825,173 @@ where
         }
         ChangeToApply::Create(calendar_url, item) => {
             let cal = source.get_calendar(calendar_url).await.unwrap();
-            cal.lock().unwrap().add_item(item.clone()).await.unwrap();
+            cal.write().unwrap().add_item(item.clone()).await.unwrap();
             calendar_url.clone()
         },
     }
 }
+
+
+
+/// Running scenarii against actual servers (see `server_matrix.rs`)
+///
+/// Remote changes cannot be forged on a real server, so scenarii are run by two "devices" (i.e. two providers, each with its own local cache) that sync with the same server:
+/// the remote changes of a device are made by the other device, that pushes them to the server before the first device syncs.
+#[cfg(feature = "server_matrix_tests")]
+#[allow(dead_code)] // sync.rs does not use it
+pub mod on_server {
+    use super::*;
+    use kitchen_fridge::CalDavProvider;
+
+    /// The URLs of the calendars the scenarii use
+    pub fn calendars(scenarii: &[ItemScenario]) -> Vec<Url> {
+        let mut calendars = Vec::new();
+        for item in scenarii {
+            let in_states = [&item.initial_state, &item.after_sync].iter()
+                .filter_map(|located| located.state())
+                .map(|state| state.calendar.clone())
+                .collect::<Vec<_>>();
+            let in_changes = item.local_changes_to_apply.iter().chain(&item.remote_changes_to_apply)
+                .filter_map(|change| match change {
+                    ChangeToApply::Create(calendar_url, _) => Some(calendar_url.clone()),
+                    _ => None,
+                });
+            for calendar_url in in_states.into_iter().chain(in_changes) {
+                if calendars.contains(&calendar_url) == false {
+                    calendars.push(calendar_url);
+                }
+            }
+        }
+        calendars
+    }
+
+    /// Run scenarii with two devices. `server_url` gives the URL on the server of every calendar of the scenarii.
+    ///
+    /// Scenarii that contain interrupted uploads are not supported
+    pub async fn run_scenarii(scenarii: &[ItemScenario], device: &mut CalDavProvider, other_device: &mut CalDavProvider, server_url: &dyn Fn(&Url) -> Url) -> Result<(), Box<dyn Error>> {
+        if scenarii.iter().any(|item| matches!(item.initial_state, LocatedState::InterruptedUpload(_))) {
+            return Err("Interrupted uploads cannot be forged on a server".into());
+        }
+        let calendar_urls: Vec<Url> = calendars(scenarii).iter().map(server_url).collect();
+
+        // Items that are synced at both locations are created by the device...
+        for item in scenarii {
+            if let LocatedState::BothSynced(state) = &item.initial_state {
+                add_task(device, &server_url(&state.calendar), server_item_url(&item.url, server_url), &state.name, state.completed).await?;
+            }
+        }
+        sync(device, &calendar_urls).await?;
+        sync(other_device, &calendar_urls).await?;
+
+        // ...items that are only on the server are created by the other device...
+        for item in scenarii {
+            if let LocatedState::Remote(state) = &item.initial_state {
+                add_task(other_device, &server_url(&state.calendar), server_item_url(&item.url, server_url), &state.name, state.completed).await?;
+            }
+        }
+        sync(other_device, &calendar_urls).await?;
+
+        // ...and items that are only local are not synced yet
+        for item in scenarii {
+            if let LocatedState::Local(state) = &item.initial_state {
+                add_task(device, &server_url(&state.calendar), server_item_url(&item.url, server_url), &state.name, state.completed).await?;
+            }
+        }
+
+        for item in scenarii {
+            apply_changes_on_device(device, item, &item.local_changes_to_apply, server_url).await?;
+            apply_changes_on_device(other_device, item, &item.remote_changes_to_apply, server_url).await?;
+        }
+        sync(other_device, &calendar_urls).await?;
+        sync(device, &calendar_urls).await?;
+        sync(other_device, &calendar_urls).await?;
+
+        check_device(device, scenarii, server_url).await
+            .map_err(|err| format!("Unexpected content after the sync: {}", err))?;
+        check_device(other_device, scenarii, server_url).await
+            .map_err(|err| format!("Unexpected content on the other device: {}", err).into())
+    }
+
+    async fn sync(device: &mut CalDavProvider, calendar_urls: &[Url]) -> Result<(), Box<dyn Error>> {
+        match device.sync_calendars(calendar_urls).await {
+            true => Ok(()),
+            false => Err("Sync did not complete".into()),
+        }
+    }
+
+    /// The URL on the server of an item of the scenarii
+    fn server_item_url(url: &Url, server_url: &dyn Fn(&Url) -> Url) -> Url {
+        let calendar_url = url.join(".").unwrap();
+        let file_name = url.path_segments().and_then(|mut segments| segments.next_back()).unwrap_or_default();
+        server_url(&calendar_url).join(file_name).unwrap()
+    }
+
+    /// Add a task to the local cache of a device (it is pushed to the server at the next sync)
+    async fn add_task(device: &mut CalDavProvider, calendar_url: &Url, url: Url, name: &str, completed: bool) -> Result<(), Box<dyn Error>> {
+        let completion_status = match completed {
+            false => CompletionStatus::Uncompleted,
+            true => CompletionStatus::Completed(Some(Utc::now())),
+        };
+        let task = TaskBuilder::new(name.to_string(), calendar_url)
+            .with_uid(Uid::new(&url)?)
+            .with_url(url)
+            .with_completion_status(completion_status)
+            .build()?;
+        get_or_insert_calendar(device.local_mut(), calendar_url).await?.write().unwrap().add_item(Item::Task(task)).await?;
+        Ok(())
+    }
+
+    /// Apply changes as local changes of a device
+    async fn apply_changes_on_device(device: &mut CalDavProvider, item: &ItemScenario, changes: &[ChangeToApply], server_url: &dyn Fn(&Url) -> Url) -> Result<(), Box<dyn Error>> {
+        let url = server_item_url(&item.url, server_url);
+        let mut calendar_url = item.initial_state.state().map(|state| server_url(&state.calendar));
+        for change in changes {
+            calendar_url = Some(match change {
+                // Items to create have the URLs of the scenarii, they have to be created again on the server
+                ChangeToApply::Create(scenario_calendar_url, Item::Task(task)) => {
+                    let new_calendar_url = server_url(scenario_calendar_url);
+                    add_task(device, &new_calendar_url, url.clone(), task.name(), task.completed()).await?;
+                    new_calendar_url
+                },
+                ChangeToApply::Create(_, _) => return Err("Only tasks can be created on a server".into()),
+                change => apply_change(device.local(), calendar_url, &url, change, false).await,
+            });
+        }
+        Ok(())
+    }
+
+    /// Check the local cache of a device contains the items the scenarii expect after the sync
+    async fn check_device(device: &CalDavProvider, scenarii: &[ItemScenario], server_url: &dyn Fn(&Url) -> Url) -> Result<(), Box<dyn Error>> {
+        for item in scenarii {
+            let url = server_item_url(&item.url, server_url);
+            match &item.after_sync {
+                LocatedState::None => {
+                    for calendar_url in calendars(scenarii).iter().map(server_url) {
+                        if let Some(cal) = device.local().get_calendar(&calendar_url).await {
+                            if CompleteCalendar::get_item_by_url(&*cal.read().unwrap(), &url).await.is_some() {
+                                return Err(format!("item {} has not been deleted", url).into());
+                            }
+                        }
+                    }
+                },
+                LocatedState::BothSynced(expected) => {
+                    let cal = device.local().get_calendar(&server_url(&expected.calendar)).await
+                        .ok_or_else(|| format!("calendar {} is missing", expected.calendar))?;
+                    let cal = cal.read().unwrap();
+                    let task = match CompleteCalendar::get_item_by_url(&*cal, &url).await {
+                        Some(Item::Task(task)) => task,
+                        _ => return Err(format!("task \"{}\" is missing", expected.name).into()),
+                    };
+                    if task.name() != expected.name || task.completed() != expected.completed {
+                        return Err(format!("got task \"{}\" (completed: {}), expected \"{}\" (completed: {})", task.name(), task.completed(), expected.name, expected.completed).into());
+                    }
+                    if matches!(task.sync_status(), SyncStatus::Synced(_)) == false {
+                        return Err(format!("task \"{}\" is not synced", task.name()).into());
+                    }
+                },
+                _ => return Err("only synced or deleted items can be expected after a sync".into()),
+            }
+        }
+        Ok(())
+    }
+}
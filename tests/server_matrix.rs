@@ -0,0 +1,183 @@
+//! Sync tests against actual CalDAV servers
+//!
+//! Unlike `sync.rs`, that uses a local cache to mock a server, these tests talk to real servers (e.g. Radicale, Baikal, Nextcloud), so that we know how compatible this crate is with them.
+//! They are only built with the `server_matrix_tests` Cargo feature, and are configured with environment variables:
+//! * `KF_MATRIX_SERVERS`: a comma-separated list of server names (e.g. `radicale,baikal,nextcloud`)
+//! * for each server `<NAME>` (in uppercase):
+//!   * `KF_MATRIX_<NAME>_URL`: the URL to connect to
+//!   * `KF_MATRIX_<NAME>_USERNAME` and `KF_MATRIX_<NAME>_PASSWORD`
+//!   * `KF_MATRIX_<NAME>_CALENDAR_HOME`: the collection in which test calendars will be created (and deleted afterwards)
+//! * `KF_MATRIX_REPORT` (optional): where to write the compatibility report (defaults to `target/server-matrix-report.md`)
+//!
+//! `tests/server_matrix/docker-compose.yml` starts such servers locally.
+//!
+//! These tests run the scenarii of `scenarii.rs`. Remote changes cannot be forged on a real server, so every scenario syncs two "devices" (i.e. two local caches) with the same server: changes made on one device are the remote changes of the other one (see `scenarii::on_server`).
+#![cfg(feature = "server_matrix_tests")]
+
+// The helpers that populate mocked servers are not used here
+#[allow(dead_code)]
+mod scenarii;
+
+use std::error::Error;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use url::Url;
+
+use kitchen_fridge::cache::Cache;
+use kitchen_fridge::traits::CalDavSource;
+use kitchen_fridge::{CalDavProvider, Client};
+
+use scenarii::ItemScenario;
+
+struct ServerConfig {
+    name: String,
+    url: String,
+    username: String,
+    password: String,
+    calendar_home: Url,
+}
+
+impl ServerConfig {
+    fn from_env(name: &str) -> Result<Self, Box<dyn Error>> {
+        let var = |suffix: &str| {
+            let key = format!("KF_MATRIX_{}_{}", name.to_uppercase(), suffix);
+            std::env::var(&key).map_err(|_| format!("Missing environment variable {}", key))
+        };
+        let mut calendar_home: Url = var("CALENDAR_HOME")?.parse()?;
+        if calendar_home.path().ends_with('/') == false {
+            calendar_home.set_path(&format!("{}/", calendar_home.path()));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            url: var("URL")?,
+            username: var("USERNAME")?,
+            password: var("PASSWORD")?,
+            calendar_home,
+        })
+    }
+}
+
+/// Two devices that sync with the same server
+struct Devices {
+    run_id: String,
+    calendar_home: Url,
+    a: CalDavProvider,
+    b: CalDavProvider,
+}
+
+impl Devices {
+    fn new(server: &ServerConfig) -> Result<Self, Box<dyn Error>> {
+        let run_id = uuid::Uuid::new_v4().to_hyphenated().to_string();
+        let device = |device_name: &str| -> Result<CalDavProvider, Box<dyn Error>> {
+            let folder = std::env::temp_dir().join(format!("kf-matrix-{}-{}", run_id, device_name));
+            let client = Client::new(&server.url, &server.username, &server.password)?;
+            Ok(CalDavProvider::new(client, Cache::new(&folder)))
+        };
+        let (a, b) = (device("a")?, device("b")?);
+        Ok(Self { run_id, calendar_home: server.calendar_home.clone(), a, b })
+    }
+
+    /// Delete the test calendars, from device A then from the server
+    async fn cleanup(&mut self, calendar_urls: &[Url]) {
+        for url in calendar_urls {
+            if let Err(err) = self.a.local_mut().delete_calendar(url).await {
+                log::warn!("Unable to delete test calendar {}: {}", url, err);
+            }
+        }
+        if self.a.sync_calendars(calendar_urls).await == false {
+            log::warn!("Unable to delete the test calendars from the server");
+        }
+    }
+}
+
+/// Where a calendar of the scenarii is created on the server. Every run has its own calendars
+fn server_calendar_url(calendar_home: &Url, run_id: &str, url: &Url) -> Url {
+    let name = url.path_segments()
+        .and_then(|segments| segments.filter(|segment| segment.is_empty() == false).last())
+        .unwrap_or("calendar");
+    calendar_home.join(&format!("kf-matrix-{}-{}/", run_id, name)).unwrap()
+}
+
+/// The scenarii to run, and their names in the report.
+/// Interrupted uploads (see `scenarii::scenarii_interrupted_upload`) cannot be forged on a real server
+fn scenario_sets() -> Vec<(&'static str, Vec<ItemScenario>)> {
+    vec![
+        ("basic", scenarii::scenarii_basic()),
+        ("first sync to local", scenarii::scenarii_first_sync_to_local()),
+        ("first sync to server", scenarii::scenarii_first_sync_to_server()),
+        ("transient task", scenarii::scenarii_transient_task()),
+    ]
+}
+
+/// Run scenarii with new devices and new calendars, that are deleted afterwards
+async fn run_scenarii(server: &ServerConfig, scenarii: &[ItemScenario]) -> Result<(), Box<dyn Error>> {
+    let mut devices = Devices::new(server)?;
+    let (calendar_home, run_id) = (devices.calendar_home.clone(), devices.run_id.clone());
+    let server_url = move |url: &Url| server_calendar_url(&calendar_home, &run_id, url);
+    let calendar_urls: Vec<Url> = scenarii::on_server::calendars(scenarii).iter().map(&server_url).collect();
+
+    let result = scenarii::on_server::run_scenarii(scenarii, &mut devices.a, &mut devices.b, &server_url).await;
+    devices.cleanup(&calendar_urls).await;
+    result
+}
+
+#[derive(Debug)]
+struct Outcome {
+    server: String,
+    scenario: &'static str,
+    duration_ms: u128,
+    error: Option<String>,
+}
+
+async fn run_server(server: &ServerConfig) -> Vec<Outcome> {
+    let mut outcomes = Vec::new();
+    for (scenario, scenarii) in scenario_sets() {
+        let start = Instant::now();
+        let error = run_scenarii(server, &scenarii).await.err().map(|err| err.to_string());
+        println!("[{}] {}: {}", server.name, scenario, error.as_deref().unwrap_or("ok"));
+        outcomes.push(Outcome { server: server.name.clone(), scenario, duration_ms: start.elapsed().as_millis(), error });
+    }
+    outcomes
+}
+
+fn write_report(outcomes: &[Outcome]) -> std::io::Result<PathBuf> {
+    let path = std::env::var("KF_MATRIX_REPORT")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from("target/server-matrix-report.md"));
+
+    let mut report = String::from("# kitchen-fridge server compatibility\n\n| Server | Scenario | Result | Duration |\n|---|---|---|---|\n");
+    for outcome in outcomes {
+        let result = match &outcome.error {
+            None => "✔".to_string(),
+            Some(err) => format!("✘ {}", err.replace('|', "\\|").replace('\n', " ")),
+        };
+        report.push_str(&format!("| {} | {} | {} | {} ms |\n", outcome.server, outcome.scenario, result, outcome.duration_ms));
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, report)?;
+    Ok(path)
+}
+
+#[tokio::test]
+async fn test_server_matrix() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let server_names = std::env::var("KF_MATRIX_SERVERS").expect("KF_MATRIX_SERVERS must list the servers to test");
+    let mut outcomes = Vec::new();
+    for name in server_names.split(',').map(str::trim).filter(|n| n.is_empty() == false) {
+        match ServerConfig::from_env(name) {
+            Ok(server) => outcomes.extend(run_server(&server).await),
+            Err(err) => outcomes.push(Outcome { server: name.to_string(), scenario: "configuration", duration_ms: 0, error: Some(err.to_string()) }),
+        }
+    }
+
+    let report_path = write_report(&outcomes).unwrap();
+    println!("Compatibility report written to {}", report_path.display());
+
+    let failures: Vec<_> = outcomes.iter().filter(|o| o.error.is_some()).collect();
+    assert!(failures.is_empty(), "{} scenario(s) failed: {:?}", failures.len(), failures);
+}
@@ -23,6 +23,7 @@ impl TestFlavour {
     pub fn first_sync_to_local() -> Self { Self{} }
     pub fn first_sync_to_server() -> Self { Self{} }
     pub fn transient_task() -> Self { Self{} }
+    pub fn interrupted_upload() -> Self { Self{} }
     pub fn normal_with_errors1() -> Self { Self{} }
     pub fn normal_with_errors2() -> Self { Self{} }
     pub fn normal_with_errors3() -> Self { Self{} }
@@ -71,6 +72,13 @@ impl TestFlavour {
         }
     }
 
+    pub fn interrupted_upload() -> Self {
+        Self {
+            scenarii: scenarii::scenarii_interrupted_upload(),
+            mock_behaviour: Arc::new(Mutex::new(MockBehaviour::new())),
+        }
+    }
+
     pub fn normal_with_errors1() -> Self {
         Self {
             scenarii: scenarii::scenarii_basic(),
@@ -266,6 +274,12 @@ async fn test_sync_transient_task() {
     run_flavour(TestFlavour::transient_task(), 1).await;
 }
 
+#[tokio::test]
+#[cfg_attr(not(feature="integration_tests"), ignore)]
+async fn test_sync_interrupted_upload() {
+    run_flavour(TestFlavour::interrupted_upload(), 1).await;
+}
+
 #[tokio::test]
 #[cfg_attr(not(feature="integration_tests"), ignore)]
 async fn test_errors_in_regular_sync1() {
@@ -345,10 +359,32 @@ use kitchen_fridge::{traits::CalDavSource,
                calendar::cached_calendar::CachedCalendar,
 };
 
+#[cfg(feature = "integration_tests")]
+type TestProvider = Provider<Cache, CachedCalendar, Cache, CachedCalendar>;
+
+/// A provider that is populated with the basic scenarii (whose mocked errors are suspended), and the flavour these scenarii come from
+#[cfg(feature = "integration_tests")]
+async fn test_provider() -> (TestProvider, TestFlavour) {
+    let _ = env_logger::builder().is_test(true).try_init();
+    let flavour = TestFlavour::normal();
+    flavour.mock_behaviour.lock().unwrap().suspend();
+    let provider = scenarii::populate_test_provider_before_sync(&flavour.scenarii, Arc::clone(&flavour.mock_behaviour)).await;
+    (provider, flavour)
+}
+
+/// A provider that has been synced once with the basic scenarii, and the URL of one of its calendars
+#[cfg(feature = "integration_tests")]
+async fn synced_test_provider() -> (TestProvider, url::Url) {
+    let (mut provider, _flavour) = test_provider().await;
+    assert!(provider.sync().await);
+    let cal_url = provider.remote().get_calendars().await.unwrap().keys().next().unwrap().clone();
+    (provider, cal_url)
+}
+
 /// Print the contents of the provider. This is usually used for debugging
 #[allow(dead_code)]
 #[cfg(feature = "integration_tests")]
-async fn print_provider(provider: &Provider<Cache, CachedCalendar, Cache, CachedCalendar>, title: &str) {
+async fn print_provider(provider: &TestProvider, title: &str) {
     let cals_server = provider.remote().get_calendars().await.unwrap();
     println!("----Server, {}-------", title);
     kitchen_fridge::utils::print_calendar_list(&cals_server).await;
@@ -356,3 +392,874 @@ async fn print_provider(provider: &Provider<Cache, CachedCalendar, Cache, Cached
     println!("-----Local, {}-------", title);
     kitchen_fridge::utils::print_calendar_list(&cals_local).await;
 }
+
+#[tokio::test]
+#[cfg(feature = "integration_tests")]
+async fn test_fetch_remote() {
+    use kitchen_fridge::item::SyncStatus;
+    use kitchen_fridge::traits::{BaseCalendar, CompleteCalendar};
+    use kitchen_fridge::{Item, Task};
+
+    let (mut provider, cal_url) = synced_test_provider().await;
+
+    // Create an item on the server, and sync it
+    let item_url = {
+        let cal = provider.remote().get_calendar(&cal_url).await.unwrap();
+        let mut cal = cal.write().unwrap();
+        let task = Task::new("Created on the server".to_string(), false, &cal_url);
+        let item_url = task.url().clone();
+        cal.add_item(Item::Task(task)).await.unwrap();
+        cal.get_item_by_url_mut(&item_url).await.unwrap().set_sync_status(SyncStatus::random_synced());
+        item_url
+    };
+    provider.sync().await;
+
+    // Then rename it on the server only
+    {
+        let cal = provider.remote().get_calendar(&cal_url).await.unwrap();
+        let mut cal = cal.write().unwrap();
+        cal.get_item_by_url_mut(&item_url).await.unwrap().unwrap_task_mut().mock_remote_calendar_set_name("Renamed on the server".to_string());
+    }
+
+    let remote_item = provider.fetch_remote(&item_url).await.unwrap().unwrap();
+    assert_eq!(remote_item.name(), "Renamed on the server");
+
+    // The local item is left untouched
+    let cal = provider.local().get_calendar(&cal_url).await.unwrap();
+    let cal = cal.read().unwrap();
+    let local_item = cal.get_item_by_url(&item_url).await.unwrap();
+    assert_eq!(local_item.name(), "Created on the server");
+    assert!(local_item.last_known_version_tag().is_some());
+    assert_ne!(local_item.last_known_version_tag(), remote_item.last_known_version_tag());
+}
+
+#[tokio::test]
+#[cfg(feature = "integration_tests")]
+async fn test_sync_moved_item() {
+    use kitchen_fridge::item::SyncStatus;
+    use kitchen_fridge::provider::conflict::{ConflictKind, ConflictOutcome, ConflictResolution};
+    use kitchen_fridge::task::TaskBuilder;
+    use kitchen_fridge::traits::{BaseCalendar, CompleteCalendar};
+    use kitchen_fridge::Item;
+
+    let (mut provider, _cal_url) = synced_test_provider().await;
+
+    // Pick a synced task
+    let (cal_url, old_url, uid) = {
+        let cals = provider.local().get_calendars().await.unwrap();
+        let (cal_url, cal) = cals.iter().next().unwrap();
+        let cal = cal.read().unwrap();
+        let (url, item) = cal.get_items().await.unwrap().into_iter().next().unwrap();
+        (cal_url.clone(), url.clone(), item.uid().clone())
+    };
+
+    // The server moves it to another URL...
+    let new_url = kitchen_fridge::utils::random_url(&cal_url);
+    {
+        use kitchen_fridge::traits::DavCalendar;
+        let cal = provider.remote().get_calendar(&cal_url).await.unwrap();
+        let mut cal = cal.write().unwrap();
+        let name = CompleteCalendar::get_item_by_url(&*cal, &old_url).await.unwrap().name().to_string();
+        cal.delete_item(&old_url).await.unwrap();
+        let moved = TaskBuilder::new(name, &cal_url)
+            .with_url(new_url.clone())
+            .with_uid(uid.clone())
+            .with_sync_status(SyncStatus::random_synced())
+            .with_creation_date(None)
+            .with_last_modified(chrono::Utc::now() - chrono::Duration::seconds(10))
+            .with_ical_prod_id("prod_id".to_string())
+            .build()
+            .unwrap();
+        cal.add_item(Item::Task(moved)).await.unwrap();
+    }
+
+    // ...while it is locally modified
+    {
+        let cal = provider.local().get_calendar(&cal_url).await.unwrap();
+        let mut cal = cal.write().unwrap();
+        cal.get_item_by_url_mut(&old_url).await.unwrap().unwrap_task_mut().set_name("Locally renamed".to_string());
+    }
+
+    // This is a conflict, just like any other item that is modified on both ends
+    provider.set_conflict_resolution(ConflictResolution::PreferLocal);
+    assert!(provider.sync().await);
+    let conflicts: Vec<_> = provider.last_sync_result().unwrap().conflicts().collect();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].item_url(), &old_url);
+    assert_eq!(conflicts[0].kind(), ConflictKind::BothModified);
+    assert_eq!(conflicts[0].outcome(), ConflictOutcome::KeptLocal);
+
+    // The local change has not been lost
+    for (source, cal) in &[("local", provider.local().get_calendar(&cal_url).await.unwrap()), ("remote", provider.remote().get_calendar(&cal_url).await.unwrap())] {
+        let cal = cal.read().unwrap();
+        assert!(cal.get_item_by_url(&old_url).await.is_none(), "{} still has the old URL", source);
+        let item = cal.get_item_by_url(&new_url).await.unwrap();
+        assert_eq!(item.name(), "Locally renamed", "{} has lost the local change", source);
+        assert_eq!(item.uid(), &uid);
+    }
+}
+
+#[tokio::test]
+#[cfg(feature = "integration_tests")]
+async fn test_subtask_completion_policy() {
+    use kitchen_fridge::provider::SubtaskCompletionPolicy;
+    use kitchen_fridge::traits::{BaseCalendar, CompleteCalendar};
+    use kitchen_fridge::{Item, Task};
+
+    let (mut provider, _flavour) = test_provider().await;
+
+    // parent <- child <- grandchild
+    let cal_url = provider.local().get_calendars().await.unwrap().keys().next().unwrap().clone();
+    let (parent_url, child_url, grandchild_url) = {
+        let cal = provider.local().get_calendar(&cal_url).await.unwrap();
+        let mut cal = cal.write().unwrap();
+        let parent = Task::new("Parent".to_string(), false, &cal_url);
+        let mut child = Task::new("Child".to_string(), false, &cal_url);
+        child.set_parent(Some(parent.uid().clone()));
+        let mut grandchild = Task::new("Grandchild".to_string(), false, &cal_url);
+        grandchild.set_parent(Some(child.uid().clone()));
+        let urls = (parent.url().clone(), child.url().clone(), grandchild.url().clone());
+        for task in vec![parent, child, grandchild] {
+            cal.add_item(Item::Task(task)).await.unwrap();
+        }
+        urls
+    };
+    async fn is_completed(provider: &TestProvider, cal_url: &url::Url, url: &url::Url) -> bool {
+        let cal = provider.local().get_calendar(cal_url).await.unwrap();
+        let cal = cal.read().unwrap();
+        cal.get_item_by_url(url).await.unwrap().unwrap_task().completed()
+    }
+
+    provider.set_subtask_policy(SubtaskCompletionPolicy::BlockWhileChildrenOpen);
+    assert!(provider.set_task_completion(&parent_url, true).await.is_err());
+    assert_eq!(is_completed(&provider, &cal_url, &parent_url).await, false);
+
+    provider.set_subtask_policy(SubtaskCompletionPolicy::CompleteChildren);
+    let modified = provider.set_task_completion(&parent_url, true).await.unwrap();
+    assert_eq!(modified.len(), 3);
+    assert!(is_completed(&provider, &cal_url, &parent_url).await);
+    assert!(is_completed(&provider, &cal_url, &child_url).await);
+    assert!(is_completed(&provider, &cal_url, &grandchild_url).await);
+
+    // Un-completing a task does not cascade
+    let modified = provider.set_task_completion(&child_url, false).await.unwrap();
+    assert_eq!(modified, vec![child_url.clone()]);
+    assert!(is_completed(&provider, &cal_url, &parent_url).await);
+    assert!(is_completed(&provider, &cal_url, &grandchild_url).await);
+}
+
+#[tokio::test]
+#[cfg(feature = "integration_tests")]
+async fn test_evicted_items() {
+    use kitchen_fridge::traits::CompleteCalendar;
+
+    let (mut provider, _cal_url) = synced_test_provider().await;
+
+    let (cal_url, item_url, name) = {
+        let cals = provider.local().get_calendars().await.unwrap();
+        let (cal_url, cal) = cals.iter().next().unwrap();
+        let mut cal = cal.write().unwrap();
+        let (url, item) = cal.get_items().await.unwrap().into_iter().next().unwrap();
+        let name = item.name().to_string();
+        cal.evict_item(&url).unwrap();
+        assert!(cal.get_item_by_url(&url).await.is_none());
+        (cal_url.clone(), url, name)
+    };
+
+    // Evicted items are not downloaded again by syncs
+    assert!(provider.sync().await);
+    {
+        let cal = provider.local().get_calendar(&cal_url).await.unwrap();
+        let cal = cal.read().unwrap();
+        assert!(cal.get_item_by_url(&item_url).await.is_none());
+        assert!(cal.evicted_items().contains_key(&item_url));
+    }
+
+    // ...but on demand
+    let item = provider.load_item(&item_url).await.unwrap();
+    assert_eq!(item.name(), name);
+    let cal = provider.local().get_calendar(&cal_url).await.unwrap();
+    let cal = cal.read().unwrap();
+    assert!(cal.get_item_by_url(&item_url).await.is_some());
+    assert!(cal.evicted_items().is_empty());
+}
+
+#[tokio::test]
+#[cfg(feature = "integration_tests")]
+async fn test_discard_local_changes() {
+    use kitchen_fridge::provider::pending_changes::PendingChangeKind;
+    use kitchen_fridge::traits::{BaseCalendar, CompleteCalendar};
+    use kitchen_fridge::{Item, Task};
+
+    let (provider, _cal_url) = synced_test_provider().await;
+    assert!(provider.pending_changes().await.unwrap().is_empty());
+
+    // Rename a task, delete another one and create a third one
+    let (cal_url, renamed_url, deleted_url, original_name) = {
+        let cals = provider.local().get_calendars().await.unwrap();
+        let (cal_url, cal) = cals.iter().max_by_key(|(_url, cal)| cal.read().unwrap().item_count()).unwrap();
+        let mut cal = cal.write().unwrap();
+        let mut urls: Vec<url::Url> = cal.get_item_urls().await.unwrap().into_iter().collect();
+        urls.sort();
+        let (renamed_url, deleted_url) = (urls[0].clone(), urls[1].clone());
+        let mut item = cal.get_item_by_url_mut(&renamed_url).await.unwrap();
+        let original_name = item.name().to_string();
+        item.unwrap_task_mut().set_name("Renamed locally".to_string());
+        cal.mark_for_deletion(&deleted_url).await.unwrap();
+        (cal_url.clone(), renamed_url, deleted_url, original_name)
+    };
+    let added_url = {
+        let cal = provider.local().get_calendar(&cal_url).await.unwrap();
+        let mut cal = cal.write().unwrap();
+        let task = Task::new("Created locally".to_string(), false, &cal_url);
+        let url = task.url().clone();
+        cal.add_item(Item::Task(task)).await.unwrap();
+        url
+    };
+
+    let changes = provider.pending_changes().await.unwrap();
+    assert_eq!(changes.len(), 3);
+    let renamed = changes.iter().find(|c| c.item_url() == &renamed_url).unwrap();
+    assert_eq!(renamed.kind(), PendingChangeKind::Modification);
+    assert!(renamed.last_synced_version_tag().is_some());
+    assert!(provider.changed_properties(&renamed_url).await.unwrap().contains(&"SUMMARY".to_string()));
+    assert_eq!(changes.iter().find(|c| c.item_url() == &deleted_url).unwrap().kind(), PendingChangeKind::Deletion);
+    assert_eq!(changes.iter().find(|c| c.item_url() == &added_url).unwrap().kind(), PendingChangeKind::Addition);
+
+    for url in &[&renamed_url, &deleted_url, &added_url] {
+        provider.discard_local_change(url).await.unwrap();
+    }
+    assert!(provider.pending_changes().await.unwrap().is_empty());
+    assert!(provider.discard_local_change(&renamed_url).await.is_err());
+
+    let cal = provider.local().get_calendar(&cal_url).await.unwrap();
+    let cal = cal.read().unwrap();
+    assert_eq!(cal.get_item_by_url(&renamed_url).await.unwrap().name(), original_name);
+    assert!(cal.get_item_by_url(&deleted_url).await.is_some());
+    assert!(cal.get_item_by_url(&added_url).await.is_none());
+}
+
+#[tokio::test]
+#[cfg(feature = "integration_tests")]
+async fn test_sync_window() {
+    use chrono::{Duration, Utc};
+    use kitchen_fridge::item::SyncStatus;
+    use kitchen_fridge::provider::SyncWindow;
+    use kitchen_fridge::traits::{BaseCalendar, CompleteCalendar, DavCalendar};
+    use kitchen_fridge::{Event, Item};
+
+    let (mut provider, cal_url) = synced_test_provider().await;
+
+    // Create an old event and a recent one on the server
+    let now = Utc::now();
+    let old_event = Event::new("Two years ago".to_string(), now - Duration::days(730), now - Duration::days(730) + Duration::hours(1), &cal_url);
+    let recent_event = Event::new("Yesterday".to_string(), now - Duration::days(1), now - Duration::days(1) + Duration::hours(1), &cal_url);
+    let (old_url, recent_url) = (old_event.url().clone(), recent_event.url().clone());
+    {
+        let cal = provider.remote().get_calendar(&cal_url).await.unwrap();
+        let mut cal = cal.write().unwrap();
+        for event in vec![old_event, recent_event] {
+            let url = event.url().clone();
+            cal.add_item(Item::Event(event)).await.unwrap();
+            cal.get_item_by_url_mut(&url).await.unwrap().set_sync_status(SyncStatus::random_synced());
+        }
+    }
+
+    // Only the recent one is pulled
+    provider.set_sync_window(Some(SyncWindow::new(Duration::days(30), Duration::days(365))));
+    assert!(provider.sync().await);
+    {
+        let cal = provider.local().get_calendar(&cal_url).await.unwrap();
+        let cal = cal.read().unwrap();
+        assert!(cal.get_item_by_url(&recent_url).await.is_some());
+        assert!(cal.get_item_by_url(&old_url).await.is_none());
+    }
+
+    // Once pulled by a full sync, local copies of events that are out of the window are left as they are
+    provider.set_sync_window(None);
+    assert!(provider.sync().await);
+    {
+        let cal = provider.remote().get_calendar(&cal_url).await.unwrap();
+        let mut cal = cal.write().unwrap();
+        DavCalendar::delete_item(&mut *cal, &old_url).await.unwrap();
+    }
+    provider.set_sync_window(Some(SyncWindow::new(Duration::days(30), Duration::days(365))));
+    assert!(provider.sync().await);
+    {
+        let cal = provider.local().get_calendar(&cal_url).await.unwrap();
+        let cal = cal.read().unwrap();
+        assert!(cal.get_item_by_url(&old_url).await.is_some());
+        assert!(cal.get_item_by_url(&recent_url).await.is_some());
+    }
+
+    // An event that is missing from a windowed listing may have been moved out of the window, it is only deleted once a full listing confirms it
+    {
+        let cal = provider.remote().get_calendar(&cal_url).await.unwrap();
+        let mut cal = cal.write().unwrap();
+        DavCalendar::delete_item(&mut *cal, &recent_url).await.unwrap();
+    }
+    assert!(provider.sync().await);
+    assert!(provider.local().get_calendar(&cal_url).await.unwrap().read().unwrap().get_item_by_url(&recent_url).await.is_some());
+    provider.set_sync_window(None);
+    assert!(provider.sync().await);
+    assert!(provider.local().get_calendar(&cal_url).await.unwrap().read().unwrap().get_item_by_url(&recent_url).await.is_none());
+}
+
+#[tokio::test]
+#[cfg(feature = "integration_tests")]
+async fn test_conflict_resolution() {
+    use kitchen_fridge::item::SyncStatus;
+    use kitchen_fridge::provider::conflict::{ConflictKind, ConflictOutcome, ConflictResolution};
+    use kitchen_fridge::traits::{BaseCalendar, CompleteCalendar, DavCalendar};
+    use kitchen_fridge::{Item, Task};
+
+    let (mut provider, cal_url) = synced_test_provider().await;
+
+    // Create a task, then modify it on both ends
+    let task = Task::new("Original name".to_string(), false, &cal_url);
+    let task_url = task.url().clone();
+    {
+        let cal = provider.local().get_calendar(&cal_url).await.unwrap();
+        cal.write().unwrap().add_item(Item::Task(task)).await.unwrap();
+    }
+    assert!(provider.sync().await);
+    {
+        let cal = provider.local().get_calendar(&cal_url).await.unwrap();
+        let mut cal = cal.write().unwrap();
+        cal.get_item_by_url_mut(&task_url).await.unwrap().unwrap_task_mut().set_name("Local name".to_string());
+    }
+    {
+        let cal = provider.remote().get_calendar(&cal_url).await.unwrap();
+        let mut cal = cal.write().unwrap();
+        let item = DavCalendar::get_item_by_url(&*cal, &task_url).await.unwrap().unwrap();
+        let mut remote_task = item.unwrap_task().clone();
+        remote_task.set_name("Remote name".to_string());
+        remote_task.set_sync_status(SyncStatus::random_synced());
+        cal.update_item(Item::Task(remote_task)).await.unwrap();
+    }
+
+    // Plans do not call custom resolutions
+    provider.set_conflict_resolution(ConflictResolution::custom(|_conflict| panic!("A plan has called a custom resolution")));
+    let plan = provider.sync_plan().await.unwrap();
+    let cal_plan = plan.calendars().iter().find(|plan| plan.calendar_url() == &cal_url).unwrap();
+    assert_eq!(cal_plan.undecided_conflicts(), &[task_url.clone()]);
+    assert!(cal_plan.conflicts().is_empty());
+    assert!(plan.is_destructive());
+
+    // Both versions are kept
+    provider.set_conflict_resolution(ConflictResolution::KeepBoth);
+    assert!(provider.sync().await);
+    let conflicts: Vec<_> = provider.last_sync_result().unwrap().conflicts().collect();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].item_url(), &task_url);
+    assert_eq!(conflicts[0].kind(), ConflictKind::BothModified);
+    assert_eq!(conflicts[0].outcome(), ConflictOutcome::KeptBoth);
+
+    let cal = provider.local().get_calendar(&cal_url).await.unwrap();
+    let cal = cal.read().unwrap();
+    assert_eq!(cal.get_item_by_url(&task_url).await.unwrap().name(), "Remote name");
+    assert!(cal.iter_items().any(|(url, item)| url != &task_url && item.name() == "Local name"));
+}
+
+#[tokio::test]
+#[cfg(feature = "integration_tests")]
+async fn test_cancelled_sync() {
+    use kitchen_fridge::provider::sync_progress::CancellationToken;
+
+    let (mut provider, flavour) = test_provider().await;
+
+    let token = CancellationToken::new();
+    token.cancel();
+    assert_eq!(provider.sync_with_cancellation(token).await, false);
+
+    // Nothing has been left half-synced
+    assert!(provider.sync().await);
+    let expected_provider = scenarii::populate_test_provider_after_sync(&flavour.scenarii, Arc::clone(&flavour.mock_behaviour)).await;
+    assert!(provider.remote().has_same_observable_content_as(expected_provider.remote()).await.unwrap());
+    assert!(provider.local().has_same_observable_content_as(expected_provider.local()).await.unwrap());
+}
+
+#[tokio::test]
+#[cfg(feature = "integration_tests")]
+async fn test_sync_cancelled_while_fetching() {
+    use kitchen_fridge::provider::SyncOptions;
+    use kitchen_fridge::provider::sync_progress::{CancellationToken, SyncObserver, SyncPhase};
+    use kitchen_fridge::traits::{BaseCalendar, CompleteCalendar};
+    use kitchen_fridge::{Item, Task};
+
+    let (mut provider, cal_url) = synced_test_provider().await;
+    let added_url = {
+        let cal = provider.remote().get_calendar(&cal_url).await.unwrap();
+        let mut cal = cal.write().unwrap();
+        let task = Task::new("Created on the server".to_string(), false, &cal_url);
+        let url = task.url().clone();
+        cal.add_item(Item::Task(task)).await.unwrap();
+        url
+    };
+
+    // The sync is cancelled as soon as it is about to download something
+    let token = CancellationToken::new();
+    let token_ = token.clone();
+    let observer: Arc<dyn SyncObserver> = Arc::new(move |phase: &SyncPhase| {
+        if let SyncPhase::Fetching{ total, .. } = phase {
+            if *total > 0 {
+                token_.cancel();
+            }
+        }
+    });
+    let result = provider.sync_with(SyncOptions { observer: Some(observer), cancellation: Some(token), ..Default::default() }).await;
+    assert_eq!(result.is_success(), false);
+    assert!(provider.local().get_calendar(&cal_url).await.unwrap().read().unwrap().get_item_by_url(&added_url).await.is_none());
+
+    // What has not been synced is synced at the next sync
+    assert!(provider.sync().await);
+    assert!(provider.local().get_calendar(&cal_url).await.unwrap().read().unwrap().get_item_by_url(&added_url).await.is_some());
+    assert!(provider.remote().has_same_observable_content_as(provider.local()).await.unwrap());
+}
+
+#[tokio::test]
+#[cfg(feature = "integration_tests")]
+async fn test_dry_run() {
+    use kitchen_fridge::provider::SyncOptions;
+
+    let (mut provider, flavour) = test_provider().await;
+    let dry_run = || SyncOptions { dry_run: true, ..Default::default() };
+
+    let plan = provider.sync_with(dry_run()).await;
+    assert!(plan.is_success());
+    assert!(plan.is_dry_run());
+    assert!(plan.is_empty() == false);
+    assert!(plan.is_destructive());
+    assert!(provider.last_sync_result().is_none());
+
+    // Computing a plan does not change anything
+    let untouched_provider = scenarii::populate_test_provider_before_sync(&flavour.scenarii, Arc::clone(&flavour.mock_behaviour)).await;
+    assert!(provider.local().has_same_observable_content_as(untouched_provider.local()).await.unwrap());
+    assert!(provider.remote().has_same_observable_content_as(untouched_provider.remote()).await.unwrap());
+
+    assert!(provider.sync().await);
+    assert!(provider.sync_with(dry_run()).await.is_empty());
+}
+
+#[tokio::test]
+#[cfg(feature = "integration_tests")]
+async fn test_sync_plan() {
+    let (mut provider, flavour) = test_provider().await;
+
+    let plan = provider.sync_plan().await.unwrap();
+    assert!(plan.is_empty() == false);
+    assert!(plan.is_destructive());
+
+    // Computing a plan does not change anything
+    let untouched_provider = scenarii::populate_test_provider_before_sync(&flavour.scenarii, Arc::clone(&flavour.mock_behaviour)).await;
+    assert!(provider.local().has_same_observable_content_as(untouched_provider.local()).await.unwrap());
+    assert!(provider.remote().has_same_observable_content_as(untouched_provider.remote()).await.unwrap());
+
+    assert!(provider.sync().await);
+    assert!(provider.sync_plan().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+#[cfg(feature = "integration_tests")]
+async fn test_sync_result() {
+    use kitchen_fridge::provider::SyncOptions;
+    use kitchen_fridge::provider::sync_result::{ItemOperation, SyncDirection};
+
+    let (mut provider, _flavour) = test_provider().await;
+
+    let result = provider.sync_with(SyncOptions::default()).await;
+    assert!(result.is_success());
+    assert_eq!(result.failed_items().count(), 0);
+    let synced: Vec<_> = result.calendars().iter().flat_map(|cal| cal.synced().iter()).collect();
+    assert!(synced.iter().any(|item| item.operation() == ItemOperation::Added && item.direction() == SyncDirection::Pushed));
+    assert!(synced.iter().any(|item| item.operation() == ItemOperation::Deleted && item.direction() == SyncDirection::Pulled));
+
+    // Nothing is left to sync
+    let result = provider.sync_with(SyncOptions::default()).await;
+    assert!(result.is_success());
+    assert!(result.calendars().iter().all(|cal| cal.synced().is_empty()));
+}
+
+#[tokio::test]
+#[cfg(feature = "integration_tests")]
+async fn test_sync_observer() {
+    use kitchen_fridge::provider::SyncOptions;
+    use kitchen_fridge::provider::sync_progress::{SyncObserver, SyncPhase};
+    use kitchen_fridge::traits::{BaseCalendar, CompleteCalendar};
+    use kitchen_fridge::{Item, Task};
+
+    let (mut provider, cal_url) = synced_test_provider().await;
+    let cal_name = provider.local().get_calendar(&cal_url).await.unwrap().read().unwrap().name().to_string();
+
+    // One item is deleted from the server and two are added to it, while another one is added locally
+    {
+        let cal = provider.remote().get_calendar(&cal_url).await.unwrap();
+        let mut cal = cal.write().unwrap();
+        let deleted_url = cal.iter_items().next().unwrap().0.clone();
+        cal.immediately_delete_item(&deleted_url).await.unwrap();
+        for name in &["First remote task", "Second remote task"] {
+            cal.add_item(Item::Task(Task::new(name.to_string(), false, &cal_url))).await.unwrap();
+        }
+    }
+    {
+        let cal = provider.local().get_calendar(&cal_url).await.unwrap();
+        cal.write().unwrap().add_item(Item::Task(Task::new("Local task".to_string(), false, &cal_url))).await.unwrap();
+    }
+
+    let phases = Arc::new(Mutex::new(Vec::new()));
+    let phases_ = Arc::clone(&phases);
+    let observer: Arc<dyn SyncObserver> = Arc::new(move |phase: &SyncPhase| phases_.lock().unwrap().push(phase.clone()));
+    let result = provider.sync_with(SyncOptions { observer: Some(observer), ..Default::default() }).await;
+    assert!(result.is_success());
+    assert_eq!(result.calendar(&cal_url).unwrap().added(), 3);
+    assert_eq!(result.calendar(&cal_url).unwrap().deleted(), 1);
+
+    let phases = phases.lock().unwrap();
+    assert_eq!(phases.first(), Some(&SyncPhase::ListingCalendars));
+    assert_eq!(phases.last(), Some(&SyncPhase::Finished{ success: true }));
+
+    // The steps of this calendar come in order...
+    let step = |phase: &SyncPhase| match phase {
+        SyncPhase::ListingItems{ calendar } if calendar == &cal_name => Some(0),
+        SyncPhase::Deleting{ calendar, .. } if calendar == &cal_name => Some(1),
+        SyncPhase::Fetching{ calendar, .. } if calendar == &cal_name => Some(2),
+        SyncPhase::Pushing{ calendar, .. } if calendar == &cal_name => Some(3),
+        _ => None,
+    };
+    let steps: Vec<(usize, &SyncPhase)> = phases.iter().filter_map(|phase| step(phase).map(|step| (step, phase))).collect();
+    assert_eq!(steps.first().map(|(step, _)| *step), Some(0));
+    assert!(steps.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+
+    // ...and they end up with every item done
+    let last_progress = |wanted: usize| steps.iter().rev()
+        .find(|(step, _)| *step == wanted)
+        .map(|(_, phase)| match phase {
+            SyncPhase::Deleting{ done, total, .. } | SyncPhase::Fetching{ done, total, .. } | SyncPhase::Pushing{ done, total, .. } => (*done, *total),
+            _ => unreachable!(),
+        });
+    assert_eq!(last_progress(1), Some((1, 1)));
+    assert_eq!(last_progress(2), Some((2, 2)));
+    assert_eq!(last_progress(3), Some((1, 1)));
+}
+
+#[tokio::test]
+#[cfg(feature = "integration_tests")]
+async fn test_lost_update_is_refused() {
+    use kitchen_fridge::item::{SyncStatus, VersionTag};
+    use kitchen_fridge::traits::{BaseCalendar, DavCalendar};
+    use kitchen_fridge::{Item, Task};
+
+    let (provider, cal_url) = synced_test_provider().await;
+
+    // Someone else has changed this item since the version we know about
+    let task = Task::new("Original name".to_string(), false, &cal_url);
+    let url = task.url().clone();
+    let cal = provider.remote().get_calendar(&cal_url).await.unwrap();
+    let mut cal = cal.write().unwrap();
+    cal.add_item(Item::Task(task)).await.unwrap();
+    let mut item = DavCalendar::get_item_by_url(&*cal, &url).await.unwrap().unwrap();
+    item.set_sync_status(SyncStatus::LocallyModified(VersionTag::from("outdated-tag".to_string())));
+
+    let err = cal.update_item(item).await.unwrap_err();
+    assert!(matches!(err, kitchen_fridge::Error::Conflict { .. }));
+}
+
+#[tokio::test]
+#[cfg(feature = "integration_tests")]
+async fn test_move_item() {
+    use kitchen_fridge::item::SyncStatus;
+    use kitchen_fridge::traits::{BaseCalendar, CompleteCalendar, DavCalendar};
+
+    let (mut provider, _cal_url) = synced_test_provider().await;
+
+    let mut cal_urls: Vec<url::Url> = provider.local().get_calendars().await.unwrap().keys().cloned().collect();
+    cal_urls.sort();
+    let (source_url, target_url) = (cal_urls[0].clone(), cal_urls[1].clone());
+    let (item_url, uid) = {
+        let cal = provider.local().get_calendar(&source_url).await.unwrap();
+        let cal = cal.read().unwrap();
+        let (url, item) = cal.iter_items().find(|(_url, item)| matches!(item.sync_status(), SyncStatus::Synced(_))).unwrap();
+        (url.clone(), item.uid().clone())
+    };
+
+    let new_url = provider.move_item(&item_url, &target_url).await.unwrap();
+    assert_eq!(provider.local().pending_moves().get(&item_url), Some(&new_url));
+    {
+        let cal = provider.local().get_calendar(&target_url).await.unwrap();
+        let cal = cal.read().unwrap();
+        assert_eq!(cal.get_item_by_url(&new_url).await.unwrap().uid(), &uid);
+    }
+    // Items cannot be moved again once they have been deleted
+    assert!(provider.move_item(&item_url, &target_url).await.is_err());
+
+    // Mocked remote calendars cannot move items, so the move is pushed as a deletion followed by a creation
+    assert!(provider.sync().await);
+    assert!(provider.local().pending_moves().is_empty());
+    let cal = provider.remote().get_calendar(&source_url).await.unwrap();
+    assert!(DavCalendar::get_item_by_url(&*cal.read().unwrap(), &item_url).await.unwrap().is_none());
+    let cal = provider.remote().get_calendar(&target_url).await.unwrap();
+    let moved = DavCalendar::get_item_by_url(&*cal.read().unwrap(), &new_url).await.unwrap().unwrap();
+    assert_eq!(moved.uid(), &uid);
+    assert!(provider.remote().has_same_observable_content_as(provider.local()).await.unwrap());
+}
+
+#[tokio::test]
+#[cfg(feature = "integration_tests")]
+async fn test_duplicates() {
+    use kitchen_fridge::item::SyncStatus;
+    use kitchen_fridge::provider::SyncOptions;
+    use kitchen_fridge::traits::{BaseCalendar, CompleteCalendar};
+
+    let (mut provider, cal_url) = synced_test_provider().await;
+
+    // The same task has been imported twice on the server
+    let urls = vec![cal_url.join("imported-1.ics").unwrap(), cal_url.join("imported-2.ics").unwrap()];
+    {
+        let cal = provider.remote().get_calendar(&cal_url).await.unwrap();
+        let mut cal = cal.write().unwrap();
+        for url in &urls {
+            let ics = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//Example//Import//EN\r\nBEGIN:VTODO\r\nUID:imported-twice\r\nSUMMARY:Water the plants\r\nLAST-MODIFIED:20210321T001600Z\r\nEND:VTODO\r\nEND:VCALENDAR\r\n";
+            let item = kitchen_fridge::ical::parse(ics, url.clone(), SyncStatus::NotSynced).unwrap();
+            cal.add_item(item).await.unwrap();
+            cal.get_item_by_url_mut(url).await.unwrap().set_sync_status(SyncStatus::random_synced());
+        }
+    }
+
+    let result = provider.sync_with(SyncOptions::default()).await;
+    assert!(result.is_success());
+    let duplicates = result.calendar(&cal_url).unwrap().duplicates();
+    assert_eq!(duplicates.len(), 1);
+    assert_eq!(duplicates[0].uid().as_str(), "imported-twice");
+    assert_eq!(provider.find_duplicates().await.unwrap(), duplicates.to_vec());
+
+    // Only one of them is kept
+    assert!(provider.merge_duplicates(&duplicates[0], &cal_url).await.is_err());
+    provider.merge_duplicates(&duplicates[0], &urls[1]).await.unwrap();
+    assert!(provider.find_duplicates().await.unwrap().is_empty());
+    let result = provider.sync_with(SyncOptions::default()).await;
+    assert!(result.is_success());
+    assert!(result.calendar(&cal_url).unwrap().duplicates().is_empty());
+    let cal = provider.remote().get_calendar(&cal_url).await.unwrap();
+    let cal = cal.read().unwrap();
+    assert!(cal.get_item_by_url(&urls[0]).await.is_none());
+    assert!(cal.get_item_by_url(&urls[1]).await.is_some());
+}
+
+#[tokio::test]
+#[cfg(feature = "integration_tests")]
+async fn test_failed_calendar_is_kept() {
+    let (mut provider, flavour) = test_provider().await;
+    assert!(provider.sync().await);
+    let cal_urls = |cals: std::collections::HashMap<url::Url, _>| {
+        let mut urls: Vec<url::Url> = cals.into_keys().collect();
+        urls.sort();
+        urls
+    };
+    let local_cals = cal_urls(provider.local().get_calendars().await.unwrap());
+
+    // Listing the items of the first calendar fails, e.g. because of a timeout
+    {
+        let mut behaviour = flavour.mock_behaviour.lock().unwrap();
+        behaviour.get_item_version_tags_behaviour = (0, 1);
+        behaviour.resume();
+    }
+    assert_eq!(provider.sync().await, false);
+    flavour.mock_behaviour.lock().unwrap().suspend();
+
+    // This must not be mistaken for a deletion of this calendar on the server
+    assert_eq!(cal_urls(provider.local().get_calendars().await.unwrap()), local_cals);
+    assert!(provider.sync().await);
+    assert!(provider.remote().has_same_observable_content_as(provider.local()).await.unwrap());
+}
+
+#[tokio::test]
+#[cfg(feature = "integration_tests")]
+async fn test_calendar_deletion_is_pushed() {
+    let (mut provider, cal_url) = synced_test_provider().await;
+
+    provider.local_mut().delete_calendar(&cal_url).await.unwrap();
+    assert!(provider.local().deleted_calendars().contains(&cal_url));
+
+    assert!(provider.sync().await);
+    assert!(provider.remote().get_calendar(&cal_url).await.is_none());
+    assert!(provider.local().get_calendar(&cal_url).await.is_none());
+    assert!(provider.local().deleted_calendars().is_empty());
+    assert!(provider.remote().has_same_observable_content_as(provider.local()).await.unwrap());
+}
+
+#[tokio::test]
+#[cfg(feature = "integration_tests")]
+async fn test_calendar_properties_are_pushed() {
+    use kitchen_fridge::traits::{BaseCalendar, CompleteCalendar};
+
+    let (mut provider, cal_url) = synced_test_provider().await;
+
+    // Local properties are pushed...
+    {
+        let cal = provider.local().get_calendar(&cal_url).await.unwrap();
+        let mut cal = cal.write().unwrap();
+        cal.set_name("Renamed locally".to_string());
+        cal.set_description(Some("A new description".to_string()));
+    }
+    assert!(provider.sync().await);
+    {
+        let cal = provider.remote().get_calendar(&cal_url).await.unwrap();
+        let cal = cal.read().unwrap();
+        assert_eq!(cal.name(), "Renamed locally");
+        assert_eq!(cal.properties().description.as_deref(), Some("A new description"));
+    }
+    let cal = provider.local().get_calendar(&cal_url).await.unwrap();
+    assert_eq!(cal.read().unwrap().has_modified_properties(), false);
+
+    // ...and remote ones are pulled
+    {
+        let cal = provider.remote().get_calendar(&cal_url).await.unwrap();
+        let mut cal = cal.write().unwrap();
+        let properties = cal.properties();
+        cal.set_synced_properties(kitchen_fridge::calendar::CalendarProperties {
+            name: "Renamed on the server".to_string(),
+            ..properties
+        });
+    }
+    assert!(provider.sync().await);
+    let cal = provider.local().get_calendar(&cal_url).await.unwrap();
+    assert_eq!(cal.read().unwrap().name(), "Renamed on the server");
+}
+
+#[tokio::test]
+#[cfg(feature = "integration_tests")]
+async fn test_read_only_calendar_refuses_pushes() {
+    use kitchen_fridge::calendar::Privileges;
+    use kitchen_fridge::provider::SyncOptions;
+    use kitchen_fridge::traits::{BaseCalendar, CompleteCalendar};
+    use kitchen_fridge::{Item, Task};
+
+    let (mut provider, cal_url) = synced_test_provider().await;
+    let remote_name = {
+        let cal = provider.remote().get_calendar(&cal_url).await.unwrap();
+        let mut cal = cal.write().unwrap();
+        cal.set_privileges(Privileges::READ);
+        cal.name().to_string()
+    };
+
+    let task_url = {
+        let cal = provider.local().get_calendar(&cal_url).await.unwrap();
+        let mut cal = cal.write().unwrap();
+        cal.set_name("Renamed locally".to_string());
+        let task = Task::new("Created locally".to_string(), false, &cal_url);
+        let task_url = task.url().clone();
+        cal.add_item(Item::Task(task)).await.unwrap();
+        task_url
+    };
+
+    let result = provider.sync_with(SyncOptions::default()).await;
+    assert_eq!(result.is_success(), false);
+    assert!(result.failed_items().any(|failed| failed.url() == &task_url));
+
+    // Nothing has been pushed...
+    {
+        let cal = provider.remote().get_calendar(&cal_url).await.unwrap();
+        let cal = cal.read().unwrap();
+        assert!(cal.get_item_by_url(&task_url).await.is_none());
+        assert_eq!(cal.name(), remote_name);
+    }
+    // ...and nothing has been lost
+    let cal = provider.local().get_calendar(&cal_url).await.unwrap();
+    let cal = cal.read().unwrap();
+    assert!(cal.get_item_by_url(&task_url).await.is_some());
+    assert_eq!(cal.privileges(), Privileges::READ);
+    assert_eq!(cal.is_writable(), false);
+}
+
+#[tokio::test]
+#[cfg(feature = "integration_tests")]
+async fn test_scheduled_events() {
+    use chrono::{TimeZone, Utc};
+    use kitchen_fridge::attendee::{Attendee, Organizer};
+    use kitchen_fridge::event::EventBuilder;
+    use kitchen_fridge::item::ItemMut;
+    use kitchen_fridge::traits::{CompleteCalendar, DavCalendar};
+    use kitchen_fridge::Item;
+
+    let (mut provider, cal_url) = synced_test_provider().await;
+    provider.set_scheduling_address(Some("mailto:john@example.com".to_string()));
+
+    let event = EventBuilder::new("Meeting".to_string(), Utc.ymd(2021, 4, 1).and_hms(9, 0, 0), Utc.ymd(2021, 4, 1).and_hms(10, 0, 0), &cal_url)
+        .with_organizer(Organizer::new("mailto:john@example.com".to_string(), None))
+        .with_attendee(Attendee::new("mailto:jane@example.com".to_string()))
+        .with_attendee(Attendee::new("mailto:bob@example.com".to_string()))
+        .build().unwrap();
+    let url = event.url().clone();
+    {
+        let cal = provider.local().get_calendar(&cal_url).await.unwrap();
+        cal.write().unwrap().add_item(Item::Event(event)).await.unwrap();
+    }
+    // Mocked remote sources cannot send the invitations, which is reported as a failure. The event is pushed anyway
+    assert_eq!(provider.sync().await, false);
+    assert!(provider.last_sync_result().unwrap().error().is_none());
+
+    // Uninviting Bob must be told to the attendees with a higher SEQUENCE
+    {
+        let cal = provider.local().get_calendar(&cal_url).await.unwrap();
+        let mut cal = cal.write().unwrap();
+        if let Some(Item::Event(event)) = cal.get_item_by_url_mut(&url).await.map(ItemMut::into_mut) {
+            let attendees = event.attendees()[..1].to_vec();
+            event.set_attendees(attendees);
+        }
+    }
+    assert_eq!(provider.sync().await, false);
+    let cal = provider.remote().get_calendar(&cal_url).await.unwrap();
+    let pushed = DavCalendar::get_item_by_url(&*cal.read().unwrap(), &url).await.unwrap().unwrap();
+    assert_eq!(pushed.unwrap_event().sequence(), 1);
+    assert_eq!(pushed.unwrap_event().attendees().len(), 1);
+
+    // Events that are organized by someone else are not scheduled
+    provider.set_scheduling_address(Some("mailto:jane@example.com".to_string()));
+    {
+        let cal = provider.local().get_calendar(&cal_url).await.unwrap();
+        let mut cal = cal.write().unwrap();
+        if let Some(Item::Event(event)) = cal.get_item_by_url_mut(&url).await.map(ItemMut::into_mut) {
+            event.set_attendees(Vec::new());
+        }
+    }
+    assert!(provider.sync().await);
+}
+
+#[tokio::test]
+#[cfg(feature = "integration_tests")]
+async fn test_sync_with_sync_tokens() {
+    use kitchen_fridge::traits::{BaseCalendar, CompleteCalendar};
+    use kitchen_fridge::{Item, Task};
+
+    let (mut provider, flavour) = test_provider().await;
+    flavour.mock_behaviour.lock().unwrap().supports_sync_tokens = true;
+    assert!(provider.sync().await);
+    let cal_url = provider.remote().get_calendars().await.unwrap().keys().next().unwrap().clone();
+    let sync_token = |provider: &TestProvider| {
+        let cal = provider.local().get_calendar_sync(&cal_url).unwrap();
+        let token = cal.read().unwrap().sync_token().map(String::from);
+        token
+    };
+    let first_token = sync_token(&provider);
+    assert!(first_token.is_some());
+
+    // Add, rename and delete items on the server, they are synced from what has changed since the last sync token
+    {
+        let cal = provider.remote().get_calendar(&cal_url).await.unwrap();
+        let mut cal = cal.write().unwrap();
+        let task_urls: Vec<url::Url> = cal.iter_items().filter(|(_, item)| item.is_task()).map(|(url, _)| url.clone()).take(2).collect();
+        assert_eq!(task_urls.len(), 2);
+        cal.get_item_by_url_mut(&task_urls[0]).await.unwrap().unwrap_task_mut().mock_remote_calendar_set_name("Renamed on the server".to_string());
+        cal.immediately_delete_item(&task_urls[1]).await.unwrap();
+        cal.add_item(Item::Task(Task::new("Created on the server".to_string(), false, &cal_url))).await.unwrap();
+    }
+    assert!(provider.sync().await);
+    assert!(provider.remote().has_same_observable_content_as(provider.local()).await.unwrap());
+    assert!(sync_token(&provider).is_some());
+    assert_ne!(sync_token(&provider), first_token);
+
+    // Unknown tokens make the next sync list every item, just like a server that has forgotten about them
+    provider.local().get_calendar_sync(&cal_url).unwrap().write().unwrap().set_sync_token(Some("mock-sync-token/123456".to_string()));
+    assert!(provider.sync().await);
+    assert!(provider.remote().has_same_observable_content_as(provider.local()).await.unwrap());
+}